@@ -0,0 +1,70 @@
+// Per-request overhead of the generated route handlers, measured in-process
+// through the router (no sockets) so only framework dispatch is timed.
+//
+// Run with `cargo bench --bench dispatch`.
+
+use axum::body::Body;
+use axum::http::Request;
+use criterion::{criterion_group, criterion_main, Criterion};
+use ferrox::{http_method, Server};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+#[derive(Deserialize)]
+struct NewItem {
+    name: String,
+}
+
+#[http_method(GET, "/static")]
+fn static_route() -> Value {
+    json!({ "ok": true })
+}
+
+#[http_method(GET, "/items/:id")]
+fn item(id: u64) -> Value {
+    json!({ "id": id })
+}
+
+#[http_method(POST, "/items")]
+async fn create_item(body: NewItem) -> Value {
+    json!({ "name": body.name })
+}
+
+fn dispatch(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let router = Server::new().into_router();
+
+    let mut group = c.benchmark_group("dispatch");
+    group.bench_function("static", |b| {
+        b.to_async(&runtime).iter(|| {
+            let request = Request::get("/static").body(Body::empty()).unwrap();
+            router.clone().oneshot(request)
+        })
+    });
+    group.bench_function("path_param", |b| {
+        b.to_async(&runtime).iter(|| {
+            let request = Request::get("/items/42").body(Body::empty()).unwrap();
+            router.clone().oneshot(request)
+        })
+    });
+    group.bench_function("json_body", |b| {
+        b.to_async(&runtime).iter(|| {
+            let request = Request::post("/items")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"name":"widget"}"#))
+                .unwrap();
+            router.clone().oneshot(request)
+        })
+    });
+    group.bench_function("not_found", |b| {
+        b.to_async(&runtime).iter(|| {
+            let request = Request::get("/missing").body(Body::empty()).unwrap();
+            router.clone().oneshot(request)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
@@ -0,0 +1,50 @@
+// Time per request with many requests in flight, through `ferrox::bench`, so
+// contention between concurrent requests (shared registries, locks) shows up
+// as it grows with the concurrency.
+//
+// Run with `cargo bench --bench load`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ferrox::bench::LoadTest;
+use ferrox::{http_method, Server};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+struct NewItem {
+    name: String,
+}
+
+#[http_method(GET, "/items/:id")]
+fn item(id: u64) -> Value {
+    json!({ "id": id })
+}
+
+#[http_method(POST, "/items")]
+async fn create_item(body: NewItem) -> Value {
+    json!({ "name": body.name })
+}
+
+fn load(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    let mix = LoadTest::new(Server::new().quiet())
+        .request("show", 4, |client| client.get("/items/42"))
+        .request("create", 1, |client| client.post("/items").json(&json!({ "name": "widget" })))
+        .request("not_found", 1, |client| client.get("/missing"));
+
+    let mut group = c.benchmark_group("load");
+    group.throughput(Throughput::Elements(1));
+    for concurrency in [1, 8, 64] {
+        let mix = mix.clone().concurrency(concurrency);
+        group.bench_with_input(BenchmarkId::from_parameter(concurrency), &mix, |b, mix| {
+            b.to_async(&runtime).iter_custom(|iters| {
+                let load = mix.clone().requests(iters);
+                async move { load.run().await.elapsed() }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, load);
+criterion_main!(benches);
@@ -1,52 +1,223 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, ItemFn};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, FnArg, ItemFn, Pat, Token};
+
+mod validate;
 
 /// Attribute macro for HTTP methods
 /// Usage: #[http_method(GET, "/users")]
+/// Works on both `fn` and `async fn` handlers
 /// Generates inventory registration directly
+///
+/// Handlers return anything implementing `ferrox::IntoHandlerResponse`, any other
+/// `Serialize` type (sent as `Json<T>` is), or a `Result` of either whose error
+/// converts into `FerroxError`. The declared return type is recorded for the
+/// OpenAPI document's response schema.
+///
+/// Methods may be joined with `|` and paths listed one after the other, as in
+/// `#[http_method(GET | HEAD, "/things", "/v1/things")]`, to register the handler
+/// once per method and path, e.g. keeping an old path during a migration. Every
+/// path needs the same placeholders. The GET-only options `cache`, `coalesce` and
+/// `head` apply to the GET registrations, and a listed HEAD is served by a
+/// registration of its own instead of the GET route.
+///
+/// Options follow the paths as `key = "value"` pairs:
+/// - `timeout = "5s"` bounds the handler call (`ms`, `s`, `m` and `h` units), overriding
+///   `Server::default_timeout`
+/// - `auth = "jwt"` (or `"api_key"`, or any scheme given to `Server::authenticator`) runs
+///   the authenticator registered for that scheme before the handler
+/// - `rate_limit = "100/min"` limits requests per client (`s`, `min`, `hour` and `day`
+///   units), answering 429 once exceeded
+/// - `max_body_size = "10MB"` limits the request body (bytes, or `KB`, `MB` and `GB` of
+///   1024), answering 413 once exceeded; overrides `Server::max_body_size`
+/// - `blocking = true` runs a non-async handler off the async workers, on the
+///   `Server::blocking_threads` pool or else tokio's blocking pool, for CPU-heavy work
+/// - `cache = "30s"` serves a GET route's responses from memory for that long, by
+///   path and query string; see `ferrox::cache`
+/// - `version = "v1"` registers the handler as one version of the route, served
+///   as `Server::versioning` says (under `/v1` by default)
+/// - `head = false` stops a GET route from answering HEAD, which it otherwise does
+///   by running the handler and sending the headers without the body
+/// - `concurrency_limit = 10` serves at most that many requests to the route at once,
+///   answering 503 with `Retry-After` beyond it; see also `Server::max_in_flight`
+/// - `guards = [AdminOnly, Verified]` runs each `ferrox::guard::Guard` in order before
+///   the handler, after any `auth`; the first to reject the request answers it
+/// - `permission = "users:write"` requires the request's principal to hold that
+///   permission under the `Server::rbac` policy, answering 401 or 403 otherwise
+/// - `filter = ["status"]`, `sort = ["created_at"]` and `fields = ["id", "name"]` list the
+///   fields a `ferrox::query::QuerySpec` parameter lets clients filter, sort and select;
+///   under `Server::sparse_fieldsets`, results are narrowed to the selected `fields`
+/// - `verify = "github"` checks the request's signature with the `ferrox::verify::Verifier`
+///   given to `Server::verifier` under that name, on the raw body and before `auth`,
+///   answering 401 when it does not match
+/// - `flag = "new-checkout"` serves the route only while that feature flag is on,
+///   answering 404 like a missing route otherwise; see `ferrox::flags`
+/// - `coalesce = true` runs a GET route's handler once for identical requests arriving
+///   while it runs, by path, query string and the `Accept`, `Accept-Language`,
+///   `Authorization` and `Cookie` headers, and sends each of them its response
+/// - `circuits = ["payments"]` answers 503 without running the handler while any of
+///   those `Server::circuit_breaker`s is open; see `ferrox::resilience`
+/// - `deprecated = true`, `sunset = "2026-06-01"` and `successor = "/v2/users"` send
+///   the `Deprecation`, `Sunset` and `Link: <...>; rel="successor-version"` headers
+///   with the route's responses, and mark the operation in the OpenAPI document
+/// - `tag = "Users"` groups the operation under that tag in the OpenAPI document
+/// - `cors = "any"`, `cors = ["https://app.example.com"]` or `cors = false` gives the
+///   route its own CORS policy in place of `Server::cors`; see `ferrox::cors::RouteCors`
+/// - `cost = 5` charges each request that many units of the client's `Server::quotas`
+///   budget, answering 429 once it is spent; see `ferrox::quota`
+/// - `audit = false` leaves the route's requests out of the `Server::audit` trail
+/// - `if_match = true` answers PUT, PATCH and DELETE requests carrying neither `If-Match`
+///   nor `If-Unmodified-Since` with 428, so the handler's `ferrox::etag::check_precondition`
+///   always has a version to compare
+/// - `registry = "admin"` puts the route in that named registry, served by
+///   `Server::from_registry("admin")` instead of `Server::new()`
+/// - `status = 201` sends the handler's successful results with that 2xx status
+///   instead of 200; a status the handler sets itself, e.g. by returning
+///   `ferrox::NoContent`, is kept
+///
+/// The handler's doc comment is recorded too, and becomes the operation's summary
+/// (its first paragraph) and description in the OpenAPI document. Parameters may
+/// carry `///` comments of their own, which describe them there.
+///
+/// Path placeholders are written `{id}` or `:id`, each a whole segment. A last
+/// `{*rest}` (or `*rest`) segment is a catch-all matching the rest of the path,
+/// slashes included, e.g. `/files/{*path}`. `{id:u64}` or `{slug:[a-z-]+}`
+/// constrains a placeholder to a type or a regex: other values answer 400, and
+/// routes of the same shape with different constraints are picked per request.
+///
+/// Handler parameters are matched by name (a leading `_` is ignored):
+/// - a name matching a path placeholder (`id` for `/users/:id`) receives that parameter
+/// - `path`, `query` and `body` receive all path parameters, the query string and the body
+/// - a parameter of type `QuerySpec`, whatever its name, receives the `filter`, `sort`
+///   and `fields` query parameters allowed by the route's options; see `ferrox::query`
+/// - a parameter of type `Pagination`, whatever its name, receives the `page`, `per_page`
+///   and `cursor` query parameters; see `ferrox::pagination`
+/// - a parameter of type `Tx<DB>`, whatever its name, receives the transaction the
+///   request runs in; see `ferrox::database::TransactionLayer`
+/// - a parameter of type `TenantId`, whatever its name, receives the request's tenant,
+///   answering 400 without one; see `ferrox::tenancy`
+/// - a parameter of type `Dep<T>`, whatever its name, receives the `T` service from the
+///   `Server::services` container, built for the request if it is per-request; see `ferrox::di`
+/// - a parameter of type `Locale`, whatever its name, receives the locale negotiated from
+///   `Accept-Language`; see `ferrox::i18n`
+/// - a parameter of type `Bytes`, whatever its name, receives the body exactly as sent,
+///   which is then not parsed at all (for signature checks and binary uploads)
+/// - in an `async fn`, any other parameter is extracted by its type's
+///   `ferrox::FromFerroxRequest` impl, e.g. `user: CurrentUser`
+///
+/// Each parameter may have any `DeserializeOwned` type; failures answer 400.
+/// Types that implement `ferrox::validate::Validate` are then validated, failures
+/// answering 422 with the errors by field.
+/// Parameters of type `State<S>` receive state registered with `Server::with_state`,
+/// `&RequestContext` (or `RequestContext`) parameters the request's headers, method,
+/// URI and client address, and `Claims` or `ApiKey` parameters the identity checked by an
+/// `auth = "jwt"` or `auth = "api_key"` route, and `Session` or `Cookies` parameters the
+/// request's session and cookies (see `Server::sessions`), whatever their name.
+/// A handler with three parameters that match none of these names keeps the
+/// positional `(path, query, body)` convention.
 #[proc_macro_attribute]
 pub fn http_method(args: TokenStream, input: TokenStream) -> TokenStream {
-    let input_fn = parse_macro_input!(input as ItemFn);
+    let mut input_fn = parse_macro_input!(input as ItemFn);
 
-    // Parse the attribute arguments using simple string processing
-    let args_str = args.to_string();
-    let args_content = args_str.trim_matches(|c| c == '(' || c == ')').trim();
-
-    let (method_part, path_part) = if let Some(comma_pos) = args_content.find(',') {
-        let method_str = args_content[..comma_pos].trim();
-        let path_str = args_content[comma_pos + 1..].trim();
-        (method_str, Some(path_str))
-    } else {
-        (args_content, None)
+    // Collect #[middleware(...)] attributes placed below this one
+    let (middleware, middleware_names, middleware_markers) = match take_middleware(&mut input_fn) {
+        Ok(middleware) => middleware,
+        Err(err) => return err.to_compile_error().into(),
     };
 
-    // Determine method and path
-    let method_str = match method_part {
-        "GET" | "\"GET\"" => "GET",
-        "POST" | "\"POST\"" => "POST",
-        "PUT" | "\"PUT\"" => "PUT",
-        "PATCH" | "\"PATCH\"" => "PATCH",
-        "DELETE" | "\"DELETE\"" => "DELETE",
-        "HEAD" | "\"HEAD\"" => "HEAD",
-        "OPTIONS" | "\"OPTIONS\"" => "OPTIONS",
-        _ => "GET",
-    };
+    let param_docs = take_param_docs(&mut input_fn);
+    let doc = doc_text(&input_fn.attrs);
+
+    let route_args = parse_macro_input!(args as RouteArgs);
+    if let (Some(blocking), Some(asyncness)) = (&route_args.blocking, &input_fn.sig.asyncness) {
+        let mut err = syn::Error::new_spanned(blocking, "`blocking = true` needs a non-async handler");
+        err.combine(syn::Error::new_spanned(
+            asyncness,
+            "remove `async`, or call `tokio::task::spawn_blocking` from the handler",
+        ));
+        return err.to_compile_error().into();
+    }
+    let method_str = route_args.methods[0].as_str();
+    let path_str = route_args.paths[0].1.as_str();
+    let raw_body = input_fn.sig.inputs.iter().any(|arg| matches!(arg, FnArg::Typed(pat_type) if is_bytes_type(&pat_type.ty)));
 
-    let path_str = path_part.unwrap_or("/").trim_matches('"');
+    // Work out how each handler parameter is extracted
+    let extractions = match parameter_extractions(&input_fn, path_str, "#[http_method]", true, input_fn.sig.asyncness.is_some()) {
+        Ok(extractions) => described(extractions, &param_docs),
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let bindings = extractions.iter().map(|(ident, _, _)| ident);
+    let param_infos: Vec<_> = extractions.iter().map(|(_, _, info)| info).collect();
+    let extract_stmts: Vec<_> = extractions
+        .iter()
+        .map(|(ident, source, _)| {
+            quote! {
+                let #ident = match #source {
+                    Ok(value) => value,
+                    Err(err) => return ::ferrox::IntoHandlerResponse::into_handler_response(err),
+                };
+            }
+        })
+        .collect();
 
     // Generate inventory registration code directly
     let fn_name = &input_fn.sig.ident;
-    let register_code = format!(
-        "inventory::submit!(RouteRegistration {{
-            method: \"{}\",
-            path: \"{}\",
-            handler_fn: || std::sync::Arc::new({}),
-        }});",
-        method_str, path_str, fn_name
-    );
+    let fn_name_str = fn_name.to_string();
+    let response_type = return_type_name(&input_fn.sig.output);
+    let call = if input_fn.sig.asyncness.is_some() {
+        quote! { #fn_name(#(#bindings),*).await }
+    } else {
+        quote! { #fn_name(#(#bindings),*) }
+    };
+    let respond = quote! {
+        {
+            use ::ferrox::__private::{ViaHandlerResponse as _, ViaResult as _, ViaSerialize as _};
+            (&&&::ferrox::__private::Respond::new(#call)).respond()
+        }
+    };
+    let handler = if input_fn.sig.asyncness.is_some() {
+        quote! {
+            ::ferrox::RouteHandler::from_async(|__ctx: ::ferrox::RequestContext| async move {
+                #(#extract_stmts)*
+                #respond
+            })
+        }
+    } else {
+        quote! {
+            ::ferrox::RouteHandler::from_sync(|__ctx: ::ferrox::RequestContext| {
+                #(#extract_stmts)*
+                #respond
+            })
+        }
+    };
 
-    let register_stmt: syn::Stmt = syn::parse_str(&register_code).unwrap();
+    // One registration per method and path
+    let mut register_stmts = Vec::new();
+    for (_, path, constraints) in &route_args.paths {
+        for method in &route_args.methods {
+            let mut options = route_args.options(method, constraints);
+            if raw_body {
+                options = quote! { #options.raw_body() };
+            }
+            register_stmts.push(quote! {
+                ::ferrox::inventory::submit!(::ferrox::RouteRegistration {
+                    method: #method,
+                    path: #path,
+                    handler: ::ferrox::RouteKind::Http(|| #handler),
+                    middleware: &[#(#middleware),*],
+                    middleware_names: &[#(#middleware_names),*],
+                    handler_name: #fn_name_str,
+                    doc: #doc,
+                    params: &[#(#param_infos),*],
+                    response: #response_type,
+                    location: ::core::concat!(::core::file!(), ":", ::core::line!()),
+                    options: #options,
+                });
+            });
+        }
+    }
 
     // Create new function with constants added at the beginning
     let method_const = format!("const METHOD: &str = \"{}\";", method_str);
@@ -70,8 +241,1864 @@ pub fn http_method(args: TokenStream, input: TokenStream) -> TokenStream {
         #new_fn
 
         // Automatically register this route via inventory
-        #register_stmt
+        #(#register_stmts)*
+
+        #(#middleware_markers)*
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// Attribute macro for WebSocket routes
+/// Usage: #[websocket("/ws/chat")]
+/// Works on `async fn` handlers that take the socket as a `JsonSocket<T>` or `WebSocket`
+/// parameter; the handler runs once the connection is upgraded
+///
+/// The other parameters follow `#[http_method]` rules (path placeholders, `path`,
+/// `query` and `State<S>`) and are extracted before the upgrade, so a bad request
+/// is rejected with 400 instead of being upgraded.
+#[proc_macro_attribute]
+pub fn websocket(args: TokenStream, input: TokenStream) -> TokenStream {
+    let path = parse_macro_input!(args as syn::LitStr);
+    let mut input_fn = parse_macro_input!(input as ItemFn);
+    let (path_str, constraints) = match route_path(&path) {
+        Ok(path) => path,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let options = constrained_options(quote! { ::ferrox::RouteOptions::DEFAULT }, &constraints);
+
+    let (middleware, middleware_names, middleware_markers) = match take_middleware(&mut input_fn) {
+        Ok(middleware) => middleware,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let mut param_docs = take_param_docs(&mut input_fn);
+    let doc = doc_text(&input_fn.attrs);
+
+    if input_fn.sig.asyncness.is_none() {
+        return syn::Error::new_spanned(input_fn.sig.fn_token, "#[websocket] handlers must be `async fn`")
+            .to_compile_error()
+            .into();
+    }
+
+    // The socket is handed over after the upgrade; everything else is extracted up front
+    let sockets: Vec<_> = input_fn
+        .sig
+        .inputs
+        .iter()
+        .enumerate()
+        .filter(|(_, arg)| matches!(arg, FnArg::Typed(pat_type) if is_socket_type(&pat_type.ty)))
+        .map(|(index, _)| index)
+        .collect();
+    let socket_index = match sockets.as_slice() {
+        [index] => *index,
+        [] => {
+            return syn::Error::new_spanned(
+                &input_fn.sig,
+                "#[websocket] handlers take a `JsonSocket<T>` or `WebSocket` parameter",
+            )
+            .to_compile_error()
+            .into()
+        }
+        [_, second, ..] => {
+            return syn::Error::new_spanned(&input_fn.sig.inputs[*second], "#[websocket] handlers take a single socket")
+                .to_compile_error()
+                .into()
+        }
+    };
+    // The session outlives the upgrade request, so it cannot borrow the context
+    let borrowed_context = input_fn.sig.inputs.iter().find(
+        |arg| matches!(arg, FnArg::Typed(pat_type) if is_context_type(&pat_type.ty) && matches!(*pat_type.ty, syn::Type::Reference(_))),
+    );
+    if let Some(arg) = borrowed_context {
+        return syn::Error::new_spanned(arg, "#[websocket] handlers take `RequestContext` by value")
+            .to_compile_error()
+            .into();
+    }
+
+    let mut request_fn = input_fn.clone();
+    request_fn.sig.inputs = input_fn
+        .sig
+        .inputs
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| *index != socket_index)
+        .map(|(_, arg)| arg.clone())
+        .collect();
+
+    param_docs.remove(socket_index);
+    let extractions = match parameter_extractions(&request_fn, &path_str, "#[websocket]", false, false) {
+        Ok(extractions) => described(extractions, &param_docs),
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let mut bindings: Vec<_> = extractions.iter().map(|(ident, _, _)| ident.clone()).collect();
+    let socket = syn::Ident::new("__socket", proc_macro2::Span::call_site());
+    bindings.insert(socket_index, socket.clone());
+    let param_infos = extractions.iter().map(|(_, _, info)| info);
+    let extract_stmts = rejecting_extractions(&extractions);
+
+    let fn_name = &input_fn.sig.ident;
+    let fn_name_str = fn_name.to_string();
+    let response_type = return_type_name(&input_fn.sig.output);
+    let expanded = quote! {
+        #input_fn
+
+        // Automatically register this route via inventory
+        ::ferrox::inventory::submit!(::ferrox::RouteRegistration {
+            method: "GET",
+            path: #path_str,
+            handler: ::ferrox::RouteKind::WebSocket(|| {
+                ::ferrox::ws::WsHandler::new(|__ctx: ::ferrox::RequestContext| {
+                    #(#extract_stmts)*
+                    Ok(::ferrox::ws::WsSession::new(move |#socket: ::ferrox::ws::WebSocket| async move {
+                        let #socket = ::ferrox::ws::FromSocket::from_socket(#socket);
+                        #fn_name(#(#bindings),*).await
+                    }))
+                })
+            }),
+            middleware: &[#(#middleware),*],
+            middleware_names: &[#(#middleware_names),*],
+            handler_name: #fn_name_str,
+            doc: #doc,
+            params: &[#(#param_infos),*],
+            response: #response_type,
+            location: ::core::concat!(::core::file!(), ":", ::core::line!()),
+            options: #options,
+        });
+
+        #(#middleware_markers)*
     };
 
     TokenStream::from(expanded)
 }
+
+/// Attribute macro for Server-Sent Events routes
+/// Usage: #[sse("/events")]
+/// Works on both `fn` and `async fn` handlers returning `SseStream` or
+/// `Result<SseStream, E>`; an `Err` is answered with its status instead of a stream
+///
+/// Parameters follow `#[http_method]` rules (path placeholders, `path`, `query`,
+/// `State<S>` and `FromFerroxRequest` types) and are extracted before the stream starts.
+#[proc_macro_attribute]
+pub fn sse(args: TokenStream, input: TokenStream) -> TokenStream {
+    let path = parse_macro_input!(args as syn::LitStr);
+    let mut input_fn = parse_macro_input!(input as ItemFn);
+    let (path_str, constraints) = match route_path(&path) {
+        Ok(path) => path,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let options = constrained_options(quote! { ::ferrox::RouteOptions::DEFAULT }, &constraints);
+
+    let (middleware, middleware_names, middleware_markers) = match take_middleware(&mut input_fn) {
+        Ok(middleware) => middleware,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let param_docs = take_param_docs(&mut input_fn);
+    let doc = doc_text(&input_fn.attrs);
+
+    let extractions = match parameter_extractions(&input_fn, &path_str, "#[sse]", false, true) {
+        Ok(extractions) => described(extractions, &param_docs),
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let bindings = extractions.iter().map(|(ident, _, _)| ident);
+    let param_infos = extractions.iter().map(|(_, _, info)| info);
+    let extract_stmts = rejecting_extractions(&extractions);
+
+    let fn_name = &input_fn.sig.ident;
+    let fn_name_str = fn_name.to_string();
+    let response_type = return_type_name(&input_fn.sig.output);
+    let call = if input_fn.sig.asyncness.is_some() {
+        quote! { #fn_name(#(#bindings),*).await }
+    } else {
+        quote! { #fn_name(#(#bindings),*) }
+    };
+    let expanded = quote! {
+        #input_fn
+
+        // Automatically register this route via inventory
+        ::ferrox::inventory::submit!(::ferrox::RouteRegistration {
+            method: "GET",
+            path: #path_str,
+            handler: ::ferrox::RouteKind::Sse(|| {
+                ::ferrox::sse::SseHandler::new(|__ctx: ::ferrox::RequestContext| async move {
+                    #(#extract_stmts)*
+                    ::ferrox::sse::IntoSseStream::into_sse_stream(#call)
+                })
+            }),
+            middleware: &[#(#middleware),*],
+            middleware_names: &[#(#middleware_names),*],
+            handler_name: #fn_name_str,
+            doc: #doc,
+            params: &[#(#param_infos),*],
+            response: #response_type,
+            location: ::core::concat!(::core::file!(), ":", ::core::line!()),
+            options: #options,
+        });
+
+        #(#middleware_markers)*
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// Attribute macro grouping the routes of an inline module
+/// Usage: #[route_group(prefix = "/api/v1", middleware = [auth])]
+/// Prefixes the path of every `#[http_method]`, `#[websocket]` and `#[sse]` handler in
+/// the module (and its inline submodules) and wraps them in the group's middleware,
+/// outside any `#[middleware]` declared on the handler itself
+///
+/// `middleware` takes the same entries as `#[middleware(...)]`; they are resolved
+/// next to each handler, so use paths valid in every module of the group
+/// (e.g. `crate::auth`). A nested
+/// `#[route_group]` extends the outer prefix and runs inside the outer middleware.
+///
+/// `guards = [crate::AdminOnly]` adds guards to every `#[http_method]` route of the
+/// group, run before the route's own (and those of nested groups). Like middleware
+/// they are resolved next to each handler. A guarded group cannot contain
+/// `#[websocket]` or `#[sse]` routes.
+///
+/// `cors = ["https://partner.example"]` (or `"any"`, or `false`) sets the CORS policy
+/// of every `#[http_method]` route of the group; a route's or nested group's own
+/// `cors` replaces it.
+#[proc_macro_attribute]
+pub fn route_group(args: TokenStream, input: TokenStream) -> TokenStream {
+    let group = parse_macro_input!(args as GroupArgs);
+    let mut module = parse_macro_input!(input as syn::ItemMod);
+    let Some((_, items)) = module.content.as_mut() else {
+        return syn::Error::new_spanned(&module, "#[route_group] needs an inline module (`mod name { ... }`)")
+            .to_compile_error()
+            .into();
+    };
+    for item in items.iter_mut() {
+        if let Err(err) = group.apply(item) {
+            return err.to_compile_error().into();
+        }
+    }
+    quote! { #module }.into()
+}
+
+/// Attribute macro for scheduled tasks
+/// Usage: #[scheduled("0 */5 * * * *")] with a cron expression (seconds first, UTC),
+/// or #[scheduled(every = "30s")] for a fixed interval (`ms`, `s`, `m` and `h` units)
+///
+/// Works on `async fn` and blocking `fn` tasks, which run on the blocking thread pool.
+/// Tasks take only `State<S>` parameters and return `()` or `Result<(), E>`; they
+/// run while a server started with `Server::start` is up.
+#[proc_macro_attribute]
+pub fn scheduled(args: TokenStream, input: TokenStream) -> TokenStream {
+    let input_fn = parse_macro_input!(input as ItemFn);
+    let schedule = match parse_schedule(args.into()) {
+        Ok(schedule) => schedule,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let mut bindings = Vec::new();
+    for (index, arg) in input_fn.sig.inputs.iter().enumerate() {
+        match arg {
+            FnArg::Typed(pat_type) if is_state_type(&pat_type.ty) => {
+                bindings.push(syn::Ident::new(&format!("__arg{}", index), proc_macro2::Span::call_site()));
+            }
+            _ => {
+                return syn::Error::new_spanned(arg, "#[scheduled] tasks only take `State<S>` parameters")
+                    .to_compile_error()
+                    .into();
+            }
+        }
+    }
+    let extract_stmts = bindings.iter().map(|binding| {
+        quote! {
+            let #binding = ::ferrox::scheduler::state(&__state)?;
+        }
+    });
+
+    let fn_name = &input_fn.sig.ident;
+    let fn_name_str = fn_name.to_string();
+    let task = if input_fn.sig.asyncness.is_some() {
+        quote! {
+            ::ferrox::scheduler::Task::from_async(|__state: ::ferrox::AppState| async move {
+                #(#extract_stmts)*
+                ::ferrox::scheduler::TaskOutput::into_result(#fn_name(#(#bindings),*).await)
+            })
+        }
+    } else {
+        quote! {
+            ::ferrox::scheduler::Task::from_sync(|__state: ::ferrox::AppState| -> ::core::result::Result<(), ::std::string::String> {
+                #(#extract_stmts)*
+                ::ferrox::scheduler::TaskOutput::into_result(#fn_name(#(#bindings),*))
+            })
+        }
+    };
+
+    quote! {
+        #input_fn
+
+        ::ferrox::inventory::submit!(::ferrox::scheduler::TaskRegistration {
+            name: #fn_name_str,
+            schedule: #schedule,
+            task: || #task,
+            location: ::core::concat!(::core::file!(), ":", ::core::line!()),
+        });
+    }
+    .into()
+}
+
+/// Attribute macro for event subscribers
+/// Usage: #[subscribe] on a function taking the event by value, e.g. `user: UserCreated`
+///
+/// Works on `async fn` and blocking `fn` subscribers, which run on the blocking thread
+/// pool. Besides the event, subscribers take only `State<S>` parameters and return `()`
+/// or `Result<(), E>`; they receive every event of their type published on the
+/// `Server::event_bus`. See `ferrox::events`.
+#[proc_macro_attribute]
+pub fn subscribe(args: TokenStream, input: TokenStream) -> TokenStream {
+    let input_fn = parse_macro_input!(input as ItemFn);
+    if !args.is_empty() {
+        return syn::Error::new(proc_macro2::Span::call_site(), "#[subscribe] takes no arguments")
+            .to_compile_error()
+            .into();
+    }
+
+    let mut event = None;
+    let mut bindings = Vec::new();
+    let mut extract_stmts = Vec::new();
+    for (index, arg) in input_fn.sig.inputs.iter().enumerate() {
+        let binding = syn::Ident::new(&format!("__arg{}", index), proc_macro2::Span::call_site());
+        match arg {
+            FnArg::Typed(pat_type) if is_state_type(&pat_type.ty) => {
+                extract_stmts.push(quote! {
+                    let #binding = ::ferrox::scheduler::state(&__state)?;
+                });
+            }
+            FnArg::Typed(pat_type) if event.is_none() => {
+                event = Some(&pat_type.ty);
+                extract_stmts.push(quote! {
+                    let #binding = __event;
+                });
+            }
+            _ => {
+                return syn::Error::new_spanned(
+                    arg,
+                    "#[subscribe] functions take one event parameter and otherwise only `State<S>` parameters",
+                )
+                .to_compile_error()
+                .into();
+            }
+        }
+        bindings.push(binding);
+    }
+    let Some(event) = event else {
+        return syn::Error::new_spanned(&input_fn.sig, "#[subscribe] functions take the event as a parameter")
+            .to_compile_error()
+            .into();
+    };
+
+    let fn_name = &input_fn.sig.ident;
+    let fn_name_str = fn_name.to_string();
+    let subscriber = if input_fn.sig.asyncness.is_some() {
+        quote! {
+            ::ferrox::events::Subscriber::from_async(|__state: ::ferrox::AppState, __event: #event| async move {
+                #(#extract_stmts)*
+                ::ferrox::scheduler::TaskOutput::into_result(#fn_name(#(#bindings),*).await)
+            })
+        }
+    } else {
+        quote! {
+            ::ferrox::events::Subscriber::from_sync(|__state: ::ferrox::AppState, __event: #event| -> ::core::result::Result<(), ::std::string::String> {
+                #(#extract_stmts)*
+                ::ferrox::scheduler::TaskOutput::into_result(#fn_name(#(#bindings),*))
+            })
+        }
+    };
+
+    quote! {
+        #input_fn
+
+        ::ferrox::inventory::submit!(::ferrox::events::SubscriberRegistration {
+            name: #fn_name_str,
+            subscriber: || #subscriber,
+            location: ::core::concat!(::core::file!(), ":", ::core::line!()),
+        });
+    }
+    .into()
+}
+
+/// Derive macro for `ferrox::validate::Validate`
+/// Usage: #[derive(Validate)] on a struct, with rules such as
+/// #[validate(length(min = 3), email)] on its fields
+///
+/// Rules: `length(min = .., max = ..)`, `range(min = .., max = ..)`, `email`,
+/// `custom = "path::to_fn"` (a `fn(&T) -> Result<(), String>`) and `nested`.
+/// `Option` fields are checked when present.
+#[proc_macro_derive(Validate, attributes(validate))]
+pub fn derive_validate(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    match validate::derive(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+// `"<cron expression>"` or `every = "<duration>"` as a `ScheduleSpec`
+fn parse_schedule(args: proc_macro2::TokenStream) -> syn::Result<proc_macro2::TokenStream> {
+    let expected = "expected #[scheduled(\"<cron expression>\")] or #[scheduled(every = \"<duration>\")]";
+    if let Ok(expression) = syn::parse2::<syn::LitStr>(args.clone()) {
+        // The full syntax is checked when the server starts; catch the common five-field mistake here
+        let fields = expression.value().split_whitespace().count();
+        if !(6..=7).contains(&fields) {
+            return Err(syn::Error::new_spanned(
+                &expression,
+                "expected a cron expression with 6 or 7 fields: sec min hour day-of-month month day-of-week [year]",
+            ));
+        }
+        return Ok(quote! { ::ferrox::scheduler::ScheduleSpec::Cron(#expression) });
+    }
+    let option = syn::parse2::<syn::MetaNameValue>(args).map_err(|err| syn::Error::new(err.span(), expected))?;
+    if !option.path.is_ident("every") {
+        return Err(syn::Error::new_spanned(&option.path, expected));
+    }
+    let syn::Expr::Lit(syn::ExprLit {
+        lit: syn::Lit::Str(period),
+        ..
+    }) = &option.value
+    else {
+        return Err(syn::Error::new_spanned(&option.value, expected));
+    };
+    let ms = parse_duration_ms(period)?;
+    Ok(quote! { ::ferrox::scheduler::ScheduleSpec::Every(::std::time::Duration::from_millis(#ms)) })
+}
+
+/// Wrap a `#[http_method]`, `#[websocket]` or `#[sse]` route in middleware
+/// Usage: #[middleware(auth)] for an axum `from_fn` function, or
+/// #[middleware(layer = TimeoutLayer::new(...))] for any tower layer
+/// Must be placed below the route attribute; the first attribute is the outermost layer
+#[proc_macro_attribute]
+pub fn middleware(args: TokenStream, input: TokenStream) -> TokenStream {
+    // Marker emitted by the route attributes so the attribute's import counts as used
+    if args.to_string() == "__ferrox_used" {
+        return TokenStream::new();
+    }
+    let input = proc_macro2::TokenStream::from(input);
+    let err = syn::Error::new(
+        proc_macro2::Span::call_site(),
+        "#[middleware] must be placed below #[http_method], #[websocket] or #[sse] on a route handler",
+    )
+    .to_compile_error();
+    quote! { #err #input }.into()
+}
+
+// A path as written, as registered, and the constraints of its `{name:constraint}`
+// segments as (name, constraint)
+type RoutePath = (syn::LitStr, String, Vec<(String, String)>);
+
+// Methods a route can be registered for
+const HTTP_METHODS: [&str; 7] = ["GET", "POST", "PUT", "PATCH", "DELETE", "HEAD", "OPTIONS"];
+
+// Arguments of #[http_method]: methods, paths, then `key = "value"` options.
+// Methods are separated by `|`, may be quoted, and default to GET; an unknown one
+// is an error. Paths follow one another, and default to "/".
+struct RouteArgs {
+    // The methods and paths as written, the first of each being the handler's own
+    methods: Vec<String>,
+    paths: Vec<RoutePath>,
+    timeout_ms: Option<u64>,
+    auth: Option<syn::LitStr>,
+    // Requests per window in seconds
+    rate_limit: Option<(u32, u64)>,
+    max_body_size: Option<u64>,
+    // Kept for its span, and only set when true
+    blocking: Option<syn::LitBool>,
+    version: Option<syn::LitStr>,
+    cache_ms: Option<u64>,
+    // Kept for its span, and only set when false
+    no_head: Option<syn::LitBool>,
+    concurrency_limit: Option<usize>,
+    // `guards = [...]` entries, in order; a repeated option adds to them
+    guards: Vec<syn::Expr>,
+    permission: Option<syn::LitStr>,
+    verify: Option<syn::LitStr>,
+    flag: Option<syn::LitStr>,
+    // Kept for its span, and only set when true
+    deprecated: Option<syn::LitBool>,
+    coalesce: Option<syn::LitBool>,
+    sunset: Option<syn::LitStr>,
+    successor: Option<syn::LitStr>,
+    tag: Option<syn::LitStr>,
+    // A `::ferrox::cors::RouteCors` expression; a repeated option replaces it
+    cors: Option<proc_macro2::TokenStream>,
+    cost: Option<u32>,
+    registry: Option<syn::LitStr>,
+    status: Option<u16>,
+    // Kept for its span, and only set when false
+    no_audit: Option<syn::LitBool>,
+    // Kept for its span, and only set when true
+    if_match: Option<syn::LitBool>,
+    // `filter`, `sort` and `fields` lists for `QuerySpec` parameters
+    circuits: Vec<syn::LitStr>,
+    query_filter: Vec<syn::LitStr>,
+    query_sort: Vec<syn::LitStr>,
+    query_fields: Vec<syn::LitStr>,
+}
+
+impl RouteArgs {
+    // `RouteOptions` literal for the registration of `method` at a path with `constraints`;
+    // the GET-only options apply to the GET registration alone
+    fn options(&self, method: &str, constraints: &[(String, String)]) -> proc_macro2::TokenStream {
+        let mut options = quote! { ::ferrox::RouteOptions::DEFAULT };
+        if let Some(ms) = self.timeout_ms {
+            options = quote! { #options.timeout(::std::time::Duration::from_millis(#ms)) };
+        }
+        if let Some(scheme) = &self.auth {
+            options = quote! { #options.auth(#scheme) };
+        }
+        if let Some((limit, secs)) = self.rate_limit {
+            options = quote! {
+                #options.rate_limit(::ferrox::ratelimit::RateLimit::new(#limit, ::std::time::Duration::from_secs(#secs)))
+            };
+        }
+        if let Some(bytes) = self.max_body_size {
+            let bytes = proc_macro2::Literal::u64_unsuffixed(bytes);
+            options = quote! { #options.max_body_size(#bytes) };
+        }
+        if self.blocking.is_some() {
+            options = quote! { #options.blocking() };
+        }
+        if let Some(version) = &self.version {
+            options = quote! { #options.version(#version) };
+        }
+        if let Some(ms) = self.cache_ms.filter(|_| method == "GET") {
+            options = quote! { #options.cache(::std::time::Duration::from_millis(#ms)) };
+        }
+        // A HEAD registration of its own replaces the one GET routes get
+        if method == "GET" && (self.no_head.is_some() || self.methods.iter().any(|method| method == "HEAD")) {
+            options = quote! { #options.without_head() };
+        }
+        if let Some(limit) = self.concurrency_limit {
+            options = quote! { #options.concurrency_limit(#limit) };
+        }
+        if let Some(permission) = &self.permission {
+            options = quote! { #options.permission(#permission) };
+        }
+        if let Some(verifier) = &self.verify {
+            options = quote! { #options.verify(#verifier) };
+        }
+        if let Some(flag) = &self.flag {
+            options = quote! { #options.flag(#flag) };
+        }
+        if self.deprecated.is_some() {
+            options = quote! { #options.deprecated() };
+        }
+        if self.coalesce.is_some() && method == "GET" {
+            options = quote! { #options.coalesce() };
+        }
+        if let Some(date) = &self.sunset {
+            options = quote! { #options.sunset(#date) };
+        }
+        if let Some(path) = &self.successor {
+            options = quote! { #options.successor(#path) };
+        }
+        if let Some(tag) = &self.tag {
+            options = quote! { #options.tag(#tag) };
+        }
+        if let Some(cors) = &self.cors {
+            options = quote! { #options.cors(#cors) };
+        }
+        if let Some(cost) = self.cost {
+            options = quote! { #options.cost(#cost) };
+        }
+        if let Some(registry) = &self.registry {
+            options = quote! { #options.registry(#registry) };
+        }
+        if let Some(status) = self.status {
+            options = quote! { #options.status(#status) };
+        }
+        if self.no_audit.is_some() {
+            options = quote! { #options.without_audit() };
+        }
+        if self.if_match.is_some() && matches!(method, "PUT" | "PATCH" | "DELETE") {
+            options = quote! { #options.if_match() };
+        }
+        if !self.query_filter.is_empty() || !self.query_sort.is_empty() || !self.query_fields.is_empty() {
+            let (filter, sort, fields) = (&self.query_filter, &self.query_sort, &self.query_fields);
+            options = quote! {
+                #options.query(::ferrox::query::Allowlist {
+                    filter: &[#(#filter),*],
+                    sort: &[#(#sort),*],
+                    fields: &[#(#fields),*],
+                })
+            };
+        }
+        if !self.circuits.is_empty() {
+            let circuits = &self.circuits;
+            options = quote! { #options.circuits(&[#(#circuits),*]) };
+        }
+        if !self.guards.is_empty() {
+            let guards = &self.guards;
+            options = quote! {
+                #options.guards(&[#((|| ::ferrox::guard::BoxedGuard::new(#guards)) as ::ferrox::guard::GuardFn),*])
+            };
+        }
+        constrained_options(options, constraints)
+    }
+
+    fn serves_get(&self) -> bool {
+        self.methods.iter().any(|method| method == "GET")
+    }
+}
+
+impl Parse for RouteArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = RouteArgs {
+            methods: Vec::new(),
+            paths: Vec::new(),
+            timeout_ms: None,
+            auth: None,
+            rate_limit: None,
+            max_body_size: None,
+            blocking: None,
+            version: None,
+            cache_ms: None,
+            no_head: None,
+            concurrency_limit: None,
+            guards: Vec::new(),
+            permission: None,
+            verify: None,
+            flag: None,
+            deprecated: None,
+            coalesce: None,
+            sunset: None,
+            successor: None,
+            tag: None,
+            cors: None,
+            cost: None,
+            registry: None,
+            status: None,
+            no_audit: None,
+            if_match: None,
+            circuits: Vec::new(),
+            query_filter: Vec::new(),
+            query_sort: Vec::new(),
+            query_fields: Vec::new(),
+        };
+        if input.is_empty() {
+            args.finish();
+            return Ok(args);
+        }
+
+        loop {
+            let (method, span) = if input.peek(syn::LitStr) {
+                let literal = input.parse::<syn::LitStr>()?;
+                (literal.value(), literal.span())
+            } else {
+                let ident = input.parse::<syn::Ident>()?;
+                (ident.to_string(), ident.span())
+            };
+            if !HTTP_METHODS.contains(&method.as_str()) {
+                let message = match HTTP_METHODS.iter().find(|known| known.eq_ignore_ascii_case(&method)) {
+                    Some(known) => format!("unknown HTTP method `{}`; methods are upper case, like `{}`", method, known),
+                    None => format!("unknown HTTP method `{}`; expected one of {}", method, HTTP_METHODS.join(", ")),
+                };
+                return Err(syn::Error::new(span, message));
+            }
+            if args.methods.contains(&method) {
+                return Err(syn::Error::new(span, format!("{} is listed twice", method)));
+            }
+            args.methods.push(method);
+            if !input.peek(Token![|]) {
+                break;
+            }
+            input.parse::<Token![|]>()?;
+        }
+
+        // Paths come first, one after the other
+        let mut paths = true;
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            if paths && input.peek(syn::LitStr) {
+                let literal = input.parse::<syn::LitStr>()?;
+                let (path, constraints) = route_path(&literal)?;
+                if args.paths.iter().any(|(_, existing, _)| *existing == path) {
+                    return Err(syn::Error::new_spanned(literal, "this path is listed twice"));
+                }
+                args.paths.push((literal, path, constraints));
+                continue;
+            }
+            paths = false;
+
+            let key: syn::Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            if key == "blocking" {
+                let value: syn::LitBool = input.parse()?;
+                args.blocking = value.value.then_some(value);
+                continue;
+            }
+            if key == "deprecated" {
+                let value: syn::LitBool = input.parse()?;
+                args.deprecated = value.value.then_some(value);
+                continue;
+            }
+            if key == "coalesce" {
+                if !args.serves_get() {
+                    return Err(syn::Error::new_spanned(key, "`coalesce` only applies to GET routes"));
+                }
+                let value: syn::LitBool = input.parse()?;
+                args.coalesce = value.value.then_some(value);
+                continue;
+            }
+            if key == "head" {
+                if !args.serves_get() {
+                    return Err(syn::Error::new_spanned(key, "`head` only applies to GET routes"));
+                }
+                let value: syn::LitBool = input.parse()?;
+                args.no_head = (!value.value).then_some(value);
+                continue;
+            }
+            if key == "concurrency_limit" {
+                let value: syn::LitInt = input.parse()?;
+                let limit = value.base10_parse::<usize>()?;
+                if limit == 0 {
+                    return Err(syn::Error::new_spanned(value, "`concurrency_limit` must be at least 1"));
+                }
+                args.concurrency_limit = Some(limit);
+                continue;
+            }
+            if key == "audit" {
+                let value: syn::LitBool = input.parse()?;
+                args.no_audit = (!value.value).then_some(value);
+                continue;
+            }
+            if key == "if_match" {
+                if !args.methods.iter().any(|method| matches!(method.as_str(), "PUT" | "PATCH" | "DELETE")) {
+                    return Err(syn::Error::new_spanned(key, "`if_match` only applies to PUT, PATCH and DELETE routes"));
+                }
+                let value: syn::LitBool = input.parse()?;
+                args.if_match = value.value.then_some(value);
+                continue;
+            }
+            if key == "cost" {
+                let value: syn::LitInt = input.parse()?;
+                let cost = value.base10_parse::<u32>()?;
+                if cost == 0 {
+                    return Err(syn::Error::new_spanned(value, "`cost` must be at least 1"));
+                }
+                args.cost = Some(cost);
+                continue;
+            }
+            if key == "status" {
+                let value: syn::LitInt = input.parse()?;
+                let status = value.base10_parse::<u16>()?;
+                if !(200..300).contains(&status) {
+                    return Err(syn::Error::new_spanned(value, "`status` must be a 2xx status such as 201"));
+                }
+                args.status = Some(status);
+                continue;
+            }
+            if key == "guards" {
+                let content;
+                syn::bracketed!(content in input);
+                args.guards.extend(Punctuated::<syn::Expr, Token![,]>::parse_terminated(&content)?);
+                continue;
+            }
+            if key == "cors" {
+                args.cors = Some(parse_cors(input)?);
+                continue;
+            }
+            if key == "circuits" {
+                let content;
+                syn::bracketed!(content in input);
+                let names = Punctuated::<syn::LitStr, Token![,]>::parse_terminated(&content)?;
+                if let Some(name) = names.iter().find(|name| name.value().is_empty()) {
+                    return Err(syn::Error::new_spanned(name, "expected a circuit breaker name such as \"payments\""));
+                }
+                args.circuits.extend(names);
+                continue;
+            }
+            if key == "filter" || key == "sort" || key == "fields" {
+                let content;
+                syn::bracketed!(content in input);
+                let names = Punctuated::<syn::LitStr, Token![,]>::parse_terminated(&content)?;
+                if let Some(name) = names.iter().find(|name| !is_field_name(&name.value())) {
+                    return Err(syn::Error::new_spanned(name, "expected a field name such as \"created_at\""));
+                }
+                let list = match key.to_string().as_str() {
+                    "filter" => &mut args.query_filter,
+                    "sort" => &mut args.query_sort,
+                    _ => &mut args.query_fields,
+                };
+                list.extend(names);
+                continue;
+            }
+            let value: syn::LitStr = input.parse()?;
+            match key.to_string().as_str() {
+                "timeout" => args.timeout_ms = Some(parse_duration_ms(&value)?),
+                "auth" => args.auth = Some(value),
+                "rate_limit" => args.rate_limit = Some(parse_rate_limit(&value)?),
+                "max_body_size" => args.max_body_size = Some(parse_size(&value)?),
+                "cache" => {
+                    if !args.serves_get() {
+                        return Err(syn::Error::new_spanned(key, "`cache` only applies to GET routes"));
+                    }
+                    args.cache_ms = Some(parse_duration_ms(&value)?);
+                }
+                "permission" => {
+                    let permission = value.value();
+                    if permission.is_empty() || permission.chars().any(char::is_whitespace) {
+                        return Err(syn::Error::new_spanned(value, "expected a permission such as \"users:write\""));
+                    }
+                    args.permission = Some(value);
+                }
+                "verify" => {
+                    if value.value().is_empty() {
+                        return Err(syn::Error::new_spanned(value, "expected a verifier name such as \"github\""));
+                    }
+                    args.verify = Some(value);
+                }
+                "flag" => {
+                    if value.value().is_empty() {
+                        return Err(syn::Error::new_spanned(value, "expected a flag name such as \"new-checkout\""));
+                    }
+                    args.flag = Some(value);
+                }
+                "sunset" => {
+                    if !is_date(&value.value()) {
+                        return Err(syn::Error::new_spanned(value, "expected a date such as \"2026-06-01\""));
+                    }
+                    args.sunset = Some(value);
+                }
+                "successor" => {
+                    if value.value().is_empty() {
+                        return Err(syn::Error::new_spanned(value, "expected a successor such as \"/v2/users\""));
+                    }
+                    args.successor = Some(value);
+                }
+                "registry" => {
+                    if value.value().is_empty() {
+                        return Err(syn::Error::new_spanned(value, "expected a registry name such as \"admin\""));
+                    }
+                    args.registry = Some(value);
+                }
+                "tag" => {
+                    if value.value().trim().is_empty() {
+                        return Err(syn::Error::new_spanned(value, "expected a tag such as \"Users\""));
+                    }
+                    args.tag = Some(value);
+                }
+                "version" => {
+                    let version = value.value();
+                    if version.is_empty() || !version.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_')) {
+                        return Err(syn::Error::new_spanned(
+                            value,
+                            "expected a version such as \"v1\" (letters, digits, `.`, `-` and `_`)",
+                        ));
+                    }
+                    args.version = Some(value);
+                }
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        key,
+                        "unknown #[http_method] option; expected `timeout`, `auth`, `rate_limit`, `max_body_size`, `blocking`, `version`, `cache`, `head`, `concurrency_limit`, `guards`, `permission`, `verify`, `flag`, `deprecated`, `sunset`, `successor`, `coalesce`, `circuits`, `tag`, `cors`, `cost`, `registry`, `status`, `audit`, `if_match`, `filter`, `sort` or `fields`",
+                    ))
+                }
+            }
+        }
+        if let (Some(_), Some(auth)) = (args.cache_ms, &args.auth) {
+            return Err(syn::Error::new_spanned(
+                auth,
+                "`cache` cannot be combined with `auth`: cached responses are shared between clients",
+            ));
+        }
+        if let Some(head) = args.no_head.as_ref().filter(|_| args.methods.iter().any(|method| method == "HEAD")) {
+            return Err(syn::Error::new_spanned(head, "`head = false` cannot be combined with HEAD in the methods"));
+        }
+        // Every path must offer the handler the same parameters
+        if let Some((_, first, _)) = args.paths.first() {
+            let mut expected = path_placeholders(first);
+            expected.sort_unstable();
+            for (literal, path, _) in &args.paths[1..] {
+                let mut placeholders = path_placeholders(path);
+                placeholders.sort_unstable();
+                if placeholders != expected {
+                    return Err(syn::Error::new_spanned(
+                        literal,
+                        format!("every path of a handler needs the same placeholders as the first, `{}`", first),
+                    ));
+                }
+            }
+        }
+        args.finish();
+        Ok(args)
+    }
+}
+
+impl RouteArgs {
+    // Defaults for what was left out
+    fn finish(&mut self) {
+        if self.methods.is_empty() {
+            self.methods.push("GET".to_string());
+        }
+        if self.paths.is_empty() {
+            let root = syn::LitStr::new("/", proc_macro2::Span::call_site());
+            self.paths.push((root, "/".to_string(), Vec::new()));
+        }
+    }
+}
+
+// "250ms", "5s", "2m" or "1h" as milliseconds
+fn parse_duration_ms(value: &syn::LitStr) -> syn::Result<u64> {
+    let text = value.value();
+    let digits = text.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let scale = match &text[digits.len()..] {
+        "ms" => 1,
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        _ => return Err(syn::Error::new_spanned(value, "expected a duration like \"500ms\", \"5s\", \"2m\" or \"1h\"")),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|amount| amount.checked_mul(scale))
+        .ok_or_else(|| syn::Error::new_spanned(value, "expected a duration like \"500ms\", \"5s\", \"2m\" or \"1h\""))
+}
+
+// `cors = "any"`, `cors = false` or `cors = ["https://a.example", ...]` as a `RouteCors`
+fn parse_cors(input: ParseStream) -> syn::Result<proc_macro2::TokenStream> {
+    if input.peek(syn::token::Bracket) {
+        let content;
+        let bracket = syn::bracketed!(content in input);
+        let origins = Punctuated::<syn::LitStr, Token![,]>::parse_terminated(&content)?;
+        if origins.is_empty() {
+            return Err(syn::Error::new(bracket.span.join(), "expected at least one origin"));
+        }
+        if let Some(origin) = origins.iter().find(|origin| !is_origin(&origin.value())) {
+            return Err(syn::Error::new_spanned(origin, "expected an origin such as \"https://app.example.com\""));
+        }
+        let origins = origins.iter();
+        return Ok(quote! { ::ferrox::cors::RouteCors::Origins(&[#(#origins),*]) });
+    }
+    if input.peek(syn::LitBool) {
+        let value: syn::LitBool = input.parse()?;
+        if value.value {
+            return Err(syn::Error::new_spanned(
+                value,
+                "routes follow `Server::cors` by default; expected `\"any\"`, `false` or a list of origins",
+            ));
+        }
+        return Ok(quote! { ::ferrox::cors::RouteCors::Disabled });
+    }
+    let value: syn::LitStr = input.parse()?;
+    if value.value() != "any" {
+        return Err(syn::Error::new_spanned(value, "expected `\"any\"`, `false` or a list of origins"));
+    }
+    Ok(quote! { ::ferrox::cors::RouteCors::Any })
+}
+
+// `scheme://host[:port]`, as sent in the `Origin` header
+fn is_origin(origin: &str) -> bool {
+    let origin = origin.trim_end_matches('/');
+    let host = origin
+        .strip_prefix("https://")
+        .or_else(|| origin.strip_prefix("http://"))
+        .unwrap_or("");
+    !host.is_empty() && !host.contains(['/', '?', '#', '*']) && !host.chars().any(char::is_whitespace)
+}
+
+// "100/min" as (100, 60); same units as `ferrox::ratelimit::RateLimit::from_str`
+fn parse_rate_limit(value: &syn::LitStr) -> syn::Result<(u32, u64)> {
+    let invalid = || syn::Error::new_spanned(value, "expected a rate limit like \"100/min\" or \"10/s\"");
+    let text = value.value();
+    let (count, unit) = text.split_once('/').ok_or_else(invalid)?;
+    let limit = count.trim().parse::<u32>().ok().filter(|limit| *limit > 0).ok_or_else(invalid)?;
+    let secs = match unit.trim() {
+        "s" | "sec" | "second" => 1,
+        "m" | "min" | "minute" => 60,
+        "h" | "hour" => 3600,
+        "d" | "day" => 86400,
+        _ => return Err(invalid()),
+    };
+    Ok((limit, secs))
+}
+
+// "512", "64KB", "10MB" or "1GB" as bytes, in multiples of 1024
+fn parse_size(value: &syn::LitStr) -> syn::Result<u64> {
+    let invalid = || syn::Error::new_spanned(value, "expected a size like \"512\", \"64KB\", \"10MB\" or \"1GB\"");
+    let text = value.value();
+    let digits = text.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let scale: u64 = match text[digits.len()..].to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "KB" | "KIB" => 1 << 10,
+        "MB" | "MIB" => 1 << 20,
+        "GB" | "GIB" => 1 << 30,
+        _ => return Err(invalid()),
+    };
+    digits
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|amount| amount.checked_mul(scale))
+        .ok_or_else(invalid)
+}
+
+// Arguments of #[route_group]: `prefix = "..."`, `middleware = [...]`, `guards = [...]`
+// and `cors = ...`, all optional
+struct GroupArgs {
+    prefix: String,
+    // Entries of the `middleware` list, kept as written for a `#[middleware(...)]` attribute
+    middleware: Option<proc_macro2::TokenStream>,
+    // Entries of the `guards` list, kept as written for the routes' `guards` option
+    guards: Option<proc_macro2::TokenStream>,
+    // The `cors` value, kept as written for the routes' `cors` option
+    cors: Option<proc_macro2::TokenStream>,
+}
+
+impl Parse for GroupArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut group = GroupArgs {
+            prefix: String::new(),
+            middleware: None,
+            guards: None,
+            cors: None,
+        };
+        while !input.is_empty() {
+            let key: syn::Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "prefix" => {
+                    let prefix: syn::LitStr = input.parse()?;
+                    let value = prefix.value();
+                    if !value.starts_with('/') {
+                        return Err(syn::Error::new_spanned(prefix, "route group prefix must start with `/`"));
+                    }
+                    group.prefix = value.trim_end_matches('/').to_string();
+                }
+                "middleware" => {
+                    let content;
+                    syn::bracketed!(content in input);
+                    let entries: proc_macro2::TokenStream = content.parse()?;
+                    // Validate now so errors point at the group rather than each handler
+                    syn::parse::Parser::parse2(Punctuated::<MiddlewareArg, Token![,]>::parse_terminated, entries.clone())?;
+                    group.middleware = Some(entries);
+                }
+                "guards" => {
+                    let content;
+                    syn::bracketed!(content in input);
+                    let entries: proc_macro2::TokenStream = content.parse()?;
+                    syn::parse::Parser::parse2(Punctuated::<syn::Expr, Token![,]>::parse_terminated, entries.clone())?;
+                    group.guards = Some(entries);
+                }
+                "cors" => {
+                    // Validated as the routes would, then handed to them as written
+                    let fork = input.fork();
+                    parse_cors(&fork)?;
+                    let mut value = proc_macro2::TokenStream::new();
+                    while !input.is_empty() && !input.peek(Token![,]) {
+                        value.extend([input.parse::<proc_macro2::TokenTree>()?]);
+                    }
+                    group.cors = Some(value);
+                }
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        key,
+                        "unknown #[route_group] option; expected `prefix`, `middleware`, `guards` or `cors`",
+                    ))
+                }
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(group)
+    }
+}
+
+impl GroupArgs {
+    fn apply(&self, item: &mut syn::Item) -> syn::Result<()> {
+        match item {
+            syn::Item::Fn(item_fn) => {
+                let Some(position) = item_fn.attrs.iter().position(|attr| route_attribute(attr).is_some()) else {
+                    return Ok(());
+                };
+                let is_http = route_attribute(&item_fn.attrs[position]) == Some("http_method");
+                self.prefix_route(&mut item_fn.attrs[position], is_http)?;
+                if let Some(guards) = &self.guards {
+                    if !is_http {
+                        return Err(syn::Error::new_spanned(
+                            &item_fn.attrs[position],
+                            "guards of a #[route_group] only apply to #[http_method] routes; move this route out of the group",
+                        ));
+                    }
+                    guard_route(&mut item_fn.attrs[position], guards);
+                }
+                // WebSockets are not subject to CORS, and `#[sse]` routes take no options
+                if let (Some(cors), true) = (&self.cors, is_http) {
+                    add_route_option(&mut item_fn.attrs[position], quote! { cors = #cors });
+                }
+                // Right below the route attribute, so it is the outermost #[middleware]
+                if let Some(entries) = &self.middleware {
+                    item_fn
+                        .attrs
+                        .insert(position + 1, syn::parse_quote! { #[::ferrox::middleware(#entries)] });
+                }
+                Ok(())
+            }
+            syn::Item::Mod(module) => {
+                if let Some(position) = module.attrs.iter().position(|attr| last_segment_is(attr, "route_group")) {
+                    // The nested group expands later; hand it our prefix and middleware instead
+                    let nested: GroupArgs = module.attrs[position].parse_args()?;
+                    let prefix = format!("{}{}", self.prefix, nested.prefix);
+                    let middleware = combined(&self.middleware, &nested.middleware);
+                    let guards = combined(&self.guards, &nested.guards);
+                    // The nested group's own policy replaces ours
+                    let cors = nested.cors.or_else(|| self.cors.clone());
+                    let path = module.attrs[position].path().clone();
+                    let middleware = middleware.map(|middleware| quote! { , middleware = [#middleware] });
+                    let guards = guards.map(|guards| quote! { , guards = [#guards] });
+                    let cors = cors.map(|cors| quote! { , cors = #cors });
+                    module.attrs[position] = syn::parse_quote! { #[#path(prefix = #prefix #middleware #guards #cors)] };
+                    return Ok(());
+                }
+                if let Some((_, items)) = module.content.as_mut() {
+                    for item in items {
+                        self.apply(item)?;
+                    }
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Rewrite the path literal of a route attribute to include the prefix
+    fn prefix_route(&self, attr: &mut syn::Attribute, is_http: bool) -> syn::Result<()> {
+        let syn::Meta::List(list) = &mut attr.meta else {
+            return Err(syn::Error::new_spanned(&*attr, "expected route arguments"));
+        };
+        let mut tokens: Vec<proc_macro2::TokenTree> = list.tokens.clone().into_iter().collect();
+        let positions = path_positions(&tokens);
+        match positions.last() {
+            Some(_) => {
+                for &index in &positions {
+                    let literal = syn::parse_str::<syn::LitStr>(&tokens[index].to_string())?;
+                    let joined = self.join(&literal.value());
+                    tokens[index] = proc_macro2::TokenTree::Literal(proc_macro2::Literal::string(&joined));
+                }
+                list.tokens = tokens.into_iter().collect();
+            }
+            // `#[http_method(GET)]` serves "/", which becomes the prefix itself
+            None if is_http => {
+                let joined = self.join("/");
+                let method = &list.tokens;
+                list.tokens = if method.is_empty() { quote! { GET, #joined } } else { quote! { #method, #joined } };
+            }
+            None => return Err(syn::Error::new_spanned(&*attr, "expected a route path starting with `/`")),
+        }
+        Ok(())
+    }
+
+    fn join(&self, path: &str) -> String {
+        match (self.prefix.as_str(), path) {
+            ("", path) => path.to_string(),
+            (prefix, "/") => prefix.to_string(),
+            (prefix, path) => format!("{}{}", prefix, path),
+        }
+    }
+}
+
+// Outer then inner entries of a group list
+fn combined(
+    outer: &Option<proc_macro2::TokenStream>,
+    inner: &Option<proc_macro2::TokenStream>,
+) -> Option<proc_macro2::TokenStream> {
+    match (outer, inner) {
+        (Some(outer), Some(inner)) => Some(quote! { #outer, #inner }),
+        (outer, inner) => outer.clone().or_else(|| inner.clone()),
+    }
+}
+
+// Add a group's guards to an `#[http_method]` attribute, so they come before the route's own
+fn guard_route(attr: &mut syn::Attribute, guards: &proc_macro2::TokenStream) {
+    add_route_option(attr, quote! { guards = [#guards] });
+}
+
+// Add an option to an `#[http_method]` attribute right after the paths, ahead of
+// the route's own options, which a repeated one then extends or replaces
+fn add_route_option(attr: &mut syn::Attribute, option: proc_macro2::TokenStream) {
+    let syn::Meta::List(list) = &mut attr.meta else {
+        return;
+    };
+    let mut tokens: Vec<proc_macro2::TokenTree> = list.tokens.clone().into_iter().collect();
+    if let Some(&index) = path_positions(&tokens).last() {
+        let option: Vec<proc_macro2::TokenTree> = quote! { , #option }.into_iter().collect();
+        tokens.splice(index + 1..index + 1, option);
+        list.tokens = tokens.into_iter().collect();
+    }
+}
+
+// Indexes of the path literals among a route attribute's arguments, which all
+// come before the first option
+fn path_positions(tokens: &[proc_macro2::TokenTree]) -> Vec<usize> {
+    let options = tokens
+        .iter()
+        .position(|token| matches!(token, proc_macro2::TokenTree::Punct(punct) if punct.as_char() == '='))
+        .unwrap_or(tokens.len());
+    (0..options)
+        .filter(|index| {
+            matches!(&tokens[*index], proc_macro2::TokenTree::Literal(literal)
+                if syn::parse_str::<syn::LitStr>(&literal.to_string()).is_ok_and(|lit| lit.value().starts_with('/')))
+        })
+        .collect()
+}
+
+// Which route attribute `attr` is, if any
+fn route_attribute(attr: &syn::Attribute) -> Option<&'static str> {
+    ["http_method", "websocket", "sse"]
+        .into_iter()
+        .find(|name| last_segment_is(attr, name))
+}
+
+fn last_segment_is(attr: &syn::Attribute, name: &str) -> bool {
+    attr.path().segments.last().is_some_and(|segment| segment.ident == name)
+}
+
+enum MiddlewareArg {
+    Layer(syn::Expr),
+    FromFn(syn::Path),
+}
+
+impl Parse for MiddlewareArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(syn::Ident) && input.peek2(Token![=]) {
+            let key: syn::Ident = input.parse()?;
+            if key != "layer" {
+                return Err(syn::Error::new_spanned(key, "expected `layer = <expr>` or a middleware function path"));
+            }
+            input.parse::<Token![=]>()?;
+            Ok(MiddlewareArg::Layer(input.parse()?))
+        } else {
+            Ok(MiddlewareArg::FromFn(input.parse()?))
+        }
+    }
+}
+
+// Remove #[middleware] attributes from the handler and turn each into a route wrapper,
+// with a name for route listings. Also returns one marker item per attribute path so
+// its import is not reported unused.
+fn take_middleware(
+    input_fn: &mut ItemFn,
+) -> syn::Result<(Vec<proc_macro2::TokenStream>, Vec<String>, Vec<proc_macro2::TokenStream>)> {
+    let mut wrappers = Vec::new();
+    let mut names = Vec::new();
+    let mut markers = Vec::new();
+    let mut kept = Vec::new();
+    for attr in input_fn.attrs.drain(..) {
+        let is_middleware = attr.path().segments.last().is_some_and(|segment| segment.ident == "middleware");
+        if !is_middleware {
+            kept.push(attr);
+            continue;
+        }
+        let args = attr.parse_args_with(Punctuated::<MiddlewareArg, Token![,]>::parse_terminated)?;
+        let attr_path = attr.path();
+        markers.push(quote! { #[#attr_path(__ferrox_used)] const _: () = (); });
+        for arg in args {
+            names.push(match &arg {
+                MiddlewareArg::FromFn(func) => path_name(func),
+                MiddlewareArg::Layer(layer) => layer_name(layer),
+            });
+            wrappers.push(match arg {
+                MiddlewareArg::FromFn(func) => quote! {
+                    |route| route.layer(::ferrox::axum::middleware::from_fn(#func))
+                },
+                MiddlewareArg::Layer(layer) => quote! {
+                    |route| route.layer(#layer)
+                },
+            });
+        }
+    }
+    input_fn.attrs = kept;
+    Ok((wrappers, names, markers))
+}
+
+// `auth` or `crate::middleware::auth`, as written
+fn path_name(path: &syn::Path) -> String {
+    let segments: Vec<String> = path.segments.iter().map(|segment| segment.ident.to_string()).collect();
+    segments.join("::")
+}
+
+// The layer type a `layer = ...` expression builds: `TimeoutLayer` for
+// `TimeoutLayer::new(..)` or `TimeoutLayer::new(..).with(..)`
+fn layer_name(expr: &syn::Expr) -> String {
+    match expr {
+        syn::Expr::Path(path) => path_name(&path.path),
+        syn::Expr::Call(call) => match &*call.func {
+            syn::Expr::Path(func) if func.path.segments.len() > 1 => {
+                let mut path = func.path.clone();
+                path.segments.pop();
+                path.segments.pop_punct();
+                path_name(&path)
+            }
+            func => layer_name(func),
+        },
+        syn::Expr::MethodCall(call) => layer_name(&call.receiver),
+        syn::Expr::Paren(inner) => layer_name(&inner.expr),
+        _ => "layer".to_string(),
+    }
+}
+
+// Types a `{name:type}` path segment can be constrained to
+const CONSTRAINT_TYPES: [&str; 15] = [
+    "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize", "f32", "f64", "bool",
+];
+
+// The route path of `literal`, with `{param}` and `{*rest}` segments written as
+// `:param` and `*rest`, and the constraints of `{param:constraint}` segments.
+// Paths the router would reject or misread are compile errors naming the segment:
+// no leading `/`, empty segments, unbalanced braces, characters URLs cannot hold,
+// `:` or `*` inside a static segment, repeated placeholder names, and a catch-all
+// before the last segment
+fn route_path(literal: &syn::LitStr) -> syn::Result<(String, Vec<(String, String)>)> {
+    let path = literal.value();
+    if !path.starts_with('/') {
+        return Err(syn::Error::new_spanned(
+            literal,
+            format!("route path must start with `/`, like `/{}`", path),
+        ));
+    }
+    let segments: Vec<&str> = path.split('/').collect();
+    let mut normalized = Vec::with_capacity(segments.len());
+    let mut constraints = Vec::new();
+    let mut names: Vec<String> = Vec::new();
+    let mut offset = 0;
+    for (index, written) in segments.iter().enumerate() {
+        let at = offset;
+        offset += written.len() + 1;
+        let error = |message: String| segment_error(literal, at, written, message);
+        // Only the root and a trailing slash leave a segment empty
+        if written.is_empty() && index != 0 && index + 1 != segments.len() {
+            return Err(error("empty path segment; remove the repeated `/`".to_string()));
+        }
+        if written.matches('{').count() != written.matches('}').count() {
+            return Err(error(format!("unbalanced braces in path segment `{}`", written)));
+        }
+        let segment = match written.strip_prefix('{').and_then(|rest| rest.strip_suffix('}')) {
+            Some(inner) => {
+                let (name, constraint) = match inner.split_once(':') {
+                    Some((name, constraint)) => (name, Some(constraint)),
+                    None => (inner, None),
+                };
+                if let Some(constraint) = constraint {
+                    check_constraint(literal, at, written, constraint)?;
+                    constraints.push((name.trim_start_matches('*').to_string(), constraint.to_string()));
+                }
+                match name.strip_prefix('*') {
+                    Some(name) => format!("*{}", name),
+                    None => format!(":{}", name),
+                }
+            }
+            None if written.contains(['{', '}']) => {
+                return Err(error(format!("path parameter `{}` must be a whole segment, like `{{id}}`", written)));
+            }
+            None => {
+                let rest = written.strip_prefix([':', '*']).unwrap_or(written);
+                if let Some(c) = rest.chars().find(|c| !is_path_char(*c)) {
+                    return Err(error(match c {
+                        ':' | '*' => format!(
+                            "`{}` inside path segment `{}` would start a placeholder; placeholders must be whole segments",
+                            c, written
+                        ),
+                        '?' | '#' => format!("`{}` in path segment `{}`; the query string and fragment are not part of a route", c, written),
+                        _ => format!("invalid character `{}` in path segment `{}`; percent-encode it", c.escape_default(), written),
+                    }));
+                }
+                written.to_string()
+            }
+        };
+        if let Some(name) = segment.strip_prefix(':').or_else(|| segment.strip_prefix('*')) {
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(error(format!("path parameter `{}` needs a name of letters, digits and `_`", written)));
+            }
+            if names.iter().any(|existing| existing == name) {
+                return Err(error(format!("path parameter `{}` appears more than once", name)));
+            }
+            names.push(name.to_string());
+            if segment.starts_with('*') && index + 1 != segments.len() {
+                return Err(error(format!("catch-all `{}` must be the last path segment", written)));
+            }
+        }
+        normalized.push(segment);
+    }
+    Ok((normalized.join("/"), constraints))
+}
+
+// Characters a path segment may hold as written: unreserved and sub-delimiter
+// characters, `:`, `@` and `%` escapes
+fn is_path_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "-._~!$&'()*+,;=:@%".contains(c)
+}
+
+// An error at `segment`, found `offset` bytes into the path of `literal`; the
+// compiler points inside the string where it can, or else at all of it
+fn segment_error(literal: &syn::LitStr, offset: usize, segment: &str, message: String) -> syn::Error {
+    let token = literal.token();
+    // Offsets in the source only match the value for strings without escapes
+    let plain = token.to_string() == format!("\"{}\"", literal.value());
+    let span = plain
+        .then(|| token.subspan(offset + 1..offset + 1 + segment.len().max(1)))
+        .flatten()
+        .unwrap_or_else(|| literal.span());
+    syn::Error::new(span, message)
+}
+
+// A constraint is one of `CONSTRAINT_TYPES` or a regex the whole value must match
+fn check_constraint(literal: &syn::LitStr, offset: usize, segment: &str, constraint: &str) -> syn::Result<()> {
+    if CONSTRAINT_TYPES.contains(&constraint) {
+        return Ok(());
+    }
+    match regex_syntax::Parser::new().parse(constraint) {
+        Ok(_) if !constraint.is_empty() => Ok(()),
+        Ok(_) => Err(segment_error(literal, offset, segment, format!("empty constraint in `{}`", segment))),
+        Err(err) => Err(segment_error(
+            literal,
+            offset,
+            segment,
+            format!(
+                "constraint in `{}` is neither a type such as `u64` nor a valid regex: {}",
+                segment, err
+            ),
+        )),
+    }
+}
+
+// `options` with the route's path constraints, if it has any
+fn constrained_options(options: proc_macro2::TokenStream, constraints: &[(String, String)]) -> proc_macro2::TokenStream {
+    if constraints.is_empty() {
+        return options;
+    }
+    let pairs = constraints.iter().map(|(name, constraint)| quote! { (#name, #constraint) });
+    quote! { #options.constraints(&[#(#pairs),*]) }
+}
+
+// Names of the `:param` and `*param` placeholders in a route path
+fn path_placeholders(path: &str) -> Vec<&str> {
+    path.split('/')
+        .filter_map(|segment| segment.strip_prefix(':').or_else(|| segment.strip_prefix('*')))
+        .collect()
+}
+
+// `State<S>` parameters are injected by type rather than by name
+fn is_state_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "State"),
+        _ => false,
+    }
+}
+
+// Extraction statements for handlers that answer a failed extraction with `Err(response)`
+fn rejecting_extractions(
+    extractions: &[(syn::Ident, proc_macro2::TokenStream, proc_macro2::TokenStream)],
+) -> Vec<proc_macro2::TokenStream> {
+    extractions
+        .iter()
+        .map(|(ident, source, _)| {
+            quote! {
+                let #ident = match #source {
+                    Ok(value) => value,
+                    Err(err) => return Err(::ferrox::IntoHandlerResponse::into_handler_response(err)),
+                };
+            }
+        })
+        .collect()
+}
+
+// `RequestContext` and `&RequestContext` parameters receive the whole request context
+fn is_context_type(ty: &syn::Type) -> bool {
+    let ty = match ty {
+        syn::Type::Reference(reference) => reference.elem.as_ref(),
+        ty => ty,
+    };
+    match ty {
+        syn::Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "RequestContext"),
+        _ => false,
+    }
+}
+
+// Identities recorded by an authenticator: `Claims` for `auth = "jwt"`, `ApiKey` for `auth = "api_key"`
+fn is_identity_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Claims" || segment.ident == "ApiKey"),
+        _ => false,
+    }
+}
+
+// `Session` and `Cookies` parameters receive the request's session and cookie jar
+fn session_kind(ty: &syn::Type) -> Option<&'static str> {
+    match ty {
+        syn::Type::Path(type_path) => match type_path.path.segments.last()?.ident.to_string().as_str() {
+            "Session" => Some("session"),
+            "Cookies" => Some("cookies"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Letters, digits, `_`, `.` and `-`, not starting with `-`, which marks a descending sort
+fn is_field_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('-')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+// `YYYY-MM-DD`, with a month and day in range
+fn is_date(date: &str) -> bool {
+    let parts: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return false;
+    };
+    let number = |part: &str, digits: usize| {
+        (part.len() == digits && part.bytes().all(|b| b.is_ascii_digit())).then(|| part.parse::<u32>().ok()).flatten()
+    };
+    matches!(
+        (number(year, 4), number(month, 2), number(day, 2)),
+        (Some(_), Some(1..=12), Some(1..=31))
+    )
+}
+
+// `QuerySpec` parameters read the filter, sort and fieldset query parameters
+fn is_query_spec_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "QuerySpec"),
+        _ => false,
+    }
+}
+
+// `Pagination` parameters read the pagination query parameters
+fn is_pagination_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Pagination"),
+        _ => false,
+    }
+}
+
+// `Locale` parameters receive the request's negotiated locale
+fn is_locale_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Locale"),
+        _ => false,
+    }
+}
+
+// `Dep<T>` parameters receive a service from the server's container
+fn is_dep_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Dep"),
+        _ => false,
+    }
+}
+
+// `TenantId` parameters receive the request's tenant
+fn is_tenant_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "TenantId"),
+        _ => false,
+    }
+}
+
+// `Tx` parameters receive the request's database transaction
+fn is_transaction_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Tx"),
+        _ => false,
+    }
+}
+
+// `Bytes` parameters receive the raw request body
+fn is_bytes_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Bytes"),
+        _ => false,
+    }
+}
+
+// Sockets are handed to `#[websocket]` handlers after the upgrade
+fn is_socket_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "JsonSocket" || segment.ident == "WebSocket"),
+        _ => false,
+    }
+}
+
+// The declared return type without spaces, for `RouteRegistration::response`
+fn return_type_name(output: &syn::ReturnType) -> String {
+    match output {
+        syn::ReturnType::Default => "()".to_string(),
+        syn::ReturnType::Type(_, ty) => quote!(#ty).to_string().replace(' ', ""),
+    }
+}
+
+// `ParamInfo` literal describing one handler parameter
+fn param_info(name: &str, source: &str, ty: &syn::Type) -> proc_macro2::TokenStream {
+    let source = syn::Ident::new(source, proc_macro2::Span::call_site());
+    let type_name = quote!(#ty).to_string().replace(' ', "");
+    quote! { ::ferrox::ParamInfo::new(#name, ::ferrox::ParamSource::#source, #type_name) }
+}
+
+// The text of the `///` comments among `attrs`, without the space after `///`
+fn doc_text(attrs: &[syn::Attribute]) -> String {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value: syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(line), ..
+                }),
+                ..
+            }) => Some(line.value()),
+            _ => None,
+        })
+        .collect();
+    let lines: Vec<&str> = lines.iter().map(|line| line.strip_prefix(' ').unwrap_or(line).trim_end()).collect();
+    lines.join("\n").trim().to_string()
+}
+
+// Remove the doc comments of the handler's parameters, which Rust does not allow
+// there, returning each parameter's text
+fn take_param_docs(input_fn: &mut ItemFn) -> Vec<String> {
+    input_fn
+        .sig
+        .inputs
+        .iter_mut()
+        .map(|arg| match arg {
+            FnArg::Typed(pat_type) => {
+                let text = doc_text(&pat_type.attrs);
+                pat_type.attrs.retain(|attr| !attr.path().is_ident("doc"));
+                text
+            }
+            FnArg::Receiver(_) => String::new(),
+        })
+        .collect()
+}
+
+// `extractions` with each parameter's doc comment added to its `ParamInfo`
+fn described(
+    mut extractions: Vec<(syn::Ident, proc_macro2::TokenStream, proc_macro2::TokenStream)>,
+    docs: &[String],
+) -> Vec<(syn::Ident, proc_macro2::TokenStream, proc_macro2::TokenStream)> {
+    for ((_, _, info), doc) in extractions.iter_mut().zip(docs) {
+        if !doc.is_empty() {
+            *info = quote! { #info.description(#doc) };
+        }
+    }
+    extractions
+}
+
+// For each handler parameter, a binding name, the expression that extracts it,
+// and its `ParamInfo`. `attribute` names the macro in errors; WebSocket upgrades have no body.
+// `awaits` says whether the extractions run in an async block, as `FromFerroxRequest` needs.
+fn parameter_extractions(
+    input_fn: &ItemFn,
+    path: &str,
+    attribute: &str,
+    has_body: bool,
+    awaits: bool,
+) -> syn::Result<Vec<(syn::Ident, proc_macro2::TokenStream, proc_macro2::TokenStream)>> {
+    let placeholders = path_placeholders(path);
+    let mut params = Vec::new();
+    for (index, arg) in input_fn.sig.inputs.iter().enumerate() {
+        let FnArg::Typed(pat_type) = arg else {
+            return Err(syn::Error::new_spanned(arg, format!("{} handlers cannot take `self`", attribute)));
+        };
+        let Pat::Ident(pat_ident) = pat_type.pat.as_ref() else {
+            return Err(syn::Error::new_spanned(
+                &pat_type.pat,
+                format!("{} handler parameters must be plain identifiers", attribute),
+            ));
+        };
+        let binding = syn::Ident::new(&format!("__arg{}", index), pat_ident.ident.span());
+        let name = pat_ident.ident.to_string().trim_start_matches('_').to_string();
+        params.push((binding, name, pat_type.ty.as_ref(), pat_ident));
+    }
+
+    let is_known = |name: &str| matches!(name, "path" | "query" | "body") || placeholders.contains(&name);
+    let named: Vec<_> = params
+        .iter()
+        .filter(|(_, _, ty, _)| {
+            !is_state_type(ty)
+                && !is_context_type(ty)
+                && !is_identity_type(ty)
+                && session_kind(ty).is_none()
+                && !is_bytes_type(ty)
+                && !is_pagination_type(ty)
+                && !is_query_spec_type(ty)
+                && !is_transaction_type(ty)
+                && !is_tenant_type(ty)
+                && !is_dep_type(ty)
+                && !is_locale_type(ty)
+        })
+        .collect();
+    let positional = has_body && named.len() == 3 && named.iter().all(|(_, name, _, _)| !is_known(name));
+
+    let mut position = 0;
+    let mut extractions = Vec::new();
+    for (binding, name, ty, pat_ident) in &params {
+        if is_context_type(ty) {
+            let context = if matches!(ty, syn::Type::Reference(_)) {
+                quote! { &__ctx }
+            } else {
+                quote! { __ctx.clone() }
+            };
+            let info = param_info("context", "Context", ty);
+            extractions.push((binding.clone(), quote! { Ok::<_, ::ferrox::FerroxError>(#context) }, info));
+            continue;
+        }
+        if is_identity_type(ty) {
+            let info = param_info("identity", "Identity", ty);
+            extractions.push((binding.clone(), quote! { ::ferrox::auth::identity::<#ty>(&__ctx) }, info));
+            continue;
+        }
+        if let Some(kind) = session_kind(ty) {
+            let extract = syn::Ident::new(kind, proc_macro2::Span::call_site());
+            let info = param_info(kind, "Session", ty);
+            extractions.push((binding.clone(), quote! { ::ferrox::session::#extract(&__ctx) }, info));
+            continue;
+        }
+        if is_state_type(ty) {
+            let info = param_info("state", "State", ty);
+            extractions.push((binding.clone(), quote! { ::ferrox::extract::state(&__ctx) }, info));
+            continue;
+        }
+        if is_query_spec_type(ty) {
+            let info = param_info("query_spec", "QuerySpec", ty);
+            extractions.push((binding.clone(), quote! { ::ferrox::query::query_spec(&__ctx) }, info));
+            continue;
+        }
+        if is_pagination_type(ty) {
+            let info = param_info("pagination", "Pagination", ty);
+            extractions.push((binding.clone(), quote! { ::ferrox::pagination::pagination(&__ctx) }, info));
+            continue;
+        }
+        if is_transaction_type(ty) {
+            let info = param_info("transaction", "Transaction", ty);
+            extractions.push((binding.clone(), quote! { ::ferrox::database::transaction(&__ctx) }, info));
+            continue;
+        }
+        if is_locale_type(ty) {
+            let info = param_info("locale", "Locale", ty);
+            extractions.push((binding.clone(), quote! { ::ferrox::i18n::locale(&__ctx) }, info));
+            continue;
+        }
+        if is_tenant_type(ty) {
+            let info = param_info("tenant", "Tenant", ty);
+            extractions.push((binding.clone(), quote! { ::ferrox::tenancy::tenant_id(&__ctx) }, info));
+            continue;
+        }
+        if is_dep_type(ty) {
+            let info = param_info(name, "Service", ty);
+            extractions.push((binding.clone(), quote! { ::ferrox::di::resolve(&__ctx) }, info));
+            continue;
+        }
+        if is_bytes_type(ty) {
+            if !has_body {
+                return Err(syn::Error::new_spanned(
+                    ty,
+                    format!("{} handlers have no request body to take as `Bytes`", attribute),
+                ));
+            }
+            let info = param_info("body", "RawBody", ty);
+            let source = quote! { Ok::<_, ::ferrox::FerroxError>(__ctx.raw_body().clone()) };
+            extractions.push((binding.clone(), source, info));
+            continue;
+        }
+
+        let name = if positional { ["path", "query", "body"][position] } else { name.as_str() };
+        position += 1;
+
+        // Placeholders win over the whole-source names
+        let (source, info) = if !positional && placeholders.contains(&name) {
+            (
+                quote! { ::ferrox::extract::path_param::<#ty>(&__ctx.path, #name) },
+                param_info(name, "PathParam", ty),
+            )
+        } else {
+            match name {
+                "path" => (quote! { ::ferrox::extract::path::<#ty>(&__ctx.path) }, param_info(name, "Path", ty)),
+                "query" => (quote! { ::ferrox::extract::query::<#ty>(&__ctx.query) }, param_info(name, "Query", ty)),
+                "body" if has_body => (quote! { ::ferrox::extract::request_body::<#ty>(&__ctx) }, param_info(name, "Body", ty)),
+                // Anything else is the application's own extractor
+                _ if awaits => {
+                    let source = quote! { <#ty as ::ferrox::FromFerroxRequest>::from_request(&__ctx).await };
+                    extractions.push((binding.clone(), source, param_info(name, "Extractor", ty)));
+                    continue;
+                }
+                _ => {
+                    let sources = if has_body { "`path`, `query`, `body`" } else { "`path`, `query`" };
+                    let hint = if has_body { "; `FromFerroxRequest` extractors need an `async fn` handler" } else { "" };
+                    return Err(syn::Error::new_spanned(
+                        pat_ident,
+                        format!(
+                            "parameter `{}` does not match a path placeholder in \"{}\" or one of {}{}",
+                            pat_ident.ident, path, sources, hint
+                        ),
+                    ));
+                }
+            }
+        };
+        extractions.push((binding.clone(), validated(source), info));
+    }
+    Ok(extractions)
+}
+
+// Check a deserialized parameter with its `Validate` impl, if its type has one
+fn validated(source: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    quote! {
+        #source.and_then(|value| {
+            use ::ferrox::validate::__private::{ViaNothing as _, ViaValidate as _};
+            (&::ferrox::validate::__private::Check(&value)).check().map(|()| value)
+        })
+    }
+}
@@ -0,0 +1,212 @@
+// `#[derive(Validate)]`: each `#[validate(...)]` rule on a field becomes a call to
+// a helper in `ferrox::validate::__private`, recording its message under the
+// field's serialized name when it fails.
+
+use quote::quote;
+use syn::spanned::Spanned;
+
+pub(crate) fn derive(input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let syn::Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(&input.ident, "#[derive(Validate)] only supports structs"));
+    };
+    let syn::Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "#[derive(Validate)] needs a struct with named fields",
+        ));
+    };
+    let rename_all = serde_option(&input.attrs, "rename_all");
+
+    let mut checks = Vec::new();
+    for field in &fields.named {
+        let ident = field.ident.as_ref().expect("named fields have names");
+        let key = match serde_option(&field.attrs, "rename") {
+            Some(rename) => rename,
+            None => {
+                let name = ident.to_string().trim_start_matches("r#").to_string();
+                match &rename_all {
+                    Some(rule) => rename_field(&name, rule),
+                    None => name,
+                }
+            }
+        };
+        let mut rules = Vec::new();
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("validate")) {
+            attr.parse_nested_meta(|meta| {
+                rules.push(rule(&meta, &key)?);
+                Ok(())
+            })?;
+        }
+        if rules.is_empty() {
+            continue;
+        }
+        let check = if is_option(&field.ty) {
+            quote! {
+                if let ::core::option::Option::Some(__value) = __value {
+                    #(#rules)*
+                }
+            }
+        } else {
+            quote! { #(#rules)* }
+        };
+        checks.push(quote! {
+            {
+                let __value = &self.#ident;
+                #check
+            }
+        });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::ferrox::validate::Validate for #name #type_generics #where_clause {
+            fn validate(&self) -> ::core::result::Result<(), ::ferrox::validate::ValidationErrors> {
+                let mut __errors = ::ferrox::validate::ValidationErrors::new();
+                #(#checks)*
+                __errors.into_result()
+            }
+        }
+    })
+}
+
+// One rule of a `#[validate(...)]` attribute, checking `__value`
+fn rule(meta: &syn::meta::ParseNestedMeta, key: &str) -> syn::Result<proc_macro2::TokenStream> {
+    let helpers = quote! { ::ferrox::validate::__private };
+    let record = quote! {
+        __errors.add(#key, __message);
+    };
+    if meta.path.is_ident("length") || meta.path.is_ident("range") {
+        let (mut min, mut max) = (None, None);
+        meta.parse_nested_meta(|bound| {
+            let slot = if bound.path.is_ident("min") {
+                &mut min
+            } else if bound.path.is_ident("max") {
+                &mut max
+            } else {
+                return Err(bound.error("expected `min` or `max`"));
+            };
+            let value: syn::Expr = bound.value()?.parse()?;
+            *slot = Some(value);
+            Ok(())
+        })?;
+        if min.is_none() && max.is_none() {
+            return Err(meta.error("expected `min = ...`, `max = ...` or both"));
+        }
+        let bound = |value: Option<syn::Expr>| match value {
+            Some(value) => quote! { ::core::option::Option::Some(#value) },
+            None => quote! { ::core::option::Option::None },
+        };
+        let (min, max) = (bound(min), bound(max));
+        let helper = if meta.path.is_ident("length") {
+            quote! { #helpers::length }
+        } else {
+            quote! { #helpers::range }
+        };
+        return Ok(quote! {
+            if let ::core::result::Result::Err(__message) = #helper(__value, #min, #max) {
+                #record
+            }
+        });
+    }
+    if meta.path.is_ident("email") {
+        return Ok(quote! {
+            if let ::core::result::Result::Err(__message) = #helpers::email(__value) {
+                #record
+            }
+        });
+    }
+    if meta.path.is_ident("custom") {
+        let function: syn::LitStr = meta.value()?.parse()?;
+        let function: syn::Path = function.parse()?;
+        return Ok(quote! {
+            if let ::core::result::Result::Err(__message) = #function(__value) {
+                #record
+            }
+        });
+    }
+    if meta.path.is_ident("nested") {
+        return Ok(quote! {
+            if let ::core::result::Result::Err(__nested) = ::ferrox::validate::Validate::validate(__value) {
+                __errors.nest(#key, __nested);
+            }
+        });
+    }
+    Err(syn::Error::new(
+        meta.path.span(),
+        "unknown validation rule; expected `length`, `range`, `email`, `custom` or `nested`",
+    ))
+}
+
+// The string value of `#[serde(<name> = "...")]`, ignoring the other serde options
+fn serde_option(attrs: &[syn::Attribute], name: &str) -> Option<String> {
+    let mut found = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        // serde reports malformed attributes itself
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(name) && meta.input.peek(syn::Token![=]) {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                found = Some(value.value());
+            } else if meta.path.is_ident(name) {
+                // `rename(deserialize = "...")`: requests are deserialized
+                meta.parse_nested_meta(|direction| {
+                    let value: syn::LitStr = direction.value()?.parse()?;
+                    if direction.path.is_ident("deserialize") {
+                        found = Some(value.value());
+                    }
+                    Ok(())
+                })?;
+            } else if meta.input.peek(syn::Token![=]) {
+                meta.value()?.parse::<syn::Expr>()?;
+            } else if meta.input.peek(syn::token::Paren) {
+                let _content;
+                syn::parenthesized!(_content in meta.input);
+            }
+            Ok(())
+        });
+    }
+    found
+}
+
+// A snake_case field name under a serde `rename_all` rule
+fn rename_field(name: &str, rule: &str) -> String {
+    let capitalized = || {
+        name.split('_')
+            .map(|word| {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                    None => String::new(),
+                }
+            })
+            .collect::<String>()
+    };
+    match rule {
+        "lowercase" => name.to_lowercase(),
+        "UPPERCASE" | "SCREAMING_SNAKE_CASE" => name.to_uppercase(),
+        "PascalCase" => capitalized(),
+        "camelCase" => {
+            let pascal = capitalized();
+            let mut chars = pascal.chars();
+            match chars.next() {
+                Some(first) => first.to_lowercase().chain(chars).collect(),
+                None => pascal,
+            }
+        }
+        "kebab-case" => name.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => name.replace('_', "-").to_uppercase(),
+        _ => name.to_string(),
+    }
+}
+
+fn is_option(ty: &syn::Type) -> bool {
+    let syn::Type::Path(path) = ty else {
+        return false;
+    };
+    path.qself.is_none()
+        && path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option" && !segment.arguments.is_empty())
+}
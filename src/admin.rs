@@ -0,0 +1,237 @@
+//! Operational endpoints: version, uptime, configuration, routes and log level.
+//!
+//! ```ignore
+//! Server::new().admin(
+//!     Admin::new()
+//!         .version(env!("CARGO_PKG_VERSION"))
+//!         .bind("127.0.0.1:9090") // its own port, kept off the public listener
+//!         .auth("api_key"),
+//! );
+//! ```
+//!
+//! Under the admin path (`/admin` by default):
+//!
+//! - `GET /info`: version, start time, uptime, open connections and in-flight requests
+//! - `GET /config`: the server's settings as they were when it started
+//! - `GET /routes`: the registered and dynamic routes
+//! - `GET /log-level` and `PUT /log-level` with `{"level": "debug"}`: the level
+//!   filter of the log subscriber, changed without a restart
+//! - `GET /body-log` and `PUT /body-log` with `{"enabled": true, "routes": ["POST /orders"], "for_secs": 600}`:
+//!   whether `Server::body_logging` logs bodies, and of which requests
+//!
+//! The endpoints are served on the main listeners outside every middleware,
+//! like the health probes, or only on the address given to `bind`. Either way
+//! they expose the service's internals, so keep them on a private address or
+//! behind an authenticator with `auth`.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::routing::{get, MethodRouter};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::auth::Authenticator;
+use crate::body_log::{BodyLog, BodyLogChange};
+use crate::context::AppState;
+use crate::dynamic::DynamicRoutes;
+use crate::error::FerroxError;
+use crate::routes::RouteInfo;
+
+/// Where and how the admin endpoints are served.
+#[derive(Debug, Clone)]
+pub struct Admin {
+    pub(crate) path: String,
+    pub(crate) addr: Option<String>,
+    version: Option<String>,
+    scheme: Option<&'static str>,
+}
+
+impl Default for Admin {
+    fn default() -> Self {
+        Admin {
+            path: "/admin".to_string(),
+            addr: None,
+            version: None,
+            scheme: None,
+        }
+    }
+}
+
+impl Admin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve the endpoints under `path` instead of `/admin`.
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = path.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Serve the endpoints only on their own listener at `addr`, e.g. `127.0.0.1:9090`.
+    pub fn bind(mut self, addr: &str) -> Self {
+        self.addr = Some(addr.to_string());
+        self
+    }
+
+    /// The build version reported by `/info`, usually `env!("CARGO_PKG_VERSION")`.
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = Some(version.to_string());
+        self
+    }
+
+    /// Require the server's authenticator for `scheme` on every admin endpoint.
+    pub fn auth(mut self, scheme: &'static str) -> Self {
+        self.scheme = Some(scheme);
+        self
+    }
+
+    pub(crate) fn scheme(&self) -> Option<&'static str> {
+        self.scheme
+    }
+}
+
+// What the endpoints report, shared with the layer counting requests
+pub(crate) struct Runtime {
+    version: Option<String>,
+    started: Instant,
+    started_at: String,
+    in_flight: AtomicUsize,
+    config: Value,
+    routes: Vec<RouteInfo>,
+    dynamic: DynamicRoutes,
+    body_log: Option<BodyLog>,
+}
+
+impl Runtime {
+    pub(crate) fn new(
+        admin: &Admin,
+        config: Value,
+        routes: Vec<RouteInfo>,
+        dynamic: DynamicRoutes,
+        body_log: Option<BodyLog>,
+    ) -> Arc<Self> {
+        Arc::new(Runtime {
+            version: admin.version.clone(),
+            started: Instant::now(),
+            started_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            in_flight: AtomicUsize::new(0),
+            config,
+            routes,
+            dynamic,
+            body_log,
+        })
+    }
+}
+
+// Count the requests the rest of `router` is serving
+pub(crate) fn count_requests(router: Router<AppState>, runtime: Arc<Runtime>) -> Router<AppState> {
+    router.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let runtime = runtime.clone();
+        async move {
+            runtime.in_flight.fetch_add(1, Ordering::Relaxed);
+            let response = next.run(request).await;
+            runtime.in_flight.fetch_sub(1, Ordering::Relaxed);
+            response
+        }
+    }))
+}
+
+#[derive(Deserialize)]
+struct LevelChange {
+    level: String,
+}
+
+// Add the endpoints to `router`
+pub(crate) fn mount(
+    router: Router<AppState>,
+    admin: &Admin,
+    runtime: Arc<Runtime>,
+    authenticator: Option<Arc<dyn Authenticator>>,
+) -> Router<AppState> {
+    let protect = |route: MethodRouter<AppState>| match admin.scheme() {
+        Some(scheme) => crate::auth::require(route, scheme, authenticator.clone()),
+        None => route,
+    };
+    let info = {
+        let runtime = runtime.clone();
+        get(move || async move {
+            Json(json!({
+                "version": runtime.version,
+                "started_at": runtime.started_at,
+                "uptime_secs": runtime.started.elapsed().as_secs(),
+                "connections": crate::listener::open_connections(),
+                "in_flight": runtime.in_flight.load(Ordering::Relaxed),
+            }))
+        })
+    };
+    let config = {
+        let runtime = runtime.clone();
+        get(move || async move { Json(runtime.config.clone()) })
+    };
+    let body_log = runtime.body_log.clone();
+    let routes = get(move || async move {
+        let dynamic: Vec<Value> = runtime
+            .dynamic
+            .routes()
+            .into_iter()
+            .map(|(method, path)| json!({ "method": method, "path": path }))
+            .collect();
+        Json(json!({ "routes": runtime.routes, "dynamic": dynamic }))
+    });
+    let level = get(|| async { Json(json!({ "level": crate::logging::level() })) }).put(|body: axum::body::Bytes| async move {
+        let change: LevelChange = match serde_json::from_slice(&body) {
+            Ok(change) => change,
+            Err(err) => return FerroxError::BadRequest(format!("Invalid request body: {}", err)).into_response(),
+        };
+        match crate::logging::set_level(&change.level) {
+            Ok(()) => {
+                tracing::info!(level = change.level, "Log level changed");
+                Json(json!({ "level": change.level })).into_response()
+            }
+            Err(message) => FerroxError::BadRequest(message).into_response(),
+        }
+    });
+    let path = &admin.path;
+    let router = router
+        .route(&format!("{}/info", path), protect(info))
+        .route(&format!("{}/config", path), protect(config))
+        .route(&format!("{}/routes", path), protect(routes))
+        .route(&format!("{}/log-level", path), protect(level));
+    match body_log {
+        Some(log) => router.route(&format!("{}/body-log", path), protect(body_logging(log))),
+        None => router,
+    }
+}
+
+fn body_logging(log: BodyLog) -> MethodRouter<AppState> {
+    let status = log.clone();
+    get(move || async move { Json(status.status()) }).put(move |body: axum::body::Bytes| async move {
+        let change: BodyLogChange = match serde_json::from_slice(&body) {
+            Ok(change) => change,
+            Err(err) => return FerroxError::BadRequest(format!("Invalid request body: {}", err)).into_response(),
+        };
+        let status = change.apply(&log);
+        tracing::warn!(enabled = status.enabled, routes = ?status.routes, "Body logging changed");
+        Json(status).into_response()
+    })
+}
+
+// The endpoints alone, for their own listener
+pub(crate) fn router(
+    admin: &Admin,
+    runtime: Arc<Runtime>,
+    authenticator: Option<Arc<dyn Authenticator>>,
+    state: AppState,
+) -> Router {
+    mount(Router::new(), admin, runtime, authenticator)
+        .fallback(crate::not_found_handler)
+        .with_state(state)
+}
+
@@ -0,0 +1,347 @@
+//! An audit trail of the requests that change things: who made them, what
+//! they did, and when.
+//!
+//! ```ignore
+//! Server::new()
+//!     .jwt(verifier)
+//!     .audit(AuditLog::new(FileSink::new("audit.ndjson")).redact_field("card_number"));
+//!
+//! #[http_method(POST, "/sessions/refresh", audit = false)]
+//! async fn refresh() -> Value { ... }
+//! ```
+//!
+//! `Server::audit` writes one [`AuditRecord`] per POST, PUT, PATCH and DELETE
+//! request to a `#[http_method]` route, once it has been answered: the
+//! principal (found as `Server::rbac` finds it), client IP, method, route
+//! pattern and path, status, and a SHA-256 hash of the body. JSON bodies are
+//! also kept, with `password`, `token` and `secret` fields at any depth, and
+//! those given to `redact_field`, recorded as `[redacted]`.
+//!
+//! Requests are audited after authentication, so requests refused for their
+//! permissions or by guards are recorded along with those that ran; requests
+//! that fail authentication have no principal to record and are left out.
+//! Routes opt out with `audit = false`.
+//!
+//! Records go to an [`AuditSink`]: a file ([`FileSink`]), an HTTP endpoint
+//! ([`HttpSink`], with the `client` feature), memory ([`MemorySink`], for
+//! tests), or a function such as an insert into a database table
+//! ([`sink_fn`]). A sink that fails is logged; the response is sent anyway.
+
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use axum::body::{Body, Bytes};
+use axum::extract::Request;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::routing::MethodRouter;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::context::AppState;
+use crate::error::FerroxError;
+use crate::logging::RequestId;
+use crate::network;
+use crate::rbac::Rbac;
+use crate::session::StoreFuture;
+
+/// One audited request, as written to the sink.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// When the request came in, as RFC 3339.
+    pub at: String,
+    /// Id of the principal making the request, if it had one.
+    pub principal: Option<String>,
+    pub roles: Vec<String>,
+    pub client_ip: Option<String>,
+    /// The request's `X-Request-Id`, when the access log is enabled.
+    pub request_id: Option<String>,
+    pub method: String,
+    /// The route pattern, e.g. `/users/:id`.
+    pub route: String,
+    /// The path requested, e.g. `/users/42`.
+    pub path: String,
+    pub status: u16,
+    /// Hex SHA-256 of the request body; `None` if it was empty.
+    pub body_sha256: Option<String>,
+    /// The JSON request body with secrets redacted; `None` for other bodies.
+    pub body: Option<Value>,
+}
+
+/// Where audit records are written.
+pub trait AuditSink: Send + Sync + 'static {
+    fn record<'a>(&'a self, record: &'a AuditRecord) -> StoreFuture<'a, ()>;
+}
+
+/// Appends records to a file, one JSON object per line.
+pub struct FileSink {
+    path: Arc<PathBuf>,
+    // Opened on the first record
+    file: Arc<Mutex<Option<File>>>,
+}
+
+impl FileSink {
+    /// Append to the file at `path`, creating it if needed.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileSink {
+            path: Arc::new(path.into()),
+            file: Arc::default(),
+        }
+    }
+}
+
+impl AuditSink for FileSink {
+    fn record<'a>(&'a self, record: &'a AuditRecord) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let mut line = serde_json::to_vec(record).map_err(|err| FerroxError::Internal(err.to_string()))?;
+            line.push(b'\n');
+            let (path, file) = (self.path.clone(), self.file.clone());
+            let written = tokio::task::spawn_blocking(move || {
+                let mut file = file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                if file.is_none() {
+                    *file = Some(OpenOptions::new().create(true).append(true).open(path.as_ref())?);
+                }
+                match file.as_mut() {
+                    Some(file) => file.write_all(&line),
+                    None => Ok(()),
+                }
+            })
+            .await;
+            match written {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(err)) => Err(FerroxError::Internal(format!("failed to write {}: {}", self.path.display(), err))),
+                Err(err) => Err(FerroxError::Internal(err.to_string())),
+            }
+        })
+    }
+}
+
+/// Posts each record as JSON to an HTTP endpoint, such as a log collector.
+#[cfg(feature = "client")]
+pub struct HttpSink {
+    url: String,
+    http: reqwest::Client,
+    headers: Vec<(String, String)>,
+}
+
+#[cfg(feature = "client")]
+impl HttpSink {
+    pub fn new(url: impl Into<String>) -> Self {
+        HttpSink {
+            url: url.into(),
+            http: reqwest::Client::new(),
+            headers: Vec::new(),
+        }
+    }
+
+    /// Send `name: value` with every record, e.g. an `Authorization` header.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+#[cfg(feature = "client")]
+impl AuditSink for HttpSink {
+    fn record<'a>(&'a self, record: &'a AuditRecord) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let mut request = self.http.post(&self.url).json(record);
+            for (name, value) in &self.headers {
+                request = request.header(name, value);
+            }
+            let response = request
+                .send()
+                .await
+                .map_err(|err| FerroxError::Internal(format!("failed to send an audit record: {}", err)))?;
+            if !response.status().is_success() {
+                return Err(FerroxError::Internal(format!("audit endpoint answered {}", response.status())));
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Keeps records in memory, e.g. to check them in tests.
+///
+/// Clones share the same records.
+#[derive(Clone, Default)]
+pub struct MemorySink {
+    records: Arc<Mutex<Vec<AuditRecord>>>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The records written so far, oldest first.
+    pub fn records(&self) -> Vec<AuditRecord> {
+        self.records.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+}
+
+impl AuditSink for MemorySink {
+    fn record<'a>(&'a self, record: &'a AuditRecord) -> StoreFuture<'a, ()> {
+        self.records
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(record.clone());
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// A sink calling `write` with each record, e.g. to insert it into a table:
+///
+/// ```ignore
+/// let sink = sink_fn(move |record: AuditRecord| {
+///     let pool = pool.clone();
+///     async move {
+///         sqlx::query("INSERT INTO audit_log (at, principal, method, route, status, body) VALUES ($1, $2, $3, $4, $5, $6)")
+///             .bind(record.at).bind(record.principal).bind(record.method)
+///             .bind(record.route).bind(i32::from(record.status)).bind(record.body)
+///             .execute(&pool)
+///             .await?;
+///         Ok(())
+///     }
+/// });
+/// ```
+pub fn sink_fn<F, Fut>(write: F) -> impl AuditSink
+where
+    F: Fn(AuditRecord) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), FerroxError>> + Send + 'static,
+{
+    FnSink(write)
+}
+
+struct FnSink<F>(F);
+
+impl<F, Fut> AuditSink for FnSink<F>
+where
+    F: Fn(AuditRecord) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), FerroxError>> + Send + 'static,
+{
+    fn record<'a>(&'a self, record: &'a AuditRecord) -> StoreFuture<'a, ()> {
+        Box::pin((self.0)(record.clone()))
+    }
+}
+
+/// What `Server::audit` records, and where.
+#[derive(Clone)]
+pub struct AuditLog {
+    sink: Arc<dyn AuditSink>,
+    methods: Vec<Method>,
+    redact_fields: Vec<String>,
+    bodies: bool,
+}
+
+impl AuditLog {
+    /// Write records of POST, PUT, PATCH and DELETE requests to `sink`.
+    pub fn new(sink: impl AuditSink) -> Self {
+        AuditLog {
+            sink: Arc::new(sink),
+            methods: vec![Method::POST, Method::PUT, Method::PATCH, Method::DELETE],
+            redact_fields: ["password", "token", "secret"].map(str::to_string).to_vec(),
+            bodies: true,
+        }
+    }
+
+    /// Audit requests with these methods instead.
+    pub fn methods(mut self, methods: impl IntoIterator<Item = Method>) -> Self {
+        self.methods = methods.into_iter().collect();
+        self
+    }
+
+    /// Also record JSON body fields named `name` (in any case), at any depth, as `[redacted]`.
+    pub fn redact_field(mut self, name: &str) -> Self {
+        self.redact_fields.push(name.to_string());
+        self
+    }
+
+    /// Keep JSON bodies in records (on by default); the hash is recorded either way.
+    pub fn bodies(mut self, enabled: bool) -> Self {
+        self.bodies = enabled;
+        self
+    }
+
+    // Whether requests to a `method` route are audited
+    pub(crate) fn covers(&self, method: &str) -> bool {
+        self.methods.iter().any(|audited| audited.as_str() == method)
+    }
+
+    fn body(&self, request: &Request, bytes: &Bytes) -> Option<Value> {
+        let is_json = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("json"));
+        if !self.bodies || !is_json {
+            return None;
+        }
+        let mut value = serde_json::from_slice(bytes).ok()?;
+        crate::recorder::redact(&mut value, &self.redact_fields);
+        Some(value)
+    }
+}
+
+// Audit the requests to one route, served at `route`
+pub(crate) fn audit_route(
+    route: MethodRouter<AppState>,
+    log: AuditLog,
+    rbac: Rbac,
+    pattern: String,
+    max_body_size: usize,
+) -> MethodRouter<AppState> {
+    route.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let (log, rbac, pattern) = (log.clone(), rbac.clone(), pattern.clone());
+        async move {
+            let at = chrono::Utc::now().to_rfc3339();
+            if crate::expect::declares_over(request.headers(), max_body_size) {
+                return crate::dispatch::body_too_large(max_body_size);
+            }
+            let (parts, body) = request.into_parts();
+            let bytes = match axum::body::to_bytes(body, max_body_size).await {
+                Ok(bytes) => bytes,
+                Err(err) => {
+                    let over_limit = std::error::Error::source(&err)
+                        .is_some_and(|source| source.is::<http_body_util::LengthLimitError>());
+                    if over_limit {
+                        return crate::dispatch::body_too_large(max_body_size);
+                    }
+                    return FerroxError::BadRequest("Failed to read request body".to_string()).into_response();
+                }
+            };
+            let request = Request::from_parts(parts, Body::from(bytes.clone()));
+            let principal = rbac.find_principal(request.extensions());
+            let mut record = AuditRecord {
+                at,
+                principal: principal.as_ref().and_then(|principal| principal.id.clone()),
+                roles: principal.map(|principal| principal.roles).unwrap_or_default(),
+                client_ip: network::client_ip(request.extensions()).map(|ip| ip.to_string()),
+                request_id: request.extensions().get::<RequestId>().map(|RequestId(id)| id.clone()),
+                method: request.method().to_string(),
+                route: pattern,
+                path: request.uri().path().to_string(),
+                status: 0,
+                body_sha256: (!bytes.is_empty()).then(|| hex(&Sha256::digest(&bytes))),
+                body: log.body(&request, &bytes),
+            };
+
+            let response = next.run(request).await;
+            record.status = response.status().as_u16();
+            if let Err(err) = log.sink.record(&record).await {
+                tracing::error!("Failed to write the audit record of {} {}: {}", record.method, record.path, err);
+            }
+            response
+        }
+    }))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
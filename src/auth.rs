@@ -0,0 +1,97 @@
+//! Request authentication for routes declared with `auth = "<scheme>"`.
+//!
+//! A scheme name is bound to an [`Authenticator`] with `Server::authenticator`
+//! (or a shortcut such as `Server::jwt`). Before the handler runs, the
+//! authenticator checks the request and records who made it in the request
+//! extensions, where typed handler parameters like `Claims` or `ApiKey` pick it up.
+//! Failures are answered with the error envelope, normally 401.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use axum::extract::Request;
+use axum::http::header::WWW_AUTHENTICATE;
+use axum::http::{request, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::routing::MethodRouter;
+
+use crate::context::{AppState, RequestContext};
+use crate::error::FerroxError;
+
+pub mod api_key;
+#[cfg(feature = "jwt")]
+pub mod jwt;
+
+/// Future returned by [`Authenticator::authenticate`].
+pub type AuthFuture<'a> = Pin<Box<dyn Future<Output = Result<(), FerroxError>> + Send + 'a>>;
+
+/// Checks requests for routes using one authentication scheme.
+pub trait Authenticator: Send + Sync + 'static {
+    /// Accept the request, recording the authenticated identity in
+    /// `request.extensions`, or reject it with an error (usually
+    /// `FerroxError::Unauthorized`).
+    fn authenticate<'a>(&'a self, request: &'a mut request::Parts) -> AuthFuture<'a>;
+
+    /// `WWW-Authenticate` value sent with 401 responses, e.g. `Bearer`.
+    fn challenge(&self) -> Option<HeaderValue> {
+        None
+    }
+}
+
+/// Take the identity an authenticator recorded for this request.
+///
+/// Answers 401 when there is none, i.e. the route has no `auth` option.
+pub fn identity<T: Clone + Send + Sync + 'static>(ctx: &RequestContext) -> Result<T, FerroxError> {
+    ctx.extensions.get::<T>().cloned().ok_or_else(|| {
+        tracing::error!(
+            "No {} was recorded for this request; is the route missing an `auth` option?",
+            std::any::type_name::<T>()
+        );
+        FerroxError::Unauthorized("Authentication required".to_string())
+    })
+}
+
+/// The `Bearer` token from the `Authorization` header.
+pub fn bearer_token(request: &request::Parts) -> Option<&str> {
+    let value = request.headers.get(axum::http::header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+}
+
+// Run `authenticator` in front of a route; `None` for a scheme no authenticator was registered for
+pub(crate) fn require(
+    route: MethodRouter<AppState>,
+    scheme: &'static str,
+    authenticator: Option<Arc<dyn Authenticator>>,
+) -> MethodRouter<AppState> {
+    if authenticator.is_none() {
+        tracing::error!("No authenticator registered for scheme `{}`; its routes will answer 500", scheme);
+    }
+    route.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let authenticator = authenticator.clone();
+        async move {
+            // Fail closed when the scheme is not configured
+            let Some(authenticator) = authenticator else {
+                return FerroxError::Internal(format!(
+                    "Internal server error: authentication scheme `{}` is not configured",
+                    scheme
+                ))
+                .into_response();
+            };
+            let (mut parts, body) = request.into_parts();
+            match authenticator.authenticate(&mut parts).await {
+                Ok(()) => next.run(Request::from_parts(parts, body)).await,
+                Err(err) => {
+                    let unauthorized = err.status() == StatusCode::UNAUTHORIZED;
+                    let mut response = err.into_response();
+                    if let (true, Some(challenge)) = (unauthorized, authenticator.challenge()) {
+                        response.headers_mut().insert(WWW_AUTHENTICATE, challenge);
+                    }
+                    response
+                }
+            }
+        }
+    }))
+}
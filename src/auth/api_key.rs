@@ -0,0 +1,258 @@
+//! API key authentication.
+//!
+//! ```ignore
+//! let keys = InMemoryKeyStore::new().with_key("reporting", "k-2f9c...");
+//! Server::new().api_keys(ApiKeyAuth::new(keys));
+//!
+//! #[http_method(GET, "/reports", auth = "api_key")]
+//! fn reports(key: ApiKey) -> Value { json!({ "client": key.name }) }
+//! ```
+//!
+//! Keys are read from the `X-Api-Key` header by default; another header or a
+//! query parameter can be configured. The store maps each key to an [`ApiKey`]
+//! naming its holder, which is what handlers see; the key itself is never
+//! handed to them.
+
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use axum::http::{request, HeaderMap, HeaderName, Uri};
+
+use super::{AuthFuture, Authenticator};
+use crate::error::FerroxError;
+
+/// The holder of a valid API key, available to handlers of routes declared
+/// with `auth = "api_key"` through an `ApiKey` parameter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKey {
+    pub name: String,
+}
+
+/// Future returned by [`KeyStore::lookup`].
+pub type KeyFuture<'a> = Pin<Box<dyn Future<Output = Option<ApiKey>> + Send + 'a>>;
+
+/// Where valid API keys come from.
+pub trait KeyStore: Send + Sync + 'static {
+    /// The holder of `key`, or `None` if it is not a valid key.
+    fn lookup<'a>(&'a self, key: &'a str) -> KeyFuture<'a>;
+}
+
+/// Keys held in memory.
+///
+/// Every lookup compares against all keys in constant time per key, so
+/// response timing does not reveal how much of a key matched.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryKeyStore {
+    keys: Vec<(String, ApiKey)>,
+}
+
+impl InMemoryKeyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept `key` on behalf of `name`.
+    pub fn with_key(mut self, name: impl Into<String>, key: impl Into<String>) -> Self {
+        self.insert(name, key);
+        self
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, key: impl Into<String>) {
+        self.keys.push((key.into(), ApiKey { name: name.into() }));
+    }
+
+    fn find(&self, key: &str) -> Option<ApiKey> {
+        let mut found = None;
+        for (candidate, holder) in &self.keys {
+            if constant_time_eq(candidate.as_bytes(), key.as_bytes()) && found.is_none() {
+                found = Some(holder.clone());
+            }
+        }
+        found
+    }
+}
+
+impl KeyStore for InMemoryKeyStore {
+    fn lookup<'a>(&'a self, key: &'a str) -> KeyFuture<'a> {
+        Box::pin(async move { self.find(key) })
+    }
+}
+
+/// Keys read from a file of `name:key` lines; blank lines and `#` comments are ignored.
+///
+/// The file is re-read when its modification time changes, checked at most
+/// once per `reload_interval` (5 seconds by default). If a reload fails the
+/// previous keys stay in use.
+pub struct FileKeyStore {
+    path: PathBuf,
+    reload_interval: Duration,
+    state: RwLock<FileState>,
+}
+
+struct FileState {
+    keys: InMemoryKeyStore,
+    modified: Option<SystemTime>,
+    checked_at: Instant,
+}
+
+impl FileKeyStore {
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let (keys, modified) = read_keys(&path)?;
+        Ok(FileKeyStore {
+            path,
+            reload_interval: Duration::from_secs(5),
+            state: RwLock::new(FileState {
+                keys,
+                modified,
+                checked_at: Instant::now(),
+            }),
+        })
+    }
+
+    pub fn reload_interval(mut self, interval: Duration) -> Self {
+        self.reload_interval = interval;
+        self
+    }
+
+    fn reload_if_changed(&self) {
+        {
+            let state = self.state.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if state.checked_at.elapsed() < self.reload_interval {
+                return;
+            }
+        }
+        let mut state = self.state.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.checked_at = Instant::now();
+        let modified = std::fs::metadata(&self.path).and_then(|meta| meta.modified()).ok();
+        if modified == state.modified {
+            return;
+        }
+        match read_keys(&self.path) {
+            Ok((keys, modified)) => {
+                tracing::info!("Reloaded API keys from {}", self.path.display());
+                state.keys = keys;
+                state.modified = modified;
+            }
+            Err(err) => tracing::error!("Failed to reload API keys from {}: {}", self.path.display(), err),
+        }
+    }
+}
+
+impl KeyStore for FileKeyStore {
+    fn lookup<'a>(&'a self, key: &'a str) -> KeyFuture<'a> {
+        Box::pin(async move {
+            self.reload_if_changed();
+            let state = self.state.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+            state.keys.find(key)
+        })
+    }
+}
+
+fn read_keys(path: &Path) -> io::Result<(InMemoryKeyStore, Option<SystemTime>)> {
+    let modified = std::fs::metadata(path)?.modified().ok();
+    let text = std::fs::read_to_string(path)?;
+    let mut keys = InMemoryKeyStore::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((name, key)) = line.split_once(':') else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: expected `name:key`", number + 1),
+            ));
+        };
+        keys.insert(name.trim(), key.trim());
+    }
+    Ok((keys, modified))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Authenticates requests by API key; register it with `Server::api_keys`.
+#[derive(Clone)]
+pub struct ApiKeyAuth {
+    store: Arc<dyn KeyStore>,
+    header: Option<HeaderName>,
+    query_param: Option<String>,
+}
+
+impl ApiKeyAuth {
+    /// Check keys from the `X-Api-Key` header against `store`.
+    pub fn new<S: KeyStore>(store: S) -> Self {
+        ApiKeyAuth {
+            store: Arc::new(store),
+            header: Some(HeaderName::from_static("x-api-key")),
+            query_param: None,
+        }
+    }
+
+    /// Read the key from `name` instead of `X-Api-Key`.
+    pub fn header(mut self, name: HeaderName) -> Self {
+        self.header = Some(name);
+        self
+    }
+
+    /// Also accept the key as the query parameter `name`; the header wins if both are sent.
+    pub fn query_param(mut self, name: impl Into<String>) -> Self {
+        self.query_param = Some(name.into());
+        self
+    }
+
+    /// Accept the key only as the query parameter `name`.
+    pub fn query_param_only(mut self, name: impl Into<String>) -> Self {
+        self.header = None;
+        self.query_param(name)
+    }
+
+    // The holder of the key the request presents, if it is a valid one
+    pub(crate) async fn holder(&self, headers: &HeaderMap, uri: &Uri) -> Option<ApiKey> {
+        let key = self.presented_key(headers, uri)?;
+        self.store.lookup(&key).await
+    }
+
+    fn presented_key(&self, headers: &HeaderMap, uri: &Uri) -> Option<String> {
+        let from_header = self
+            .header
+            .as_ref()
+            .and_then(|name| headers.get(name))
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        from_header.or_else(|| {
+            let name = self.query_param.as_ref()?;
+            let query = uri.query()?;
+            serde_urlencoded::from_str::<Vec<(String, String)>>(query)
+                .ok()?
+                .into_iter()
+                .find_map(|(key, value)| (&key == name).then_some(value))
+        })
+    }
+}
+
+impl Authenticator for ApiKeyAuth {
+    fn authenticate<'a>(&'a self, request: &'a mut request::Parts) -> AuthFuture<'a> {
+        Box::pin(async move {
+            let key = self
+                .presented_key(&request.headers, &request.uri)
+                .ok_or_else(|| FerroxError::Unauthorized("Missing API key".to_string()))?;
+            let holder = self
+                .store
+                .lookup(&key)
+                .await
+                .ok_or_else(|| FerroxError::Unauthorized("Invalid API key".to_string()))?;
+            request.extensions.insert(holder);
+            Ok(())
+        })
+    }
+}
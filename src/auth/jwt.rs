@@ -0,0 +1,277 @@
+//! JWT bearer authentication (feature `jwt`).
+//!
+//! ```ignore
+//! let verifier = JwtVerifier::hs256(secret).issuer("https://auth.example.com").audience("api");
+//! Server::new().jwt(verifier);
+//!
+//! #[http_method(GET, "/me", auth = "jwt")]
+//! fn me(claims: Claims) -> Value { json!({ "sub": claims.subject() }) }
+//! ```
+//!
+//! Tokens are read from `Authorization: Bearer <token>`. Signature, expiry and
+//! not-before are always checked; issuer and audience when configured. With
+//! the `jwks` feature, RS256 keys can be fetched from a JWKS endpoint and are
+//! cached by key id.
+
+#[cfg(feature = "jwks")]
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::http::{request, HeaderValue};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use super::{bearer_token, AuthFuture, Authenticator};
+use crate::error::FerroxError;
+
+/// Validated claims of the request's token, available to handlers of routes
+/// declared with `auth = "jwt"` through a `Claims` parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Claims(pub Value);
+
+impl Claims {
+    /// The `sub` claim.
+    pub fn subject(&self) -> Option<&str> {
+        self.0.get("sub").and_then(Value::as_str)
+    }
+
+    /// A single claim by name.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.0.get(name)
+    }
+
+    /// All claims as an application type.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, FerroxError> {
+        serde_json::from_value(self.0.clone())
+            .map_err(|err| FerroxError::Unauthorized(format!("Invalid token claims: {}", err)))
+    }
+}
+
+#[derive(Clone)]
+enum Keys {
+    Static(Algorithm, DecodingKey),
+    #[cfg(feature = "jwks")]
+    Jwks(Arc<jwks::JwksCache>),
+}
+
+/// Verifies JWT bearer tokens; register it with `Server::jwt`.
+#[derive(Clone)]
+pub struct JwtVerifier {
+    keys: Keys,
+    issuer: Option<String>,
+    audience: Option<String>,
+    leeway: Duration,
+    #[cfg(feature = "jwks")]
+    refresh_interval: Duration,
+}
+
+impl JwtVerifier {
+    fn with_keys(keys: Keys) -> Self {
+        JwtVerifier {
+            keys,
+            issuer: None,
+            audience: None,
+            leeway: Duration::from_secs(60),
+            #[cfg(feature = "jwks")]
+            refresh_interval: Duration::from_secs(300),
+        }
+    }
+
+    /// Verify HS256 tokens signed with a shared secret.
+    pub fn hs256(secret: impl AsRef<[u8]>) -> Self {
+        Self::with_keys(Keys::Static(Algorithm::HS256, DecodingKey::from_secret(secret.as_ref())))
+    }
+
+    /// Verify RS256 tokens against a PEM-encoded RSA public key.
+    pub fn rs256_pem(public_key: impl AsRef<[u8]>) -> Result<Self, FerroxError> {
+        let key = DecodingKey::from_rsa_pem(public_key.as_ref())
+            .map_err(|err| FerroxError::Internal(format!("Invalid RSA public key: {}", err)))?;
+        Ok(Self::with_keys(Keys::Static(Algorithm::RS256, key)))
+    }
+
+    /// Verify RS256 tokens against keys published at a JWKS `url`.
+    ///
+    /// Keys are fetched on first use and again when a token names an unknown
+    /// key id, at most once per `refresh_interval` (5 minutes by default).
+    /// Verifications with cached keys never wait on a fetch, and a fetch that
+    /// stalls gives up after 10 seconds.
+    #[cfg(feature = "jwks")]
+    pub fn jwks(url: impl Into<String>) -> Self {
+        Self::with_keys(Keys::Jwks(Arc::new(jwks::JwksCache::new(url.into()))))
+    }
+
+    /// Minimum time between JWKS fetches; ignored for static keys.
+    #[cfg(feature = "jwks")]
+    pub fn refresh_interval(mut self, interval: Duration) -> Self {
+        self.refresh_interval = interval;
+        self
+    }
+
+    /// Require the `iss` claim to equal `issuer`.
+    pub fn issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    /// Require the `aud` claim to contain `audience`.
+    pub fn audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    /// Clock skew tolerated for `exp` and `nbf` (60 seconds by default).
+    pub fn leeway(mut self, leeway: Duration) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Verify `token` and return its claims.
+    pub async fn verify(&self, token: &str) -> Result<Claims, FerroxError> {
+        match &self.keys {
+            Keys::Static(algorithm, key) => self.decode(token, *algorithm, key),
+            #[cfg(feature = "jwks")]
+            Keys::Jwks(cache) => {
+                let header = jsonwebtoken::decode_header(token).map_err(invalid_token)?;
+                let kid = header
+                    .kid
+                    .ok_or_else(|| FerroxError::Unauthorized("Invalid token: missing key id".to_string()))?;
+                let (algorithm, key) = cache.key(&kid, self.refresh_interval).await?;
+                self.decode(token, algorithm, &key)
+            }
+        }
+    }
+
+    fn decode(&self, token: &str, algorithm: Algorithm, key: &DecodingKey) -> Result<Claims, FerroxError> {
+        let mut validation = Validation::new(algorithm);
+        validation.leeway = self.leeway.as_secs();
+        validation.validate_nbf = true;
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        jsonwebtoken::decode::<Value>(token, key, &validation)
+            .map(|data| Claims(data.claims))
+            .map_err(invalid_token)
+    }
+}
+
+impl Authenticator for JwtVerifier {
+    fn authenticate<'a>(&'a self, request: &'a mut request::Parts) -> AuthFuture<'a> {
+        Box::pin(async move {
+            let token = bearer_token(request)
+                .ok_or_else(|| FerroxError::Unauthorized("Missing bearer token".to_string()))?
+                .to_string();
+            let claims = self.verify(&token).await?;
+            request.extensions.insert(claims);
+            Ok(())
+        })
+    }
+
+    fn challenge(&self) -> Option<HeaderValue> {
+        Some(HeaderValue::from_static("Bearer"))
+    }
+}
+
+fn invalid_token(err: jsonwebtoken::errors::Error) -> FerroxError {
+    FerroxError::Unauthorized(format!("Invalid token: {}", err))
+}
+
+#[cfg(feature = "jwks")]
+mod jwks {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    use jsonwebtoken::jwk::{AlgorithmParameters, JwkSet};
+    use jsonwebtoken::{Algorithm, DecodingKey};
+
+    use crate::error::FerroxError;
+
+    pub(super) struct JwksCache {
+        url: String,
+        client: reqwest::Client,
+        state: Mutex<CacheState>,
+        // Held for the length of a fetch, so concurrent misses share one request
+        refreshing: tokio::sync::Mutex<()>,
+    }
+
+    #[derive(Default)]
+    struct CacheState {
+        keys: HashMap<String, (Algorithm, DecodingKey)>,
+        fetched_at: Option<Instant>,
+    }
+
+    impl JwksCache {
+        pub(super) fn new(url: String) -> Self {
+            let client = reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(5))
+                .read_timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default();
+            JwksCache {
+                url,
+                client,
+                state: Mutex::new(CacheState::default()),
+                refreshing: tokio::sync::Mutex::new(()),
+            }
+        }
+
+        pub(super) async fn key(
+            &self,
+            kid: &str,
+            refresh_interval: Duration,
+        ) -> Result<(Algorithm, DecodingKey), FerroxError> {
+            let seen = match self.cached(kid) {
+                (Some(key), _) => return Ok(key),
+                (None, fetched_at) => fetched_at,
+            };
+            let _refreshing = self.refreshing.lock().await;
+            // Unknown key id: the keys may have rotated, but don't let bad tokens hammer the endpoint
+            let (key, fetched_at) = self.cached(kid);
+            let due = fetched_at == seen && fetched_at.is_none_or(|at| at.elapsed() >= refresh_interval);
+            if key.is_none() && due {
+                let keys = self.fetch().await;
+                let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                state.fetched_at = Some(Instant::now());
+                match keys {
+                    Ok(keys) => state.keys = keys,
+                    Err(err) => tracing::error!("Failed to fetch JWKS from {}: {}", self.url, err),
+                }
+            }
+            key.or_else(|| self.cached(kid).0)
+                .ok_or_else(|| FerroxError::Unauthorized("Invalid token: unknown key id".to_string()))
+        }
+
+        fn cached(&self, kid: &str) -> (Option<(Algorithm, DecodingKey)>, Option<Instant>) {
+            let state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            (state.keys.get(kid).cloned(), state.fetched_at)
+        }
+
+        async fn fetch(&self) -> Result<HashMap<String, (Algorithm, DecodingKey)>, String> {
+            let set: JwkSet = self
+                .client
+                .get(&self.url)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|err| err.to_string())?
+                .json()
+                .await
+                .map_err(|err| err.to_string())?;
+            let mut keys = HashMap::new();
+            for jwk in &set.keys {
+                let (Some(kid), AlgorithmParameters::RSA(_)) = (&jwk.common.key_id, &jwk.algorithm) else {
+                    continue;
+                };
+                if let Ok(key) = DecodingKey::from_jwk(jwk) {
+                    keys.insert(kid.clone(), (Algorithm::RS256, key));
+                }
+            }
+            Ok(keys)
+        }
+    }
+}
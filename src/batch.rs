@@ -0,0 +1,222 @@
+//! A batch endpoint running several requests in one round trip.
+//!
+//! ```ignore
+//! Server::new().batch(Batch::new().path("/batch").concurrency(4).max_requests(20));
+//! ```
+//!
+//! A `POST` to the batch path takes a JSON array of requests:
+//!
+//! ```text
+//! [
+//!     { "method": "GET", "path": "/users/7" },
+//!     { "method": "POST", "path": "/orders", "body": { "sku": "A-1" } },
+//!     { "method": "GET", "path": "/reports?year=2024", "headers": { "accept-language": "de" } }
+//! ]
+//! ```
+//!
+//! and answers 200 with their responses in the same order, each as
+//! `{"status", "headers", "body"}`; a JSON body is included as JSON and any
+//! other as a string. Each request is routed through the whole server, layers
+//! included, with the batch request's headers (its `Authorization`, say) and
+//! its own on top, so auth, guards and rate limits apply to each request as if
+//! it were sent alone. At most `concurrency` of them run at once. A request
+//! that fails answers its own status without failing the batch; batches are
+//! not nested.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use axum::http::{HeaderName, HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router};
+use futures_util::stream::{self, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tower::Service;
+
+use crate::dispatch;
+use crate::error::FerroxError;
+
+/// The batch endpoint's settings; register it with `Server::batch`.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    path: String,
+    concurrency: usize,
+    max_requests: usize,
+}
+
+impl Default for Batch {
+    fn default() -> Self {
+        Batch {
+            path: "/batch".to_string(),
+            concurrency: 8,
+            max_requests: 50,
+        }
+    }
+}
+
+impl Batch {
+    /// A batch endpoint at `/batch`, running 8 requests at once and taking at most 50.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve the endpoint at `path` instead of `/batch`.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// How many of a batch's requests run at once (8 by default, at least 1).
+    pub fn concurrency(mut self, requests: usize) -> Self {
+        self.concurrency = requests.max(1);
+        self
+    }
+
+    /// Most requests in one batch (50 by default); larger batches answer 400.
+    pub fn max_requests(mut self, requests: usize) -> Self {
+        self.max_requests = requests;
+        self
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Item {
+    method: String,
+    path: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    body: Option<Value>,
+}
+
+// Serve `config`'s endpoint in front of `router`, sending each batched request through it
+pub(crate) fn layer(router: Router, config: Batch, max_body_size: usize) -> Router {
+    let config = Arc::new(config);
+    let inner = router.clone();
+    let service = axum::middleware::from_fn(move |request: Request, next: Next| {
+        let config = config.clone();
+        let inner = inner.clone();
+        async move {
+            if request.method() != Method::POST || request.uri().path() != config.path {
+                return next.run(request).await;
+            }
+            run(&config, inner, request, max_body_size).await
+        }
+    });
+    Router::new().fallback_service(tower::Layer::layer(&service, router))
+}
+
+async fn run(config: &Batch, router: Router, request: Request, max_body_size: usize) -> Response {
+    if crate::expect::declares_over(request.headers(), max_body_size) {
+        return dispatch::body_too_large(max_body_size);
+    }
+    let (parts, body) = request.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, max_body_size).await else {
+        return dispatch::body_too_large(max_body_size);
+    };
+    let items: Vec<Item> = match serde_json::from_slice(&bytes) {
+        Ok(items) => items,
+        Err(err) => return FerroxError::BadRequest(format!("Invalid batch: {}", err)).into_response(),
+    };
+    if items.len() > config.max_requests {
+        let message = format!("A batch holds at most {} requests, got {}", config.max_requests, items.len());
+        return FerroxError::BadRequest(message).into_response();
+    }
+
+    let parts = &parts;
+    let responses: Vec<Value> = stream::iter(items)
+        .map(|item| {
+            let mut router = router.clone();
+            async move {
+                let response = match sub_request(config, parts, item) {
+                    Ok(request) => router.call(request).await.unwrap_or_else(|never| match never {}),
+                    Err(err) => err.into_response(),
+                };
+                describe(response).await
+            }
+        })
+        .buffered(config.concurrency)
+        .collect()
+        .await;
+    Json(responses).into_response()
+}
+
+// One batched request, with the batch request's headers and extensions under its own
+fn sub_request(config: &Batch, parts: &axum::http::request::Parts, item: Item) -> Result<Request, FerroxError> {
+    let method = Method::from_bytes(item.method.to_ascii_uppercase().as_bytes())
+        .map_err(|_| FerroxError::BadRequest(format!("Invalid method `{}`", item.method)))?;
+    if !item.path.starts_with('/') {
+        return Err(FerroxError::BadRequest(format!("Path `{}` must start with /", item.path)));
+    }
+    if item.path.split('?').next() == Some(config.path.as_str()) {
+        return Err(FerroxError::BadRequest("Batches cannot be nested".to_string()));
+    }
+    let mut request = Request::builder()
+        .method(method)
+        .uri(&item.path)
+        .body(match &item.body {
+            Some(body) => Body::from(body.to_string()),
+            None => Body::empty(),
+        })
+        .map_err(|_| FerroxError::BadRequest(format!("Invalid path `{}`", item.path)))?;
+
+    let headers = request.headers_mut();
+    for (name, value) in &parts.headers {
+        if name != CONTENT_LENGTH && name != CONTENT_TYPE {
+            headers.append(name, value.clone());
+        }
+    }
+    if item.body.is_some() {
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    }
+    for (name, value) in &item.headers {
+        match (HeaderName::try_from(name.as_str()), HeaderValue::from_str(value)) {
+            (Ok(name), Ok(value)) => {
+                headers.insert(name, value);
+            }
+            _ => return Err(FerroxError::BadRequest(format!("Invalid header `{}`", name))),
+        }
+    }
+    // The client address and the like, as seen for the batch request
+    *request.extensions_mut() = parts.extensions.clone();
+    Ok(request)
+}
+
+async fn describe(response: Response) -> Value {
+    let (parts, body) = response.into_parts();
+    let mut headers = serde_json::Map::new();
+    for (name, value) in &parts.headers {
+        let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+        match headers.get_mut(name.as_str()) {
+            Some(Value::String(existing)) => {
+                existing.push_str(", ");
+                existing.push_str(&value);
+            }
+            _ => {
+                headers.insert(name.to_string(), Value::String(value));
+            }
+        }
+    }
+    let json = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("json"));
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) if bytes.is_empty() => Value::Null,
+        Ok(bytes) => match serde_json::from_slice(&bytes) {
+            Ok(value) if json => value,
+            _ => Value::String(String::from_utf8_lossy(&bytes).into_owned()),
+        },
+        Err(err) => {
+            tracing::error!("Failed to read a batched response: {}", err);
+            return json!({ "status": 500, "headers": {}, "body": null });
+        }
+    };
+    json!({ "status": parts.status.as_u16(), "headers": headers, "body": body })
+}
@@ -0,0 +1,300 @@
+//! Load tests of the registered routes, run in-process.
+//!
+//! ```ignore
+//! let report = LoadTest::new(Server::new().state(db))
+//!     .request("show", 3, |client| client.get("/items/42"))
+//!     .request("create", 1, |client| client.post("/items").json(&json!({ "name": "widget" })))
+//!     .concurrency(32)
+//!     .duration(Duration::from_secs(10))
+//!     .run()
+//!     .await;
+//! println!("{}", report);
+//! assert!(report.percentile(99.0) < Duration::from_millis(5));
+//! ```
+//!
+//! Requests go through the same router as [`TestClient`](crate::test::TestClient),
+//! without sockets, so the report measures the framework and the handlers. The
+//! mix is deterministic: with weights 3 and 1, three of every four requests are
+//! `show`. `concurrency` tasks send requests back to back until the request
+//! count or the duration is reached; run it on a multi-threaded runtime to
+//! surface contention between them.
+//!
+//! In a criterion bench, `iter_custom` runs a given number of requests and
+//! times them:
+//!
+//! ```ignore
+//! b.to_async(&runtime).iter_custom(|iters| {
+//!     let load = load.clone().requests(iters);
+//!     async move { load.run().await.elapsed() }
+//! });
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::http::StatusCode;
+use tokio::task::JoinSet;
+
+use crate::test::{TestClient, TestRequest};
+use crate::Server;
+
+type Build = Arc<dyn Fn(&TestClient) -> TestRequest + Send + Sync>;
+
+#[derive(Clone)]
+struct Entry {
+    name: String,
+    build: Build,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Until {
+    Requests(u64),
+    Elapsed(Duration),
+}
+
+/// A request mix to send to a server; see the module docs.
+#[derive(Clone)]
+pub struct LoadTest {
+    client: TestClient,
+    entries: Vec<Entry>,
+    // Index into `entries` for each slot of one round of the mix
+    rotation: Arc<Vec<usize>>,
+    concurrency: usize,
+    until: Until,
+}
+
+impl LoadTest {
+    /// A load test of `server`, with its state, layers and other settings.
+    pub fn new(server: Server) -> Self {
+        Self::from_client(TestClient::from_server(server))
+    }
+
+    /// A load test sending its requests through `client`.
+    pub fn from_client(client: TestClient) -> Self {
+        LoadTest {
+            client,
+            entries: Vec::new(),
+            rotation: Arc::new(Vec::new()),
+            concurrency: 8,
+            until: Until::Requests(1_000),
+        }
+    }
+
+    /// Add `build`'s request to the mix, sent `weight` times per round and
+    /// reported as `name`.
+    pub fn request<F>(mut self, name: &str, weight: u32, build: F) -> Self
+    where
+        F: Fn(&TestClient) -> TestRequest + Send + Sync + 'static,
+    {
+        let index = self.entries.len();
+        self.entries.push(Entry {
+            name: name.to_string(),
+            build: Arc::new(build),
+        });
+        Arc::make_mut(&mut self.rotation).extend(std::iter::repeat_n(index, weight as usize));
+        self
+    }
+
+    /// Requests in flight at once, 8 by default.
+    pub fn concurrency(mut self, tasks: usize) -> Self {
+        self.concurrency = tasks.max(1);
+        self
+    }
+
+    /// Stop after `count` requests, 1000 by default.
+    pub fn requests(mut self, count: u64) -> Self {
+        self.until = Until::Requests(count);
+        self
+    }
+
+    /// Send requests for `duration` instead of a fixed count.
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.until = Until::Elapsed(duration);
+        self
+    }
+
+    /// Send the mix and report how it went. Panics if the mix is empty.
+    pub async fn run(&self) -> Report {
+        assert!(!self.rotation.is_empty(), "a load test needs a request with a weight above 0");
+        let sent = Arc::new(AtomicU64::new(0));
+        let started = Instant::now();
+        let mut tasks = JoinSet::new();
+        for _ in 0..self.concurrency {
+            let (load, sent) = (self.clone(), sent.clone());
+            tasks.spawn(async move { load.work(&sent, started).await });
+        }
+
+        let mut samples = Vec::new();
+        while let Some(result) = tasks.join_next().await {
+            samples.extend(result.unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic())));
+        }
+        let elapsed = started.elapsed();
+
+        let mut total = Stats::default();
+        let mut by_name: Vec<Stats> = vec![Stats::default(); self.entries.len()];
+        for (index, status, latency) in samples {
+            total.record(status, latency);
+            by_name[index].record(status, latency);
+        }
+        total.latencies.sort_unstable();
+        let requests = self
+            .entries
+            .iter()
+            .zip(by_name)
+            .map(|(entry, mut stats)| {
+                stats.latencies.sort_unstable();
+                (entry.name.clone(), stats)
+            })
+            .collect();
+        Report { elapsed, total, requests }
+    }
+
+    // One task's share: take the next slot of the mix until the run is over
+    async fn work(&self, sent: &AtomicU64, started: Instant) -> Vec<(usize, StatusCode, Duration)> {
+        let mut samples = Vec::new();
+        loop {
+            let slot = sent.fetch_add(1, Ordering::Relaxed);
+            let over = match self.until {
+                Until::Requests(count) => slot >= count,
+                Until::Elapsed(duration) => started.elapsed() >= duration,
+            };
+            if over {
+                return samples;
+            }
+            let index = self.rotation[(slot % self.rotation.len() as u64) as usize];
+            let request = (self.entries[index].build)(&self.client);
+            let start = Instant::now();
+            let response = request.await;
+            samples.push((index, response.status(), start.elapsed()));
+        }
+    }
+}
+
+impl fmt::Debug for LoadTest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = self.entries.iter().map(|entry| entry.name.as_str()).collect();
+        f.debug_struct("LoadTest")
+            .field("requests", &names)
+            .field("concurrency", &self.concurrency)
+            .field("until", &self.until)
+            .finish()
+    }
+}
+
+/// Latencies and statuses of a set of requests.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    statuses: BTreeMap<u16, u64>,
+    // Sorted once the run is over
+    latencies: Vec<Duration>,
+}
+
+impl Stats {
+    fn record(&mut self, status: StatusCode, latency: Duration) {
+        *self.statuses.entry(status.as_u16()).or_default() += 1;
+        self.latencies.push(latency);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.latencies.len() as u64
+    }
+
+    /// How many requests got each status code.
+    pub fn statuses(&self) -> &BTreeMap<u16, u64> {
+        &self.statuses
+    }
+
+    /// Requests answered with a 4xx or 5xx status.
+    pub fn failures(&self) -> u64 {
+        self.statuses.range(400..).map(|(_, count)| count).sum()
+    }
+
+    /// The latency `percent`% of the requests stayed within, by nearest rank;
+    /// zero when there were none.
+    pub fn percentile(&self, percent: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = (percent.clamp(0.0, 100.0) / 100.0 * self.latencies.len() as f64).ceil() as usize;
+        self.latencies[rank.saturating_sub(1)]
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.latencies.len() {
+            0 => Duration::ZERO,
+            count => self.latencies.iter().sum::<Duration>() / count as u32,
+        }
+    }
+
+    pub fn max(&self) -> Duration {
+        self.latencies.last().copied().unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} requests, {} failed, p50 {:.2?}, p90 {:.2?}, p99 {:.2?}, max {:.2?}",
+            self.count(),
+            self.failures(),
+            self.percentile(50.0),
+            self.percentile(90.0),
+            self.percentile(99.0),
+            self.max()
+        )
+    }
+}
+
+/// The outcome of [`LoadTest::run`]: overall stats, which it derefs to, and
+/// stats for each request of the mix.
+#[derive(Debug, Clone)]
+pub struct Report {
+    elapsed: Duration,
+    total: Stats,
+    requests: Vec<(String, Stats)>,
+}
+
+impl Report {
+    /// Wall time of the run.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Requests completed per second.
+    pub fn throughput(&self) -> f64 {
+        self.total.count() as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// The stats of the mix's request named `name`.
+    pub fn request(&self, name: &str) -> Option<&Stats> {
+        self.requests.iter().find(|(request, _)| request == name).map(|(_, stats)| stats)
+    }
+
+    /// Each request of the mix with its stats, in the order they were added.
+    pub fn requests(&self) -> impl Iterator<Item = (&str, &Stats)> {
+        self.requests.iter().map(|(name, stats)| (name.as_str(), stats))
+    }
+}
+
+impl std::ops::Deref for Report {
+    type Target = Stats;
+
+    fn deref(&self) -> &Stats {
+        &self.total
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:.2?}, {:.1} requests/s", self.elapsed, self.throughput())?;
+        write!(f, "total: {}", self.total)?;
+        for (name, stats) in &self.requests {
+            write!(f, "\n{}: {}", name, stats)?;
+        }
+        Ok(())
+    }
+}
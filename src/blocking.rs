@@ -0,0 +1,53 @@
+// The dedicated threads `Server::blocking_threads` sets up for `blocking = true`
+// handlers, so CPU-heavy routes neither stall the async workers nor compete with
+// other users of tokio's blocking pool. Jobs queue until a thread is free.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
+
+use tokio::sync::oneshot;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+#[derive(Clone)]
+pub(crate) struct BlockingPool {
+    // The threads exit once every clone is dropped with the router
+    sender: mpsc::Sender<Job>,
+}
+
+impl BlockingPool {
+    pub(crate) fn new(threads: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for index in 0..threads.max(1) {
+            let receiver = receiver.clone();
+            std::thread::Builder::new()
+                .name(format!("ferrox-blocking-{}", index))
+                .spawn(move || {
+                    loop {
+                        let job = receiver.lock().unwrap().recv();
+                        match job {
+                            Ok(job) => job(),
+                            Err(_) => break,
+                        }
+                    }
+                })
+                .expect("failed to spawn a blocking handler thread");
+        }
+        BlockingPool { sender }
+    }
+
+    // Run `f` on one of the threads; `None` if it panicked
+    pub(crate) async fn run<T, F>(&self, f: F) -> Option<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let (done, result) = oneshot::channel();
+        let job: Job = Box::new(move || {
+            let _ = done.send(catch_unwind(AssertUnwindSafe(f)));
+        });
+        self.sender.send(job).ok()?;
+        result.await.ok()?.ok()
+    }
+}
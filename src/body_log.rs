@@ -0,0 +1,405 @@
+//! Logging of request and response bodies while debugging, with secrets redacted.
+//!
+//! ```ignore
+//! Server::new()
+//!     .body_logging(BodyLog::new().redact("$.card.number").redact("$.items[*].coupon").max_body_size(2048))
+//!     .admin(Admin::new().bind("127.0.0.1:9090"));
+//! ```
+//!
+//! Nothing is logged until body logging is switched on, either from the start
+//! with `enabled(true)` or while the server runs, from the admin endpoint:
+//!
+//! ```text
+//! PUT /admin/body-log {"enabled": true, "routes": ["POST /orders", "/users/*"], "for_secs": 600}
+//! GET /admin/body-log
+//! ```
+//!
+//! `routes` limits it to requests matching one of the patterns: a path, or a
+//! prefix ending in `*`, optionally after a method. With `for_secs` it turns
+//! itself off again, so a forgotten session does not keep logging bodies.
+//!
+//! Each matching exchange is logged at `info` under the `ferrox::body_log`
+//! target, with the method, path, status, request id and both bodies. JSON bodies have
+//! the fields at the redacted JSON paths replaced with `[redacted]`: `$.a.b`,
+//! `$.a[0]`, `$.a[*].b` and `$..name` (`name` at any depth) are understood,
+//! and `password`, `token`, `secret` and `api_key` fields are redacted at any
+//! depth by default. Logged bodies are cut at `max_body_size` bytes (4 KiB by
+//! default); bodies of unknown length, such as streams, or over 1 MiB are not
+//! read and are logged as left out.
+
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use axum::body::{Body, HttpBody};
+use axum::extract::Request;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, Method};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::Router;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::context::AppState;
+use crate::logging::RequestId;
+
+const REDACTED: &str = "[redacted]";
+
+// Largest body read to be logged
+const MAX_READ: u64 = 1024 * 1024;
+
+/// Which bodies `Server::body_logging` logs, and how.
+///
+/// Clones share whether logging is on, so the one given to the server can be
+/// switched on and off from anywhere.
+#[derive(Clone)]
+pub struct BodyLog {
+    redactions: Arc<Vec<JsonPath>>,
+    max_body_size: usize,
+    activation: Arc<RwLock<Activation>>,
+}
+
+#[derive(Default)]
+struct Activation {
+    enabled: bool,
+    routes: Vec<RoutePattern>,
+    until: Option<Instant>,
+}
+
+/// Whether body logging is on, as reported by the admin endpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BodyLogStatus {
+    pub enabled: bool,
+    /// The patterns requests must match; every request when empty.
+    pub routes: Vec<String>,
+    /// Seconds until logging turns itself off, if it does.
+    pub expires_in_secs: Option<u64>,
+}
+
+impl Default for BodyLog {
+    fn default() -> Self {
+        let redactions = ["$..password", "$..token", "$..secret", "$..api_key"]
+            .into_iter()
+            .filter_map(|path| JsonPath::parse(path).ok())
+            .collect();
+        BodyLog {
+            redactions: Arc::new(redactions),
+            max_body_size: 4 * 1024,
+            activation: Arc::default(),
+        }
+    }
+}
+
+impl BodyLog {
+    /// Off, for every route, with the default redactions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also log the values at `path` as `[redacted]`, e.g. `$.user.ssn` or `$..cvv`.
+    ///
+    /// # Panics
+    ///
+    /// If `path` is not a JSON path this module understands.
+    pub fn redact(mut self, path: &str) -> Self {
+        let parsed = JsonPath::parse(path).unwrap_or_else(|err| panic!("invalid JSON path {:?}: {}", path, err));
+        Arc::make_mut(&mut self.redactions).push(parsed);
+        self
+    }
+
+    /// Cut logged bodies at `bytes` (4 KiB by default).
+    pub fn max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = bytes;
+        self
+    }
+
+    /// Log from the start, for the routes given to `routes`.
+    pub fn enabled(self, enabled: bool) -> Self {
+        self.write().enabled = enabled;
+        self
+    }
+
+    /// Only log requests matching one of `patterns`, e.g. `POST /orders` or `/users/*`.
+    pub fn routes<I, T>(self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        self.write().routes = patterns.into_iter().map(|pattern| RoutePattern::parse(pattern.as_ref())).collect();
+        self
+    }
+
+    /// Start logging the requests matching `patterns` (all if empty), for at most `duration`.
+    pub fn enable<I, T>(&self, patterns: I, duration: Option<Duration>)
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        let mut activation = self.write();
+        activation.enabled = true;
+        activation.routes = patterns.into_iter().map(|pattern| RoutePattern::parse(pattern.as_ref())).collect();
+        activation.until = duration.map(|duration| Instant::now() + duration);
+    }
+
+    pub fn disable(&self) {
+        *self.write() = Activation::default();
+    }
+
+    pub fn status(&self) -> BodyLogStatus {
+        let activation = self.activation.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = Instant::now();
+        let enabled = activation.enabled && activation.until.is_none_or(|until| until > now);
+        BodyLogStatus {
+            enabled,
+            routes: if enabled { activation.routes.iter().map(RoutePattern::to_string).collect() } else { Vec::new() },
+            expires_in_secs: activation.until.filter(|_| enabled).map(|until| (until - now).as_secs()),
+        }
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, Activation> {
+        self.activation.write().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // Whether to log the exchange of `request`
+    fn logs(&self, request: &Request) -> bool {
+        let activation = self.activation.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        activation.enabled
+            && activation.until.is_none_or(|until| until > Instant::now())
+            && (activation.routes.is_empty() || activation.routes.iter().any(|pattern| pattern.matches(request)))
+    }
+
+    // The body as logged, and the body to pass on
+    async fn body(&self, headers: &HeaderMap, body: Body) -> (String, Body) {
+        let Some(size) = body.size_hint().exact().filter(|size| *size <= MAX_READ) else {
+            return ("[body not logged: unknown length or over 1 MiB]".to_string(), body);
+        };
+        let bytes = match axum::body::to_bytes(body, size as usize).await {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                tracing::warn!("Failed to read a body to log: {}", err);
+                return ("[body not logged: unreadable]".to_string(), Body::empty());
+            }
+        };
+        let is_json = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("json"));
+        let mut text = match serde_json::from_slice::<Value>(&bytes) {
+            Ok(mut value) if is_json => {
+                for path in self.redactions.iter() {
+                    path.redact(&mut value);
+                }
+                value.to_string()
+            }
+            _ => String::from_utf8_lossy(&bytes).into_owned(),
+        };
+        if text.len() > self.max_body_size {
+            let mut end = self.max_body_size;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            text = format!("{}… [{} bytes]", &text[..end], text.len());
+        }
+        (text, Body::from(bytes))
+    }
+}
+
+/// A change made through `PUT /admin/body-log`.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct BodyLogChange {
+    enabled: bool,
+    #[serde(default)]
+    routes: Vec<String>,
+    for_secs: Option<u64>,
+}
+
+impl BodyLogChange {
+    pub(crate) fn apply(self, log: &BodyLog) -> BodyLogStatus {
+        match self.enabled {
+            true => log.enable(self.routes, self.for_secs.map(Duration::from_secs)),
+            false => log.disable(),
+        }
+        log.status()
+    }
+}
+
+// `POST /orders`, `/users/*` or `GET /users/*`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RoutePattern {
+    method: Option<Method>,
+    path: String,
+}
+
+impl RoutePattern {
+    fn parse(pattern: &str) -> Self {
+        let pattern = pattern.trim();
+        match pattern.split_once(' ') {
+            Some((method, path)) => RoutePattern {
+                method: method.to_ascii_uppercase().parse().ok(),
+                path: path.trim().to_string(),
+            },
+            None => RoutePattern {
+                method: None,
+                path: pattern.to_string(),
+            },
+        }
+    }
+
+    fn matches(&self, request: &Request) -> bool {
+        let path = request.uri().path();
+        let path_matches = match self.path.strip_suffix('*') {
+            Some(prefix) => path.starts_with(prefix),
+            None => path == self.path,
+        };
+        path_matches && self.method.as_ref().is_none_or(|method| method == request.method())
+    }
+}
+
+impl std::fmt::Display for RoutePattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.method {
+            Some(method) => write!(f, "{} {}", method, self.path),
+            None => f.write_str(&self.path),
+        }
+    }
+}
+
+// The JSON paths redactions understand
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct JsonPath(Vec<Step>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Field(String),
+    Index(usize),
+    // `[*]` or `.*`
+    Any,
+    // `..name`
+    Descendant(String),
+}
+
+impl JsonPath {
+    fn parse(path: &str) -> Result<Self, String> {
+        let mut rest = path.strip_prefix('$').ok_or("expected a path starting with `$`")?;
+        let mut steps = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix("..") {
+                let (name, next) = split_name(after);
+                if name.is_empty() || name == "*" {
+                    return Err("expected a field name after `..`".to_string());
+                }
+                steps.push(Step::Descendant(name.to_string()));
+                rest = next;
+            } else if let Some(after) = rest.strip_prefix('.') {
+                let (name, next) = split_name(after);
+                steps.push(match name {
+                    "" => return Err("expected a field name after `.`".to_string()),
+                    "*" => Step::Any,
+                    name => Step::Field(name.to_string()),
+                });
+                rest = next;
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let (inside, next) = after.split_once(']').ok_or("unclosed `[`")?;
+                let inside = inside.trim();
+                steps.push(if inside == "*" {
+                    Step::Any
+                } else if let Some(name) = inside.strip_prefix('\'').and_then(|name| name.strip_suffix('\'')) {
+                    Step::Field(name.to_string())
+                } else {
+                    Step::Index(inside.parse().map_err(|_| format!("expected an index, `*` or a quoted name in `[{}]`", inside))?)
+                });
+                rest = next;
+            } else {
+                return Err(format!("unexpected `{}`", rest));
+            }
+        }
+        if steps.is_empty() {
+            return Err("the whole body cannot be redacted".to_string());
+        }
+        Ok(JsonPath(steps))
+    }
+
+    fn redact(&self, value: &mut Value) {
+        redact_steps(value, &self.0);
+    }
+}
+
+// A field name, up to the next `.` or `[`, and what follows it
+fn split_name(path: &str) -> (&str, &str) {
+    let end = path.find(['.', '[']).unwrap_or(path.len());
+    path.split_at(end)
+}
+
+fn redact_steps(value: &mut Value, steps: &[Step]) {
+    let Some((step, rest)) = steps.split_first() else {
+        *value = Value::from(REDACTED);
+        return;
+    };
+    match (step, value) {
+        (Step::Field(name), Value::Object(object)) => {
+            if let Some(field) = object.get_mut(name) {
+                redact_steps(field, rest);
+            }
+        }
+        (Step::Index(index), Value::Array(items)) => {
+            if let Some(item) = items.get_mut(*index) {
+                redact_steps(item, rest);
+            }
+        }
+        (Step::Any, Value::Object(object)) => object.values_mut().for_each(|field| redact_steps(field, rest)),
+        (Step::Any, Value::Array(items)) => items.iter_mut().for_each(|item| redact_steps(item, rest)),
+        (Step::Descendant(name), value) => redact_descendants(value, name, rest),
+        _ => {}
+    }
+}
+
+// Apply `rest` to every field named `name` under `value`
+fn redact_descendants(value: &mut Value, name: &str, rest: &[Step]) {
+    match value {
+        Value::Object(object) => {
+            for (key, field) in object.iter_mut() {
+                if key == name {
+                    redact_steps(field, rest);
+                } else {
+                    redact_descendants(field, name, rest);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| redact_descendants(item, name, rest)),
+        _ => {}
+    }
+}
+
+// Log the bodies of the exchanges through `router` that `log` says to
+pub(crate) fn layer(router: Router<AppState>, log: BodyLog) -> Router<AppState> {
+    router.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let log = log.clone();
+        async move {
+            if !log.logs(&request) {
+                return next.run(request).await;
+            }
+            let (method, path) = (request.method().clone(), request.uri().path().to_string());
+            let request_id = request.extensions().get::<RequestId>().map(|RequestId(id)| id.clone());
+            let (parts, body) = request.into_parts();
+            // Read by the handler once it is sent, after the client's wait for 100 Continue
+            let (request_body, passed) = match crate::expect::awaits_continue(&parts.headers) {
+                true => ("[body not logged: sent after 100 Continue]".to_string(), body),
+                false => log.body(&parts.headers, body).await,
+            };
+            let response = next.run(Request::from_parts(parts, passed)).await;
+            let (parts, body) = response.into_parts();
+            let (response_body, passed) = log.body(&parts.headers, body).await;
+            tracing::info!(
+                target: "ferrox::body_log",
+                method = %method,
+                path = %path,
+                status = parts.status.as_u16(),
+                request_id = request_id.as_deref(),
+                request_body = %request_body,
+                response_body = %response_body,
+                "Request and response bodies"
+            );
+            Response::from_parts(parts, passed)
+        }
+    }))
+}
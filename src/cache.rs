@@ -0,0 +1,321 @@
+//! In-process caching of GET responses, for routes registered with `cache = "..."`.
+//!
+//! ```ignore
+//! #[http_method(GET, "/users", cache = "30s")]
+//! async fn list_users(db: State<Db>) -> Value { ... }
+//!
+//! #[http_method(POST, "/users")]
+//! async fn create_user(body: NewUser, db: State<Db>) -> Value {
+//!     let user = db.insert(body).await;
+//!     ferrox::cache::invalidate("/users");
+//!     user
+//! }
+//! ```
+//!
+//! Successful responses are stored by path and query string and served from
+//! memory until the route's TTL runs out; the handler only runs again after
+//! that. Each server keeps the most recently used entries, up to
+//! `Server::cache_capacity` (1000 by default). Cached responses are shared by
+//! every client, so cached routes cannot use `auth`; other layers such as rate
+//! limits still apply to cache hits.
+//!
+//! `Server::cache_store` keeps responses in a [`CacheStore`] instead, such as
+//! `ferrox::redis::RedisCache`, shared by every instance of the service.
+//! `invalidate` and `clear` then reach the store in the background; await the
+//! store's own `invalidate` where the next request must not see the old
+//! response. A store that fails is logged and the handler runs.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::extract::Request;
+use axum::http::header::ACCEPT;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::routing::MethodRouter;
+
+use serde::{Deserialize, Serialize};
+
+use crate::context::AppState;
+use crate::error_response;
+use crate::format;
+use crate::session::StoreFuture;
+
+pub(crate) const DEFAULT_CAPACITY: usize = 1000;
+
+// Every server's cache, so `invalidate` reaches them all
+static CACHES: Mutex<Vec<Weak<Mutex<Entries>>>> = Mutex::new(Vec::new());
+static STORES: Mutex<Vec<Weak<dyn CacheStore>>> = Mutex::new(Vec::new());
+
+/// Drop the cached responses for `path`, whatever their query string, in every server.
+pub fn invalidate(path: &str) {
+    for_each_cache(|entries| entries.remove_path(path));
+    let path = path.to_string();
+    for_each_store(move |store| {
+        let path = path.clone();
+        async move { store.invalidate(&path).await }
+    });
+}
+
+/// Drop every cached response in every server.
+pub fn clear() {
+    for_each_cache(|entries| {
+        entries.by_key.clear();
+        entries.by_use.clear();
+    });
+    for_each_store(|store| async move { store.clear().await });
+}
+
+fn for_each_store<F, Fut>(f: F)
+where
+    F: Fn(Arc<dyn CacheStore>) -> Fut,
+    Fut: std::future::Future<Output = Result<(), crate::error::FerroxError>> + Send + 'static,
+{
+    let mut stores = STORES.lock().unwrap();
+    stores.retain(|store| store.strong_count() > 0);
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    for store in stores.iter().filter_map(Weak::upgrade) {
+        let work = f(store);
+        runtime.spawn(async move {
+            if let Err(err) = work.await {
+                tracing::error!("Failed to invalidate cached responses: {}", err);
+            }
+        });
+    }
+}
+
+fn for_each_cache(mut f: impl FnMut(&mut Entries)) {
+    let mut caches = CACHES.lock().unwrap();
+    caches.retain(|cache| match cache.upgrade() {
+        Some(cache) => {
+            f(&mut cache.lock().unwrap());
+            true
+        }
+        None => false,
+    });
+}
+
+/// Where `Server::cache_store` keeps cached responses.
+pub trait CacheStore: Send + Sync + 'static {
+    /// The response kept under `key`, if any.
+    fn get<'a>(&'a self, key: &'a CacheKey) -> StoreFuture<'a, Option<CachedResponse>>;
+
+    /// Keep `response` under `key` for `ttl`.
+    fn set<'a>(&'a self, key: &'a CacheKey, response: &'a CachedResponse, ttl: Duration) -> StoreFuture<'a, ()>;
+
+    /// Drop the responses for `path`, whatever their query string.
+    fn invalidate<'a>(&'a self, path: &'a str) -> StoreFuture<'a, ()>;
+
+    /// Drop every response.
+    fn clear(&self) -> StoreFuture<'_, ()>;
+}
+
+/// What a cached response is stored under.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub path: String,
+    pub query: String,
+    /// The request's `Accept` header when other formats than JSON are enabled, or else empty.
+    pub accept: String,
+}
+
+/// A response kept by a `CacheStore`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub headers: Vec<(String, Vec<u8>)>,
+    pub body: Vec<u8>,
+}
+
+impl CachedResponse {
+    fn new(headers: &HeaderMap, body: &Bytes) -> Self {
+        CachedResponse {
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+                .collect(),
+            body: body.to_vec(),
+        }
+    }
+
+    fn into_response(self) -> Response {
+        let mut response = Response::new(Body::from(self.body));
+        for (name, value) in self.headers {
+            if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+                response.headers_mut().append(name, value);
+            }
+        }
+        response
+    }
+}
+
+// The cache of one server, shared by its cached routes
+#[derive(Clone)]
+pub(crate) enum ResponseCache {
+    Memory(Arc<Mutex<Entries>>),
+    Store(Arc<dyn CacheStore>),
+}
+
+pub(crate) struct Entries {
+    capacity: usize,
+    // Bumped on every use, to find the least recently used entry
+    tick: u64,
+    by_key: HashMap<CacheKey, Entry>,
+    by_use: BTreeMap<u64, CacheKey>,
+}
+
+struct Entry {
+    expires: Instant,
+    used: u64,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl ResponseCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let entries = Arc::new(Mutex::new(Entries {
+            capacity: capacity.max(1),
+            tick: 0,
+            by_key: HashMap::new(),
+            by_use: BTreeMap::new(),
+        }));
+        CACHES.lock().unwrap().push(Arc::downgrade(&entries));
+        ResponseCache::Memory(entries)
+    }
+
+    pub(crate) fn store(store: Arc<dyn CacheStore>) -> Self {
+        STORES.lock().unwrap().push(Arc::downgrade(&store));
+        ResponseCache::Store(store)
+    }
+
+    async fn get(&self, key: &CacheKey) -> Option<Response> {
+        match self {
+            ResponseCache::Memory(entries) => memory_get(entries, key),
+            ResponseCache::Store(store) => match store.get(key).await {
+                Ok(response) => response.map(CachedResponse::into_response),
+                Err(err) => {
+                    tracing::error!("Failed to read the response cache: {}", err);
+                    None
+                }
+            },
+        }
+    }
+
+    async fn insert(&self, key: CacheKey, ttl: Duration, headers: HeaderMap, body: Bytes) {
+        match self {
+            ResponseCache::Memory(entries) => memory_insert(entries, key, ttl, headers, body),
+            ResponseCache::Store(store) => {
+                if let Err(err) = store.set(&key, &CachedResponse::new(&headers, &body), ttl).await {
+                    tracing::error!("Failed to write the response cache: {}", err);
+                }
+            }
+        }
+    }
+}
+
+fn memory_get(entries: &Mutex<Entries>, key: &CacheKey) -> Option<Response> {
+    let mut entries = entries.lock().unwrap();
+    let now = Instant::now();
+    let entries = &mut *entries;
+    let entry = entries.by_key.get_mut(key)?;
+    if entry.expires <= now {
+        let used = entry.used;
+        entries.by_key.remove(key);
+        entries.by_use.remove(&used);
+        return None;
+    }
+    entries.tick += 1;
+    entries.by_use.remove(&entry.used);
+    entry.used = entries.tick;
+    entries.by_use.insert(entry.used, key.clone());
+
+    let mut response = Response::new(Body::from(entry.body.clone()));
+    *response.headers_mut() = entry.headers.clone();
+    Some(response)
+}
+
+fn memory_insert(entries: &Mutex<Entries>, key: CacheKey, ttl: Duration, headers: HeaderMap, body: Bytes) {
+    let mut entries = entries.lock().unwrap();
+    entries.tick += 1;
+    let used = entries.tick;
+    if let Some(previous) = entries.by_key.insert(
+        key.clone(),
+        Entry {
+            expires: Instant::now() + ttl,
+            used,
+            headers,
+            body,
+        },
+    ) {
+        entries.by_use.remove(&previous.used);
+    }
+    entries.by_use.insert(used, key);
+    while entries.by_key.len() > entries.capacity {
+        let Some((_, oldest)) = entries.by_use.pop_first() else {
+            break;
+        };
+        entries.by_key.remove(&oldest);
+    }
+}
+
+impl Entries {
+    fn remove_path(&mut self, path: &str) {
+        let by_use = &mut self.by_use;
+        self.by_key.retain(|key, entry| {
+            let keep = key.path != path;
+            if !keep {
+                by_use.remove(&entry.used);
+            }
+            keep
+        });
+    }
+}
+
+// Serve `route`'s GET responses from `cache` for `ttl`
+pub(crate) fn cache_route(route: MethodRouter<AppState>, cache: ResponseCache, ttl: Duration) -> MethodRouter<AppState> {
+    route.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let cache = cache.clone();
+        async move { cached(cache, ttl, request, next).await }
+    }))
+}
+
+async fn cached(cache: ResponseCache, ttl: Duration, request: Request, next: Next) -> Response {
+    // HEAD requests run the GET handler, but their bodies are dropped
+    if request.method() != Method::GET {
+        return next.run(request).await;
+    }
+    let accept = if format::NEGOTIATED {
+        request
+            .headers()
+            .get(ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    } else {
+        String::new()
+    };
+    let key = CacheKey {
+        path: request.uri().path().to_string(),
+        query: request.uri().query().unwrap_or_default().to_string(),
+        accept,
+    };
+    if let Some(response) = cache.get(&key).await {
+        return response;
+    }
+
+    let response = next.run(request).await;
+    // Only complete bodies of successful responses are kept
+    if response.status() != StatusCode::OK || response.body().size_hint().exact().is_none() {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read response".to_string());
+    };
+    cache.insert(key, ttl, parts.headers.clone(), bytes.clone()).await;
+    Response::from_parts(parts, Body::from(bytes))
+}
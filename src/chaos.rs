@@ -0,0 +1,184 @@
+//! Fault injection for resilience testing: added latency, failed responses and
+//! dropped connections on chosen routes.
+//!
+//! ```ignore
+//! Server::new().chaos(
+//!     Chaos::new()
+//!         .enabled(std::env::var("CHAOS").is_ok())
+//!         .inject(Fault::latency(Duration::from_millis(100)..Duration::from_millis(800)).on("/orders/*"))
+//!         .inject(Fault::error(StatusCode::SERVICE_UNAVAILABLE).on("/orders/*").probability(0.1))
+//!         .inject(Fault::drop().on("/payments").method(Method::POST).probability(0.05)),
+//! );
+//! ```
+//!
+//! Nothing is injected unless the `Chaos` is `enabled`, so the faults can stay
+//! configured in production code and be switched on per environment; the
+//! server warns at startup while they are on. Each request draws against every
+//! matching fault in order: latency delays the request and goes on to the
+//! next, an error answers the status at once, with `X-Chaos-Fault: error`, and
+//! a drop closes the connection without a complete response, as a crashed
+//! upstream would. Injected faults are logged, counted in metrics and seen by
+//! the access log; health probes are never affected.
+
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use futures_util::stream;
+use rand::Rng;
+
+use crate::context::AppState;
+use crate::error::FerroxError;
+
+/// The faults to inject; register them with `Server::chaos`.
+#[derive(Debug, Clone, Default)]
+pub struct Chaos {
+    enabled: bool,
+    faults: Vec<Fault>,
+}
+
+impl Chaos {
+    /// No faults, and disabled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inject the faults (off by default).
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Add `fault`, drawn after the ones added before it.
+    pub fn inject(mut self, fault: Fault) -> Self {
+        self.faults.push(fault);
+        self
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled && !self.faults.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Latency(Range<Duration>),
+    Error(StatusCode),
+    Drop,
+}
+
+/// One fault, injected into a share of the matching requests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fault {
+    kind: Kind,
+    paths: Vec<String>,
+    methods: Vec<Method>,
+    probability: f64,
+}
+
+impl Fault {
+    fn new(kind: Kind) -> Self {
+        Fault {
+            kind,
+            paths: Vec::new(),
+            methods: Vec::new(),
+            probability: 1.0,
+        }
+    }
+
+    /// Delay requests by a random duration in `range`.
+    pub fn latency(range: Range<Duration>) -> Self {
+        Self::new(Kind::Latency(range))
+    }
+
+    /// Answer with `status` instead of running the request.
+    pub fn error(status: StatusCode) -> Self {
+        Self::new(Kind::Error(status))
+    }
+
+    /// Close the connection instead of answering.
+    pub fn drop() -> Self {
+        Self::new(Kind::Drop)
+    }
+
+    /// Only for requests to `path`, or under it when it ends in `*`; every path by default.
+    pub fn on(mut self, path: &str) -> Self {
+        self.paths.push(path.to_string());
+        self
+    }
+
+    /// Only for `method` requests; every method by default.
+    pub fn method(mut self, method: Method) -> Self {
+        self.methods.push(method);
+        self
+    }
+
+    /// Share of the matching requests to inject the fault into, from 0 to 1 (1 by default).
+    pub fn probability(mut self, probability: f64) -> Self {
+        self.probability = probability.clamp(0.0, 1.0);
+        self
+    }
+
+    fn matches(&self, request: &Request) -> bool {
+        let path = request.uri().path();
+        let path_matches = self.paths.is_empty()
+            || self.paths.iter().any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => path.starts_with(prefix),
+                None => path == pattern,
+            });
+        path_matches && (self.methods.is_empty() || self.methods.contains(request.method()))
+    }
+}
+
+// Inject `chaos`'s faults into requests to `router`
+pub(crate) fn layer(router: Router<AppState>, chaos: Chaos) -> Router<AppState> {
+    tracing::warn!("Chaos fault injection is enabled");
+    let faults = Arc::new(chaos.faults);
+    router.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let faults = faults.clone();
+        async move {
+            let matching: Vec<&Fault> = faults.iter().filter(|fault| fault.matches(&request)).collect();
+            let (method, path) = (request.method().clone(), request.uri().path().to_string());
+            for fault in matching {
+                if !rand::thread_rng().gen_bool(fault.probability) {
+                    continue;
+                }
+                match &fault.kind {
+                    Kind::Latency(range) => {
+                        let delay = match range.is_empty() {
+                            true => range.start,
+                            false => rand::thread_rng().gen_range(range.clone()),
+                        };
+                        tracing::info!("Chaos: delaying {} {} by {:?}", method, path, delay);
+                        tokio::time::sleep(delay).await;
+                    }
+                    Kind::Error(status) => {
+                        tracing::info!("Chaos: answering {} {} with {}", method, path, status);
+                        let mut response = FerroxError::Status(*status, "Injected fault".to_string()).into_response();
+                        response.headers_mut().insert("x-chaos-fault", HeaderValue::from_static("error"));
+                        return response;
+                    }
+                    Kind::Drop => {
+                        tracing::info!("Chaos: dropping the connection of {} {}", method, path);
+                        return dropped();
+                    }
+                }
+            }
+            next.run(request).await
+        }
+    }))
+}
+
+// A response whose body fails at once, so the connection is closed before it completes
+fn dropped() -> Response {
+    let body = stream::once(async {
+        Err::<axum::body::Bytes, _>(std::io::Error::from(std::io::ErrorKind::ConnectionAborted))
+    });
+    (StatusCode::OK, Body::from_stream(body)).into_response()
+}
@@ -0,0 +1,301 @@
+//! An HTTP client for calls to other services, built on `reqwest`.
+//!
+//! ```ignore
+//! let client = Client::new().retry(RetryPolicy::exponential(3, Duration::from_millis(100)));
+//! Server::new().with_state(client);
+//!
+//! #[http_method(GET, "/orders/:id")]
+//! async fn order(id: u64, client: State<Client>) -> Result<Value, FerroxError> {
+//!     let customer = client.get(format!("http://customers/customers/{}", id)).send().await?;
+//!     Ok(customer.json().await?)
+//! }
+//! ```
+//!
+//! Requests made while handling a request carry its `X-Request-Id` when the
+//! access log is enabled, so one id follows a call through every service it
+//! reaches. They also continue its trace: with the `otel` feature and
+//! `Server::opentelemetry`, `traceparent` names the request's span as the
+//! parent; otherwise the request's own `traceparent` and `tracestate` are
+//! passed on unchanged.
+//!
+//! Requests with an idempotent method (`GET`, `HEAD`, `OPTIONS`, `PUT`,
+//! `DELETE`, `TRACE`) are retried under the client's retry policy after
+//! connection errors, timeouts and 429, 502, 503 or 504 answers; the last
+//! answer is returned once the attempts run out. Other requests are sent once
+//! unless marked `idempotent`, e.g. a `POST` carrying an `Idempotency-Key`.
+//! Streamed bodies cannot be resent and are never retried.
+//!
+//! Every attempt is counted in the `/metrics` output of
+//! `Server::enable_metrics` as `ferrox_http_client_requests_total`, by host,
+//! method and status (`error` when no answer came), and timed in
+//! `ferrox_http_client_request_duration_seconds` by host and method. `reqwest`
+//! errors convert to `FerroxError`, a 504 for timeouts and a 502 otherwise, so
+//! `?` answers for a failed dependency.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use reqwest::{IntoUrl, Response};
+use serde::Serialize;
+
+use crate::error::FerroxError;
+use crate::jobs::RetryPolicy;
+use crate::logging::{self, REQUEST_ID};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Answers worth another attempt: the server is overloaded or a proxy could not reach it
+const RETRY_STATUSES: [StatusCode; 4] = [
+    StatusCode::TOO_MANY_REQUESTS,
+    StatusCode::BAD_GATEWAY,
+    StatusCode::SERVICE_UNAVAILABLE,
+    StatusCode::GATEWAY_TIMEOUT,
+];
+
+/// Makes requests to other services; cheap to clone, clones share connections.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    retry: RetryPolicy,
+}
+
+impl Client {
+    /// A client with a 30s timeout per attempt that sends each request once.
+    pub fn new() -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(DEFAULT_TIMEOUT)
+            .user_agent(concat!("ferrox/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("HTTP client configuration is valid");
+        Client::from_reqwest(http)
+    }
+
+    /// Wrap a configured `reqwest::Client`, e.g. one with client certificates.
+    pub fn from_reqwest(http: reqwest::Client) -> Self {
+        Client {
+            http,
+            retry: RetryPolicy::none(),
+        }
+    }
+
+    /// How often and how soon retryable requests are tried again.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// A `GET` request.
+    pub fn get(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::GET, url)
+    }
+
+    /// A `POST` request.
+    pub fn post(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::POST, url)
+    }
+
+    /// A `PUT` request.
+    pub fn put(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::PUT, url)
+    }
+
+    /// A `PATCH` request.
+    pub fn patch(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::PATCH, url)
+    }
+
+    /// A `DELETE` request.
+    pub fn delete(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::DELETE, url)
+    }
+
+    /// A request with any method.
+    pub fn request(&self, method: Method, url: impl IntoUrl) -> RequestBuilder {
+        RequestBuilder {
+            idempotent: method.is_idempotent(),
+            inner: self.http.request(method, url),
+            retry: self.retry,
+        }
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Client::new()
+    }
+}
+
+/// A request being built by a [`Client`]; `send` it to get the response.
+#[derive(Debug)]
+pub struct RequestBuilder {
+    inner: reqwest::RequestBuilder,
+    retry: RetryPolicy,
+    idempotent: bool,
+}
+
+impl RequestBuilder {
+    /// Add a header.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.inner = self.inner.header(name, value);
+        self
+    }
+
+    /// Add several headers.
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
+        self.inner = self.inner.headers(headers);
+        self
+    }
+
+    /// Send `Authorization: Bearer <token>`.
+    pub fn bearer_auth(mut self, token: impl std::fmt::Display) -> Self {
+        self.inner = self.inner.bearer_auth(token);
+        self
+    }
+
+    /// Append `query` to the URL's query string.
+    pub fn query<T: Serialize + ?Sized>(mut self, query: &T) -> Self {
+        self.inner = self.inner.query(query);
+        self
+    }
+
+    /// Send `body` as JSON.
+    pub fn json<T: Serialize + ?Sized>(mut self, body: &T) -> Self {
+        self.inner = self.inner.json(body);
+        self
+    }
+
+    /// Send `body` as is.
+    pub fn body(mut self, body: impl Into<reqwest::Body>) -> Self {
+        self.inner = self.inner.body(body);
+        self
+    }
+
+    /// How long each attempt may take, instead of the client's timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.inner = self.inner.timeout(timeout);
+        self
+    }
+
+    /// The retry policy for this request, instead of the client's.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Whether sending the request twice is harmless, so it may be retried;
+    /// true by default for idempotent methods.
+    pub fn idempotent(mut self, idempotent: bool) -> Self {
+        self.idempotent = idempotent;
+        self
+    }
+
+    /// Send the request, retrying it as the retry policy allows. Answers of
+    /// any status are `Ok`; see `Response::error_for_status`.
+    pub async fn send(self) -> Result<Response, reqwest::Error> {
+        let (http, request) = self.inner.build_split();
+        let mut request = request?;
+        propagate(request.headers_mut());
+        let attempts = if self.idempotent { self.retry.attempts() } else { 1 };
+        let host = request.url().host_str().unwrap_or_default().to_string();
+        let mut attempt = 1;
+        loop {
+            // Keep a copy to send again, unless this is the last attempt or the body is streamed
+            let retry = if attempt < attempts { request.try_clone() } else { None };
+            let (method, url) = (request.method().clone(), request.url().clone());
+            let started = Instant::now();
+            let result = http.execute(request).await;
+            record(&host, &method, result.as_ref().ok().map(Response::status), started.elapsed());
+            let retryable = match &result {
+                Ok(response) => RETRY_STATUSES.contains(&response.status()),
+                Err(err) => err.is_connect() || err.is_timeout(),
+            };
+            match retry {
+                Some(copy) if retryable => request = copy,
+                _ => return result,
+            }
+            tracing::debug!(target: "ferrox::client", %method, %url, attempt, "retrying request");
+            tokio::time::sleep(self.retry.backoff(attempt)).await;
+            attempt += 1;
+        }
+    }
+}
+
+// Add the current request's id and trace context to an outgoing request
+fn propagate(headers: &mut HeaderMap) {
+    let propagated = logging::propagated();
+    let request_id = propagated.as_ref().and_then(|propagated| HeaderValue::from_str(&propagated.request_id).ok());
+    if let Some(request_id) = request_id {
+        headers.entry(REQUEST_ID).or_insert(request_id);
+    }
+    #[cfg(feature = "otel")]
+    if crate::otel::inject(headers) {
+        return;
+    }
+    for (name, value) in propagated.into_iter().flat_map(|propagated| propagated.trace) {
+        headers.entry(name).or_insert(value);
+    }
+}
+
+#[derive(Default)]
+struct Outbound {
+    // Attempts by status, or `None` when no answer came
+    statuses: BTreeMap<Option<u16>, u64>,
+    sum: f64,
+    count: u64,
+}
+
+// Attempts by host and method, for every client in the process
+static OUTBOUND: Mutex<BTreeMap<(String, String), Outbound>> = Mutex::new(BTreeMap::new());
+
+fn record(host: &str, method: &Method, status: Option<StatusCode>, elapsed: Duration) {
+    let mut outbound = OUTBOUND.lock().unwrap();
+    let calls = outbound.entry((host.to_string(), method.to_string())).or_default();
+    *calls.statuses.entry(status.map(|status| status.as_u16())).or_default() += 1;
+    calls.sum += elapsed.as_secs_f64();
+    calls.count += 1;
+}
+
+// Append the outbound request metrics to a Prometheus text exposition
+pub(crate) fn render_metrics(out: &mut String) {
+    let outbound = OUTBOUND.lock().unwrap();
+    if outbound.is_empty() {
+        return;
+    }
+    let host = |host: &str| host.replace('\\', "\\\\").replace('"', "\\\"");
+    out.push_str("# HELP ferrox_http_client_requests_total Outgoing request attempts, by host, method and status.\n");
+    out.push_str("# TYPE ferrox_http_client_requests_total counter\n");
+    for ((name, method), calls) in outbound.iter() {
+        for (status, count) in &calls.statuses {
+            let status = status.map_or_else(|| "error".to_string(), |status| status.to_string());
+            let _ = writeln!(
+                out,
+                "ferrox_http_client_requests_total{{host=\"{}\",method=\"{}\",status=\"{}\"}} {}",
+                host(name),
+                method,
+                status,
+                count
+            );
+        }
+    }
+    out.push_str("# HELP ferrox_http_client_request_duration_seconds Outgoing request attempt latency.\n");
+    out.push_str("# TYPE ferrox_http_client_request_duration_seconds summary\n");
+    for ((name, method), calls) in outbound.iter() {
+        let labels = format!("host=\"{}\",method=\"{}\"", host(name), method);
+        let _ = writeln!(out, "ferrox_http_client_request_duration_seconds_sum{{{}}} {}", labels, calls.sum);
+        let _ = writeln!(out, "ferrox_http_client_request_duration_seconds_count{{{}}} {}", labels, calls.count);
+    }
+}
+
+impl From<reqwest::Error> for FerroxError {
+    fn from(err: reqwest::Error) -> Self {
+        tracing::warn!(target: "ferrox::client", "Outgoing request failed: {}", err);
+        if err.is_timeout() {
+            FerroxError::new(StatusCode::GATEWAY_TIMEOUT, "Upstream service timed out")
+        } else {
+            FerroxError::new(StatusCode::BAD_GATEWAY, "Upstream service request failed")
+        }
+    }
+}
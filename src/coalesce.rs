@@ -0,0 +1,116 @@
+// Request coalescing for `coalesce = true` routes: identical GET requests
+// arriving while the handler runs for one of them wait for its response instead
+// of running it again, so a burst of requests for an expensive resource, such as
+// the one a cache entry expiring lets through, costs one handler call.
+// Requests are identical when their path, query string and `VARY` headers
+// match. Streamed bodies are not shared: the requests waiting on one run the
+// handler themselves, as they do when the first request is cancelled.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::extract::Request;
+use axum::http::header::{ACCEPT, ACCEPT_LANGUAGE, AUTHORIZATION, COOKIE};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::routing::MethodRouter;
+use tokio::sync::broadcast;
+
+use crate::context::AppState;
+use crate::error_response;
+
+// The request headers responses may differ by; credentials keep one client's
+// response from reaching another
+const VARY: [HeaderName; 4] = [ACCEPT, ACCEPT_LANGUAGE, AUTHORIZATION, COOKIE];
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Key {
+    path_and_query: String,
+    headers: Vec<Option<HeaderValue>>,
+}
+
+// A response as it is sent to every request waiting for it
+type Shared = Arc<(StatusCode, HeaderMap, Bytes)>;
+
+type InFlight = Arc<Mutex<HashMap<Key, broadcast::Sender<Shared>>>>;
+
+// Run `route`'s handler once for identical GET requests arriving while it runs
+pub(crate) fn coalesce_route(route: MethodRouter<AppState>) -> MethodRouter<AppState> {
+    let in_flight = InFlight::default();
+    route.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        coalesced(in_flight.clone(), request, next)
+    }))
+}
+
+async fn coalesced(in_flight: InFlight, request: Request, next: Next) -> Response {
+    // HEAD requests run the GET handler, but their bodies are dropped
+    if request.method() != Method::GET {
+        return next.run(request).await;
+    }
+    let key = Key {
+        path_and_query: request.uri().path_and_query().map(|path| path.to_string()).unwrap_or_default(),
+        headers: VARY.iter().map(|name| request.headers().get(name).cloned()).collect(),
+    };
+    let waiting = {
+        let mut requests = in_flight.lock().unwrap();
+        match requests.get(&key) {
+            Some(sender) => Some(sender.subscribe()),
+            None => {
+                requests.insert(key.clone(), broadcast::channel(1).0);
+                None
+            }
+        }
+    };
+    if let Some(mut receiver) = waiting {
+        return match receiver.recv().await {
+            Ok(shared) => {
+                let (status, headers, body) = &*shared;
+                let mut response = Response::new(Body::from(body.clone()));
+                *response.status_mut() = *status;
+                *response.headers_mut() = headers.clone();
+                response
+            }
+            // The first request was cancelled, or its body is streamed
+            Err(_) => next.run(request).await,
+        };
+    }
+
+    let mut first = First { in_flight, key: Some(key) };
+    let response = next.run(request).await;
+    let sender = first.finish();
+    if response.body().size_hint().exact().is_none() {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read response".to_string());
+    };
+    if let Some(sender) = sender {
+        let _ = sender.send(Arc::new((parts.status, parts.headers.clone(), bytes.clone())));
+    }
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+// The request running the handler; requests arriving once it is done or
+// dropped run the handler again
+struct First {
+    in_flight: InFlight,
+    key: Option<Key>,
+}
+
+impl First {
+    // Stop taking waiting requests, returning the sender reaching those waiting
+    fn finish(&mut self) -> Option<broadcast::Sender<Shared>> {
+        let key = self.key.take()?;
+        self.in_flight.lock().unwrap().remove(&key)
+    }
+}
+
+impl Drop for First {
+    fn drop(&mut self) {
+        // Dropping the sender tells the waiting requests to run the handler themselves
+        self.finish();
+    }
+}
@@ -0,0 +1,458 @@
+//! Client code generated from the registered `#[http_method]` routes: a Rust
+//! client built on `reqwest` and a TypeScript client built on `fetch`, with one
+//! function per endpoint.
+//!
+//! ```ignore
+//! // src/bin/clients.rs, run with `cargo run --bin clients` after changing routes
+//! let config = ClientConfig::new("UsersApi");
+//! std::fs::write("clients/users_api.rs", codegen::rust_client(&config))?;
+//! std::fs::write("clients/users-api.ts", codegen::typescript_client(&config))?;
+//! ```
+//!
+//! Functions are named after the handlers and take the path placeholders, then
+//! `query` for routes with a typed query parameter and `body` for routes that take
+//! a body. Parameter types follow the handler signatures as the OpenAPI document
+//! does: primitives, `String`, `Option<T>` and `Vec<T>` map to their equivalents,
+//! while other Rust types become `impl Serialize` arguments in Rust and open object
+//! types named after the Rust type in TypeScript. Responses are deserialized into
+//! a type chosen by the caller (`R` in Rust, `T` in TypeScript), and non-2xx
+//! answers become errors carrying the status and the decoded body. WebSocket and
+//! SSE routes are left out.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::openapi::{accepts_body, generic_argument, is_optional, is_untyped, placeholders};
+use crate::routes::registered;
+use crate::{ParamSource, RouteKind, RouteRegistration};
+
+/// Settings for the generated clients.
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    name: String,
+    registry: Option<String>,
+}
+
+impl ClientConfig {
+    /// Clients named `name`: the Rust struct and the TypeScript class.
+    pub fn new(name: impl Into<String>) -> Self {
+        ClientConfig {
+            name: name.into(),
+            registry: None,
+        }
+    }
+
+    /// Call the routes of the named registry instead of the default one's,
+    /// see `Server::from_registry`.
+    pub fn registry(mut self, name: impl Into<String>) -> Self {
+        self.registry = Some(name.into());
+        self
+    }
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self::new("ApiClient")
+    }
+}
+
+// A route as the clients see it
+struct Endpoint {
+    method: String,
+    path: &'static str,
+    // snake_case, unique among the endpoints
+    function: String,
+    // Placeholder names with the declared type, if the handler took it on its own and typed
+    path_params: Vec<(&'static str, Option<&'static str>)>,
+    query: Option<&'static str>,
+    body: Option<&'static str>,
+}
+
+fn endpoints(config: &ClientConfig) -> Vec<Endpoint> {
+    let mut registrations: Vec<&RouteRegistration> = registered(config.registry.as_deref())
+        .filter(|registration| matches!(registration.handler, RouteKind::Http(_)))
+        .collect();
+    registrations.sort_by_key(|registration| (registration.path, registration.method));
+
+    let mut taken = BTreeSet::new();
+    registrations
+        .into_iter()
+        .map(|registration| {
+            let typed = |source: ParamSource| {
+                registration
+                    .params
+                    .iter()
+                    .find(|param| param.source == source)
+                    .map(|param| param.type_name)
+            };
+            let path_params = placeholders(registration.path)
+                .map(|name| {
+                    let declared = registration
+                        .params
+                        .iter()
+                        .find(|param| param.source == ParamSource::PathParam && param.name == name)
+                        .map(|param| param.type_name)
+                        .filter(|type_name| !is_untyped(type_name));
+                    (name, declared)
+                })
+                .collect();
+            let query = typed(ParamSource::Query).filter(|type_name| !is_untyped(type_name));
+            let body = typed(ParamSource::Body).filter(|type_name| !is_untyped(type_name) || accepts_body(registration.method));
+
+            // Handlers in different modules may share a name
+            let mut function = identifier(registration.handler_name);
+            let mut suffix = 2;
+            while !taken.insert(function.clone()) {
+                function = format!("{}_{}", identifier(registration.handler_name), suffix);
+                suffix += 1;
+            }
+            Endpoint {
+                method: registration.method.to_uppercase(),
+                path: registration.path,
+                function,
+                path_params,
+                query,
+                body,
+            }
+        })
+        .collect()
+}
+
+fn identifier(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+fn camel_case(name: &str) -> String {
+    let mut camel = String::new();
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = !camel.is_empty();
+        } else if upper {
+            camel.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            camel.push(c);
+        }
+    }
+    camel
+}
+
+// The last path segment of a type name, e.g. `CreateUser` for `models::CreateUser`
+fn base_name(type_name: &str) -> &str {
+    type_name.rsplit("::").next().unwrap_or(type_name)
+}
+
+const INTEGERS: &[&str] = &[
+    "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+];
+
+/// A Rust client for every registered route, as the source of one module.
+///
+/// The module depends on `reqwest` (with its `json` feature) and `serde`.
+pub fn rust_client(config: &ClientConfig) -> String {
+    let name = &config.name;
+    let mut out = String::new();
+    out.push_str(
+        "// Generated by ferrox::codegen from the registered routes; do not edit.\n\n\
+         #![allow(dead_code, clippy::all)]\n\n",
+    );
+    let _ = write!(
+        out,
+        r#"/// A request failed, or the server answered with a non-2xx status.
+#[derive(Debug)]
+pub enum ClientError {{
+    Http(reqwest::Error),
+    Status {{ status: u16, body: serde_json::Value }},
+}}
+
+impl std::fmt::Display for ClientError {{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {{
+        match self {{
+            ClientError::Http(err) => write!(f, "request failed: {{}}", err),
+            ClientError::Status {{ status, body }} => write!(f, "server answered {{}}: {{}}", status, body),
+        }}
+    }}
+}}
+
+impl std::error::Error for ClientError {{}}
+
+impl From<reqwest::Error> for ClientError {{
+    fn from(err: reqwest::Error) -> Self {{
+        ClientError::Http(err)
+    }}
+}}
+
+#[derive(Debug, Clone)]
+pub struct {name} {{
+    base_url: String,
+    http: reqwest::Client,
+}}
+
+impl {name} {{
+    /// A client for the server at `base_url`, e.g. `http://localhost:3000`.
+    pub fn new(base_url: impl Into<String>) -> Self {{
+        Self::with_client(base_url, reqwest::Client::new())
+    }}
+
+    /// A client sending its requests through `http`, e.g. one with default headers.
+    pub fn with_client(base_url: impl Into<String>, http: reqwest::Client) -> Self {{
+        {name} {{
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            http,
+        }}
+    }}
+"#
+    );
+
+    for endpoint in endpoints(config) {
+        let mut args = vec!["&self".to_string()];
+        let mut segments = Vec::new();
+        for (placeholder, declared) in &endpoint.path_params {
+            let arg = identifier(placeholder);
+            let ty = match declared.map(|type_name| base_name(type_name.trim())) {
+                Some(ty) if INTEGERS.contains(&ty) || matches!(ty, "f32" | "f64" | "bool" | "char") => ty.to_string(),
+                Some("String") | Some("&str") | None => "&str".to_string(),
+                Some(_) => "impl std::fmt::Display".to_string(),
+            };
+            args.push(format!("{}: {}", arg, ty));
+            let keep_slashes = endpoint.path.contains(&format!("*{}", placeholder));
+            segments.push(format!("encode(&{}.to_string(), {})", arg, keep_slashes));
+        }
+        if endpoint.query.is_some() {
+            args.push("query: &impl serde::Serialize".to_string());
+        }
+        if endpoint.body.is_some() {
+            args.push("body: &impl serde::Serialize".to_string());
+        }
+
+        let template: Vec<String> = endpoint
+            .path
+            .split('/')
+            .map(|segment| {
+                if segment.starts_with(':') || segment.starts_with('*') {
+                    "{}".to_string()
+                } else {
+                    segment.replace('{', "{{").replace('}', "}}")
+                }
+            })
+            .collect();
+        let method = match endpoint.method.as_str() {
+            "GET" | "POST" | "PUT" | "PATCH" | "DELETE" | "HEAD" | "OPTIONS" => {
+                format!("reqwest::Method::{}", endpoint.method)
+            }
+            other => format!("reqwest::Method::from_bytes(b\"{}\").expect(\"valid method\")", other),
+        };
+
+        let _ = write!(
+            out,
+            "\n    /// `{} {}`\n    pub async fn {}<R: serde::de::DeserializeOwned>({}) -> Result<R, ClientError> {{\n",
+            endpoint.method,
+            endpoint.path,
+            endpoint.function,
+            args.join(", ")
+        );
+        let mut format_args = vec![format!("\"{{}}{}\"", template.join("/")), "self.base_url".to_string()];
+        format_args.extend(segments);
+        let _ = writeln!(out, "        let url = format!({});", format_args.join(", "));
+        let _ = write!(out, "        let request = self.http.request({}, url)", method);
+        if endpoint.query.is_some() {
+            out.push_str(".query(query)");
+        }
+        if endpoint.body.is_some() {
+            out.push_str(".json(body)");
+        }
+        out.push_str(";\n        self.send(request).await\n    }\n");
+    }
+
+    out.push_str(
+        r#"
+    async fn send<R: serde::de::DeserializeOwned>(&self, request: reqwest::RequestBuilder) -> Result<R, ClientError> {
+        let response = request.send().await?;
+        let status = response.status();
+        let bytes = response.bytes().await?;
+        let body: serde_json::Value = if bytes.is_empty() {
+            serde_json::Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap_or_else(|_| serde_json::Value::String(String::from_utf8_lossy(&bytes).into_owned()))
+        };
+        if !status.is_success() {
+            return Err(ClientError::Status { status: status.as_u16(), body });
+        }
+        serde_json::from_value(body).map_err(|err| ClientError::Status { status: status.as_u16(), body: serde_json::Value::String(err.to_string()) })
+    }
+}
+
+// Percent-encode a path parameter; wildcard parameters keep their slashes
+fn encode(value: &str, keep_slashes: bool) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            b'/' if keep_slashes => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+"#,
+    );
+    out
+}
+
+// TypeScript for a Rust type name, collecting the object types it names
+fn typescript_type(type_name: &str, objects: &mut BTreeSet<String>) -> String {
+    let type_name = type_name.trim();
+    if let Some(inner) = generic_argument(type_name, "Option") {
+        return typescript_type(inner, objects);
+    }
+    if let Some(inner) = generic_argument(type_name, "Vec") {
+        let inner = typescript_type(inner, objects);
+        return if inner.contains(' ') { format!("Array<{}>", inner) } else { format!("{}[]", inner) };
+    }
+    match base_name(type_name) {
+        ty if INTEGERS.contains(&ty) || matches!(ty, "f32" | "f64") => "number".to_string(),
+        "bool" => "boolean".to_string(),
+        "String" | "&str" | "char" => "string".to_string(),
+        "Value" => "unknown".to_string(),
+        name if name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => {
+            objects.insert(name.to_string());
+            name.to_string()
+        }
+        // Generic types such as `HashMap<String, u32>`
+        _ => "Record<string, unknown>".to_string(),
+    }
+}
+
+/// A TypeScript client for every registered route, as the source of one module.
+///
+/// The module uses the global `fetch`, as browsers, Node 18+ and Deno provide.
+pub fn typescript_client(config: &ClientConfig) -> String {
+    let mut objects = BTreeSet::new();
+    let mut methods = String::new();
+    for endpoint in endpoints(config) {
+        let mut args = Vec::new();
+        let mut template = String::new();
+        for segment in endpoint.path.split('/').skip(1) {
+            template.push('/');
+            match segment.strip_prefix(':').or_else(|| segment.strip_prefix('*')) {
+                Some(placeholder) => {
+                    let declared = endpoint
+                        .path_params
+                        .iter()
+                        .find(|(name, _)| *name == placeholder)
+                        .and_then(|(_, declared)| *declared);
+                    let ty = match declared {
+                        Some(type_name) => typescript_type(type_name, &mut objects),
+                        None => "string".to_string(),
+                    };
+                    let arg = camel_case(&identifier(placeholder));
+                    args.push(format!("{}: {}", arg, ty));
+                    if segment.starts_with('*') {
+                        let _ = write!(
+                            template,
+                            "${{String({}).split(\"/\").map(encodeURIComponent).join(\"/\")}}",
+                            arg
+                        );
+                    } else {
+                        let _ = write!(template, "${{encodeURIComponent(String({}))}}", arg);
+                    }
+                }
+                None => template.push_str(&segment.replace('`', "\\`").replace("${", "\\${")),
+            }
+        }
+        if template.is_empty() {
+            template.push('/');
+        }
+        if let Some(type_name) = endpoint.query {
+            let optional = if is_optional(type_name) { "?" } else { "" };
+            args.push(format!("query{}: {}", optional, typescript_type(type_name, &mut objects)));
+        }
+        if let Some(type_name) = endpoint.body {
+            let optional = if is_optional(type_name) { "?" } else { "" };
+            args.push(format!("body{}: {}", optional, typescript_type(type_name, &mut objects)));
+        }
+        let query = if endpoint.query.is_some() { "query" } else { "undefined" };
+        let body = if endpoint.body.is_some() { "body" } else { "undefined" };
+        let _ = write!(
+            methods,
+            "\n  /** `{method} {path}` */\n  async {function}<T = unknown>({args}): Promise<T> {{\n    return this.request<T>(\"{method}\", `{template}`, {query}, {body});\n  }}\n",
+            method = endpoint.method,
+            path = endpoint.path,
+            function = camel_case(&endpoint.function),
+            args = args.join(", "),
+        );
+    }
+
+    let mut out = String::new();
+    out.push_str("// Generated by ferrox::codegen from the registered routes; do not edit.\n\n");
+    out.push_str(
+        r#"/** A request was answered with a non-2xx status. */
+export class ApiError extends Error {
+  constructor(public readonly status: number, public readonly body: unknown) {
+    super(`server answered ${status}`);
+  }
+}
+"#,
+    );
+    if !objects.is_empty() {
+        out.push_str("\n// Rust types taken by the handlers; their fields are not known here\n");
+        for object in &objects {
+            let _ = writeln!(out, "export type {} = Record<string, unknown>;", object);
+        }
+    }
+    let _ = write!(
+        out,
+        r#"
+export class {name} {{
+  /** A client for the server at `baseUrl`, e.g. `http://localhost:3000`; `init` is merged into every request. */
+  constructor(private readonly baseUrl: string, private readonly init: RequestInit = {{}}) {{
+    this.baseUrl = baseUrl.replace(/\/+$/, "");
+  }}
+{methods}
+  private async request<T>(method: string, path: string, query?: object, body?: unknown): Promise<T> {{
+    let url = this.baseUrl + path;
+    if (query !== undefined) {{
+      const params = new URLSearchParams();
+      for (const [key, value] of Object.entries(query)) {{
+        for (const item of Array.isArray(value) ? value : [value]) {{
+          if (item !== undefined && item !== null) params.append(key, String(item));
+        }}
+      }}
+      const search = params.toString();
+      if (search) url += "?" + search;
+    }}
+    const headers = new Headers(this.init.headers);
+    if (body !== undefined) headers.set("Content-Type", "application/json");
+    const response = await fetch(url, {{
+      ...this.init,
+      method,
+      headers,
+      body: body === undefined ? undefined : JSON.stringify(body),
+    }});
+    const text = await response.text();
+    let data: unknown = null;
+    if (text) {{
+      try {{
+        data = JSON.parse(text);
+      }} catch {{
+        data = text;
+      }}
+    }}
+    if (!response.ok) throw new ApiError(response.status, data);
+    return data as T;
+  }}
+}}
+"#,
+        name = config.name,
+    );
+    out
+}
@@ -0,0 +1,206 @@
+//! Compressed responses for clients that accept them, and compressed request bodies.
+//!
+//! ```ignore
+//! Server::new().compression(
+//!     CompressionConfig::new()
+//!         .min_size(4096)
+//!         .algorithms([Encoding::Zstd, Encoding::Gzip]),
+//! );
+//! ```
+//!
+//! Responses are compressed with the algorithm the client's `Accept-Encoding`
+//! ranks highest, ties going to the first in the configured order. Responses
+//! below the minimum size, images, gRPC and Server-Sent Events, and responses
+//! that already have a `Content-Encoding` (such as precompressed static files)
+//! are sent as they are. Request bodies with `Content-Encoding: gzip`, `br` or
+//! `zstd` are decompressed before handlers see them; other encodings answer 415.
+
+use axum::body::HttpBody;
+use axum::extract::Request;
+use axum::http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH};
+use axum::http::{HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::Router;
+use serde::Deserialize;
+use tower_http::compression::predicate::{NotForContentType, Predicate};
+use tower_http::compression::CompressionLayer;
+use tower_http::decompression::RequestDecompressionLayer;
+
+use crate::context::AppState;
+use crate::error_response;
+
+/// A content coding for response bodies; `gzip`, `br` or `zstd` in configuration files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Encoding {
+    #[serde(rename = "gzip")]
+    Gzip,
+    #[serde(rename = "br")]
+    Brotli,
+    #[serde(rename = "zstd")]
+    Zstd,
+}
+
+impl Encoding {
+    fn token(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+        }
+    }
+}
+
+/// How responses are compressed; pass it to `Server::compression`.
+///
+/// By default brotli, zstd and gzip are offered in that order for responses of
+/// 1 KiB or more, and compressed request bodies are accepted.
+#[derive(Debug, Clone)]
+pub struct CompressionConfig {
+    algorithms: Vec<Encoding>,
+    min_size: u64,
+    decompress_requests: bool,
+}
+
+impl CompressionConfig {
+    pub fn new() -> Self {
+        CompressionConfig {
+            algorithms: vec![Encoding::Brotli, Encoding::Zstd, Encoding::Gzip],
+            min_size: 1024,
+            decompress_requests: true,
+        }
+    }
+
+    /// The algorithms to offer, most preferred first; others are never used.
+    pub fn algorithms(mut self, algorithms: impl IntoIterator<Item = Encoding>) -> Self {
+        self.algorithms = algorithms.into_iter().collect();
+        self
+    }
+
+    /// Smallest response body, in bytes, worth compressing.
+    ///
+    /// Bodies of unknown length, such as streams, are always compressed.
+    pub fn min_size(mut self, bytes: u64) -> Self {
+        self.min_size = bytes;
+        self
+    }
+
+    /// Decompress request bodies sent with a `Content-Encoding` (on by default).
+    pub fn decompress_requests(mut self, enabled: bool) -> Self {
+        self.decompress_requests = enabled;
+        self
+    }
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Compress responses on `router` and, if enabled, decompress request bodies
+pub(crate) fn layer(mut router: Router<AppState>, config: CompressionConfig) -> Router<AppState> {
+    if config.decompress_requests {
+        router = router
+            .layer(RequestDecompressionLayer::new().no_deflate())
+            .layer(axum::middleware::from_fn(reject_unsupported_encoding));
+    }
+    let enabled = |encoding| config.algorithms.contains(&encoding);
+    let compression = CompressionLayer::new()
+        .no_deflate()
+        .gzip(enabled(Encoding::Gzip))
+        .br(enabled(Encoding::Brotli))
+        .zstd(enabled(Encoding::Zstd))
+        .compress_when(
+            MinSize(config.min_size)
+                .and(NotForContentType::GRPC)
+                .and(NotForContentType::IMAGES)
+                .and(NotForContentType::SSE),
+        );
+    let algorithms = config.algorithms;
+    router
+        .layer(compression)
+        .layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+            let algorithms = algorithms.clone();
+            async move { prefer(&algorithms, request, next).await }
+        }))
+}
+
+// Narrow `Accept-Encoding` to the one encoding chosen by the configured order,
+// since the compression layer breaks ties between equal q-values its own way
+async fn prefer(algorithms: &[Encoding], mut request: Request, next: Next) -> Response {
+    if let Some(accept) = request.headers().get(ACCEPT_ENCODING).and_then(|value| value.to_str().ok()) {
+        let chosen = choose(algorithms, accept).map_or("identity", Encoding::token);
+        request
+            .headers_mut()
+            .insert(ACCEPT_ENCODING, HeaderValue::from_static(chosen));
+    }
+    next.run(request).await
+}
+
+// The configured encoding with the highest q-value in `accept`, earliest on ties
+fn choose(algorithms: &[Encoding], accept: &str) -> Option<Encoding> {
+    let mut best: Option<(Encoding, f32)> = None;
+    for &encoding in algorithms {
+        let quality = accept_quality(accept, encoding.token());
+        if quality > 0.0 && best.is_none_or(|(_, current)| quality > current) {
+            best = Some((encoding, quality));
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
+// The q-value `accept` gives `token`, directly or through `*`
+fn accept_quality(accept: &str, token: &str) -> f32 {
+    let mut wildcard = 0.0;
+    for entry in accept.split(',') {
+        let mut parts = entry.split(';');
+        let name = parts.next().unwrap_or_default().trim();
+        let quality = parts
+            .find_map(|param| param.trim().strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if name.eq_ignore_ascii_case(token) {
+            return quality;
+        }
+        if name == "*" {
+            wildcard = quality;
+        }
+    }
+    wildcard
+}
+
+// Answer 415 with the error envelope for encodings the decompression layer
+// cannot undo, instead of its bare rejection
+async fn reject_unsupported_encoding(request: Request, next: Next) -> Response {
+    if let Some(encoding) = request.headers().get(CONTENT_ENCODING) {
+        let encoding = encoding.to_str().unwrap_or_default().trim().to_ascii_lowercase();
+        if !matches!(encoding.as_str(), "gzip" | "x-gzip" | "br" | "zstd" | "identity") {
+            return error_response(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!("Unsupported Content-Encoding {:?}", encoding),
+            );
+        }
+    }
+    next.run(request).await
+}
+
+// Compress bodies of at least the given size, or of unknown size
+#[derive(Clone, Copy)]
+struct MinSize(u64);
+
+impl Predicate for MinSize {
+    fn should_compress<B>(&self, response: &axum::http::Response<B>) -> bool
+    where
+        B: HttpBody,
+    {
+        let size = response.body().size_hint().exact().or_else(|| {
+            response
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok())
+        });
+        size.is_none_or(|size| size >= self.0)
+    }
+}
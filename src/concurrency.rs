@@ -0,0 +1,60 @@
+// In-flight request limits: `concurrency_limit = N` on a route, and
+// `Server::max_in_flight` across the whole server. Requests beyond a limit are
+// shed at once with 503 and `Retry-After` instead of queueing, so a slow or
+// expensive endpoint cannot pile up work until the service falls over. A
+// request holds its slot until the handler has returned its response.
+
+use std::sync::Arc;
+
+use axum::extract::Request;
+use axum::http::header::RETRY_AFTER;
+use axum::http::{HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::MethodRouter;
+use axum::Router;
+use tokio::sync::Semaphore;
+
+use crate::context::AppState;
+use crate::error::FerroxError;
+
+// Seconds a shed client is asked to wait before retrying
+const RETRY_AFTER_SECS: u32 = 1;
+
+// Run at most `limit` requests to one route at a time
+pub(crate) fn limit_route(route: MethodRouter<AppState>, limit: usize) -> MethodRouter<AppState> {
+    let slots = Arc::new(Semaphore::new(limit));
+    route.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        handle(slots.clone(), request, next)
+    }))
+}
+
+// Run at most `limit` requests to the router at a time, whatever their route
+pub(crate) fn limit_router(router: Router<AppState>, limit: usize) -> Router<AppState> {
+    let slots = Arc::new(Semaphore::new(limit));
+    router.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        handle(slots.clone(), request, next)
+    }))
+}
+
+async fn handle(slots: Arc<Semaphore>, request: Request, next: Next) -> Response {
+    match slots.try_acquire_owned() {
+        Ok(_slot) => next.run(request).await,
+        Err(_) => {
+            tracing::warn!(path = %request.uri().path(), "Shedding request: too many in flight");
+            overloaded()
+        }
+    }
+}
+
+fn overloaded() -> Response {
+    let mut response = FerroxError::Status(
+        StatusCode::SERVICE_UNAVAILABLE,
+        "Server is busy, try again later".to_string(),
+    )
+    .into_response();
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(RETRY_AFTER_SECS));
+    response
+}
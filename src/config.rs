@@ -0,0 +1,768 @@
+//! Operational settings from a file, overridden by `FERROX_*` environment variables.
+//!
+//! ```ignore
+//! let config = Config::load("ferrox.toml")?;
+//! Server::from_config(config).run().await?;
+//! ```
+//!
+//! Files are TOML (with the `toml` feature), YAML (with the `yaml` feature) or
+//! JSON, chosen by extension. Every setting is optional:
+//!
+//! ```toml
+//! addr = "0.0.0.0:8080"
+//! metrics_path = "/metrics"
+//!
+//! [tls]
+//! cert = "/etc/ferrox/cert.pem"
+//! key = "/etc/ferrox/key.pem"
+//!
+//! [timeouts]
+//! body_read = "30s"
+//! handler = "5s"
+//! shutdown = "30s"
+//!
+//! [log]
+//! level = "info,ferrox::access=warn"
+//! format = "json"
+//! access_log = true
+//!
+//! [limits]
+//! max_body_size = "10MB"
+//! rate_limit = "100/min"
+//! max_in_flight = 512
+//!
+//! [cors]
+//! allow_origins = ["https://app.example.com"]
+//! allow_methods = ["GET", "POST"]
+//! allow_headers = ["content-type", "authorization"]
+//! allow_credentials = true
+//! max_age = "1h"
+//!
+//! [compression]
+//! min_size = 1024
+//! algorithms = ["br", "gzip"]
+//!
+//! [flags]
+//! new-checkout = true
+//! ```
+//!
+//! Durations take an `ms`, `s`, `m` or `h` suffix, sizes are bytes or take a
+//! `KB`, `MB` or `GB` suffix (multiples of 1024), and `"*"` in a CORS list
+//! allows any value, except with `allow_credentials = true`. The environment
+//! variables are `FERROX_ADDR`, `FERROX_TLS_CERT`, `FERROX_TLS_KEY`,
+//! `FERROX_BODY_READ_TIMEOUT`, `FERROX_HANDLER_TIMEOUT`, `FERROX_SHUTDOWN_TIMEOUT`, `FERROX_LOG_LEVEL`,
+//! `FERROX_LOG_FORMAT`, `FERROX_ACCESS_LOG`, `FERROX_LOG_QUIET`, `FERROX_MAX_BODY_SIZE`,
+//! `FERROX_RATE_LIMIT`, `FERROX_MAX_IN_FLIGHT`, `FERROX_CORS_ALLOW_ORIGINS` (comma-separated),
+//! `FERROX_COMPRESSION` (`true` or `false`) and `FERROX_METRICS_PATH`.
+//!
+//! ## Reloading
+//!
+//! A [`ConfigWatcher`] given to `Server::watch_config` reloads the file when
+//! it changes, and on `SIGHUP`:
+//!
+//! ```ignore
+//! let watcher = ConfigWatcher::load("ferrox.toml")?;
+//! Server::from_config(watcher.config()).watch_config(watcher).run().await?;
+//! ```
+//!
+//! The log level, the rate limit, the CORS origins and the flags take their new
+//! values at once, together, and only if the whole file is valid. Every other
+//! setting, and adding or removing the `[limits]` rate limit or the `[cors]`
+//! and `[flags]` sections, needs a restart: such changes are left out and
+//! listed in the [`ReloadReport`], and logged as a warning.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use axum::http::{HeaderName, HeaderValue, Method};
+use serde::{Deserialize, Deserializer};
+use tokio::task::JoinHandle;
+use tracing_subscriber::EnvFilter;
+
+use crate::compression::{CompressionConfig, Encoding};
+use crate::cors::{CorsConfig, SharedOrigins};
+use crate::flags::{ConfigFlags, Flags};
+use crate::logging::LogFormat;
+use crate::ratelimit::{RateLimit, RateLimiter};
+use crate::Server;
+
+/// Settings for `Server::from_config`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Address `Server::run` binds, `127.0.0.1:3000` if unset.
+    pub addr: Option<String>,
+    /// Certificate files; `Server::run` serves HTTPS when set.
+    pub tls: Option<TlsFiles>,
+    pub timeouts: Timeouts,
+    pub log: LogSettings,
+    pub limits: Limits,
+    /// CORS is only enabled when this section is present.
+    pub cors: Option<CorsSettings>,
+    /// Compression is only enabled when this section is present.
+    pub compression: Option<CompressionSettings>,
+    /// Path of the Prometheus metrics endpoint; metrics are off if unset.
+    pub metrics_path: Option<String>,
+    /// Feature flags, registered as `Server::flags` when this section is present.
+    pub flags: Option<BTreeMap<String, bool>>,
+}
+
+/// PEM certificate chain and private key files.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Timeouts {
+    /// `Server::body_read_timeout`.
+    #[serde(deserialize_with = "optional_duration")]
+    pub body_read: Option<Duration>,
+    /// `Server::default_timeout`.
+    #[serde(deserialize_with = "optional_duration")]
+    pub handler: Option<Duration>,
+    /// `Server::shutdown_timeout`.
+    #[serde(deserialize_with = "optional_duration")]
+    pub shutdown: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogSettings {
+    /// `Server::log_level`.
+    pub level: Option<String>,
+    /// `Server::log_format`: `text` or `json`.
+    pub format: LogFormat,
+    /// `Server::access_log`.
+    pub access_log: bool,
+    /// `Server::quiet`.
+    pub quiet: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// `Server::max_body_size`.
+    #[serde(deserialize_with = "optional_size")]
+    pub max_body_size: Option<usize>,
+    /// `Server::rate_limit`, per client IP, e.g. `100/min`.
+    #[serde(deserialize_with = "optional_rate_limit")]
+    pub rate_limit: Option<RateLimit>,
+    /// `Server::max_in_flight`.
+    pub max_in_flight: Option<usize>,
+}
+
+/// The `CorsConfig` for `Server::cors`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsSettings {
+    pub allow_origins: Vec<String>,
+    pub allow_methods: Vec<String>,
+    pub allow_headers: Vec<String>,
+    pub expose_headers: Vec<String>,
+    pub allow_credentials: bool,
+    #[serde(deserialize_with = "optional_duration")]
+    pub max_age: Option<Duration>,
+}
+
+impl CorsSettings {
+    // `"*"` with credentials, which `Server::cors` refuses
+    fn check(&self) -> Result<(), String> {
+        let wildcards: Vec<&str> = [
+            ("cors.allow_origins", &self.allow_origins),
+            ("cors.allow_methods", &self.allow_methods),
+            ("cors.allow_headers", &self.allow_headers),
+        ]
+        .into_iter()
+        .filter(|(_, values)| values.iter().any(|value| value == "*"))
+        .map(|(name, _)| name)
+        .collect();
+        if !self.allow_credentials || wildcards.is_empty() {
+            return Ok(());
+        }
+        Err(format!("\"*\" in {} cannot be combined with cors.allow_credentials", wildcards.join(", ")))
+    }
+}
+
+/// The `CompressionConfig` for `Server::compression`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompressionSettings {
+    pub min_size: Option<u64>,
+    pub algorithms: Option<Vec<Encoding>>,
+}
+
+/// A configuration source that could not be read or parsed.
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, String),
+    /// The file's extension names no format this build supports.
+    UnsupportedFormat(PathBuf),
+    /// An environment variable holds an invalid value.
+    Env { var: String, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, err) => write!(f, "cannot read config file {}: {}", path.display(), err),
+            ConfigError::Parse(path, message) => write!(f, "invalid config file {}: {}", path.display(), message),
+            ConfigError::UnsupportedFormat(path) => write!(
+                f,
+                "unsupported config file {}: expected .json, .toml (`toml` feature) or .yaml (`yaml` feature)",
+                path.display()
+            ),
+            ConfigError::Env { var, message } => write!(f, "invalid {}: {}", var, message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Read(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Config {
+    /// Read `path`, then apply the `FERROX_*` environment variables.
+    pub fn load(path: impl AsRef<Path>) -> Result<Config, ConfigError> {
+        Config::from_file(path)?.with_env()
+    }
+
+    /// Settings from the `FERROX_*` environment variables alone.
+    pub fn from_env() -> Result<Config, ConfigError> {
+        Config::default().with_env()
+    }
+
+    /// Read `path` without looking at the environment.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Config, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|err| ConfigError::Read(path.to_path_buf(), err))?;
+        let parse_error = |message: String| ConfigError::Parse(path.to_path_buf(), message);
+        let config: Config = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => serde_json::from_str(&text).map_err(|err| parse_error(err.to_string()))?,
+            #[cfg(feature = "toml")]
+            Some("toml") => toml::from_str(&text).map_err(|err| parse_error(err.to_string()))?,
+            #[cfg(feature = "yaml")]
+            Some("yaml" | "yml") => serde_yaml::from_str(&text).map_err(|err| parse_error(err.to_string()))?,
+            _ => return Err(ConfigError::UnsupportedFormat(path.to_path_buf())),
+        };
+        if let Some(cors) = &config.cors {
+            cors.check().map_err(parse_error)?;
+        }
+        Ok(config)
+    }
+
+    /// Apply the `FERROX_*` environment variables over these settings.
+    pub fn with_env(self) -> Result<Config, ConfigError> {
+        self.with_vars(std::env::vars())
+    }
+
+    /// Apply `FERROX_*` variables from `vars` as if they were the environment.
+    pub fn with_vars<K, V>(mut self, vars: impl IntoIterator<Item = (K, V)>) -> Result<Config, ConfigError>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        for (var, value) in vars {
+            let (var, value) = (var.as_ref(), value.as_ref());
+            let invalid = |message: String| ConfigError::Env {
+                var: var.to_string(),
+                message,
+            };
+            match var {
+                "FERROX_ADDR" => self.addr = Some(value.to_string()),
+                "FERROX_TLS_CERT" => self.tls_files().cert = PathBuf::from(value),
+                "FERROX_TLS_KEY" => self.tls_files().key = PathBuf::from(value),
+                "FERROX_BODY_READ_TIMEOUT" => self.timeouts.body_read = Some(parse_duration(value).map_err(invalid)?),
+                "FERROX_HANDLER_TIMEOUT" => self.timeouts.handler = Some(parse_duration(value).map_err(invalid)?),
+                "FERROX_SHUTDOWN_TIMEOUT" => self.timeouts.shutdown = Some(parse_duration(value).map_err(invalid)?),
+                "FERROX_LOG_LEVEL" => self.log.level = Some(value.to_string()),
+                "FERROX_LOG_FORMAT" => {
+                    self.log.format = match value.to_ascii_lowercase().as_str() {
+                        "text" => LogFormat::Text,
+                        "json" => LogFormat::Json,
+                        _ => return Err(invalid(format!("expected `text` or `json`, got {:?}", value))),
+                    }
+                }
+                "FERROX_ACCESS_LOG" => self.log.access_log = parse_bool(value).map_err(invalid)?,
+                "FERROX_LOG_QUIET" => self.log.quiet = parse_bool(value).map_err(invalid)?,
+                "FERROX_MAX_BODY_SIZE" => self.limits.max_body_size = Some(parse_size(value).map_err(invalid)?),
+                "FERROX_RATE_LIMIT" => self.limits.rate_limit = Some(value.parse().map_err(invalid)?),
+                "FERROX_MAX_IN_FLIGHT" => {
+                    self.limits.max_in_flight = Some(
+                        value
+                            .trim()
+                            .parse()
+                            .map_err(|_| invalid(format!("expected a number of requests, got {:?}", value)))?,
+                    )
+                }
+                "FERROX_CORS_ALLOW_ORIGINS" => {
+                    let cors = self.cors.get_or_insert_with(CorsSettings::default);
+                    cors.allow_origins =
+                        value.split(',').map(str::trim).filter(|origin| !origin.is_empty()).map(String::from).collect();
+                    cors.check().map_err(invalid)?;
+                }
+                "FERROX_COMPRESSION" => {
+                    self.compression = match parse_bool(value).map_err(invalid)? {
+                        true => self.compression.or_else(|| Some(CompressionSettings::default())),
+                        false => None,
+                    }
+                }
+                "FERROX_METRICS_PATH" => self.metrics_path = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        if let Some(tls) = &self.tls
+            && (tls.cert.as_os_str().is_empty() || tls.key.as_os_str().is_empty())
+        {
+            return Err(ConfigError::Env {
+                var: "FERROX_TLS_CERT".to_string(),
+                message: "FERROX_TLS_CERT and FERROX_TLS_KEY must be set together".to_string(),
+            });
+        }
+        Ok(self)
+    }
+
+    // The TLS section, created empty for an environment variable to fill in
+    fn tls_files(&mut self) -> &mut TlsFiles {
+        self.tls.get_or_insert_with(|| TlsFiles {
+            cert: PathBuf::new(),
+            key: PathBuf::new(),
+        })
+    }
+}
+
+// "500ms", "5s", "2m" or "1h"
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let invalid = || format!("expected a duration like \"500ms\", \"5s\", \"2m\" or \"1h\", got {:?}", text);
+    let digits = text.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let amount: u64 = digits.trim().parse().map_err(|_| invalid())?;
+    match &text[digits.len()..] {
+        "ms" => Ok(Duration::from_millis(amount)),
+        "s" => Ok(Duration::from_secs(amount)),
+        "m" => amount.checked_mul(60).map(Duration::from_secs).ok_or_else(invalid),
+        "h" => amount.checked_mul(3600).map(Duration::from_secs).ok_or_else(invalid),
+        _ => Err(invalid()),
+    }
+}
+
+// "512", "64KB", "10MB" or "1GB", in multiples of 1024 like `max_body_size` on routes
+fn parse_size(text: &str) -> Result<usize, String> {
+    let invalid = || format!("expected a size like \"512\", \"64KB\", \"10MB\" or \"1GB\", got {:?}", text);
+    let digits = text.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let scale: usize = match text[digits.len()..].to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "KB" | "KIB" => 1 << 10,
+        "MB" | "MIB" => 1 << 20,
+        "GB" | "GIB" => 1 << 30,
+        _ => return Err(invalid()),
+    };
+    digits
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|amount| amount.checked_mul(scale))
+        .ok_or_else(invalid)
+}
+
+fn parse_bool(text: &str) -> Result<bool, String> {
+    match text.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Ok(true),
+        "false" | "0" | "no" | "off" => Ok(false),
+        _ => Err(format!("expected `true` or `false`, got {:?}", text)),
+    }
+}
+
+fn optional_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    parse_duration(&text).map(Some).map_err(serde::de::Error::custom)
+}
+
+fn optional_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<usize>, D::Error> {
+    // A plain number of bytes, or a string with a unit
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(usize),
+        Text(String),
+    }
+    match Size::deserialize(deserializer)? {
+        Size::Bytes(bytes) => Ok(Some(bytes)),
+        Size::Text(text) => parse_size(&text).map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+fn optional_rate_limit<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<RateLimit>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    text.parse().map(Some).map_err(serde::de::Error::custom)
+}
+
+// The `Server` builder calls equivalent to `config`
+pub(crate) fn apply(mut server: Server, config: Config) -> Server {
+    if let Some(timeout) = config.timeouts.body_read {
+        server = server.body_read_timeout(timeout);
+    }
+    if let Some(timeout) = config.timeouts.handler {
+        server = server.default_timeout(timeout);
+    }
+    if let Some(timeout) = config.timeouts.shutdown {
+        server = server.shutdown_timeout(timeout);
+    }
+    if let Some(level) = &config.log.level {
+        server = server.log_level(level);
+    }
+    server = server.log_format(config.log.format).access_log(config.log.access_log);
+    if config.log.quiet {
+        server = server.quiet();
+    }
+    if let Some(bytes) = config.limits.max_body_size {
+        server = server.max_body_size(bytes);
+    }
+    if let Some(rate) = config.limits.rate_limit {
+        server = server.rate_limit(RateLimiter::new(rate));
+    }
+    if let Some(limit) = config.limits.max_in_flight {
+        server = server.max_in_flight(limit);
+    }
+    if let Some(cors) = config.cors {
+        server = server.cors(cors_config(cors));
+    }
+    if let Some(settings) = config.compression {
+        let mut compression = CompressionConfig::new();
+        if let Some(bytes) = settings.min_size {
+            compression = compression.min_size(bytes);
+        }
+        if let Some(algorithms) = settings.algorithms {
+            compression = compression.algorithms(algorithms);
+        }
+        server = server.compression(compression);
+    }
+    if let Some(path) = &config.metrics_path {
+        server = server.metrics_path(path);
+    }
+    if let Some(values) = config.flags {
+        let flags = ConfigFlags::new(values);
+        server = server.flags(Flags::new().provider(flags.clone()));
+        server.config_flags = Some(flags);
+    }
+    server.bind_addr = config.addr;
+    server.tls_files = config.tls;
+    server
+}
+
+fn cors_config(settings: CorsSettings) -> CorsConfig {
+    let any = |values: &[String]| values.iter().any(|value| value == "*");
+    let mut cors = CorsConfig::new().allow_credentials(settings.allow_credentials);
+    if any(&settings.allow_origins) {
+        cors = cors.allow_any_origin();
+    } else {
+        for origin in &settings.allow_origins {
+            cors = cors.allow_origin(origin);
+        }
+    }
+    if any(&settings.allow_methods) {
+        cors = cors.allow_any_method();
+    } else {
+        cors = cors.allow_methods(parsed("method", &settings.allow_methods, |method| {
+            Method::from_bytes(method.to_ascii_uppercase().as_bytes()).ok()
+        }));
+    }
+    if any(&settings.allow_headers) {
+        cors = cors.allow_any_header();
+    } else {
+        cors = cors.allow_headers(parsed("header", &settings.allow_headers, header_name));
+    }
+    cors = cors.expose_headers(parsed("header", &settings.expose_headers, header_name));
+    if let Some(max_age) = settings.max_age {
+        cors = cors.max_age(max_age);
+    }
+    cors
+}
+
+fn header_name(name: &str) -> Option<HeaderName> {
+    HeaderName::from_bytes(name.as_bytes()).ok()
+}
+
+// Parse each value, logging and skipping invalid ones like `CorsConfig::allow_origin` does
+fn parsed<T>(kind: &str, values: &[String], parse: impl Fn(&str) -> Option<T>) -> Vec<T> {
+    values
+        .iter()
+        .filter_map(|value| {
+            let parsed = parse(value);
+            if parsed.is_none() {
+                tracing::warn!("Ignoring invalid CORS {} {:?}", kind, value);
+            }
+            parsed
+        })
+        .collect()
+}
+
+/// What a reload changed, and what it left for a restart.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReloadReport {
+    /// The settings now in effect with their new values, e.g. `log.level`.
+    pub applied: Vec<String>,
+    /// The settings changed in the file that keep their old values until the
+    /// server restarts, e.g. `addr` or `limits.max_body_size`.
+    pub needs_restart: Vec<String>,
+}
+
+/// Reloads a configuration file while the server runs; give it to
+/// `Server::watch_config`.
+///
+/// Clones share the file and the settings in effect, so one kept by the
+/// application can `reload` on its own schedule.
+#[derive(Clone)]
+pub struct ConfigWatcher {
+    path: Arc<PathBuf>,
+    interval: Duration,
+    watched: Arc<Mutex<Watched>>,
+}
+
+struct Watched {
+    // The settings in effect, which a reload compares the file against
+    config: Config,
+    modified: Option<SystemTime>,
+    targets: Targets,
+}
+
+// What a reload changes, attached as the server is built
+#[derive(Default)]
+pub(crate) struct Targets {
+    pub(crate) rate_limiter: Option<RateLimiter>,
+    pub(crate) cors_origins: Option<SharedOrigins>,
+    pub(crate) flags: Option<ConfigFlags>,
+}
+
+impl ConfigWatcher {
+    /// Load `path` like `Config::load`, to be reloaded from the same file and environment.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref().to_path_buf();
+        let modified = modified(&path);
+        let config = Config::load(&path)?;
+        Ok(ConfigWatcher {
+            path: Arc::new(path),
+            interval: Duration::from_secs(2),
+            watched: Arc::new(Mutex::new(Watched {
+                config,
+                modified,
+                targets: Targets::default(),
+            })),
+        })
+    }
+
+    /// How often the file's modification time is checked (every 2 seconds by default).
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// The settings in effect: those loaded, with the reloaded values applied since.
+    pub fn config(&self) -> Config {
+        self.lock().config.clone()
+    }
+
+    /// Read the file again and apply what changed that can be without a restart.
+    ///
+    /// A file that cannot be read or parsed, or has an invalid log level or
+    /// CORS origin, changes nothing.
+    pub fn reload(&self) -> Result<ReloadReport, ConfigError> {
+        let mut watched = self.lock();
+        watched.modified = modified(&self.path);
+        let config = Config::load(self.path.as_ref())?;
+        let (next, report) = self.apply(&watched, config)?;
+        watched.config = next;
+        if !report.applied.is_empty() {
+            tracing::info!("Reloaded {}: {}", self.path.display(), report.applied.join(", "));
+        }
+        if !report.needs_restart.is_empty() {
+            tracing::warn!(
+                "Ignoring changes to {} that need a restart: {}",
+                self.path.display(),
+                report.needs_restart.join(", ")
+            );
+        }
+        Ok(report)
+    }
+
+    // Compare `config` with the settings in effect, then apply the changes
+    // that can be, once they are all known to be valid; returns the settings
+    // now in effect
+    fn apply(&self, watched: &Watched, config: Config) -> Result<(Config, ReloadReport), ConfigError> {
+        let invalid = |message: String| ConfigError::Parse(self.path.to_path_buf(), message);
+        let old = &watched.config;
+        let targets = &watched.targets;
+        let mut next = old.clone();
+        let mut report = ReloadReport::default();
+        let mut restart = |name: &str, changed: bool| {
+            if changed {
+                report.needs_restart.push(name.to_string());
+            }
+        };
+        restart("addr", old.addr != config.addr);
+        restart("tls", old.tls != config.tls);
+        restart("timeouts.body_read", old.timeouts.body_read != config.timeouts.body_read);
+        restart("timeouts.handler", old.timeouts.handler != config.timeouts.handler);
+        restart("timeouts.shutdown", old.timeouts.shutdown != config.timeouts.shutdown);
+        restart("log.format", old.log.format != config.log.format);
+        restart("log.access_log", old.log.access_log != config.log.access_log);
+        restart("log.quiet", old.log.quiet != config.log.quiet);
+        restart("limits.max_body_size", old.limits.max_body_size != config.limits.max_body_size);
+        restart("limits.max_in_flight", old.limits.max_in_flight != config.limits.max_in_flight);
+        restart("compression", old.compression != config.compression);
+        restart("metrics_path", old.metrics_path != config.metrics_path);
+
+        let level = match &config.log.level {
+            Some(level) if old.log.level.as_ref() != Some(level) => {
+                EnvFilter::try_new(level).map_err(|err| invalid(format!("invalid log.level {:?}: {}", level, err)))?;
+                Some(level)
+            }
+            Some(_) => None,
+            None => {
+                restart("log.level", old.log.level.is_some());
+                None
+            }
+        };
+        let rate = match (old.limits.rate_limit, config.limits.rate_limit, &targets.rate_limiter) {
+            (Some(old), Some(new), Some(_)) if old != new => Some(new),
+            (old, new, _) => {
+                restart("limits.rate_limit", old != new);
+                None
+            }
+        };
+        let mut origins = None;
+        match (&old.cors, &config.cors) {
+            (Some(old), Some(new)) => {
+                let any = |origins: &[String]| origins.iter().any(|origin| origin == "*");
+                if old.allow_origins != new.allow_origins {
+                    if any(&old.allow_origins) || any(&new.allow_origins) || targets.cors_origins.is_none() {
+                        restart("cors.allow_origins", true);
+                    } else {
+                        let parsed = new
+                            .allow_origins
+                            .iter()
+                            .map(|origin| {
+                                HeaderValue::from_str(origin.trim_end_matches('/'))
+                                    .map_err(|_| invalid(format!("invalid cors.allow_origins entry {:?}", origin)))
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+                        origins = Some((new.allow_origins.clone(), parsed));
+                    }
+                }
+                restart("cors.allow_methods", old.allow_methods != new.allow_methods);
+                restart("cors.allow_headers", old.allow_headers != new.allow_headers);
+                restart("cors.expose_headers", old.expose_headers != new.expose_headers);
+                restart("cors.allow_credentials", old.allow_credentials != new.allow_credentials);
+                restart("cors.max_age", old.max_age != new.max_age);
+            }
+            (old, new) => restart("cors", old != new),
+        }
+        let flags = match (&config.flags, &targets.flags) {
+            (Some(flags), Some(_)) if old.flags.as_ref() != Some(flags) => Some(flags.clone()),
+            (flags, _) => {
+                restart("flags", old.flags != *flags);
+                None
+            }
+        };
+
+        // Everything is valid: apply it all
+        if let Some(level) = level {
+            match crate::logging::set_level(level) {
+                Ok(()) => {
+                    next.log.level = Some(level.clone());
+                    report.applied.push("log.level".to_string());
+                }
+                Err(err) => tracing::warn!("Not changing the log level to {:?}: {}", level, err),
+            }
+        }
+        if let (Some(rate), Some(limiter)) = (rate, &targets.rate_limiter) {
+            limiter.set_rate(rate);
+            next.limits.rate_limit = Some(rate);
+            report.applied.push("limits.rate_limit".to_string());
+        }
+        if let (Some((settings, parsed)), Some(shared), Some(cors)) = (origins, &targets.cors_origins, &mut next.cors) {
+            *shared.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = parsed;
+            cors.allow_origins = settings;
+            report.applied.push("cors.allow_origins".to_string());
+        }
+        if let (Some(values), Some(provider)) = (flags, &targets.flags) {
+            provider.replace(values.clone());
+            next.flags = Some(values);
+            report.applied.push("flags".to_string());
+        }
+        Ok((next, report))
+    }
+
+    // The parts of the server a reload changes, once it is built
+    pub(crate) fn attach(&self, targets: Targets) {
+        self.lock().targets = targets;
+    }
+
+    // Reload when the file's modification time changes, or on SIGHUP, until aborted
+    pub(crate) fn spawn(&self) -> JoinHandle<()> {
+        let watcher = self.clone();
+        tokio::spawn(async move {
+            #[cfg(unix)]
+            let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+                Ok(hangup) => Some(hangup),
+                Err(err) => {
+                    tracing::warn!("Cannot reload the configuration on SIGHUP: {}", err);
+                    None
+                }
+            };
+            let mut ticks = tokio::time::interval(watcher.interval);
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                #[cfg(unix)]
+                let signalled = async {
+                    match &mut hangup {
+                        Some(hangup) => hangup.recv().await,
+                        None => std::future::pending().await,
+                    }
+                };
+                #[cfg(not(unix))]
+                let signalled = std::future::pending::<Option<()>>();
+                tokio::select! {
+                    _ = ticks.tick() => {
+                        let changed = modified(&watcher.path).is_some_and(|at| Some(at) != watcher.lock().modified);
+                        if !changed {
+                            continue;
+                        }
+                    }
+                    _ = signalled => tracing::info!("SIGHUP received, reloading {}", watcher.path.display()),
+                }
+                if let Err(err) = watcher.reload() {
+                    tracing::error!("Keeping the current settings: {}", err);
+                }
+            }
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Watched> {
+        self.watched.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl fmt::Debug for ConfigWatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfigWatcher")
+            .field("path", &self.path)
+            .field("interval", &self.interval)
+            .finish()
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
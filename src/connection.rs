@@ -0,0 +1,255 @@
+//! Connection-level tuning and limits, to keep slow or numerous clients from
+//! tying the server up.
+//!
+//! ```ignore
+//! Server::new().connections(
+//!     ConnectionConfig::new()
+//!         .tcp_nodelay(true)
+//!         .tcp_keepalive(Duration::from_secs(60))
+//!         .max_connections(10_000)
+//!         .header_read_timeout(Duration::from_secs(5))
+//!         .write_timeout(Duration::from_secs(30))
+//!         .slow_request_threshold(Duration::from_secs(2)),
+//! );
+//! ```
+//!
+//! `max_connections` caps the connections open at once across every listener:
+//! at the cap, plain listeners stop accepting until one closes, leaving new
+//! clients in the system's backlog, and HTTPS holds accepted connections
+//! before the TLS handshake. `header_read_timeout` closes HTTP/1.1 connections
+//! whose request headers take longer to arrive, as slow-loris clients' do, and
+//! `write_timeout` those whose client stops reading the response. Requests
+//! taking longer than `slow_request_threshold` to answer are logged as
+//! warnings. Nothing is changed from the system's and hyper's defaults unless
+//! set; `Server::body_read_timeout` bounds the time to send a body.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::Router;
+use hyper_util::rt::{TokioExecutor, TokioTimer};
+use hyper_util::server::conn::auto;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Sleep;
+
+use crate::context::AppState;
+use crate::http2::Protocols;
+
+/// Connection settings for `Server::connections`.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionConfig {
+    nodelay: Option<bool>,
+    keepalive: Option<Duration>,
+    max_connections: Option<usize>,
+    header_read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    slow_request_threshold: Option<Duration>,
+}
+
+impl ConnectionConfig {
+    /// The system's and hyper's defaults, without limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send small writes at once instead of batching them (Nagle's algorithm off).
+    pub fn tcp_nodelay(mut self, enabled: bool) -> Self {
+        self.nodelay = Some(enabled);
+        self
+    }
+
+    /// Probe TCP connections idle for `idle`, so dead peers are noticed and dropped.
+    pub fn tcp_keepalive(mut self, idle: Duration) -> Self {
+        self.keepalive = Some(idle);
+        self
+    }
+
+    /// Most connections open at once.
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.max_connections = Some(max.max(1));
+        self
+    }
+
+    /// Close HTTP/1.1 connections whose request headers take longer to arrive.
+    pub fn header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.header_read_timeout = Some(timeout);
+        self
+    }
+
+    /// Close connections on which a response write makes no progress for this long.
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = Some(timeout);
+        self
+    }
+
+    /// Log a warning for each request taking longer than `threshold` to answer.
+    pub fn slow_request_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_request_threshold = Some(threshold);
+        self
+    }
+
+    pub(crate) fn slow_requests(&self) -> Option<Duration> {
+        self.slow_request_threshold
+    }
+
+    // Apply the timeouts to how plain listeners serve connections
+    pub(crate) fn configure(&self, protocols: &mut Protocols) {
+        let Some(timeout) = self.header_read_timeout else {
+            return;
+        };
+        match protocols {
+            Protocols::Auto(builder) => self.configure_auto(builder),
+            Protocols::Http1(builder) => {
+                builder.timer(TokioTimer::new()).header_read_timeout(timeout);
+            }
+        }
+    }
+
+    pub(crate) fn configure_auto(&self, builder: &mut auto::Builder<TokioExecutor>) {
+        if let Some(timeout) = self.header_read_timeout {
+            builder.http1().timer(TokioTimer::new()).header_read_timeout(timeout);
+        }
+    }
+}
+
+// The connection settings of one server, and the slots its listeners' connections share
+#[derive(Clone)]
+pub(crate) struct Tuning {
+    config: Arc<ConnectionConfig>,
+    slots: Option<Arc<Semaphore>>,
+}
+
+impl Tuning {
+    pub(crate) fn new(config: &ConnectionConfig) -> Self {
+        Tuning {
+            config: Arc::new(config.clone()),
+            slots: config.max_connections.map(|max| Arc::new(Semaphore::new(max))),
+        }
+    }
+
+    // A connection slot, once one is free under `max_connections`
+    pub(crate) async fn slot(&self) -> Option<OwnedSemaphorePermit> {
+        match &self.slots {
+            Some(slots) => slots.clone().acquire_owned().await.ok(),
+            None => None,
+        }
+    }
+
+    // Apply the TCP options to an accepted stream
+    pub(crate) fn tune(&self, stream: &TcpStream) {
+        if let Some(enabled) = self.config.nodelay
+            && let Err(err) = stream.set_nodelay(enabled)
+        {
+            tracing::debug!("Failed to set TCP_NODELAY: {}", err);
+        }
+        if let Some(idle) = self.config.keepalive {
+            let keepalive = socket2::TcpKeepalive::new().with_time(idle);
+            if let Err(err) = socket2::SockRef::from(stream).set_tcp_keepalive(&keepalive) {
+                tracing::debug!("Failed to enable TCP keep-alive: {}", err);
+            }
+        }
+    }
+
+    // `stream` under the write timeout, holding its connection slot until closed
+    pub(crate) fn wrap<S>(&self, stream: S, slot: Option<OwnedSemaphorePermit>) -> Tuned<S> {
+        Tuned {
+            inner: stream,
+            write_timeout: self.config.write_timeout,
+            stalled: None,
+            _slot: slot,
+        }
+    }
+}
+
+// An accepted stream whose writes fail once they have been pending for the write timeout
+pub(crate) struct Tuned<S> {
+    inner: S,
+    write_timeout: Option<Duration>,
+    // Running while a write is pending
+    stalled: Option<Pin<Box<Sleep>>>,
+    _slot: Option<OwnedSemaphorePermit>,
+}
+
+impl<S> Tuned<S> {
+    // Track a write's progress: `Pending` until the write timeout, then an error
+    fn check<T>(&mut self, cx: &mut Context<'_>, poll: Poll<io::Result<T>>) -> Poll<io::Result<T>> {
+        if poll.is_ready() {
+            self.stalled = None;
+            return poll;
+        }
+        let Some(timeout) = self.write_timeout else {
+            return Poll::Pending;
+        };
+        let stalled = self.stalled.get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+        match stalled.as_mut().poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(io::Error::new(io::ErrorKind::TimedOut, "write timed out"))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Tuned<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Tuned<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.check(cx, poll)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+        self.check(cx, poll)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.inner).poll_flush(cx);
+        self.check(cx, poll)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+// Warn about requests answered more slowly than `threshold`
+pub(crate) fn log_slow_requests(router: Router<AppState>, threshold: Duration) -> Router<AppState> {
+    router.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let method = request.method().clone();
+        let path = request.uri().path().to_string();
+        async move {
+            let started = Instant::now();
+            let response = next.run(request).await;
+            let elapsed = started.elapsed();
+            if elapsed > threshold {
+                tracing::warn!(
+                    method = %method,
+                    path = %path,
+                    status = response.status().as_u16(),
+                    elapsed_ms = elapsed.as_millis() as u64,
+                    "Slow request"
+                );
+            }
+            response
+        }
+    }))
+}
@@ -0,0 +1,214 @@
+// Path parameter constraints from `{name:constraint}` route segments: a type
+// such as `u64`, or a regex the whole value must match. Values breaking a
+// constraint are answered with 400 before the handler runs. Routes whose paths
+// have the same shape and differ only by constraints share one router entry
+// and are picked per request by the values they accept.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use axum::extract::{RawPathParams, Request};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::MethodRouter;
+use axum::Router;
+use futures_util::future::BoxFuture;
+use regex::Regex;
+use tower::Service;
+
+use crate::context::AppState;
+use crate::error::FerroxError;
+
+// `(name, constraint)` for each constrained placeholder of a route
+pub(crate) type Constraints = &'static [(&'static str, &'static str)];
+
+#[derive(Debug, Clone)]
+enum Constraint {
+    Type(&'static str),
+    Pattern(&'static str, Regex),
+}
+
+impl Constraint {
+    fn new(constraint: &'static str) -> Self {
+        match constraint {
+            "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64" | "i128" | "isize"
+            | "f32" | "f64" | "bool" => Constraint::Type(constraint),
+            // Checked by #[http_method] already
+            pattern => Constraint::Pattern(
+                pattern,
+                Regex::new(&format!("^(?:{})$", pattern)).expect("path constraint is a valid regex"),
+            ),
+        }
+    }
+
+    fn accepts(&self, value: &str) -> bool {
+        match self {
+            Constraint::Type(ty) => match *ty {
+                "u8" => value.parse::<u8>().is_ok(),
+                "u16" => value.parse::<u16>().is_ok(),
+                "u32" => value.parse::<u32>().is_ok(),
+                "u64" => value.parse::<u64>().is_ok(),
+                "u128" => value.parse::<u128>().is_ok(),
+                "usize" => value.parse::<usize>().is_ok(),
+                "i8" => value.parse::<i8>().is_ok(),
+                "i16" => value.parse::<i16>().is_ok(),
+                "i32" => value.parse::<i32>().is_ok(),
+                "i64" => value.parse::<i64>().is_ok(),
+                "i128" => value.parse::<i128>().is_ok(),
+                "isize" => value.parse::<isize>().is_ok(),
+                "f32" => value.parse::<f32>().is_ok(),
+                "f64" => value.parse::<f64>().is_ok(),
+                "bool" => value.parse::<bool>().is_ok(),
+                _ => false,
+            },
+            Constraint::Pattern(_, regex) => regex.is_match(value),
+        }
+    }
+
+    fn expected(&self) -> String {
+        match self {
+            Constraint::Type(ty) => format!("expected {}", ty),
+            Constraint::Pattern(pattern, _) => format!("expected a value matching `{}`", pattern),
+        }
+    }
+}
+
+// The constraints of one route, by placeholder name
+#[derive(Debug, Clone, Default)]
+struct Checks(Arc<HashMap<&'static str, Constraint>>);
+
+impl Checks {
+    fn new(constraints: Constraints) -> Self {
+        let checks = constraints
+            .iter()
+            .map(|(name, constraint)| (*name, Constraint::new(constraint)))
+            .collect();
+        Checks(Arc::new(checks))
+    }
+
+    // The first value breaking a constraint, as an error naming the parameter
+    fn check<'a>(&self, values: impl IntoIterator<Item = (&'a str, &'a str)>) -> Result<(), FerroxError> {
+        for (name, value) in values {
+            if let Some(constraint) = self.0.get(name)
+                && !constraint.accepts(value)
+            {
+                return Err(FerroxError::BadRequest(format!(
+                    "Invalid path parameter `{}`: {}",
+                    name,
+                    constraint.expected()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Answer 400 for requests to `route` whose path parameters break `constraints`
+pub(crate) fn check_route(route: MethodRouter<AppState>, constraints: Constraints) -> MethodRouter<AppState> {
+    let checks = Checks::new(constraints);
+    route.layer(axum::middleware::from_fn(move |params: RawPathParams, request: Request, next: Next| {
+        let checks = checks.clone();
+        async move {
+            match checks.check(params.iter()) {
+                Ok(()) => next.run(request).await,
+                Err(err) => err.into_response(),
+            }
+        }
+    }))
+}
+
+// `path` with placeholder names left out, so `/users/:id` and `/users/:name` have the same shape
+pub(crate) fn shape(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.chars().next() {
+            Some(kind @ (':' | '*')) => kind.to_string(),
+            _ => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// One of the routes sharing a shape: its path, the methods it serves and its router
+pub(crate) type Candidate = (String, Constraints, Vec<&'static str>, Router);
+
+// A candidate ready to check: the constrained name at each path segment, if any
+type Prepared = (Vec<Option<&'static str>>, Checks, Vec<&'static str>, Router);
+
+// Routes of one shape told apart by their constraints, picked per request
+#[derive(Clone)]
+pub(crate) struct ConstrainedRoute {
+    candidates: Arc<Vec<Prepared>>,
+}
+
+impl ConstrainedRoute {
+    pub(crate) fn new(mut candidates: Vec<Candidate>) -> Self {
+        // The most constrained first; unconstrained routes take what is left
+        candidates.sort_by_key(|(_, constraints, ..)| std::cmp::Reverse(constraints.len()));
+        let candidates = candidates
+            .into_iter()
+            .map(|(path, constraints, methods, router)| {
+                let names = path
+                    .split('/')
+                    .map(|segment| {
+                        let name = segment.strip_prefix(':').or_else(|| segment.strip_prefix('*'))?;
+                        constraints.iter().find(|(constrained, _)| *constrained == name).map(|(name, _)| *name)
+                    })
+                    .collect();
+                (names, Checks::new(constraints), methods, router)
+            })
+            .collect();
+        ConstrainedRoute {
+            candidates: Arc::new(candidates),
+        }
+    }
+
+    fn pick(&self, method: &Method, path: &str) -> &Router {
+        let method = if method == Method::HEAD { "GET" } else { method.as_str() };
+        let segments: Vec<&str> = path.split('/').collect();
+        let accepts = |names: &Vec<Option<&'static str>>, checks: &Checks| {
+            let values = names.iter().enumerate().filter_map(|(index, name)| {
+                let name = (*name)?;
+                // Only the last placeholder can be a catch-all, taking the rest of the path
+                let value = if index + 1 == names.len() {
+                    segments.get(index..)?.join("/")
+                } else {
+                    segments.get(index)?.to_string()
+                };
+                let value = percent_encoding::percent_decode_str(&value).decode_utf8_lossy().into_owned();
+                Some((name, value))
+            });
+            let values: Vec<(&str, String)> = values.collect();
+            checks.check(values.iter().map(|(name, value)| (*name, value.as_str()))).is_ok()
+        };
+        let candidates = self.candidates.iter();
+        let serves = |methods: &Vec<&str>| methods.contains(&method);
+        candidates
+            .clone()
+            .find(|(names, checks, methods, _)| serves(methods) && accepts(names, checks))
+            // Answered with 400 by the route's own checks
+            .or_else(|| candidates.clone().find(|(_, _, methods, _)| serves(methods)))
+            // Answered with 405
+            .or_else(|| candidates.clone().find(|(names, checks, ..)| accepts(names, checks)))
+            .or_else(|| self.candidates.last())
+            .map(|(.., router)| router)
+            .expect("a constrained route has candidates")
+    }
+}
+
+impl Service<Request> for ConstrainedRoute {
+    type Response = Response;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Response, Infallible>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let mut router = self.pick(request.method(), request.uri().path()).clone();
+        Box::pin(async move { router.call(request).await })
+    }
+}
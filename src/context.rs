@@ -0,0 +1,238 @@
+use axum::body::Bytes;
+use axum::extract::{ConnectInfo, Request};
+use axum::http::{request, Extensions, HeaderMap, Method, Uri};
+use axum::middleware::Next;
+use axum::Router;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+/// Everything the framework extracted from a request, handed to route handlers.
+///
+/// Handlers can also declare a `&RequestContext` (or `RequestContext`) parameter
+/// to read headers, the method, the URI or the client address.
+#[derive(Clone)]
+pub struct RequestContext {
+    pub path: serde_json::Value,
+    pub query: serde_json::Value,
+    pub body: serde_json::Value,
+    pub headers: HeaderMap,
+    pub method: Method,
+    pub uri: Uri,
+    /// Address of the connected client; `None` when the router is not served by
+    /// `Server` (e.g. driven directly from `Server::into_router`).
+    pub remote_addr: Option<SocketAddr>,
+    /// Request extensions, including the identity recorded by an `Authenticator`.
+    pub extensions: Extensions,
+    pub(crate) raw_body: Bytes,
+    pub(crate) state: AppState,
+}
+
+impl RequestContext {
+    pub fn new(path: serde_json::Value, query: serde_json::Value, body: serde_json::Value) -> Self {
+        let mut extensions = Extensions::new();
+        extensions.insert(RequestCache::default());
+        Self {
+            path,
+            query,
+            body,
+            headers: HeaderMap::new(),
+            method: Method::GET,
+            uri: Uri::default(),
+            remote_addr: None,
+            extensions,
+            raw_body: Bytes::new(),
+            state: AppState::default(),
+        }
+    }
+
+    // Context for a request whose path, query and body were already converted to JSON
+    pub(crate) fn from_parts(
+        parts: request::Parts,
+        path: serde_json::Value,
+        query: serde_json::Value,
+        body: serde_json::Value,
+        state: AppState,
+    ) -> Self {
+        let remote_addr = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| *addr);
+        Self {
+            path,
+            query,
+            body,
+            headers: parts.headers,
+            method: parts.method,
+            uri: parts.uri,
+            remote_addr,
+            extensions: parts.extensions,
+            raw_body: Bytes::new(),
+            state,
+        }
+    }
+
+    /// Value of the request header `name`, if present and valid UTF-8.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    /// The request body as it was sent, before any parsing; empty outside `#[http_method]`
+    /// and dynamic routes.
+    pub fn raw_body(&self) -> &Bytes {
+        &self.raw_body
+    }
+
+    /// The client's IP address, read from forwarding headers when the peer is one of
+    /// `Server::trusted_proxies`, or else the peer's.
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        crate::network::client_ip(&self.extensions)
+    }
+
+    /// Shared state registered with `Server::with_state`, if any was registered for `S`,
+    /// or the tenant's own, given with `TenantConfig::with_state`.
+    pub fn state<S: Clone + Send + Sync + 'static>(&self) -> Option<S> {
+        crate::tenancy::current(&self.extensions)
+            .and_then(|tenant| tenant.state::<S>())
+            .or_else(|| self.state.get::<S>())
+    }
+
+    /// The request's tenant, under `Server::tenancy`.
+    pub fn tenant(&self) -> Option<&crate::tenancy::TenantId> {
+        crate::tenancy::current(&self.extensions).map(|tenant| tenant.id())
+    }
+
+    /// The tenant's setting `key`, or the `Tenancy`'s default for it, if it has the type `T`.
+    pub fn tenant_setting<T: serde::de::DeserializeOwned>(&self, key: &str) -> Option<T> {
+        crate::tenancy::current(&self.extensions)?.setting(key)
+    }
+
+    /// The values cached for this request, shared with its middleware, guards and hooks.
+    pub fn cache(&self) -> RequestCache {
+        RequestCache::of(&self.extensions)
+    }
+
+    /// The `T` cached for this request, computing it with `compute` if nothing cached one yet.
+    pub fn cached<T: Clone + Send + Sync + 'static>(&self, compute: impl FnOnce() -> T) -> T {
+        self.cache().get_or_insert_with(compute)
+    }
+}
+
+/// Values computed once per request, keyed by type, such as a principal parsed by
+/// a middleware and read again by guards and the handler.
+///
+/// Every request served by `Server` carries one in its extensions; clones share
+/// the values, so whatever a middleware, guard or `before` hook inserts is seen
+/// by everything that runs after it for the same request.
+///
+/// ```ignore
+/// async fn load_user(request: Request, next: Next) -> Response {
+///     let user = lookup_user(request.headers()).await;
+///     RequestCache::of(request.extensions()).insert(user);
+///     next.run(request).await
+/// }
+///
+/// #[http_method(GET, "/me")]
+/// fn me(ctx: &RequestContext) -> Result<Json<User>, FerroxError> {
+///     ctx.cache().get::<User>().map(Json).ok_or(FerroxError::Unauthorized("Sign in first".to_string()))
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct RequestCache {
+    values: Arc<Mutex<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>>,
+}
+
+impl RequestCache {
+    /// The cache of the request with `extensions`, or an empty one holding its values
+    /// alone for requests not served by `Server`.
+    pub fn of(extensions: &Extensions) -> RequestCache {
+        extensions.get::<RequestCache>().cloned().unwrap_or_default()
+    }
+
+    /// The cached `T`, if any.
+    pub fn get<T: Clone + Send + Sync + 'static>(&self) -> Option<T> {
+        self.values
+            .lock()
+            .unwrap()
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>())
+            .cloned()
+    }
+
+    /// Cache `value`, replacing the `T` cached before.
+    pub fn insert<T: Clone + Send + Sync + 'static>(&self, value: T) {
+        self.values.lock().unwrap().insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    /// Remove the cached `T`, returning it.
+    pub fn remove<T: Clone + Send + Sync + 'static>(&self) -> Option<T> {
+        let value = self.values.lock().unwrap().remove(&TypeId::of::<T>())?;
+        value.downcast_ref::<T>().cloned()
+    }
+
+    /// The cached `T`, or `compute`'s result, cached for the rest of the request.
+    pub fn get_or_insert_with<T: Clone + Send + Sync + 'static>(&self, compute: impl FnOnce() -> T) -> T {
+        self.get_or_try_insert_with(|| Ok::<T, std::convert::Infallible>(compute()))
+            .unwrap_or_else(|never| match never {})
+    }
+
+    /// The cached `T`, or `compute`'s result, cached if it succeeded; a failure is
+    /// returned without caching anything, so the next caller tries again.
+    pub fn get_or_try_insert_with<T: Clone + Send + Sync + 'static, E>(
+        &self,
+        compute: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, E> {
+        if let Some(value) = self.get::<T>() {
+            return Ok(value);
+        }
+        // Computed without the lock held, so `compute` may use the cache itself
+        let value = compute()?;
+        self.insert(value.clone());
+        Ok(value)
+    }
+}
+
+// Give each request a cache of its own, replacing one copied from a batch request
+pub(crate) fn cache_layer(router: Router<AppState>) -> Router<AppState> {
+    router.layer(axum::middleware::from_fn(|mut request: Request, next: Next| {
+        request.extensions_mut().insert(RequestCache::default());
+        next.run(request)
+    }))
+}
+
+/// Shared application state injected into a handler parameter.
+///
+/// ```ignore
+/// #[http_method(GET, "/config")]
+/// fn config(state: State<AppConfig>) -> Value { json!(state.0.name) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct State<S>(pub S);
+
+impl<S> std::ops::Deref for State<S> {
+    type Target = S;
+
+    fn deref(&self) -> &S {
+        &self.0
+    }
+}
+
+/// States registered with `Server::with_state`, keyed by type. This is the axum router state.
+#[derive(Clone, Default)]
+pub struct AppState {
+    values: Arc<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>,
+}
+
+impl AppState {
+    pub(crate) fn insert<S: Clone + Send + Sync + 'static>(&mut self, state: S) {
+        Arc::make_mut(&mut self.values).insert(TypeId::of::<S>(), Arc::new(state));
+    }
+
+    pub(crate) fn get<S: Clone + Send + Sync + 'static>(&self) -> Option<S> {
+        self.values
+            .get(&TypeId::of::<S>())
+            .and_then(|value| value.downcast_ref::<S>())
+            .cloned()
+    }
+}
@@ -0,0 +1,323 @@
+//! Cross-origin resource sharing for browser clients on other origins.
+//!
+//! ```ignore
+//! Server::new().cors(
+//!     CorsConfig::new()
+//!         .allow_origin("https://app.example.com")
+//!         .allow_methods([Method::GET, Method::POST])
+//!         .allow_headers([CONTENT_TYPE, AUTHORIZATION])
+//!         .allow_credentials(true),
+//! );
+//! ```
+//!
+//! Preflight (`OPTIONS` with `Access-Control-Request-Method`) requests are
+//! answered for every path without reaching handlers, rate limits or
+//! middleware. The CORS headers are also added to error responses, so
+//! browsers can read a 401 or 429 instead of reporting a network error.
+//!
+//! Routes can override the server's policy with `cors = ...` on
+//! `#[http_method]` or `#[route_group]`, see `RouteCors`:
+//!
+//! ```ignore
+//! #[http_method(GET, "/public/feed", cors = "any")]
+//! async fn feed() -> Value { ... }
+//!
+//! #[route_group(prefix = "/partners", cors = ["https://partner.example"])]
+//! mod partners { ... }
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use axum::extract::Request;
+use axum::http::header::ACCESS_CONTROL_REQUEST_METHOD;
+use axum::http::{HeaderName, HeaderValue, Method};
+use axum::middleware::Next;
+use axum::Router;
+use tower::{Layer, ServiceExt};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer, ExposeHeaders};
+
+use crate::context::AppState;
+
+/// The CORS policy of a route, in place of `Server::cors`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteCors {
+    /// `cors = "any"`: any origin, method and header, without credentials;
+    /// for public endpoints such as feeds.
+    Any,
+    /// `cors = ["https://a.example", ...]`: these origins, with the methods,
+    /// headers and credentials of `Server::cors`.
+    Origins(&'static [&'static str]),
+    /// `cors = false`: no CORS headers, so only same-origin pages can call the route.
+    Disabled,
+}
+
+/// Which cross-origin requests browsers may make; pass it to `Server::cors`.
+///
+/// Nothing is allowed until configured. Browsers reject `*` on credentialed
+/// requests, so `Server::cors` refuses a wildcard (any origin, method or header)
+/// combined with `allow_credentials(true)`, unless the config was built with
+/// `mirror_any_origin_with_credentials`.
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    // `None` allows any
+    origins: Option<Vec<HeaderValue>>,
+    methods: Option<Vec<Method>>,
+    headers: Option<Vec<HeaderName>>,
+    expose_headers: Vec<HeaderName>,
+    credentials: bool,
+    max_age: Option<Duration>,
+    // Echo the request's values in place of wildcards on credentialed requests
+    mirror: bool,
+    // The origins, when a configuration reload can replace them
+    shared_origins: Option<SharedOrigins>,
+}
+
+// The allowed origins, read on every request
+pub(crate) type SharedOrigins = Arc<RwLock<Vec<HeaderValue>>>;
+
+impl CorsConfig {
+    pub fn new() -> Self {
+        CorsConfig {
+            origins: Some(Vec::new()),
+            methods: Some(Vec::new()),
+            headers: Some(Vec::new()),
+            expose_headers: Vec::new(),
+            credentials: false,
+            max_age: None,
+            mirror: false,
+            shared_origins: None,
+        }
+    }
+
+    /// Any origin, method and header, without credentials; meant for development.
+    pub fn permissive() -> Self {
+        CorsConfig::new().allow_any_origin().allow_any_method().allow_any_header()
+    }
+
+    /// Allow requests from `origin`, e.g. `https://app.example.com`; can be called repeatedly.
+    ///
+    /// An origin that is not a valid header value is logged and skipped.
+    pub fn allow_origin(mut self, origin: &str) -> Self {
+        match HeaderValue::from_str(origin.trim_end_matches('/')) {
+            Ok(value) => self.origins.get_or_insert_with(Vec::new).push(value),
+            Err(_) => tracing::warn!("Ignoring invalid CORS origin {:?}", origin),
+        }
+        self
+    }
+
+    pub fn allow_any_origin(mut self) -> Self {
+        self.origins = None;
+        self
+    }
+
+    pub fn allow_methods(mut self, methods: impl IntoIterator<Item = Method>) -> Self {
+        self.methods.get_or_insert_with(Vec::new).extend(methods);
+        self
+    }
+
+    pub fn allow_any_method(mut self) -> Self {
+        self.methods = None;
+        self
+    }
+
+    /// Request headers browsers may send beyond the CORS-safelisted ones.
+    pub fn allow_headers(mut self, headers: impl IntoIterator<Item = HeaderName>) -> Self {
+        self.headers.get_or_insert_with(Vec::new).extend(headers);
+        self
+    }
+
+    pub fn allow_any_header(mut self) -> Self {
+        self.headers = None;
+        self
+    }
+
+    /// Response headers scripts may read beyond the CORS-safelisted ones.
+    pub fn expose_headers(mut self, headers: impl IntoIterator<Item = HeaderName>) -> Self {
+        self.expose_headers.extend(headers);
+        self
+    }
+
+    /// Allow cookies and `Authorization` on cross-origin requests.
+    pub fn allow_credentials(mut self, allow: bool) -> Self {
+        self.credentials = allow;
+        self
+    }
+
+    /// Allow credentialed requests from any origin by echoing the request's
+    /// `Origin`; `allow_any_method` and `allow_any_header` then echo what the
+    /// preflight asks for.
+    ///
+    /// Any page the user visits can then call the API with their cookies, so
+    /// only use it for APIs meant to be called that way from everywhere.
+    pub fn mirror_any_origin_with_credentials(mut self) -> Self {
+        self.origins = None;
+        self.credentials = true;
+        self.mirror = true;
+        self
+    }
+
+    /// How long browsers may cache a preflight response.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    // The wildcards that cannot be combined with credentials, unless mirrored
+    pub(crate) fn check(&self) -> Result<(), String> {
+        if !self.credentials || self.mirror {
+            return Ok(());
+        }
+        let wildcards: Vec<&str> = [
+            (self.origins.is_none(), "allow_any_origin()"),
+            (self.methods.is_none(), "allow_any_method()"),
+            (self.headers.is_none(), "allow_any_header()"),
+        ]
+        .into_iter()
+        .filter_map(|(any, wildcard)| any.then_some(wildcard))
+        .collect();
+        if wildcards.is_empty() {
+            return Ok(());
+        }
+        Err(format!(
+            "CORS {} cannot be combined with allow_credentials(true): list what is allowed, \
+             or use mirror_any_origin_with_credentials() to echo the request's values",
+            wildcards.join(", ")
+        ))
+    }
+
+    // What a route with its own `policy` allows, based on the server's `global`
+    // config; `None` for no CORS
+    fn for_route(global: Option<&CorsConfig>, policy: RouteCors) -> Option<CorsConfig> {
+        match policy {
+            RouteCors::Any => {
+                let mut config = CorsConfig::permissive();
+                if let Some(global) = global {
+                    config.expose_headers = global.expose_headers.clone();
+                    config.max_age = global.max_age;
+                }
+                Some(config)
+            }
+            RouteCors::Origins(origins) => {
+                let mut config = global
+                    .cloned()
+                    .unwrap_or_else(|| CorsConfig::new().allow_any_method().allow_any_header());
+                config.origins = Some(Vec::new());
+                config.shared_origins = None;
+                Some(origins.iter().fold(config, |config, origin| config.allow_origin(origin)))
+            }
+            RouteCors::Disabled => None,
+        }
+    }
+
+    // Look the allowed origins up on every request, so they can be replaced
+    // through the returned list; `None` when any origin is allowed
+    pub(crate) fn share_origins(&mut self) -> Option<SharedOrigins> {
+        let origins = Arc::new(RwLock::new(self.origins.clone()?));
+        self.shared_origins = Some(origins.clone());
+        Some(origins)
+    }
+
+    pub(crate) fn into_layer(self) -> CorsLayer {
+        let origin = match (self.origins, self.shared_origins) {
+            (Some(_), Some(shared)) => AllowOrigin::predicate(move |origin, _| {
+                shared.read().unwrap_or_else(|poisoned| poisoned.into_inner()).contains(origin)
+            }),
+            (Some(origins), None) => AllowOrigin::list(origins),
+            (None, _) if self.credentials && self.mirror => AllowOrigin::mirror_request(),
+            (None, _) => AllowOrigin::any(),
+        };
+        let methods = match self.methods {
+            Some(methods) => AllowMethods::list(methods),
+            None if self.credentials && self.mirror => AllowMethods::mirror_request(),
+            None => AllowMethods::any(),
+        };
+        let headers = match self.headers {
+            Some(headers) => AllowHeaders::list(headers),
+            None if self.credentials && self.mirror => AllowHeaders::mirror_request(),
+            None => AllowHeaders::any(),
+        };
+        let mut layer = CorsLayer::new()
+            .allow_origin(origin)
+            .allow_methods(methods)
+            .allow_headers(headers)
+            .expose_headers(ExposeHeaders::list(self.expose_headers))
+            .allow_credentials(self.credentials);
+        if let Some(max_age) = self.max_age {
+            layer = layer.max_age(max_age);
+        }
+        layer
+    }
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The policies of routes with their own, by method, under the route's path
+type RoutePolicies = matchit::Router<HashMap<Method, Option<CorsLayer>>>;
+
+// Apply `global` to every request, except those to the `routes` with their own
+// policy, given as `(path, method, policy)`
+pub(crate) fn layer(
+    router: Router<AppState>,
+    global: Option<CorsConfig>,
+    routes: Vec<(String, &'static str, RouteCors)>,
+) -> Router<AppState> {
+    if routes.is_empty() {
+        return match global {
+            Some(config) => router.layer(config.into_layer()),
+            None => router,
+        };
+    }
+    // Paths told apart by constraints share a shape, and one entry
+    let mut shapes: BTreeMap<String, (String, HashMap<Method, Option<CorsLayer>>)> = BTreeMap::new();
+    for (path, method, policy) in routes {
+        let Ok(method) = method.parse::<Method>() else {
+            continue;
+        };
+        let layer = CorsConfig::for_route(global.as_ref(), policy).map(CorsConfig::into_layer);
+        let (_, methods) = shapes
+            .entry(crate::constraints::shape(&path))
+            .or_insert_with(|| (path, HashMap::new()));
+        // GET routes answer HEAD too
+        if method == Method::GET {
+            methods.entry(Method::HEAD).or_insert_with(|| layer.clone());
+        }
+        methods.insert(method, layer);
+    }
+    let mut policies = RoutePolicies::new();
+    for (path, methods) in shapes.into_values() {
+        if let Err(err) = policies.insert(path.as_str(), methods) {
+            tracing::error!("Ignoring the CORS policy of {}: {}", path, err);
+        }
+    }
+    let policies = Arc::new(policies);
+    let global = global.map(CorsConfig::into_layer);
+    router.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let policy = route_policy(&policies, &request).unwrap_or(&global).clone();
+        async move {
+            match policy {
+                Some(layer) => match layer.layer(next).oneshot(request).await {
+                    Ok(response) => response,
+                    Err(never) => match never {},
+                },
+                None => next.run(request).await,
+            }
+        }
+    }))
+}
+
+// The policy of the route `request` is for, if it has its own
+fn route_policy<'a>(policies: &'a RoutePolicies, request: &Request) -> Option<&'a Option<CorsLayer>> {
+    let methods = policies.at(request.uri().path()).ok()?.value;
+    // A preflight asks about the method of the request to follow
+    let method = match request.headers().get(ACCESS_CONTROL_REQUEST_METHOD) {
+        Some(value) if request.method() == Method::OPTIONS => Method::from_bytes(value.as_bytes()).ok()?,
+        _ => request.method().clone(),
+    };
+    methods.get(&method)
+}
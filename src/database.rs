@@ -0,0 +1,345 @@
+//! Database connection pools managed by the server (the `sqlx` and
+//! `deadpool-postgres` features).
+//!
+//! ```ignore
+//! Server::new()
+//!     .with_database::<PgPool>("postgres://app@localhost/app", PoolConfig::new().max_connections(20))
+//!     .enable_health_checks();
+//!
+//! #[http_method(GET, "/users/:id")]
+//! async fn get_user(id: i64, pool: State<PgPool>) -> Result<Json<User>, FerroxError> {
+//!     let user = sqlx::query_as("SELECT * FROM users WHERE id = $1").bind(id).fetch_one(&*pool).await?;
+//!     Ok(Json(user))
+//! }
+//! ```
+//!
+//! `Server::with_database` creates the pool, any `sqlx::Pool` or a
+//! `deadpool_postgres::Pool`, and shares it with handlers as `State<P>`.
+//! Connections are opened as requests need them, so the server starts while
+//! the database is still coming up; a `database` readiness check pings it for
+//! `/readyz` and `/healthz`, and the pool is closed once the server has shut
+//! down. The database drivers are chosen through the application's own `sqlx`
+//! features.
+//!
+//! `sqlx` errors convert into `FerroxError` with `?`: a missing row answers
+//! 404, a pool with no free connection 503, and anything else is logged and
+//! answers 500 without the details.
+//!
+//! With `TransactionLayer`, each request runs in a transaction that handlers
+//! reach through a `Tx<DB>` parameter, whatever its name:
+//!
+//! ```ignore
+//! Server::new().route_layer("POST", "/orders", TransactionLayer::new(pool.clone()));
+//!
+//! #[http_method(POST, "/orders")]
+//! async fn create_order(body: NewOrder, tx: Tx<Postgres>) -> Result<Json<Order>, FerroxError> {
+//!     let mut conn = tx.conn().await?;
+//!     let order = sqlx::query_as("INSERT INTO orders ...").fetch_one(&mut *conn).await?;
+//!     sqlx::query("UPDATE stock ...").execute(&mut *conn).await?;
+//!     Ok(Json(order))
+//! }
+//! ```
+//!
+//! The transaction begins before the handler runs, answering 503 when no
+//! connection is free, and is committed once the handler has answered,
+//! unless the request failed with a `FerroxError`, from the handler or the
+//! framework, or the response is a 5xx, which roll it back. A commit that fails answers 500. A handler that panics or is
+//! cancelled drops the transaction, which rolls it back.
+
+#[cfg(feature = "sqlx")]
+use std::sync::Arc;
+#[cfg(feature = "sqlx")]
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+#[cfg(feature = "sqlx")]
+use axum::extract::Request;
+#[cfg(feature = "sqlx")]
+use axum::response::{IntoResponse, Response};
+use futures_util::future::BoxFuture;
+#[cfg(feature = "sqlx")]
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+#[cfg(feature = "sqlx")]
+use tower::{Layer, Service};
+
+#[cfg(feature = "sqlx")]
+use crate::context::RequestContext;
+#[cfg(feature = "sqlx")]
+use crate::error::FerroxError;
+#[cfg(feature = "sqlx")]
+use crate::response::Failed;
+
+/// Size and timeouts of a connection pool; pass it to `Server::with_database`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolConfig {
+    pub(crate) max_connections: u32,
+    pub(crate) min_connections: u32,
+    pub(crate) acquire_timeout: Duration,
+    pub(crate) idle_timeout: Option<Duration>,
+    pub(crate) max_lifetime: Option<Duration>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout: Duration::from_secs(30),
+            idle_timeout: Some(Duration::from_secs(10 * 60)),
+            max_lifetime: Some(Duration::from_secs(30 * 60)),
+        }
+    }
+}
+
+impl PoolConfig {
+    /// At most 10 connections, waiting up to 30s for a free one.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Most connections open at once (10 by default).
+    pub fn max_connections(mut self, connections: u32) -> Self {
+        self.max_connections = connections;
+        self
+    }
+
+    /// Connections kept open even when idle (none by default; `sqlx` only).
+    pub fn min_connections(mut self, connections: u32) -> Self {
+        self.min_connections = connections;
+        self
+    }
+
+    /// How long a request waits for a connection before failing (30s by default).
+    pub fn acquire_timeout(mut self, timeout: Duration) -> Self {
+        self.acquire_timeout = timeout;
+        self
+    }
+
+    /// Close connections idle for longer than `timeout` (10 minutes by default; `sqlx` only).
+    pub fn idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Replace connections once they are `lifetime` old (30 minutes by default; `sqlx` only).
+    pub fn max_lifetime(mut self, lifetime: Option<Duration>) -> Self {
+        self.max_lifetime = lifetime;
+        self
+    }
+}
+
+/// A connection pool `Server::with_database` can manage.
+pub trait Database: Clone + Send + Sync + 'static {
+    /// Create the pool for `url` without connecting yet.
+    fn connect(url: &str, config: &PoolConfig) -> Result<Self, String>;
+
+    /// Check that a connection can be made and used.
+    fn ping(&self) -> BoxFuture<'static, Result<(), String>>;
+
+    /// Close the pool's connections and refuse new ones.
+    fn close(&self) -> BoxFuture<'static, ()>;
+}
+
+#[cfg(feature = "sqlx")]
+impl<DB: sqlx::Database> Database for sqlx::Pool<DB> {
+    fn connect(url: &str, config: &PoolConfig) -> Result<Self, String> {
+        sqlx::pool::PoolOptions::<DB>::new()
+            .max_connections(config.max_connections)
+            .min_connections(config.min_connections)
+            .acquire_timeout(config.acquire_timeout)
+            .idle_timeout(config.idle_timeout)
+            .max_lifetime(config.max_lifetime)
+            .connect_lazy(url)
+            .map_err(|err| err.to_string())
+    }
+
+    fn ping(&self) -> BoxFuture<'static, Result<(), String>> {
+        let pool = self.clone();
+        Box::pin(async move {
+            let mut connection = pool.acquire().await.map_err(|err| err.to_string())?;
+            sqlx::Connection::ping(&mut *connection).await.map_err(|err| err.to_string())
+        })
+    }
+
+    fn close(&self) -> BoxFuture<'static, ()> {
+        let pool = self.clone();
+        Box::pin(async move { pool.close().await })
+    }
+}
+
+#[cfg(feature = "sqlx")]
+impl From<sqlx::Error> for FerroxError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => FerroxError::NotFound("Not found".to_string()),
+            sqlx::Error::PoolTimedOut => FerroxError::Status(
+                axum::http::StatusCode::SERVICE_UNAVAILABLE,
+                "No database connection available".to_string(),
+            ),
+            err => {
+                tracing::error!("Database error: {}", err);
+                FerroxError::Internal("Database error".to_string())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "deadpool-postgres")]
+impl Database for deadpool_postgres::Pool {
+    fn connect(url: &str, config: &PoolConfig) -> Result<Self, String> {
+        let mut pool = deadpool_postgres::PoolConfig::new(config.max_connections as usize);
+        pool.timeouts.wait = Some(config.acquire_timeout);
+        pool.timeouts.create = Some(config.acquire_timeout);
+        let settings = deadpool_postgres::Config {
+            url: Some(url.to_string()),
+            pool: Some(pool),
+            ..deadpool_postgres::Config::default()
+        };
+        settings
+            .create_pool(Some(deadpool_postgres::Runtime::Tokio1), deadpool_postgres::tokio_postgres::NoTls)
+            .map_err(|err| err.to_string())
+    }
+
+    fn ping(&self) -> BoxFuture<'static, Result<(), String>> {
+        let pool = self.clone();
+        Box::pin(async move {
+            let client = pool.get().await.map_err(|err| err.to_string())?;
+            client.simple_query("SELECT 1").await.map(drop).map_err(|err| err.to_string())
+        })
+    }
+
+    fn close(&self) -> BoxFuture<'static, ()> {
+        deadpool_postgres::Pool::close(self);
+        Box::pin(async {})
+    }
+}
+
+/// The transaction the request runs in, shared with its handler by `TransactionLayer`.
+#[cfg(feature = "sqlx")]
+pub struct Tx<DB: sqlx::Database>(Arc<Mutex<Option<sqlx::Transaction<'static, DB>>>>);
+
+#[cfg(feature = "sqlx")]
+impl<DB: sqlx::Database> Clone for Tx<DB> {
+    fn clone(&self) -> Self {
+        Tx(self.0.clone())
+    }
+}
+
+#[cfg(feature = "sqlx")]
+impl<DB: sqlx::Database> Tx<DB> {
+    /// The transaction's connection, to run queries on until the guard is dropped.
+    pub async fn conn(&self) -> Result<MappedMutexGuard<'_, DB::Connection>, FerroxError> {
+        MutexGuard::try_map(self.0.lock().await, |tx| tx.as_deref_mut())
+            .map_err(|_| FerroxError::Internal("The request's transaction has already ended".to_string()))
+    }
+}
+
+/// Read the transaction `TransactionLayer` opened for the request.
+#[cfg(feature = "sqlx")]
+pub fn transaction<DB: sqlx::Database>(ctx: &RequestContext) -> Result<Tx<DB>, FerroxError> {
+    ctx.extensions
+        .get::<Tx<DB>>()
+        .cloned()
+        .ok_or_else(|| FerroxError::Internal("No transaction is open for this route; wrap it in a TransactionLayer".to_string()))
+}
+
+/// Runs each request in a transaction on `pool`; see the module docs.
+#[cfg(feature = "sqlx")]
+pub struct TransactionLayer<DB: sqlx::Database> {
+    pool: sqlx::Pool<DB>,
+}
+
+#[cfg(feature = "sqlx")]
+impl<DB: sqlx::Database> TransactionLayer<DB> {
+    pub fn new(pool: sqlx::Pool<DB>) -> Self {
+        TransactionLayer { pool }
+    }
+}
+
+#[cfg(feature = "sqlx")]
+impl<DB: sqlx::Database> Clone for TransactionLayer<DB> {
+    fn clone(&self) -> Self {
+        TransactionLayer { pool: self.pool.clone() }
+    }
+}
+
+#[cfg(feature = "sqlx")]
+impl<S, DB: sqlx::Database> Layer<S> for TransactionLayer<DB> {
+    type Service = TransactionService<S, DB>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TransactionService {
+            inner,
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+/// The service `TransactionLayer` wraps routes in.
+#[cfg(feature = "sqlx")]
+pub struct TransactionService<S, DB: sqlx::Database> {
+    inner: S,
+    pool: sqlx::Pool<DB>,
+}
+
+#[cfg(feature = "sqlx")]
+impl<S: Clone, DB: sqlx::Database> Clone for TransactionService<S, DB> {
+    fn clone(&self) -> Self {
+        TransactionService {
+            inner: self.inner.clone(),
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "sqlx")]
+impl<S, DB> Service<Request> for TransactionService<S, DB>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    DB: sqlx::Database,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        // The clone takes the place of the service that was polled ready
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let pool = self.pool.clone();
+        Box::pin(async move {
+            let tx = match pool.begin().await {
+                Ok(tx) => tx,
+                Err(err) => return Ok(FerroxError::from(err).into_response()),
+            };
+            let slot = Arc::new(Mutex::new(Some(tx)));
+            request.extensions_mut().insert(Tx(slot.clone()));
+            let response = inner.call(request).await?;
+
+            let Some(tx) = slot.lock().await.take() else {
+                return Ok(response);
+            };
+            let failed = response.status().is_server_error()
+                || response.extensions().get::<Failed>().is_some()
+                || response.extensions().get::<FerroxError>().is_some();
+            if failed {
+                if let Err(err) = tx.rollback().await {
+                    tracing::error!("Failed to roll back the request's transaction: {}", err);
+                }
+                return Ok(response);
+            }
+            match tx.commit().await {
+                Ok(()) => Ok(response),
+                Err(err) => {
+                    tracing::error!("Failed to commit the request's transaction: {}", err);
+                    Ok(FerroxError::Internal("Failed to commit the transaction".to_string()).into_response())
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,65 @@
+// Deprecation notices: `deprecated = true`, `sunset = "2026-06-01"` and
+// `successor = "/v2/users"` on a route add the `Deprecation` (RFC 9745),
+// `Sunset` (RFC 8594) and `Link: <...>; rel="successor-version"` headers to
+// each of its responses, so clients learn from the traffic itself that they
+// have to move, by when, and where to.
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use axum::extract::Request;
+use axum::http::header::LINK;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::routing::MethodRouter;
+
+use crate::context::AppState;
+use crate::RouteOptions;
+
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+const SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+// Send the route's deprecation headers, if it has any
+pub(crate) fn mark_route(route: MethodRouter<AppState>, options: &RouteOptions) -> MethodRouter<AppState> {
+    let headers = headers(options);
+    if headers.is_empty() {
+        return route;
+    }
+    route.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let headers = headers.clone();
+        async move {
+            let mut response = next.run(request).await;
+            for (name, value) in headers {
+                response.headers_mut().append(name, value);
+            }
+            response
+        }
+    }))
+}
+
+fn headers(options: &RouteOptions) -> Vec<(HeaderName, HeaderValue)> {
+    let mut headers = Vec::new();
+    if options.deprecated {
+        headers.push((DEPRECATION, HeaderValue::from_static("true")));
+    }
+    if let Some(date) = options.sunset {
+        match http_date(date) {
+            Some(value) => headers.push((SUNSET, value)),
+            None => tracing::warn!("Ignoring the invalid sunset date {:?}", date),
+        }
+    }
+    if let Some(successor) = options.successor {
+        match HeaderValue::try_from(format!("<{}>; rel=\"successor-version\"", successor)) {
+            Ok(value) => headers.push((LINK, value)),
+            Err(_) => tracing::warn!("Ignoring the invalid successor {:?}", successor),
+        }
+    }
+    headers
+}
+
+// Midnight UTC of a `YYYY-MM-DD` date, as an HTTP date
+fn http_date(date: &str) -> Option<HeaderValue> {
+    let date = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    let secs = date.and_hms_opt(0, 0, 0)?.and_utc().timestamp();
+    let time = UNIX_EPOCH + Duration::from_secs(u64::try_from(secs).ok()?);
+    HeaderValue::try_from(httpdate::fmt_http_date(time)).ok()
+}
@@ -0,0 +1,143 @@
+//! A registry of the services handlers depend on, handed to them by type.
+//!
+//! ```ignore
+//! let services = Container::new()
+//!     .singleton(Mailer::new(smtp_url))
+//!     .per_request(|ctx: &RequestContext| {
+//!         let db = di::resolve::<Db>(ctx)?;
+//!         Ok(UserService::new(db, ctx.tenant().cloned()))
+//!     })
+//!     .singleton(Db::connect(&database_url)?);
+//! Server::new().services(services);
+//!
+//! #[http_method(POST, "/users/:id/invite")]
+//! async fn invite(id: u64, users: Dep<UserService>, mailer: Dep<Mailer>) -> Result<Value, FerroxError> { ... }
+//! ```
+//!
+//! `Dep<T>` handler parameters, whatever their name, receive the `T` registered
+//! with `Server::services`:
+//!
+//! - a **singleton** is built once by the application and shared by every request
+//! - a **per-request** service is built by its factory from the `RequestContext`
+//!   the first time a request needs it, and shared by everything that resolves it
+//!   during that request, including other factories calling [`resolve`]
+//!
+//! A factory's error answers the request. A `Dep` of a type nobody registered
+//! answers 500 and logs the missing type, as a missing `State` does.
+
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::context::RequestContext;
+use crate::error::FerroxError;
+
+type AnyService = Arc<dyn Any + Send + Sync>;
+type Factory = Arc<dyn Fn(&RequestContext) -> Result<AnyService, FerroxError> + Send + Sync>;
+
+#[derive(Clone)]
+enum Lifetime {
+    Singleton(AnyService),
+    PerRequest(Factory),
+}
+
+/// The services handlers can take as `Dep<T>`, by type; register them with
+/// `Server::services`.
+#[derive(Clone, Default)]
+pub struct Container {
+    services: Arc<HashMap<TypeId, Lifetime>>,
+}
+
+impl Container {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Share `service` between every request; registering a `T` again replaces it.
+    pub fn singleton<T: Send + Sync + 'static>(mut self, service: T) -> Self {
+        let service: AnyService = Arc::new(service);
+        Arc::make_mut(&mut self.services).insert(TypeId::of::<T>(), Lifetime::Singleton(service));
+        self
+    }
+
+    /// Build a `T` with `factory` for each request that needs one.
+    pub fn per_request<T, F>(mut self, factory: F) -> Self
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&RequestContext) -> Result<T, FerroxError> + Send + Sync + 'static,
+    {
+        let factory: Factory = Arc::new(move |ctx| factory(ctx).map(|service| Arc::new(service) as AnyService));
+        Arc::make_mut(&mut self.services).insert(TypeId::of::<T>(), Lifetime::PerRequest(factory));
+        self
+    }
+
+    /// Whether a `T` is registered.
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.services.contains_key(&TypeId::of::<T>())
+    }
+
+    /// The registered singleton `T`; `None` for per-request services, which need a request.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<Dep<T>> {
+        match self.services.get(&TypeId::of::<T>())? {
+            Lifetime::Singleton(service) => service.clone().downcast().ok().map(Dep),
+            Lifetime::PerRequest(_) => None,
+        }
+    }
+}
+
+impl fmt::Debug for Container {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Container").field("services", &self.services.len()).finish()
+    }
+}
+
+/// A service from the server's `Container`, shared by reference.
+pub struct Dep<T: ?Sized>(pub Arc<T>);
+
+impl<T: ?Sized> Clone for Dep<T> {
+    fn clone(&self) -> Self {
+        Dep(self.0.clone())
+    }
+}
+
+impl<T: ?Sized> Deref for Dep<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Dep<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// The request's `T` from the server's `Container`, for `Dep<T>` handler
+/// parameters and for factories building on other services.
+pub fn resolve<T: Send + Sync + 'static>(ctx: &RequestContext) -> Result<Dep<T>, FerroxError> {
+    let missing = || {
+        tracing::error!(
+            "No service of type {} was registered with Server::services",
+            type_name::<T>()
+        );
+        FerroxError::Internal("Internal server error: missing service".to_string())
+    };
+    let container = ctx.state::<Container>().ok_or_else(missing)?;
+    let factory = match container.services.get(&TypeId::of::<T>()).ok_or_else(missing)? {
+        Lifetime::Singleton(service) => return service.clone().downcast().map(Dep).map_err(|_| missing()),
+        Lifetime::PerRequest(factory) => factory.clone(),
+    };
+    // Built once per request; the cache is not held while the factory runs, so
+    // it can resolve services of its own
+    let cache = ctx.cache();
+    if let Some(service) = cache.get::<Dep<T>>() {
+        return Ok(service);
+    }
+    let service: Dep<T> = factory(ctx)?.downcast().map(Dep).map_err(|_| missing())?;
+    cache.insert(service.clone());
+    Ok(service)
+}
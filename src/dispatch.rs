@@ -0,0 +1,234 @@
+// Request dispatch for `#[http_method]` routes: turns an axum request into a
+// `RequestContext`, runs the handler within the configured timeouts and
+// renders its `HandlerResponse`
+
+use crate::blocking::BlockingPool;
+use crate::context::{AppState, RequestContext};
+use crate::envelope::Responder;
+use crate::expect;
+use crate::extract;
+use crate::format::Format;
+use crate::hooks::RouteHooks;
+use crate::query;
+use crate::response::{HandlerResponse, NonObjectResponse};
+use crate::streaming::StreamRequest;
+use crate::{error_response, RouteHandler};
+use axum::extract::{Path, Query, State as AxumState};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{delete, get, patch, post, put, MethodRouter};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+// Server settings every route handler needs
+#[derive(Clone)]
+pub(crate) struct Settings {
+    pub(crate) non_object_response: NonObjectResponse,
+    pub(crate) body_read_timeout: Option<Duration>,
+    pub(crate) handler_timeout: Option<Duration>,
+    pub(crate) max_body_size: usize,
+    // The handler takes the body as `Bytes`, so it is not parsed
+    pub(crate) raw_body: bool,
+    // What the route's `QuerySpec` parameters accept
+    pub(crate) query: query::Allowlist,
+    // Narrow results to the requested `fields` (`Server::sparse_fieldsets`)
+    pub(crate) sparse_fields: bool,
+    // `status = ...`: sent in place of 200 with the handler's results
+    pub(crate) status: Option<StatusCode>,
+    pub(crate) sync_execution: SyncExecution,
+    pub(crate) hooks: RouteHooks,
+}
+
+// Where a synchronous handler runs
+#[derive(Clone)]
+pub(crate) enum SyncExecution {
+    // On the async worker, or on tokio's blocking pool while a timeout applies
+    Default,
+    // `blocking = true` without `Server::blocking_threads`
+    TokioBlocking,
+    // `blocking = true` with `Server::blocking_threads`
+    Dedicated(BlockingPool),
+}
+
+// Convert path or query parameters to a JSON object
+pub(crate) fn params_to_json(params: HashMap<String, String>) -> serde_json::Value {
+    let mut json = serde_json::Map::new();
+    for (key, value) in params {
+        json.insert(key, serde_json::Value::String(value));
+    }
+    serde_json::Value::Object(json)
+}
+
+// Build the axum route for one registration, or None for an unsupported method
+pub(crate) fn method_router(
+    method: &str,
+    handler: RouteHandler,
+    settings: Settings,
+) -> Option<MethodRouter<AppState>> {
+    let Settings {
+        non_object_response,
+        body_read_timeout,
+        handler_timeout,
+        max_body_size,
+        raw_body,
+        query,
+        sparse_fields,
+        status,
+        sync_execution,
+        hooks,
+    } = settings;
+
+    // Create a generic handler that extracts path, query, and body parameters
+    let generic_handler = move |
+        AxumState(state): AxumState<AppState>,
+        Path(path_params): Path<HashMap<String, String>>,
+        Query(query_params): Query<HashMap<String, String>>,
+        request: axum::extract::Request
+    | async move {
+        let path_identifiers = params_to_json(path_params);
+        let query_arguments = params_to_json(query_params);
+        let (parts, body) = request.into_parts();
+        let format = Format::from_accept(&parts.headers);
+        let responder = Responder::of(&parts.extensions);
+        let error_context = responder.context(&parts);
+        let stream_request = StreamRequest::from_headers(&parts.headers);
+
+        // Body parameters - read phase, refusing a declared length over the limit upfront,
+        // before a client waiting on `Expect: 100-continue` sends the body
+        if expect::declares_over(&parts.headers, max_body_size) {
+            return body_too_large(max_body_size);
+        }
+        let read = axum::body::to_bytes(body, max_body_size);
+        let bytes = match body_read_timeout {
+            Some(limit) => match tokio::time::timeout(limit, read).await {
+                Ok(bytes) => bytes,
+                Err(_) => {
+                    return error_response(
+                        StatusCode::REQUEST_TIMEOUT,
+                        format!("Request body was not received within {:?}", limit),
+                    )
+                }
+            },
+            None => read.await,
+        };
+        let bytes = match bytes {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                let over_limit = std::error::Error::source(&err)
+                    .is_some_and(|source| source.is::<http_body_util::LengthLimitError>());
+                if over_limit {
+                    return body_too_large(max_body_size);
+                }
+                return error_response(StatusCode::BAD_REQUEST, "Failed to read request body".to_string());
+            }
+        };
+        let body_value = if raw_body {
+            serde_json::Value::Null
+        } else if extract::is_form(&parts.headers) {
+            match extract::form_to_json(&bytes) {
+                Ok(fields) => fields,
+                Err(err) => return err.into_response(),
+            }
+        } else if let Some(body_format) = Format::from_content_type(&parts.headers).filter(|_| !bytes.is_empty()) {
+            match body_format.decode(&bytes) {
+                Ok(value) => value,
+                Err(err) => return error_response(StatusCode::BAD_REQUEST, format!("Invalid request body: {}", err)),
+            }
+        } else if bytes.is_empty() {
+            serde_json::Value::Null
+        } else {
+            match serde_json::from_slice(&bytes) {
+                Ok(value) => value,
+                Err(err) => return error_response(StatusCode::BAD_REQUEST, format!("Invalid JSON body: {}", err)),
+            }
+        };
+
+        // The fieldset to narrow the result to, refused before the handler runs
+        let mut fieldset = None;
+        if sparse_fields && !query.fields.is_empty() {
+            match query::requested_fields(parts.uri.query(), query.fields) {
+                Ok(fields) => fieldset = fields,
+                Err(err) => return err.into_response(),
+            }
+        }
+        let mut ctx = RequestContext::from_parts(parts, path_identifiers, query_arguments, body_value, state);
+        ctx.raw_body = bytes;
+        ctx.extensions.insert(query);
+        if let Err(err) = hooks.before(&mut ctx) {
+            return err.into_response();
+        }
+
+        // Call the handler with the request context - handler phase; `None` if it
+        // panicked off the async worker
+        let call: Pin<Box<dyn Future<Output = Option<HandlerResponse>> + Send>> =
+            match (&handler, &sync_execution, handler_timeout) {
+                (RouteHandler::Async(f), _, _) => {
+                    let future = f(ctx);
+                    Box::pin(async move { Some(future.await) })
+                }
+                (RouteHandler::Sync(f), SyncExecution::Dedicated(pool), _) => {
+                    let (f, pool) = (f.clone(), pool.clone());
+                    Box::pin(async move { pool.run(move || f(ctx)).await })
+                }
+                // A sync handler can only be cut off if it runs off the async worker
+                (RouteHandler::Sync(f), SyncExecution::TokioBlocking, _) | (RouteHandler::Sync(f), _, Some(_)) => {
+                    let f = f.clone();
+                    Box::pin(async move { tokio::task::spawn_blocking(move || f(ctx)).await.ok() })
+                }
+                (RouteHandler::Sync(f), SyncExecution::Default, None) => {
+                    let value = f(ctx);
+                    Box::pin(async move { Some(value) })
+                }
+            };
+        let outcome = match handler_timeout {
+            Some(limit) => match tokio::time::timeout(limit, call).await {
+                Ok(outcome) => outcome,
+                Err(_) => {
+                    return error_response(
+                        StatusCode::GATEWAY_TIMEOUT,
+                        format!("Handler did not complete within {:?}", limit),
+                    )
+                }
+            },
+            None => call.await,
+        };
+        match outcome {
+            // Convert JSON to HTTP response
+            Some(mut response) => {
+                if let Some(status) = status.filter(|_| response.is_success() && response.status() == StatusCode::OK) {
+                    response = response.with_status(status);
+                }
+                if let Some(fields) = fieldset.as_ref().filter(|_| response.is_success()) {
+                    query::prune(response.body_mut(), fields);
+                }
+                responder
+                    .handle(hooks.after(response), error_context.as_ref())
+                    .for_request(stream_request)
+                    .render(non_object_response, &responder, format)
+            }
+            // A sync handler run off the async worker panicked
+            None => crate::panic_response(),
+        }
+    };
+
+    // Register the route based on HTTP method
+    Some(match method {
+        "GET" => get(generic_handler),
+        "POST" => post(generic_handler),
+        "PUT" => put(generic_handler),
+        "PATCH" => patch(generic_handler),
+        "DELETE" => delete(generic_handler),
+        "HEAD" => axum::routing::head(generic_handler),
+        "OPTIONS" => axum::routing::options(generic_handler),
+        _ => return None,
+    })
+}
+
+pub(crate) fn body_too_large(limit: usize) -> axum::response::Response {
+    error_response(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!("Request body exceeds the limit of {} bytes", limit),
+    )
+}
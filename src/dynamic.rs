@@ -0,0 +1,228 @@
+//! Routes added and removed while the server runs, beside the `#[http_method]` ones.
+//!
+//! ```ignore
+//! let server = Server::new();
+//! let routes = server.dynamic_routes();
+//! let handle = server.start_in_background("0.0.0.0:8080").await?;
+//!
+//! let hook = RouteHandler::from_async(|ctx| async move { deliver(ctx.body).await });
+//! handle.add_route("POST", "/hooks/:name", hook)?;
+//! // Later, from an admin endpoint holding `routes`
+//! routes.remove_route("POST", "/hooks/:name");
+//! ```
+//!
+//! Every change builds a new router for the dynamic routes and swaps it in at
+//! once: requests already dispatched finish on the router they started with,
+//! and later ones see the change. Dynamic routes run inside the server-wide
+//! layers (sessions, rate limits, `layer`s, CORS, metrics, ...) but take none of
+//! the per-route options. A path already served by a registered route is
+//! refused; where a dynamic and a registered path both match a request, the more
+//! specific one serves it, as between registered routes.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
+
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::Router;
+use tower::Service;
+
+use crate::context::AppState;
+use crate::dispatch::{self, Settings};
+use crate::RouteHandler;
+
+/// Why a route could not be added.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteError {
+    /// Not one of GET, POST, PUT, PATCH or DELETE.
+    UnsupportedMethod(String),
+    /// Not a route path, e.g. one without the leading `/`.
+    InvalidPath(String),
+    /// The path cannot be told apart from one already served.
+    Conflict(String),
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteError::UnsupportedMethod(method) => write!(f, "unsupported method {}", method),
+            RouteError::InvalidPath(path) => write!(f, "invalid route path {:?}", path),
+            RouteError::Conflict(message) => write!(f, "conflicting route: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for RouteError {}
+
+/// The routes added at runtime, shared by every clone of the handle.
+#[derive(Clone, Default)]
+pub struct DynamicRoutes {
+    shared: Arc<Shared>,
+}
+
+#[derive(Default)]
+struct Shared {
+    // Edits take turns, each compiling the whole table into a new router
+    table: Mutex<Table>,
+    current: RwLock<Arc<Compiled>>,
+}
+
+#[derive(Default)]
+struct Table {
+    // Handlers per path, then per method
+    routes: BTreeMap<String, BTreeMap<String, RouteHandler>>,
+    // Set once the server's router is built
+    server: Option<Setup>,
+}
+
+// What dynamic routes take from the server they are mounted on
+struct Setup {
+    settings: Settings,
+    state: AppState,
+    reserved: Vec<String>,
+}
+
+// Every path served, `true` for the dynamic ones, and the router serving those
+struct Compiled {
+    matcher: matchit::Router<bool>,
+    router: Option<Router>,
+}
+
+impl Default for Compiled {
+    fn default() -> Self {
+        Compiled {
+            matcher: matchit::Router::new(),
+            router: None,
+        }
+    }
+}
+
+impl DynamicRoutes {
+    /// Serve `method` requests to `path` with `handler`, replacing any handler
+    /// already added for both. The path takes the syntax of `#[http_method]`.
+    pub fn add_route(&self, method: &str, path: &str, handler: RouteHandler) -> Result<(), RouteError> {
+        let method = method.to_ascii_uppercase();
+        if !matches!(method.as_str(), "GET" | "POST" | "PUT" | "PATCH" | "DELETE") {
+            return Err(RouteError::UnsupportedMethod(method));
+        }
+        if !path.starts_with('/') || path.contains("//") {
+            return Err(RouteError::InvalidPath(path.to_string()));
+        }
+        let mut table = self.shared.table.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if !table.routes.contains_key(path) {
+            let mut matcher = self.current().matcher.clone();
+            matcher
+                .insert(path, true)
+                .map_err(|err| RouteError::Conflict(format!("{}: {}", path, err)))?;
+        }
+        table.routes.entry(path.to_string()).or_default().insert(method, handler);
+        self.swap(&table);
+        Ok(())
+    }
+
+    /// Stop serving `method` requests to `path`; `false` if no such route was added.
+    pub fn remove_route(&self, method: &str, path: &str) -> bool {
+        let mut table = self.shared.table.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(methods) = table.routes.get_mut(path) else {
+            return false;
+        };
+        if methods.remove(&method.to_ascii_uppercase()).is_none() {
+            return false;
+        }
+        if methods.is_empty() {
+            table.routes.remove(path);
+        }
+        self.swap(&table);
+        true
+    }
+
+    /// The methods and paths added, in path order.
+    pub fn routes(&self) -> Vec<(String, String)> {
+        let table = self.shared.table.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let routes = table.routes.iter();
+        routes
+            .flat_map(|(path, methods)| methods.keys().map(|method| (method.clone(), path.clone())))
+            .collect()
+    }
+
+    // Mount on a server, whose own paths keep precedence over routes added before it was built
+    pub(crate) fn install(&self, settings: Settings, state: AppState, reserved: Vec<String>) {
+        let mut table = self.shared.table.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut matcher = matcher(&reserved, []);
+        table.routes.retain(|path, _| match matcher.insert(path.as_str(), true) {
+            Ok(()) => true,
+            Err(err) => {
+                tracing::error!("Dropping dynamic route {}: {}", path, err);
+                false
+            }
+        });
+        table.server = Some(Setup {
+            settings,
+            state,
+            reserved,
+        });
+        self.swap(&table);
+    }
+
+    fn current(&self) -> Arc<Compiled> {
+        self.shared.current.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    // Compile the table and put it in place of the current router
+    fn swap(&self, table: &Table) {
+        let reserved = table.server.as_ref().map(|setup| setup.reserved.as_slice()).unwrap_or_default();
+        let compiled = Compiled {
+            matcher: matcher(reserved, table.routes.keys()),
+            router: table.server.as_ref().map(|setup| compile(&table.routes, setup)),
+        };
+        *self.shared.current.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(compiled);
+    }
+}
+
+// Registered paths first, so they win conflicts among themselves as when they were routed
+fn matcher<'a>(reserved: &[String], dynamic: impl IntoIterator<Item = &'a String>) -> matchit::Router<bool> {
+    let mut matcher = matchit::Router::new();
+    for path in reserved {
+        // Paths told apart by constraints share a shape; the first stands for all
+        let _ = matcher.insert(path.as_str(), false);
+    }
+    for path in dynamic {
+        let _ = matcher.insert(path.as_str(), true);
+    }
+    matcher
+}
+
+fn compile(routes: &BTreeMap<String, BTreeMap<String, RouteHandler>>, setup: &Setup) -> Router {
+    let mut router = Router::<AppState>::new();
+    for (path, handlers) in routes {
+        let mut served = Vec::new();
+        let mut route = axum::routing::MethodRouter::new();
+        for (method, handler) in handlers {
+            if let Some(handler) = dispatch::method_router(method, handler.clone(), setup.settings.clone()) {
+                route = route.merge(handler);
+            }
+            served.push(method.as_str());
+            if method == "GET" {
+                served.push("HEAD");
+            }
+        }
+        router = router.route(path, crate::with_method_fallback(route, &served));
+    }
+    router.with_state(setup.state.clone())
+}
+
+// Serve the requests a dynamic route matches, before the rest of `router`
+pub(crate) fn layer(router: Router<AppState>, routes: DynamicRoutes) -> Router<AppState> {
+    router.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        // Held until the response is ready, so swaps never cut a request short
+        let current = routes.current();
+        async move {
+            let dynamic = matches!(current.matcher.at(request.uri().path()), Ok(found) if *found.value);
+            match &current.router {
+                Some(router) if dynamic => router.clone().call(request).await.unwrap_or_else(|never| match never {}),
+                _ => next.run(request).await,
+            }
+        }
+    }))
+}
@@ -0,0 +1,218 @@
+//! The JSON shape wrapped around handler results and errors.
+//!
+//! ```ignore
+//! Server::new().response_envelope(ProblemDetails);
+//! ```
+//!
+//! By default non-object results are sent as `{"success": true, "data": ...,
+//! "message": ""}` and errors as `{"success": false, "data": null, "message":
+//! ...}` (`ApiEnvelope`). `NoEnvelope` sends results and error messages bare,
+//! and `ProblemDetails` answers errors with RFC 7807 `application/problem+json`.
+//! Implement `ResponseEnvelope` for any other shape.
+//!
+//! The envelope applies to every error the framework sends, including 404s,
+//! 405s, authentication failures and rate limit rejections. Objects returned by
+//! handlers are still sent unchanged.
+
+use std::sync::Arc;
+
+use axum::extract::Request;
+use axum::http::header::{CONTENT_LANGUAGE, CONTENT_LENGTH, CONTENT_TYPE};
+use axum::http::{request, Extensions, HeaderValue};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use serde_json::{json, Value};
+
+use crate::context::AppState;
+use crate::error::{ErrorContext, ErrorHook, ErrorMapper, FerroxError};
+use crate::i18n::{Locale, Localization};
+use crate::response::{ApiResponse, HandlerResponse};
+
+/// Builds response bodies around handler results and errors; register it with
+/// `Server::response_envelope`.
+pub trait ResponseEnvelope: Send + Sync + 'static {
+    /// Body for a non-object value a handler returned, under `NonObjectResponse::Envelope`.
+    fn success(&self, data: Value) -> Value;
+
+    /// Body for an error response.
+    fn error(&self, error: &FerroxError) -> Value;
+
+    /// Content type of error bodies sent as JSON.
+    fn error_content_type(&self) -> &'static str {
+        "application/json"
+    }
+}
+
+/// The default `ApiResponse` envelope.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ApiEnvelope;
+
+impl ResponseEnvelope for ApiEnvelope {
+    fn success(&self, data: Value) -> Value {
+        serde_json::to_value(ApiResponse::ok(data)).expect("ApiResponse always serializes")
+    }
+
+    fn error(&self, error: &FerroxError) -> Value {
+        serde_json::to_value(error.envelope()).expect("ApiResponse always serializes")
+    }
+}
+
+/// No wrapping: results are sent as returned and errors as their message string,
+/// or validation errors as their field map.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoEnvelope;
+
+impl ResponseEnvelope for NoEnvelope {
+    fn success(&self, data: Value) -> Value {
+        data
+    }
+
+    fn error(&self, error: &FerroxError) -> Value {
+        error
+            .details()
+            .unwrap_or_else(|| Value::String(error.message().to_string()))
+    }
+}
+
+/// RFC 7807 problem details for errors, e.g.
+/// `{"type": "about:blank", "title": "Not Found", "status": 404, "detail": "User 9 not found"}`.
+///
+/// Validation failures add their field map as `errors`. Results are sent as returned.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProblemDetails;
+
+impl ResponseEnvelope for ProblemDetails {
+    fn success(&self, data: Value) -> Value {
+        data
+    }
+
+    fn error(&self, error: &FerroxError) -> Value {
+        let status = error.status();
+        let mut problem = json!({
+            "type": "about:blank",
+            "title": status.canonical_reason().unwrap_or("Unknown Status"),
+            "status": status.as_u16(),
+            "detail": error.message(),
+        });
+        if let Some(errors) = error.details() {
+            problem["errors"] = errors;
+        }
+        problem
+    }
+
+    fn error_content_type(&self) -> &'static str {
+        "application/problem+json"
+    }
+}
+
+// How errors are answered: the configured envelope, `on_error` hook and
+// `error_mapper`, carried in request extensions to the handlers
+#[derive(Clone, Default)]
+pub(crate) struct Responder {
+    pub(crate) envelope: Option<Arc<dyn ResponseEnvelope>>,
+    pub(crate) on_error: Option<ErrorHook>,
+    pub(crate) error_mapper: Option<ErrorMapper>,
+    pub(crate) localization: Option<Arc<Localization>>,
+    // The request's negotiated locale, under `localization`
+    locale: Option<Locale>,
+    // The request errors answer, under `error_mapper`
+    request: Option<ErrorContext>,
+}
+
+impl Responder {
+    pub(crate) fn is_default(&self) -> bool {
+        self.envelope.is_none() && self.on_error.is_none() && self.error_mapper.is_none() && self.localization.is_none()
+    }
+
+    // The response `error_mapper` builds for `error`, if it builds one
+    pub(crate) fn map(&self, error: &FerroxError) -> Option<Response> {
+        match (&self.error_mapper, &self.request) {
+            (Some(mapper), Some(request)) => mapper(error, request),
+            _ => None,
+        }
+    }
+
+    // The responder for a request, or the default when none is configured
+    pub(crate) fn of(extensions: &Extensions) -> Responder {
+        extensions.get::<Responder>().cloned().unwrap_or_default()
+    }
+
+    pub(crate) fn envelope(&self) -> &dyn ResponseEnvelope {
+        match &self.envelope {
+            Some(envelope) => envelope.as_ref(),
+            None => &ApiEnvelope,
+        }
+    }
+
+    // Context for the `on_error` hook and `error_mapper`, only collected when there is one
+    pub(crate) fn context(&self, parts: &request::Parts) -> Option<ErrorContext> {
+        (self.on_error.is_some() || self.error_mapper.is_some()).then(|| ErrorContext::from_parts(parts))
+    }
+
+    // Pass the error in `response`, if any, through the `on_error` hook, then translate it
+    pub(crate) fn handle(&self, response: HandlerResponse, context: Option<&ErrorContext>) -> HandlerResponse {
+        let response = match (&self.on_error, context) {
+            (Some(hook), Some(context)) => response.map_error(|error| hook(error, context)),
+            _ => response,
+        };
+        match self.locale.is_some() {
+            true => response.map_error(|error| self.translate(error)),
+            false => response,
+        }
+    }
+
+    // `error` in the request's locale
+    fn translate(&self, error: FerroxError) -> FerroxError {
+        match (&self.localization, &self.locale) {
+            (Some(localization), Some(locale)) => localization.translate_error(locale, error),
+            _ => error,
+        }
+    }
+}
+
+// Hand the responder to handlers, and answer the errors the framework sent with
+// the default envelope, which `FerroxError::into_response` marks, through it
+pub(crate) fn layer(router: Router<AppState>, responder: Responder) -> Router<AppState> {
+    router.layer(axum::middleware::from_fn(move |mut request: Request, next: Next| {
+        let mut responder = responder.clone();
+        async move {
+            let (parts, body) = request.into_parts();
+            let context = responder.context(&parts);
+            responder.request = responder.error_mapper.as_ref().and(context.clone());
+            responder.locale = responder.localization.as_ref().map(|localization| localization.negotiate(&parts.headers));
+            request = Request::from_parts(parts, body);
+            if let Some(locale) = &responder.locale {
+                request.extensions_mut().insert(locale.clone());
+            }
+            request.extensions_mut().insert(responder.clone());
+            let mut response = next.run(request).await;
+            // Errors are answered in the request's locale
+            if let Some(locale) = &responder.locale
+                && response.status().as_u16() >= 400
+                && let Ok(language) = HeaderValue::from_str(locale.as_str())
+            {
+                response.headers_mut().entry(CONTENT_LANGUAGE).or_insert(language);
+            }
+            let Some(mut error) = response.extensions_mut().remove::<FerroxError>() else {
+                return response;
+            };
+            if let (Some(hook), Some(context)) = (&responder.on_error, &context) {
+                error = hook(error, context);
+            }
+            let error = responder.translate(error);
+            if let Some(mapped) = responder.map(&error) {
+                return mapped;
+            }
+            let envelope = responder.envelope();
+            let (mut parts, _) = response.into_parts();
+            parts.status = error.status();
+            parts.headers.remove(CONTENT_LENGTH);
+            parts
+                .headers
+                .insert(CONTENT_TYPE, HeaderValue::from_static(envelope.error_content_type()));
+            let body = axum::Json(envelope.error(&error)).into_response().into_body();
+            Response::from_parts(parts, body)
+        }
+    }))
+}
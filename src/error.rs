@@ -0,0 +1,137 @@
+use axum::extract::MatchedPath;
+use axum::http::{request, Method, StatusCode};
+use axum::response::IntoResponse;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::rbac::Denial;
+use crate::response::ApiResponse;
+use crate::validate::ValidationErrors;
+
+/// Error a handler can return to answer with a 4xx/5xx status.
+///
+/// The message is sent to the client in the `message` field of a failed
+/// `ApiResponse` envelope (or as the configured `ResponseEnvelope` renders it),
+/// so it should not carry internal details.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FerroxError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    UnprocessableEntity(String),
+    Internal(String),
+    /// Any other status code.
+    Status(StatusCode, String),
+    /// Failed `Validate` constraints, sent as 422 with the messages by field in `data`.
+    Validation(ValidationErrors),
+    /// A `permission = "..."` route the principal's roles do not grant, sent as 403
+    /// with the permission and roles in `data`.
+    PermissionDenied(Denial),
+}
+
+impl FerroxError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        let message = message.into();
+        match status {
+            StatusCode::BAD_REQUEST => FerroxError::BadRequest(message),
+            StatusCode::UNAUTHORIZED => FerroxError::Unauthorized(message),
+            StatusCode::FORBIDDEN => FerroxError::Forbidden(message),
+            StatusCode::NOT_FOUND => FerroxError::NotFound(message),
+            StatusCode::CONFLICT => FerroxError::Conflict(message),
+            StatusCode::UNPROCESSABLE_ENTITY => FerroxError::UnprocessableEntity(message),
+            StatusCode::INTERNAL_SERVER_ERROR => FerroxError::Internal(message),
+            status => FerroxError::Status(status, message),
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            FerroxError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            FerroxError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            FerroxError::Forbidden(_) => StatusCode::FORBIDDEN,
+            FerroxError::NotFound(_) => StatusCode::NOT_FOUND,
+            FerroxError::Conflict(_) => StatusCode::CONFLICT,
+            FerroxError::UnprocessableEntity(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            FerroxError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            FerroxError::Status(status, _) => *status,
+            FerroxError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            FerroxError::PermissionDenied(_) => StatusCode::FORBIDDEN,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            FerroxError::BadRequest(message)
+            | FerroxError::Unauthorized(message)
+            | FerroxError::Forbidden(message)
+            | FerroxError::NotFound(message)
+            | FerroxError::Conflict(message)
+            | FerroxError::UnprocessableEntity(message)
+            | FerroxError::Internal(message)
+            | FerroxError::Status(_, message) => message,
+            FerroxError::Validation(errors) => errors.message(),
+            FerroxError::PermissionDenied(_) => "Permission denied",
+        }
+    }
+
+    /// Structured detail beyond the message: the field errors of `Validation`, or
+    /// the reason for `PermissionDenied`.
+    pub fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            FerroxError::Validation(errors) => Some(serde_json::to_value(errors).expect("field errors always serialize")),
+            FerroxError::PermissionDenied(denial) => Some(serde_json::to_value(denial).expect("denials always serialize")),
+            _ => None,
+        }
+    }
+
+    /// The failed `ApiResponse` envelope sent for this error.
+    pub fn envelope(&self) -> ApiResponse<serde_json::Value> {
+        ApiResponse {
+            success: false,
+            data: self.details(),
+            message: self.message().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for FerroxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.status(), self.message())
+    }
+}
+
+impl std::error::Error for FerroxError {}
+
+impl IntoResponse for FerroxError {
+    fn into_response(self) -> axum::response::Response {
+        let mut response = (self.status(), axum::Json(self.envelope())).into_response();
+        // Lets the envelope layer re-render the body in the configured shape
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// The request an error answers, passed to the `Server::on_error` hook.
+#[derive(Debug, Clone)]
+pub struct ErrorContext {
+    pub method: Method,
+    pub path: String,
+    /// Pattern of the matched route, e.g. `/users/:id`; `None` for unmatched paths.
+    pub route: Option<String>,
+}
+
+impl ErrorContext {
+    pub(crate) fn from_parts(parts: &request::Parts) -> Self {
+        ErrorContext {
+            method: parts.method.clone(),
+            path: parts.uri.path().to_string(),
+            route: parts.extensions.get::<MatchedPath>().map(|matched| matched.as_str().to_string()),
+        }
+    }
+}
+
+pub(crate) type ErrorHook = Arc<dyn Fn(FerroxError, &ErrorContext) -> FerroxError + Send + Sync>;
+
+pub(crate) type ErrorMapper = Arc<dyn Fn(&FerroxError, &ErrorContext) -> Option<axum::response::Response> + Send + Sync>;
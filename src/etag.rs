@@ -0,0 +1,297 @@
+//! Conditional requests: strong ETags for JSON responses, and `Last-Modified`
+//! checks, answered with 304 Not Modified when the client's copy is current.
+//!
+//! ```ignore
+//! Server::new().etags(true);
+//!
+//! #[http_method(GET, "/report")]
+//! async fn report(ctx: &RequestContext) -> HandlerResponse {
+//!     let modified = report_modified_at().await;
+//!     if etag::not_modified_since(ctx, modified) {
+//!         return etag::not_modified(modified);
+//!     }
+//!     HandlerResponse::new(StatusCode::OK, build_report().await).with_last_modified(modified)
+//! }
+//! ```
+//!
+//! With `Server::etags`, successful GET and HEAD responses with a JSON body get
+//! an `ETag` computed from the body, unless the handler set one, and requests
+//! whose `If-None-Match` lists it are answered with 304 and no body. Responses
+//! carrying `Last-Modified` are also answered with 304 when `If-Modified-Since`
+//! is not older, for requests without `If-None-Match`. The handler still runs;
+//! `not_modified_since` lets it skip the work when only the date is needed.
+//!
+//! Writes are guarded against lost updates by the resource's [`ResourceVersion`]:
+//!
+//! ```ignore
+//! #[http_method(GET, "/documents/:id")]
+//! async fn show(id: u64) -> Result<HandlerResponse, FerroxError> {
+//!     let doc = Document::find(id).await?;
+//!     Ok(HandlerResponse::new(StatusCode::OK, json!(doc)).with_version(&doc.version()))
+//! }
+//!
+//! #[http_method(PUT, "/documents/:id", if_match = true)]
+//! async fn update(ctx: &RequestContext, id: u64, body: Edit) -> Result<HandlerResponse, FerroxError> {
+//!     let doc = Document::find(id).await?;
+//!     etag::check_precondition(ctx, &doc.version())?;
+//!     let doc = doc.apply(body).save().await?;
+//!     Ok(HandlerResponse::new(StatusCode::OK, json!(doc)).with_version(&doc.version()))
+//! }
+//!
+//! impl Document {
+//!     fn version(&self) -> ResourceVersion {
+//!         ResourceVersion::tag(self.revision).with_modified(self.updated_at)
+//!     }
+//! }
+//! ```
+//!
+//! `HandlerResponse::with_version` sends the version as `ETag` and
+//! `Last-Modified`. `check_precondition` answers 412 Precondition Failed when
+//! the request's `If-Match` lists none of the current version's tags (strongly
+//! compared; `*` matches any), or, without `If-Match`, when the resource changed
+//! after its `If-Unmodified-Since`; requests with neither go through. Routes with
+//! `if_match = true` refuse PUT, PATCH and DELETE requests with neither header
+//! with 428 Precondition Required, before the handler runs, so clients cannot
+//! skip the check by leaving the headers out.
+
+use std::fmt;
+use std::time::SystemTime;
+
+use axum::body::{Body, HttpBody};
+use axum::extract::Request;
+use axum::http::header::{
+    CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_UNMODIFIED_SINCE, LAST_MODIFIED,
+};
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::MethodRouter;
+use axum::Router;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::context::{AppState, RequestContext};
+use crate::error::FerroxError;
+use crate::error_response;
+use crate::response::HandlerResponse;
+
+/// The current version of a resource, which writes to it must name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceVersion {
+    etag: Option<String>,
+    modified: Option<SystemTime>,
+}
+
+impl ResourceVersion {
+    /// The version named by `tag`, such as a revision number, sent as the
+    /// strong ETag `"tag"`; `"` characters in it are dropped.
+    pub fn tag(tag: impl fmt::Display) -> Self {
+        let tag = tag.to_string().replace('"', "");
+        ResourceVersion {
+            etag: Some(format!("\"{}\"", tag)),
+            modified: None,
+        }
+    }
+
+    /// The version of `value`, tagged with the hash of its JSON as `strong_etag` does.
+    pub fn of<T: Serialize>(value: &T) -> Self {
+        let json = serde_json::to_vec(value).unwrap_or_default();
+        ResourceVersion {
+            etag: Some(strong_etag(&json)),
+            modified: None,
+        }
+    }
+
+    /// A version known only by when it last changed, checked against `If-Unmodified-Since`.
+    pub fn modified_at(modified: SystemTime) -> Self {
+        ResourceVersion {
+            etag: None,
+            modified: Some(modified),
+        }
+    }
+
+    /// Also date the version, for clients sending `If-Unmodified-Since`.
+    pub fn with_modified(mut self, modified: SystemTime) -> Self {
+        self.modified = Some(modified);
+        self
+    }
+
+    /// The ETag, quoted as the header carries it.
+    pub fn etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+}
+
+/// Check the request's preconditions against the resource's `current` version,
+/// failing with 412 Precondition Failed when the client's copy is out of date.
+pub fn check_precondition(ctx: &RequestContext, current: &ResourceVersion) -> Result<(), FerroxError> {
+    let failed = || {
+        FerroxError::new(
+            StatusCode::PRECONDITION_FAILED,
+            "The resource has changed since it was read; fetch it again and retry",
+        )
+    };
+    if ctx.headers.contains_key(IF_MATCH) {
+        return match current.etag() {
+            Some(etag) if matches_strongly(&ctx.headers, etag) => Ok(()),
+            _ if lists_any(&ctx.headers) => Ok(()),
+            _ => Err(failed()),
+        };
+    }
+    let since = ctx
+        .headers
+        .get(IF_UNMODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok());
+    match (since, current.modified()) {
+        (Some(since), Some(modified)) if !unchanged_since_date(modified, since) => Err(failed()),
+        _ => Ok(()),
+    }
+}
+
+/// Whether the client's copy, dated by `If-Modified-Since`, is at least as recent
+/// as `modified`, so the handler can answer with `not_modified`.
+pub fn not_modified_since(ctx: &RequestContext, modified: SystemTime) -> bool {
+    !ctx.headers.contains_key(IF_NONE_MATCH) && unchanged_since(&ctx.headers, modified)
+}
+
+/// A 304 Not Modified response carrying `modified` as `Last-Modified`.
+pub fn not_modified(modified: SystemTime) -> HandlerResponse {
+    HandlerResponse::new(StatusCode::NOT_MODIFIED, serde_json::Value::Null).with_last_modified(modified)
+}
+
+/// The strong ETag for a response body, quoted as the header carries it.
+pub fn strong_etag(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    // 128 bits are plenty to tell versions of one resource apart
+    let hex: String = digest[..16].iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("\"{}\"", hex)
+}
+
+fn unchanged_since(headers: &HeaderMap, modified: SystemTime) -> bool {
+    let Some(since) = headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok())
+    else {
+        return false;
+    };
+    unchanged_since_date(modified, since)
+}
+
+fn unchanged_since_date(modified: SystemTime, since: SystemTime) -> bool {
+    // HTTP dates have whole seconds
+    let modified = httpdate::parse_http_date(&httpdate::fmt_http_date(modified)).unwrap_or(modified);
+    modified <= since
+}
+
+// Whether `If-Match` lists `etag`, compared strongly: weak tags never match
+fn matches_strongly(headers: &HeaderMap, etag: &str) -> bool {
+    !etag.starts_with("W/") && if_match_tags(headers).any(|candidate| candidate == etag)
+}
+
+// Whether `If-Match` is `*`, matching any current version
+fn lists_any(headers: &HeaderMap) -> bool {
+    if_match_tags(headers).any(|candidate| candidate == "*")
+}
+
+fn if_match_tags(headers: &HeaderMap) -> impl Iterator<Item = &str> {
+    headers
+        .get_all(IF_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+}
+
+// Whether `If-None-Match` lists `etag`, compared weakly as RFC 9110 requires
+fn matches_etag(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+    headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|candidate| candidate.trim() == "*" || opaque(candidate) == etag)
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|media_type| {
+            let media_type = media_type.trim();
+            media_type.eq_ignore_ascii_case("application/json") || media_type.ends_with("+json")
+        })
+}
+
+// ETags and 304s for the responses of `router`
+pub(crate) fn layer(router: Router<AppState>) -> Router<AppState> {
+    router.layer(axum::middleware::from_fn(conditional))
+}
+
+async fn conditional(request: Request, next: Next) -> Response {
+    if !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return next.run(request).await;
+    }
+    let headers = request.headers().clone();
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    // Only bodies already in memory are hashed; streams are sent as they are
+    let response = if !response.headers().contains_key(ETAG)
+        && is_json(response.headers())
+        && response.body().size_hint().exact().is_some()
+    {
+        let (mut parts, body) = response.into_parts();
+        let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read response".to_string());
+        };
+        if let Ok(etag) = HeaderValue::from_str(&strong_etag(&bytes)) {
+            parts.headers.insert(ETAG, etag);
+        }
+        Response::from_parts(parts, Body::from(bytes))
+    } else {
+        response
+    };
+
+    let fresh = match response.headers().get(ETAG).and_then(|value| value.to_str().ok()) {
+        Some(etag) if headers.contains_key(IF_NONE_MATCH) => matches_etag(&headers, etag),
+        _ if headers.contains_key(IF_NONE_MATCH) => false,
+        _ => response
+            .headers()
+            .get(LAST_MODIFIED)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| httpdate::parse_http_date(value).ok())
+            .is_some_and(|modified| unchanged_since(&headers, modified)),
+    };
+    if !fresh {
+        return response;
+    }
+    let (mut parts, _) = response.into_parts();
+    parts.status = StatusCode::NOT_MODIFIED;
+    parts.headers.remove(CONTENT_TYPE);
+    parts.headers.remove(CONTENT_LENGTH);
+    Response::from_parts(parts, Body::empty())
+}
+
+// Refuse PUT, PATCH and DELETE requests that name no version with 428
+pub(crate) fn require_precondition(route: MethodRouter<AppState>) -> MethodRouter<AppState> {
+    route.layer(axum::middleware::from_fn(|request: Request, next: Next| async move {
+        let writes = matches!(*request.method(), Method::PUT | Method::PATCH | Method::DELETE);
+        let conditional = request.headers().contains_key(IF_MATCH) || request.headers().contains_key(IF_UNMODIFIED_SINCE);
+        if writes && !conditional {
+            let message = "This request must be conditional: send If-Match with the resource's ETag";
+            return FerroxError::new(StatusCode::PRECONDITION_REQUIRED, message).into_response();
+        }
+        next.run(request).await
+    }))
+}
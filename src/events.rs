@@ -0,0 +1,454 @@
+//! In-process events between handlers and background subscribers.
+//!
+//! ```ignore
+//! #[derive(Clone, Serialize, Deserialize)]
+//! struct UserCreated {
+//!     id: u64,
+//!     email: String,
+//! }
+//!
+//! #[subscribe]
+//! async fn send_welcome(user: UserCreated, mailer: State<Mailer>) -> Result<(), MailError> {
+//!     mailer.welcome(&user.email).await
+//! }
+//!
+//! Server::new().event_bus(EventBus::new());
+//!
+//! #[http_method(POST, "/users")]
+//! async fn create_user(body: NewUser, bus: State<EventBus>) -> Result<Value, FerroxError> {
+//!     let user = save(body).await?;
+//!     bus.publish(UserCreated { id: user.id, email: user.email.clone() });
+//!     Ok(json!(user))
+//! }
+//! ```
+//!
+//! `publish` returns at once. Each subscriber to the event's type gets its own
+//! copy in its own task, so a slow or failing subscriber holds up neither the
+//! request nor the other subscribers; errors and panics are logged under the
+//! `ferrox::events` target and the event is not delivered again. Subscribers
+//! are `#[subscribe]` functions, taking the event and `State<S>` parameters, or
+//! closures given to [`EventBus::subscribe`]. Any `Clone + Send + Sync` type
+//! can be an event.
+//!
+//! `Server::event_bus` shares the bus with handlers as `State<EventBus>` and
+//! hands events to the `#[subscribe]` functions once the server is built. On
+//! shutdown, deliveries still running get up to the flush timeout (10 seconds
+//! by default).
+//!
+//! [`EventBus::bridge`] also carries one event type through an external broker,
+//! as JSON under a topic, so that every instance of a service sees it: events
+//! published on the bus are sent to the broker, and events published by other
+//! instances are handed to the local subscribers. A [`Bridge`] connects the bus
+//! to a broker; with the `redis` feature, `RedisBridge` uses Redis pub/sub, and
+//! other brokers plug in by implementing the trait.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{BuildHasher, RandomState};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use futures_util::stream::BoxStream;
+use futures_util::{FutureExt, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+use crate::context::AppState;
+use crate::scheduler::{TaskFuture, TaskOutput};
+
+const DEFAULT_FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Wait before subscribing to a bridged topic again once its stream has ended
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+type Payload = Arc<dyn Any + Send + Sync>;
+
+type SubscriberFn = Arc<dyn Fn(AppState, &Payload) -> TaskFuture + Send + Sync>;
+
+/// A function receiving the events of one type, as made by `#[subscribe]`.
+#[derive(Clone)]
+pub struct Subscriber {
+    event: TypeId,
+    event_name: &'static str,
+    run: SubscriberFn,
+}
+
+impl Subscriber {
+    pub fn from_async<E, F, Fut>(subscriber: F) -> Self
+    where
+        E: Clone + Send + Sync + 'static,
+        F: Fn(AppState, E) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: TaskOutput,
+    {
+        Subscriber {
+            event: TypeId::of::<E>(),
+            event_name: std::any::type_name::<E>(),
+            run: Arc::new(move |state, event| {
+                let run = subscriber(state, downcast::<E>(event));
+                Box::pin(async move { run.await.into_result() })
+            }),
+        }
+    }
+
+    /// A blocking subscriber, run on the blocking thread pool.
+    pub fn from_sync<E, F, R>(subscriber: F) -> Self
+    where
+        E: Clone + Send + Sync + 'static,
+        F: Fn(AppState, E) -> R + Send + Sync + 'static,
+        R: TaskOutput + Send + 'static,
+    {
+        let subscriber = Arc::new(subscriber);
+        Subscriber {
+            event: TypeId::of::<E>(),
+            event_name: std::any::type_name::<E>(),
+            run: Arc::new(move |state, event| {
+                let (subscriber, event) = (subscriber.clone(), downcast::<E>(event));
+                Box::pin(async move {
+                    match tokio::task::spawn_blocking(move || subscriber(state, event)).await {
+                        Ok(output) => output.into_result(),
+                        Err(_) => Err("subscriber panicked".to_string()),
+                    }
+                })
+            }),
+        }
+    }
+}
+
+fn downcast<E: Clone + 'static>(event: &Payload) -> E {
+    event.downcast_ref::<E>().expect("events only reach subscribers of their type").clone()
+}
+
+/// A subscriber registered by `#[subscribe]`.
+pub struct SubscriberRegistration {
+    /// Name of the annotated function.
+    pub name: &'static str,
+    pub subscriber: fn() -> Subscriber,
+    /// `file:line` of the attribute, for log messages.
+    pub location: &'static str,
+}
+
+inventory::collect!(SubscriberRegistration);
+
+/// Carries serialized events to and from an external broker, for [`EventBus::bridge`].
+pub trait Bridge: Send + Sync + 'static {
+    /// Send `payload` to everyone subscribed to `topic`.
+    fn publish(&self, topic: &str, payload: Vec<u8>) -> BoxFuture<'static, Result<(), String>>;
+
+    /// The payloads sent to `topic` from now on, by any instance; the bus
+    /// subscribes again when the stream ends.
+    fn subscribe(&self, topic: &str) -> BoxFuture<'static, Result<BoxStream<'static, Vec<u8>>, String>>;
+}
+
+// What crosses the broker: the event and the bus that published it, which
+// skips its own events when they come back
+#[derive(Serialize, Deserialize)]
+struct Envelope<O, E> {
+    origin: O,
+    event: E,
+}
+
+// Where one event type is sent beyond the process
+struct Outbound {
+    topic: String,
+    bridge: Arc<dyn Bridge>,
+    encode: fn(&Payload, &str) -> Result<Vec<u8>, serde_json::Error>,
+}
+
+fn encode<E: Serialize + 'static>(event: &Payload, origin: &str) -> Result<Vec<u8>, serde_json::Error> {
+    let event = event.downcast_ref::<E>().expect("events only reach bridges of their type");
+    serde_json::to_vec(&Envelope { origin, event })
+}
+
+struct Named {
+    name: String,
+    subscriber: Subscriber,
+}
+
+/// Delivers published events to the subscribers of their type; cheap to
+/// clone, clones share subscribers.
+///
+/// Register it with `Server::event_bus` to take it as a `State<EventBus>`
+/// handler parameter and run the `#[subscribe]` functions.
+#[derive(Clone)]
+pub struct EventBus {
+    inner: Arc<Inner>,
+    flush_timeout: Duration,
+}
+
+struct Inner {
+    // Tells this bus's events apart from other instances' on a bridge
+    id: String,
+    subscribers: RwLock<HashMap<TypeId, Vec<Arc<Named>>>>,
+    bridges: RwLock<HashMap<TypeId, Vec<Outbound>>>,
+    consumers: Mutex<Vec<JoinHandle<()>>>,
+    // The state `#[subscribe]` functions take their parameters from, once bound
+    state: RwLock<Option<AppState>>,
+    bound: AtomicBool,
+    // Deliveries and bridge publishes running
+    pending: AtomicUsize,
+    idle: Notify,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let id = format!("{:016x}", RandomState::new().hash_one(NEXT.fetch_add(1, Ordering::Relaxed)));
+        EventBus {
+            inner: Arc::new(Inner {
+                id,
+                subscribers: RwLock::new(HashMap::new()),
+                bridges: RwLock::new(HashMap::new()),
+                consumers: Mutex::new(Vec::new()),
+                state: RwLock::new(None),
+                bound: AtomicBool::new(false),
+                pending: AtomicUsize::new(0),
+                idle: Notify::new(),
+            }),
+            flush_timeout: DEFAULT_FLUSH_TIMEOUT,
+        }
+    }
+
+    /// How long `Server` waits for running deliveries once it has stopped (10 seconds by default).
+    pub fn flush_timeout(mut self, timeout: Duration) -> Self {
+        self.flush_timeout = timeout;
+        self
+    }
+
+    /// Call `subscriber` with every event of type `E` published from now on;
+    /// `name` identifies it in log messages.
+    pub fn subscribe<E, F, Fut>(&self, name: &str, subscriber: F)
+    where
+        E: Clone + Send + Sync + 'static,
+        F: Fn(E) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: TaskOutput,
+    {
+        self.add(name, Subscriber::from_async(move |_, event: E| subscriber(event)));
+    }
+
+    fn add(&self, name: &str, subscriber: Subscriber) {
+        let named = Arc::new(Named {
+            name: name.to_string(),
+            subscriber,
+        });
+        self.inner.subscribers.write().unwrap().entry(named.subscriber.event).or_default().push(named);
+    }
+
+    /// Carry events of type `E` through `bridge` under `topic`: those published
+    /// on this bus are sent to it, and those other instances sent to it are
+    /// handed to this bus's subscribers. Call it inside the Tokio runtime, where
+    /// the topic is consumed.
+    pub fn bridge<E>(&self, topic: &str, bridge: impl Bridge)
+    where
+        E: Clone + Send + Sync + Serialize + DeserializeOwned + 'static,
+    {
+        let bridge: Arc<dyn Bridge> = Arc::new(bridge);
+        let consumer = tokio::spawn(consume::<E>(Arc::downgrade(&self.inner), topic.to_string(), bridge.clone()));
+        self.inner.consumers.lock().unwrap().push(consumer);
+        self.inner.bridges.write().unwrap().entry(TypeId::of::<E>()).or_default().push(Outbound {
+            topic: topic.to_string(),
+            bridge,
+            encode: encode::<E>,
+        });
+    }
+
+    /// Hand `event` to every subscriber of its type and bridge, returning how
+    /// many subscribers in this process will receive it.
+    pub fn publish<E: Clone + Send + Sync + 'static>(&self, event: E) -> usize {
+        let event: Payload = Arc::new(event);
+        let bridges = self.inner.bridges.read().unwrap();
+        for outbound in bridges.get(&TypeId::of::<E>()).into_iter().flatten() {
+            let topic = outbound.topic.clone();
+            match (outbound.encode)(&event, &self.inner.id) {
+                Ok(payload) => {
+                    let publish = outbound.bridge.publish(&topic, payload);
+                    self.inner.spawn(async move {
+                        if let Err(err) = publish.await {
+                            tracing::error!(target: "ferrox::events", topic, "Bridging event failed: {}", err);
+                        }
+                    });
+                }
+                Err(err) => tracing::error!(target: "ferrox::events", topic, "Event does not serialize: {}", err),
+            }
+        }
+        drop(bridges);
+        self.inner.deliver(TypeId::of::<E>(), event)
+    }
+
+    /// Deliveries and bridge publishes still running.
+    pub fn pending(&self) -> usize {
+        self.inner.pending.load(Ordering::SeqCst)
+    }
+
+    /// Wait up to `timeout` for the running deliveries, returning whether they
+    /// all finished; `Server` calls this on shutdown with the flush timeout.
+    pub async fn flush(&self, timeout: Duration) -> bool {
+        let flushed = tokio::time::timeout(timeout, async {
+            loop {
+                // Registered before checking, so a delivery finishing in between still wakes us
+                let idle = self.inner.idle.notified();
+                tokio::pin!(idle);
+                idle.as_mut().enable();
+                if self.pending() == 0 {
+                    break;
+                }
+                idle.await;
+            }
+        })
+        .await
+        .is_ok();
+        if !flushed {
+            tracing::warn!(target: "ferrox::events", "{} event deliveries did not finish within {:?}", self.pending(), timeout);
+        }
+        flushed
+    }
+
+    pub(crate) fn timeout(&self) -> Duration {
+        self.flush_timeout
+    }
+
+    // Add the `#[subscribe]` functions, taking their parameters from `state`
+    pub(crate) fn bind(&self, state: AppState) {
+        *self.inner.state.write().unwrap() = Some(state);
+        if self.inner.bound.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        for registration in inventory::iter::<SubscriberRegistration> {
+            tracing::debug!(target: "ferrox::events", subscriber = registration.name, "Event subscriber registered");
+            self.add(registration.name, (registration.subscriber)());
+        }
+    }
+
+    // Stop consuming bridged topics and let go of the application state, which holds the bus
+    pub(crate) fn close(&self) {
+        for consumer in self.inner.consumers.lock().unwrap().drain(..) {
+            consumer.abort();
+        }
+        self.inner.state.write().unwrap().take();
+    }
+}
+
+impl Inner {
+    // Hand `event` to the subscribers in this process
+    fn deliver(self: &Arc<Self>, event_type: TypeId, event: Payload) -> usize {
+        let subscribers = self.subscribers.read().unwrap().get(&event_type).cloned().unwrap_or_default();
+        let state = self.state.read().unwrap().clone().unwrap_or_default();
+        for named in &subscribers {
+            let run = (named.subscriber.run)(state.clone(), &event);
+            let (named, event_name) = (named.clone(), named.subscriber.event_name);
+            self.spawn(async move {
+                match AssertUnwindSafe(run).catch_unwind().await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(message)) => tracing::error!(
+                        target: "ferrox::events",
+                        subscriber = %named.name,
+                        event = event_name,
+                        "Event subscriber failed: {}",
+                        message
+                    ),
+                    Err(_) => tracing::error!(
+                        target: "ferrox::events",
+                        subscriber = %named.name,
+                        event = event_name,
+                        "Event subscriber panicked"
+                    ),
+                }
+            });
+        }
+        subscribers.len()
+    }
+
+    fn spawn(self: &Arc<Self>, task: impl Future<Output = ()> + Send + 'static) {
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        let inner = self.clone();
+        tokio::spawn(async move {
+            task.await;
+            if inner.pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+                inner.idle.notify_waiters();
+            }
+        });
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus::new()
+    }
+}
+
+// Hand the events other instances send to `topic` to the bus's subscribers, until the bus is dropped
+async fn consume<E>(bus: std::sync::Weak<Inner>, topic: String, bridge: Arc<dyn Bridge>)
+where
+    E: Clone + Send + Sync + DeserializeOwned + 'static,
+{
+    loop {
+        match bridge.subscribe(&topic).await {
+            Ok(mut payloads) => {
+                while let Some(payload) = payloads.next().await {
+                    let Some(inner) = bus.upgrade() else {
+                        return;
+                    };
+                    match serde_json::from_slice::<Envelope<String, E>>(&payload) {
+                        Ok(envelope) if envelope.origin == inner.id => {}
+                        Ok(envelope) => {
+                            inner.deliver(TypeId::of::<E>(), Arc::new(envelope.event));
+                        }
+                        Err(err) => {
+                            tracing::warn!(target: "ferrox::events", topic, "Ignoring a bridged event: {}", err)
+                        }
+                    }
+                }
+                tracing::warn!(target: "ferrox::events", topic, "Bridged topic ended; subscribing again");
+            }
+            Err(err) => tracing::error!(target: "ferrox::events", topic, "Subscribing to a bridged topic failed: {}", err),
+        }
+        tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+        if bus.strong_count() == 0 {
+            return;
+        }
+    }
+}
+
+/// A [`Bridge`] over Redis pub/sub (the `redis` feature): topics are channels.
+#[cfg(feature = "redis")]
+#[derive(Clone)]
+pub struct RedisBridge {
+    client: ::redis::Client,
+    connection: ::redis::aio::ConnectionManager,
+}
+
+#[cfg(feature = "redis")]
+impl RedisBridge {
+    /// Connect to the Redis server at `url`, e.g. `redis://127.0.0.1/`.
+    pub async fn connect(url: &str) -> Result<Self, ::redis::RedisError> {
+        let client = ::redis::Client::open(url)?;
+        let connection = ::redis::aio::ConnectionManager::new(client.clone()).await?;
+        Ok(RedisBridge { client, connection })
+    }
+}
+
+#[cfg(feature = "redis")]
+impl Bridge for RedisBridge {
+    fn publish(&self, topic: &str, payload: Vec<u8>) -> BoxFuture<'static, Result<(), String>> {
+        use ::redis::AsyncCommands;
+
+        let (mut connection, topic) = (self.connection.clone(), topic.to_string());
+        Box::pin(async move { connection.publish::<_, _, ()>(topic, payload).await.map_err(|err| err.to_string()) })
+    }
+
+    fn subscribe(&self, topic: &str) -> BoxFuture<'static, Result<BoxStream<'static, Vec<u8>>, String>> {
+        let (client, topic) = (self.client.clone(), topic.to_string());
+        Box::pin(async move {
+            let mut pubsub = client.get_async_pubsub().await.map_err(|err| err.to_string())?;
+            pubsub.subscribe(&topic).await.map_err(|err| err.to_string())?;
+            Ok(pubsub.into_on_message().map(|message| message.get_payload_bytes().to_vec()).boxed())
+        })
+    }
+}
@@ -0,0 +1,48 @@
+// `Expect: 100-continue`: a client about to upload a large body sends its
+// headers first and waits for `100 Continue` before sending the body. hyper
+// sends it the first time the body is read, so a request refused before then
+// (a declared `Content-Length` over the route's `max_body_size`, failed
+// authentication, a rate limit) is answered with its 413, 401 or 429 without
+// the body ever being sent. Every layer that reads the body checks the
+// declared length first, and the body log and recorder leave the body of such
+// requests unread until the handler wants it. Expectations other than
+// `100-continue` are refused with 417 Expectation Failed.
+
+use axum::extract::Request;
+use axum::http::header::{CONTENT_LENGTH, EXPECT};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::Router;
+
+use crate::error::FerroxError;
+
+// Whether the client waits for `100 Continue` before sending the body
+pub(crate) fn awaits_continue(headers: &HeaderMap) -> bool {
+    headers
+        .get(EXPECT)
+        .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"100-continue"))
+}
+
+// Whether the request declares a body over `limit`, so it can be refused before any is read
+pub(crate) fn declares_over(headers: &HeaderMap, limit: usize) -> bool {
+    headers
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .is_some_and(|length| length > limit as u64)
+}
+
+// Refuse the expectations the server cannot meet
+pub(crate) fn layer(router: Router) -> Router {
+    router.layer(axum::middleware::from_fn(|request: Request, next: Next| async move {
+        match request.headers().get(EXPECT) {
+            Some(_) if !awaits_continue(request.headers()) => FerroxError::new(
+                StatusCode::EXPECTATION_FAILED,
+                "Only the 100-continue expectation is supported",
+            )
+            .into_response(),
+            _ => next.run(request).await,
+        }
+    }))
+}
@@ -0,0 +1,152 @@
+//! Deserialization helpers used by `#[http_method]` for typed handler parameters.
+//!
+//! Path, query and form values arrive as strings, so typed targets (`u64`, `bool`,
+//! structs with numeric fields) are parsed the same way axum's `Query` does.
+//! Every failure becomes a `FerroxError::BadRequest` naming the source.
+//!
+//! Applications add their own parameter types by implementing
+//! [`FromFerroxRequest`]:
+//!
+//! ```ignore
+//! struct CurrentUser(User);
+//!
+//! impl FromFerroxRequest for CurrentUser {
+//!     async fn from_request(ctx: &RequestContext) -> Result<Self, FerroxError> {
+//!         let claims = ferrox::auth::identity::<Claims>(ctx)?;
+//!         let db = ctx.state::<Db>().ok_or_else(|| FerroxError::Internal("No database".into()))?;
+//!         db.user(&claims.sub).await.map(CurrentUser).ok_or_else(|| FerroxError::Unauthorized("Unknown user".into()))
+//!     }
+//! }
+//!
+//! #[http_method(GET, "/me", auth = "jwt")]
+//! async fn me(user: CurrentUser) -> Json<User> {
+//!     Json(user.0)
+//! }
+//! ```
+
+use std::future::Future;
+
+use axum::http::header::CONTENT_TYPE;
+use axum::http::HeaderMap;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::context::{RequestContext, State};
+use crate::error::FerroxError;
+use crate::format::Format;
+
+/// A handler parameter type the application extracts from the request itself,
+/// e.g. the signed-in `CurrentUser` or a `TenantDb` connection.
+///
+/// In an `async fn` `#[http_method]` or `#[sse]` handler, a parameter whose
+/// name is not a path placeholder, `path`, `query` or `body`, and whose type is
+/// not one the framework provides, is extracted with this trait, whatever its
+/// name. Extractors run in parameter order, after the route's middleware,
+/// authentication and guards; an error is sent as the response and the handler
+/// does not run. A handler whose parameters are exactly three such extractors
+/// is read as `(path, query, body)` instead, so give one of them a source name
+/// or add a `&RequestContext` parameter.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be taken from the request",
+    label = "not a path placeholder, `path`, `query` or `body`, and no `FromFerroxRequest` impl",
+    note = "rename the parameter after its source, or implement `ferrox::FromFerroxRequest` for `{Self}`"
+)]
+pub trait FromFerroxRequest: Sized + Send {
+    fn from_request(ctx: &RequestContext) -> impl Future<Output = Result<Self, FerroxError>> + Send;
+}
+
+/// Deserialize a single path parameter, e.g. `id: u64` for `/users/:id`.
+pub fn path_param<T: DeserializeOwned>(path: &Value, name: &str) -> Result<T, FerroxError> {
+    let raw = path
+        .get(name)
+        .ok_or_else(|| FerroxError::BadRequest(format!("Missing path parameter `{}`", name)))?;
+
+    serde_json::from_value(raw.clone()).or_else(|err| {
+        // "42" should still deserialize into a number or bool
+        raw.as_str()
+            .and_then(|text| serde_json::from_str(text).ok())
+            .ok_or_else(|| FerroxError::BadRequest(format!("Invalid path parameter `{}`: {}", name, err)))
+    })
+}
+
+/// Deserialize all path parameters into one type.
+pub fn path<T: DeserializeOwned>(path: &Value) -> Result<T, FerroxError> {
+    from_string_map(path).map_err(|err| FerroxError::BadRequest(format!("Invalid path parameters: {}", err)))
+}
+
+/// Deserialize the query string parameters.
+pub fn query<T: DeserializeOwned>(query: &Value) -> Result<T, FerroxError> {
+    from_string_map(query).map_err(|err| FerroxError::BadRequest(format!("Invalid query string: {}", err)))
+}
+
+/// Deserialize the JSON request body. A missing body is `null`.
+pub fn body<T: DeserializeOwned>(body: &Value) -> Result<T, FerroxError> {
+    serde_json::from_value(body.clone())
+        .map_err(|err| FerroxError::BadRequest(format!("Invalid request body: {}", err)))
+}
+
+/// Deserialize the request body, JSON, an urlencoded form or another enabled format.
+pub fn request_body<T: DeserializeOwned>(ctx: &RequestContext) -> Result<T, FerroxError> {
+    if is_form(&ctx.headers) {
+        return from_string_map(&ctx.body)
+            .map_err(|err| FerroxError::BadRequest(format!("Invalid form body: {}", err)));
+    }
+    if Format::from_content_type(&ctx.headers).is_some_and(Format::has_text_values) {
+        return from_string_map(&ctx.body)
+            .map_err(|err| FerroxError::BadRequest(format!("Invalid request body: {}", err)));
+    }
+    body(&ctx.body)
+}
+
+// Whether the request body is `application/x-www-form-urlencoded`
+pub(crate) fn is_form(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case("application/x-www-form-urlencoded"))
+}
+
+// Form fields as a JSON object of strings; a repeated field becomes an array
+pub(crate) fn form_to_json(bytes: &[u8]) -> Result<Value, FerroxError> {
+    let pairs: Vec<(String, String)> = serde_urlencoded::from_bytes(bytes)
+        .map_err(|err| FerroxError::BadRequest(format!("Invalid form body: {}", err)))?;
+    let mut fields = serde_json::Map::new();
+    for (key, value) in pairs {
+        match fields.get_mut(&key) {
+            Some(Value::Array(values)) => values.push(Value::String(value)),
+            Some(first) => *first = Value::Array(vec![first.take(), Value::String(value)]),
+            None => {
+                fields.insert(key, Value::String(value));
+            }
+        }
+    }
+    Ok(Value::Object(fields))
+}
+
+/// Fetch shared state registered with `Server::with_state`.
+pub fn state<S: Clone + Send + Sync + 'static>(ctx: &RequestContext) -> Result<State<S>, FerroxError> {
+    ctx.state::<S>().map(State).ok_or_else(|| {
+        tracing::error!(
+            "No state of type {} was registered with Server::with_state",
+            std::any::type_name::<S>()
+        );
+        FerroxError::Internal("Internal server error: missing application state".to_string())
+    })
+}
+
+// Try the JSON value as-is first, then re-parse it with urlencoded semantics so
+// string values can fill numeric and boolean fields.
+fn from_string_map<T: DeserializeOwned>(value: &Value) -> Result<T, String> {
+    serde_json::from_value(value.clone()).or_else(|direct| {
+        let Some(map) = value.as_object() else {
+            return Err(direct.to_string());
+        };
+        let pairs: Vec<(&str, &str)> = map
+            .iter()
+            .filter_map(|(key, value)| value.as_str().map(|value| (key.as_str(), value)))
+            .collect();
+        let encoded = serde_urlencoded::to_string(pairs).map_err(|err| err.to_string())?;
+        serde_urlencoded::from_str(&encoded).map_err(|err| err.to_string())
+    })
+}
@@ -0,0 +1,270 @@
+//! Feature flags, looked up in handlers or gating whole routes.
+//!
+//! ```ignore
+//! Server::new().flags(
+//!     Flags::new()
+//!         .provider(EnvFlags::new())
+//!         .provider(RemoteFlags::new(Duration::from_secs(30), fetch_flags))
+//!         .provider(StaticFlags::new().set("new-checkout", false)),
+//! );
+//!
+//! #[http_method(GET, "/cart")]
+//! fn cart(flags: State<Flags>) -> Value {
+//!     json!({ "checkout": if flags.is_enabled("new-checkout") { "v2" } else { "v1" } })
+//! }
+//!
+//! #[http_method(POST, "/checkout/v2", flag = "new-checkout")]
+//! async fn checkout(body: Order) -> Result<Receipt, FerroxError> { ... }
+//! ```
+//!
+//! A flag is on when the first provider that knows it says so, and off when
+//! none does. `StaticFlags` holds fixed values; `EnvFlags` reads `FERROX_FLAG_NEW_CHECKOUT`-style variables (`true`,
+//! `1`, `on` or `yes`, and `false`, `0`, `off` or `no`) on every lookup; and
+//! `RemoteFlags` polls a flag service with the given fetch function, keeping
+//! the last values it got while the service fails. Other sources implement
+//! [`FlagProvider`]. The `[flags]` section of a configuration file given to
+//! `Server::from_config` registers its values, which a `ConfigWatcher` updates
+//! when the file changes.
+//!
+//! `Server::flags` registers the `Flags` as state, so handlers take them as a
+//! `State<Flags>` parameter. A route registered with `flag = "..."` answers 404,
+//! like a missing route, while its flag is off; the flag is looked up on every
+//! request, so it can be turned on without a restart.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Once, RwLock, Weak};
+use std::time::Duration;
+
+use axum::extract::Request;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::MethodRouter;
+
+use crate::context::AppState;
+use crate::error::FerroxError;
+
+/// A source of flag values.
+pub trait FlagProvider: Send + Sync + 'static {
+    /// Whether `flag` is on, or `None` when this provider does not know it.
+    fn flag(&self, flag: &str) -> Option<bool>;
+}
+
+/// The flag providers, in order; register them with `Server::flags`.
+#[derive(Clone, Default)]
+pub struct Flags {
+    providers: Vec<Arc<dyn FlagProvider>>,
+}
+
+impl Flags {
+    /// No providers: every flag is off.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask `provider` after the ones added before it.
+    pub fn provider(mut self, provider: impl FlagProvider) -> Self {
+        self.providers.push(Arc::new(provider));
+        self
+    }
+
+    /// Whether `flag` is on.
+    pub fn is_enabled(&self, flag: &str) -> bool {
+        self.providers.iter().find_map(|provider| provider.flag(flag)).unwrap_or(false)
+    }
+}
+
+/// Fixed flag values.
+#[derive(Debug, Clone, Default)]
+pub struct StaticFlags {
+    flags: HashMap<String, bool>,
+}
+
+impl StaticFlags {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Turn `flag` on or off.
+    pub fn set(mut self, flag: &str, enabled: bool) -> Self {
+        self.flags.insert(flag.to_string(), enabled);
+        self
+    }
+}
+
+impl<K: Into<String>> FromIterator<(K, bool)> for StaticFlags {
+    fn from_iter<I: IntoIterator<Item = (K, bool)>>(flags: I) -> Self {
+        StaticFlags {
+            flags: flags.into_iter().map(|(flag, enabled)| (flag.into(), enabled)).collect(),
+        }
+    }
+}
+
+impl FlagProvider for StaticFlags {
+    fn flag(&self, flag: &str) -> Option<bool> {
+        self.flags.get(flag).copied()
+    }
+}
+
+// The `[flags]` section of the configuration file, replaced when it is reloaded
+#[derive(Clone, Default)]
+pub(crate) struct ConfigFlags {
+    flags: Arc<RwLock<HashMap<String, bool>>>,
+}
+
+impl ConfigFlags {
+    pub(crate) fn new(flags: impl IntoIterator<Item = (String, bool)>) -> Self {
+        ConfigFlags {
+            flags: Arc::new(RwLock::new(flags.into_iter().collect())),
+        }
+    }
+
+    pub(crate) fn replace(&self, flags: impl IntoIterator<Item = (String, bool)>) {
+        *self.flags.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = flags.into_iter().collect();
+    }
+}
+
+impl FlagProvider for ConfigFlags {
+    fn flag(&self, flag: &str) -> Option<bool> {
+        let flags = self.flags.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        flags.get(flag).copied()
+    }
+}
+
+/// Flags from environment variables: `new-checkout` is `FERROX_FLAG_NEW_CHECKOUT`.
+#[derive(Debug, Clone)]
+pub struct EnvFlags {
+    prefix: String,
+}
+
+impl EnvFlags {
+    /// Variables named `FERROX_FLAG_` and the flag.
+    pub fn new() -> Self {
+        Self::prefix("FERROX_FLAG_")
+    }
+
+    /// Variables named `prefix` and the flag.
+    pub fn prefix(prefix: &str) -> Self {
+        EnvFlags {
+            prefix: prefix.to_string(),
+        }
+    }
+}
+
+impl Default for EnvFlags {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlagProvider for EnvFlags {
+    fn flag(&self, flag: &str) -> Option<bool> {
+        let name: String = flag
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+            .collect();
+        let value = std::env::var(format!("{}{}", self.prefix, name)).ok()?;
+        match value.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" | "on" | "yes" => Some(true),
+            "false" | "0" | "off" | "no" => Some(false),
+            _ => {
+                tracing::warn!("Ignoring flag {} with the invalid value {:?}", flag, value);
+                None
+            }
+        }
+    }
+}
+
+/// The future of a `RemoteFlags` fetch.
+pub type FetchFuture = Pin<Box<dyn Future<Output = Result<HashMap<String, bool>, FerroxError>> + Send>>;
+
+/// Flags polled from a flag service.
+#[derive(Clone)]
+pub struct RemoteFlags {
+    inner: Arc<Remote>,
+}
+
+struct Remote {
+    fetch: Box<dyn Fn() -> FetchFuture + Send + Sync>,
+    interval: Duration,
+    flags: RwLock<HashMap<String, bool>>,
+    polling: Once,
+}
+
+impl RemoteFlags {
+    /// Call `fetch` for every flag's value, once every `interval`, starting
+    /// with the first lookup.
+    pub fn new<F, Fut>(interval: Duration, fetch: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<HashMap<String, bool>, FerroxError>> + Send + 'static,
+    {
+        let remote = Remote {
+            fetch: Box::new(move || Box::pin(fetch())),
+            interval,
+            flags: RwLock::default(),
+            polling: Once::new(),
+        };
+        RemoteFlags { inner: Arc::new(remote) }
+    }
+
+    /// Fetch the flags now, e.g. before the server starts, so the first
+    /// requests see them.
+    pub async fn refresh(&self) -> Result<(), FerroxError> {
+        self.inner.refresh().await
+    }
+}
+
+impl Remote {
+    async fn refresh(&self) -> Result<(), FerroxError> {
+        let flags = (self.fetch)().await?;
+        *self.flags.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = flags;
+        Ok(())
+    }
+}
+
+// Refresh the flags every interval, until the provider is dropped
+async fn poll(remote: Weak<Remote>, interval: Duration) {
+    loop {
+        let Some(remote) = remote.upgrade() else {
+            return;
+        };
+        if let Err(err) = remote.refresh().await {
+            tracing::error!("Fetching the remote flags failed, keeping the last values: {}", err);
+        }
+        drop(remote);
+        tokio::time::sleep(interval).await;
+    }
+}
+
+impl FlagProvider for RemoteFlags {
+    fn flag(&self, flag: &str) -> Option<bool> {
+        self.inner.polling.call_once(|| match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(poll(Arc::downgrade(&self.inner), self.inner.interval));
+            }
+            Err(_) => tracing::error!("Remote flags are only polled from within a tokio runtime"),
+        });
+        let flags = self.inner.flags.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        flags.get(flag).copied()
+    }
+}
+
+// Answer 404 while `flag` is off, as if the route were not registered
+pub(crate) fn gate_route(route: MethodRouter<AppState>, flag: &'static str, flags: Flags) -> MethodRouter<AppState> {
+    route.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let enabled = flags.is_enabled(flag);
+        async move {
+            if enabled {
+                return next.run(request).await;
+            }
+            not_found(&request)
+        }
+    }))
+}
+
+fn not_found(request: &Request) -> Response {
+    FerroxError::new(StatusCode::NOT_FOUND, format!("Route {} not found", request.uri().path())).into_response()
+}
@@ -0,0 +1,201 @@
+// Body formats other than JSON, enabled by the `msgpack`, `cbor` and `xml`
+// features: responses are encoded in the best format the `Accept` header
+// allows, and request bodies decoded by their `Content-Type`. Handlers still
+// see and return JSON values either way.
+
+use axum::http::header::{ACCEPT, CONTENT_TYPE};
+use axum::http::HeaderMap;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum Format {
+    #[default]
+    Json,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+    #[cfg(feature = "cbor")]
+    Cbor,
+    #[cfg(feature = "xml")]
+    Xml,
+}
+
+// Whether responses can differ by `Accept`, and so need `Vary: Accept`
+pub(crate) const NEGOTIATED: bool = cfg!(any(feature = "msgpack", feature = "cbor", feature = "xml"));
+
+impl Format {
+    fn from_media_type(media_type: &str) -> Option<Format> {
+        match media_type.trim().to_ascii_lowercase().as_str() {
+            "application/json" | "application/*" | "*/*" => Some(Format::Json),
+            #[cfg(feature = "msgpack")]
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(Format::MessagePack),
+            #[cfg(feature = "cbor")]
+            "application/cbor" => Some(Format::Cbor),
+            #[cfg(feature = "xml")]
+            "application/xml" | "text/xml" => Some(Format::Xml),
+            _ => None,
+        }
+    }
+
+    // The supported format the client prefers; JSON when it names none of them
+    pub(crate) fn from_accept(headers: &HeaderMap) -> Format {
+        let Some(accept) = headers.get(ACCEPT).and_then(|value| value.to_str().ok()) else {
+            return Format::Json;
+        };
+        let mut best = (Format::Json, 0.0);
+        for entry in accept.split(',') {
+            let mut parts = entry.split(';');
+            let Some(format) = parts.next().and_then(Format::from_media_type) else {
+                continue;
+            };
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if quality > best.1 {
+                best = (format, quality);
+            }
+        }
+        best.0
+    }
+
+    // The body's format, if it is one of the non-JSON formats
+    pub(crate) fn from_content_type(headers: &HeaderMap) -> Option<Format> {
+        let content_type = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+        Format::from_media_type(content_type.split(';').next()?).filter(|format| *format != Format::Json)
+    }
+
+    pub(crate) fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack => "application/msgpack",
+            #[cfg(feature = "cbor")]
+            Format::Cbor => "application/cbor",
+            #[cfg(feature = "xml")]
+            Format::Xml => "application/xml",
+        }
+    }
+
+    pub(crate) fn encode(self, body: &Value) -> Result<Vec<u8>, String> {
+        match self {
+            Format::Json => serde_json::to_vec(body).map_err(|err| err.to_string()),
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack => rmp_serde::to_vec_named(body).map_err(|err| err.to_string()),
+            #[cfg(feature = "cbor")]
+            Format::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(body, &mut bytes).map_err(|err| err.to_string())?;
+                Ok(bytes)
+            }
+            #[cfg(feature = "xml")]
+            Format::Xml => xml::encode(body),
+        }
+    }
+
+    pub(crate) fn decode(self, bytes: &[u8]) -> Result<Value, String> {
+        match self {
+            Format::Json => serde_json::from_slice(bytes).map_err(|err| err.to_string()),
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack => rmp_serde::from_slice(bytes).map_err(|err| err.to_string()),
+            #[cfg(feature = "cbor")]
+            Format::Cbor => ciborium::from_reader(bytes).map_err(|err| err.to_string()),
+            #[cfg(feature = "xml")]
+            Format::Xml => xml::decode(bytes),
+        }
+    }
+
+    // Whether decoded values are all strings, to be parsed like query parameters
+    pub(crate) fn has_text_values(self) -> bool {
+        #[cfg(feature = "xml")]
+        if self == Format::Xml {
+            return true;
+        }
+        false
+    }
+}
+
+// XML documents map to JSON objects: the root element stands for the whole
+// value, child elements become fields (repeated ones an array), text becomes a
+// string and empty elements `null`. Attributes are ignored.
+#[cfg(feature = "xml")]
+mod xml {
+    use quick_xml::events::Event;
+    use quick_xml::Reader;
+    use serde_json::{Map, Value};
+
+    const ROOT: &str = "response";
+
+    pub(super) fn encode(body: &Value) -> Result<Vec<u8>, String> {
+        // A bare array would produce one root element per item
+        let wrapped;
+        let body = match body {
+            Value::Array(_) => {
+                wrapped = serde_json::json!({ "item": body });
+                &wrapped
+            }
+            _ => body,
+        };
+        let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        xml.push_str(&quick_xml::se::to_string_with_root(ROOT, body).map_err(|err| err.to_string())?);
+        Ok(xml.into_bytes())
+    }
+
+    pub(super) fn decode(bytes: &[u8]) -> Result<Value, String> {
+        let mut reader = Reader::from_reader(bytes);
+        reader.config_mut().trim_text(true);
+        // Open elements: name, fields so far, text so far
+        let mut open: Vec<(String, Map<String, Value>, String)> = Vec::new();
+        loop {
+            let event = reader.read_event().map_err(|err| err.to_string())?;
+            let closed = match event {
+                Event::Start(start) => {
+                    open.push((name(start.local_name().as_ref())?, Map::new(), String::new()));
+                    continue;
+                }
+                Event::Empty(empty) => (name(empty.local_name().as_ref())?, Value::Null),
+                Event::Text(text) => {
+                    if let Some((_, _, content)) = open.last_mut() {
+                        content.push_str(&text.unescape().map_err(|err| err.to_string())?);
+                    }
+                    continue;
+                }
+                Event::CData(data) => {
+                    if let Some((_, _, content)) = open.last_mut() {
+                        content.push_str(std::str::from_utf8(&data).map_err(|err| err.to_string())?);
+                    }
+                    continue;
+                }
+                Event::End(_) => {
+                    let (name, fields, text) = open.pop().ok_or("unbalanced closing tag")?;
+                    let value = match (fields.is_empty(), text.is_empty()) {
+                        (false, _) => Value::Object(fields),
+                        (true, false) => Value::String(text),
+                        (true, true) => Value::Null,
+                    };
+                    (name, value)
+                }
+                Event::Eof => return Err("document has no root element".to_string()),
+                _ => continue,
+            };
+            let (name, value) = closed;
+            match open.last_mut() {
+                Some((_, fields, _)) => insert(fields, name, value),
+                None => return Ok(value),
+            }
+        }
+    }
+
+    fn name(bytes: &[u8]) -> Result<String, String> {
+        String::from_utf8(bytes.to_vec()).map_err(|err| err.to_string())
+    }
+
+    fn insert(fields: &mut Map<String, Value>, name: String, value: Value) {
+        match fields.get_mut(&name) {
+            Some(Value::Array(values)) => values.push(value),
+            Some(first) => *first = Value::Array(vec![first.take(), value]),
+            None => {
+                fields.insert(name, value);
+            }
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! GraphQL endpoints serving an `async-graphql` schema next to the REST routes
+//! (the `graphql` feature).
+//!
+//! ```ignore
+//! struct Query;
+//!
+//! #[async_graphql::Object]
+//! impl Query {
+//!     async fn user(&self, ctx: &async_graphql::Context<'_>, id: u64) -> async_graphql::Result<User> {
+//!         let db: Db = ferrox::graphql::state(ctx)?;
+//!         let claims: Claims = ferrox::graphql::identity(ctx)?;
+//!         db.user(id, &claims).await
+//!     }
+//! }
+//!
+//! let schema = Schema::new(Query, EmptyMutation, EmptySubscription);
+//! Server::new().graphql(GraphQL::new(schema).path("/api/graphql").auth("jwt"));
+//! ```
+//!
+//! Queries are accepted as JSON `POST` bodies, single or batched, and as `GET`
+//! query strings. A browser `GET` on the path opens GraphiQL unless it is
+//! turned off with `graphiql(false)`. Resolvers reach the request's
+//! `RequestContext` with `context`, shared state registered with
+//! `Server::with_state` with `state`, and the identity recorded by the `auth`
+//! scheme with `identity`. The endpoint runs through the server's layers, so
+//! middleware, rate limits and CORS apply as for the REST routes.
+
+use std::sync::Arc;
+
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{BatchRequest, BatchResponse, Executor};
+use axum::body::Bytes;
+use axum::extract::{Request, State as AxumState};
+use axum::http::header::ACCEPT;
+use axum::http::Method;
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::MethodRouter;
+use axum::Router;
+use futures_util::future::BoxFuture;
+use serde_json::Value;
+
+use crate::auth::{self, Authenticator};
+use crate::context::{AppState, RequestContext};
+use crate::dispatch;
+use crate::error::FerroxError;
+
+type Execute = Arc<dyn Fn(BatchRequest) -> BoxFuture<'static, BatchResponse> + Send + Sync>;
+
+/// A GraphQL endpoint for a schema; register it with `Server::graphql`.
+#[derive(Clone)]
+pub struct GraphQL {
+    execute: Execute,
+    path: String,
+    graphiql: bool,
+    auth: Option<&'static str>,
+}
+
+impl GraphQL {
+    /// Serve `schema` at `/graphql`, with GraphiQL.
+    pub fn new<E: Executor>(schema: E) -> Self {
+        GraphQL {
+            execute: Arc::new(move |request| {
+                let schema = schema.clone();
+                Box::pin(async move { schema.execute_batch(request).await })
+            }),
+            path: "/graphql".to_string(),
+            graphiql: true,
+            auth: None,
+        }
+    }
+
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Serve the GraphiQL IDE to browsers at the path (on by default).
+    pub fn graphiql(mut self, enabled: bool) -> Self {
+        self.graphiql = enabled;
+        self
+    }
+
+    /// Authenticate requests with the scheme registered with `Server::authenticator`, as
+    /// `auth = "..."` does for routes. GraphiQL is served without authentication.
+    pub fn auth(mut self, scheme: &'static str) -> Self {
+        self.auth = Some(scheme);
+        self
+    }
+
+    pub(crate) fn scheme(&self) -> Option<&'static str> {
+        self.auth
+    }
+}
+
+/// The `RequestContext` of the request being resolved.
+///
+/// # Panics
+///
+/// If the schema is not executed by a `Server::graphql` endpoint.
+pub fn context<'a>(ctx: &'a async_graphql::Context<'_>) -> &'a RequestContext {
+    ctx.data_unchecked::<RequestContext>()
+}
+
+/// Shared state registered with `Server::with_state`.
+pub fn state<S: Clone + Send + Sync + 'static>(ctx: &async_graphql::Context<'_>) -> async_graphql::Result<S> {
+    context(ctx).state::<S>().ok_or_else(|| {
+        async_graphql::Error::new(format!(
+            "no state of type {} was registered with Server::with_state",
+            std::any::type_name::<S>()
+        ))
+    })
+}
+
+/// The identity recorded by the endpoint's `auth` scheme, e.g. `Claims` or `ApiKey`.
+pub fn identity<T: Clone + Send + Sync + 'static>(ctx: &async_graphql::Context<'_>) -> async_graphql::Result<T> {
+    auth::identity::<T>(context(ctx)).map_err(|err| async_graphql::Error::new(err.message()))
+}
+
+// Mount `config` on `router`, behind its authenticator if it has a scheme
+pub(crate) fn mount(
+    router: Router<AppState>,
+    config: GraphQL,
+    authenticator: Option<Arc<dyn Authenticator>>,
+    max_body_size: usize,
+) -> Router<AppState> {
+    let path = config.path.clone();
+    let graphiql = config.graphiql.then(|| {
+        let page = GraphiQLSource::build().endpoint(&path).finish();
+        Html(page)
+    });
+    let config = Arc::new(config);
+    let mut endpoint: MethodRouter<AppState> = axum::routing::get({
+        let config = config.clone();
+        move |state: AxumState<AppState>, request: Request| execute(config, max_body_size, state, request)
+    })
+    .post({
+        let config = config.clone();
+        move |state: AxumState<AppState>, request: Request| execute(config, max_body_size, state, request)
+    });
+    if let Some(scheme) = config.auth {
+        endpoint = auth::require(endpoint, scheme, authenticator);
+    }
+
+    // GraphiQL is picked before authentication: browsers cannot send the credentials
+    let ide = move |request: Request, next: axum::middleware::Next| {
+        let graphiql = graphiql.clone();
+        async move {
+            let browser = request.method() == Method::GET
+                && request.uri().query().is_none()
+                && request
+                    .headers()
+                    .get(ACCEPT)
+                    .and_then(|value| value.to_str().ok())
+                    .is_some_and(|accept| accept.contains("text/html"));
+            match graphiql {
+                Some(page) if browser => page.into_response(),
+                _ => next.run(request).await,
+            }
+        }
+    };
+    router.route(&path, endpoint.layer(axum::middleware::from_fn(ide)))
+}
+
+async fn execute(
+    config: Arc<GraphQL>,
+    max_body_size: usize,
+    AxumState(state): AxumState<AppState>,
+    request: Request,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let batch = if parts.method == Method::GET {
+        async_graphql::http::parse_query_string(parts.uri.query().unwrap_or_default()).map(BatchRequest::Single)
+    } else {
+        match axum::body::to_bytes(body, max_body_size).await {
+            Ok(bytes) => parse_body(&bytes),
+            Err(err) => {
+                let over_limit = std::error::Error::source(&err)
+                    .is_some_and(|source| source.is::<http_body_util::LengthLimitError>());
+                if over_limit {
+                    return dispatch::body_too_large(max_body_size);
+                }
+                return FerroxError::BadRequest("Failed to read request body".to_string()).into_response();
+            }
+        }
+    };
+    let batch = match batch {
+        Ok(batch) => batch,
+        Err(err) => return FerroxError::BadRequest(format!("Invalid GraphQL request: {}", err)).into_response(),
+    };
+    let ctx = RequestContext::from_parts(parts, Value::Null, Value::Null, Value::Null, state);
+    let response = (config.execute)(batch.data(ctx)).await;
+    axum::Json(response).into_response()
+}
+
+fn parse_body(bytes: &Bytes) -> Result<BatchRequest, async_graphql::ParseRequestError> {
+    serde_json::from_slice(bytes).map_err(|err| async_graphql::ParseRequestError::InvalidRequest(Box::new(err)))
+}
@@ -0,0 +1,55 @@
+//! gRPC services served next to the REST routes (the `grpc` feature).
+//!
+//! ```ignore
+//! Server::new()
+//!     .grpc(GreeterServer::new(MyGreeter::default()))
+//!     .grpc(HealthServer::new(health_service))
+//!     .start("0.0.0.0:3000")
+//!     .await?;
+//! ```
+//!
+//! `Server::grpc` takes any tonic service, such as the servers generated by
+//! `tonic-build`. Requests whose `Content-Type` is `application/grpc` (or
+//! `application/grpc+proto` and the like) go to the service named by their
+//! path, over the same listeners as every other request; clients reach them
+//! over HTTPS through ALPN or over h2c, so see `Server::http2`. Calls to a
+//! service or method that is not registered answer `UNIMPLEMENTED`.
+//!
+//! gRPC calls run inside the access log, metrics and tracing layers and are
+//! drained on shutdown like other requests, but skip the REST layers: the
+//! response envelope, sessions, rate limits, CORS and compression, which gRPC
+//! handles itself.
+
+use std::convert::Infallible;
+
+use axum::extract::Request;
+use axum::http::header::CONTENT_TYPE;
+use axum::middleware::Next;
+use axum::Router;
+use tonic::service::Routes;
+use tower::Service;
+
+use crate::AppState;
+
+// The tonic services' router, in front of `router` for gRPC requests
+pub(crate) fn layer(router: Router<AppState>, routes: Routes) -> Router<AppState> {
+    let services = routes.into_axum_router();
+    router.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let mut services = services.clone();
+        async move {
+            if !is_grpc(&request) {
+                return next.run(request).await;
+            }
+            services.call(request).await.unwrap_or_else(|never: Infallible| match never {})
+        }
+    }))
+}
+
+// `application/grpc` and its `+proto`/`+json` variants, but not gRPC-Web
+fn is_grpc(request: &Request) -> bool {
+    let Some(content_type) = request.headers().get(CONTENT_TYPE).and_then(|value| value.to_str().ok()) else {
+        return false;
+    };
+    let content_type = content_type.split(';').next().unwrap_or_default().trim();
+    content_type == "application/grpc" || content_type.starts_with("application/grpc+")
+}
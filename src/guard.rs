@@ -0,0 +1,123 @@
+//! Route guards: checks that run before a handler and can turn the request away.
+//!
+//! ```ignore
+//! struct AdminOnly;
+//!
+//! impl Guard for AdminOnly {
+//!     async fn check(&self, ctx: &RequestContext) -> GuardResult {
+//!         match ctx.extensions.get::<Claims>() {
+//!             Some(claims) if claims.role == "admin" => GuardResult::Allow,
+//!             Some(_) => GuardResult::Forbidden("Admins only".to_string()),
+//!             None => GuardResult::Unauthorized("Authentication required".to_string()),
+//!         }
+//!     }
+//! }
+//!
+//! #[http_method(DELETE, "/users/:id", auth = "jwt", guards = [AdminOnly, Verified])]
+//! async fn delete_user(id: u64) -> Result<Value, FerroxError> { ... }
+//! ```
+//!
+//! Guards are listed on a route with `guards = [...]`, or on a `#[route_group]`
+//! for every route in it, the group's first. Each entry is an expression
+//! evaluated once when the router is built. They run in order after the
+//! route's authenticator, so they can read the identity it recorded, and the
+//! first rejection is answered with the error envelope without running the
+//! remaining guards or the handler. A guard sees the path and query parameters,
+//! headers, extensions and state of the request, but not its body, which is
+//! only read once every guard has allowed it.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use axum::extract::{FromRequestParts, Path, Request};
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::routing::MethodRouter;
+
+use crate::context::{AppState, RequestContext};
+use crate::dispatch::params_to_json;
+use crate::error::FerroxError;
+
+/// What a [`Guard`] decided about a request.
+#[derive(Debug)]
+pub enum GuardResult {
+    /// Let the request through to the next guard, or the handler.
+    Allow,
+    /// Answer 401: the client has not said who it is.
+    Unauthorized(String),
+    /// Answer 403: the client may not do this.
+    Forbidden(String),
+    /// Answer with any other error.
+    Reject(FerroxError),
+}
+
+impl From<FerroxError> for GuardResult {
+    fn from(err: FerroxError) -> Self {
+        GuardResult::Reject(err)
+    }
+}
+
+/// A check run before the handlers of the routes it is attached to.
+pub trait Guard: Send + Sync + 'static {
+    /// Decide whether the request may go on; implemented as an `async fn`.
+    fn check(&self, ctx: &RequestContext) -> impl Future<Output = GuardResult> + Send;
+}
+
+/// Builds one guard of a route; the entries of `guards = [...]` become these.
+pub type GuardFn = fn() -> BoxedGuard;
+
+/// A guard of any type, as held by the router.
+#[derive(Clone)]
+pub struct BoxedGuard(Arc<dyn DynGuard>);
+
+impl BoxedGuard {
+    pub fn new<G: Guard>(guard: G) -> Self {
+        BoxedGuard(Arc::new(guard))
+    }
+}
+
+type CheckFuture<'a> = Pin<Box<dyn Future<Output = GuardResult> + Send + 'a>>;
+
+// `Guard` with a boxed future, so guards of different types can share a list
+trait DynGuard: Send + Sync {
+    fn check<'a>(&'a self, ctx: &'a RequestContext) -> CheckFuture<'a>;
+}
+
+impl<G: Guard> DynGuard for G {
+    fn check<'a>(&'a self, ctx: &'a RequestContext) -> CheckFuture<'a> {
+        Box::pin(Guard::check(self, ctx))
+    }
+}
+
+// Run `guards` in order in front of a route, answering the first rejection
+pub(crate) fn guard_route(route: MethodRouter<AppState>, guards: Vec<BoxedGuard>, state: AppState) -> MethodRouter<AppState> {
+    let guards: Arc<[BoxedGuard]> = guards.into();
+    route.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let (guards, state) = (guards.clone(), state.clone());
+        async move {
+            let (mut parts, body) = request.into_parts();
+            let path = match Path::<HashMap<String, String>>::from_request_parts(&mut parts, &state).await {
+                Ok(Path(params)) => params_to_json(params),
+                Err(_) => params_to_json(HashMap::new()),
+            };
+            let query = parts
+                .uri
+                .query()
+                .and_then(|query| serde_urlencoded::from_str::<HashMap<String, String>>(query).ok())
+                .unwrap_or_default();
+            let ctx = RequestContext::from_parts(parts.clone(), path, params_to_json(query), serde_json::Value::Null, state);
+            for guard in guards.iter() {
+                let rejection = match guard.0.check(&ctx).await {
+                    GuardResult::Allow => continue,
+                    GuardResult::Unauthorized(message) => FerroxError::Unauthorized(message),
+                    GuardResult::Forbidden(message) => FerroxError::Forbidden(message),
+                    GuardResult::Reject(err) => err,
+                };
+                return rejection.into_response();
+            }
+            next.run(Request::from_parts(parts, body)).await
+        }
+    }))
+}
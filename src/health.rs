@@ -0,0 +1,201 @@
+//! Liveness, readiness and health probe endpoints for Kubernetes and load balancers.
+//!
+//! ```ignore
+//! let pool = pool.clone();
+//! Server::new().health_checks(
+//!     HealthChecks::new()
+//!         .check("database", move || {
+//!             let pool = pool.clone();
+//!             async move { pool.ping().await }
+//!         })
+//!         .timeout(Duration::from_secs(2)),
+//! );
+//! ```
+//!
+//! `/livez` runs the liveness checks, `/readyz` the readiness checks and
+//! `/healthz` both. Checks run concurrently, each bounded by the timeout, and
+//! the endpoint answers 200 when all of them pass or 503 otherwise, with a
+//! report such as:
+//!
+//! ```json
+//! {"status": "fail", "checks": {"database": {"status": "fail", "duration_ms": 2000, "error": "timed out after 2s"}}}
+//! ```
+//!
+//! Liveness checks should only fail when restarting the process would help;
+//! dependencies such as databases belong in readiness checks. The endpoints
+//! are served outside every middleware, so probes are neither authenticated,
+//! rate limited, logged nor counted in metrics.
+
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use futures_util::future::{join_all, BoxFuture};
+use serde_json::{json, Map, Value};
+
+use crate::context::AppState;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(crate) type Check = Arc<dyn Fn() -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+/// The probe endpoints and their checks; pass it to `Server::health_checks`.
+///
+/// Without checks every endpoint reports healthy as long as the server answers.
+#[derive(Clone)]
+pub struct HealthChecks {
+    readiness: Vec<(String, Check)>,
+    liveness: Vec<(String, Check)>,
+    timeout: Duration,
+    paths: Paths,
+}
+
+#[derive(Clone)]
+struct Paths {
+    health: String,
+    ready: String,
+    live: String,
+}
+
+impl HealthChecks {
+    pub fn new() -> Self {
+        HealthChecks {
+            readiness: Vec::new(),
+            liveness: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+            paths: Paths {
+                health: "/healthz".to_string(),
+                ready: "/readyz".to_string(),
+                live: "/livez".to_string(),
+            },
+        }
+    }
+
+    /// Add a readiness check named `name`, reported by `/readyz` and `/healthz`.
+    ///
+    /// The check fails when it returns an error, panics or exceeds the timeout.
+    pub fn check<F, Fut, E>(mut self, name: &str, check: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display,
+    {
+        self.readiness.push((name.to_string(), boxed(check)));
+        self
+    }
+
+    /// Add a liveness check named `name`, reported by `/livez` and `/healthz`.
+    pub fn liveness_check<F, Fut, E>(mut self, name: &str, check: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display,
+    {
+        self.liveness.push((name.to_string(), boxed(check)));
+        self
+    }
+
+    /// How long each check may take before it counts as failed (defaults to 5s).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Serve the endpoints at other paths than `/healthz`, `/readyz` and `/livez`.
+    pub fn paths(mut self, health: &str, ready: &str, live: &str) -> Self {
+        self.paths = Paths {
+            health: health.to_string(),
+            ready: ready.to_string(),
+            live: live.to_string(),
+        };
+        self
+    }
+}
+
+impl HealthChecks {
+    // Add readiness checks registered with the server itself, such as its database's
+    pub(crate) fn with_readiness(mut self, checks: Vec<(String, Check)>) -> Self {
+        self.readiness.extend(checks);
+        self
+    }
+}
+
+impl Default for HealthChecks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(crate) fn boxed<F, Fut, E>(check: F) -> Check
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), E>> + Send + 'static,
+    E: Display,
+{
+    Arc::new(move || {
+        let result = check();
+        Box::pin(async move { result.await.map_err(|err| err.to_string()) })
+    })
+}
+
+// Serve the probe endpoints, outside every layer like the metrics endpoint
+pub(crate) fn mount(router: Router<AppState>, config: HealthChecks) -> Router<AppState> {
+    let all: Vec<(String, Check)> = config.liveness.iter().chain(&config.readiness).cloned().collect();
+    let timeout = config.timeout;
+    router
+        .route(&config.paths.health, endpoint(all, timeout))
+        .route(&config.paths.ready, endpoint(config.readiness, timeout))
+        .route(&config.paths.live, endpoint(config.liveness, timeout))
+}
+
+fn endpoint(checks: Vec<(String, Check)>, timeout: Duration) -> axum::routing::MethodRouter<AppState> {
+    let checks = Arc::new(checks);
+    get(move || {
+        let checks = checks.clone();
+        async move { report(&checks, timeout).await }
+    })
+}
+
+// Run every check concurrently and report each result
+async fn report(checks: &[(String, Check)], timeout: Duration) -> Response {
+    let results = join_all(checks.iter().map(|(name, check)| {
+        // Spawned so a panicking check fails alone instead of taking the probe down
+        let run = tokio::spawn(tokio::time::timeout(timeout, check()));
+        async move {
+            let started = Instant::now();
+            let outcome = match run.await {
+                Ok(Ok(result)) => result,
+                Ok(Err(_)) => Err(format!("timed out after {:?}", timeout)),
+                Err(_) => Err("check panicked".to_string()),
+            };
+            (name, outcome, started.elapsed())
+        }
+    }))
+    .await;
+
+    let mut healthy = true;
+    let mut report = Map::new();
+    for (name, outcome, elapsed) in results {
+        let mut entry = json!({
+            "status": if outcome.is_ok() { "ok" } else { "fail" },
+            "duration_ms": elapsed.as_millis() as u64,
+        });
+        if let Err(message) = outcome {
+            tracing::warn!(check = %name, "Health check failed: {}", message);
+            entry["error"] = Value::String(message);
+            healthy = false;
+        }
+        report.insert(name.clone(), entry);
+    }
+    let status = if healthy { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    let body = json!({
+        "status": if healthy { "ok" } else { "fail" },
+        "checks": report,
+    });
+    (status, Json(body)).into_response()
+}
@@ -0,0 +1,91 @@
+//! Hooks run around `#[http_method]` handlers, on every route or on one.
+//!
+//! ```ignore
+//! Server::new()
+//!     // Fill in defaults before the parameters are parsed
+//!     .before_route("GET", "/users", |ctx: &mut RequestContext| {
+//!         if ctx.query.get("limit").is_none() {
+//!             ctx.query["limit"] = json!("20");
+//!         }
+//!         Ok(())
+//!     })
+//!     // Strip internal fields from every result
+//!     .after(|response: &mut HandlerResponse| {
+//!         if let Some(object) = response.body_mut().as_object_mut() {
+//!             object.retain(|key, _| !key.starts_with('_'));
+//!         }
+//!     });
+//! ```
+//!
+//! Before hooks get the request's path, query and body values, and may change
+//! them, before the handler's parameters are parsed from them; an error they
+//! return is sent in place of calling the handler. After hooks get successful
+//! results before they are wrapped and serialized; errors and streamed
+//! responses do not pass through them.
+//!
+//! Server-wide hooks run around the route's own: before hooks in the order
+//! registered, server-wide first, and after hooks the route's first.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::context::RequestContext;
+use crate::error::FerroxError;
+use crate::response::HandlerResponse;
+
+pub(crate) type BeforeHook = Arc<dyn Fn(&mut RequestContext) -> Result<(), FerroxError> + Send + Sync>;
+pub(crate) type AfterHook = Arc<dyn Fn(&mut HandlerResponse) + Send + Sync>;
+
+// The hooks registered with `Server`, server-wide and by (method, path)
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+    pub(crate) before: Vec<BeforeHook>,
+    pub(crate) after: Vec<AfterHook>,
+    pub(crate) routes: HashMap<(String, String), RouteHooks>,
+}
+
+impl Hooks {
+    // Every hook one route runs, in order
+    pub(crate) fn route(&self, method: &str, path: &str) -> RouteHooks {
+        let own = self.routes.get(&(method.to_string(), path.to_string()));
+        let mut hooks = RouteHooks {
+            before: self.before.clone(),
+            after: Vec::new(),
+        };
+        if let Some(own) = own {
+            hooks.before.extend(own.before.iter().cloned());
+            hooks.after.extend(own.after.iter().cloned());
+        }
+        hooks.after.extend(self.after.iter().cloned());
+        hooks
+    }
+
+    // The server-wide hooks alone, for routes registered at runtime
+    pub(crate) fn global(&self) -> RouteHooks {
+        RouteHooks {
+            before: self.before.clone(),
+            after: self.after.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct RouteHooks {
+    pub(crate) before: Vec<BeforeHook>,
+    pub(crate) after: Vec<AfterHook>,
+}
+
+impl RouteHooks {
+    pub(crate) fn before(&self, ctx: &mut RequestContext) -> Result<(), FerroxError> {
+        self.before.iter().try_for_each(|hook| hook(ctx))
+    }
+
+    pub(crate) fn after(&self, mut response: HandlerResponse) -> HandlerResponse {
+        if response.is_success() {
+            for hook in &self.after {
+                hook(&mut response);
+            }
+        }
+        response
+    }
+}
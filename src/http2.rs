@@ -0,0 +1,166 @@
+//! HTTP/2 support: over TLS, negotiated through ALPN, and in cleartext (h2c) for
+//! service-to-service traffic.
+//!
+//! ```ignore
+//! Server::new().http2(
+//!     Http2Config::new()
+//!         .max_concurrent_streams(500)
+//!         .max_frame_size(64 * 1024)
+//!         .keep_alive(Duration::from_secs(20), Duration::from_secs(10)),
+//! );
+//! ```
+//!
+//! Both are on by default. HTTPS listeners offer `h2` and `http/1.1` to clients
+//! through ALPN, and plain listeners accept HTTP/2 from clients that start with
+//! the HTTP/2 preface ("prior knowledge", e.g. `curl --http2-prior-knowledge` or a
+//! gRPC client). HTTP/1.1 `Upgrade: h2c` requests are served as HTTP/1.1. The
+//! tunables only apply to HTTP/2 connections; unset ones keep hyper's defaults.
+
+use std::time::Duration;
+
+use hyper_util::rt::{TokioExecutor, TokioTimer};
+use hyper_util::server::conn::auto;
+
+/// HTTP/2 settings for `Server::http2`.
+#[derive(Debug, Clone)]
+pub struct Http2Config {
+    alpn: bool,
+    h2c: bool,
+    max_concurrent_streams: Option<u32>,
+    max_frame_size: Option<u32>,
+    max_header_list_size: Option<u32>,
+    initial_stream_window_size: Option<u32>,
+    initial_connection_window_size: Option<u32>,
+    adaptive_window: bool,
+    keep_alive: Option<(Duration, Duration)>,
+}
+
+impl Http2Config {
+    /// HTTP/2 over TLS and h2c, with hyper's default limits.
+    pub fn new() -> Self {
+        Http2Config {
+            alpn: true,
+            h2c: true,
+            max_concurrent_streams: None,
+            max_frame_size: None,
+            max_header_list_size: None,
+            initial_stream_window_size: None,
+            initial_connection_window_size: None,
+            adaptive_window: false,
+            keep_alive: None,
+        }
+    }
+
+    /// Serve HTTP/1.1 only, on every listener.
+    pub fn disabled() -> Self {
+        Self::new().alpn(false).h2c(false)
+    }
+
+    /// Whether HTTPS listeners offer HTTP/2 through ALPN.
+    pub fn alpn(mut self, enabled: bool) -> Self {
+        self.alpn = enabled;
+        self
+    }
+
+    /// Whether plain listeners accept HTTP/2 with prior knowledge.
+    pub fn h2c(mut self, enabled: bool) -> Self {
+        self.h2c = enabled;
+        self
+    }
+
+    /// Most streams a client may have open on one connection (200 by default).
+    pub fn max_concurrent_streams(mut self, max: u32) -> Self {
+        self.max_concurrent_streams = Some(max);
+        self
+    }
+
+    /// Largest frame payload accepted, between 16 KiB (the default) and 16 MiB.
+    pub fn max_frame_size(mut self, size: u32) -> Self {
+        self.max_frame_size = Some(size);
+        self
+    }
+
+    /// Largest header block accepted, in bytes.
+    pub fn max_header_list_size(mut self, size: u32) -> Self {
+        self.max_header_list_size = Some(size);
+        self
+    }
+
+    /// Flow control window of each stream, in bytes.
+    pub fn initial_stream_window_size(mut self, size: u32) -> Self {
+        self.initial_stream_window_size = Some(size);
+        self
+    }
+
+    /// Flow control window of each connection, in bytes.
+    pub fn initial_connection_window_size(mut self, size: u32) -> Self {
+        self.initial_connection_window_size = Some(size);
+        self
+    }
+
+    /// Size flow control windows from measured bandwidth, overriding the fixed windows.
+    pub fn adaptive_window(mut self, enabled: bool) -> Self {
+        self.adaptive_window = enabled;
+        self
+    }
+
+    /// Ping idle connections every `interval`, closing those that do not answer within `timeout`.
+    pub fn keep_alive(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.keep_alive = Some((interval, timeout));
+        self
+    }
+
+    #[cfg(feature = "tls")]
+    pub(crate) fn offers_alpn(&self) -> bool {
+        self.alpn
+    }
+
+    // How plain listeners serve connections
+    pub(crate) fn protocols(&self) -> Protocols {
+        if !self.h2c {
+            return Protocols::Http1(hyper::server::conn::http1::Builder::new());
+        }
+        let mut builder = auto::Builder::new(TokioExecutor::new());
+        self.configure(&mut builder);
+        Protocols::Auto(builder)
+    }
+
+    pub(crate) fn configure(&self, builder: &mut auto::Builder<TokioExecutor>) {
+        let mut http2 = builder.http2();
+        http2.timer(TokioTimer::new());
+        if let Some(max) = self.max_concurrent_streams {
+            http2.max_concurrent_streams(max);
+        }
+        if let Some(size) = self.max_frame_size {
+            http2.max_frame_size(size);
+        }
+        if let Some(size) = self.max_header_list_size {
+            http2.max_header_list_size(size);
+        }
+        if let Some(size) = self.initial_stream_window_size {
+            http2.initial_stream_window_size(size);
+        }
+        if let Some(size) = self.initial_connection_window_size {
+            http2.initial_connection_window_size(size);
+        }
+        if self.adaptive_window {
+            http2.adaptive_window(true);
+        }
+        if let Some((interval, timeout)) = self.keep_alive {
+            http2.keep_alive_interval(interval).keep_alive_timeout(timeout);
+        }
+    }
+}
+
+// HTTP/1.1 and HTTP/2 told apart by the connection preface, or HTTP/1.1 only
+// (the auto builder ignores `http1_only` when serving upgrades)
+pub(crate) enum Protocols {
+    Auto(auto::Builder<TokioExecutor>),
+    Http1(hyper::server::conn::http1::Builder),
+}
+
+impl Default for Http2Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,263 @@
+//! `Accept-Language` negotiation and translated error messages.
+//!
+//! ```ignore
+//! Server::new().localization(
+//!     Localization::new("en").supported(&["es", "fr"]).source(
+//!         Catalog::new()
+//!             .message("es", "Route {path} not found", "Ruta {path} no encontrada")
+//!             .message("es", "Rate limit exceeded", "Demasiadas solicitudes")
+//!             .message("es", "Validation failed", "La validación falló")
+//!             .message("es", "length must be at least {min}", "la longitud debe ser al menos {min}"),
+//!     ),
+//! );
+//!
+//! #[http_method(GET, "/greeting")]
+//! fn greeting(locale: Locale) -> Value {
+//!     json!({ "text": if locale.language() == "es" { "hola" } else { "hello" } })
+//! }
+//! ```
+//!
+//! Each request's locale is the supported one the client prefers most in its
+//! `Accept-Language` header, matching `es-MX` to `es` when only the language
+//! is supported, or else the default. `Locale` handler parameters receive it.
+//!
+//! Error messages are passed through the translation sources, in order, for
+//! the request's locale: those of handlers' `FerroxError`s and the framework's
+//! own, such as 404, 405, 429, authentication failures and the message and
+//! field messages of validation errors. Messages without a translation are sent
+//! as they are. Error responses carry the locale in `Content-Language`.
+//!
+//! A `Catalog` matches messages against templates, whose `{name}` placeholders
+//! stand for any text and are put back into the translation by name. Other
+//! sources, e.g. reading `.ftl` or `.po` files, implement [`TranslationSource`].
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use axum::http::header::ACCEPT_LANGUAGE;
+use axum::http::HeaderMap;
+
+use crate::context::RequestContext;
+use crate::error::FerroxError;
+
+/// The locale a request is answered in, e.g. `es` or `pt-BR`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Locale(Arc<str>);
+
+impl Locale {
+    pub fn new(tag: &str) -> Self {
+        Locale(tag.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The language subtag, lowercased: `pt` for `pt-BR`.
+    pub fn language(&self) -> String {
+        language(&self.0).to_ascii_lowercase()
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Translations of messages into locales.
+pub trait TranslationSource: Send + Sync + 'static {
+    /// `message` in `locale`, or `None` when this source has no translation for it.
+    fn translate(&self, locale: &Locale, message: &str) -> Option<String>;
+}
+
+/// Message templates and their translations, by locale.
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    locales: HashMap<String, Vec<(Template, String)>>,
+}
+
+impl Catalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Translate messages matching `template`, e.g. `Route {path} not found`, into `locale`.
+    pub fn message(mut self, locale: &str, template: &str, translation: &str) -> Self {
+        let messages = self.locales.entry(locale.to_ascii_lowercase()).or_default();
+        messages.push((Template::parse(template), translation.to_string()));
+        self
+    }
+
+    /// Add the `(template, translation)` pairs of `messages` for `locale`.
+    pub fn messages<K, V>(self, locale: &str, messages: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        messages.into_iter().fold(self, |catalog, (template, translation)| {
+            catalog.message(locale, template.as_ref(), translation.as_ref())
+        })
+    }
+}
+
+impl TranslationSource for Catalog {
+    fn translate(&self, locale: &Locale, message: &str) -> Option<String> {
+        let tag = locale.as_str().to_ascii_lowercase();
+        // The locale's own messages, then its language's
+        [tag.as_str(), language(&tag)].into_iter().find_map(|tag| {
+            self.locales.get(tag)?.iter().find_map(|(template, translation)| {
+                let values = template.matches(message)?;
+                Some(fill(translation, &values))
+            })
+        })
+    }
+}
+
+// Literal text and the placeholders between it: `Route {path} not found` is
+// `["Route ", " not found"]` around `["path"]`
+#[derive(Debug, Clone)]
+struct Template {
+    literals: Vec<String>,
+    names: Vec<String>,
+}
+
+impl Template {
+    fn parse(template: &str) -> Self {
+        let mut literals = Vec::new();
+        let mut names = Vec::new();
+        let mut rest = template;
+        while let Some((literal, after)) = rest.split_once('{') {
+            let Some((name, after)) = after.split_once('}') else {
+                break;
+            };
+            literals.push(literal.to_string());
+            names.push(name.to_string());
+            rest = after;
+        }
+        literals.push(rest.to_string());
+        Template { literals, names }
+    }
+
+    // The placeholders' values in `message`, if it has the template's shape
+    fn matches<'m>(&self, message: &'m str) -> Option<Vec<(&str, &'m str)>> {
+        let mut rest = message.strip_prefix(self.literals[0].as_str())?;
+        let mut values = Vec::new();
+        for (index, (name, literal)) in self.names.iter().zip(&self.literals[1..]).enumerate() {
+            // A placeholder ending the template takes the rest of the message
+            let end = match literal.is_empty() && index + 1 == self.names.len() {
+                true => rest.len(),
+                false => rest.find(literal.as_str())?,
+            };
+            values.push((name.as_str(), &rest[..end]));
+            rest = &rest[end + literal.len()..];
+        }
+        rest.is_empty().then_some(values)
+    }
+}
+
+fn fill(translation: &str, values: &[(&str, &str)]) -> String {
+    values.iter().fold(translation.to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{}}}", name), value)
+    })
+}
+
+/// The locales a server answers in and where their messages come from;
+/// register it with `Server::localization`.
+#[derive(Clone)]
+pub struct Localization {
+    default: Locale,
+    supported: Vec<Locale>,
+    sources: Vec<Arc<dyn TranslationSource>>,
+}
+
+impl Localization {
+    /// Answer in `default` unless the client prefers a supported locale.
+    pub fn new(default: &str) -> Self {
+        Localization {
+            default: Locale::new(default),
+            supported: vec![Locale::new(default)],
+            sources: Vec::new(),
+        }
+    }
+
+    /// Also answer in `locales`.
+    pub fn supported(mut self, locales: &[&str]) -> Self {
+        self.supported.extend(locales.iter().map(|locale| Locale::new(locale)));
+        self
+    }
+
+    /// Look translations up in `source`, after the sources added before it.
+    pub fn source(mut self, source: impl TranslationSource) -> Self {
+        self.sources.push(Arc::new(source));
+        self
+    }
+
+    /// The supported locale the `Accept-Language` header in `headers` prefers most.
+    pub fn negotiate(&self, headers: &HeaderMap) -> Locale {
+        let header = headers.get(ACCEPT_LANGUAGE).and_then(|value| value.to_str().ok());
+        preferences(header.unwrap_or_default())
+            .into_iter()
+            .find_map(|tag| {
+                let exact = self.supported.iter().find(|locale| locale.as_str().eq_ignore_ascii_case(tag));
+                exact.or_else(|| {
+                    let wanted = language(tag);
+                    self.supported.iter().find(|locale| language(&locale.0).eq_ignore_ascii_case(wanted))
+                })
+            })
+            .cloned()
+            .unwrap_or_else(|| self.default.clone())
+    }
+
+    // `message` in `locale`, from the first source that translates it
+    fn translate(&self, locale: &Locale, message: &str) -> Option<String> {
+        self.sources.iter().find_map(|source| source.translate(locale, message))
+    }
+
+    // `error` with its messages translated into `locale`
+    pub(crate) fn translate_error(&self, locale: &Locale, error: FerroxError) -> FerroxError {
+        let translate = |message: &str| self.translate(locale, message).unwrap_or_else(|| message.to_string());
+        match error {
+            FerroxError::Validation(errors) => {
+                let message = translate(errors.message());
+                FerroxError::Validation(errors.translated(message, translate))
+            }
+            error @ FerroxError::PermissionDenied(_) => error,
+            error => FerroxError::new(error.status(), translate(error.message())),
+        }
+    }
+}
+
+// The language tags in an `Accept-Language` value, most preferred first;
+// `*` and tags with `q=0` are left out
+fn preferences(header: &str) -> Vec<&str> {
+    let mut tags: Vec<(&str, f32)> = header
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (!tag.is_empty() && tag != "*" && quality > 0.0).then_some((tag, quality))
+        })
+        .collect();
+    // Stable, so equally preferred tags keep their order
+    tags.sort_by(|a, b| b.1.total_cmp(&a.1));
+    tags.into_iter().map(|(tag, _)| tag).collect()
+}
+
+fn language(tag: &str) -> &str {
+    tag.split(['-', '_']).next().unwrap_or(tag)
+}
+
+/// The request's locale, for `Locale` handler parameters: the negotiated one
+/// under `Server::localization`, or else the client's first preference, or `en`.
+pub fn locale(ctx: &RequestContext) -> Result<Locale, FerroxError> {
+    if let Some(locale) = ctx.extensions.get::<Locale>() {
+        return Ok(locale.clone());
+    }
+    let header = ctx.header(ACCEPT_LANGUAGE.as_str()).unwrap_or_default();
+    Ok(preferences(header).first().map_or_else(|| Locale::new("en"), |tag| Locale::new(tag)))
+}
@@ -0,0 +1,355 @@
+//! Safe retries of unsafe requests with the `Idempotency-Key` header.
+//!
+//! ```ignore
+//! Server::new().layer(IdempotencyLayer::new(MemoryStore::new()).ttl(Duration::from_secs(24 * 3600)));
+//! ```
+//!
+//! The first POST or PATCH request carrying a key runs as usual, and its
+//! response is stored under the key. Retries with the same key are answered
+//! with the stored response, marked `Idempotent-Replayed: true`, without running
+//! the handler again, so a client that lost a response can resend a payment
+//! without charging twice. Requests without the header are left alone unless
+//! `required(true)`.
+//!
+//! A key is tied to the request it first came with (method, path, query,
+//! `Authorization` header and body): reusing it for another request answers
+//! 422, and a retry arriving while the first is still running answers 409.
+//! Server errors, 401, 403, 408, 409 and 429 answers are not stored, so the
+//! retry runs again; neither are streamed responses of unknown length.
+//! Store failures answer 500.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::extract::Request;
+use axum::http::header::AUTHORIZATION;
+use axum::http::{HeaderName, HeaderValue, Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tower::{Layer, Service};
+
+use crate::error::FerroxError;
+use crate::session::StoreFuture;
+
+/// A response kept for replay, with the fingerprint of the request it answered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredResponse {
+    pub fingerprint: String,
+    pub status: u16,
+    pub headers: Vec<(String, Vec<u8>)>,
+    pub body: Vec<u8>,
+}
+
+/// What an [`IdempotencyStore`] holds under a key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Entry {
+    /// The first request with the key is still running.
+    InProgress { fingerprint: String },
+    Complete(StoredResponse),
+}
+
+/// Where idempotency keys and their responses live.
+///
+/// `claim` must be atomic, so that of two requests racing with the same key
+/// only one runs.
+pub trait IdempotencyStore: Send + Sync + 'static {
+    /// Mark `key` in progress for at most `lease` unless it holds an entry;
+    /// `None` once claimed, or else the entry it holds.
+    fn claim<'a>(&'a self, key: &'a str, fingerprint: &'a str, lease: Duration) -> StoreFuture<'a, Option<Entry>>;
+
+    /// Replace the claim on `key` with `response`, kept for `ttl`.
+    fn complete<'a>(&'a self, key: &'a str, response: &'a StoredResponse, ttl: Duration) -> StoreFuture<'a, ()>;
+
+    /// Drop the claim on `key`, so its next request runs.
+    fn release<'a>(&'a self, key: &'a str) -> StoreFuture<'a, ()>;
+}
+
+/// Keys kept in process memory; they are lost on restart and not shared
+/// between instances.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    entries: Arc<Mutex<HashMap<String, (Entry, Instant)>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IdempotencyStore for MemoryStore {
+    fn claim<'a>(&'a self, key: &'a str, fingerprint: &'a str, lease: Duration) -> StoreFuture<'a, Option<Entry>> {
+        let mut entries = self.entries.lock().unwrap();
+        // Drop expired keys as new ones arrive, so they do not pile up
+        let now = Instant::now();
+        entries.retain(|_, (_, expires)| *expires > now);
+        let held = match entries.get(key) {
+            Some((entry, _)) => Some(entry.clone()),
+            None => {
+                let entry = Entry::InProgress {
+                    fingerprint: fingerprint.to_string(),
+                };
+                entries.insert(key.to_string(), (entry, now + lease));
+                None
+            }
+        };
+        Box::pin(async move { Ok(held) })
+    }
+
+    fn complete<'a>(&'a self, key: &'a str, response: &'a StoredResponse, ttl: Duration) -> StoreFuture<'a, ()> {
+        let entry = (Entry::Complete(response.clone()), Instant::now() + ttl);
+        self.entries.lock().unwrap().insert(key.to_string(), entry);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn release<'a>(&'a self, key: &'a str) -> StoreFuture<'a, ()> {
+        self.entries.lock().unwrap().remove(key);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// A tower layer replaying stored responses to requests with a known
+/// `Idempotency-Key`; pass it to `Server::layer`, `Server::route_layer` or a
+/// route group's `middleware` as `layer = ...`.
+///
+/// By default POST and PATCH responses are kept for 24 hours, a running
+/// request holds its key for at most 5 minutes, and request bodies are read up
+/// to 2 MiB.
+#[derive(Clone)]
+pub struct IdempotencyLayer {
+    store: Arc<dyn IdempotencyStore>,
+    header: HeaderName,
+    methods: Vec<Method>,
+    ttl: Duration,
+    lease: Duration,
+    required: bool,
+    max_body_size: usize,
+}
+
+const MAX_KEY_LENGTH: usize = 255;
+
+impl IdempotencyLayer {
+    pub fn new(store: impl IdempotencyStore) -> Self {
+        IdempotencyLayer {
+            store: Arc::new(store),
+            header: HeaderName::from_static("idempotency-key"),
+            methods: vec![Method::POST, Method::PATCH],
+            ttl: Duration::from_secs(24 * 60 * 60),
+            lease: Duration::from_secs(5 * 60),
+            required: false,
+            max_body_size: 2 * 1024 * 1024,
+        }
+    }
+
+    /// How long a stored response is replayed.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// How long a running request holds its key, after which a retry runs
+    /// again; longer than the slowest handler.
+    pub fn lease(mut self, lease: Duration) -> Self {
+        self.lease = lease;
+        self
+    }
+
+    /// The methods whose requests are deduplicated, instead of POST and PATCH.
+    pub fn methods<const N: usize>(mut self, methods: [Method; N]) -> Self {
+        self.methods = methods.to_vec();
+        self
+    }
+
+    /// Read the key from `header` instead of `Idempotency-Key`.
+    pub fn header(mut self, header: &'static str) -> Self {
+        self.header = HeaderName::from_static(header);
+        self
+    }
+
+    /// Answer 400 to requests of the deduplicated methods that carry no key.
+    pub fn required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+
+    /// Request bodies over `bytes` answer 413.
+    pub fn max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = bytes;
+        self
+    }
+}
+
+impl<S> Layer<S> for IdempotencyLayer {
+    type Service = IdempotencyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IdempotencyService {
+            inner,
+            config: Arc::new(self.clone()),
+        }
+    }
+}
+
+/// The service `IdempotencyLayer` wraps routes in.
+#[derive(Clone)]
+pub struct IdempotencyService<S> {
+    inner: S,
+    config: Arc<IdempotencyLayer>,
+}
+
+impl<S> Service<Request> for IdempotencyService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        // The clone takes the place of the service that was polled ready
+        let clone = self.inner.clone();
+        let inner = std::mem::replace(&mut self.inner, clone);
+        let config = self.config.clone();
+        Box::pin(deduplicated(config, inner, request))
+    }
+}
+
+async fn deduplicated<S>(config: Arc<IdempotencyLayer>, mut inner: S, request: Request) -> Result<Response, S::Error>
+where
+    S: Service<Request, Response = Response>,
+{
+    if !config.methods.contains(request.method()) {
+        return inner.call(request).await;
+    }
+    let key = match request.headers().get(&config.header).map(HeaderValue::to_str) {
+        Some(Ok(key)) if !key.is_empty() && key.len() <= MAX_KEY_LENGTH => key.to_string(),
+        Some(_) => {
+            let message = format!("The {} header must be 1 to {} visible characters", config.header, MAX_KEY_LENGTH);
+            return Ok(FerroxError::BadRequest(message).into_response());
+        }
+        None if config.required => {
+            let message = format!("The {} header is required", config.header);
+            return Ok(FerroxError::BadRequest(message).into_response());
+        }
+        None => return inner.call(request).await,
+    };
+
+    if crate::expect::declares_over(request.headers(), config.max_body_size) {
+        return Ok(crate::dispatch::body_too_large(config.max_body_size));
+    }
+    let (parts, body) = request.into_parts();
+    let bytes = match axum::body::to_bytes(body, config.max_body_size).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            let over_limit = std::error::Error::source(&err)
+                .is_some_and(|source| source.is::<http_body_util::LengthLimitError>());
+            if over_limit {
+                return Ok(crate::dispatch::body_too_large(config.max_body_size));
+            }
+            return Ok(FerroxError::BadRequest("Failed to read request body".to_string()).into_response());
+        }
+    };
+    let fingerprint = fingerprint(&parts, &bytes);
+
+    match config.store.claim(&key, &fingerprint, config.lease).await {
+        Ok(None) => {}
+        Ok(Some(entry)) => return Ok(answer_duplicate(entry, &fingerprint, &config.header)),
+        Err(err) => {
+            tracing::error!("Idempotency store failed: {}", err);
+            return Ok(FerroxError::Internal("Internal server error".to_string()).into_response());
+        }
+    }
+
+    let response = inner.call(Request::from_parts(parts, Body::from(bytes))).await?;
+    let status = response.status();
+    let storable = !(status.is_server_error()
+        || matches!(
+            status,
+            StatusCode::UNAUTHORIZED
+                | StatusCode::FORBIDDEN
+                | StatusCode::REQUEST_TIMEOUT
+                | StatusCode::CONFLICT
+                | StatusCode::TOO_MANY_REQUESTS
+        ));
+    if !storable || response.body().size_hint().exact().is_none() {
+        if let Err(err) = config.store.release(&key).await {
+            tracing::error!("Idempotency store failed: {}", err);
+        }
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let Ok(body) = axum::body::to_bytes(body, usize::MAX).await else {
+        let _ = config.store.release(&key).await;
+        return Ok(FerroxError::Internal("Failed to read response".to_string()).into_response());
+    };
+    let stored = StoredResponse {
+        fingerprint,
+        status: status.as_u16(),
+        headers: parts
+            .headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+            .collect(),
+        body: body.to_vec(),
+    };
+    if let Err(err) = config.store.complete(&key, &stored, config.ttl).await {
+        tracing::error!("Idempotency store failed: {}", err);
+    }
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+// What a key is tied to: the same key on another request is a client bug
+fn fingerprint(parts: &axum::http::request::Parts, body: &Bytes) -> String {
+    let mut hash = Sha256::new();
+    for part in [
+        parts.method.as_str().as_bytes(),
+        parts.uri.path().as_bytes(),
+        parts.uri.query().unwrap_or_default().as_bytes(),
+        parts.headers.get(AUTHORIZATION).map(HeaderValue::as_bytes).unwrap_or_default(),
+    ] {
+        hash.update((part.len() as u64).to_be_bytes());
+        hash.update(part);
+    }
+    hash.update(body);
+    hash.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn answer_duplicate(entry: Entry, fingerprint: &str, header: &HeaderName) -> Response {
+    let held = match &entry {
+        Entry::InProgress { fingerprint } => fingerprint,
+        Entry::Complete(stored) => &stored.fingerprint,
+    };
+    if held != fingerprint {
+        let message = format!("The {} was already used for a different request", header);
+        return FerroxError::UnprocessableEntity(message).into_response();
+    }
+    let stored = match entry {
+        Entry::InProgress { .. } => {
+            let message = format!("A request with this {} is still being processed", header);
+            return FerroxError::Conflict(message).into_response();
+        }
+        Entry::Complete(stored) => stored,
+    };
+    let mut response = Response::new(Body::from(stored.body));
+    *response.status_mut() = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    for (name, value) in stored.headers {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::from_bytes(&value)) {
+            response.headers_mut().append(name, value);
+        }
+    }
+    response
+        .headers_mut()
+        .insert(HeaderName::from_static("idempotent-replayed"), HeaderValue::from_static("true"));
+    response
+}
@@ -0,0 +1,319 @@
+//! Background jobs run by a pool of workers, outside the request that queued them.
+//!
+//! ```ignore
+//! let jobs = JobQueue::new(JobsConfig::new().workers(8).retry(RetryPolicy::exponential(5, Duration::from_secs(1))));
+//! Server::new().jobs(jobs);
+//!
+//! #[http_method(POST, "/users")]
+//! fn create_user(jobs: State<JobQueue>, body: NewUser) -> Result<Value, FerroxError> {
+//!     let email = body.email.clone();
+//!     jobs.enqueue("welcome_email", move || send_welcome(email.clone()))?;
+//!     Ok(json!({ "created": true }))
+//! }
+//! ```
+//!
+//! A job is a closure returning a future that resolves to `Result<(), E>`; it is
+//! called again for each retry. Failed attempts, including panics, are retried
+//! under the queue's `RetryPolicy`, the worker waiting out the backoff, and
+//! logged. `enqueue` answers 503 when the queue is full or shutting down.
+//!
+//! When the server shuts down gracefully, jobs already queued still run within
+//! the shutdown timeout, after in-flight requests have finished.
+
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinHandle;
+
+use crate::error::FerroxError;
+
+type JobFn = Arc<dyn Fn() -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+/// How often and how soon a failed job is tried again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    delay: Duration,
+    exponential: bool,
+    max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Run each job once.
+    pub const fn none() -> Self {
+        RetryPolicy::fixed(1, Duration::ZERO)
+    }
+
+    /// Up to `max_attempts` attempts, `delay` apart.
+    pub const fn fixed(max_attempts: u32, delay: Duration) -> Self {
+        RetryPolicy {
+            max_attempts,
+            delay,
+            exponential: false,
+            max_delay: delay,
+        }
+    }
+
+    /// Up to `max_attempts` attempts, waiting `base` after the first failure and
+    /// twice as long after each further one, at most 5 minutes.
+    pub const fn exponential(max_attempts: u32, base: Duration) -> Self {
+        RetryPolicy {
+            max_attempts,
+            delay: base,
+            exponential: true,
+            max_delay: Duration::from_secs(300),
+        }
+    }
+
+    /// Longest wait between attempts.
+    pub const fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    // How many attempts a task gets, at least one
+    pub(crate) fn attempts(&self) -> u32 {
+        self.max_attempts.max(1)
+    }
+
+    // The wait after `failures` failed attempts
+    pub(crate) fn backoff(&self, failures: u32) -> Duration {
+        if !self.exponential {
+            return self.delay;
+        }
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        self.delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts, 1s and then 2s apart.
+    fn default() -> Self {
+        RetryPolicy::exponential(3, Duration::from_secs(1))
+    }
+}
+
+/// Size of the worker pool and queue; pass it to `JobQueue::new`.
+///
+/// By default 4 workers take jobs from a queue of up to 1024, retrying with
+/// `RetryPolicy::default()`.
+#[derive(Debug, Clone)]
+pub struct JobsConfig {
+    workers: usize,
+    capacity: usize,
+    retry: RetryPolicy,
+}
+
+impl JobsConfig {
+    pub fn new() -> Self {
+        JobsConfig {
+            workers: 4,
+            capacity: 1024,
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// How many jobs run at once.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// How many jobs may wait for a worker before `enqueue` is refused.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Retry policy for jobs queued with `enqueue`.
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Queued {
+    name: String,
+    job: JobFn,
+    retry: RetryPolicy,
+}
+
+/// A handle for queueing jobs; clones share the same queue and workers.
+///
+/// Register it with `Server::jobs` to take it as a `State<JobQueue>` handler
+/// parameter and drain it on shutdown.
+#[derive(Clone)]
+pub struct JobQueue {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    config: JobsConfig,
+    // Dropped on shutdown, so workers stop once the queue is empty
+    sender: Mutex<Option<mpsc::Sender<Queued>>>,
+    receiver: Arc<tokio::sync::Mutex<mpsc::Receiver<Queued>>>,
+    // Started with the first job, so a queue can be created outside a runtime
+    workers: OnceLock<Vec<JoinHandle<()>>>,
+    // Jobs queued or running
+    pending: AtomicUsize,
+    idle: Notify,
+}
+
+impl JobQueue {
+    pub fn new(config: JobsConfig) -> Self {
+        let (sender, receiver) = mpsc::channel(config.capacity);
+        JobQueue {
+            inner: Arc::new(Inner {
+                config,
+                sender: Mutex::new(Some(sender)),
+                receiver: Arc::new(tokio::sync::Mutex::new(receiver)),
+                workers: OnceLock::new(),
+                pending: AtomicUsize::new(0),
+                idle: Notify::new(),
+            }),
+        }
+    }
+
+    /// Queue `job` under `name`, which identifies it in logs, with the queue's retry policy.
+    pub fn enqueue<F, Fut, E>(&self, name: &str, job: F) -> Result<(), FerroxError>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display,
+    {
+        self.enqueue_with(name, self.inner.config.retry, job)
+    }
+
+    /// Queue `job` with its own retry policy.
+    pub fn enqueue_with<F, Fut, E>(&self, name: &str, retry: RetryPolicy, job: F) -> Result<(), FerroxError>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display,
+    {
+        let job: JobFn = Arc::new(move || {
+            let run = job();
+            Box::pin(async move { run.await.map_err(|err| err.to_string()) })
+        });
+        let queued = Queued {
+            name: name.to_string(),
+            job,
+            retry,
+        };
+        let sender = self.inner.sender.lock().unwrap().clone();
+        let Some(sender) = sender else {
+            return Err(unavailable("Job queue is shutting down"));
+        };
+        self.start_workers();
+        self.inner.pending.fetch_add(1, Ordering::SeqCst);
+        sender.try_send(queued).map_err(|err| {
+            self.finished();
+            match err {
+                mpsc::error::TrySendError::Full(_) => unavailable("Job queue is full"),
+                mpsc::error::TrySendError::Closed(_) => unavailable("Job queue is shutting down"),
+            }
+        })
+    }
+
+    /// Jobs queued or running.
+    pub fn pending(&self) -> usize {
+        self.inner.pending.load(Ordering::SeqCst)
+    }
+
+    /// Stop taking jobs and wait up to `timeout` for those already queued to finish.
+    ///
+    /// Returns whether they all did; `Server` calls this on graceful shutdown.
+    pub async fn shutdown(&self, timeout: Duration) -> bool {
+        self.inner.sender.lock().unwrap().take();
+        let drained = tokio::time::timeout(timeout, async {
+            loop {
+                // Registered before checking, so a job finishing in between still wakes us
+                let idle = self.inner.idle.notified();
+                tokio::pin!(idle);
+                idle.as_mut().enable();
+                if self.pending() == 0 {
+                    break;
+                }
+                idle.await;
+            }
+        })
+        .await
+        .is_ok();
+        if !drained {
+            tracing::warn!("{} background jobs did not finish within {:?}", self.pending(), timeout);
+        }
+        drained
+    }
+
+    // Stop taking jobs and cancel those running
+    pub(crate) fn abort(&self) {
+        self.inner.sender.lock().unwrap().take();
+        for worker in self.inner.workers.get().into_iter().flatten() {
+            worker.abort();
+        }
+    }
+
+    fn start_workers(&self) {
+        self.inner.workers.get_or_init(|| {
+            (0..self.inner.config.workers)
+                .map(|_| {
+                    let queue = self.clone();
+                    tokio::spawn(async move { queue.work().await })
+                })
+                .collect()
+        });
+    }
+
+    async fn work(self) {
+        loop {
+            let next = self.inner.receiver.lock().await.recv().await;
+            let Some(queued) = next else { break };
+            run(queued).await;
+            self.finished();
+        }
+    }
+
+    fn finished(&self) {
+        if self.inner.pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.inner.idle.notify_waiters();
+        }
+    }
+}
+
+// Run one job, retrying failures under its policy
+async fn run(queued: Queued) {
+    let Queued { name, job, retry } = queued;
+    for attempt in 1..=retry.attempts() {
+        // Spawned so a panicking job fails its attempt instead of killing the worker
+        let error = match tokio::spawn(job()).await {
+            Ok(Ok(())) => {
+                tracing::debug!(job = %name, attempt, "Job succeeded");
+                return;
+            }
+            Ok(Err(message)) => message,
+            Err(_) => "job panicked".to_string(),
+        };
+        if attempt >= retry.attempts() {
+            tracing::error!(job = %name, attempt, "Job failed, giving up: {}", error);
+            return;
+        }
+        let backoff = retry.backoff(attempt);
+        tracing::warn!(job = %name, attempt, "Job failed, retrying in {:?}: {}", backoff, error);
+        tokio::time::sleep(backoff).await;
+    }
+}
+
+fn unavailable(message: &str) -> FerroxError {
+    FerroxError::new(axum::http::StatusCode::SERVICE_UNAVAILABLE, message)
+}
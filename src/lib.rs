@@ -1,161 +1,2243 @@
-// Re-export the macro for convenience
-pub use ferrox_macros::http_method;
+// Re-export the macros for convenience
+pub use ferrox_macros::{http_method, middleware, route_group, scheduled, sse, subscribe, websocket};
 
-// Server-side runtime imports
-use axum::{
-    extract::{Path, Query},
-    http::StatusCode,
-    response::{IntoResponse, Json},
-    routing::{delete, get, patch, post, put},
-    Router,
+pub mod admin;
+pub mod audit;
+pub mod auth;
+pub mod batch;
+pub mod bench;
+pub mod body_log;
+pub mod cache;
+pub mod chaos;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod codegen;
+pub mod compression;
+pub mod config;
+pub mod connection;
+pub mod cors;
+#[cfg(any(feature = "sqlx", feature = "deadpool-postgres"))]
+pub mod database;
+pub mod di;
+pub mod dynamic;
+pub mod envelope;
+pub mod etag;
+pub mod events;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod extract;
+pub mod flags;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod guard;
+pub mod health;
+pub mod hooks;
+pub mod http2;
+pub mod i18n;
+pub mod idempotency;
+pub mod jobs;
+pub mod listener;
+pub mod logging;
+pub mod middleware;
+pub mod mock;
+pub mod network;
+pub mod normalize;
+pub mod openapi;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod pagination;
+pub mod patch;
+pub mod plugin;
+pub mod proxy;
+pub mod query;
+pub mod quota;
+pub mod ratelimit;
+pub mod rbac;
+pub mod recorder;
+pub mod resilience;
+#[cfg(unix)]
+pub mod restart;
+#[cfg(feature = "redis")]
+pub mod redis;
+pub mod runtime;
+pub mod scheduler;
+pub mod security;
+pub mod session;
+pub mod sse;
+pub mod static_files;
+pub mod streaming;
+#[cfg(feature = "templates")]
+pub mod templates;
+pub mod tenancy;
+pub mod test;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod validate;
+pub mod verify;
+pub mod versioning;
+#[cfg(feature = "webhooks")]
+pub mod webhooks;
+pub mod ws;
+
+mod blocking;
+mod coalesce;
+mod concurrency;
+mod constraints;
+mod context;
+mod deprecation;
+mod dispatch;
+mod error;
+mod expect;
+mod format;
+mod lifecycle;
+mod metrics;
+mod response;
+mod routes;
+mod shutdown;
+
+pub use axum::http::StatusCode;
+pub use context::{AppState, RequestCache, RequestContext, State};
+pub use error::{ErrorContext, FerroxError};
+pub use extract::FromFerroxRequest;
+pub use lifecycle::StartupError;
+pub use response::{
+    json_response, Accepted, ApiResponse, Created, HandlerResponse, IntoHandlerResponse, Json, NoContent, NonObjectResponse,
 };
-use inventory;
-use lazy_static::lazy_static;
-use serde::Serialize;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use tower::ServiceBuilder;
+pub use routes::{routes, RouteConflict, RouteInfo};
+pub use shutdown::ServerHandle;
+pub use streaming::StreamingResponse;
 
-#[derive(Serialize, Clone)]
-pub struct ApiResponse<T> {
-    pub success: bool,
-    pub data: Option<T>,
-    pub message: String,
-}
+// Used by code generated from #[http_method]
+#[doc(hidden)]
+pub use axum;
+#[doc(hidden)]
+pub use response::__private;
+#[doc(hidden)]
+pub use inventory;
+
+// Server-side runtime imports
+use axum::{response::IntoResponse, Router};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tower::{Layer, Service};
 
 // Route registration via inventory with generic function interface
 // Routes are automatically registered by macros - no naming scheme needed
 pub struct RouteRegistration {
     pub method: &'static str,
     pub path: &'static str,
-    pub handler_fn: fn() -> RouteHandler,
+    pub handler: RouteKind,
+    /// Layers from `#[middleware]` attributes, outermost first.
+    pub middleware: &'static [middleware::MiddlewareFn],
+    /// Names of the `#[middleware]` entries, in the same order, for `routes()`.
+    pub middleware_names: &'static [&'static str],
+    /// Name of the annotated function.
+    pub handler_name: &'static str,
+    /// The handler's doc comment, for documentation; empty without one.
+    pub doc: &'static str,
+    /// Handler parameters as declared, for documentation.
+    pub params: &'static [ParamInfo],
+    /// The handler's return type as declared, e.g. `Result<Json<User>,FerroxError>`,
+    /// for documentation; `()` when it returns nothing.
+    pub response: &'static str,
+    /// `file:line` of the route attribute, for error messages.
+    pub location: &'static str,
+    /// Per-route settings from the route attribute's options.
+    pub options: RouteOptions,
 }
 
-inventory::collect!(RouteRegistration);
+/// Per-route settings given as `#[http_method]` options.
+///
+/// Built in constant context by the route macros, starting from
+/// `RouteOptions::DEFAULT` and applying one setter per option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RouteOptions {
+    /// `timeout = "..."`: handler time limit, overriding `Server::default_timeout`.
+    pub timeout: Option<Duration>,
+    /// `auth = "..."`: authentication scheme registered with `Server::authenticator`.
+    pub auth: Option<&'static str>,
+    /// `rate_limit = "..."`: request limit for this route, counted separately from `Server::rate_limit`.
+    pub rate_limit: Option<ratelimit::RateLimit>,
+    /// `max_body_size = "..."`: request body limit in bytes, overriding `Server::max_body_size`.
+    pub max_body_size: Option<usize>,
+    /// `blocking = true`: run the synchronous handler off the async workers, on
+    /// the `Server::blocking_threads` pool or else tokio's blocking pool.
+    pub blocking: bool,
+    /// `version = "..."`: the API version this handler serves, see `Server::versioning`.
+    pub version: Option<&'static str>,
+    /// `cache = "..."`: how long GET responses are served from the response cache.
+    pub cache: Option<Duration>,
+    /// `coalesce = true`: identical GET requests arriving together share one handler call.
+    pub coalesce: bool,
+    /// `circuits = [...]`: the `Server::circuit_breaker`s whose opening makes the
+    /// route answer 503, see `ferrox::resilience`.
+    pub circuits: &'static [&'static str],
+    /// `head = false` turns off the HEAD responses a GET route serves by running
+    /// the handler and dropping the body.
+    pub head: bool,
+    /// `{name:constraint}` path segments, as `(name, constraint)`: a type such as
+    /// `u64`, or a regex the whole value must match.
+    pub constraints: &'static [(&'static str, &'static str)],
+    /// `concurrency_limit = N`: most requests the handler serves at once, more
+    /// answering 503 with `Retry-After`.
+    pub concurrency_limit: Option<usize>,
+    /// `guards = [...]`: checks run in order before the handler, see `ferrox::guard`.
+    pub guards: &'static [guard::GuardFn],
+    /// `permission = "..."`: permission the request's principal needs, see `ferrox::rbac`.
+    pub permission: Option<&'static str>,
+    /// `verify = "..."`: webhook signature check registered with `Server::verifier`.
+    pub verify: Option<&'static str>,
+    /// Set when the handler takes a `Bytes` parameter: the body is handed over
+    /// as sent instead of being parsed.
+    pub raw_body: bool,
+    /// `filter = [...]`, `sort = [...]` and `fields = [...]`: what `QuerySpec`
+    /// parameters accept, see `ferrox::query`.
+    pub query: query::Allowlist,
+    /// `flag = "..."`: feature flag the route answers 404 without, see `ferrox::flags`.
+    pub flag: Option<&'static str>,
+    /// `deprecated = true`: responses carry `Deprecation`, see `successor` and `sunset`.
+    pub deprecated: bool,
+    /// `sunset = "..."`: the `YYYY-MM-DD` date the route stops being served, sent as `Sunset`.
+    pub sunset: Option<&'static str>,
+    /// `successor = "..."`: the route replacing this one, sent as a `successor-version` link.
+    pub successor: Option<&'static str>,
+    /// `tag = "..."`: the group the route is documented under, in OpenAPI and `routes()`.
+    pub tag: Option<&'static str>,
+    /// `cors = ...`: the route's own CORS policy, in place of `Server::cors`.
+    pub cors: Option<cors::RouteCors>,
+    /// `cost = N`: units each request takes from the client's `Server::quotas` budget.
+    pub cost: Option<u32>,
+    /// `registry = "..."`: the named registry the route belongs to, served by
+    /// `Server::from_registry`; `None` for the default one.
+    pub registry: Option<&'static str>,
+    /// `status = 201`: the 2xx status of the handler's successful results, in place of 200.
+    pub status: Option<u16>,
+    /// `audit = false` leaves the route's requests out of `Server::audit`.
+    pub audit: bool,
+    /// `if_match = true`: PUT, PATCH and DELETE requests without `If-Match` or
+    /// `If-Unmodified-Since` answer 428, see `ferrox::etag`.
+    pub if_match: bool,
+}
+
+impl RouteOptions {
+    pub const DEFAULT: RouteOptions = RouteOptions {
+        timeout: None,
+        auth: None,
+        rate_limit: None,
+        max_body_size: None,
+        blocking: false,
+        version: None,
+        cache: None,
+        coalesce: false,
+        circuits: &[],
+        head: true,
+        constraints: &[],
+        concurrency_limit: None,
+        guards: &[],
+        permission: None,
+        verify: None,
+        raw_body: false,
+        query: query::Allowlist::NONE,
+        flag: None,
+        deprecated: false,
+        sunset: None,
+        successor: None,
+        tag: None,
+        cors: None,
+        cost: None,
+        registry: None,
+        status: None,
+        audit: true,
+        if_match: false,
+    };
+
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub const fn auth(mut self, scheme: &'static str) -> Self {
+        self.auth = Some(scheme);
+        self
+    }
+
+    pub const fn rate_limit(mut self, rate: ratelimit::RateLimit) -> Self {
+        self.rate_limit = Some(rate);
+        self
+    }
+
+    pub const fn max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = Some(bytes);
+        self
+    }
+
+    pub const fn blocking(mut self) -> Self {
+        self.blocking = true;
+        self
+    }
+
+    pub const fn version(mut self, version: &'static str) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    pub const fn cache(mut self, ttl: Duration) -> Self {
+        self.cache = Some(ttl);
+        self
+    }
+
+    pub const fn coalesce(mut self) -> Self {
+        self.coalesce = true;
+        self
+    }
+
+    pub const fn circuits(mut self, names: &'static [&'static str]) -> Self {
+        self.circuits = names;
+        self
+    }
+
+    pub const fn without_head(mut self) -> Self {
+        self.head = false;
+        self
+    }
+
+    pub const fn constraints(mut self, constraints: &'static [(&'static str, &'static str)]) -> Self {
+        self.constraints = constraints;
+        self
+    }
+
+    pub const fn concurrency_limit(mut self, limit: usize) -> Self {
+        self.concurrency_limit = Some(limit);
+        self
+    }
+
+    pub const fn guards(mut self, guards: &'static [guard::GuardFn]) -> Self {
+        self.guards = guards;
+        self
+    }
+
+    pub const fn permission(mut self, permission: &'static str) -> Self {
+        self.permission = Some(permission);
+        self
+    }
+
+    pub const fn verify(mut self, verifier: &'static str) -> Self {
+        self.verify = Some(verifier);
+        self
+    }
+
+    pub const fn raw_body(mut self) -> Self {
+        self.raw_body = true;
+        self
+    }
+
+    pub const fn query(mut self, allowlist: query::Allowlist) -> Self {
+        self.query = allowlist;
+        self
+    }
+
+    pub const fn flag(mut self, flag: &'static str) -> Self {
+        self.flag = Some(flag);
+        self
+    }
+
+    pub const fn deprecated(mut self) -> Self {
+        self.deprecated = true;
+        self
+    }
+
+    pub const fn sunset(mut self, date: &'static str) -> Self {
+        self.sunset = Some(date);
+        self
+    }
+
+    pub const fn successor(mut self, path: &'static str) -> Self {
+        self.successor = Some(path);
+        self
+    }
+
+    pub const fn tag(mut self, tag: &'static str) -> Self {
+        self.tag = Some(tag);
+        self
+    }
+
+    pub const fn cors(mut self, cors: cors::RouteCors) -> Self {
+        self.cors = Some(cors);
+        self
+    }
+
+    pub const fn cost(mut self, cost: u32) -> Self {
+        self.cost = Some(cost);
+        self
+    }
+
+    pub const fn registry(mut self, name: &'static str) -> Self {
+        self.registry = Some(name);
+        self
+    }
+
+    pub const fn status(mut self, status: u16) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub const fn without_audit(mut self) -> Self {
+        self.audit = false;
+        self
+    }
+
+    pub const fn if_match(mut self) -> Self {
+        self.if_match = true;
+        self
+    }
+}
+
+impl Default for RouteOptions {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// What serves a registered route.
+pub enum RouteKind {
+    /// A REST handler from `#[http_method]`.
+    Http(fn() -> RouteHandler),
+    /// A WebSocket upgrade handler from `#[websocket]`.
+    WebSocket(fn() -> ws::WsHandler),
+    /// A Server-Sent Events stream from `#[sse]`.
+    Sse(fn() -> sse::SseHandler),
+}
+
+/// A handler parameter recorded by `#[http_method]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct ParamInfo {
+    /// Placeholder name for `PathParam`, otherwise the source name (`path`, `query`, `body`, `state`, `context`, `identity`, `tenant`).
+    pub name: &'static str,
+    pub source: ParamSource,
+    /// The declared Rust type, e.g. `u64` or `Option<Filters>`.
+    #[serde(rename = "type")]
+    pub type_name: &'static str,
+    /// The parameter's doc comment, if it has one.
+    pub description: Option<&'static str>,
+}
+
+impl ParamInfo {
+    pub const fn new(name: &'static str, source: ParamSource, type_name: &'static str) -> Self {
+        ParamInfo {
+            name,
+            source,
+            type_name,
+            description: None,
+        }
+    }
+
+    pub const fn description(mut self, description: &'static str) -> Self {
+        self.description = Some(description);
+        self
+    }
+}
 
-// Global route registry
-lazy_static! {
-    pub static ref GLOBAL_ROUTE_REGISTRY: Mutex<HashMap<(String, String), RouteHandler>> = Mutex::new(HashMap::new());
+/// Where the framework takes a handler parameter from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamSource {
+    /// One path placeholder.
+    PathParam,
+    /// All path parameters.
+    Path,
+    Query,
+    Body,
+    State,
+    /// The whole `RequestContext`.
+    Context,
+    /// The identity recorded by the route's authenticator, e.g. `Claims` or `ApiKey`.
+    Identity,
+    /// The request's `Session` or `Cookies`.
+    Session,
+    /// The unparsed request body, as `Bytes`.
+    RawBody,
+    /// The `page`, `per_page` and `cursor` query parameters, as `Pagination`.
+    Pagination,
+    /// The `filter`, `sort` and `fields` query parameters, as `QuerySpec`.
+    QuerySpec,
+    /// The request's database transaction, as `Tx`.
+    Transaction,
+    /// The request's tenant, as `TenantId`.
+    Tenant,
+    /// The request's negotiated `Locale`.
+    Locale,
+    /// An application type implementing `FromFerroxRequest`.
+    Extractor,
+    /// A service from the server's `Container`, as `Dep<T>`.
+    Service,
 }
 
+inventory::collect!(RouteRegistration);
+
 // Generic handler interface - functions take JSON params and return JSON response
 // The framework converts JSON responses to HTTP responses automatically
-pub type RouteHandler = Arc<dyn Fn(serde_json::Value, serde_json::Value, serde_json::Value) -> serde_json::Value + Send + Sync>;
+pub type HandlerFuture = Pin<Box<dyn Future<Output = HandlerResponse> + Send>>;
+pub type SyncRouteHandler = Arc<dyn Fn(RequestContext) -> HandlerResponse + Send + Sync>;
+pub type AsyncRouteHandler = Arc<dyn Fn(RequestContext) -> HandlerFuture + Send + Sync>;
+
+/// A registered handler: either a plain function or an `async fn` returning a boxed future.
+#[derive(Clone)]
+pub enum RouteHandler {
+    Sync(SyncRouteHandler),
+    Async(AsyncRouteHandler),
+}
+
+impl RouteHandler {
+    /// Wrap a synchronous handler returning any `IntoHandlerResponse` type.
+    pub fn from_sync<F, R>(handler: F) -> Self
+    where
+        F: Fn(RequestContext) -> R + Send + Sync + 'static,
+        R: IntoHandlerResponse,
+    {
+        RouteHandler::Sync(Arc::new(move |ctx| handler(ctx).into_handler_response()))
+    }
+
+    /// Wrap an async handler, boxing the future it returns.
+    pub fn from_async<F, Fut>(handler: F) -> Self
+    where
+        F: Fn(RequestContext) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: IntoHandlerResponse,
+    {
+        RouteHandler::Async(Arc::new(move |ctx| {
+            let future = handler(ctx);
+            Box::pin(async move { future.await.into_handler_response() })
+        }))
+    }
+}
 
 // Server struct - routes are automatically registered by http_method! macros
-pub struct Server;
+//
+// Request timeouts are split into two phases. The body read timeout bounds the
+// whole time spent receiving the request body, so a client streaming a large
+// upload slowly is cut off with 408 Request Timeout. The handler timeout starts
+// once the body has been read and bounds the handler call; exceeding it returns
+// 504 Gateway Timeout. Both are disabled by default, and a route's own
+// `timeout` option takes precedence over the server-wide handler timeout.
+#[derive(Default)]
+pub struct Server {
+    non_object_response: NonObjectResponse,
+    body_read_timeout: Option<Duration>,
+    default_timeout: Option<Duration>,
+    max_body_size: Option<usize>,
+    blocking_threads: Option<usize>,
+    cache_capacity: Option<usize>,
+    cache_store: Option<Arc<dyn cache::CacheStore>>,
+    state: AppState,
+    layers: Vec<middleware::RouterLayer>,
+    route_layers: HashMap<(String, String), Vec<middleware::MethodRouterLayer>>,
+    hooks: hooks::Hooks,
+    handle_signals: bool,
+    restart_on_signal: bool,
+    shutdown_timeout: Option<Duration>,
+    openapi: Option<openapi::OpenApiConfig>,
+    authenticators: HashMap<&'static str, Arc<dyn auth::Authenticator>>,
+    verifiers: HashMap<&'static str, Arc<dyn verify::Verifier>>,
+    circuits: Vec<resilience::CircuitBreaker>,
+    rbac: Option<rbac::Rbac>,
+    quotas: Option<quota::Quotas>,
+    audit: Option<audit::AuditLog>,
+    rate_limiter: Option<ratelimit::RateLimiter>,
+    max_in_flight: Option<usize>,
+    dynamic: dynamic::DynamicRoutes,
+    trusted_proxies: Vec<network::IpNet>,
+    cors: Option<cors::CorsConfig>,
+    compression: Option<compression::CompressionConfig>,
+    etags: bool,
+    sparse_fieldsets: bool,
+    responder: envelope::Responder,
+    not_found: Option<axum::routing::MethodRouter<AppState>>,
+    log_format: logging::LogFormat,
+    log_level: Option<String>,
+    quiet: bool,
+    startup_report: Option<logging::StartupReporter>,
+    access_log: bool,
+    metrics_path: Option<String>,
+    debug_routes: Option<String>,
+    health_checks: Option<health::HealthChecks>,
+    // Readiness checks of the pools the server manages, added to `health_checks`
+    readiness_checks: Vec<(String, health::Check)>,
+    sessions: Option<session::SessionConfig>,
+    jobs: Option<jobs::JobQueue>,
+    events: Option<events::EventBus>,
+    schedules: Vec<scheduler::ScheduledTask>,
+    startup_hooks: Vec<lifecycle::Hook>,
+    plugins: Vec<String>,
+    shutdown_hooks: Vec<lifecycle::Hook>,
+    #[cfg(feature = "otel")]
+    otel: Option<otel::OtelConfig>,
+    static_files: Vec<static_files::StaticFiles>,
+    spa_fallback: Option<std::path::PathBuf>,
+    proxies: Vec<proxy::Proxy>,
+    #[cfg(feature = "graphql")]
+    graphql: Vec<graphql::GraphQL>,
+    #[cfg(feature = "grpc")]
+    grpc: Option<tonic::service::Routes>,
+    #[cfg(feature = "templates")]
+    templates: Option<templates::Templates>,
+    listeners: Vec<listener::Listener>,
+    http2: http2::Http2Config,
+    connections: connection::ConnectionConfig,
+    runtime: runtime::RuntimeConfig,
+    admin: Option<admin::Admin>,
+    // The admin endpoints and their address, when served on their own listener
+    admin_app: Option<(String, Router)>,
+    versioning: Option<versioning::Versioning>,
+    path_normalization: Option<normalize::PathNormalization>,
+    batch: Option<batch::Batch>,
+    mock: Option<mock::MockServer>,
+    recorder: Option<recorder::Recorder>,
+    body_log: Option<body_log::BodyLog>,
+    chaos: Option<chaos::Chaos>,
+    tenancy: Option<tenancy::Tenancy>,
+    bind_addr: Option<String>,
+    tls_files: Option<config::TlsFiles>,
+    config_watcher: Option<config::ConfigWatcher>,
+    // The `[flags]` of the configuration file, for `config_watcher` to update
+    config_flags: Option<flags::ConfigFlags>,
+    // The named registry whose routes are served; the default one when `None`
+    registry: Option<String>,
+    // `api_keys`, also checked by rate limits keyed by API key
+    api_keys: Option<auth::api_key::ApiKeyAuth>,
+}
+
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+const DEFAULT_ADDR: &str = "127.0.0.1:3000";
 
 impl Server {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// A server with the settings in `config`, to be started with `run`.
+    ///
+    /// Builder calls made afterwards override the loaded settings.
+    pub fn from_config(config: config::Config) -> Self {
+        config::apply(Self::default(), config)
+    }
+
+    /// A server for the routes declared with `registry = "<name>"`, instead of
+    /// those declared without one, so one binary can serve several independent
+    /// APIs, e.g. a public one and an internal admin one:
+    ///
+    /// ```ignore
+    /// #[http_method(POST, "/tenants/:id/suspend", registry = "admin")]
+    /// async fn suspend(id: u64) -> Result<NoContent, FerroxError> { ... }
+    ///
+    /// let public = Server::new().start_in_background("0.0.0.0:8080").await?;
+    /// let admin = Server::from_registry("admin").start_in_background("127.0.0.1:9000").await?;
+    /// ```
+    ///
+    /// Everything else is configured per server as usual. `#[websocket]` and
+    /// `#[sse]` routes belong to the default registry.
+    pub fn from_registry(name: impl Into<String>) -> Self {
+        Server {
+            registry: Some(name.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Reload the settings of `watcher`'s file while running, when the file
+    /// changes or the process gets `SIGHUP`. See `ferrox::config`.
+    ///
+    /// ```ignore
+    /// let watcher = ConfigWatcher::load("ferrox.toml")?;
+    /// Server::from_config(watcher.config()).watch_config(watcher).run().await?;
+    /// ```
+    pub fn watch_config(mut self, watcher: config::ConfigWatcher) -> Self {
+        self.config_watcher = Some(watcher);
+        self
+    }
+
+    /// Wrap results and errors in `envelope` instead of the default `ApiResponse` shape.
+    ///
+    /// Every error the framework sends uses it, including 404s and rate limit rejections.
+    pub fn response_envelope(mut self, envelope: impl envelope::ResponseEnvelope) -> Self {
+        self.responder.envelope = Some(Arc::new(envelope));
+        self
+    }
+
+    /// Negotiate each request's locale from `Accept-Language`, for `Locale`
+    /// parameters, and send error messages translated into it.
+    ///
+    /// ```ignore
+    /// Server::new().localization(Localization::new("en").supported(&["de"]).source(catalog))
+    /// ```
+    pub fn localization(mut self, localization: i18n::Localization) -> Self {
+        self.responder.localization = Some(Arc::new(localization));
+        self
+    }
+
+    /// Pass every error response through `hook` before it is sent, to log or replace it.
+    ///
+    /// The hook sees handler errors, the framework's own (404, 405, 401, 429, ...)
+    /// and panics, which are answered with a 500 error.
+    ///
+    /// ```ignore
+    /// Server::new().on_error(|error, request| {
+    ///     tracing::warn!(path = %request.path, "{}", error);
+    ///     error
+    /// })
+    /// ```
+    pub fn on_error<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(FerroxError, &ErrorContext) -> FerroxError + Send + Sync + 'static,
+    {
+        self.responder.on_error = Some(Arc::new(hook));
+        self
+    }
+
+    /// Send the response `mapper` builds for an error instead of the envelope, e.g.
+    /// an HTML error page; errors it returns `None` for keep the envelope.
+    ///
+    /// The mapper sees every error `on_error` does, after that hook has run.
+    ///
+    /// ```ignore
+    /// Server::new().error_mapper(|error, request| {
+    ///     request.path.starts_with("/app").then(|| {
+    ///         (error.status(), Html(format!("<h1>{}</h1>", error.message()))).into_response()
+    ///     })
+    /// })
+    /// ```
+    pub fn error_mapper<F>(mut self, mapper: F) -> Self
+    where
+        F: Fn(&FerroxError, &ErrorContext) -> Option<axum::response::Response> + Send + Sync + 'static,
+    {
+        self.responder.error_mapper = Some(Arc::new(mapper));
+        self
+    }
+
+    /// Answer requests no route matches with `handler`, any axum handler, instead
+    /// of the 404 error envelope.
+    ///
+    /// A `FerroxError` it returns is still sent through the envelope, `on_error`
+    /// and `error_mapper`. Static files served at `/` answer missing files
+    /// themselves, and `spa_fallback` answers page navigations first.
+    ///
+    /// ```ignore
+    /// Server::new().not_found(|uri: Uri| async move {
+    ///     (StatusCode::NOT_FOUND, Json(json!({ "error": "no_route", "path": uri.path() })))
+    /// })
+    /// ```
+    pub fn not_found<H, T>(mut self, handler: H) -> Self
+    where
+        H: axum::handler::Handler<T, AppState>,
+        T: 'static,
+    {
+        self.not_found = Some(axum::routing::any(handler));
+        self
+    }
+
+    /// Choose how non-object handler results are wrapped (defaults to `NonObjectResponse::Envelope`).
+    pub fn non_object_response(mut self, policy: NonObjectResponse) -> Self {
+        self.non_object_response = policy;
+        self
+    }
+
+    /// Share `state` with handlers that declare a `State<S>` parameter.
+    ///
+    /// Can be called once per state type; registering the same type again replaces it.
+    pub fn with_state<S: Clone + Send + Sync + 'static>(mut self, state: S) -> Self {
+        self.state.insert(state);
+        self
+    }
+
+    /// Hand the services in `container` to `Dep<T>` handler parameters; see `ferrox::di`.
+    ///
+    /// ```ignore
+    /// Server::new().services(Container::new().singleton(mailer).per_request(UserService::for_request))
+    /// ```
+    pub fn services(mut self, container: di::Container) -> Self {
+        self.state.insert(container);
+        self
+    }
+
+    /// Look feature flags up in `flags`' providers, for `State<Flags>` parameters
+    /// and routes registered with a `flag` option.
+    ///
+    /// ```ignore
+    /// Server::new().flags(Flags::new().provider(EnvFlags::new()).provider(StaticFlags::new().set("new-checkout", true)))
+    /// ```
+    pub fn flags(self, flags: flags::Flags) -> Self {
+        self.with_state(flags)
+    }
+
+    /// Page sizes for handlers' `Pagination` parameters, instead of 20 by default and at most 100.
+    pub fn pagination(self, config: pagination::PaginationConfig) -> Self {
+        self.with_state(config)
+    }
+
+    /// Wrap every route, including the not-found fallback, in a tower layer.
+    ///
+    /// Layers added later wrap the ones added before them.
+    pub fn layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<axum::routing::Route> + Clone + Send + 'static,
+        L::Service: Service<axum::extract::Request> + Clone + Send + 'static,
+        <L::Service as Service<axum::extract::Request>>::Response: IntoResponse + 'static,
+        <L::Service as Service<axum::extract::Request>>::Error: Into<std::convert::Infallible> + 'static,
+        <L::Service as Service<axum::extract::Request>>::Future: Send + 'static,
+    {
+        self.layers.push(middleware::router_layer(layer));
+        self
+    }
+
+    /// Wrap the single route registered for `method` and `path` in a tower layer.
+    ///
+    /// Runs inside any `#[middleware]` layers declared on the handler.
+    pub fn route_layer<L>(mut self, method: &str, path: &str, layer: L) -> Self
+    where
+        L: Layer<axum::routing::Route> + Clone + Send + 'static,
+        L::Service: Service<axum::extract::Request> + Clone + Send + 'static,
+        <L::Service as Service<axum::extract::Request>>::Response: IntoResponse + 'static,
+        <L::Service as Service<axum::extract::Request>>::Error: Into<std::convert::Infallible> + 'static,
+        <L::Service as Service<axum::extract::Request>>::Future: Send + 'static,
+    {
+        self.route_layers
+            .entry((method.to_uppercase(), path.to_string()))
+            .or_default()
+            .push(middleware::method_router_layer(layer));
+        self
+    }
+
+    /// Run `hook` before every `#[http_method]` handler, on the request's path,
+    /// query and body values, which it may change; an error it returns is sent
+    /// instead of calling the handler. See `ferrox::hooks`.
+    ///
+    /// ```ignore
+    /// Server::new().before(|ctx: &mut RequestContext| {
+    ///     ctx.body["source"] = json!("api");
+    ///     Ok(())
+    /// })
+    /// ```
+    pub fn before<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&mut RequestContext) -> Result<(), FerroxError> + Send + Sync + 'static,
+    {
+        self.hooks.before.push(Arc::new(hook));
+        self
+    }
+
+    /// Run `hook` on every successful handler result, before it is wrapped and serialized.
+    pub fn after<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&mut HandlerResponse) + Send + Sync + 'static,
+    {
+        self.hooks.after.push(Arc::new(hook));
+        self
+    }
+
+    /// Run `hook` before the handler registered for `method` and `path`, after
+    /// the server-wide `before` hooks.
+    pub fn before_route<F>(mut self, method: &str, path: &str, hook: F) -> Self
+    where
+        F: Fn(&mut RequestContext) -> Result<(), FerroxError> + Send + Sync + 'static,
+    {
+        let key = (method.to_uppercase(), path.to_string());
+        self.hooks.routes.entry(key).or_default().before.push(Arc::new(hook));
+        self
+    }
+
+    /// Run `hook` on the successful results of the handler registered for
+    /// `method` and `path`, before the server-wide `after` hooks.
+    pub fn after_route<F>(mut self, method: &str, path: &str, hook: F) -> Self
+    where
+        F: Fn(&mut HandlerResponse) + Send + Sync + 'static,
+    {
+        let key = (method.to_uppercase(), path.to_string());
+        self.hooks.routes.entry(key).or_default().after.push(Arc::new(hook));
+        self
+    }
+
+    /// Limit how long a client may take to send the request body (408 when exceeded).
+    pub fn body_read_timeout(mut self, timeout: Duration) -> Self {
+        self.body_read_timeout = Some(timeout);
+        self
+    }
+
+    /// Limit how long a handler may run once the body is read (504 when exceeded).
+    ///
+    /// Applies to every route without its own `timeout` option. Async handlers are
+    /// cancelled at their next `.await`. Synchronous handlers run on tokio's blocking
+    /// pool while a timeout applies; one that overruns keeps running there and only
+    /// its response is dropped.
+    pub fn default_timeout(mut self, timeout: Duration) -> Self {
+        self.default_timeout = Some(timeout);
+        self
+    }
+
+    /// Run `blocking = true` handlers on `threads` dedicated threads instead of
+    /// tokio's blocking pool; requests beyond that wait for a free thread.
+    pub fn blocking_threads(mut self, threads: usize) -> Self {
+        self.blocking_threads = Some(threads);
+        self
+    }
+
+    /// Most responses kept by the cache of `cache = "..."` routes (1000 by
+    /// default); the least recently used are dropped first.
+    pub fn cache_capacity(mut self, entries: usize) -> Self {
+        self.cache_capacity = Some(entries);
+        self
+    }
+
+    /// Keep the responses of `cache = "..."` routes in `store`, such as a Redis
+    /// cache shared by every instance, instead of process memory.
+    pub fn cache_store(mut self, store: impl cache::CacheStore) -> Self {
+        self.cache_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Largest request body accepted, in bytes (2 MiB by default; 413 when exceeded).
+    ///
+    /// Applies to every route without its own `max_body_size` option. A declared
+    /// `Content-Length` over the limit is rejected before any of the body is read,
+    /// and a body without one is cut off once it passes the limit.
+    pub fn max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = Some(bytes);
+        self
+    }
+
+    /// Authenticate routes declared with `auth = "<scheme>"` using `authenticator`.
+    ///
+    /// Routes naming a scheme that was never registered answer 500.
+    pub fn authenticator<A: auth::Authenticator>(mut self, scheme: &'static str, authenticator: A) -> Self {
+        self.authenticators.insert(scheme, Arc::new(authenticator));
+        self
+    }
+
+    /// Check the signature of requests to routes declared with `verify = "<name>"`
+    /// using `verifier`, e.g. `verify::GitHub::new(&secret)`.
+    ///
+    /// Routes naming a verifier that was never registered answer 500.
+    pub fn verifier<V: verify::Verifier>(mut self, name: &'static str, verifier: V) -> Self {
+        self.verifiers.insert(name, Arc::new(verifier));
+        self
+    }
+
+    /// Answer 503 on routes declared with `circuits = ["<name>"]` while `breaker`,
+    /// named so, is open, and report it with the metrics; see `ferrox::resilience`.
+    pub fn circuit_breaker(mut self, breaker: resilience::CircuitBreaker) -> Self {
+        self.circuits.retain(|circuit| circuit.name() != breaker.name());
+        self.circuits.push(breaker);
+        self
+    }
+
+    /// Authenticate routes declared with `auth = "api_key"` using `api_keys`.
+    pub fn api_keys(mut self, api_keys: auth::api_key::ApiKeyAuth) -> Self {
+        self.api_keys = Some(api_keys.clone());
+        self.authenticator("api_key", api_keys)
+    }
+
+    /// Authenticate routes declared with `auth = "jwt"` using `verifier`.
+    #[cfg(feature = "jwt")]
+    pub fn jwt(self, verifier: auth::jwt::JwtVerifier) -> Self {
+        self.authenticator("jwt", verifier)
+    }
+
+    /// Enforce `policy` on routes declared with `permission = "..."`, and make it
+    /// available to handlers as `State<Rbac>`.
+    ///
+    /// Routes requiring a permission answer 500 while no policy is set.
+    pub fn rbac(mut self, policy: rbac::Rbac) -> Self {
+        self.state.insert(policy.clone());
+        self.rbac = Some(policy);
+        self
+    }
+
+    /// Charge routes declared with `cost = N` to the client's budget in `quotas`,
+    /// and make them available to handlers as `State<Quotas>`.
+    ///
+    /// Routes with a cost are not metered while no quotas are set.
+    pub fn quotas(mut self, quotas: quota::Quotas) -> Self {
+        self.state.insert(quotas.clone());
+        self.quotas = Some(quotas);
+        self
+    }
+
+    /// Record who made each mutating request to a route, and what it did, in `log`.
+    ///
+    /// Routes declared with `audit = false` are left out.
+    pub fn audit(mut self, log: audit::AuditLog) -> Self {
+        self.audit = Some(log);
+        self
+    }
+
+    /// Serve the admin endpoints: version, uptime, configuration, routes and log level.
+    pub fn admin(mut self, config: admin::Admin) -> Self {
+        self.admin = Some(config);
+        self
+    }
+
+    /// A handle for adding and removing routes once the server is running, also
+    /// given by `ServerHandle::add_route` and `ServerHandle::remove_route`.
+    ///
+    /// Routes added before the server starts are served from the start.
+    pub fn dynamic_routes(&self) -> dynamic::DynamicRoutes {
+        self.dynamic.clone()
+    }
+
+    /// Serve `method` requests to `path` with `handler`, as a dynamic route added
+    /// before the server starts; see `dynamic_routes`.
+    ///
+    /// Panics if the method is not supported, the path is invalid, or it cannot
+    /// be told apart from another dynamic route.
+    pub fn route(self, method: &str, path: &str, handler: RouteHandler) -> Self {
+        if let Err(err) = self.dynamic.add_route(method, path, handler) {
+            panic!("{} {}: {}", method, path, err);
+        }
+        self
+    }
+
+    /// Install `plugin`, letting it add routes, middleware, state and startup tasks.
+    ///
+    /// A plugin whose name is already installed is skipped.
+    pub fn plugin<P: plugin::Plugin>(mut self, plugin: P) -> Self {
+        let name = plugin.name().to_string();
+        if self.plugins.contains(&name) {
+            tracing::warn!(plugin = name, "Plugin is already installed");
+            return self;
+        }
+        tracing::info!(plugin = name, "Installing plugin");
+        self.plugins.push(name);
+        plugin.install(self)
+    }
+
+    /// The names of the plugins installed, in installation order.
+    pub fn plugins(&self) -> &[String] {
+        &self.plugins
+    }
+
+    /// Limit requests per client across all routes (429 when exceeded).
+    ///
+    /// Routes with their own `rate_limit` option are also counted against it, and
+    /// use this limiter's algorithm, client key and header names for their own limit.
+    pub fn rate_limit(mut self, limiter: ratelimit::RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Most requests served at once across all routes; more are shed with 503
+    /// Service Unavailable and `Retry-After` rather than queued.
+    ///
+    /// Routes can also cap their own share with `concurrency_limit = N`. Health
+    /// probes and the metrics endpoint are not counted.
+    pub fn max_in_flight(mut self, limit: usize) -> Self {
+        self.max_in_flight = Some(limit);
+        self
+    }
+
+    /// Addresses or CIDR ranges of the load balancers and proxies in front of the
+    /// server, whose `Forwarded` and `X-Forwarded-For` headers name the client IP.
+    ///
+    /// # Panics
+    ///
+    /// If an entry is not an address or CIDR range.
+    pub fn trusted_proxies<I, T>(mut self, proxies: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        self.trusted_proxies
+            .extend(proxies.into_iter().map(|proxy| network::parse_net(proxy.as_ref())));
+        self
+    }
+
+    /// Allow cross-origin browser requests as configured by `config`.
+    ///
+    /// CORS wraps every other layer but the access log, so preflight requests are answered before
+    /// any middleware runs and error responses still carry the CORS headers. Routes with
+    /// `cors = ...` use their own policy instead, see `cors::RouteCors`.
+    ///
+    /// Panics if `config` combines a wildcard with credentials, see `cors::CorsConfig`.
+    pub fn cors(mut self, config: cors::CorsConfig) -> Self {
+        if let Err(err) = config.check() {
+            panic!("{}", err);
+        }
+        self.cors = Some(config);
+        self
+    }
+
+    /// Compress responses for clients that accept it, and decompress compressed request bodies.
+    pub fn compression(mut self, config: compression::CompressionConfig) -> Self {
+        self.compression = Some(config);
+        self
+    }
+
+    /// Add strong ETags to JSON responses and answer conditional GET requests
+    /// (`If-None-Match`, `If-Modified-Since`) with 304 Not Modified.
+    pub fn etags(mut self, enabled: bool) -> Self {
+        self.etags = enabled;
+        self
+    }
+
+    /// Format of the log subscriber installed by `start` (text by default).
+    pub fn log_format(mut self, format: logging::LogFormat) -> Self {
+        self.log_format = format;
+        self
+    }
+
+    /// Level filter of the log subscriber installed by `start`, e.g. `debug` or
+    /// `info,ferrox::access=warn`, used when `RUST_LOG` is not set (`info` by default).
+    pub fn log_level(mut self, level: &str) -> Self {
+        self.log_level = Some(level.to_string());
+        self
+    }
+
+    /// Skip the startup report: the routes and addresses logged once listening.
+    pub fn quiet(mut self) -> Self {
+        self.quiet = true;
+        self
+    }
+
+    /// Give the startup report to `callback` instead of logging it, e.g. to print
+    /// a banner or register with service discovery.
+    pub fn startup_report<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&logging::StartupReport) + Send + Sync + 'static,
+    {
+        self.startup_report = Some(Box::new(callback));
+        self
+    }
+
+    /// Narrow the results of routes with a `fields` option to the fields a request
+    /// asks for with `?fields=id,name,address.city`, without the handlers taking a
+    /// `QuerySpec`; fields outside the route's allowlist answer 400. See `ferrox::query`.
+    pub fn sparse_fieldsets(mut self, enabled: bool) -> Self {
+        self.sparse_fieldsets = enabled;
+        self
+    }
+
+    /// Log method, path, matched route, status, latency and request id of every request.
+    ///
+    /// Events use the `ferrox::access` target at info level; responses carry the
+    /// request id in `X-Request-Id`.
+    pub fn access_log(mut self, enabled: bool) -> Self {
+        self.access_log = enabled;
+        self
+    }
+
+    /// Serve Prometheus metrics at `/metrics`: request counts by route and
+    /// status, and a latency histogram per route.
+    pub fn enable_metrics(mut self) -> Self {
+        self.metrics_path.get_or_insert_with(|| metrics::DEFAULT_PATH.to_string());
+        self
+    }
+
+    /// Serve the metrics enabled by `enable_metrics` at `path` instead.
+    pub fn metrics_path(mut self, path: &str) -> Self {
+        self.metrics_path = Some(path.to_string());
+        self
+    }
+
+    /// Record an OpenTelemetry server span for every request and export it over OTLP.
+    #[cfg(feature = "otel")]
+    pub fn opentelemetry(mut self, config: otel::OtelConfig) -> Self {
+        self.otel = Some(config);
+        self
     }
 
+    /// Load `Session` and `Cookies` handler parameters, saving sessions and
+    /// sending cookies with each response.
+    pub fn sessions(mut self, config: session::SessionConfig) -> Self {
+        self.sessions = Some(config);
+        self
+    }
+
+    /// Make `queue` available to handlers as `State<JobQueue>`, and let graceful
+    /// shutdown finish its queued jobs within `shutdown_timeout`.
+    pub fn jobs(mut self, queue: jobs::JobQueue) -> Self {
+        self.state.insert(queue.clone());
+        self.jobs = Some(queue);
+        self
+    }
+
+    /// Run `task` on `schedule` while the server is up, alongside `#[scheduled]` tasks.
+    pub fn schedule<F, Fut>(mut self, name: &str, schedule: scheduler::Schedule, task: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: scheduler::TaskOutput,
+    {
+        self.schedules.push(scheduler::ScheduledTask {
+            name: name.to_string(),
+            schedule,
+            task: scheduler::Task::from_async(move |_| task()),
+        });
+        self
+    }
+
+    /// Deliver the events published on `bus` to the `#[subscribe]` functions,
+    /// making it available to handlers as `State<EventBus>`; running deliveries
+    /// get up to its flush timeout once the server has stopped.
+    pub fn event_bus(mut self, bus: events::EventBus) -> Self {
+        self.state.insert(bus.clone());
+        self.events = Some(bus.clone());
+        let timeout = bus.timeout();
+        self.on_shutdown(move || async move {
+            bus.flush(timeout).await;
+            bus.close();
+            Ok::<(), String>(())
+        })
+    }
+
+    /// Deliver `webhooks`, making them available to handlers as `State<Webhooks>`;
+    /// pending deliveries get up to its flush timeout once the server has stopped.
+    #[cfg(feature = "webhooks")]
+    pub fn webhooks(mut self, webhooks: webhooks::Webhooks) -> Self {
+        self.state.insert(webhooks.clone());
+        let timeout = webhooks.flush_timeout();
+        self.on_shutdown(move || async move {
+            webhooks.flush(timeout).await;
+            Ok::<(), String>(())
+        })
+    }
+
+    /// Create a connection pool for `url`, shared with handlers as `State<P>`.
+    ///
+    /// The pool connects as requests need it, is checked by a `database`
+    /// readiness check and is closed once the server has stopped. Call it
+    /// inside the Tokio runtime, where the pool runs its upkeep.
+    ///
+    /// ```ignore
+    /// Server::new().with_database::<PgPool>(&config.database_url, PoolConfig::new().max_connections(20))
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `url` is not a valid URL for the pool's database.
+    #[cfg(any(feature = "sqlx", feature = "deadpool-postgres"))]
+    pub fn with_database<P: database::Database>(mut self, url: &str, config: database::PoolConfig) -> Self {
+        let pool = P::connect(url, &config).unwrap_or_else(|err| panic!("invalid database URL: {}", err));
+        self.state.insert(pool.clone());
+        let ping = pool.clone();
+        self.readiness_checks
+            .push(("database".to_string(), health::boxed(move || ping.ping())));
+        self.on_shutdown(move || async move {
+            pool.close().await;
+            Ok::<(), String>(())
+        })
+    }
+
+    /// Share a Redis connection with handlers, as `State<Redis>`, and report it in readiness.
+    ///
+    /// ```ignore
+    /// let redis = Redis::connect(&config.redis_url).await?;
+    /// Server::new().cache_store(redis.cache_store()).with_redis(redis)
+    /// ```
+    #[cfg(feature = "redis")]
+    pub fn with_redis(mut self, redis: redis::Redis) -> Self {
+        self.state.insert(redis.clone());
+        self.readiness_checks.push((
+            "redis".to_string(),
+            health::boxed(move || {
+                let redis = redis.clone();
+                async move { redis.ping().await }
+            }),
+        ));
+        self
+    }
+
+    /// Run `hook` before the server starts listening, e.g. to run migrations or warm caches.
+    ///
+    /// Startup hooks run in registration order when the server is started with
+    /// `start`, `run` or their variants. If one fails the rest are skipped and
+    /// the server does not start, returning a `StartupError`.
+    ///
+    /// ```ignore
+    /// Server::new().on_startup(move || async move { db.migrate().await })
+    /// ```
+    pub fn on_startup<F, Fut, E>(mut self, hook: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: std::fmt::Display,
+    {
+        self.startup_hooks.push(lifecycle::hook(hook));
+        self
+    }
+
+    /// Run `hook` once the server has stopped, e.g. to flush buffers.
+    ///
+    /// Shutdown hooks run in registration order after in-flight requests,
+    /// scheduled tasks and background jobs have finished or timed out. A failing
+    /// hook is logged and the rest still run.
+    pub fn on_shutdown<F, Fut, E>(mut self, hook: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: std::fmt::Display,
+    {
+        self.shutdown_hooks.push(lifecycle::hook(hook));
+        self
+    }
+
+    /// Serve `/healthz`, `/readyz` and `/livez` probe endpoints without any checks.
+    pub fn enable_health_checks(mut self) -> Self {
+        self.health_checks.get_or_insert_with(health::HealthChecks::new);
+        self
+    }
+
+    /// Serve the probe endpoints with the checks in `checks`.
+    pub fn health_checks(mut self, checks: health::HealthChecks) -> Self {
+        self.health_checks = Some(checks);
+        self
+    }
+
+    /// Serve the files under `dir` at `prefix`, e.g. `serve_static("/assets", "./public")`.
+    pub fn serve_static(self, prefix: &str, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.static_files(static_files::StaticFiles::new(prefix, dir))
+    }
+
+    /// Serve a directory of files configured by `files`.
+    ///
+    /// Routes take precedence over files. A `/` prefix serves every path no
+    /// route matches, in place of the usual 404.
+    pub fn static_files(mut self, files: static_files::StaticFiles) -> Self {
+        self.static_files.push(files);
+        self
+    }
+
+    /// Serve the single-page app document `index`, e.g. `spa_fallback("./dist/index.html")`,
+    /// for `GET` requests that match no route or file and accept `text/html`.
+    ///
+    /// Client-side routes such as `/settings/profile` then load the app on a
+    /// reload. Other requests, including API calls accepting JSON, still get
+    /// the 404. The document is sent with `Cache-Control: no-cache`.
+    pub fn spa_fallback(mut self, index: impl Into<std::path::PathBuf>) -> Self {
+        self.spa_fallback = Some(index.into());
+        self
+    }
+
+    /// Forward requests matching `path` to an upstream service, e.g.
+    /// `proxy("/legacy/*path", "http://old-service:8080")`.
+    ///
+    /// # Panics
+    ///
+    /// If `upstream` is not an `http://` URL.
+    pub fn proxy(self, path: &str, upstream: &str) -> Self {
+        self.reverse_proxy(proxy::Proxy::new(path, upstream))
+    }
+
+    /// Mount a proxy route configured by `proxy`, which then runs through the server's layers.
+    pub fn reverse_proxy(mut self, proxy: proxy::Proxy) -> Self {
+        self.proxies.push(proxy);
+        self
+    }
+
+    /// Serve a GraphQL schema next to the REST routes, configured by `endpoint`.
+    #[cfg(feature = "graphql")]
+    pub fn graphql(mut self, endpoint: graphql::GraphQL) -> Self {
+        self.graphql.push(endpoint);
+        self
+    }
+
+    /// Serve the tonic service `service` on the server's listeners, next to the REST routes.
+    #[cfg(feature = "grpc")]
+    pub fn grpc<S>(mut self, service: S) -> Self
+    where
+        S: tower::Service<
+                axum::http::Request<tonic::body::BoxBody>,
+                Response = axum::http::Response<tonic::body::BoxBody>,
+                Error = std::convert::Infallible,
+            > + tonic::server::NamedService
+            + Clone
+            + Send
+            + 'static,
+        S::Future: Send + 'static,
+    {
+        self.grpc = Some(self.grpc.take().unwrap_or_default().add_service(service));
+        self
+    }
+
+    /// Render the `Template`s handlers return with `templates`.
+    #[cfg(feature = "templates")]
+    pub fn templates(mut self, templates: templates::Templates) -> Self {
+        self.templates = Some(templates);
+        self
+    }
+
+    /// Choose how requests pick between the versions of routes registered with
+    /// `version = "..."`; versions are served under a path prefix (`/v1/...`) by default.
+    ///
+    /// ```ignore
+    /// Server::new().versioning(Versioning::header("Accept-Version").default_version("v1"));
+    /// ```
+    pub fn versioning(mut self, versioning: versioning::Versioning) -> Self {
+        self.versioning = Some(versioning);
+        self
+    }
+
+    /// Correct trailing slashes, repeated slashes and, optionally, letter case in
+    /// request paths that match no route as sent; paths are matched exactly by default.
+    ///
+    /// ```ignore
+    /// Server::new().path_normalization(PathNormalization::new().trailing_slash(TrailingSlash::Rewrite));
+    /// ```
+    pub fn path_normalization(mut self, config: normalize::PathNormalization) -> Self {
+        self.path_normalization = Some(config);
+        self
+    }
+
+    /// Accept batches of requests, run through the server one by one, at the
+    /// endpoint configured by `batch`.
+    ///
+    /// ```ignore
+    /// Server::new().batch(Batch::new().concurrency(4));
+    /// ```
+    pub fn batch(mut self, batch: batch::Batch) -> Self {
+        self.batch = Some(batch);
+        self
+    }
+
+    /// Answer every `#[http_method]` route with `mock`'s canned or example
+    /// responses instead of running its handler, e.g. for a frontend to develop against.
+    ///
+    /// ```ignore
+    /// Server::new().mock(MockServer::new().respond("GET", "/users/:id", StatusCode::OK, json!({ "id": 7 })))
+    /// ```
+    pub fn mock(mut self, mock: mock::MockServer) -> Self {
+        self.mock = Some(mock);
+        self
+    }
+
+    /// Record every request and its response to a file, for `recorder::Replay`
+    /// to send again later; credentials are redacted.
+    ///
+    /// ```ignore
+    /// Server::new().record(Recorder::new("recordings/traffic.ndjson").redact_field("ssn"))
+    /// ```
+    pub fn record(mut self, recorder: recorder::Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Log the request and response bodies of matching routes while debugging,
+    /// with secrets redacted; off until `log` is enabled, e.g. from `PUT /admin/body-log`.
+    ///
+    /// ```ignore
+    /// Server::new().body_logging(BodyLog::new().redact("$.card.number")).admin(Admin::new())
+    /// ```
+    pub fn body_logging(mut self, log: body_log::BodyLog) -> Self {
+        self.body_log = Some(log);
+        self
+    }
+
+    /// Inject `chaos`'s faults (latency, errors, dropped connections) into
+    /// matching requests, once it is `enabled`.
+    ///
+    /// ```ignore
+    /// Server::new().chaos(Chaos::new().enabled(true).inject(Fault::error(StatusCode::BAD_GATEWAY).probability(0.2)))
+    /// ```
+    pub fn chaos(mut self, chaos: chaos::Chaos) -> Self {
+        self.chaos = Some(chaos);
+        self
+    }
+
+    /// Tell apart the tenants of a multi-tenant service, from each request's
+    /// subdomain, a header or its path prefix, with their own settings, state
+    /// and rate limits.
+    ///
+    /// ```ignore
+    /// Server::new().tenancy(Tenancy::new().header(HeaderName::from_static("x-tenant-id")).allow_unknown(true))
+    /// ```
+    pub fn tenancy(mut self, tenancy: tenancy::Tenancy) -> Self {
+        self.tenancy = Some(tenancy);
+        self
+    }
+
+    /// Serve the registered routes as JSON at `path`, usually `/_routes`, for debugging.
+    ///
+    /// The listing goes through the server's layers like any route, and reveals
+    /// every handler and its source location, so keep it out of production.
+    pub fn debug_routes(mut self, path: &str) -> Self {
+        self.debug_routes = Some(path.to_string());
+        self
+    }
+
+    /// Serve an OpenAPI 3.1 document for all registered routes (and optionally Swagger UI).
+    pub fn openapi(mut self, config: openapi::OpenApiConfig) -> Self {
+        self.openapi = Some(config);
+        self
+    }
+
+    /// Stop gracefully on SIGINT (Ctrl-C) or SIGTERM when running via `start`.
+    ///
+    /// In-flight requests get `shutdown_timeout` to finish before connections are dropped.
+    pub fn handle_signals(mut self, enabled: bool) -> Self {
+        self.handle_signals = enabled;
+        self
+    }
+
+    /// Restart without downtime on SIGUSR2 when `handle_signals` is set: a fresh
+    /// start of the binary takes over the listening sockets, then this process
+    /// drains and stops. See `ferrox::restart`.
+    #[cfg(unix)]
+    pub fn restart_on_signal(mut self, enabled: bool) -> Self {
+        self.restart_on_signal = enabled;
+        self
+    }
+
+    /// How long signal-triggered shutdown waits for in-flight requests (defaults to 30s).
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = Some(timeout);
+        self
+    }
+
+    /// Also serve on `listener`: a TCP address, a Unix socket or a pre-bound socket.
+    ///
+    /// Every listener serves the same routes. `run` serves the bound listeners,
+    /// and `start` serves them alongside its address.
+    pub fn bind(mut self, listener: listener::Listener) -> Self {
+        self.listeners.push(listener);
+        self
+    }
+
+    /// HTTP/2 settings: whether HTTPS offers it through ALPN, whether plain
+    /// listeners accept h2c, and stream and frame limits. Both are on by default.
+    pub fn http2(mut self, config: http2::Http2Config) -> Self {
+        self.http2 = config;
+        self
+    }
+
+    /// Connection settings: TCP options, the most connections open at once, header
+    /// read and write timeouts, and slow request logging. See `ferrox::connection`.
+    ///
+    /// ```ignore
+    /// Server::new().connections(ConnectionConfig::new().max_connections(10_000).header_read_timeout(Duration::from_secs(5)))
+    /// ```
+    pub fn connections(mut self, config: connection::ConnectionConfig) -> Self {
+        self.connections = config;
+        self
+    }
+
+    /// The runtime `run_blocking` builds: worker threads, thread names and the
+    /// blocking pool's size. See `ferrox::runtime`.
+    pub fn runtime(mut self, config: runtime::RuntimeConfig) -> Self {
+        self.runtime = config;
+        self
+    }
+
+    /// Serve on the listeners given to `bind`, or else the configured address
+    /// (`127.0.0.1:3000` by default), until the process exits or until a signal
+    /// when `handle_signals` is set.
+    ///
+    /// When the configuration names certificate files, serves HTTPS on the
+    /// configured address instead, like `start_tls`.
+    pub async fn run(mut self) -> Result<(), Box<dyn std::error::Error>> {
+        match self.tls_files.take() {
+            None => {
+                let (handle_signals, restart_on_signal) = (self.handle_signals, self.restart_on_signal);
+                let shutdown_timeout = self.shutdown_timeout.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT);
+                let handle = self.run_in_background().await?;
+                handle.run(handle_signals, restart_on_signal, shutdown_timeout).await?;
+                Ok(())
+            }
+            #[cfg(feature = "tls")]
+            Some(files) => {
+                let addr = self.bind_addr.take().unwrap_or_else(|| DEFAULT_ADDR.to_string());
+                self.start_tls(&addr, tls::TlsConfig::from_pem_files(files.cert, files.key)).await
+            }
+            #[cfg(not(feature = "tls"))]
+            Some(_) => Err("TLS is configured but ferrox was built without the `tls` feature".into()),
+        }
+    }
+
+    /// Build the runtime set with `runtime` and `run` on it, blocking the calling
+    /// thread, for `main` functions without `#[tokio::main]`.
+    ///
+    /// Fails instead of blocking when called from within a tokio runtime.
+    pub fn run_blocking(self) -> Result<(), Box<dyn std::error::Error>> {
+        if tokio::runtime::Handle::try_current().is_ok() {
+            return Err("`run_blocking` was called from within a tokio runtime; await `run` instead".into());
+        }
+        let runtime = self.runtime.build()?;
+        let result = runtime.block_on(self.run());
+        // Tasks still running, such as a handler past the shutdown timeout, are dropped
+        runtime.shutdown_timeout(Duration::from_secs(1));
+        result
+    }
+
+    /// Bind the listeners `run` would serve on, over plain HTTP, and serve in a
+    /// spawned task, returning a handle to stop it.
+    pub async fn run_in_background(mut self) -> Result<ServerHandle, Box<dyn std::error::Error>> {
+        if self.listeners.is_empty() {
+            let addr = self.bind_addr.take().unwrap_or_else(|| DEFAULT_ADDR.to_string());
+            self.listeners.push(listener::Listener::tcp(addr));
+        }
+        self.serve_in_background().await
+    }
+
+    /// Serve on `addr`, and any listeners given to `bind`, until the process exits,
+    /// or until a signal when `handle_signals` is set.
     pub async fn start(self, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
-        println!("🚀 REST API Server with Generic Route Handler Interface");
-        println!("========================================================");
-        println!("🔍 Routes automatically discovered from #[http_method] annotations");
-        println!("📦 Generic interface: All functions wrapped in boxed closures");
-        println!("📍 Routes stored as (method, path) -> closure in HashMap at startup!");
-        println!();
-
-        // Populate global registry from inventory-collected routes
-        let mut global_registry = GLOBAL_ROUTE_REGISTRY.lock().unwrap();
-        for registration in inventory::iter::<RouteRegistration> {
-            global_registry.insert(
-                (registration.method.to_string(), registration.path.to_string()),
-                (registration.handler_fn)(),
-            );
+        let (handle_signals, restart_on_signal) = (self.handle_signals, self.restart_on_signal);
+        let shutdown_timeout = self.shutdown_timeout.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT);
+        let handle = self.start_in_background(addr).await?;
+        handle.run(handle_signals, restart_on_signal, shutdown_timeout).await?;
+        Ok(())
+    }
 
-            let emoji = match registration.method {
-                "GET" => "📋",
-                "POST" => "➕",
-                "PUT" => "✏️",
-                "PATCH" => "📧",
-                "DELETE" => "🗑️",
-                "HEAD" => "❓",
-                "OPTIONS" => "ℹ️",
-                _ => "🔍",
-            };
-            println!("{} {} {} - Auto-registered from annotation", emoji, registration.method, registration.path);
+    /// Bind `addr`, and any listeners given to `bind`, and serve in a spawned task,
+    /// returning a handle to stop it.
+    ///
+    /// Every listener is bound before this returns, so with port 0
+    /// (`start_in_background("127.0.0.1:0")`) `ServerHandle::local_addr` gives the
+    /// port the system picked, ready for requests.
+    pub async fn start_in_background(mut self, addr: &str) -> Result<ServerHandle, Box<dyn std::error::Error>> {
+        self.listeners.push(listener::Listener::tcp(addr));
+        self.serve_in_background().await
+    }
+
+    async fn serve_in_background(mut self) -> Result<ServerHandle, Box<dyn std::error::Error>> {
+        logging::init_with_level(self.log_format, self.log_level.as_deref().unwrap_or("info"));
+        let app = self.build_router()?;
+        lifecycle::startup(std::mem::take(&mut self.startup_hooks)).await?;
+        let config_watch = self.config_watcher.as_ref().map(config::ConfigWatcher::spawn);
+
+        let mut listeners = Vec::new();
+        for listener in std::mem::take(&mut self.listeners) {
+            listeners.push(listener.bind().await?);
         }
 
-        // Build router - routes are looked up from HashMap at runtime
-        let mut router = Router::new();
+        let admin = self.start_admin().await?;
+        let addresses = listeners.iter().map(listener::Bound::address).collect();
+        self.report_startup(addresses, &admin);
+
+        let scheduler = self.start_scheduler()?;
+        let handle = ServerHandle::spawn(listeners, app, &self.http2, &self.connections);
+        // The listeners are taken over from a parent process, if any, which can now drain
+        #[cfg(unix)]
+        restart::notify_ready();
+        Ok(handle
+            .with_admin(admin)
+            .with_scheduler(scheduler)
+            .with_jobs(self.jobs.take())
+            .with_dynamic_routes(self.dynamic.clone())
+            .with_config_watch(config_watch)
+            .with_shutdown_hooks(std::mem::take(&mut self.shutdown_hooks)))
+    }
+
+    /// Serve HTTPS on `addr` with rustls; otherwise behaves like `start`.
+    #[cfg(feature = "tls")]
+    pub async fn start_tls(self, addr: &str, tls: tls::TlsConfig) -> Result<(), Box<dyn std::error::Error>> {
+        let (handle_signals, restart_on_signal) = (self.handle_signals, self.restart_on_signal);
+        let shutdown_timeout = self.shutdown_timeout.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT);
+        let handle = self.start_tls_in_background(addr, tls).await?;
+        handle.run(handle_signals, restart_on_signal, shutdown_timeout).await?;
+        Ok(())
+    }
+
+    /// Bind `addr` and serve HTTPS in a spawned task, returning a handle to stop it.
+    #[cfg(feature = "tls")]
+    pub async fn start_tls_in_background(
+        mut self,
+        addr: &str,
+        tls: tls::TlsConfig,
+    ) -> Result<ServerHandle, Box<dyn std::error::Error>> {
+        logging::init_with_level(self.log_format, self.log_level.as_deref().unwrap_or("info"));
+        let app = self.build_router()?;
+        lifecycle::startup(std::mem::take(&mut self.startup_hooks)).await?;
+        let config_watch = self.config_watcher.as_ref().map(config::ConfigWatcher::spawn);
+
+        let socket_addr: std::net::SocketAddr = addr.parse()?;
+        #[cfg(unix)]
+        let inherited = restart::take_tcp(socket_addr);
+        #[cfg(not(unix))]
+        let inherited = None;
+        let listener = match inherited {
+            Some(listener) => listener,
+            None => std::net::TcpListener::bind(socket_addr)?,
+        };
+        let admin = self.start_admin().await?;
+        self.report_startup(vec![format!("https://{}", listener.local_addr()?)], &admin);
 
-        // Handlers that lookup from the global registry
+        let scheduler = self.start_scheduler()?;
+        let handle = tls::spawn(listener, app, tls, &self.http2, &self.connections).await?;
+        #[cfg(unix)]
+        restart::notify_ready();
+        Ok(handle
+            .with_admin(admin)
+            .with_scheduler(scheduler)
+            .with_jobs(self.jobs.take())
+            .with_dynamic_routes(self.dynamic.clone())
+            .with_config_watch(config_watch)
+            .with_shutdown_hooks(std::mem::take(&mut self.shutdown_hooks)))
+    }
 
-        // Dynamically register routes based on inventory-collected registrations
-        for registration in inventory::iter::<RouteRegistration> {
+    /// Build the axum router without binding a listener, e.g. to nest it in another
+    /// axum application or to drive it directly with `tower::ServiceExt::oneshot`.
+    ///
+    /// Panics with the locations of both definitions if two routes conflict, where
+    /// `start` returns the `RouteConflict` instead.
+    pub fn into_router(mut self) -> Router {
+        self.build_router().unwrap_or_else(|conflict| panic!("{}", conflict))
+    }
+
+    // Serve the admin endpoints on their own listener, if they have one
+    async fn start_admin(&mut self) -> std::io::Result<Option<ServerHandle>> {
+        let Some((addr, app)) = self.admin_app.take() else {
+            return Ok(None);
+        };
+        let bound = listener::Listener::tcp(&addr).bind().await?;
+        Ok(Some(ServerHandle::spawn(vec![bound], app, &self.http2, &self.connections)))
+    }
+
+    fn report_startup(&mut self, addresses: Vec<String>, admin: &Option<ServerHandle>) {
+        if self.quiet {
+            return;
+        }
+        let report = logging::StartupReport {
+            addresses,
+            admin: admin.as_ref().and_then(ServerHandle::local_addr).map(|addr| format!("http://{}", addr)),
+            routes: routes::listed(self.registry.as_deref()),
+            dynamic_routes: self.dynamic.routes(),
+            plugins: self.plugins.clone(),
+        };
+        match &self.startup_report {
+            Some(callback) => callback(&report),
+            None => logging::log_startup(&report),
+        }
+    }
+
+    // The settings reported by the admin `/config` endpoint
+    fn settings(&self) -> serde_json::Value {
+        let duration = |timeout: Option<Duration>| timeout.map(|timeout| format!("{:?}", timeout));
+        serde_json::json!({
+            "log": {
+                "level": self.log_level.as_deref().unwrap_or("info"),
+                "format": self.log_format,
+                "access_log": self.access_log,
+            },
+            "timeouts": {
+                "body_read": duration(self.body_read_timeout),
+                "handler": duration(self.default_timeout),
+                "shutdown": duration(Some(self.shutdown_timeout.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT))),
+            },
+            "limits": {
+                "max_body_size": self.max_body_size.unwrap_or(DEFAULT_MAX_BODY_SIZE),
+                "max_in_flight": self.max_in_flight,
+                "rate_limit": self.rate_limiter.is_some(),
+            },
+            "cors": self.cors.is_some(),
+            "compression": self.compression.is_some(),
+            "etags": self.etags,
+            "sparse_fieldsets": self.sparse_fieldsets,
+            "sessions": self.sessions.is_some(),
+            "metrics_path": self.metrics_path,
+            "health_checks": self.health_checks.is_some(),
+            "plugins": self.plugins,
+        })
+    }
+
+    // Start the `#[scheduled]` tasks and those added with `schedule`
+    fn start_scheduler(&mut self) -> Result<Option<scheduler::Scheduler>, String> {
+        let mut tasks = scheduler::registered()?;
+        tasks.append(&mut self.schedules);
+        Ok(scheduler::Scheduler::start(tasks, self.state.clone()))
+    }
+
+    fn build_router(&mut self) -> Result<Router, RouteConflict> {
+        let registry = self.registry.clone();
+        routes::check(routes::registered(registry.as_deref()))?;
+        if let Some(limiter) = &mut self.rate_limiter {
+            limiter.verify_keys(self.api_keys.clone());
+        }
+        if let Some(watcher) = &self.config_watcher {
+            watcher.attach(config::Targets {
+                rate_limiter: self.rate_limiter.clone(),
+                cors_origins: self.cors.as_mut().and_then(cors::CorsConfig::share_origins),
+                flags: self.config_flags.clone(),
+            });
+        }
+        let admin = self.admin.take().map(|config| {
+            let runtime = admin::Runtime::new(
+                &config,
+                self.settings(),
+                routes::listed(registry.as_deref()),
+                self.dynamic.clone(),
+                self.body_log.clone(),
+            );
+            (config, runtime)
+        });
+
+        // Build router - each route owns its handler, so the finished router is
+        // immutable and requests are dispatched without any shared lookup or lock
+        let mut router = Router::<AppState>::new();
+        let mut paths: BTreeMap<(String, constraints::Constraints), PathRoutes> = BTreeMap::new();
+        let versioning = Arc::new(self.versioning.take().unwrap_or_default());
+
+        #[cfg(feature = "templates")]
+        if let Some(templates) = self.templates.take() {
+            templates::install(templates);
+        }
+        let blocking_pool = self.blocking_threads.map(blocking::BlockingPool::new);
+        let response_cache = routes::registered(registry.as_deref())
+            .any(|registration| registration.options.cache.is_some())
+            .then(|| match self.cache_store.take() {
+                Some(store) => cache::ResponseCache::store(store),
+                None => cache::ResponseCache::new(self.cache_capacity.unwrap_or(cache::DEFAULT_CAPACITY)),
+            });
+        // Routes with a CORS policy of their own, applied in place of `Server::cors`
+        let mut route_cors = Vec::new();
+        // Versioned routes, for 404s to point at when `suggest_versions` is on
+        let mut versioned_paths = Vec::new();
+        // Dynamically register the server's registry's routes from the inventory-collected registrations
+        for registration in routes::registered(registry.as_deref()) {
             let method = registration.method;
-            let path = registration.path;
-            let handler = (registration.handler_fn)(); // Get the Arc<RouteHandler>
-
-            // Create a generic handler that extracts path, query, and body parameters
-            let generic_handler = move |
-                Path(path_params): Path<HashMap<String, String>>,
-                Query(query_params): Query<HashMap<String, String>>,
-                body: Option<Json<serde_json::Value>>
-            | async move {
-                // Convert path parameters to JSON
-                let mut path_json = serde_json::Map::new();
-                for (key, value) in path_params {
-                    path_json.insert(key, serde_json::Value::String(value));
+            let version = registration.options.version;
+            let path = versioning.served_path(version, registration.path);
+            if let Some(policy) = registration.options.cors {
+                route_cors.push((path.clone(), method, policy));
+            }
+            if let Some(version) = version.filter(|_| versioning.suggests()) {
+                versioned_paths.push((registration.path, version));
+            }
+            let settings = dispatch::Settings {
+                non_object_response: self.non_object_response,
+                body_read_timeout: self.body_read_timeout,
+                handler_timeout: registration.options.timeout.or(self.default_timeout),
+                max_body_size: registration
+                    .options
+                    .max_body_size
+                    .or(self.max_body_size)
+                    .unwrap_or(DEFAULT_MAX_BODY_SIZE),
+                raw_body: registration.options.raw_body,
+                query: registration.options.query,
+                sparse_fields: self.sparse_fieldsets,
+                status: registration.options.status.and_then(|status| StatusCode::from_u16(status).ok()),
+                sync_execution: match (registration.options.blocking, &blocking_pool) {
+                    (false, _) => dispatch::SyncExecution::Default,
+                    (true, None) => dispatch::SyncExecution::TokioBlocking,
+                    (true, Some(pool)) => dispatch::SyncExecution::Dedicated(pool.clone()),
+                },
+                hooks: self.hooks.route(method, &path),
+            };
+            let max_body_size = settings.max_body_size;
+            let mut route = match &registration.handler {
+                RouteKind::Http(make_handler) => {
+                    let handler = match &self.mock {
+                        Some(mock) => mock.handler(registration),
+                        None => make_handler(),
+                    };
+                    match dispatch::method_router(method, handler, settings) {
+                        Some(route) => route,
+                        None => continue, /* Log unsupported method */
+                    }
                 }
-                let path_identifiers = serde_json::Value::Object(path_json);
+                RouteKind::WebSocket(make_handler) => ws::method_router(make_handler()),
+                RouteKind::Sse(make_handler) => sse::method_router(make_handler()),
+            };
 
-                // Convert query parameters to JSON
-                let mut query_json = serde_json::Map::new();
-                for (key, value) in query_params {
-                    query_json.insert(key, serde_json::Value::String(value));
-                }
-                let query_arguments = serde_json::Value::Object(query_json);
+            if !registration.options.constraints.is_empty() {
+                route = constraints::check_route(route, registration.options.constraints);
+            }
+            // Inside the cache, so hits do not take a slot
+            if let Some(limit) = registration.options.concurrency_limit {
+                route = concurrency::limit_route(route, limit);
+            }
+            // Inside the cache, so only misses are coalesced, and outside the limit,
+            // so waiting requests do not take a slot
+            if registration.options.coalesce {
+                route = coalesce::coalesce_route(route);
+            }
+            // Innermost, so hits still pass rate limits and middleware
+            if let (Some(ttl), Some(cache)) = (registration.options.cache, &response_cache) {
+                route = cache::cache_route(route, cache.clone(), ttl);
+            }
+
+            // Inside authentication, permissions and guards, so only requests the handler
+            // serves are charged, and outside the cache, so hits are charged too
+            if let Some(cost) = registration.options.cost {
+                route = quota::meter_route(route, cost, self.quotas.clone(), self.rbac.clone().unwrap_or_default());
+            }
 
-                // Body parameters
-                let body_value = body.map(|Json(v)| v).unwrap_or(serde_json::Value::Null);
+            // After authentication, whose identity guards may check, and before cache hits
+            if !registration.options.guards.is_empty() {
+                let guards = registration.options.guards.iter().map(|make| make()).collect();
+                route = guard::guard_route(route, guards, self.state.clone());
+            }
 
-                // Call the handler with three separate arguments and convert JSON to HTTP response
-                let json_result = handler(path_identifiers, query_arguments, body_value);
-                axum::Json(json_result).into_response()
+            // Permissions are checked once authenticated, before any guard runs
+            if let Some(permission) = registration.options.permission {
+                route = rbac::require(route, permission, self.rbac.clone());
+            }
+
+            // Inside authentication and the audit trail, and before permissions and guards
+            // look at a request that cannot be served without a precondition
+            if registration.options.if_match {
+                route = etag::require_precondition(route);
+            }
+
+            // Inside authentication, whose principal it records, and outside permissions
+            // and guards, so their refusals are recorded too
+            if let Some(log) = self.audit.as_ref().filter(|log| registration.options.audit && log.covers(method)) {
+                let rbac = self.rbac.clone().unwrap_or_default();
+                route = audit::audit_route(route, log.clone(), rbac, path.clone(), max_body_size);
+            }
+
+            // Authentication runs inside middleware, so middleware sees its rejections
+            if let Some(scheme) = registration.options.auth {
+                route = auth::require(route, scheme, self.authenticators.get(scheme).cloned());
+            }
+
+            // Signatures are checked first, on the body exactly as it was sent
+            if let Some(name) = registration.options.verify {
+                let verifier = self.verifiers.get(name).cloned();
+                route = verify::verify_route(route, name, verifier, max_body_size);
+            }
+
+            // Before signature checks and authentication, whose work an open circuit
+            // would waste, and inside rate limits, so retries against it still count
+            if !registration.options.circuits.is_empty() {
+                let circuits = registration
+                    .options
+                    .circuits
+                    .iter()
+                    .filter_map(|name| {
+                        let circuit = self.circuits.iter().find(|circuit| circuit.name() == *name);
+                        if circuit.is_none() {
+                            tracing::error!("No circuit breaker registered as `{}`; {} {} ignores it", name, method, path);
+                        }
+                        circuit.cloned()
+                    })
+                    .collect();
+                route = resilience::guard_route(route, circuits);
+            }
+
+            // Rate limiting runs before authentication, so rejected credentials still count
+            if let Some(rate) = registration.options.rate_limit {
+                let limiter = match &self.rate_limiter {
+                    Some(global) => global.for_route(rate, format!("{} {}", method, path)),
+                    None => ratelimit::RateLimiter::new(rate),
+                };
+                route = ratelimit::limit_route(route, limiter);
+            }
+
+            // Per-route layers next, then #[middleware] from innermost to outermost
+            let key = (method.to_string(), path.clone());
+            for layer in self.route_layers.remove(&key).unwrap_or_default() {
+                route = layer(route);
+            }
+            for wrap in registration.middleware.iter().rev() {
+                route = wrap(route);
+            }
+            // Every response of a deprecated route says so, rejections included
+            route = deprecation::mark_route(route, &registration.options);
+            // Outermost, so a route whose flag is off answers like a missing one
+            if let Some(flag) = registration.options.flag {
+                route = flags::gate_route(route, flag, self.state.get::<flags::Flags>().unwrap_or_default());
+            }
+            // Versions at the same path are only told apart under the header and media type strategies
+            let version = if versioning.by_path() { None } else { version };
+            // GET routes answer HEAD too, unless they opt out
+            let served: &[&'static str] = match method {
+                "GET" if registration.options.head => &["GET", "HEAD"],
+                _ => std::slice::from_ref(&registration.method),
             };
+            let versions = paths.entry((path, registration.options.constraints)).or_default();
+            match versions.remove(&version) {
+                Some((existing, mut methods)) => {
+                    methods.extend_from_slice(served);
+                    versions.insert(version, (existing.merge(route), methods));
+                }
+                None => {
+                    versions.insert(version, (route, served.to_vec()));
+                }
+            }
+        }
 
-            // Register the route based on HTTP method
-            match method {
-                "GET" => router = router.route(path, get(generic_handler)),
-                "POST" => router = router.route(path, post(generic_handler)),
-                "PUT" => router = router.route(path, put(generic_handler)),
-                "PATCH" => router = router.route(path, patch(generic_handler)),
-                "DELETE" => router = router.route(path, delete(generic_handler)),
-                "HEAD" => router = router.route(path, axum::routing::head(generic_handler)),
-                "OPTIONS" => router = router.route(path, axum::routing::options(generic_handler)),
-                _ => { /* Log unsupported method */ }
+        let route_paths: Vec<String> = paths.keys().map(|(path, _)| path.clone()).collect();
+        // Paths differing only by constraints share a shape, and one entry in the router
+        let mut shapes: BTreeMap<String, Vec<(String, constraints::Constraints, PathRoutes)>> = BTreeMap::new();
+        for ((path, constraints), versions) in paths {
+            shapes.entry(constraints::shape(&path)).or_default().push((path, constraints, versions));
+        }
+        for (_, mut candidates) in shapes {
+            if candidates.len() == 1 {
+                let (path, _, versions) = candidates.remove(0);
+                router = mount_path(router, &path, versions, &versioning, &self.state);
+                continue;
             }
+            // Each routed on its own, and picked by the values the request has
+            let path = candidates[0].0.clone();
+            let candidates = candidates
+                .into_iter()
+                .map(|(path, constraints, versions)| {
+                    let methods = versions.values().flat_map(|(_, methods)| methods.clone()).collect();
+                    let route = mount_path(Router::new(), &path, versions, &versioning, &self.state)
+                        .with_state(self.state.clone());
+                    (path, constraints, methods, route)
+                })
+                .collect();
+            router = router.route_service(&path, constraints::ConstrainedRoute::new(candidates));
         }
 
-        let app = router.fallback(not_found_handler).layer(ServiceBuilder::new());
+        if let Some(config) = self.openapi.take() {
+            router = openapi::mount(router, config, registry.as_deref());
+        }
+        if let Some(path) = self.debug_routes.take() {
+            router = routes::mount(router, &path, registry.as_deref());
+        }
 
-        println!();
+        for proxy in self.proxies.drain(..) {
+            router = proxy::mount(router, proxy);
+        }
+        #[cfg(feature = "graphql")]
+        for endpoint in std::mem::take(&mut self.graphql) {
+            let authenticator = endpoint.scheme().and_then(|scheme| self.authenticators.get(scheme).cloned());
+            let max_body_size = self.max_body_size.unwrap_or(DEFAULT_MAX_BODY_SIZE);
+            router = graphql::mount(router, endpoint, authenticator, max_body_size);
+        }
 
-        let socket_addr: std::net::SocketAddr = addr.parse()?;
-        println!("🌐 Server running at http://{}", socket_addr);
-        println!("🔎 Each incoming request extracts (method, path) and looks up function from HashMap!");
-        println!();
+        let spa = self.spa_fallback.take().map(static_files::SpaFallback::new);
+        let not_found = self.not_found.take().unwrap_or_else(|| {
+            if versioned_paths.is_empty() {
+                return axum::routing::any(not_found_handler);
+            }
+            let hints = Arc::new(versioning::VersionHints::new(versioned_paths));
+            axum::routing::any(move |uri| version_hint_handler(uri, hints))
+        });
+        router = match &spa {
+            Some(spa) => router.fallback(static_files::spa_route(not_found, spa.clone())),
+            None => router.fallback(not_found),
+        };
+        for files in self.static_files.drain(..) {
+            router = static_files::mount(router, files, spa.clone());
+        }
+        let settings = dispatch::Settings {
+            non_object_response: self.non_object_response,
+            body_read_timeout: self.body_read_timeout,
+            handler_timeout: self.default_timeout,
+            max_body_size: self.max_body_size.unwrap_or(DEFAULT_MAX_BODY_SIZE),
+            raw_body: false,
+            query: query::Allowlist::NONE,
+            sparse_fields: false,
+            status: None,
+            sync_execution: dispatch::SyncExecution::Default,
+            hooks: self.hooks.global(),
+        };
+        self.dynamic.install(settings, self.state.clone(), route_paths.clone());
+        match &self.events {
+            Some(bus) => bus.bind(self.state.clone()),
+            None if inventory::iter::<events::SubscriberRegistration>.into_iter().next().is_some() => {
+                tracing::warn!("#[subscribe] functions receive no events without Server::event_bus")
+            }
+            None => {}
+        }
+        router = dynamic::layer(router, self.dynamic.clone());
+        if let Some(config) = self.sessions.take() {
+            router = session::layer(router, config);
+        }
+        // Tenants' limits share the store of the server's limiter
+        let tenancy = self.tenancy.take().map(|tenancy| (tenancy, self.rate_limiter.clone()));
+        if let Some(limiter) = self.rate_limiter.take() {
+            router = ratelimit::limit_router(router, limiter);
+        }
+        for layer in self.layers.drain(..) {
+            router = layer(router);
+        }
+        // Outside the app's own layers, which can then fill the cache for guards and handlers
+        router = context::cache_layer(router);
+        // Outside every other layer of the app, so shed requests cost next to nothing
+        if let Some(limit) = self.max_in_flight {
+            router = concurrency::limit_router(router, limit);
+        }
+        // Outside every layer that reads the client IP
+        if !self.trusted_proxies.is_empty() {
+            router = network::trust_proxies(router, std::mem::take(&mut self.trusted_proxies));
+        }
+        // Panics anywhere inside become 500s, sent through the envelope and `on_error`
+        router = router.layer(tower_http::catch_panic::CatchPanicLayer::custom(|panic: Box<dyn std::any::Any + Send>| {
+            let message = match panic.downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => panic.downcast_ref::<String>().cloned().unwrap_or_default(),
+            };
+            tracing::error!("Handler panicked: {}", message);
+            panic_response()
+        }));
+        let responder = std::mem::take(&mut self.responder);
+        if !responder.is_default() {
+            router = envelope::layer(router, responder);
+        }
+        // Over the final body, before compression
+        if self.etags {
+            router = etag::layer(router);
+        }
+        router = cors::layer(router, self.cors.take(), route_cors);
+        // Bodies as the handlers sent them, before compression
+        if let Some(config) = self.recorder.take() {
+            router = recorder::layer(router, config);
+        }
+        if let Some(log) = self.body_log.take() {
+            router = body_log::layer(router, log);
+        }
+        if let Some(config) = self.compression.take() {
+            router = compression::layer(router, config);
+        }
+        // Inside metrics and the access log, which then show the injected faults
+        if let Some(chaos) = self.chaos.take().filter(chaos::Chaos::is_enabled) {
+            router = chaos::layer(router, chaos);
+        }
+        // Past the REST layers, which do not apply to gRPC calls
+        #[cfg(feature = "grpc")]
+        if let Some(routes) = self.grpc.take() {
+            router = grpc::layer(router, routes);
+        }
+        // Outermost, so preflights and rejections are counted and logged too
+        let metrics = self.metrics_path.take().map(|path| (path, metrics::Metrics::new(self.circuits.clone())));
+        if let Some((_, metrics)) = &metrics {
+            router = metrics::record(router, metrics.clone());
+        }
+        #[cfg(feature = "otel")]
+        if let Some(config) = self.otel.take() {
+            router = otel::layer(router, config);
+        }
+        if let Some(threshold) = self.connections.slow_requests() {
+            router = connection::log_slow_requests(router, threshold);
+        }
+        if self.access_log {
+            router = logging::access_log(router);
+        }
+        if let Some((_, runtime)) = &admin {
+            router = admin::count_requests(router, runtime.clone());
+        }
+        if let Some((path, metrics)) = metrics {
+            router = metrics::mount(router, &path, metrics);
+        }
+        if let Some(checks) = self.health_checks.take() {
+            let checks = checks.with_readiness(std::mem::take(&mut self.readiness_checks));
+            router = health::mount(router, checks);
+        }
+        if let Some((config, runtime)) = admin {
+            let authenticator = config.scheme().and_then(|scheme| self.authenticators.get(scheme).cloned());
+            match config.addr.clone() {
+                Some(addr) => {
+                    let app = admin::router(&config, runtime, authenticator, self.state.clone());
+                    self.admin_app = Some((addr, app));
+                }
+                None => router = admin::mount(router, &config, runtime, authenticator),
+            }
+        }
+
+        let router = router.with_state(self.state.clone());
+        let router = match self.path_normalization.take() {
+            Some(config) => normalize::layer(router, config, route_paths),
+            None => router,
+        };
+        // Outside normalization, which sees the path with the tenant's prefix removed
+        let router = match tenancy {
+            Some((tenancy, limiter)) => tenancy::layer(router, tenancy, limiter.as_ref()),
+            None => router,
+        };
+        // In front of everything, so each batched request is served like any other
+        let router = match self.batch.take() {
+            Some(config) => batch::layer(router, config, self.max_body_size.unwrap_or(DEFAULT_MAX_BODY_SIZE)),
+            None => router,
+        };
+        // Outermost, so expectations the server cannot meet are refused before anything runs
+        Ok(expect::layer(router))
+    }
+}
 
-        let listener = tokio::net::TcpListener::bind(socket_addr).await?;
-        axum::serve(listener, app).await?;
+// Routes per served path and version, with the methods registered for them
+type PathRoutes = BTreeMap<Option<&'static str>, (axum::routing::MethodRouter<AppState>, Vec<&'static str>)>;
 
-        Ok(())
+// Mount the routes of one path, picking between versions per request if it has some
+fn mount_path(
+    router: Router<AppState>,
+    path: &str,
+    versions: PathRoutes,
+    versioning: &Arc<versioning::Versioning>,
+    state: &AppState,
+) -> Router<AppState> {
+    if versions.keys().all(Option::is_none) {
+        return versions.into_iter().fold(router, |router, (_, (route, methods))| {
+            router.route(path, with_method_fallback(route, &methods))
+        });
     }
+    // Each version routed on its own, and picked per request
+    let routes = versions
+        .into_iter()
+        .map(|(version, (route, methods))| {
+            let route = Router::new()
+                .route(path, with_method_fallback(route, &methods))
+                .with_state(state.clone());
+            (version, methods, route)
+        })
+        .collect();
+    router.route_service(path, versioning::VersionedRoute::new(versioning.clone(), routes))
+}
+
+async fn not_found_handler(uri: axum::http::Uri) -> axum::response::Response {
+    error_response(StatusCode::NOT_FOUND, format!("Route {} not found", uri.path()))
+}
+
+// The 404 for a path that other versions of the API serve
+async fn version_hint_handler(uri: axum::http::Uri, hints: Arc<versioning::VersionHints>) -> axum::response::Response {
+    match hints.hint(uri.path()) {
+        Some(hint) => error_response(StatusCode::NOT_FOUND, format!("Route {} not found; {}", uri.path(), hint)),
+        None => not_found_handler(uri).await,
+    }
+}
+
+// A known path with an unregistered method answers 405 rather than 404, and
+// OPTIONS with the methods it serves
+fn with_method_fallback(
+    route: axum::routing::MethodRouter<AppState>,
+    methods: &[&str],
+) -> axum::routing::MethodRouter<AppState> {
+    let allow = allow_header(&[methods, &["OPTIONS"]].concat());
+    // Unless a route handles OPTIONS itself
+    let route = if methods.contains(&"OPTIONS") {
+        route
+    } else {
+        let allow = allow.clone();
+        route.options(move || {
+            let allow = allow.clone();
+            async move { (StatusCode::NO_CONTENT, [(axum::http::header::ALLOW, allow)]) }
+        })
+    };
+    let not_allowed = move || {
+        let allow = allow.clone();
+        async move {
+            let mut response = error_response(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed".to_string());
+            response.headers_mut().insert(axum::http::header::ALLOW, allow);
+            response
+        }
+    };
+    // The router would otherwise answer HEAD with the GET handler
+    let route = if methods.contains(&"GET") && !methods.contains(&"HEAD") {
+        route.head(not_allowed.clone())
+    } else {
+        route
+    };
+    route.fallback(not_allowed)
+}
+
+// `Allow` value for a path's served methods
+fn allow_header(methods: &[&str]) -> axum::http::HeaderValue {
+    const ORDER: [&str; 7] = ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"];
+    let allowed: Vec<&str> = ORDER.into_iter().filter(|candidate| methods.contains(candidate)).collect();
+    axum::http::HeaderValue::from_str(&allowed.join(", ")).expect("method names are valid header values")
+}
+
+// Answer for a request whose handler panicked
+fn panic_response() -> axum::response::Response {
+    error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
 }
 
-async fn not_found_handler(uri: axum::http::Uri) -> (StatusCode, Json<ApiResponse<String>>) {
-    (
-        StatusCode::NOT_FOUND,
-        Json(ApiResponse {
-            success: false,
-            data: None,
-            message: format!("Route {} not found", uri.path()),
-        }),
-    )
+// Error envelope used for framework-generated failures
+fn error_response(status: StatusCode, message: String) -> axum::response::Response {
+    FerroxError::new(status, message).into_response()
 }
 
 // Server components are now available at the library root
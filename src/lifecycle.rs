@@ -0,0 +1,73 @@
+// Hooks registered with `Server::on_startup` and `Server::on_shutdown`. Startup
+// hooks run in order before the listener is bound, and the first failure stops
+// the boot; shutdown hooks run in order once the server has stopped, each
+// failure being logged without skipping the rest.
+
+use std::fmt;
+use std::future::Future;
+
+use futures_util::future::BoxFuture;
+
+pub(crate) type Hook = Box<dyn FnOnce() -> BoxFuture<'static, Result<(), String>> + Send>;
+
+pub(crate) fn hook<F, Fut, E>(hook: F) -> Hook
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), E>> + Send + 'static,
+    E: fmt::Display,
+{
+    Box::new(move || {
+        let run = hook();
+        Box::pin(async move { run.await.map_err(|err| err.to_string()) })
+    })
+}
+
+/// A startup hook failed, so the server was not started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartupError {
+    // 1-based, in registration order
+    hook: usize,
+    count: usize,
+    message: String,
+}
+
+impl StartupError {
+    /// Position of the failed hook in registration order, starting at 1.
+    pub fn hook(&self) -> usize {
+        self.hook
+    }
+
+    /// The hook's error message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for StartupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "startup hook {} of {} failed: {}", self.hook, self.count, self.message)
+    }
+}
+
+impl std::error::Error for StartupError {}
+
+pub(crate) async fn startup(hooks: Vec<Hook>) -> Result<(), StartupError> {
+    let count = hooks.len();
+    for (index, hook) in hooks.into_iter().enumerate() {
+        hook().await.map_err(|message| StartupError {
+            hook: index + 1,
+            count,
+            message,
+        })?;
+    }
+    Ok(())
+}
+
+pub(crate) async fn shutdown(hooks: Vec<Hook>) {
+    let count = hooks.len();
+    for (index, hook) in hooks.into_iter().enumerate() {
+        if let Err(message) = hook().await {
+            tracing::error!("Shutdown hook {} of {} failed: {}", index + 1, count, message);
+        }
+    }
+}
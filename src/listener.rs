@@ -0,0 +1,434 @@
+//! Where a server accepts connections: TCP addresses, Unix domain sockets and
+//! sockets bound by another process, such as those passed by systemd socket activation.
+//!
+//! ```ignore
+//! Server::new()
+//!     .bind(Listener::tcp("0.0.0.0:8080"))
+//!     .bind(Listener::unix("/run/app/http.sock"))
+//!     .run()
+//!     .await?;
+//! ```
+//!
+//! Every listener serves the same router. Requests over a Unix socket have no
+//! client address, so `RequestContext::remote_addr` is `None` and the rate
+//! limiter counts them under one key. A Unix socket path left over from a
+//! previous run is replaced, and the socket file is removed when the server stops.
+
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::ConnectInfo;
+use axum::Router;
+use hyper::body::Incoming;
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{oneshot, watch};
+use tokio::task::JoinSet;
+use tower::Service;
+
+use crate::connection::{ConnectionConfig, Tuning};
+use crate::http2::Protocols;
+
+/// A socket for `Server::bind`.
+#[derive(Debug)]
+pub struct Listener {
+    kind: Kind,
+}
+
+#[derive(Debug)]
+enum Kind {
+    Tcp(String),
+    #[cfg(unix)]
+    ReusePort(String),
+    TcpListener(std::net::TcpListener),
+    #[cfg(unix)]
+    Unix(PathBuf),
+    #[cfg(unix)]
+    UnixListener(std::os::unix::net::UnixListener),
+}
+
+impl Listener {
+    /// Bind a TCP address such as `0.0.0.0:8080`; port 0 picks a free port.
+    pub fn tcp(addr: impl Into<String>) -> Self {
+        Listener {
+            kind: Kind::Tcp(addr.into()),
+        }
+    }
+
+    /// Bind a TCP address with `SO_REUSEPORT`, so another process, such as the
+    /// next version of this one, can bind it too; see `ferrox::restart`.
+    #[cfg(unix)]
+    pub fn reuse_port(addr: impl Into<String>) -> Self {
+        Listener {
+            kind: Kind::ReusePort(addr.into()),
+        }
+    }
+
+    /// Bind a Unix domain socket at `path`.
+    #[cfg(unix)]
+    pub fn unix(path: impl Into<PathBuf>) -> Self {
+        Listener {
+            kind: Kind::Unix(path.into()),
+        }
+    }
+
+    /// Serve on an already bound TCP listener.
+    pub fn from_tcp(listener: std::net::TcpListener) -> Self {
+        Listener {
+            kind: Kind::TcpListener(listener),
+        }
+    }
+
+    /// Serve on an already bound Unix socket listener.
+    #[cfg(unix)]
+    pub fn from_unix(listener: std::os::unix::net::UnixListener) -> Self {
+        Listener {
+            kind: Kind::UnixListener(listener),
+        }
+    }
+
+    /// The sockets passed by systemd socket activation (`LISTEN_FDS`), in order.
+    ///
+    /// Empty when the process was not socket activated. The sockets are taken
+    /// once; later calls return no listeners.
+    #[cfg(unix)]
+    pub fn from_systemd() -> io::Result<Vec<Listener>> {
+        use std::os::fd::{FromRawFd, IntoRawFd};
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        // File descriptors systemd passes start after stdin, stdout and stderr
+        const FIRST_FD: i32 = 3;
+        static TAKEN: AtomicBool = AtomicBool::new(false);
+
+        let for_us = std::env::var("LISTEN_PID").ok().and_then(|pid| pid.parse::<u32>().ok()) == Some(std::process::id());
+        let count = std::env::var("LISTEN_FDS").ok().and_then(|count| count.parse::<i32>().ok()).unwrap_or(0);
+        if !for_us || count <= 0 || TAKEN.swap(true, Ordering::SeqCst) {
+            return Ok(Vec::new());
+        }
+        (FIRST_FD..FIRST_FD + count)
+            .map(|fd| {
+                // SAFETY: systemd hands these descriptors to this process, and TAKEN
+                // makes sure they are wrapped only once
+                let tcp = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+                if tcp.local_addr().is_ok() {
+                    return Ok(Listener::from_tcp(tcp));
+                }
+                // SAFETY: ownership moves back out of the TCP listener above
+                let unix = unsafe { std::os::unix::net::UnixListener::from_raw_fd(tcp.into_raw_fd()) };
+                match unix.local_addr() {
+                    Ok(_) => Ok(Listener::from_unix(unix)),
+                    Err(_) => Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("file descriptor {} passed by systemd is not a TCP or Unix socket", fd),
+                    )),
+                }
+            })
+            .collect()
+    }
+
+    pub(crate) async fn bind(self) -> io::Result<Bound> {
+        let bound = match self.kind {
+            Kind::Tcp(addr) => {
+                let addr = parse_addr(&addr)?;
+                // A socket handed over by the process this one replaces
+                #[cfg(unix)]
+                if let Some(listener) = crate::restart::take_tcp(addr) {
+                    listener.set_nonblocking(true)?;
+                    return Ok(Bound::Tcp(tokio::net::TcpListener::from_std(listener)?));
+                }
+                Bound::Tcp(tokio::net::TcpListener::bind(addr).await?)
+            }
+            #[cfg(unix)]
+            Kind::ReusePort(addr) => {
+                let addr = parse_addr(&addr)?;
+                let socket = match addr {
+                    SocketAddr::V4(_) => tokio::net::TcpSocket::new_v4()?,
+                    SocketAddr::V6(_) => tokio::net::TcpSocket::new_v6()?,
+                };
+                socket.set_reuseaddr(true)?;
+                socket.set_reuseport(true)?;
+                socket.bind(addr)?;
+                Bound::Tcp(socket.listen(1024)?)
+            }
+            Kind::TcpListener(listener) => {
+                listener.set_nonblocking(true)?;
+                Bound::Tcp(tokio::net::TcpListener::from_std(listener)?)
+            }
+            #[cfg(unix)]
+            Kind::Unix(path) => {
+                if let Some(listener) = crate::restart::take_unix(&path) {
+                    listener.set_nonblocking(true)?;
+                    return Ok(Bound::Unix(tokio::net::UnixListener::from_std(listener)?, Some(path)));
+                }
+                remove_stale_socket(&path)?;
+                Bound::Unix(tokio::net::UnixListener::bind(&path)?, Some(path))
+            }
+            #[cfg(unix)]
+            Kind::UnixListener(listener) => {
+                listener.set_nonblocking(true)?;
+                Bound::Unix(tokio::net::UnixListener::from_std(listener)?, None)
+            }
+        };
+        Ok(bound)
+    }
+}
+
+// A listening socket, with the path to remove afterwards for Unix sockets we created
+pub(crate) enum Bound {
+    Tcp(tokio::net::TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener, Option<PathBuf>),
+}
+
+impl Bound {
+    // Where clients reach the listener, for the startup report
+    pub(crate) fn address(&self) -> String {
+        match self {
+            Bound::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => format!("http://{}", addr),
+                Err(_) => "tcp (unknown address)".to_string(),
+            },
+            #[cfg(unix)]
+            Bound::Unix(listener, _) => match listener.local_addr().ok().and_then(|addr| addr.as_pathname().map(|path| path.display().to_string())) {
+                Some(path) => format!("unix:{}", path),
+                None => "unix (unnamed socket)".to_string(),
+            },
+        }
+    }
+
+    pub(crate) fn local_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Bound::Tcp(listener) => listener.local_addr().ok(),
+            #[cfg(unix)]
+            Bound::Unix(..) => None,
+        }
+    }
+
+    // A copy of the socket, to hand over to a successor process
+    #[cfg(unix)]
+    pub(crate) fn socket(&self) -> io::Result<std::os::fd::OwnedFd> {
+        use std::os::fd::AsFd;
+
+        match self {
+            Bound::Tcp(listener) => listener.as_fd().try_clone_to_owned(),
+            Bound::Unix(listener, _) => listener.as_fd().try_clone_to_owned(),
+        }
+    }
+}
+
+fn parse_addr(addr: &str) -> io::Result<SocketAddr> {
+    addr.parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid address {:?}: {}", addr, err)))
+}
+
+// A socket file from an earlier run would make binding fail
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
+
+// Connections open on every plain listener of the process, reported by the admin endpoints
+static OPEN_CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+pub(crate) fn open_connections() -> usize {
+    OPEN_CONNECTIONS.load(Ordering::Relaxed)
+}
+
+// Counted from accept until the connection's task ends
+struct OpenConnection;
+
+impl OpenConnection {
+    fn new() -> Self {
+        OPEN_CONNECTIONS.fetch_add(1, Ordering::Relaxed);
+        OpenConnection
+    }
+}
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        OPEN_CONNECTIONS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// Serve `app` on every listener until `shutdown` fires, then let open connections finish
+pub(crate) async fn serve(
+    listeners: Vec<Bound>,
+    app: Router,
+    protocols: Protocols,
+    connections: ConnectionConfig,
+    shutdown: oneshot::Receiver<()>,
+) -> io::Result<()> {
+    let protocols = Arc::new(protocols);
+    let tuning = Tuning::new(&connections);
+    let (stop, stopped) = watch::channel(false);
+    let mut servers = JoinSet::new();
+    for listener in listeners {
+        let (app, protocols, tuning, stopped) = (app.clone(), protocols.clone(), tuning.clone(), stopped.clone());
+        match listener {
+            Bound::Tcp(listener) => {
+                servers.spawn(accept_loop(listener, app, protocols, tuning, stopped));
+            }
+            #[cfg(unix)]
+            Bound::Unix(listener, path) => {
+                servers.spawn(async move {
+                    accept_loop(listener, app, protocols, tuning, stopped).await;
+                    // A successor process serves the socket file now
+                    if let Some(path) = path.filter(|_| !crate::restart::handed_over()) {
+                        let _ = std::fs::remove_file(path);
+                    }
+                });
+            }
+        }
+    }
+    // A dropped `ServerHandle` leaves the server running
+    if shutdown.await.is_err() {
+        std::future::pending::<()>().await;
+    }
+    let _ = stop.send(true);
+    while servers.join_next().await.is_some() {}
+    Ok(())
+}
+
+trait Accept: Send + 'static {
+    type Stream: AsyncRead + AsyncWrite + Send + Unpin + 'static;
+
+    // The next connection and, for TCP, the client's address
+    fn accept(&self) -> impl Future<Output = io::Result<(Self::Stream, Option<SocketAddr>)>> + Send;
+
+    // Apply the socket options that exist for this kind of stream
+    fn tune(_stream: &Self::Stream, _tuning: &Tuning) {}
+}
+
+impl Accept for tokio::net::TcpListener {
+    type Stream = tokio::net::TcpStream;
+
+    async fn accept(&self) -> io::Result<(Self::Stream, Option<SocketAddr>)> {
+        let (stream, peer) = tokio::net::TcpListener::accept(self).await?;
+        Ok((stream, Some(peer)))
+    }
+
+    fn tune(stream: &Self::Stream, tuning: &Tuning) {
+        tuning.tune(stream);
+    }
+}
+
+#[cfg(unix)]
+impl Accept for tokio::net::UnixListener {
+    type Stream = tokio::net::UnixStream;
+
+    async fn accept(&self) -> io::Result<(Self::Stream, Option<SocketAddr>)> {
+        let (stream, _) = tokio::net::UnixListener::accept(self).await?;
+        Ok((stream, None))
+    }
+}
+
+// Accept and serve connections until `stopped` changes, then wait for them to close;
+// dropping the future drops every connection with it
+async fn accept_loop<L: Accept>(
+    listener: L,
+    app: Router,
+    protocols: Arc<Protocols>,
+    tuning: Tuning,
+    mut stopped: watch::Receiver<bool>,
+) {
+    let mut connections = JoinSet::new();
+    loop {
+        // At `max_connections`, stop accepting until a connection closes
+        let slot = tokio::select! {
+            slot = tuning.slot() => slot,
+            _ = stopped.changed() => break,
+        };
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(connection) => connection,
+                Err(err) if is_connection_error(&err) => continue,
+                // Usually too many open files; wait for some to close
+                Err(err) => {
+                    tracing::error!("Failed to accept connection: {}", err);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            },
+            _ = stopped.changed() => break,
+        };
+        L::tune(&stream, &tuning);
+        let stream = tuning.wrap(stream, slot);
+        connections.spawn(serve_connection(stream, peer, app.clone(), protocols.clone(), stopped.clone()));
+        while connections.try_join_next().is_some() {}
+    }
+    // Refuse new connections instead of leaving them in the backlog while draining
+    drop(listener);
+    while connections.join_next().await.is_some() {}
+}
+
+async fn serve_connection<S>(
+    stream: S,
+    peer: Option<SocketAddr>,
+    app: Router,
+    protocols: Arc<Protocols>,
+    stopped: watch::Receiver<bool>,
+) where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let _open = OpenConnection::new();
+    let service = hyper::service::service_fn(move |mut request: hyper::Request<Incoming>| {
+        if let Some(peer) = peer {
+            request.extensions_mut().insert(ConnectInfo(peer));
+        }
+        let mut app = app.clone();
+        async move { Ok::<_, Infallible>(app.call(request).await.unwrap_or_else(|never| match never {})) }
+    });
+    let io = TokioIo::new(stream);
+    match &*protocols {
+        Protocols::Auto(builder) => {
+            let connection = builder.serve_connection_with_upgrades(io, service);
+            run_until_stopped(connection, stopped, |connection| connection.graceful_shutdown()).await;
+        }
+        Protocols::Http1(builder) => {
+            let connection = builder.serve_connection(io, service).with_upgrades();
+            run_until_stopped(connection, stopped, |connection| connection.graceful_shutdown()).await;
+        }
+    }
+}
+
+// Serve `connection` until it closes or `stopped` changes; then finish the request
+// in progress and close
+async fn run_until_stopped<C, E>(connection: C, mut stopped: watch::Receiver<bool>, graceful: fn(Pin<&mut C>))
+where
+    C: Future<Output = Result<(), E>>,
+    E: Display,
+{
+    tokio::pin!(connection);
+    tokio::select! {
+        result = connection.as_mut() => {
+            if let Err(err) = result {
+                tracing::debug!("Connection closed with an error: {}", err);
+            }
+            return;
+        }
+        _ = stopped.changed() => {}
+    }
+    graceful(connection.as_mut());
+    let _ = connection.await;
+}
+
+fn is_connection_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionAborted | io::ErrorKind::ConnectionReset
+    )
+}
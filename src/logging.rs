@@ -0,0 +1,213 @@
+//! Logging through `tracing`, with an optional per-request access log.
+//!
+//! Ferrox emits `tracing` events for startup, configuration problems and,
+//! with `Server::access_log`, every request. When started with
+//! `Server::start` it installs a subscriber printing those events as text or,
+//! with `LogFormat::Json`, one JSON object per line; an application that sets
+//! its own global subscriber first keeps it. The level is read from
+//! `RUST_LOG`, falling back to `Server::log_level` (`info` by default), and
+//! can be changed while running with [`set_level`].
+//!
+//! Once listening, the server logs its routes and addresses at `info`, unless
+//! `Server::quiet` is set or a `Server::startup_report` callback takes the
+//! [`StartupReport`] instead.
+
+use std::hash::{BuildHasher, RandomState};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use axum::extract::{MatchedPath, Request};
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::Router;
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::EnvFilter;
+
+use crate::context::AppState;
+use crate::routes::RouteInfo;
+
+pub(crate) const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+const TRACESTATE: HeaderName = HeaderName::from_static("tracestate");
+
+/// How the subscriber installed by `Server::start` formats events.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines.
+    #[default]
+    Text,
+    /// One JSON object per event, for log pipelines.
+    Json,
+}
+
+/// Install a global subscriber writing to stdout in `format`.
+///
+/// Does nothing if a global subscriber is already set.
+pub fn init(format: LogFormat) {
+    init_with_level(format, "info");
+}
+
+// `init`, with `level` as the filter when `RUST_LOG` is not set
+pub(crate) fn init_with_level(format: LogFormat, level: &str) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
+    let current = filter.to_string();
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    let (installed, reload): (_, Reload) = match format {
+        LogFormat::Text => {
+            let builder = builder.with_filter_reloading();
+            let handle = builder.reload_handle();
+            (builder.try_init(), Box::new(move |filter| handle.reload(filter).map_err(|err| err.to_string())))
+        }
+        LogFormat::Json => {
+            let builder = builder.json().flatten_event(true).with_filter_reloading();
+            let handle = builder.reload_handle();
+            (builder.try_init(), Box::new(move |filter| handle.reload(filter).map_err(|err| err.to_string())))
+        }
+    };
+    if installed.is_ok() {
+        let _ = LEVEL.set((reload, Mutex::new(current)));
+    }
+}
+
+type Reload = Box<dyn Fn(EnvFilter) -> Result<(), String> + Send + Sync>;
+
+// How to change the filter of the subscriber we installed, and the filter in use
+static LEVEL: OnceLock<(Reload, Mutex<String>)> = OnceLock::new();
+
+/// The level filter of the subscriber installed by `Server::start`; `None` if
+/// the application installed its own.
+pub fn level() -> Option<String> {
+    let (_, current) = LEVEL.get()?;
+    Some(current.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone())
+}
+
+/// Replace the level filter of the subscriber installed by `Server::start`
+/// while the server runs, e.g. `debug` or `info,ferrox::access=warn`.
+pub fn set_level(filter: &str) -> Result<(), String> {
+    let Some((reload, current)) = LEVEL.get() else {
+        return Err("the log subscriber was not installed by ferrox".to_string());
+    };
+    let parsed = EnvFilter::try_new(filter).map_err(|err| format!("invalid level filter {:?}: {}", filter, err))?;
+    let mut current = current.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    reload(parsed)?;
+    *current = filter.to_string();
+    Ok(())
+}
+
+/// Id of the current request: the client's `X-Request-Id` if it sent one,
+/// otherwise generated. Available in `RequestContext::extensions` when the
+/// access log is enabled, and echoed in the response's `X-Request-Id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+// What the request being handled passes on to the requests it makes with
+// `client::Client`
+#[derive(Clone)]
+#[cfg_attr(not(feature = "client"), allow(dead_code))]
+pub(crate) struct Propagated {
+    pub(crate) request_id: String,
+    pub(crate) trace: Vec<(HeaderName, HeaderValue)>,
+}
+
+tokio::task_local! {
+    static PROPAGATED: Propagated;
+}
+
+// The request id and incoming trace headers of the request being handled, when
+// the access log is enabled
+#[cfg_attr(not(feature = "client"), allow(dead_code))]
+pub(crate) fn propagated() -> Option<Propagated> {
+    PROPAGATED.try_with(Propagated::clone).ok()
+}
+
+// Unique within the process and hard to guess, but not a UUID
+fn generate_request_id() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    format!("{:016x}", RandomState::new().hash_one(n))
+}
+
+// Log one `ferrox::access` event per request
+pub(crate) fn access_log(router: Router<AppState>) -> Router<AppState> {
+    router.layer(axum::middleware::from_fn(|mut request: Request, next: Next| async move {
+        let started = Instant::now();
+        let request_id = request
+            .headers()
+            .get(&REQUEST_ID)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| !id.is_empty() && id.len() <= 128)
+            .map(str::to_string)
+            .unwrap_or_else(generate_request_id);
+        let method = request.method().clone();
+        let path = request.uri().path().to_string();
+        let route = request.extensions().get::<MatchedPath>().map(|matched| matched.as_str().to_string());
+        request.extensions_mut().insert(RequestId(request_id.clone()));
+        let propagated = Propagated {
+            request_id: request_id.clone(),
+            trace: [TRACEPARENT, TRACESTATE]
+                .into_iter()
+                .filter_map(|name| request.headers().get(&name).cloned().map(|value| (name, value)))
+                .collect(),
+        };
+
+        let mut response: Response = PROPAGATED.scope(propagated, next.run(request)).await;
+
+        tracing::info!(
+            target: "ferrox::access",
+            method = %method,
+            path = %path,
+            route = route.as_deref().unwrap_or("-"),
+            status = response.status().as_u16(),
+            latency_ms = (started.elapsed().as_secs_f64() * 1e6).round() / 1e3,
+            request_id = %request_id,
+            "request"
+        );
+        if let Ok(value) = HeaderValue::from_str(&request_id) {
+            response.headers_mut().insert(REQUEST_ID, value);
+        }
+        response
+    }))
+}
+
+/// What a server serves, given to the `Server::startup_report` callback once
+/// its listeners are bound.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartupReport {
+    /// Every listener, e.g. `http://127.0.0.1:8080` or `unix:/run/app.sock`.
+    pub addresses: Vec<String>,
+    /// The listener of the admin endpoints, when they have their own.
+    pub admin: Option<String>,
+    /// The registered routes, as listed by `ferrox::routes()`.
+    pub routes: Vec<RouteInfo>,
+    /// Methods and paths of the dynamic routes added before startup.
+    pub dynamic_routes: Vec<(String, String)>,
+    /// The plugins installed, in installation order.
+    pub plugins: Vec<String>,
+}
+
+pub(crate) type StartupReporter = Box<dyn Fn(&StartupReport) + Send + Sync>;
+
+// The report logged when no callback is set
+pub(crate) fn log_startup(report: &StartupReport) {
+    for route in &report.routes {
+        tracing::info!(
+            method = route.method,
+            path = route.path,
+            kind = route.kind,
+            handler = route.handler,
+            "Registered route"
+        );
+    }
+    for (method, path) in &report.dynamic_routes {
+        tracing::info!(method, path, kind = "dynamic", "Registered route");
+    }
+    for address in &report.addresses {
+        tracing::info!("Server running at {}", address);
+    }
+    if let Some(address) = &report.admin {
+        tracing::info!("Admin endpoints at {}", address);
+    }
+}
@@ -0,0 +1,152 @@
+// Request metrics served in the Prometheus text format by `Server::enable_metrics`.
+//
+// Requests are counted per method, matched route pattern and status, and
+// their latency recorded in one histogram per method and route. Requests that
+// match no route share the `route="unmatched"` label so probing random paths
+// cannot grow the label set without bound.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use axum::extract::{MatchedPath, Request};
+use axum::http::header::CONTENT_TYPE;
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+
+use crate::context::AppState;
+use crate::resilience::{self, CircuitBreaker};
+
+// Upper bounds in seconds, as in the Prometheus client libraries
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+pub(crate) const DEFAULT_PATH: &str = "/metrics";
+
+#[derive(Clone, Default)]
+pub(crate) struct Metrics {
+    routes: Arc<Mutex<HashMap<(String, String), RouteMetrics>>>,
+    // Reported alongside the requests
+    circuits: Vec<CircuitBreaker>,
+}
+
+#[derive(Default)]
+struct RouteMetrics {
+    statuses: BTreeMap<u16, u64>,
+    // Count per bucket, not cumulative
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Metrics {
+    pub(crate) fn new(circuits: Vec<CircuitBreaker>) -> Self {
+        Metrics {
+            circuits,
+            ..Metrics::default()
+        }
+    }
+
+    fn record(&self, method: String, route: String, status: u16, seconds: f64) {
+        let mut routes = self.routes.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let metrics = routes.entry((method, route)).or_default();
+        *metrics.statuses.entry(status).or_default() += 1;
+        if let Some(bucket) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+            metrics.buckets[bucket] += 1;
+        }
+        metrics.sum += seconds;
+        metrics.count += 1;
+    }
+
+    fn render(&self) -> String {
+        let routes = self.routes.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut keys: Vec<_> = routes.keys().collect();
+        keys.sort();
+
+        let mut out = String::new();
+        out.push_str("# HELP ferrox_http_requests_total Requests handled, by method, route and status.\n");
+        out.push_str("# TYPE ferrox_http_requests_total counter\n");
+        for key in &keys {
+            let (method, route) = key;
+            for (status, count) in &routes[*key].statuses {
+                let _ = writeln!(
+                    out,
+                    "ferrox_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                    method,
+                    escape(route),
+                    status,
+                    count
+                );
+            }
+        }
+
+        out.push_str("# HELP ferrox_http_request_duration_seconds Request latency, by method and route.\n");
+        out.push_str("# TYPE ferrox_http_request_duration_seconds histogram\n");
+        for key in &keys {
+            let (method, route) = key;
+            let metrics = &routes[*key];
+            let labels = format!("method=\"{}\",route=\"{}\"", method, escape(route));
+            let mut cumulative = 0;
+            for (bound, count) in BUCKETS.iter().zip(metrics.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "ferrox_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "ferrox_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, metrics.count
+            );
+            let _ = writeln!(out, "ferrox_http_request_duration_seconds_sum{{{}}} {}", labels, metrics.sum);
+            let _ = writeln!(out, "ferrox_http_request_duration_seconds_count{{{}}} {}", labels, metrics.count);
+        }
+        resilience::render_metrics(&self.circuits, &mut out);
+        #[cfg(feature = "client")]
+        crate::client::render_metrics(&mut out);
+        out
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+// Record every request to the router
+pub(crate) fn record(router: Router<AppState>, metrics: Metrics) -> Router<AppState> {
+    router.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let metrics = metrics.clone();
+        async move {
+            let started = Instant::now();
+            let method = request.method().to_string();
+            let route = match request.extensions().get::<MatchedPath>() {
+                Some(matched) => matched.as_str().to_string(),
+                None => "unmatched".to_string(),
+            };
+            let response = next.run(request).await;
+            metrics.record(method, route, response.status().as_u16(), started.elapsed().as_secs_f64());
+            response
+        }
+    }))
+}
+
+// Serve the metrics at `path`, outside every layer so scrapes are neither counted nor limited
+pub(crate) fn mount(router: Router<AppState>, path: &str, metrics: Metrics) -> Router<AppState> {
+    router.route(
+        path,
+        get(move || {
+            let metrics = metrics.clone();
+            async move {
+                (
+                    [(CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+                    metrics.render(),
+                )
+                    .into_response()
+            }
+        }),
+    )
+}
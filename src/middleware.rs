@@ -0,0 +1,39 @@
+//! Tower layer registration for `Server::layer`, `Server::route_layer` and the
+//! declarative `#[middleware]` attribute.
+
+use axum::extract::Request;
+use axum::response::IntoResponse;
+use axum::routing::{MethodRouter, Route};
+use axum::Router;
+use std::convert::Infallible;
+use tower::{Layer, Service};
+
+use crate::context::AppState;
+
+/// A route wrapper generated by `#[middleware(...)]` on a handler.
+pub type MiddlewareFn = fn(MethodRouter<AppState>) -> MethodRouter<AppState>;
+
+pub(crate) type RouterLayer = Box<dyn FnOnce(Router<AppState>) -> Router<AppState> + Send>;
+pub(crate) type MethodRouterLayer = Box<dyn FnOnce(MethodRouter<AppState>) -> MethodRouter<AppState> + Send>;
+
+pub(crate) fn router_layer<L>(layer: L) -> RouterLayer
+where
+    L: Layer<Route> + Clone + Send + 'static,
+    L::Service: Service<Request> + Clone + Send + 'static,
+    <L::Service as Service<Request>>::Response: IntoResponse + 'static,
+    <L::Service as Service<Request>>::Error: Into<Infallible> + 'static,
+    <L::Service as Service<Request>>::Future: Send + 'static,
+{
+    Box::new(move |router| router.layer(layer))
+}
+
+pub(crate) fn method_router_layer<L>(layer: L) -> MethodRouterLayer
+where
+    L: Layer<Route> + Clone + Send + 'static,
+    L::Service: Service<Request> + Clone + Send + 'static,
+    <L::Service as Service<Request>>::Response: IntoResponse + 'static,
+    <L::Service as Service<Request>>::Error: Into<Infallible> + 'static,
+    <L::Service as Service<Request>>::Future: Send + 'static,
+{
+    Box::new(move |route| route.layer(layer))
+}
@@ -0,0 +1,143 @@
+//! A fake of the API answering every route with a canned or example response.
+//!
+//! ```ignore
+//! Server::new()
+//!     .cors(CorsConfig::new().allow_origin("http://localhost:5173"))
+//!     .mock(
+//!         MockServer::new()
+//!             .respond("GET", "/users/:id", StatusCode::OK, json!({ "id": 7, "name": "alice" }))
+//!             .example("Order", json!({ "id": 1, "sku": "A-1", "status": "paid" }))
+//!             .openapi(serde_json::from_str(include_str!("../openapi.json"))?),
+//!     )
+//!     .start("127.0.0.1:4000")
+//!     .await?;
+//! ```
+//!
+//! `Server::mock` keeps the registered routes, their paths, methods and layers
+//! (auth, guards, CORS, rate limits and the rest), but runs none of their
+//! handlers. Each route answers, in order of preference:
+//!
+//! - the response given for it with `respond`;
+//! - the example of its lowest 2xx response in the `openapi` document, from
+//!   the `example` or first of the `examples` of its JSON content;
+//! - a value shaped like its declared return type, built from the same schema
+//!   as the generated OpenAPI document: `0` for numbers, `"string"` for
+//!   strings, `true` for booleans, one item for arrays, and the `example`
+//!   given for a named type, or else `{}`.
+//!
+//! Responses are the same for every request and go through the usual envelope
+//! and format negotiation. Request bodies and parameters are not parsed.
+//! WebSocket and SSE routes keep their real handlers.
+
+use std::collections::HashMap;
+
+use axum::http::StatusCode;
+use serde_json::{Map, Value};
+
+use crate::response::HandlerResponse;
+use crate::{openapi, RouteHandler, RouteRegistration};
+
+/// The canned responses of a mocked server; register it with `Server::mock`.
+#[derive(Debug, Clone, Default)]
+pub struct MockServer {
+    responses: HashMap<(String, String), (StatusCode, Value)>,
+    examples: HashMap<String, Value>,
+    openapi: Option<Value>,
+}
+
+impl MockServer {
+    /// Answer every route with a value shaped like its return type.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer the route registered as `method` `path` (e.g. `GET` `/users/:id`)
+    /// with `status` and `body`.
+    pub fn respond(mut self, method: &str, path: &str, status: StatusCode, body: Value) -> Self {
+        self.responses
+            .insert((method.to_ascii_uppercase(), path.to_string()), (status, body));
+        self
+    }
+
+    /// Use `value` wherever a response holds the type named `type_name`, e.g. `User`.
+    pub fn example(mut self, type_name: &str, value: Value) -> Self {
+        self.examples.insert(type_name.to_string(), value);
+        self
+    }
+
+    /// Take the examples of an OpenAPI document, such as one written by hand
+    /// before the handlers exist.
+    pub fn openapi(mut self, document: Value) -> Self {
+        self.openapi = Some(document);
+        self
+    }
+
+    // The handler standing in for `registration`'s, answering the same every time
+    pub(crate) fn handler(&self, registration: &RouteRegistration) -> RouteHandler {
+        let (status, body) = self.response(registration);
+        let response = HandlerResponse::new(status, body);
+        RouteHandler::from_sync(move |_| response.clone())
+    }
+
+    fn response(&self, registration: &RouteRegistration) -> (StatusCode, Value) {
+        let key = (registration.method.to_string(), registration.path.to_string());
+        if let Some(response) = self.responses.get(&key) {
+            return response.clone();
+        }
+        if let Some(response) = self.openapi_example(registration) {
+            return response;
+        }
+        let body = openapi::response_schema(registration.response)
+            .map(|schema| self.value_for(&schema))
+            .unwrap_or(Value::Null);
+        let status = StatusCode::from_u16(openapi::success_status(registration)).unwrap_or(StatusCode::OK);
+        (status, body)
+    }
+
+    fn openapi_example(&self, registration: &RouteRegistration) -> Option<(StatusCode, Value)> {
+        let operation = self
+            .openapi
+            .as_ref()?
+            .get("paths")?
+            .get(openapi::openapi_path(registration.path))?
+            .get(registration.method.to_ascii_lowercase())?;
+        let responses = operation.get("responses")?.as_object()?;
+        let (status, response) = responses
+            .iter()
+            .filter(|(status, _)| status.starts_with('2'))
+            .min_by_key(|(status, _)| *status)?;
+        let content = response.get("content")?.get("application/json")?;
+        let example = match content.get("example") {
+            Some(example) => example.clone(),
+            None => content.get("examples")?.as_object()?.values().next()?.get("value")?.clone(),
+        };
+        Some((status.parse().ok()?, example))
+    }
+
+    // A value matching `schema`, preferring its own and the registered examples
+    fn value_for(&self, schema: &Value) -> Value {
+        if let Some(example) = schema.get("example") {
+            return example.clone();
+        }
+        if let Some(example) = schema.get("title").and_then(Value::as_str).and_then(|title| self.examples.get(title)) {
+            return example.clone();
+        }
+        match schema.get("type").and_then(Value::as_str) {
+            Some("integer") => Value::from(0),
+            Some("number") => Value::from(0.0),
+            Some("boolean") => Value::Bool(true),
+            Some("string") => Value::from("string"),
+            Some("array") => Value::Array(vec![self.value_for(schema.get("items").unwrap_or(&Value::Null))]),
+            Some("object") => {
+                let properties = schema.get("properties").and_then(Value::as_object);
+                let fields: Map<String, Value> = properties
+                    .into_iter()
+                    .flatten()
+                    .map(|(name, property)| (name.clone(), self.value_for(property)))
+                    .collect();
+                Value::Object(fields)
+            }
+            _ => Value::Null,
+        }
+    }
+}
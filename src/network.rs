@@ -0,0 +1,282 @@
+//! Network access: CIDR allow and deny rules, and the client IP behind proxies.
+//!
+//! ```ignore
+//! Server::new().trusted_proxies(["10.0.0.0/8"]);
+//!
+//! #[route_group(prefix = "/admin", middleware = [
+//!     layer = ferrox::network::IpFilter::new().allow("10.0.0.0/8").allow("192.168.0.0/16"),
+//! ])]
+//! mod admin { ... }
+//! ```
+//!
+//! The client IP is the connected peer's address, unless the peer is one of
+//! `Server::trusted_proxies`: then the `Forwarded` header (or else
+//! `X-Forwarded-For`) is read from the right, skipping trusted proxies, and the
+//! first address not trusted is the client. Headers sent by untrusted peers are
+//! ignored, since anyone can set them. Rate limits, `IpFilter` and
+//! `RequestContext::client_ip` all use this address.
+//!
+//! An `IpFilter` answers 403 to clients matching a `deny` rule, and, once it has
+//! `allow` rules, to clients matching none of them, including requests whose
+//! address is unknown.
+
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use axum::extract::{ConnectInfo, Request};
+use axum::http::{Extensions, HeaderMap};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use futures_util::future::BoxFuture;
+use tower::{Layer, Service};
+
+use crate::context::AppState;
+use crate::error::FerroxError;
+
+/// A range of addresses in CIDR notation, such as `10.0.0.0/8` or `fd00::/8`;
+/// a bare address is a range of one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNet {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix)).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix)).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid address range `{}`, expected e.g. \"10.0.0.0/8\"", text);
+        let (addr, prefix) = match text.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (text.trim(), None),
+        };
+        let addr = IpAddr::from_str(addr).map_err(|_| invalid())?.to_canonical();
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>().ok().filter(|prefix| *prefix <= max).ok_or_else(invalid)?,
+            None => max,
+        };
+        Ok(IpNet { addr, prefix })
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+pub(crate) fn parse_net(net: &str) -> IpNet {
+    net.parse().unwrap_or_else(|err: String| panic!("{}", err))
+}
+
+/// Answers 403 to clients outside the allowed ranges; pass it to a route
+/// group's `middleware` as `layer = ...`, or to `Server::layer`.
+#[derive(Debug, Clone, Default)]
+pub struct IpFilter {
+    rules: Arc<Rules>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Rules {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+}
+
+impl IpFilter {
+    /// A filter letting every client through, until rules are added.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Let clients in `net` through; once any range is allowed, every other client is denied.
+    ///
+    /// # Panics
+    ///
+    /// If `net` is not an address or CIDR range.
+    pub fn allow(mut self, net: &str) -> Self {
+        Arc::make_mut(&mut self.rules).allow.push(parse_net(net));
+        self
+    }
+
+    /// Deny clients in `net`, even when an allowed range contains them.
+    ///
+    /// # Panics
+    ///
+    /// If `net` is not an address or CIDR range.
+    pub fn deny(mut self, net: &str) -> Self {
+        Arc::make_mut(&mut self.rules).deny.push(parse_net(net));
+        self
+    }
+
+    /// Whether a client at `ip` gets through; an unknown address only passes a filter without rules.
+    pub fn permits(&self, ip: Option<IpAddr>) -> bool {
+        let Some(ip) = ip else {
+            return self.rules.allow.is_empty() && self.rules.deny.is_empty();
+        };
+        !self.rules.deny.iter().any(|net| net.contains(ip))
+            && (self.rules.allow.is_empty() || self.rules.allow.iter().any(|net| net.contains(ip)))
+    }
+}
+
+impl<S> Layer<S> for IpFilter {
+    type Service = IpFilterService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IpFilterService {
+            inner,
+            filter: self.clone(),
+        }
+    }
+}
+
+/// The service `IpFilter` wraps routes in.
+#[derive(Debug, Clone)]
+pub struct IpFilterService<S> {
+    inner: S,
+    filter: IpFilter,
+}
+
+impl<S> Service<Request> for IpFilterService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        if !self.filter.permits(client_ip(request.extensions())) {
+            let response = FerroxError::Forbidden("Access denied".to_string()).into_response();
+            return Box::pin(async move { Ok(response) });
+        }
+        // The clone takes the place of the service that was polled ready
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move { inner.call(request).await })
+    }
+}
+
+// The client address resolved through trusted proxies, recorded on each request
+#[derive(Debug, Clone, Copy)]
+struct ClientIp(IpAddr);
+
+// Recorded on requests whose peer is a trusted proxy
+#[derive(Debug, Clone, Copy)]
+struct ViaTrustedProxy;
+
+// Whether the request came through one of `Server::trusted_proxies`, whose
+// headers can then be believed
+pub(crate) fn via_trusted_proxy(extensions: &Extensions) -> bool {
+    extensions.get::<ViaTrustedProxy>().is_some()
+}
+
+// The client's address: resolved through trusted proxies, or else the peer's
+pub(crate) fn client_ip(extensions: &Extensions) -> Option<IpAddr> {
+    match extensions.get::<ClientIp>() {
+        Some(ClientIp(ip)) => Some(*ip),
+        None => extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_canonical()),
+    }
+}
+
+// Resolve the client IP of every request to `router` through `proxies`
+pub(crate) fn trust_proxies(router: Router<AppState>, proxies: Vec<IpNet>) -> Router<AppState> {
+    let proxies = Arc::new(proxies);
+    router.layer(axum::middleware::from_fn(move |mut request: Request, next: Next| {
+        let proxies = proxies.clone();
+        async move {
+            let peer = request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_canonical());
+            if let Some(peer) = peer {
+                if proxies.iter().any(|net| net.contains(peer)) {
+                    request.extensions_mut().insert(ViaTrustedProxy);
+                }
+                let ip = resolve(peer, request.headers(), &proxies);
+                request.extensions_mut().insert(ClientIp(ip));
+            }
+            next.run(request).await
+        }
+    }))
+}
+
+fn resolve(peer: IpAddr, headers: &HeaderMap, proxies: &[IpNet]) -> IpAddr {
+    let trusted = |ip: IpAddr| proxies.iter().any(|net| net.contains(ip));
+    let mut client = peer;
+    for hop in forwarded_for(headers).iter().rev() {
+        if !trusted(client) {
+            break;
+        }
+        // An obfuscated or invalid hop ends what can be verified
+        let Some(ip) = hop else {
+            break;
+        };
+        client = *ip;
+    }
+    client
+}
+
+// The addresses a request was forwarded for, first hop first
+fn forwarded_for(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let values = |name: &str| -> Vec<String> {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|entry| entry.trim().to_string())
+            .collect()
+    };
+    let forwarded = values("forwarded");
+    if !forwarded.is_empty() {
+        return forwarded
+            .iter()
+            .map(|element| {
+                element.split(';').find_map(|pair| {
+                    let (key, value) = pair.trim().split_once('=')?;
+                    key.eq_ignore_ascii_case("for").then(|| parse_node(value.trim_matches('"')))?
+                })
+            })
+            .collect();
+    }
+    values("x-forwarded-for").iter().map(|hop| parse_node(hop)).collect()
+}
+
+// `1.2.3.4`, `1.2.3.4:80`, `[::1]` or `[::1]:80`
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip.to_canonical());
+    }
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split(']').next()?.parse::<IpAddr>().ok().map(|ip| ip.to_canonical());
+    }
+    node.parse::<SocketAddr>().ok().map(|addr| addr.ip().to_canonical())
+}
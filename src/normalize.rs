@@ -0,0 +1,194 @@
+//! Path normalization: trailing slashes, repeated slashes and letter case.
+//!
+//! ```ignore
+//! Server::new().path_normalization(
+//!     PathNormalization::new()
+//!         .trailing_slash(TrailingSlash::Rewrite)
+//!         .case_insensitive(true),
+//! );
+//! ```
+//!
+//! Without `Server::path_normalization`, paths are matched exactly: `/users`
+//! and `/users/` are different routes. With it, a request whose path matches no
+//! route is corrected when a route matches it with the trailing slash added or
+//! removed (unless the policy is `TrailingSlash::Strict`), with repeated slashes
+//! merged (`//users///7` as `/users/7`) and, when `case_insensitive` is on, with
+//! the route's static segments matched regardless of case. Placeholder values
+//! are passed on as sent.
+//!
+//! Corrected requests are answered with a 308 redirect to the route's path
+//! under `TrailingSlash::Redirect`, the default, and otherwise served by the
+//! route as if they had asked for its path. Requests matching a route as sent,
+//! and requests no correction makes match, are left alone. Only routes
+//! registered with `#[http_method]`, `#[websocket]` and `#[sse]` are considered.
+
+use std::sync::Arc;
+
+use axum::extract::Request;
+use axum::http::Uri;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect};
+use axum::Router;
+
+/// What to do with a path that only matches a route with its trailing slash added or removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Leave it unmatched, as without normalization.
+    Strict,
+    /// Answer 308 Permanent Redirect to the route's path.
+    Redirect,
+    /// Serve it from the route, without a round trip.
+    Rewrite,
+}
+
+/// How request paths are corrected before routing; pass it to `Server::path_normalization`.
+///
+/// By default trailing slashes are redirected, repeated slashes are merged and
+/// matching is case sensitive.
+#[derive(Debug, Clone)]
+pub struct PathNormalization {
+    trailing_slash: TrailingSlash,
+    merge_slashes: bool,
+    case_insensitive: bool,
+}
+
+impl PathNormalization {
+    pub fn new() -> Self {
+        PathNormalization {
+            trailing_slash: TrailingSlash::Redirect,
+            merge_slashes: true,
+            case_insensitive: false,
+        }
+    }
+
+    /// The trailing slash policy, which also decides whether other corrections
+    /// are redirected (`Redirect`) or served in place.
+    pub fn trailing_slash(mut self, policy: TrailingSlash) -> Self {
+        self.trailing_slash = policy;
+        self
+    }
+
+    /// Treat runs of slashes as one (on by default).
+    pub fn merge_slashes(mut self, enabled: bool) -> Self {
+        self.merge_slashes = enabled;
+        self
+    }
+
+    /// Match the static segments of route paths regardless of ASCII case (off by default).
+    pub fn case_insensitive(mut self, enabled: bool) -> Self {
+        self.case_insensitive = enabled;
+        self
+    }
+}
+
+impl Default for PathNormalization {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Normalizer {
+    config: PathNormalization,
+    // Route paths in router syntax, split into segments
+    routes: Vec<Vec<String>>,
+}
+
+impl Normalizer {
+    // The route path `path` should be served as, if it needs correcting
+    fn correct(&self, path: &str) -> Option<String> {
+        if self.routes.iter().any(|route| matches(route, path, false).is_some()) {
+            return None;
+        }
+        let merged = if self.config.merge_slashes { merge_slashes(path) } else { path.to_string() };
+        let mut candidates = vec![merged.clone()];
+        if self.config.trailing_slash != TrailingSlash::Strict && merged != "/" {
+            match merged.strip_suffix('/') {
+                Some(stripped) => candidates.push(stripped.to_string()),
+                None => candidates.push(format!("{}/", merged)),
+            }
+        }
+        let cases: &[bool] = if self.config.case_insensitive { &[false, true] } else { &[false] };
+        candidates
+            .iter()
+            .flat_map(|candidate| cases.iter().map(move |ignore_case| (candidate, *ignore_case)))
+            .find_map(|(candidate, ignore_case)| {
+                self.routes.iter().find_map(|route| matches(route, candidate, ignore_case))
+            })
+            .filter(|corrected| corrected != path)
+    }
+}
+
+// `path` as `route` spells it, if `route` matches it
+fn matches(route: &[String], path: &str, ignore_case: bool) -> Option<String> {
+    let segments: Vec<&str> = path.split('/').collect();
+    let mut corrected = Vec::with_capacity(segments.len());
+    for (index, pattern) in route.iter().enumerate() {
+        if pattern.starts_with('*') {
+            let rest = segments.get(index..)?.join("/");
+            if rest.is_empty() {
+                return None;
+            }
+            corrected.push(rest);
+            return Some(corrected.join("/"));
+        }
+        let segment = *segments.get(index)?;
+        if pattern.starts_with(':') {
+            if segment.is_empty() {
+                return None;
+            }
+            corrected.push(segment.to_string());
+        } else if pattern == segment || (ignore_case && pattern.eq_ignore_ascii_case(segment)) {
+            corrected.push(pattern.clone());
+        } else {
+            return None;
+        }
+    }
+    (route.len() == segments.len()).then(|| corrected.join("/"))
+}
+
+fn merge_slashes(path: &str) -> String {
+    let mut merged = String::with_capacity(path.len());
+    for c in path.chars() {
+        if !(c == '/' && merged.ends_with('/')) {
+            merged.push(c);
+        }
+    }
+    merged
+}
+
+// Correct the paths of requests to `router` for the route paths `routes`
+pub(crate) fn layer(router: Router, config: PathNormalization, routes: Vec<String>) -> Router {
+    let normalizer = Arc::new(Normalizer {
+        config,
+        routes: routes
+            .iter()
+            .map(|route| route.split('/').map(str::to_string).collect())
+            .collect(),
+    });
+    // Wrapping the whole router, so the corrected path is the one routed
+    let service = axum::middleware::from_fn(move |mut request: Request, next: Next| {
+        let normalizer = normalizer.clone();
+        async move {
+            let Some(path) = normalizer.correct(request.uri().path()) else {
+                return next.run(request).await;
+            };
+            let path_and_query = match request.uri().query() {
+                Some(query) => format!("{}?{}", path, query),
+                None => path,
+            };
+            if normalizer.config.trailing_slash == TrailingSlash::Redirect {
+                return Redirect::permanent(&path_and_query).into_response();
+            }
+            let mut parts = request.uri().clone().into_parts();
+            match path_and_query.parse() {
+                Ok(path_and_query) => parts.path_and_query = Some(path_and_query),
+                Err(_) => return next.run(request).await,
+            }
+            if let Ok(uri) = Uri::from_parts(parts) {
+                *request.uri_mut() = uri;
+            }
+            next.run(request).await
+        }
+    });
+    Router::new().fallback_service(tower::Layer::layer(&service, router))
+}
@@ -0,0 +1,470 @@
+//! OpenAPI 3.1 document generation from `#[http_method]` registrations.
+//!
+//! Schemas are derived from the declared parameter types: primitives, `String`,
+//! `Option<T>` and `Vec<T>` map to their JSON Schema equivalents, and any other
+//! type is described as an object titled with the type name. Successful
+//! responses are described from the declared return type: `Json<T>` or a bare
+//! `T` is sent as `T` when that is an object and in the envelope's `data` field
+//! otherwise, and `ApiResponse<T>` as the envelope around `T`. Handlers returning
+//! `Value` or `HandlerResponse` get the envelope with untyped `data`.
+
+use std::collections::HashSet;
+
+use axum::response::Html;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde_json::{json, Map, Value};
+
+use crate::context::AppState;
+use crate::routes::registered;
+use crate::{ParamInfo, ParamSource, RouteKind, RouteRegistration};
+
+/// Settings for the generated document and the endpoints serving it.
+#[derive(Debug, Clone)]
+pub struct OpenApiConfig {
+    title: String,
+    version: String,
+    spec_path: String,
+    swagger_ui_path: Option<String>,
+    registry: Option<String>,
+}
+
+impl OpenApiConfig {
+    /// Document `info` for an API; the spec is served at `/openapi.json`.
+    pub fn new(title: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            version: version.into(),
+            spec_path: "/openapi.json".to_string(),
+            swagger_ui_path: None,
+            registry: None,
+        }
+    }
+
+    /// Serve the spec at `path` instead of `/openapi.json`.
+    pub fn spec_path(mut self, path: impl Into<String>) -> Self {
+        self.spec_path = path.into();
+        self
+    }
+
+    /// Also serve a Swagger UI page at `path` (e.g. `/docs`).
+    pub fn swagger_ui(mut self, path: impl Into<String>) -> Self {
+        self.swagger_ui_path = Some(path.into());
+        self
+    }
+
+    /// Describe the routes of the named registry instead of the default one's.
+    /// A server serves the document of its own registry, see `Server::from_registry`.
+    pub fn registry(mut self, name: impl Into<String>) -> Self {
+        self.registry = Some(name.into());
+        self
+    }
+}
+
+/// Build the OpenAPI document for the routes of the configured registry,
+/// collected via inventory.
+pub fn spec(config: &OpenApiConfig) -> Value {
+    let mut paths = Map::new();
+    // Only REST handlers are described; WebSocket and SSE routes are left out
+    let mut routes: Vec<&RouteRegistration> = registered(config.registry.as_deref())
+        .filter(|registration| matches!(registration.handler, RouteKind::Http(_)))
+        .collect();
+    routes.sort_by_key(|registration| (registration.path, registration.method));
+    // A handler registered under several methods or paths needs an operation id per registration
+    let mut taken = HashSet::new();
+    for registration in routes {
+        let entry = paths
+            .entry(openapi_path(registration.path))
+            .or_insert_with(|| Value::Object(Map::new()));
+        let mut operation = operation(registration);
+        let mut id = registration.handler_name.to_string();
+        let mut suffix = 2;
+        while !taken.insert(id.clone()) {
+            id = format!("{}_{}", registration.handler_name, suffix);
+            suffix += 1;
+        }
+        operation["operationId"] = json!(id);
+        entry[registration.method.to_lowercase()] = operation;
+    }
+
+    json!({
+        "openapi": "3.1.0",
+        "info": { "title": config.title, "version": config.version },
+        "paths": paths,
+    })
+}
+
+pub(crate) fn mount(router: Router<AppState>, mut config: OpenApiConfig, registry: Option<&str>) -> Router<AppState> {
+    config.registry = registry.map(str::to_string);
+    let document = spec(&config);
+    let mut router = router.route(&config.spec_path, get(move || async move { Json(document) }));
+    if let Some(ui_path) = &config.swagger_ui_path {
+        let page = swagger_ui_page(&config.title, &config.spec_path);
+        router = router.route(ui_path, get(move || async move { Html(page) }));
+    }
+    router
+}
+
+fn operation(registration: &RouteRegistration) -> Value {
+    let mut parameters = Vec::new();
+    let mut documented_placeholders = Vec::new();
+    let mut request_body = None;
+
+    for param in registration.params {
+        match param.source {
+            ParamSource::PathParam => {
+                documented_placeholders.push(param.name);
+                parameters.push(described(param, json!({
+                    "name": param.name,
+                    "in": "path",
+                    "required": true,
+                    "schema": schema_for(param.type_name),
+                })));
+            }
+            ParamSource::Query if !is_untyped(param.type_name) => {
+                parameters.push(described(param, json!({
+                    "name": param.name,
+                    "in": "query",
+                    "required": !is_optional(param.type_name),
+                    "style": "form",
+                    "explode": true,
+                    "schema": schema_for(param.type_name),
+                })));
+            }
+            ParamSource::Body if !is_untyped(param.type_name) || accepts_body(registration.method) => {
+                let schema = schema_for(param.type_name);
+                request_body = Some(described(param, json!({
+                    "required": !is_optional(param.type_name) && !is_untyped(param.type_name),
+                    "content": {
+                        "application/json": { "schema": schema },
+                        "application/x-www-form-urlencoded": { "schema": schema },
+                    },
+                })));
+            }
+            ParamSource::Pagination => {
+                for (name, description) in [
+                    ("page", "Page number, from 1"),
+                    ("per_page", "Items per page"),
+                    ("cursor", "Where the page starts, from a previous page's `next_cursor`"),
+                ] {
+                    let schema = match name {
+                        "cursor" => json!({ "type": "string" }),
+                        _ => json!({ "type": "integer", "minimum": 1 }),
+                    };
+                    parameters.push(json!({
+                        "name": name,
+                        "in": "query",
+                        "required": false,
+                        "description": description,
+                        "schema": schema,
+                    }));
+                }
+            }
+            ParamSource::QuerySpec => {
+                let allowed = registration.options.query;
+                for field in allowed.filter {
+                    parameters.push(json!({
+                        "name": format!("filter[{}]", field),
+                        "in": "query",
+                        "required": false,
+                        "schema": { "type": "string" },
+                    }));
+                }
+                let lists = [
+                    ("sort", allowed.sort, "Fields to sort by, `-` first for descending order"),
+                    ("fields", allowed.fields, "Fields to return"),
+                ];
+                for (name, fields, description) in lists {
+                    if fields.is_empty() {
+                        continue;
+                    }
+                    parameters.push(json!({
+                        "name": name,
+                        "in": "query",
+                        "required": false,
+                        "description": format!("{}, comma-separated: {}", description, fields.join(", ")),
+                        "schema": { "type": "string" },
+                    }));
+                }
+            }
+            ParamSource::RawBody => {
+                request_body = Some(described(param, json!({
+                    "required": true,
+                    "content": {
+                        "application/octet-stream": { "schema": { "type": "string", "format": "binary" } },
+                    },
+                })));
+            }
+            _ => {}
+        }
+    }
+
+    // Every placeholder must be documented, even when taken as a whole `path` value
+    for placeholder in placeholders(registration.path) {
+        if !documented_placeholders.contains(&placeholder) {
+            parameters.push(json!({
+                "name": placeholder,
+                "in": "path",
+                "required": true,
+                "schema": { "type": "string" },
+            }));
+        }
+    }
+
+    let mut responses = Map::new();
+    let status = success_status(registration);
+    let mut success = match response_schema(registration.response) {
+        Some(schema) if status != 204 => json!({
+            "description": "Successful response",
+            "content": { "application/json": { "schema": schema } },
+        }),
+        _ => json!({ "description": "Successful response" }),
+    };
+    if generic_argument(ok_type(registration.response).unwrap_or(registration.response).trim(), "Created").is_some() {
+        success["headers"] = json!({
+            "Location": { "description": "The created resource", "schema": { "type": "string" } },
+        });
+    }
+    responses.insert(status.to_string(), success);
+    if registration
+        .params
+        .iter()
+        .any(|param| {
+            !matches!(
+                param.source,
+                ParamSource::State
+                    | ParamSource::Context
+                    | ParamSource::Identity
+                    | ParamSource::Session
+                    | ParamSource::RawBody
+                    | ParamSource::Transaction
+                    | ParamSource::Tenant
+                    | ParamSource::Locale
+                    | ParamSource::Extractor
+                    | ParamSource::Service
+            )
+                && !is_untyped(param.type_name)
+        })
+    {
+        responses.insert(
+            "400".to_string(),
+            json!({ "description": "Invalid path, query or body parameters" }),
+        );
+    }
+    if registration.options.auth.is_some() {
+        responses.insert("401".to_string(), json!({ "description": "Authentication required" }));
+    }
+    if registration.options.if_match {
+        responses.insert("412".to_string(), json!({ "description": "The resource has changed since it was read" }));
+        responses.insert("428".to_string(), json!({ "description": "`If-Match` or `If-Unmodified-Since` required" }));
+    }
+
+    let mut operation = json!({
+        "operationId": registration.handler_name,
+        "parameters": parameters,
+        "responses": responses,
+    });
+    // The doc comment's first paragraph is the summary, and the whole of it the description
+    if !registration.doc.is_empty() {
+        let summary = registration.doc.split("\n\n").next().unwrap_or_default();
+        operation["summary"] = json!(summary.split_whitespace().collect::<Vec<_>>().join(" "));
+        if summary.len() < registration.doc.len() {
+            operation["description"] = json!(registration.doc);
+        }
+    }
+    if let Some(tag) = registration.options.tag {
+        operation["tags"] = json!([tag]);
+    }
+    if let Some(body) = request_body {
+        operation["requestBody"] = body;
+    }
+    if registration.options.deprecated {
+        operation["deprecated"] = json!(true);
+    }
+    if let Some(date) = registration.options.sunset {
+        operation["x-sunset"] = json!(date);
+    }
+    if let Some(successor) = registration.options.successor {
+        operation["x-successor"] = json!(successor);
+    }
+    operation
+}
+
+// `/users/:id` and `/files/*rest` become `/users/{id}` and `/files/{rest}`
+// `object` with the parameter's doc comment as its description, if it has one
+fn described(param: &ParamInfo, mut object: Value) -> Value {
+    if let Some(description) = param.description {
+        object["description"] = json!(description);
+    }
+    object
+}
+
+pub(crate) fn openapi_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.strip_prefix(':').or_else(|| segment.strip_prefix('*')) {
+            Some(name) => format!("{{{}}}", name),
+            None => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub(crate) fn placeholders(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/')
+        .filter_map(|segment| segment.strip_prefix(':').or_else(|| segment.strip_prefix('*')))
+}
+
+pub(crate) fn accepts_body(method: &str) -> bool {
+    matches!(method, "POST" | "PUT" | "PATCH")
+}
+
+pub(crate) fn is_untyped(type_name: &str) -> bool {
+    matches!(type_name, "Value" | "serde_json::Value")
+}
+
+pub(crate) fn is_optional(type_name: &str) -> bool {
+    type_name.starts_with("Option<")
+}
+
+/// JSON Schema for a Rust type name as written in the handler signature.
+pub fn schema_for(type_name: &str) -> Value {
+    let type_name = type_name.trim();
+    if let Some(inner) = generic_argument(type_name, "Option") {
+        return schema_for(inner);
+    }
+    if let Some(inner) = generic_argument(type_name, "Vec") {
+        return json!({ "type": "array", "items": schema_for(inner) });
+    }
+    match type_name.rsplit("::").next().unwrap_or(type_name) {
+        "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64" | "i128" | "isize" => {
+            json!({ "type": "integer" })
+        }
+        "f32" | "f64" => json!({ "type": "number" }),
+        "bool" => json!({ "type": "boolean" }),
+        "String" | "&str" | "char" => json!({ "type": "string" }),
+        "Value" => json!({}),
+        name => json!({ "type": "object", "title": name }),
+    }
+}
+
+// `inner` for `Wrapper<inner>`, also accepting a path prefix such as `std::vec::Vec`
+pub(crate) fn generic_argument<'a>(type_name: &'a str, wrapper: &str) -> Option<&'a str> {
+    let open = type_name.find('<')?;
+    let outer = type_name[..open].rsplit("::").next()?;
+    (outer == wrapper && type_name.ends_with('>')).then(|| &type_name[open + 1..type_name.len() - 1])
+}
+
+// The status of a route's successful responses: its `status` option, else the
+// one its return type implies
+pub(crate) fn success_status(registration: &RouteRegistration) -> u16 {
+    if let Some(status) = registration.options.status {
+        return status;
+    }
+    let type_name = ok_type(registration.response).unwrap_or(registration.response).trim();
+    if generic_argument(type_name, "Created").is_some() {
+        return 201;
+    }
+    if generic_argument(type_name, "Accepted").is_some() {
+        return 202;
+    }
+    match type_name.rsplit("::").next() {
+        Some("NoContent") => 204,
+        _ => 200,
+    }
+}
+
+// The JSON body a handler returning `type_name` answers with on success; `None`
+// for streamed bodies and pages, whose content is not JSON
+pub(crate) fn response_schema(type_name: &str) -> Option<Value> {
+    let type_name = ok_type(type_name).unwrap_or(type_name).trim();
+    if let Some(inner) = ["Json", "Created", "Accepted"]
+        .into_iter()
+        .find_map(|wrapper| generic_argument(type_name, wrapper))
+    {
+        return Some(body_schema(inner));
+    }
+    if let Some(inner) = generic_argument(type_name, "ApiResponse") {
+        return Some(envelope_around(schema_for(inner)));
+    }
+    if generic_argument(type_name, "Template").is_some() {
+        return None;
+    }
+    if let Some(inner) = generic_argument(type_name, "Paginated") {
+        return Some(json!({
+            "type": "object",
+            "properties": {
+                "items": { "type": "array", "items": schema_for(inner) },
+                "page": { "type": "integer" },
+                "per_page": { "type": "integer" },
+                "total": { "type": "integer" },
+                "next_cursor": { "type": "string" },
+            },
+        }));
+    }
+    match type_name.rsplit("::").next().unwrap_or(type_name) {
+        "StreamingResponse" | "Template" | "NoContent" => None,
+        "Value" | "HandlerResponse" | "FerroxError" | "()" => Some(envelope_schema()),
+        name if name.starts_with("impl") => Some(envelope_schema()),
+        _ => Some(body_schema(type_name)),
+    }
+}
+
+// `T` for `Result<T, E>`
+fn ok_type(type_name: &str) -> Option<&str> {
+    let arguments = generic_argument(type_name.trim(), "Result")?;
+    let mut depth = 0;
+    for (index, c) in arguments.char_indices() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => return Some(&arguments[..index]),
+            _ => {}
+        }
+    }
+    Some(arguments)
+}
+
+// A serialized `type_name`: objects are sent unchanged, other values in the envelope
+fn body_schema(type_name: &str) -> Value {
+    let schema = schema_for(type_name);
+    if schema["type"] == "object" {
+        schema
+    } else {
+        envelope_around(schema)
+    }
+}
+
+fn envelope_schema() -> Value {
+    envelope_around(json!({}))
+}
+
+fn envelope_around(data: Value) -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "success": { "type": "boolean" },
+            "data": data,
+            "message": { "type": "string" },
+        },
+    })
+}
+
+fn swagger_ui_page(title: &str, spec_path: &str) -> String {
+    format!(
+        r##"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>{title}</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.ui = SwaggerUIBundle({{ url: "{spec_path}", dom_id: "#swagger-ui" }});
+  </script>
+</body>
+</html>"##
+    )
+}
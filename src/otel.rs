@@ -0,0 +1,202 @@
+//! OpenTelemetry server spans for every request, exported over OTLP.
+//!
+//! ```ignore
+//! Server::new().opentelemetry(
+//!     OtelConfig::new("users-api")
+//!         .endpoint("http://otel-collector:4317")
+//!         .sample_ratio(0.1),
+//! );
+//! ```
+//!
+//! Each request gets a `SpanKind::Server` span named after its method and
+//! route template (`GET /users/:id`), continuing the trace in an incoming W3C
+//! `traceparent` header. The span records the `http.request.method`,
+//! `http.route`, `url.path`, `user_agent.original` and
+//! `http.response.status_code` attributes, and its duration is the request
+//! latency; 5xx responses mark it as an error. Handlers find the span's
+//! `opentelemetry::Context` in `RequestContext::extensions`, to propagate it
+//! to outgoing calls.
+//!
+//! Spans are exported by a batch processor to the OTLP/gRPC endpoint
+//! (`http://localhost:4317` by default) and flushed when the server stops.
+//! The provider is also installed globally, so spans the application creates
+//! with `opentelemetry::global::tracer` join the same traces. Requires the
+//! `otel` feature.
+
+use axum::extract::{MatchedPath, Request};
+use axum::http::header::USER_AGENT;
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::Router;
+use opentelemetry::propagation::{Extractor, Injector, TextMapPropagator};
+use opentelemetry::trace::{FutureExt, SpanKind, Status, TraceContextExt, Tracer, TracerProvider as _};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, TracerProvider};
+use opentelemetry_sdk::Resource;
+
+use crate::context::AppState;
+
+const DEFAULT_ENDPOINT: &str = "http://localhost:4317";
+
+/// Where and how request spans are exported; pass it to `Server::opentelemetry`.
+#[derive(Debug, Clone)]
+pub struct OtelConfig {
+    service_name: String,
+    endpoint: String,
+    sample_ratio: f64,
+    provider: Option<TracerProvider>,
+}
+
+impl OtelConfig {
+    /// Export spans for the service `service_name`, the `service.name` resource attribute.
+    pub fn new(service_name: &str) -> Self {
+        OtelConfig {
+            service_name: service_name.to_string(),
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+            sample_ratio: 1.0,
+            provider: None,
+        }
+    }
+
+    /// The OTLP/gRPC collector endpoint (defaults to `http://localhost:4317`).
+    pub fn endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.to_string();
+        self
+    }
+
+    /// Fraction of new traces to record, between 0 and 1 (all by default).
+    ///
+    /// Requests continuing a trace follow the caller's sampling decision.
+    pub fn sample_ratio(mut self, ratio: f64) -> Self {
+        self.sample_ratio = ratio.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Record spans with `provider` instead of building an OTLP exporter.
+    ///
+    /// The endpoint and sample ratio are then left to the provider, and it is
+    /// not installed globally.
+    pub fn tracer_provider(mut self, provider: TracerProvider) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    // The configured provider, or a new batch OTLP exporter installed globally
+    fn into_provider(self) -> Result<TracerProvider, opentelemetry::trace::TraceError> {
+        if let Some(provider) = self.provider {
+            return Ok(provider);
+        }
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(self.endpoint)
+            .build()?;
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+            .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(self.sample_ratio))))
+            .with_resource(Resource::default().merge(&Resource::new([KeyValue::new(
+                "service.name",
+                self.service_name,
+            )])))
+            .build();
+        opentelemetry::global::set_tracer_provider(provider.clone());
+        Ok(provider)
+    }
+}
+
+// Trace every request to `router`; without a working exporter the router is unchanged
+pub(crate) fn layer(router: Router<AppState>, config: OtelConfig) -> Router<AppState> {
+    let provider = match config.into_provider() {
+        Ok(provider) => provider,
+        Err(err) => {
+            tracing::error!("OpenTelemetry export disabled: {}", err);
+            return router;
+        }
+    };
+    // The tracer keeps the provider alive with the router, so spans are flushed when it drops
+    let tracer = provider.tracer("ferrox");
+    router.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let tracer = tracer.clone();
+        async move {
+            let parent = TraceContextPropagator::new().extract(&Headers(request.headers()));
+            let method = request.method().to_string();
+            let route = request.extensions().get::<MatchedPath>().map(|matched| matched.as_str().to_string());
+            let name = match &route {
+                Some(route) => format!("{} {}", method, route),
+                None => method.clone(),
+            };
+            let mut attributes = vec![
+                KeyValue::new("http.request.method", method),
+                KeyValue::new("url.path", request.uri().path().to_string()),
+            ];
+            if let Some(route) = route {
+                attributes.push(KeyValue::new("http.route", route));
+            }
+            if let Some(agent) = request.headers().get(USER_AGENT).and_then(|value| value.to_str().ok()) {
+                attributes.push(KeyValue::new("user_agent.original", agent.to_string()));
+            }
+            let span = tracer
+                .span_builder(name)
+                .with_kind(SpanKind::Server)
+                .with_attributes(attributes)
+                .start_with_context(&tracer, &parent);
+            let cx = parent.with_span(span);
+
+            let mut request = request;
+            request.extensions_mut().insert(cx.clone());
+            let response: Response = next.run(request).with_context(cx.clone()).await;
+
+            let span = cx.span();
+            let status = response.status();
+            span.set_attribute(KeyValue::new("http.response.status_code", i64::from(status.as_u16())));
+            if status.is_server_error() {
+                span.set_status(Status::error(status.to_string()));
+            }
+            span.end();
+            response
+        }
+    }))
+}
+
+// Reads `traceparent` and `tracestate` from request headers
+struct Headers<'a>(&'a HeaderMap);
+
+impl Extractor for Headers<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+// Write the current span context into outgoing `traceparent` and `tracestate`
+// headers; returns whether there was a span to propagate
+#[cfg_attr(not(feature = "client"), allow(dead_code))]
+pub(crate) fn inject(headers: &mut HeaderMap) -> bool {
+    let cx = Context::current();
+    if !cx.span().span_context().is_valid() {
+        return false;
+    }
+    TraceContextPropagator::new().inject_context(&cx, &mut HeadersMut(headers));
+    true
+}
+
+struct HeadersMut<'a>(&'a mut HeaderMap);
+
+impl Injector for HeadersMut<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(key), HeaderValue::try_from(value)) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+/// The span context of the request `extensions` belong to, e.g. `RequestContext::extensions`,
+/// or the current context outside a traced request.
+pub fn context(extensions: &axum::http::Extensions) -> Context {
+    extensions.get::<Context>().cloned().unwrap_or_else(Context::current)
+}
@@ -0,0 +1,216 @@
+//! Paginated list endpoints: `page`/`per_page` or `cursor` query parameters in,
+//! a standard list body and `Link` header out.
+//!
+//! ```ignore
+//! Server::new().pagination(PaginationConfig::new().default_per_page(25).max_per_page(200));
+//!
+//! #[http_method(GET, "/users")]
+//! async fn list_users(page: Pagination) -> Result<Paginated<User>, FerroxError> {
+//!     let (users, total) = db.users(page.offset(), page.limit()).await?;
+//!     Ok(Paginated::new(users, &page).total(total))
+//! }
+//! ```
+//!
+//! `Pagination` handler parameters, whatever their name, read `?page=2&per_page=50`
+//! (pages count from 1) or `?cursor=...&per_page=50`. A missing `per_page` takes
+//! the configured default (20) and a larger one than the maximum (100) is
+//! lowered to it; values that are not positive integers answer 400.
+//!
+//! A `Paginated<T>` answers `{"items", "page", "per_page", "total", "next_cursor"}`
+//! with an RFC 5988 `Link` header: `next` (to `next_cursor` when one is set),
+//! `prev`, `first`, and `last` when the total is known. Without a total, a full
+//! page is taken to mean there is a next one.
+
+use axum::http::header::LINK;
+use axum::http::{StatusCode, Uri};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::context::RequestContext;
+use crate::error::FerroxError;
+use crate::response::{serialization_failure, HandlerResponse, IntoHandlerResponse};
+
+/// Page sizes for `Pagination` parameters; pass it to `Server::pagination`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaginationConfig {
+    default_per_page: u64,
+    max_per_page: u64,
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        PaginationConfig {
+            default_per_page: 20,
+            max_per_page: 100,
+        }
+    }
+}
+
+impl PaginationConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Items per page when the request does not say.
+    pub fn default_per_page(mut self, per_page: u64) -> Self {
+        self.default_per_page = per_page.max(1);
+        self
+    }
+
+    /// The most items per page a request can ask for.
+    pub fn max_per_page(mut self, per_page: u64) -> Self {
+        self.max_per_page = per_page.max(1);
+        self
+    }
+}
+
+/// The page a request asked for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pagination {
+    /// From 1; always 1 when `cursor` is set.
+    pub page: u64,
+    pub per_page: u64,
+    pub cursor: Option<String>,
+    uri: Uri,
+}
+
+impl Pagination {
+    /// Items to skip, for `OFFSET`.
+    pub fn offset(&self) -> u64 {
+        (self.page - 1).saturating_mul(self.per_page)
+    }
+
+    /// Items to return, for `LIMIT`.
+    pub fn limit(&self) -> u64 {
+        self.per_page
+    }
+
+    // `uri` with the pagination parameters replaced by `params`
+    fn link(&self, params: &[(&str, String)]) -> String {
+        let mut query: Vec<(String, String)> = serde_urlencoded::from_str(self.uri.query().unwrap_or_default())
+            .unwrap_or_default();
+        query.retain(|(name, _)| !matches!(name.as_str(), "page" | "per_page" | "cursor"));
+        query.extend(params.iter().map(|(name, value)| (name.to_string(), value.clone())));
+        let query = serde_urlencoded::to_string(&query).unwrap_or_default();
+        format!("{}?{}", self.uri.path(), query)
+    }
+}
+
+/// Read the request's `Pagination`, under the limits given to `Server::pagination`.
+pub fn pagination(ctx: &RequestContext) -> Result<Pagination, FerroxError> {
+    let config = ctx.state::<PaginationConfig>().unwrap_or_default();
+    let positive = |name: &str| -> Result<Option<u64>, FerroxError> {
+        let Some(value) = ctx.query.get(name) else {
+            return Ok(None);
+        };
+        match value.as_str().and_then(|value| value.parse::<u64>().ok()) {
+            Some(number) if number > 0 => Ok(Some(number)),
+            _ => Err(FerroxError::BadRequest(format!("Query parameter `{}` must be a positive integer", name))),
+        }
+    };
+    let per_page = positive("per_page")?.unwrap_or(config.default_per_page).min(config.max_per_page);
+    let cursor = ctx.query.get("cursor").and_then(Value::as_str).filter(|cursor| !cursor.is_empty());
+    let page = match cursor {
+        Some(_) => 1,
+        None => positive("page")?.unwrap_or(1),
+    };
+    Ok(Pagination {
+        page,
+        per_page,
+        cursor: cursor.map(str::to_string),
+        uri: ctx.uri.clone(),
+    })
+}
+
+/// One page of a list, sent with its position and `Link` header.
+#[derive(Debug, Clone)]
+pub struct Paginated<T> {
+    items: Vec<T>,
+    pagination: Pagination,
+    total: Option<u64>,
+    next_cursor: Option<String>,
+}
+
+impl<T: Serialize> Paginated<T> {
+    pub fn new(items: Vec<T>, pagination: &Pagination) -> Self {
+        Paginated {
+            items,
+            pagination: pagination.clone(),
+            total: None,
+            next_cursor: None,
+        }
+    }
+
+    /// How many items the whole list holds.
+    pub fn total(mut self, total: u64) -> Self {
+        self.total = Some(total);
+        self
+    }
+
+    /// Where the next page starts, for cursor pagination; `None` on the last page.
+    pub fn next_cursor(mut self, cursor: Option<String>) -> Self {
+        self.next_cursor = cursor;
+        self
+    }
+
+    fn links(&self) -> Vec<(String, &'static str)> {
+        let Pagination { page, per_page, .. } = self.pagination;
+        let per_page_param = ("per_page", per_page.to_string());
+        let at = |page: u64| self.pagination.link(&[("page", page.to_string()), per_page_param.clone()]);
+        let mut links = Vec::new();
+        if let Some(cursor) = &self.next_cursor {
+            links.push((self.pagination.link(&[("cursor", cursor.clone()), per_page_param.clone()]), "next"));
+            return links;
+        }
+        if self.pagination.cursor.is_some() {
+            return links;
+        }
+        let last = self.total.map(|total| total.div_ceil(per_page).max(1));
+        let has_next = match last {
+            Some(last) => page < last,
+            None => self.items.len() as u64 >= per_page,
+        };
+        if has_next {
+            links.push((at(page + 1), "next"));
+        }
+        if page > 1 {
+            links.push((at(page - 1), "prev"));
+        }
+        links.push((at(1), "first"));
+        if let Some(last) = last {
+            links.push((at(last), "last"));
+        }
+        links
+    }
+}
+
+impl<T: Serialize> IntoHandlerResponse for Paginated<T> {
+    fn into_handler_response(self) -> HandlerResponse {
+        let items = match serde_json::to_value(&self.items) {
+            Ok(items) => items,
+            Err(err) => {
+                tracing::error!("Failed to serialize response: {}", err);
+                return serialization_failure().into_handler_response();
+            }
+        };
+        let body = json!({
+            "items": items,
+            "page": self.pagination.cursor.is_none().then_some(self.pagination.page),
+            "per_page": self.pagination.per_page,
+            "total": self.total,
+            "next_cursor": self.next_cursor,
+        });
+        let link = self
+            .links()
+            .iter()
+            .map(|(url, rel)| format!("<{}>; rel=\"{}\"", url, rel))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let response = HandlerResponse::new(StatusCode::OK, body);
+        if link.is_empty() {
+            response
+        } else {
+            response.with_header(LINK, link)
+        }
+    }
+}
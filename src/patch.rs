@@ -0,0 +1,195 @@
+//! PATCH bodies as JSON Merge Patch (RFC 7386) or JSON Patch (RFC 6902).
+//!
+//! ```ignore
+//! #[http_method(PATCH, "/users/:id")]
+//! async fn update_user(id: u64, body: Patch) -> Result<Json<User>, FerroxError> {
+//!     let user = db.user(id).await?;
+//!     let user = body.apply_validated(&user)?;
+//!     Ok(Json(db.save(user).await?))
+//! }
+//! ```
+//!
+//! A `Patch` body parameter takes an object as a merge patch, where `null`
+//! removes a field and nested objects merge, and an array as JSON Patch
+//! operations (`add`, `remove`, `replace`, `move`, `copy` and `test`, with JSON
+//! Pointer paths). `apply` patches a copy of a `Serialize + DeserializeOwned`
+//! value, so the original is untouched when it fails: operations that do not
+//! fit the document, such as a missing path or a failed `test`, answer 409, and
+//! a result that no longer deserializes answers 422. `apply_validated` also
+//! runs the type's `Validate` rules on the result.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::FerroxError;
+use crate::validate::Validate;
+
+/// A patch document: a merge patch or a list of JSON Patch operations.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Patch {
+    Json(Vec<Operation>),
+    Merge(Value),
+}
+
+impl<'de> Deserialize<'de> for Patch {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Decided by the document's shape, so a malformed operation list is not taken as a merge patch
+        match Value::deserialize(deserializer)? {
+            Value::Array(operations) => serde_json::from_value(Value::Array(operations))
+                .map(Patch::Json)
+                .map_err(serde::de::Error::custom),
+            Value::Object(fields) => Ok(Patch::Merge(Value::Object(fields))),
+            _ => Err(serde::de::Error::custom(
+                "expected a merge patch object or an array of JSON Patch operations",
+            )),
+        }
+    }
+}
+
+/// One JSON Patch operation; paths are JSON Pointers such as `/tags/0`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Operation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+impl Patch {
+    /// `target` with the patch applied.
+    pub fn apply<T: Serialize + DeserializeOwned>(&self, target: &T) -> Result<T, FerroxError> {
+        let mut document = serde_json::to_value(target)
+            .map_err(|err| FerroxError::Internal(format!("Failed to serialize the patch target: {}", err)))?;
+        self.apply_to_value(&mut document)?;
+        serde_json::from_value(document)
+            .map_err(|err| FerroxError::UnprocessableEntity(format!("Patched value is invalid: {}", err)))
+    }
+
+    /// `target` with the patch applied, checked with its `Validate` rules.
+    pub fn apply_validated<T: Serialize + DeserializeOwned + Validate>(&self, target: &T) -> Result<T, FerroxError> {
+        let patched = self.apply(target)?;
+        patched.validate().map_err(FerroxError::Validation)?;
+        Ok(patched)
+    }
+
+    /// Apply the patch to a JSON document, leaving it unchanged on failure.
+    pub fn apply_to_value(&self, document: &mut Value) -> Result<(), FerroxError> {
+        match self {
+            Patch::Merge(patch) => {
+                merge(document, patch);
+                Ok(())
+            }
+            Patch::Json(operations) => {
+                let mut patched = document.clone();
+                for (index, operation) in operations.iter().enumerate() {
+                    apply_operation(&mut patched, operation)
+                        .map_err(|message| FerroxError::Conflict(format!("Patch operation {} failed: {}", index, message)))?;
+                }
+                *document = patched;
+                Ok(())
+            }
+        }
+    }
+}
+
+// RFC 7386: objects merge field by field, `null` removes, anything else replaces
+fn merge(target: &mut Value, patch: &Value) {
+    let Value::Object(fields) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(serde_json::Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (name, value) in fields {
+            if value.is_null() {
+                target.remove(name);
+            } else {
+                merge(target.entry(name.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+fn apply_operation(document: &mut Value, operation: &Operation) -> Result<(), String> {
+    match operation {
+        Operation::Add { path, value } => add(document, path, value.clone()),
+        Operation::Remove { path } => remove(document, path).map(drop),
+        Operation::Replace { path, value } => {
+            let target = document.pointer_mut(path).ok_or_else(|| format!("`{}` does not exist", path))?;
+            *target = value.clone();
+            Ok(())
+        }
+        Operation::Move { from, path } => {
+            if path.starts_with(&format!("{}/", from)) {
+                return Err(format!("cannot move `{}` into itself", from));
+            }
+            let value = remove(document, from)?;
+            add(document, path, value)
+        }
+        Operation::Copy { from, path } => {
+            let value = document.pointer(from).cloned().ok_or_else(|| format!("`{}` does not exist", from))?;
+            add(document, path, value)
+        }
+        Operation::Test { path, value } => match document.pointer(path) {
+            Some(current) if current == value => Ok(()),
+            Some(_) => Err(format!("`{}` does not hold the tested value", path)),
+            None => Err(format!("`{}` does not exist", path)),
+        },
+    }
+}
+
+// The parent of `path` and its last token, unescaped
+fn split(path: &str) -> Result<(&str, String), String> {
+    let (parent, last) = path
+        .rsplit_once('/')
+        .ok_or_else(|| format!("`{}` is not a JSON Pointer", path))?;
+    Ok((parent, last.replace("~1", "/").replace("~0", "~")))
+}
+
+fn add(document: &mut Value, path: &str, value: Value) -> Result<(), String> {
+    if path.is_empty() {
+        *document = value;
+        return Ok(());
+    }
+    let (parent, token) = split(path)?;
+    match document.pointer_mut(parent) {
+        Some(Value::Object(fields)) => {
+            fields.insert(token, value);
+            Ok(())
+        }
+        Some(Value::Array(items)) => {
+            let index = match token.as_str() {
+                "-" => items.len(),
+                token => token
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|index| *index <= items.len())
+                    .ok_or_else(|| format!("`{}` is not an index of the array", path))?,
+            };
+            items.insert(index, value);
+            Ok(())
+        }
+        Some(_) => Err(format!("`{}` is not inside an object or array", path)),
+        None => Err(format!("`{}` does not exist", parent)),
+    }
+}
+
+fn remove(document: &mut Value, path: &str) -> Result<Value, String> {
+    let (parent, token) = split(path)?;
+    let removed = match document.pointer_mut(parent) {
+        Some(Value::Object(fields)) => fields.remove(&token),
+        Some(Value::Array(items)) => match token.parse::<usize>() {
+            Ok(index) if index < items.len() => Some(items.remove(index)),
+            _ => None,
+        },
+        _ => None,
+    };
+    removed.ok_or_else(|| format!("`{}` does not exist", path))
+}
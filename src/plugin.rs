@@ -0,0 +1,42 @@
+//! Plugins: features bundled by other crates, installed with one call.
+//!
+//! ```ignore
+//! pub struct Admin { pub token: String }
+//!
+//! impl Plugin for Admin {
+//!     fn name(&self) -> &str {
+//!         "ferrox-admin"
+//!     }
+//!
+//!     fn install(self, server: Server) -> Server {
+//!         server
+//!             .with_state(AdminToken(self.token))
+//!             .route("GET", "/admin/stats", RouteHandler::from_async(stats))
+//!             .layer(AdminAudit::new())
+//!             .on_startup(|| async { load_dashboards().await })
+//!     }
+//! }
+//!
+//! Server::new().plugin(Admin { token });
+//! ```
+//!
+//! A plugin is given the server being built and returns it with its features
+//! added: routes with `Server::route` or the `Server::dynamic_routes` handle
+//! (`#[http_method]` routes in a library are served whether or not its plugin
+//! is installed), state with `with_state`, middleware with `layer`, and startup
+//! or shutdown tasks with `on_startup` and `on_shutdown`. Plugins are installed
+//! in the order given, and one is only installed once per name.
+
+use crate::Server;
+
+/// A bundle of routes, middleware, state and tasks for a [`Server`].
+pub trait Plugin: 'static {
+    /// Name in logs, also telling whether the plugin is already installed;
+    /// the type's name by default.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Add the plugin's features to `server`.
+    fn install(self, server: Server) -> Server;
+}
@@ -0,0 +1,265 @@
+//! Routes forwarding requests to an upstream HTTP service.
+//!
+//! ```ignore
+//! Server::new()
+//!     .proxy("/legacy/*path", "http://old-service:8080")
+//!     .reverse_proxy(
+//!         Proxy::new("/billing/*path", "http://billing:9000/api")
+//!             .set_header("x-gateway", "ferrox")
+//!             .remove_header("cookie")
+//!             .retries(2),
+//!     );
+//! ```
+//!
+//! The part of the request path after the route's static prefix is appended to
+//! the upstream URL, with the query string: `/legacy/users/7?page=2` goes to
+//! `http://old-service:8080/users/7?page=2`. Bodies are streamed both ways.
+//! Hop-by-hop headers are dropped, `Host` is set to the upstream's unless
+//! `preserve_host` is on, and `X-Forwarded-For`, `X-Forwarded-Host` and
+//! `X-Forwarded-Proto` tell the upstream about the client.
+//!
+//! Proxy routes run through the server's layers like registered routes. An
+//! upstream that cannot be reached is answered with 502. With `retries`,
+//! requests without a body are retried on connection errors and on 502, 503
+//! and 504 from the upstream; requests with a body are streamed once, so they
+//! are never retried. Upstreams are plain `http://` URLs.
+
+use std::sync::Arc;
+
+use axum::body::{Body, HttpBody};
+use axum::extract::Request;
+use axum::http::header::{CONNECTION, HOST};
+use axum::http::uri::{Authority, PathAndQuery, Scheme};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+
+use crate::context::AppState;
+use crate::error::FerroxError;
+use crate::network;
+
+// Headers that describe one connection, not the message, per RFC 9110
+const HOP_BY_HOP: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// A route forwarding to an upstream service; register it with `Server::reverse_proxy`.
+#[derive(Debug, Clone)]
+pub struct Proxy {
+    path: String,
+    authority: Authority,
+    base_path: String,
+    set_headers: Vec<(HeaderName, HeaderValue)>,
+    remove_headers: Vec<HeaderName>,
+    preserve_host: bool,
+    retries: u32,
+}
+
+impl Proxy {
+    /// Forward requests matching `path` (a route path such as `/legacy/*path`) to `upstream`.
+    ///
+    /// # Panics
+    ///
+    /// If `upstream` is not an `http://` URL.
+    pub fn new(path: impl Into<String>, upstream: &str) -> Self {
+        let uri: Uri = upstream
+            .parse()
+            .unwrap_or_else(|_| panic!("invalid upstream URL `{}`", upstream));
+        let authority = match (uri.scheme(), uri.authority()) {
+            (Some(scheme), Some(authority)) if *scheme == Scheme::HTTP => authority.clone(),
+            _ => panic!("upstream URL `{}` must start with http://", upstream),
+        };
+        Proxy {
+            path: path.into(),
+            authority,
+            base_path: uri.path().trim_end_matches('/').to_string(),
+            set_headers: Vec::new(),
+            remove_headers: Vec::new(),
+            preserve_host: false,
+            retries: 0,
+        }
+    }
+
+    /// Send `name: value` upstream, replacing the client's value; can be called repeatedly.
+    ///
+    /// An invalid name or value is logged and skipped.
+    pub fn set_header(mut self, name: &str, value: &str) -> Self {
+        match (HeaderName::try_from(name), HeaderValue::from_str(value)) {
+            (Ok(name), Ok(value)) => self.set_headers.push((name, value)),
+            _ => tracing::warn!("Ignoring invalid proxy header {:?}: {:?}", name, value),
+        }
+        self
+    }
+
+    /// Drop the client's `name` header before forwarding; can be called repeatedly.
+    pub fn remove_header(mut self, name: &str) -> Self {
+        match HeaderName::try_from(name) {
+            Ok(name) => self.remove_headers.push(name),
+            Err(_) => tracing::warn!("Ignoring invalid proxy header name {:?}", name),
+        }
+        self
+    }
+
+    /// Forward the client's `Host` header instead of the upstream's (off by default).
+    pub fn preserve_host(mut self, enabled: bool) -> Self {
+        self.preserve_host = enabled;
+        self
+    }
+
+    /// How many times to retry requests without a body when the upstream fails (0 by default).
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    // The route path up to its first parameter, which the forwarded path follows
+    fn prefix(&self) -> &str {
+        let end = self.path.find(['*', ':']).unwrap_or(self.path.len());
+        self.path[..end].trim_end_matches('/')
+    }
+
+    fn upstream_uri(&self, uri: &Uri) -> Result<Uri, FerroxError> {
+        let rest = uri.path().strip_prefix(self.prefix()).unwrap_or(uri.path());
+        let mut path = format!("{}{}", self.base_path, rest);
+        if path.is_empty() {
+            path.push('/');
+        }
+        if let Some(query) = uri.query() {
+            path = format!("{}?{}", path, query);
+        }
+        let path_and_query = PathAndQuery::try_from(path)
+            .map_err(|_| FerroxError::BadRequest("Invalid request path".to_string()))?;
+        Uri::builder()
+            .scheme(Scheme::HTTP)
+            .authority(self.authority.clone())
+            .path_and_query(path_and_query)
+            .build()
+            .map_err(|err| FerroxError::Internal(err.to_string()))
+    }
+}
+
+type HttpClient = Client<HttpConnector, Body>;
+
+// Mount `proxy` on `router`, for every method
+pub(crate) fn mount(router: Router<AppState>, proxy: Proxy) -> Router<AppState> {
+    let client: HttpClient = Client::builder(TokioExecutor::new()).build_http();
+    let path = proxy.path.clone();
+    let proxy = Arc::new(proxy);
+    router.route(
+        &path,
+        axum::routing::any(move |request: Request| {
+            let proxy = proxy.clone();
+            let client = client.clone();
+            async move {
+                match forward(&proxy, &client, request).await {
+                    Ok(response) => response,
+                    Err(err) => err.into_response(),
+                }
+            }
+        }),
+    )
+}
+
+async fn forward(proxy: &Proxy, client: &HttpClient, request: Request) -> Result<Response, FerroxError> {
+    let (mut parts, body) = request.into_parts();
+    parts.uri = proxy.upstream_uri(&parts.uri)?;
+    rewrite_headers(proxy, &mut parts);
+
+    // Only requests without a body can be sent again
+    let retryable = body.size_hint().exact() == Some(0);
+    let attempts = if retryable { proxy.retries + 1 } else { 1 };
+    let mut body = Some(body);
+    for attempt in 1..=attempts {
+        let body = body.take().unwrap_or_else(Body::empty);
+        let upstream = Request::from_parts(clone_parts(&parts), body);
+        match client.request(upstream).await {
+            Ok(response)
+                if attempt < attempts
+                    && matches!(
+                        response.status(),
+                        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+                    ) =>
+            {
+                tracing::warn!("Upstream {} answered {}, retrying", parts.uri, response.status());
+            }
+            Ok(response) => {
+                let (mut parts, body) = response.into_parts();
+                strip_hop_by_hop(&mut parts.headers);
+                return Ok(Response::from_parts(parts, Body::new(body)));
+            }
+            Err(err) => {
+                tracing::warn!("Upstream {} failed (attempt {} of {}): {}", parts.uri, attempt, attempts, err);
+            }
+        }
+    }
+    Err(FerroxError::Status(StatusCode::BAD_GATEWAY, "Upstream unavailable".to_string()))
+}
+
+fn rewrite_headers(proxy: &Proxy, parts: &mut axum::http::request::Parts) {
+    let client_ip = network::client_ip(&parts.extensions);
+    let headers = &mut parts.headers;
+    let original_host = headers.get(HOST).cloned();
+    strip_hop_by_hop(headers);
+
+    if let Some(ip) = client_ip {
+        let forwarded_for = match headers.get("x-forwarded-for").and_then(|value| value.to_str().ok()) {
+            Some(previous) => format!("{}, {}", previous, ip),
+            None => ip.to_string(),
+        };
+        if let Ok(value) = HeaderValue::from_str(&forwarded_for) {
+            headers.insert("x-forwarded-for", value);
+        }
+    }
+    if let Some(host) = &original_host {
+        headers.insert("x-forwarded-host", host.clone());
+    }
+    if !headers.contains_key("x-forwarded-proto") {
+        headers.insert("x-forwarded-proto", HeaderValue::from_static("http"));
+    }
+    if (!proxy.preserve_host || original_host.is_none())
+        && let Ok(host) = HeaderValue::from_str(proxy.authority.as_str())
+    {
+        headers.insert(HOST, host);
+    }
+    for name in &proxy.remove_headers {
+        headers.remove(name);
+    }
+    for (name, value) in &proxy.set_headers {
+        headers.insert(name.clone(), value.clone());
+    }
+}
+
+fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    // Headers named by `Connection` are hop-by-hop too
+    let named: Vec<HeaderName> = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::try_from(name.trim()).ok())
+        .collect();
+    for name in named.iter().chain(&HOP_BY_HOP.map(HeaderName::from_static)) {
+        headers.remove(name);
+    }
+}
+
+fn clone_parts(parts: &axum::http::request::Parts) -> axum::http::request::Parts {
+    let mut request = Request::builder()
+        .method(parts.method.clone())
+        .uri(parts.uri.clone())
+        .version(axum::http::Version::HTTP_11)
+        .body(())
+        .expect("parts of a valid request");
+    *request.headers_mut() = parts.headers.clone();
+    request.into_parts().0
+}
@@ -0,0 +1,283 @@
+//! Filtering, sorting and sparse fieldsets for list endpoints.
+//!
+//! ```ignore
+//! #[http_method(GET, "/users", filter = ["status", "age"], sort = ["created_at", "name"], fields = ["id", "name", "email"])]
+//! async fn list_users(spec: QuerySpec) -> Result<Value, FerroxError> {
+//!     let users = db.users(&spec.filters, &spec.sort).await?;
+//!     Ok(spec.select(json!(users)))
+//! }
+//! ```
+//!
+//! `QuerySpec` handler parameters, whatever their name, read:
+//!
+//! - `filter[status]=active`, or `filter[age][gte]=18` with one of the operators
+//!   `eq`, `ne`, `lt`, `lte`, `gt`, `gte` and `in` (a comma-separated list)
+//! - `sort=-created_at,name`: fields in order, `-` sorting one in descending order
+//! - `fields=id,name`: the fields the client wants back
+//!
+//! Each names a field from the route's `filter`, `sort` and `fields` options;
+//! any other field, or an unknown operator, answers 400 naming the fields
+//! allowed, so a route only filters and sorts on what it indexes. A fieldset
+//! may name nested fields, as in `fields=id,address.city`; allowing `address`
+//! allows all of its fields.
+//!
+//! With `Server::sparse_fieldsets(true)`, routes with a `fields` option have
+//! their results narrowed to the requested fieldset without taking a
+//! `QuerySpec`: a bare result, the `data` of an `ApiResponse`, or the `items`
+//! of a `Paginated` list. Fields outside the allowlist answer 400 before the
+//! handler runs, and errors are sent whole.
+
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::context::RequestContext;
+use crate::error::FerroxError;
+
+/// The fields a route lets clients filter, sort and select, from its
+/// `filter`, `sort` and `fields` options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Allowlist {
+    pub filter: &'static [&'static str],
+    pub sort: &'static [&'static str],
+    pub fields: &'static [&'static str],
+}
+
+impl Allowlist {
+    pub const NONE: Allowlist = Allowlist {
+        filter: &[],
+        sort: &[],
+        fields: &[],
+    };
+}
+
+/// How a filter compares a field with its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    In,
+}
+
+impl FilterOp {
+    fn parse(op: &str) -> Option<Self> {
+        Some(match op {
+            "eq" => FilterOp::Eq,
+            "ne" => FilterOp::Ne,
+            "lt" => FilterOp::Lt,
+            "lte" => FilterOp::Lte,
+            "gt" => FilterOp::Gt,
+            "gte" => FilterOp::Gte,
+            "in" => FilterOp::In,
+            _ => return None,
+        })
+    }
+}
+
+/// One `filter[field][op]=value` condition.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Filter {
+    pub field: String,
+    pub op: FilterOp,
+    pub value: String,
+}
+
+impl Filter {
+    /// The values of an `in` filter, or the single value of the others.
+    pub fn values(&self) -> Vec<&str> {
+        match self.op {
+            FilterOp::In => self.value.split(',').map(str::trim).filter(|value| !value.is_empty()).collect(),
+            _ => vec![self.value.as_str()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Direction::Asc => "ASC",
+            Direction::Desc => "DESC",
+        })
+    }
+}
+
+/// One `sort` key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SortKey {
+    pub field: String,
+    pub direction: Direction,
+}
+
+/// The filters, sort order and fieldset a request asked for.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct QuerySpec {
+    pub filters: Vec<Filter>,
+    pub sort: Vec<SortKey>,
+    /// `None` when the request did not narrow the fields.
+    pub fields: Option<Vec<String>>,
+}
+
+impl QuerySpec {
+    /// The value of the first equality filter on `field`.
+    pub fn filter(&self, field: &str) -> Option<&str> {
+        self.filters
+            .iter()
+            .find(|filter| filter.field == field && filter.op == FilterOp::Eq)
+            .map(|filter| filter.value.as_str())
+    }
+
+    /// Keep only the requested fields of an object, or of each object in an
+    /// array; anything is returned unchanged when no fieldset was asked for.
+    /// A dotted field such as `address.city` keeps that field of a nested object.
+    pub fn select(&self, value: Value) -> Value {
+        match &self.fields {
+            Some(fields) => select(value, &fields.iter().map(String::as_str).collect::<Vec<_>>()),
+            None => value,
+        }
+    }
+}
+
+fn select(value: Value, fields: &[&str]) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.into_iter().map(|item| select(item, fields)).collect()),
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .filter_map(|(key, value)| {
+                    if fields.contains(&key.as_str()) {
+                        return Some((key, value));
+                    }
+                    let nested: Vec<&str> = fields
+                        .iter()
+                        .filter_map(|field| field.strip_prefix(key.as_str())?.strip_prefix('.'))
+                        .collect();
+                    (!nested.is_empty()).then(|| (key, select(value, &nested)))
+                })
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+// Narrow a handler's result to `fields` for `Server::sparse_fieldsets`: the
+// `data` of an `ApiResponse`, the `items` of a `Paginated` list, or the body itself
+pub(crate) fn prune(body: &mut Value, fields: &[String]) {
+    let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+    let target = match body {
+        Value::Object(object) if object.contains_key("success") && object.contains_key("data") => object.get_mut("data"),
+        Value::Object(object) if object.contains_key("per_page") && object.get("items").is_some_and(Value::is_array) => {
+            object.get_mut("items")
+        }
+        _ => Some(body),
+    };
+    if let Some(target) = target {
+        *target = select(target.take(), &fields);
+    }
+}
+
+// The `fields` a request asked for, checked against the route's allowlist;
+// `None` when it did not narrow them
+pub(crate) fn requested_fields(query: Option<&str>, allowed: &[&str]) -> Result<Option<Vec<String>>, FerroxError> {
+    let pairs: Vec<(String, String)> = serde_urlencoded::from_str(query.unwrap_or_default())
+        .map_err(|err| FerroxError::BadRequest(format!("Invalid query string: {}", err)))?;
+    let mut requested: Option<Vec<String>> = None;
+    for (_, value) in pairs.into_iter().filter(|(key, _)| key == "fields") {
+        let fields = requested.get_or_insert_with(Vec::new);
+        for field in value.split(',').map(str::trim).filter(|field| !field.is_empty()) {
+            fields.push(allow_field(field, allowed)?);
+        }
+    }
+    Ok(requested)
+}
+
+/// Read the request's `QuerySpec`, checked against the route's allowlist.
+pub fn query_spec(ctx: &RequestContext) -> Result<QuerySpec, FerroxError> {
+    let allowed = ctx.extensions.get::<Allowlist>().copied().unwrap_or(Allowlist::NONE);
+    let pairs: Vec<(String, String)> = serde_urlencoded::from_str(ctx.uri.query().unwrap_or_default())
+        .map_err(|err| FerroxError::BadRequest(format!("Invalid query string: {}", err)))?;
+    let mut spec = QuerySpec::default();
+    for (key, value) in pairs {
+        if let Some(rest) = key.strip_prefix("filter[") {
+            let (field, op) = match rest.split_once("][") {
+                Some((field, op)) => (field, op.strip_suffix(']')),
+                None => (rest.strip_suffix(']').unwrap_or(rest), Some("eq")),
+            };
+            let op = op
+                .and_then(FilterOp::parse)
+                .ok_or_else(|| {
+                    FerroxError::BadRequest(format!(
+                        "Unknown filter `{}`; the operators are eq, ne, lt, lte, gt, gte and in",
+                        key
+                    ))
+                })?;
+            spec.filters.push(Filter {
+                field: allow(field, allowed.filter, "filter on")?,
+                op,
+                value,
+            });
+            continue;
+        }
+        match key.as_str() {
+            "sort" => {
+                for key in value.split(',').map(str::trim).filter(|key| !key.is_empty()) {
+                    let (field, direction) = match key.strip_prefix('-') {
+                        Some(field) => (field, Direction::Desc),
+                        None => (key.strip_prefix('+').unwrap_or(key), Direction::Asc),
+                    };
+                    spec.sort.push(SortKey {
+                        field: allow(field, allowed.sort, "sort by")?,
+                        direction,
+                    });
+                }
+            }
+            "fields" => {
+                let fields = spec.fields.get_or_insert_with(Vec::new);
+                for field in value.split(',').map(str::trim).filter(|field| !field.is_empty()) {
+                    fields.push(allow_field(field, allowed.fields)?);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(spec)
+}
+
+// A field to select: one the route allows, or nested in one (`address.city` under `address`)
+fn allow_field(field: &str, allowed: &[&str]) -> Result<String, FerroxError> {
+    let nested = allowed
+        .iter()
+        .any(|parent| field.strip_prefix(parent).is_some_and(|rest| rest.starts_with('.')));
+    match nested {
+        true => Ok(field.to_string()),
+        false => allow(field, allowed, "select"),
+    }
+}
+
+fn allow(field: &str, allowed: &[&str], action: &str) -> Result<String, FerroxError> {
+    if allowed.contains(&field) {
+        return Ok(field.to_string());
+    }
+    let message = match allowed {
+        [] => format!("Cannot {} `{}`: this route allows none", action, field),
+        allowed => format!(
+            "Cannot {} `{}`; expected one of {}",
+            action,
+            field,
+            allowed.iter().map(|field| format!("`{}`", field)).collect::<Vec<_>>().join(", ")
+        ),
+    };
+    Err(FerroxError::BadRequest(message))
+}
@@ -0,0 +1,278 @@
+//! Metered access: routes declare what a request costs, and each client has a
+//! budget of cost units per window.
+//!
+//! ```ignore
+//! let quotas = Quotas::new(RateLimit::per_hour(1_000))
+//!     .role("pro", "100000/day".parse()?)
+//!     .principal("partner-7", RateLimit::per_hour(50_000));
+//! Server::new().jwt(verifier).quotas(quotas);
+//!
+//! #[http_method(POST, "/reports", auth = "jwt", cost = 25)]
+//! async fn report(body: ReportRequest) -> Result<Report, FerroxError> { ... }
+//!
+//! #[http_method(GET, "/usage", auth = "jwt")]
+//! fn usage(ctx: &RequestContext, quotas: State<Quotas>) -> Json<Usage> {
+//!     Json(quotas.usage_of(ctx))
+//! }
+//! ```
+//!
+//! Requests are charged to their principal, found as `Server::rbac` finds it
+//! (a recorded `Principal`, the JWT `sub`, or the session's `user_id`), or to
+//! the client IP address when there is none. A principal's budget is its own,
+//! set with `principal`, else the largest of its roles', else the default.
+//! Windows are aligned to the clock, so a daily budget resets at midnight UTC.
+//!
+//! A route with `cost = N` takes `N` units per request, after authentication,
+//! permissions and guards, so rejected requests are free; requests the server
+//! fails (5xx) are refunded. Once a budget would be exceeded, requests answer
+//! 429 with `Retry-After` until it resets. Every metered response carries
+//! `X-Quota-Limit`, `X-Quota-Remaining` and `X-Quota-Reset` (seconds until the
+//! window resets). Routes without a cost are not metered.
+//!
+//! Usage is counted in process memory; `usage`, `usage_all` and `reset` give
+//! access to it, e.g. for billing or a support tool.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::extract::Request;
+use axum::http::header::RETRY_AFTER;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::routing::MethodRouter;
+use serde::Serialize;
+
+use crate::context::{AppState, RequestContext};
+use crate::error::FerroxError;
+use crate::network;
+use crate::ratelimit::RateLimit;
+use crate::rbac::Rbac;
+
+/// Budgets of cost units per window, and what each client used of theirs;
+/// register them with `Server::quotas`.
+///
+/// Clones share the same accounts.
+#[derive(Clone)]
+pub struct Quotas {
+    default: RateLimit,
+    roles: HashMap<String, RateLimit>,
+    principals: HashMap<String, RateLimit>,
+    accounts: Arc<Mutex<HashMap<String, Account>>>,
+}
+
+/// What a client used of its budget in the current window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Usage {
+    /// Units used so far.
+    pub used: u32,
+    /// Units the budget allows per window.
+    pub limit: u32,
+    /// Units left.
+    pub remaining: u32,
+    /// Seconds until the window resets.
+    pub reset: u64,
+}
+
+// Units charged to one client in its current window
+struct Account {
+    window: u64,
+    used: u32,
+    budget: RateLimit,
+}
+
+impl Quotas {
+    /// Give every client `budget` units per window, e.g. `RateLimit::per_hour(1_000)`.
+    pub fn new(budget: RateLimit) -> Self {
+        Quotas {
+            default: budget,
+            roles: HashMap::new(),
+            principals: HashMap::new(),
+            accounts: Arc::default(),
+        }
+    }
+
+    /// Budget of principals holding `role`; one with several roles gets the largest.
+    pub fn role(mut self, role: &str, budget: RateLimit) -> Self {
+        self.roles.insert(role.to_string(), budget);
+        self
+    }
+
+    /// Budget of the principal `id`, in place of its roles'.
+    pub fn principal(mut self, id: &str, budget: RateLimit) -> Self {
+        self.principals.insert(id.to_string(), budget);
+        self
+    }
+
+    /// Usage of the account `key`: `principal:<id>` or `ip:<address>`.
+    pub fn usage(&self, key: &str) -> Option<Usage> {
+        let accounts = self.accounts.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let account = accounts.get(key)?;
+        Some(account.usage(now()))
+    }
+
+    /// Usage of every account charged in its current window, by key.
+    pub fn usage_all(&self) -> Vec<(String, Usage)> {
+        let accounts = self.accounts.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = now();
+        let mut usage: Vec<(String, Usage)> = accounts
+            .iter()
+            .filter(|(_, account)| account.window == window_of(now, account.budget))
+            .map(|(key, account)| (key.clone(), account.usage(now)))
+            .collect();
+        usage.sort_by(|(a, _), (b, _)| a.cmp(b));
+        usage
+    }
+
+    /// Usage of the client making the request, charged or not.
+    pub fn usage_of(&self, ctx: &RequestContext) -> Usage {
+        let rbac = ctx.state::<Rbac>().unwrap_or_default();
+        let (key, budget) = self.account_for(&ctx.extensions, &rbac);
+        self.usage(&key).unwrap_or_else(|| Account::new(now(), budget).usage(now()))
+    }
+
+    /// Give the account `key` its whole budget back; `false` if it has none in use.
+    pub fn reset(&self, key: &str) -> bool {
+        let mut accounts = self.accounts.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        accounts.remove(key).is_some()
+    }
+
+    // The account a request is charged to, and its budget
+    fn account_for(&self, extensions: &axum::http::Extensions, rbac: &Rbac) -> (String, RateLimit) {
+        let principal = rbac.find_principal(extensions);
+        if let Some(principal) = principal.as_ref().filter(|principal| principal.id.is_some()) {
+            let id = principal.id.as_deref().unwrap_or_default();
+            let budget = self.principals.get(id).copied().or_else(|| {
+                principal
+                    .roles
+                    .iter()
+                    .filter_map(|role| self.roles.get(role))
+                    .max_by(|a, b| per_second(a).total_cmp(&per_second(b)))
+                    .copied()
+            });
+            return (format!("principal:{}", id), budget.unwrap_or(self.default));
+        }
+        let key = match network::client_ip(extensions) {
+            Some(ip) => format!("ip:{}", ip),
+            None => "ip:unknown".to_string(),
+        };
+        (key, self.default)
+    }
+
+    // Take `cost` units from `key`'s budget, or say why not
+    fn charge(&self, key: &str, budget: RateLimit, cost: u32) -> Result<Usage, Usage> {
+        let now = now();
+        let mut accounts = self.accounts.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let account = accounts.entry(key.to_string()).or_insert_with(|| Account::new(now, budget));
+        account.roll(now, budget);
+        if account.used.saturating_add(cost) > budget.limit() {
+            return Err(account.usage(now));
+        }
+        account.used += cost;
+        Ok(account.usage(now))
+    }
+
+    // Give back `cost` units charged in the window that is still current
+    fn refund(&self, key: &str, charged: &Usage, cost: u32) -> Usage {
+        let now = now();
+        let mut accounts = self.accounts.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match accounts.get_mut(key) {
+            Some(account) if account.window == window_of(now, account.budget) => {
+                account.used = account.used.saturating_sub(cost);
+                account.usage(now)
+            }
+            _ => *charged,
+        }
+    }
+}
+
+impl Account {
+    fn new(now: Duration, budget: RateLimit) -> Self {
+        Account {
+            window: window_of(now, budget),
+            used: 0,
+            budget,
+        }
+    }
+
+    // Move to the current window, on the budget the client has now
+    fn roll(&mut self, now: Duration, budget: RateLimit) {
+        let window = window_of(now, budget);
+        if self.window != window || self.budget.window() != budget.window() {
+            self.used = 0;
+        }
+        self.window = window;
+        self.budget = budget;
+    }
+
+    fn usage(&self, now: Duration) -> Usage {
+        let window = self.budget.window().as_secs().max(1);
+        let used = if self.window == window_of(now, self.budget) { self.used } else { 0 };
+        let resets_at = (window_of(now, self.budget) + 1) * window;
+        Usage {
+            used,
+            limit: self.budget.limit(),
+            remaining: self.budget.limit().saturating_sub(used),
+            reset: resets_at.saturating_sub(now.as_secs()),
+        }
+    }
+}
+
+// Time since the Unix epoch
+fn now() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+// The window of `budget` containing `now`, counted from the Unix epoch
+fn window_of(now: Duration, budget: RateLimit) -> u64 {
+    now.as_secs() / budget.window().as_secs().max(1)
+}
+
+fn per_second(budget: &RateLimit) -> f64 {
+    f64::from(budget.limit()) / budget.window().as_secs_f64()
+}
+
+// Charge `cost` units for every request to a route; requests pass unmetered
+// when no quotas are configured
+pub(crate) fn meter_route(
+    route: MethodRouter<AppState>,
+    cost: u32,
+    quotas: Option<Quotas>,
+    rbac: Rbac,
+) -> MethodRouter<AppState> {
+    let Some(quotas) = quotas else {
+        tracing::error!("Routes declare a `cost` but no Server::quotas are set; they are not metered");
+        return route;
+    };
+    route.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let (quotas, rbac) = (quotas.clone(), rbac.clone());
+        async move {
+            let (key, budget) = quotas.account_for(request.extensions(), &rbac);
+            let charged = match quotas.charge(&key, budget, cost) {
+                Ok(usage) => usage,
+                Err(usage) => {
+                    let mut response =
+                        FerroxError::Status(StatusCode::TOO_MANY_REQUESTS, "Quota exceeded".to_string()).into_response();
+                    response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(usage.reset));
+                    add_headers(response.headers_mut(), &usage);
+                    return response;
+                }
+            };
+            let mut response = next.run(request).await;
+            let usage = if response.status().is_server_error() {
+                quotas.refund(&key, &charged, cost)
+            } else {
+                charged
+            };
+            add_headers(response.headers_mut(), &usage);
+            response
+        }
+    }))
+}
+
+fn add_headers(headers: &mut HeaderMap, usage: &Usage) {
+    headers.insert(HeaderName::from_static("x-quota-limit"), HeaderValue::from(usage.limit));
+    headers.insert(HeaderName::from_static("x-quota-remaining"), HeaderValue::from(usage.remaining));
+    headers.insert(HeaderName::from_static("x-quota-reset"), HeaderValue::from(usage.reset));
+}
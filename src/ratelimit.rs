@@ -0,0 +1,462 @@
+//! Request rate limiting.
+//!
+//! A [`RateLimiter`] counts requests per client (by IP address or API key holder) and
+//! answers 429 with `Retry-After` once a client exceeds its [`RateLimit`].
+//! Every response it passes also carries `X-RateLimit-Limit`,
+//! `X-RateLimit-Remaining` and `X-RateLimit-Reset` (seconds until the limit is
+//! fully available again) so clients can slow down before hitting it.
+//!
+//! Limits apply server-wide with `Server::rate_limit`, or per route with
+//! `#[http_method(..., rate_limit = "100/min")]`. When both apply, the route's
+//! headers are the ones reported.
+//!
+//! Counters live in process memory unless the limiter is given a
+//! [`RateLimitStore`], such as `ferrox::redis::RedisRateLimits`, so that every
+//! instance of the service counts against the same limits. Routes' own limits
+//! use the store of `Server::rate_limit`'s limiter. When the store fails, the
+//! error is logged and the request let through.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use axum::extract::Request;
+use axum::http::header::RETRY_AFTER;
+use axum::http::{request, HeaderMap, HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::MethodRouter;
+use axum::Router;
+
+use crate::auth::api_key::ApiKeyAuth;
+use crate::context::AppState;
+use crate::error::FerroxError;
+use crate::network;
+use crate::session::StoreFuture;
+
+/// At most `limit` requests per `window`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    limit: u32,
+    window: Duration,
+}
+
+impl RateLimit {
+    pub const fn new(limit: u32, window: Duration) -> Self {
+        RateLimit { limit, window }
+    }
+
+    pub const fn per_second(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(1))
+    }
+
+    pub const fn per_minute(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(60))
+    }
+
+    pub const fn per_hour(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(3600))
+    }
+
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+}
+
+/// Parses `"<count>/<unit>"` where unit is `s`/`sec`/`second`, `m`/`min`/`minute`,
+/// `h`/`hour` or `d`/`day`, e.g. `"100/min"`.
+impl FromStr for RateLimit {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid rate limit `{}`, expected e.g. \"100/min\"", text);
+        let (count, unit) = text.split_once('/').ok_or_else(invalid)?;
+        let limit: u32 = count.trim().parse().map_err(|_| invalid())?;
+        let window = match unit.trim() {
+            "s" | "sec" | "second" => 1,
+            "m" | "min" | "minute" => 60,
+            "h" | "hour" => 3600,
+            "d" | "day" => 86400,
+            _ => return Err(invalid()),
+        };
+        if limit == 0 {
+            return Err(invalid());
+        }
+        Ok(RateLimit::new(limit, Duration::from_secs(window)))
+    }
+}
+
+/// How requests are counted against a limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    /// A bucket of `limit` tokens refilled evenly over the window; allows bursts
+    /// up to the full limit.
+    #[default]
+    TokenBucket,
+    /// Requests in the current window plus a share of the previous window's,
+    /// weighted by how much of it still overlaps the last `window`.
+    SlidingWindow,
+}
+
+/// Which client a request is counted for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum KeyBy {
+    /// The client IP address, behind `Server::trusted_proxies` if any.
+    #[default]
+    Ip,
+    /// The holder of the API key the request presents, checked against
+    /// `Server::api_keys`; requests without a valid key count for their IP address.
+    ApiKey,
+    /// The value of a header set by one of `Server::trusted_proxies`, such as the
+    /// consumer id of a gateway that authenticated the client. Requests that did
+    /// not come through a trusted proxy, or without the header, count for their
+    /// IP address.
+    Header(HeaderName),
+}
+
+/// Names of the headers reporting a client's limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitHeaders {
+    pub limit: HeaderName,
+    pub remaining: HeaderName,
+    pub reset: HeaderName,
+}
+
+impl Default for RateLimitHeaders {
+    fn default() -> Self {
+        RateLimitHeaders {
+            limit: HeaderName::from_static("x-ratelimit-limit"),
+            remaining: HeaderName::from_static("x-ratelimit-remaining"),
+            reset: HeaderName::from_static("x-ratelimit-reset"),
+        }
+    }
+}
+
+/// Shared counters for rate limiters, e.g. in Redis.
+///
+/// `check` must count the request and decide on it atomically, so that
+/// instances racing for a client's last request cannot both get it.
+pub trait RateLimitStore: Send + Sync + 'static {
+    /// Count a request for `key` against `rate`, with the counters `algorithm` keeps.
+    fn check<'a>(&'a self, key: &'a str, rate: RateLimit, algorithm: Algorithm) -> StoreFuture<'a, Decision>;
+}
+
+/// Counts requests per client and rejects those over the limit.
+#[derive(Clone)]
+pub struct RateLimiter {
+    // Shared by clones, so a configuration reload reaches the router's copy
+    rate: Arc<RwLock<RateLimit>>,
+    algorithm: Algorithm,
+    key_by: KeyBy,
+    headers: Option<RateLimitHeaders>,
+    clients: Arc<Mutex<Clients>>,
+    store: Option<Arc<dyn RateLimitStore>>,
+    // `Server::api_keys`, which `KeyBy::ApiKey` checks keys against
+    api_keys: Option<ApiKeyAuth>,
+    // Set for a route's own limit, so its counters are apart from the server's in the store
+    scope: String,
+}
+
+impl RateLimiter {
+    pub fn new(rate: RateLimit) -> Self {
+        RateLimiter {
+            rate: Arc::new(RwLock::new(rate)),
+            algorithm: Algorithm::default(),
+            key_by: KeyBy::default(),
+            headers: Some(RateLimitHeaders::default()),
+            clients: Arc::default(),
+            store: None,
+            api_keys: None,
+            scope: String::new(),
+        }
+    }
+
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn key_by(mut self, key_by: KeyBy) -> Self {
+        self.key_by = key_by;
+        self
+    }
+
+    /// Header names used to report the limit, or `None` to send only `Retry-After` on 429.
+    pub fn headers(mut self, headers: Option<RateLimitHeaders>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Keep the counters in `store` instead of process memory.
+    pub fn store(mut self, store: impl RateLimitStore) -> Self {
+        self.store = Some(Arc::new(store));
+        self
+    }
+
+    // A limiter for the route `scope` using this limiter's settings but its own counters
+    pub(crate) fn for_route(&self, rate: RateLimit, scope: String) -> Self {
+        RateLimiter {
+            rate: Arc::new(RwLock::new(rate)),
+            clients: Arc::default(),
+            scope,
+            ..self.clone()
+        }
+    }
+
+    fn rate(&self) -> RateLimit {
+        *self.rate.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // Count requests against `rate` from now on, in this limiter and its clones
+    pub(crate) fn set_rate(&self, rate: RateLimit) {
+        *self.rate.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = rate;
+    }
+
+    // Check keys against `api_keys` for `KeyBy::ApiKey`
+    pub(crate) fn verify_keys(&mut self, api_keys: Option<ApiKeyAuth>) {
+        if self.key_by == KeyBy::ApiKey && api_keys.is_none() {
+            tracing::error!("Rate limits keyed by API key need Server::api_keys; counting by IP address instead");
+        }
+        self.api_keys = api_keys;
+    }
+
+    // The authenticated client the request counts for, else its IP address. Only
+    // verified identities get counters of their own, so clients cannot escape
+    // their limit, or grow the counters, by sending made-up keys
+    async fn client_key(&self, request: &request::Parts) -> String {
+        match (&self.key_by, &self.api_keys) {
+            (KeyBy::ApiKey, Some(api_keys)) => {
+                if let Some(holder) = api_keys.holder(&request.headers, &request.uri).await {
+                    return format!("key:{}", holder.name);
+                }
+            }
+            (KeyBy::Header(name), _) if network::via_trusted_proxy(&request.extensions) => {
+                if let Some(value) = request.headers.get(name) {
+                    return format!("header:{}", String::from_utf8_lossy(value.as_bytes()));
+                }
+            }
+            _ => {}
+        }
+        match network::client_ip(&request.extensions) {
+            Some(ip) => format!("ip:{}", ip),
+            None => "ip:unknown".to_string(),
+        }
+    }
+
+    async fn check(&self, key: String, rate: RateLimit) -> Decision {
+        let Some(store) = &self.store else {
+            let mut clients = self.clients.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            return clients.check(key, rate, self.algorithm, Instant::now());
+        };
+        let key = match self.scope.as_str() {
+            "" => key,
+            scope => format!("{}|{}", scope, key),
+        };
+        match store.check(&key, rate, self.algorithm).await {
+            Ok(decision) => decision,
+            Err(err) => {
+                tracing::error!("Rate limit store failed, letting the request through: {}", err);
+                Decision {
+                    allowed: true,
+                    remaining: rate.limit,
+                    reset: Duration::ZERO,
+                    retry_after: Duration::ZERO,
+                }
+            }
+        }
+    }
+
+    // Count the request for its client, then run it or answer 429
+    async fn handle(self, request: Request, next: Next) -> Response {
+        let (parts, body) = request.into_parts();
+        let key = self.client_key(&parts).await;
+        self.handle_as(key, Request::from_parts(parts, body), next).await
+    }
+
+    // Count the request for `key`, then run it or answer 429
+    pub(crate) async fn handle_as(self, key: String, request: Request, next: Next) -> Response {
+        let rate = self.rate();
+        let decision = self.check(key, rate).await;
+        let mut response = if decision.allowed {
+            next.run(request).await
+        } else {
+            let mut response = FerroxError::Status(
+                axum::http::StatusCode::TOO_MANY_REQUESTS,
+                "Rate limit exceeded".to_string(),
+            )
+            .into_response();
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(whole_seconds(decision.retry_after)));
+            response
+        };
+        if let Some(names) = &self.headers {
+            add_headers(response.headers_mut(), names, rate.limit, &decision);
+        }
+        response
+    }
+}
+
+// Limit one route with its own counters
+pub(crate) fn limit_route(route: MethodRouter<AppState>, limiter: RateLimiter) -> MethodRouter<AppState> {
+    route.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        limiter.clone().handle(request, next)
+    }))
+}
+
+// Limit every request to the router, sharing one set of counters
+pub(crate) fn limit_router(router: Router<AppState>, limiter: RateLimiter) -> Router<AppState> {
+    router.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        limiter.clone().handle(request, next)
+    }))
+}
+
+// An inner (per-route) limiter's report takes precedence over an outer one's
+fn add_headers(headers: &mut HeaderMap, names: &RateLimitHeaders, limit: u32, decision: &Decision) {
+    if headers.contains_key(&names.limit) {
+        return;
+    }
+    headers.insert(names.limit.clone(), HeaderValue::from(limit));
+    headers.insert(names.remaining.clone(), HeaderValue::from(decision.remaining));
+    headers.insert(names.reset.clone(), HeaderValue::from(whole_seconds(decision.reset)));
+}
+
+fn whole_seconds(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+/// Whether a request is within its limit, and what is left of it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub remaining: u32,
+    /// Until the full limit is available again.
+    pub reset: Duration,
+    /// Until the next request would be allowed.
+    pub retry_after: Duration,
+}
+
+impl Decision {
+    /// The decision for a token bucket left with `tokens`, once the request was counted if `allowed`.
+    pub fn token_bucket(allowed: bool, tokens: f64, rate: RateLimit) -> Self {
+        let limit = f64::from(rate.limit);
+        let per_second = limit / rate.window.as_secs_f64();
+        Decision {
+            allowed,
+            remaining: tokens.floor() as u32,
+            reset: Duration::from_secs_f64((limit - tokens) / per_second),
+            retry_after: Duration::from_secs_f64(((1.0 - tokens) / per_second).max(0.0)),
+        }
+    }
+
+    /// The decision for a sliding window `into_window` into its current window,
+    /// with `current` requests counted there, this one included if `allowed`,
+    /// and `previous` in the window before.
+    pub fn sliding_window(allowed: bool, current: u32, previous: u32, into_window: Duration, rate: RateLimit) -> Self {
+        let limit = f64::from(rate.limit);
+        let window = rate.window.as_secs_f64();
+        let into_window = into_window.as_secs_f64();
+        let weight = 1.0 - into_window / window;
+        let count = f64::from(previous) * weight + f64::from(current);
+        let until_window_end = (window - into_window).max(0.0);
+        // The previous window's share decays linearly until the current one ends
+        let retry_after = if allowed {
+            0.0
+        } else if previous > 0 {
+            ((count + 1.0 - limit) / (f64::from(previous) / window)).min(until_window_end)
+        } else {
+            until_window_end
+        };
+        Decision {
+            allowed,
+            remaining: (limit - count).max(0.0).floor() as u32,
+            reset: Duration::from_secs_f64(until_window_end + if current > 0 { window } else { 0.0 }),
+            retry_after: Duration::from_secs_f64(retry_after),
+        }
+    }
+}
+
+enum Counter {
+    Bucket { tokens: f64, updated: Instant },
+    Window { started: Instant, current: u32, previous: u32 },
+}
+
+#[derive(Default)]
+struct Clients {
+    counters: HashMap<String, Counter>,
+    swept: Option<Instant>,
+}
+
+impl Clients {
+    fn check(&mut self, key: String, rate: RateLimit, algorithm: Algorithm, now: Instant) -> Decision {
+        self.sweep(rate.window, now);
+        let limit = f64::from(rate.limit);
+        let window = rate.window.as_secs_f64();
+        let counter = self.counters.entry(key).or_insert_with(|| match algorithm {
+            Algorithm::TokenBucket => Counter::Bucket {
+                tokens: limit,
+                updated: now,
+            },
+            Algorithm::SlidingWindow => Counter::Window {
+                started: now,
+                current: 0,
+                previous: 0,
+            },
+        });
+
+        match counter {
+            Counter::Bucket { tokens, updated } => {
+                let per_second = limit / window;
+                *tokens = (*tokens + now.duration_since(*updated).as_secs_f64() * per_second).min(limit);
+                *updated = now;
+                let allowed = *tokens >= 1.0;
+                if allowed {
+                    *tokens -= 1.0;
+                }
+                Decision::token_bucket(allowed, *tokens, rate)
+            }
+            Counter::Window {
+                started,
+                current,
+                previous,
+            } => {
+                // Roll the fixed windows forward
+                let elapsed = now.duration_since(*started);
+                if elapsed >= rate.window * 2 {
+                    *started = now;
+                    *previous = 0;
+                    *current = 0;
+                } else if elapsed >= rate.window {
+                    *started += rate.window;
+                    *previous = *current;
+                    *current = 0;
+                }
+                let into_window = now.duration_since(*started);
+                let weight = 1.0 - into_window.as_secs_f64() / window;
+                let count = f64::from(*previous) * weight + f64::from(*current);
+                let allowed = count + 1.0 <= limit;
+                if allowed {
+                    *current += 1;
+                }
+                Decision::sliding_window(allowed, *current, *previous, into_window, rate)
+            }
+        }
+    }
+
+    // Forget clients idle for two windows, at most once per window
+    fn sweep(&mut self, window: Duration, now: Instant) {
+        if self.swept.is_some_and(|at| now.duration_since(at) < window) {
+            return;
+        }
+        self.swept = Some(now);
+        self.counters.retain(|_, counter| match counter {
+            Counter::Bucket { updated, .. } => now.duration_since(*updated) < window * 2,
+            Counter::Window { started, .. } => now.duration_since(*started) < window * 2,
+        });
+    }
+}
@@ -0,0 +1,240 @@
+//! Role-based access control for routes declared with `permission = "..."`.
+//!
+//! ```ignore
+//! let policy = Rbac::new()
+//!     .role("viewer", ["users:read", "reports:read"])
+//!     .role("editor", ["users:write"])
+//!     .inherits("editor", "viewer")
+//!     .role("admin", ["*"]);
+//! Server::new().jwt(verifier).rbac(policy);
+//!
+//! #[http_method(PUT, "/users/:id", auth = "jwt", permission = "users:write")]
+//! async fn update_user(id: u64, body: UserUpdate) -> Result<Value, FerroxError> { ... }
+//! ```
+//!
+//! Roles and the permissions they grant are declared once, on the [`Rbac`]
+//! policy given to `Server::rbac`. A permission is granted by its exact name,
+//! by a `users:*` pattern covering everything under `users:`, or by `*`. Before
+//! the handler runs, after the route's authenticator, the request's
+//! [`Principal`] is looked up: one recorded in the request extensions by an
+//! authenticator or middleware, else the `roles` claim of a JWT (`sub` being its
+//! id), else the `roles` value of the session (`user_id` being its id). A
+//! request with no principal answers 401; one whose roles lack the permission
+//! answers 403, with the permission and roles in `data`.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use axum::extract::Request;
+use axum::http::Extensions;
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::routing::MethodRouter;
+use serde::Serialize;
+
+use crate::context::{AppState, RequestContext};
+use crate::error::FerroxError;
+
+/// Who made a request, and the roles they hold.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Principal {
+    pub id: Option<String>,
+    pub roles: Vec<String>,
+}
+
+impl Principal {
+    pub fn new<I, T>(id: impl Into<String>, roles: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        Principal {
+            id: Some(id.into()),
+            roles: roles.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// Why a request was refused, sent in the `data` field of the 403 response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Denial {
+    /// The permission the route requires.
+    pub permission: String,
+    /// The roles the principal holds, none of which grant it.
+    pub roles: Vec<String>,
+}
+
+/// The roles of an application and the permissions each grants.
+///
+/// Clones share the same policy.
+#[derive(Clone, Default)]
+pub struct Rbac {
+    inner: Arc<Policy>,
+}
+
+#[derive(Clone)]
+struct Policy {
+    grants: HashMap<String, Vec<String>>,
+    parents: HashMap<String, Vec<String>>,
+    roles_claim: String,
+    session_key: String,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            grants: HashMap::new(),
+            parents: HashMap::new(),
+            roles_claim: "roles".to_string(),
+            session_key: "roles".to_string(),
+        }
+    }
+}
+
+impl Rbac {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare `role` as granting `permissions`, adding to any it grants already.
+    pub fn role<I, T>(mut self, role: &str, permissions: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.policy()
+            .grants
+            .entry(role.to_string())
+            .or_default()
+            .extend(permissions.into_iter().map(Into::into));
+        self
+    }
+
+    /// Grant `role` every permission of `parent` as well.
+    pub fn inherits(mut self, role: &str, parent: &str) -> Self {
+        let policy = self.policy();
+        policy.grants.entry(role.to_string()).or_default();
+        policy.parents.entry(role.to_string()).or_default().push(parent.to_string());
+        self
+    }
+
+    /// JWT claim holding the principal's roles, `roles` by default; a string or
+    /// an array of strings.
+    pub fn roles_claim(mut self, claim: &str) -> Self {
+        self.policy().roles_claim = claim.to_string();
+        self
+    }
+
+    /// Session value holding the principal's roles, `roles` by default.
+    pub fn session_key(mut self, key: &str) -> Self {
+        self.policy().session_key = key.to_string();
+        self
+    }
+
+    fn policy(&mut self) -> &mut Policy {
+        Arc::make_mut(&mut self.inner)
+    }
+
+    /// Whether any of `roles` grants `permission`, directly or through inheritance.
+    pub fn allows<T: AsRef<str>>(&self, roles: &[T], permission: &str) -> bool {
+        let mut seen = HashSet::new();
+        let mut pending: Vec<&str> = roles.iter().map(AsRef::as_ref).collect();
+        while let Some(role) = pending.pop() {
+            if !seen.insert(role) {
+                continue;
+            }
+            let grants = self.inner.grants.get(role);
+            if grants.is_some_and(|grants| grants.iter().any(|grant| covers(grant, permission))) {
+                return true;
+            }
+            if let Some(parents) = self.inner.parents.get(role) {
+                pending.extend(parents.iter().map(String::as_str));
+            }
+        }
+        false
+    }
+
+    /// Check that the request's principal holds `permission`, for handlers that
+    /// decide on permissions themselves.
+    pub fn require(&self, ctx: &RequestContext, permission: &str) -> Result<(), FerroxError> {
+        self.check(&ctx.extensions, permission)
+    }
+
+    /// The request's principal, as found by the policy.
+    pub fn principal(&self, ctx: &RequestContext) -> Option<Principal> {
+        self.find_principal(&ctx.extensions)
+    }
+
+    fn check(&self, extensions: &Extensions, permission: &str) -> Result<(), FerroxError> {
+        let Some(principal) = self.find_principal(extensions) else {
+            return Err(FerroxError::Unauthorized("Authentication required".to_string()));
+        };
+        if self.allows(&principal.roles, permission) {
+            return Ok(());
+        }
+        Err(FerroxError::PermissionDenied(Denial {
+            permission: permission.to_string(),
+            roles: principal.roles,
+        }))
+    }
+
+    pub(crate) fn find_principal(&self, extensions: &Extensions) -> Option<Principal> {
+        if let Some(principal) = extensions.get::<Principal>() {
+            return Some(principal.clone());
+        }
+        #[cfg(feature = "jwt")]
+        if let Some(claims) = extensions.get::<crate::auth::jwt::Claims>() {
+            return Some(Principal {
+                id: claims.subject().map(str::to_string),
+                roles: role_names(claims.get(&self.inner.roles_claim)),
+            });
+        }
+        let session = extensions.get::<crate::session::Session>()?;
+        let roles = session.get::<serde_json::Value>(&self.inner.session_key)?;
+        Some(Principal {
+            id: session.get::<serde_json::Value>("user_id").map(|id| match id {
+                serde_json::Value::String(id) => id,
+                id => id.to_string(),
+            }),
+            roles: role_names(Some(&roles)),
+        })
+    }
+}
+
+// "users:write" is covered by itself, "users:*" and "*"
+fn covers(grant: &str, permission: &str) -> bool {
+    match grant.strip_suffix('*') {
+        Some(prefix) => permission.starts_with(prefix),
+        None => grant == permission,
+    }
+}
+
+// Roles given as one string or an array of strings
+fn role_names(value: Option<&serde_json::Value>) -> Vec<String> {
+    match value {
+        Some(serde_json::Value::String(role)) => vec![role.clone()],
+        Some(serde_json::Value::Array(roles)) => roles.iter().filter_map(|role| role.as_str().map(str::to_string)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Enforce `permission` in front of a route; `None` when no policy was configured
+pub(crate) fn require(route: MethodRouter<AppState>, permission: &'static str, policy: Option<Rbac>) -> MethodRouter<AppState> {
+    if policy.is_none() {
+        tracing::error!("Routes require permission `{}` but no Server::rbac policy is set; they will answer 500", permission);
+    }
+    route.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let policy = policy.clone();
+        async move {
+            // Fail closed when there is no policy
+            let Some(policy) = policy else {
+                return FerroxError::Internal("Internal server error: no access control policy is configured".to_string())
+                    .into_response();
+            };
+            match policy.check(request.extensions(), permission) {
+                Ok(()) => next.run(request).await,
+                Err(err) => err.into_response(),
+            }
+        }
+    }))
+}
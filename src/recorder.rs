@@ -0,0 +1,375 @@
+//! Recording of request/response pairs to disk, and their replay against a
+//! server as a regression test.
+//!
+//! ```ignore
+//! Server::new().record(Recorder::new("recordings/traffic.ndjson").redact_header("x-session-token"));
+//!
+//! #[tokio::test]
+//! async fn serves_what_it_used_to() {
+//!     let mismatches = Replay::load("recordings/traffic.ndjson")?
+//!         .header("authorization", "Bearer test-token")
+//!         .ignore_field("created_at")
+//!         .run(&TestClient::new())
+//!         .await;
+//!     assert!(mismatches.is_empty(), "{:#?}", mismatches);
+//! }
+//! ```
+//!
+//! `Server::record` appends one JSON [`Exchange`] per line to the file as each
+//! response goes out. Bodies are kept as JSON when their content type says so
+//! and as text otherwise; streamed bodies and bodies over the recorder's
+//! `max_body_size` are left out. `Authorization`, `Cookie`, `Set-Cookie`,
+//! `Proxy-Authorization` and `X-Api-Key` headers are recorded as `[redacted]`,
+//! as are `password` fields anywhere in JSON bodies; `redact_header` and
+//! `redact_field` add to those.
+//!
+//! `Replay` sends each recorded request again, without its redacted headers
+//! unless `header` gives them a value (redacted body fields are sent as
+//! recorded), and reports the exchanges whose status or body differ. Fields
+//! that change from run to run, such as ids and timestamps, can be left out of
+//! the comparison with `ignore_field`.
+
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::extract::Request;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, HeaderName, Method};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::Router;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::context::AppState;
+use crate::test::{TestClient, TestResponse};
+
+const REDACTED: &str = "[redacted]";
+
+/// Where and how `Server::record` records traffic.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    path: PathBuf,
+    redact_headers: Vec<HeaderName>,
+    redact_fields: Vec<String>,
+    max_body_size: usize,
+}
+
+impl Recorder {
+    /// Append exchanges to the file at `path`, creating it if needed.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Recorder {
+            path: path.into(),
+            redact_headers: ["authorization", "cookie", "set-cookie", "proxy-authorization", "x-api-key"]
+                .into_iter()
+                .map(HeaderName::from_static)
+                .collect(),
+            redact_fields: vec!["password".to_string()],
+            max_body_size: 64 * 1024,
+        }
+    }
+
+    /// Also record `name`'s values as `[redacted]`.
+    ///
+    /// # Panics
+    ///
+    /// If `name` is not a valid header name.
+    pub fn redact_header(mut self, name: &str) -> Self {
+        let name = HeaderName::try_from(name).unwrap_or_else(|err| panic!("invalid header name {:?}: {}", name, err));
+        self.redact_headers.push(name);
+        self
+    }
+
+    /// Also record JSON body fields named `name`, at any depth, as `[redacted]`.
+    pub fn redact_field(mut self, name: &str) -> Self {
+        self.redact_fields.push(name.to_string());
+        self
+    }
+
+    /// Largest body recorded, in bytes (64 KiB by default); larger ones are left out.
+    pub fn max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = bytes;
+        self
+    }
+
+    // The body as JSON or text, and the body to pass on; `None` if not recorded
+    async fn body(&self, headers: &HeaderMap, body: Body) -> (Option<Value>, Body) {
+        let known_size = body.size_hint().exact().is_some_and(|size| size <= self.max_body_size as u64);
+        // A body the client only sends after 100 Continue is left for the handler to ask for
+        if !known_size || crate::expect::awaits_continue(headers) {
+            return (None, body);
+        }
+        let bytes = match axum::body::to_bytes(body, self.max_body_size).await {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                tracing::warn!("Failed to read a body to record: {}", err);
+                return (None, Body::empty());
+            }
+        };
+        let value = match (is_json(headers), serde_json::from_slice::<Value>(&bytes)) {
+            _ if bytes.is_empty() => None,
+            (true, Ok(mut value)) => {
+                redact(&mut value, &self.redact_fields);
+                Some(value)
+            }
+            _ => Some(Value::String(String::from_utf8_lossy(&bytes).into_owned())),
+        };
+        (value, Body::from(bytes))
+    }
+}
+
+// One entry per header name, with repeated values joined
+fn recorded_headers(headers: &HeaderMap, redacted: &[HeaderName]) -> BTreeMap<String, String> {
+    let mut recorded: BTreeMap<String, String> = BTreeMap::new();
+    for (name, value) in headers {
+        if redacted.contains(name) {
+            recorded.insert(name.to_string(), REDACTED.to_string());
+            continue;
+        }
+        let value = String::from_utf8_lossy(value.as_bytes());
+        recorded
+            .entry(name.to_string())
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(&value);
+            })
+            .or_insert_with(|| value.into_owned());
+    }
+    recorded
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("json"))
+}
+
+pub(crate) fn redact(value: &mut Value, fields: &[String]) {
+    match value {
+        Value::Object(object) => {
+            for (name, field) in object.iter_mut() {
+                if fields.iter().any(|redacted| redacted.eq_ignore_ascii_case(name)) {
+                    *field = Value::from(REDACTED);
+                } else {
+                    redact(field, fields);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| redact(item, fields)),
+        _ => {}
+    }
+}
+
+fn remove_fields(value: &mut Value, fields: &[String]) {
+    match value {
+        Value::Object(object) => {
+            object.retain(|name, _| !fields.contains(name));
+            object.values_mut().for_each(|field| remove_fields(field, fields));
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| remove_fields(item, fields)),
+        _ => {}
+    }
+}
+
+/// One recorded request and the response it got, a line of the recording.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Exchange {
+    /// When the request came in, as RFC 3339.
+    pub recorded_at: String,
+    pub duration_ms: f64,
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// The path and query string.
+    pub uri: String,
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<Value>,
+}
+
+// Record every exchange through `router` to `config`'s file
+pub(crate) fn layer(router: Router<AppState>, config: Recorder) -> Router<AppState> {
+    let file = OpenOptions::new().create(true).append(true).open(&config.path);
+    let file = match file {
+        Ok(file) => Arc::new(Mutex::new(file)),
+        Err(err) => {
+            tracing::error!("Not recording: failed to open {}: {}", config.path.display(), err);
+            return router;
+        }
+    };
+    let config = Arc::new(config);
+    router.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let config = config.clone();
+        let file = file.clone();
+        async move {
+            let started = Instant::now();
+            let recorded_at = chrono::Utc::now().to_rfc3339();
+            let (parts, body) = request.into_parts();
+            let (body, passed) = config.body(&parts.headers, body).await;
+            let request = RecordedRequest {
+                method: parts.method.to_string(),
+                uri: parts.uri.path_and_query().map_or("/", |path| path.as_str()).to_string(),
+                headers: recorded_headers(&parts.headers, &config.redact_headers),
+                body,
+            };
+
+            let response = next.run(Request::from_parts(parts, passed)).await;
+            let (parts, body) = response.into_parts();
+            let (body, passed) = config.body(&parts.headers, body).await;
+            let exchange = Exchange {
+                recorded_at,
+                duration_ms: (started.elapsed().as_secs_f64() * 1e6).round() / 1e3,
+                request,
+                response: RecordedResponse {
+                    status: parts.status.as_u16(),
+                    headers: recorded_headers(&parts.headers, &config.redact_headers),
+                    body,
+                },
+            };
+            let written = tokio::task::spawn_blocking(move || write(&file, &exchange)).await;
+            if let Ok(Err(err)) = written {
+                tracing::error!("Failed to record an exchange: {}", err);
+            }
+            Response::from_parts(parts, passed)
+        }
+    }))
+}
+
+fn write(file: &Mutex<File>, exchange: &Exchange) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(exchange)?;
+    line.push(b'\n');
+    let mut file = file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    file.write_all(&line)
+}
+
+/// Recorded exchanges to send again; see the module docs.
+#[derive(Debug, Clone, Default)]
+pub struct Replay {
+    exchanges: Vec<Exchange>,
+    headers: Vec<(String, String)>,
+    ignored_fields: Vec<String>,
+}
+
+/// A replayed exchange whose response differs from the recorded one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    /// The exchange's line in the recording, from 0.
+    pub index: usize,
+    pub request: RecordedRequest,
+    pub expected: RecordedResponse,
+    pub actual: RecordedResponse,
+}
+
+impl Replay {
+    /// The exchanges recorded in the file at `path`, skipping blank lines.
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let mut exchanges = Vec::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            exchanges.push(serde_json::from_str(&line)?);
+        }
+        Ok(Replay::new(exchanges))
+    }
+
+    pub fn new(exchanges: Vec<Exchange>) -> Self {
+        Replay {
+            exchanges,
+            ..Self::default()
+        }
+    }
+
+    pub fn exchanges(&self) -> &[Exchange] {
+        &self.exchanges
+    }
+
+    /// Send `name: value` with every request, replacing any recorded or redacted value.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_ascii_lowercase(), value.to_string()));
+        self
+    }
+
+    /// Leave JSON fields named `name`, at any depth, out of the comparison.
+    pub fn ignore_field(mut self, name: &str) -> Self {
+        self.ignored_fields.push(name.to_string());
+        self
+    }
+
+    /// Send every request in order and return the exchanges that came out differently.
+    pub async fn run(&self, client: &TestClient) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+        for (index, exchange) in self.exchanges.iter().enumerate() {
+            let response = self.send(client, &exchange.request).await;
+            let actual = recorded(&response);
+            let mut expected_body = exchange.response.body.clone();
+            let mut actual_body = actual.body.clone();
+            for body in [&mut expected_body, &mut actual_body].into_iter().flatten() {
+                remove_fields(body, &self.ignored_fields);
+            }
+            if actual.status != exchange.response.status || actual_body != expected_body {
+                mismatches.push(Mismatch {
+                    index,
+                    request: exchange.request.clone(),
+                    expected: exchange.response.clone(),
+                    actual,
+                });
+            }
+        }
+        mismatches
+    }
+
+    async fn send(&self, client: &TestClient, request: &RecordedRequest) -> TestResponse {
+        let method = Method::from_bytes(request.method.as_bytes()).unwrap_or(Method::GET);
+        let mut sent = client.request(method, &request.uri);
+        for (name, value) in &request.headers {
+            let replaced = self.headers.iter().any(|(header, _)| header == name);
+            if value == REDACTED || replaced || name == "content-length" || name == "host" {
+                continue;
+            }
+            // Several values were recorded joined; send them as one
+            sent = sent.header(name, value);
+        }
+        for (name, value) in &self.headers {
+            sent = sent.header(name, value);
+        }
+        match &request.body {
+            Some(Value::String(text)) if !request.headers.get("content-type").is_some_and(|value| value.contains("json")) => {
+                sent.body(Bytes::from(text.clone())).await
+            }
+            Some(body) => sent.body(Bytes::from(body.to_string())).await,
+            None => sent.await,
+        }
+    }
+}
+
+fn recorded(response: &TestResponse) -> RecordedResponse {
+    let body = response.bytes();
+    let body = match (is_json(response.headers()), serde_json::from_slice::<Value>(body)) {
+        _ if body.is_empty() => None,
+        (true, Ok(value)) => Some(value),
+        _ => Some(Value::String(String::from_utf8_lossy(body).into_owned())),
+    };
+    RecordedResponse {
+        status: response.status().as_u16(),
+        headers: recorded_headers(response.headers(), &[]),
+        body,
+    }
+}
@@ -0,0 +1,274 @@
+//! A Redis connection shared by handlers and by the stores of sessions,
+//! cached responses and rate limits (the `redis` feature).
+//!
+//! ```ignore
+//! let redis = Redis::connect("redis://127.0.0.1/").await?;
+//! Server::new()
+//!     .sessions(SessionConfig::new(&secret).store(redis.session_store()))
+//!     .cache_store(redis.cache_store())
+//!     .rate_limit(RateLimiter::new(RateLimit::per_minute(600)).store(redis.rate_limits()))
+//!     .with_redis(redis);
+//! ```
+//!
+//! `Server::with_redis` shares the connection with handlers as `State<Redis>`
+//! and adds a `redis` readiness check. The connection is multiplexed, so one is
+//! enough for every request, and reconnects on its own when it drops.
+//!
+//! Every key starts with the connection's prefix, `ferrox:` by default:
+//! `session:<id>` for sessions, `cache:<path>?<query>` for cached responses,
+//! with a `cache-index:<path>` set to invalidate them by path, and
+//! `ratelimit:<client>` for rate limit counters, which a script updates
+//! atomically so that every instance counts against the same limits.
+
+use std::time::Duration;
+
+use ::redis::aio::ConnectionManager;
+use ::redis::{AsyncCommands, RedisError, Script};
+
+use crate::cache::{CacheKey, CacheStore, CachedResponse};
+use crate::error::FerroxError;
+use crate::ratelimit::{Algorithm, Decision, RateLimit, RateLimitStore};
+use crate::session::redis::RedisStore;
+use crate::session::StoreFuture;
+
+/// A connection to Redis, reconnecting automatically when it drops.
+#[derive(Clone)]
+pub struct Redis {
+    connection: ConnectionManager,
+    prefix: String,
+}
+
+impl Redis {
+    /// Connect to the Redis server at `url`, e.g. `redis://127.0.0.1/`.
+    pub async fn connect(url: &str) -> Result<Self, RedisError> {
+        let client = ::redis::Client::open(url)?;
+        Ok(Redis::new(ConnectionManager::new(client).await?))
+    }
+
+    /// Use an existing connection.
+    pub fn new(connection: ConnectionManager) -> Self {
+        Redis {
+            connection,
+            prefix: "ferrox:".to_string(),
+        }
+    }
+
+    /// Prefix for the keys of the stores (defaults to `ferrox:`), set before creating them.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// The connection, for handlers' own commands.
+    pub fn connection(&self) -> ConnectionManager {
+        self.connection.clone()
+    }
+
+    /// Check that the server answers.
+    pub async fn ping(&self) -> Result<(), RedisError> {
+        ::redis::cmd("PING").query_async::<()>(&mut self.connection()).await
+    }
+
+    /// A session store on this connection, for `SessionConfig::store`.
+    pub fn session_store(&self) -> RedisStore {
+        RedisStore::new(self.connection()).prefix(&format!("{}session:", self.prefix))
+    }
+
+    /// A response cache on this connection, for `Server::cache_store`.
+    pub fn cache_store(&self) -> RedisCache {
+        RedisCache {
+            connection: self.connection(),
+            prefix: self.prefix.clone(),
+        }
+    }
+
+    /// Rate limit counters on this connection, for `RateLimiter::store`.
+    pub fn rate_limits(&self) -> RedisRateLimits {
+        RedisRateLimits {
+            connection: self.connection(),
+            prefix: format!("{}ratelimit:", self.prefix),
+        }
+    }
+}
+
+fn store_error(err: impl std::fmt::Display) -> FerroxError {
+    FerroxError::Internal(format!("Redis: {}", err))
+}
+
+/// Cached responses in Redis, shared by every instance; see `Redis::cache_store`.
+#[derive(Clone)]
+pub struct RedisCache {
+    connection: ConnectionManager,
+    prefix: String,
+}
+
+impl RedisCache {
+    fn key(&self, key: &CacheKey) -> String {
+        format!("{}cache:{}?{}\n{}", self.prefix, key.path, key.query, key.accept)
+    }
+
+    fn index(&self, path: &str) -> String {
+        format!("{}cache-index:{}", self.prefix, path)
+    }
+}
+
+impl CacheStore for RedisCache {
+    fn get<'a>(&'a self, key: &'a CacheKey) -> StoreFuture<'a, Option<CachedResponse>> {
+        Box::pin(async move {
+            let mut connection = self.connection.clone();
+            let (headers, body): (Option<Vec<u8>>, Option<Vec<u8>>) = ::redis::cmd("HMGET")
+                .arg(self.key(key))
+                .arg("headers")
+                .arg("body")
+                .query_async(&mut connection)
+                .await
+                .map_err(store_error)?;
+            let (Some(headers), Some(body)) = (headers, body) else {
+                return Ok(None);
+            };
+            // Unreadable entries count as misses
+            Ok(serde_json::from_slice(&headers).ok().map(|headers| CachedResponse { headers, body }))
+        })
+    }
+
+    fn set<'a>(&'a self, key: &'a CacheKey, response: &'a CachedResponse, ttl: Duration) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let headers = serde_json::to_vec(&response.headers).map_err(store_error)?;
+            let entry = self.key(key);
+            let index = self.index(&key.path);
+            let ttl = ttl.as_millis().max(1) as i64;
+            let mut connection = self.connection.clone();
+            ::redis::pipe()
+                .atomic()
+                .hset_multiple(&entry, &[("headers", headers), ("body", response.body.clone())])
+                .pexpire(&entry, ttl)
+                .sadd(&index, &entry)
+                .pexpire(&index, ttl)
+                .query_async::<()>(&mut connection)
+                .await
+                .map_err(store_error)
+        })
+    }
+
+    fn invalidate<'a>(&'a self, path: &'a str) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let index = self.index(path);
+            let mut connection = self.connection.clone();
+            let mut keys: Vec<String> = connection.smembers(&index).await.map_err(store_error)?;
+            keys.push(index);
+            connection.del::<_, ()>(keys).await.map_err(store_error)
+        })
+    }
+
+    fn clear(&self) -> StoreFuture<'_, ()> {
+        Box::pin(async move {
+            let mut connection = self.connection.clone();
+            let mut keys = Vec::new();
+            {
+                let mut iter = connection
+                    .scan_match::<_, String>(format!("{}cache*", self.prefix))
+                    .await
+                    .map_err(store_error)?;
+                while let Some(key) = iter.next_item().await {
+                    keys.push(key);
+                }
+            }
+            for keys in keys.chunks(500) {
+                connection.del::<_, ()>(keys).await.map_err(store_error)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+// Both scripts take the limit and the window in milliseconds, and keep their
+// counters in a hash that expires once it would be back to the full limit
+const TOKEN_BUCKET: &str = r"
+local limit = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + tonumber(time[2]) / 1000
+local state = redis.call('HMGET', KEYS[1], 'tokens', 'updated')
+local tokens = tonumber(state[1]) or limit
+local updated = tonumber(state[2]) or now
+tokens = math.min(limit, tokens + math.max(0, now - updated) * limit / window)
+local allowed = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'updated', tostring(now))
+redis.call('PEXPIRE', KEYS[1], window * 2)
+return {allowed, tostring(tokens)}
+";
+
+const SLIDING_WINDOW: &str = r"
+local limit = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + tonumber(time[2]) / 1000
+local state = redis.call('HMGET', KEYS[1], 'started', 'current', 'previous')
+local started = tonumber(state[1]) or now
+local current = tonumber(state[2]) or 0
+local previous = tonumber(state[3]) or 0
+local elapsed = now - started
+if elapsed >= window * 2 then
+    started = now
+    previous = 0
+    current = 0
+elseif elapsed >= window then
+    started = started + window
+    previous = current
+    current = 0
+end
+local into = now - started
+local allowed = 0
+if previous * (1 - into / window) + current + 1 <= limit then
+    current = current + 1
+    allowed = 1
+end
+redis.call('HSET', KEYS[1], 'started', tostring(started), 'current', current, 'previous', previous)
+redis.call('PEXPIRE', KEYS[1], window * 2)
+return {allowed, current, previous, tostring(into)}
+";
+
+/// Rate limit counters in Redis, shared by every instance; see `Redis::rate_limits`.
+#[derive(Clone)]
+pub struct RedisRateLimits {
+    connection: ConnectionManager,
+    prefix: String,
+}
+
+impl RateLimitStore for RedisRateLimits {
+    fn check<'a>(&'a self, key: &'a str, rate: RateLimit, algorithm: Algorithm) -> StoreFuture<'a, Decision> {
+        Box::pin(async move {
+            let mut connection = self.connection.clone();
+            let window = rate.window().as_millis().max(1) as u64;
+            match algorithm {
+                Algorithm::TokenBucket => {
+                    let (allowed, tokens): (i64, String) = Script::new(TOKEN_BUCKET)
+                        .key(format!("{}{}", self.prefix, key))
+                        .arg(rate.limit())
+                        .arg(window)
+                        .invoke_async(&mut connection)
+                        .await
+                        .map_err(store_error)?;
+                    let tokens = tokens.parse().map_err(store_error)?;
+                    Ok(Decision::token_bucket(allowed == 1, tokens, rate))
+                }
+                Algorithm::SlidingWindow => {
+                    let (allowed, current, previous, into_window): (i64, u32, u32, String) = Script::new(SLIDING_WINDOW)
+                        .key(format!("{}{}", self.prefix, key))
+                        .arg(rate.limit())
+                        .arg(window)
+                        .invoke_async(&mut connection)
+                        .await
+                        .map_err(store_error)?;
+                    let into_window: f64 = into_window.parse().map_err(store_error)?;
+                    let into_window = Duration::from_secs_f64(into_window.max(0.0) / 1000.0);
+                    Ok(Decision::sliding_window(allowed == 1, current, previous, into_window, rate))
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,391 @@
+//! Circuit breakers for outbound dependencies: once calls to a dependency keep
+//! failing, further calls fail at once instead of waiting on it, giving it time
+//! to recover.
+//!
+//! ```ignore
+//! let payments = CircuitBreaker::new("payments").failure_threshold(5).open_for(Duration::from_secs(30));
+//! Server::new().circuit_breaker(payments.clone()).with_state(payments);
+//!
+//! #[http_method(POST, "/checkout", circuits = ["payments"])]
+//! async fn checkout(body: Order, payments: State<CircuitBreaker>) -> Result<Value, FerroxError> {
+//!     let receipt = payments.call(|| charge(&body)).await?;
+//!     Ok(json!({ "receipt": receipt }))
+//! }
+//! ```
+//!
+//! A closed circuit lets calls through and opens after `failure_threshold`
+//! failures in a row. An open one fails calls with `CircuitError::Open` until
+//! `open_for` has passed, then turns half-open and lets `half_open_probes`
+//! calls through: the circuit closes once they all succeed and opens again as
+//! soon as one fails. Calls fail when their future returns `Err`.
+//!
+//! Routes declared with `circuits = [...]` answer 503 with `Retry-After`,
+//! without running the handler, while any of those circuits is open. With
+//! `Server::enable_metrics`, each circuit given to `Server::circuit_breaker`
+//! reports its state and call counts.
+
+use std::fmt::{self, Display, Write};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::Request;
+use axum::http::header::RETRY_AFTER;
+use axum::http::{HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::routing::MethodRouter;
+
+use crate::context::AppState;
+use crate::error::FerroxError;
+
+/// Whether a circuit lets calls through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls go through.
+    Closed,
+    /// Calls fail at once.
+    Open,
+    /// Probe calls go through, to find out whether the dependency recovered.
+    HalfOpen,
+}
+
+/// Counts of a circuit's calls since it was created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CircuitMetrics {
+    pub successes: u64,
+    pub failures: u64,
+    /// Calls failed at once because the circuit was open.
+    pub rejected: u64,
+    /// Times the circuit opened.
+    pub opened: u64,
+}
+
+/// The error of a call through a `CircuitBreaker`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CircuitError<E> {
+    /// The circuit is open and the call was not made; retry after the duration.
+    Open { circuit: String, retry_after: Duration },
+    /// The call was made and failed.
+    Failed(E),
+}
+
+impl<E: Display> Display for CircuitError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitError::Open { circuit, .. } => write!(f, "circuit `{}` is open", circuit),
+            CircuitError::Failed(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<E: fmt::Debug + Display> std::error::Error for CircuitError<E> {}
+
+// An open circuit is answered with 503, and a failed call as its own error
+impl<E: Into<FerroxError>> From<CircuitError<E>> for FerroxError {
+    fn from(err: CircuitError<E>) -> Self {
+        match err {
+            CircuitError::Open { circuit, .. } => unavailable(&circuit),
+            CircuitError::Failed(err) => err.into(),
+        }
+    }
+}
+
+fn unavailable(circuit: &str) -> FerroxError {
+    FerroxError::Status(
+        StatusCode::SERVICE_UNAVAILABLE,
+        format!("Service unavailable: dependency `{}` is failing", circuit),
+    )
+}
+
+/// A circuit breaker around calls to one dependency; clones share its state.
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    name: Arc<str>,
+    failure_threshold: u32,
+    open_for: Duration,
+    half_open_probes: u32,
+    state: Arc<Mutex<Circuit>>,
+}
+
+#[derive(Default)]
+struct Circuit {
+    phase: Phase,
+    metrics: CircuitMetrics,
+}
+
+#[derive(Default)]
+enum Phase {
+    #[default]
+    Closed,
+    // Consecutive failures so far
+    Failing(u32),
+    Open {
+        until: Instant,
+    },
+    HalfOpen {
+        in_flight: u32,
+        succeeded: u32,
+    },
+}
+
+impl CircuitBreaker {
+    /// A closed circuit opening after 5 failures in a row, for 30 seconds, then
+    /// letting one probe call through.
+    pub fn new(name: impl Into<String>) -> Self {
+        CircuitBreaker {
+            name: name.into().into(),
+            failure_threshold: 5,
+            open_for: Duration::from_secs(30),
+            half_open_probes: 1,
+            state: Arc::default(),
+        }
+    }
+
+    /// Open after `failures` failed calls in a row.
+    pub fn failure_threshold(mut self, failures: u32) -> Self {
+        self.failure_threshold = failures.max(1);
+        self
+    }
+
+    /// Fail calls at once for `duration` after opening, before probing.
+    pub fn open_for(mut self, duration: Duration) -> Self {
+        self.open_for = duration;
+        self
+    }
+
+    /// Probe calls let through while half-open, all of which must succeed to close.
+    pub fn half_open_probes(mut self, probes: u32) -> Self {
+        self.half_open_probes = probes.max(1);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn state(&self) -> CircuitState {
+        let mut circuit = self.lock();
+        self.phase(&mut circuit)
+    }
+
+    pub fn metrics(&self) -> CircuitMetrics {
+        self.lock().metrics
+    }
+
+    /// Make the call `f` starts unless the circuit is open, and record whether it failed.
+    pub async fn call<F, Fut, T, E>(&self, f: F) -> Result<T, CircuitError<E>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let call = self.admit()?;
+        let result = f().await;
+        call.finish(result.is_ok());
+        result.map_err(CircuitError::Failed)
+    }
+
+    /// Until when calls fail at once, if the circuit is open.
+    pub fn open_until(&self) -> Option<Instant> {
+        let mut circuit = self.lock();
+        self.phase(&mut circuit);
+        match circuit.phase {
+            Phase::Open { until } => Some(until),
+            _ => None,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Circuit> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // The current state, turning an open circuit half-open once its time is up
+    fn phase(&self, circuit: &mut Circuit) -> CircuitState {
+        match circuit.phase {
+            Phase::Closed | Phase::Failing(_) => CircuitState::Closed,
+            Phase::Open { until } if Instant::now() < until => CircuitState::Open,
+            Phase::Open { .. } => {
+                circuit.phase = Phase::HalfOpen {
+                    in_flight: 0,
+                    succeeded: 0,
+                };
+                CircuitState::HalfOpen
+            }
+            Phase::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+
+    // Let a call through, or fail it at once
+    fn admit<E>(&self) -> Result<Call<'_>, CircuitError<E>> {
+        let mut circuit = self.lock();
+        self.phase(&mut circuit);
+        let rejected = match &mut circuit.phase {
+            Phase::Closed | Phase::Failing(_) => false,
+            Phase::Open { .. } => true,
+            Phase::HalfOpen { in_flight, succeeded } => {
+                let full = *in_flight + *succeeded >= self.half_open_probes;
+                if !full {
+                    *in_flight += 1;
+                }
+                full
+            }
+        };
+        if rejected {
+            circuit.metrics.rejected += 1;
+            let retry_after = match circuit.phase {
+                Phase::Open { until } => until.saturating_duration_since(Instant::now()),
+                _ => Duration::ZERO,
+            };
+            return Err(CircuitError::Open {
+                circuit: self.name.to_string(),
+                retry_after,
+            });
+        }
+        let probe = matches!(circuit.phase, Phase::HalfOpen { .. });
+        Ok(Call {
+            breaker: self,
+            probe,
+            done: false,
+        })
+    }
+
+    fn record(&self, probe: bool, succeeded: bool) {
+        let mut circuit = self.lock();
+        let circuit = &mut *circuit;
+        match succeeded {
+            true => circuit.metrics.successes += 1,
+            false => circuit.metrics.failures += 1,
+        }
+        let next = match (&mut circuit.phase, succeeded) {
+            (Phase::HalfOpen { in_flight, succeeded: probes }, true) if probe => {
+                *in_flight = in_flight.saturating_sub(1);
+                *probes += 1;
+                (*probes >= self.half_open_probes).then_some(Phase::Closed)
+            }
+            (Phase::HalfOpen { .. }, false) if probe => Some(self.open()),
+            (Phase::Closed | Phase::Failing(_), true) => Some(Phase::Closed),
+            (Phase::Closed, false) => Some(self.failing(1)),
+            (Phase::Failing(failures), false) => Some(self.failing(*failures + 1)),
+            // Calls let through before the circuit last opened decide nothing
+            _ => None,
+        };
+        if let Some(next) = next {
+            if matches!(next, Phase::Open { .. }) && !matches!(circuit.phase, Phase::Open { .. }) {
+                circuit.metrics.opened += 1;
+                tracing::warn!(circuit = %self.name, "Circuit opened for {:?}", self.open_for);
+            }
+            if matches!(next, Phase::Closed) && matches!(circuit.phase, Phase::HalfOpen { .. }) {
+                tracing::info!(circuit = %self.name, "Circuit closed");
+            }
+            circuit.phase = next;
+        }
+    }
+
+    fn failing(&self, failures: u32) -> Phase {
+        match failures >= self.failure_threshold {
+            true => self.open(),
+            false => Phase::Failing(failures),
+        }
+    }
+
+    fn open(&self) -> Phase {
+        Phase::Open {
+            until: Instant::now() + self.open_for,
+        }
+    }
+
+    // A half-open probe that was cancelled frees its slot without deciding anything
+    fn release(&self) {
+        if let Phase::HalfOpen { in_flight, .. } = &mut self.lock().phase {
+            *in_flight = in_flight.saturating_sub(1);
+        }
+    }
+}
+
+impl fmt::Debug for CircuitBreaker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CircuitBreaker")
+            .field("name", &self.name)
+            .field("state", &self.state())
+            .finish()
+    }
+}
+
+// A call let through, recorded when it finishes
+struct Call<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+    done: bool,
+}
+
+impl Call<'_> {
+    fn finish(mut self, succeeded: bool) {
+        self.done = true;
+        self.breaker.record(self.probe, succeeded);
+    }
+}
+
+impl Drop for Call<'_> {
+    fn drop(&mut self) {
+        if !self.done && self.probe {
+            self.breaker.release();
+        }
+    }
+}
+
+// Answer 503 while any of `circuits` is open, instead of running the handler
+pub(crate) fn guard_route(route: MethodRouter<AppState>, circuits: Vec<CircuitBreaker>) -> MethodRouter<AppState> {
+    route.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let open = circuits.iter().find_map(|circuit| Some((circuit.name.clone(), circuit.open_until()?)));
+        async move {
+            let Some((name, until)) = open else {
+                return next.run(request).await;
+            };
+            let mut response = unavailable(&name).into_response();
+            let seconds = until.saturating_duration_since(Instant::now()).as_secs_f64().ceil().max(1.0);
+            response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(seconds as u64));
+            response
+        }
+    }))
+}
+
+// The circuits' states and counts in the Prometheus text format
+pub(crate) fn render_metrics(circuits: &[CircuitBreaker], out: &mut String) {
+    if circuits.is_empty() {
+        return;
+    }
+    let name = |circuit: &CircuitBreaker| circuit.name.replace('\\', "\\\\").replace('"', "\\\"");
+    out.push_str("# HELP ferrox_circuit_state Circuit state: 0 closed, 1 half-open, 2 open.\n");
+    out.push_str("# TYPE ferrox_circuit_state gauge\n");
+    for circuit in circuits {
+        let state = match circuit.state() {
+            CircuitState::Closed => 0,
+            CircuitState::HalfOpen => 1,
+            CircuitState::Open => 2,
+        };
+        let _ = writeln!(out, "ferrox_circuit_state{{circuit=\"{}\"}} {}", name(circuit), state);
+    }
+    out.push_str("# HELP ferrox_circuit_calls_total Calls through a circuit, by outcome.\n");
+    out.push_str("# TYPE ferrox_circuit_calls_total counter\n");
+    for circuit in circuits {
+        let metrics = circuit.metrics();
+        for (outcome, count) in [
+            ("success", metrics.successes),
+            ("failure", metrics.failures),
+            ("rejected", metrics.rejected),
+        ] {
+            let _ = writeln!(
+                out,
+                "ferrox_circuit_calls_total{{circuit=\"{}\",outcome=\"{}\"}} {}",
+                name(circuit),
+                outcome,
+                count
+            );
+        }
+    }
+    out.push_str("# HELP ferrox_circuit_opened_total Times a circuit opened.\n");
+    out.push_str("# TYPE ferrox_circuit_opened_total counter\n");
+    for circuit in circuits {
+        let _ = writeln!(out, "ferrox_circuit_opened_total{{circuit=\"{}\"}} {}", name(circuit), circuit.metrics().opened);
+    }
+}
@@ -0,0 +1,453 @@
+use axum::http::header::{CONTENT_TYPE, LOCATION, VARY};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::response::IntoResponse;
+use serde::Serialize;
+
+use crate::envelope::{ApiEnvelope, Responder, ResponseEnvelope};
+use crate::error::FerroxError;
+use crate::format::{self, Format};
+use crate::streaming::{StreamRequest, Streamed};
+
+#[derive(Serialize, Clone)]
+pub struct ApiResponse<T> {
+    pub success: bool,
+    pub data: Option<T>,
+    pub message: String,
+}
+
+impl<T: Serialize> ApiResponse<T> {
+    /// Successful envelope carrying `data`.
+    pub fn ok(data: T) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            message: String::new(),
+        }
+    }
+}
+
+/// A handler result serialized with serde and sent with 200, like a bare
+/// `Serialize` return type, for handlers that prefer to say so in the signature.
+///
+/// ```ignore
+/// #[http_method(GET, "/users/:id")]
+/// async fn get_user(id: u64) -> Result<Json<User>, FerroxError> {
+///     Ok(Json(db.user(id).await?))
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Json<T>(pub T);
+
+/// A handler result sent with 201 Created and a `Location` header naming the
+/// new resource.
+///
+/// ```ignore
+/// #[http_method(POST, "/users")]
+/// async fn create_user(body: NewUser) -> Result<Created<User>, FerroxError> {
+///     let user = db.insert(body).await?;
+///     Ok(Created(format!("/users/{}", user.id), user))
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Created<T>(pub String, pub T);
+
+/// A handler result sent with 202 Accepted, for work that goes on after the
+/// response, e.g. the id of a queued job.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Accepted<T>(pub T);
+
+/// An empty 204 No Content response, e.g. for a DELETE.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NoContent;
+
+/// What to do when a handler returns JSON that is not an object.
+///
+/// Objects are always sent unchanged, since handlers usually build their own
+/// `ApiResponse` envelope. Scalars, arrays and `null` are covered by this policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NonObjectResponse {
+    /// Put the value unchanged into the `data` field of a successful `ApiResponse`,
+    /// or wrap it in the configured `ResponseEnvelope`.
+    #[default]
+    Envelope,
+    /// Send the value as the response body exactly as the handler returned it.
+    PassThrough,
+}
+
+impl NonObjectResponse {
+    /// Apply the policy to a handler's return value, with the default envelope.
+    pub fn apply(self, value: serde_json::Value) -> serde_json::Value {
+        self.apply_with(&ApiEnvelope, value)
+    }
+
+    pub(crate) fn apply_with(self, envelope: &dyn ResponseEnvelope, value: serde_json::Value) -> serde_json::Value {
+        match (self, value) {
+            (_, value @ serde_json::Value::Object(_)) => value,
+            (NonObjectResponse::PassThrough, value) => value,
+            (NonObjectResponse::Envelope, value) => envelope.success(value),
+        }
+    }
+}
+
+/// Status code, headers and JSON body produced by a handler.
+///
+/// Any handler result can be turned into one with `into_handler_response()`
+/// and then given a different status or extra headers:
+///
+/// ```ignore
+/// ApiResponse::ok(user)
+///     .into_handler_response()
+///     .with_status(StatusCode::CREATED)
+///     .with_header("location", format!("/users/{}", id))
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandlerResponse {
+    status: StatusCode,
+    // Boxed to keep the type small; it is the error type of several `Result`s
+    headers: Box<HeaderMap>,
+    body: serde_json::Value,
+    // Set for errors, whose body the configured envelope renders
+    error: Option<Box<FerroxError>>,
+    // Set for `StreamingResponse`s, sent in place of `body`
+    stream: Option<Streamed>,
+}
+
+impl HandlerResponse {
+    pub fn new(status: StatusCode, body: serde_json::Value) -> Self {
+        Self {
+            status,
+            headers: Box::default(),
+            body,
+            error: None,
+            stream: None,
+        }
+    }
+
+    pub(crate) fn streamed(stream: Streamed) -> Self {
+        Self {
+            stream: Some(stream),
+            ..Self::new(StatusCode::OK, serde_json::Value::Null)
+        }
+    }
+
+    // Send a streamed body as the request asks: the part its `Range` header names,
+    // in the format its `Accept` header prefers
+    pub(crate) fn for_request(mut self, request: Option<Box<StreamRequest>>) -> Self {
+        if let Some(stream) = &mut self.stream {
+            stream.request = request;
+        }
+        self
+    }
+
+    /// Replace the status code.
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Set a response header, replacing earlier values for the same name.
+    ///
+    /// Headers set here take precedence over the framework's own, including
+    /// `content-type`. An invalid name or value is logged and skipped.
+    pub fn with_header<K, V>(mut self, name: K, value: V) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: std::fmt::Display,
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: std::fmt::Display,
+    {
+        let name = match HeaderName::try_from(name) {
+            Ok(name) => name,
+            Err(err) => {
+                tracing::warn!("Skipping response header with invalid name: {}", err);
+                return self;
+            }
+        };
+        match HeaderValue::try_from(value) {
+            Ok(value) => {
+                self.headers.insert(name, value);
+            }
+            Err(err) => tracing::warn!("Skipping response header `{}` with invalid value: {}", name, err),
+        }
+        self
+    }
+
+    /// Set `Last-Modified`, so `Server::etags` can answer `If-Modified-Since` with 304.
+    pub fn with_last_modified(self, modified: std::time::SystemTime) -> Self {
+        self.with_header(axum::http::header::LAST_MODIFIED, httpdate::fmt_http_date(modified))
+    }
+
+    /// Set `ETag` and `Last-Modified` from the resource's `version`, for clients
+    /// to send back in `If-Match` or `If-Unmodified-Since` when changing it.
+    pub fn with_version(mut self, version: &crate::etag::ResourceVersion) -> Self {
+        if let Some(etag) = version.etag() {
+            self = self.with_header(axum::http::header::ETAG, etag);
+        }
+        match version.modified() {
+            Some(modified) => self.with_last_modified(modified),
+            None => self,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn body(&self) -> &serde_json::Value {
+        &self.body
+    }
+
+    /// The body, to change it in place, e.g. from a `Server::after` hook.
+    pub fn body_mut(&mut self) -> &mut serde_json::Value {
+        &mut self.body
+    }
+
+    pub fn into_parts(self) -> (StatusCode, serde_json::Value) {
+        (self.status, self.body)
+    }
+
+    // A handler's result, as opposed to an error or a streamed body
+    pub(crate) fn is_success(&self) -> bool {
+        self.error.is_none() && self.stream.is_none()
+    }
+
+    // Change a handler's result, leaving a serialization failure as it is
+    fn map_success(self, f: impl FnOnce(Self) -> Self) -> Self {
+        if self.is_success() { f(self) } else { self }
+    }
+
+    // Replace the error this response carries, if any, and its status
+    pub(crate) fn map_error(mut self, f: impl FnOnce(FerroxError) -> FerroxError) -> Self {
+        if let Some(error) = self.error.take() {
+            let error = f(*error);
+            self.status = error.status();
+            self.error = Some(Box::new(error));
+        }
+        self
+    }
+
+    // Serialize into the HTTP response in `format`, wrapping a non-object body per
+    // `policy` and errors in the responder's envelope
+    pub(crate) fn render(
+        self,
+        policy: NonObjectResponse,
+        responder: &Responder,
+        format: Format,
+    ) -> axum::response::Response {
+        if let Some(stream) = self.stream {
+            return stream.into_response(self.status, *self.headers);
+        }
+        // 204 and 304 answers carry no body
+        if matches!(self.status, StatusCode::NO_CONTENT | StatusCode::NOT_MODIFIED) {
+            let mut response = self.status.into_response();
+            response.headers_mut().extend(*self.headers);
+            return response;
+        }
+        if let Some(mut mapped) = self.error.as_deref().and_then(|error| responder.map(error)) {
+            mapped.extensions_mut().insert(Failed);
+            mapped.headers_mut().extend(*self.headers);
+            return mapped;
+        }
+        let envelope = responder.envelope();
+        let (body, content_type) = match &self.error {
+            Some(error) if format == Format::Json => (envelope.error(error), envelope.error_content_type()),
+            Some(error) => (envelope.error(error), format.content_type()),
+            None => (policy.apply_with(envelope, self.body), format.content_type()),
+        };
+        match format.encode(&body) {
+            Ok(bytes) => {
+                let mut response = (self.status, [(CONTENT_TYPE, content_type)], bytes).into_response();
+                if self.error.is_some() {
+                    response.extensions_mut().insert(Failed);
+                }
+                if format::NEGOTIATED {
+                    response.headers_mut().insert(VARY, HeaderValue::from_static("accept"));
+                }
+                response.headers_mut().extend(*self.headers);
+                response
+            }
+            Err(err) => {
+                tracing::error!("Failed to serialize response: {}", err);
+                serialization_failure().into_response()
+            }
+        }
+    }
+}
+
+/// Conversion from a handler's return type into a `HandlerResponse`.
+///
+/// Implemented for `serde_json::Value`, `ApiResponse<T>` and `Json<T>` (sent
+/// with 200), `Created<T>`, `Accepted<T>` and `NoContent` (sent with 201, 202
+/// and 204), `FerroxError` (sent with its status) and `Result`s of those, so
+/// handlers can return `Result<ApiResponse<T>, FerroxError>`. Route handlers may
+/// also return any other `Serialize` type, or a `Result` of one, which is sent
+/// as `Json<T>` would be.
+pub trait IntoHandlerResponse {
+    fn into_handler_response(self) -> HandlerResponse;
+}
+
+impl IntoHandlerResponse for HandlerResponse {
+    fn into_handler_response(self) -> HandlerResponse {
+        self
+    }
+}
+
+impl IntoHandlerResponse for serde_json::Value {
+    fn into_handler_response(self) -> HandlerResponse {
+        HandlerResponse::new(StatusCode::OK, self)
+    }
+}
+
+impl<T: Serialize> IntoHandlerResponse for ApiResponse<T> {
+    fn into_handler_response(self) -> HandlerResponse {
+        match serde_json::to_value(&self) {
+            Ok(body) => HandlerResponse::new(StatusCode::OK, body),
+            Err(err) => {
+                tracing::error!("Failed to serialize response: {}", err);
+                serialization_failure().into_handler_response()
+            }
+        }
+    }
+}
+
+impl<T: Serialize> IntoHandlerResponse for Json<T> {
+    fn into_handler_response(self) -> HandlerResponse {
+        match serde_json::to_value(&self.0) {
+            Ok(body) => HandlerResponse::new(StatusCode::OK, body),
+            Err(err) => {
+                tracing::error!("Failed to serialize response: {}", err);
+                serialization_failure().into_handler_response()
+            }
+        }
+    }
+}
+
+impl<T: Serialize> IntoHandlerResponse for Created<T> {
+    fn into_handler_response(self) -> HandlerResponse {
+        Json(self.1)
+            .into_handler_response()
+            .map_success(|response| response.with_status(StatusCode::CREATED).with_header(LOCATION, self.0))
+    }
+}
+
+impl<T: Serialize> IntoHandlerResponse for Accepted<T> {
+    fn into_handler_response(self) -> HandlerResponse {
+        Json(self.0)
+            .into_handler_response()
+            .map_success(|response| response.with_status(StatusCode::ACCEPTED))
+    }
+}
+
+impl IntoHandlerResponse for NoContent {
+    fn into_handler_response(self) -> HandlerResponse {
+        HandlerResponse::new(StatusCode::NO_CONTENT, serde_json::Value::Null)
+    }
+}
+
+impl IntoHandlerResponse for FerroxError {
+    fn into_handler_response(self) -> HandlerResponse {
+        let body = serde_json::to_value(self.envelope()).expect("ApiResponse always serializes");
+        HandlerResponse {
+            error: Some(Box::new(self.clone())),
+            ..HandlerResponse::new(self.status(), body)
+        }
+    }
+}
+
+impl<T, E> IntoHandlerResponse for Result<T, E>
+where
+    T: IntoHandlerResponse,
+    E: Into<FerroxError>,
+{
+    fn into_handler_response(self) -> HandlerResponse {
+        match self {
+            Ok(value) => value.into_handler_response(),
+            Err(err) => err.into().into_handler_response(),
+        }
+    }
+}
+
+// Used by `#[http_method]`; not public API
+#[doc(hidden)]
+pub mod __private {
+    use std::cell::Cell;
+
+    use super::*;
+
+    // Lets the handler code convert whatever the handler returns: method lookup
+    // on `&&&Respond` tries `IntoHandlerResponse` types first, then `Result`s of
+    // `Serialize` types, then `Serialize` types
+    pub struct Respond<T>(Cell<Option<T>>);
+
+    impl<T> Respond<T> {
+        pub fn new(value: T) -> Self {
+            Respond(Cell::new(Some(value)))
+        }
+
+        fn take(&self) -> T {
+            self.0.take().expect("a handler result is converted once")
+        }
+    }
+
+    pub trait ViaHandlerResponse {
+        fn respond(&self) -> HandlerResponse;
+    }
+
+    impl<T: IntoHandlerResponse> ViaHandlerResponse for &&Respond<T> {
+        fn respond(&self) -> HandlerResponse {
+            self.take().into_handler_response()
+        }
+    }
+
+    pub trait ViaResult {
+        fn respond(&self) -> HandlerResponse;
+    }
+
+    impl<T: Serialize, E: Into<FerroxError>> ViaResult for &Respond<Result<T, E>> {
+        fn respond(&self) -> HandlerResponse {
+            self.take().map(Json).into_handler_response()
+        }
+    }
+
+    pub trait ViaSerialize {
+        fn respond(&self) -> HandlerResponse;
+    }
+
+    impl<T: Serialize> ViaSerialize for Respond<T> {
+        fn respond(&self) -> HandlerResponse {
+            Json(self.take()).into_handler_response()
+        }
+    }
+}
+
+/// Serialize `body` as a JSON response with the given status.
+///
+/// Serialization failures (non-string map keys, failing `Serialize` impls) are
+/// logged and answered with a 500 envelope instead of a half-written body.
+/// Non-finite floats are not failures: serde_json writes them as `null`.
+pub fn json_response<T: Serialize>(status: StatusCode, body: &T) -> axum::response::Response {
+    match serde_json::to_vec(body) {
+        Ok(bytes) => (
+            status,
+            [(CONTENT_TYPE, "application/json")],
+            bytes,
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!("Failed to serialize response: {}", err);
+            serialization_failure().into_response()
+        }
+    }
+}
+
+// Marks responses rendered from a handler's error, for layers that undo its work
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Failed;
+
+pub(crate) fn serialization_failure() -> FerroxError {
+    FerroxError::Internal("Internal server error: response could not be serialized".to_string())
+}
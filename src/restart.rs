@@ -0,0 +1,181 @@
+//! Zero-downtime restarts: a running server hands its listening sockets to a
+//! new process, waits until that one serves them, then drains and stops.
+//!
+//! ```ignore
+//! // In the old process, e.g. on a deploy signal
+//! let successor = handle.hand_over(&mut restart::successor()?, Duration::from_secs(30)).await?;
+//! handle.graceful_shutdown(Duration::from_secs(30)).await?;
+//!
+//! // The new process binds the same addresses as usual
+//! Server::new().start("0.0.0.0:8080").await?;
+//! ```
+//!
+//! The sockets stay open throughout, so clients connecting mid-restart wait in
+//! the listen backlog instead of being refused. The successor is started with
+//! the sockets and a readiness channel, named in `FERROX_LISTEN_FDS` and
+//! `FERROX_READY_FD`; a `Listener::tcp` or `Listener::unix` binding an address
+//! it was handed serves the inherited socket instead of binding a new one, and
+//! once its listeners are served it reports ready. `hand_over` fails, killing
+//! the successor, if it exits or takes longer than the timeout to get ready,
+//! and the old process keeps serving.
+//!
+//! With `Server::restart_on_signal` and `handle_signals`, `SIGUSR2` hands over
+//! to a fresh start of the same binary, with the same arguments, and drains.
+//!
+//! `Listener::reuse_port` is the alternative without handing anything over:
+//! both processes bind the address with `SO_REUSEPORT` and the kernel spreads
+//! new connections between them until the old one stops.
+
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+const LISTEN_FDS: &str = "FERROX_LISTEN_FDS";
+const READY_FD: &str = "FERROX_READY_FD";
+// The process that handed the sockets over, so its grandchildren do not take them too
+const LISTEN_PARENT: &str = "FERROX_LISTEN_PARENT";
+
+// Set once this process has handed its sockets over, so stopping does not remove
+// the Unix socket files its successor serves
+static HANDED_OVER: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn handed_over() -> bool {
+    HANDED_OVER.load(Ordering::SeqCst)
+}
+
+/// A command starting the running binary again, with the same arguments.
+pub fn successor() -> io::Result<Command> {
+    let mut command = Command::new(std::env::current_exe()?);
+    command.args(std::env::args_os().skip(1));
+    Ok(command)
+}
+
+// Start `command` with `sockets` and wait until it reports ready, returning its process id
+pub(crate) async fn hand_over(sockets: &[&OwnedFd], command: &mut Command, timeout: Duration) -> io::Result<u32> {
+    // Descriptors are closed on exec unless inheritable; these copies are made
+    // inheritable for the spawn and closed right after it
+    let inherited = sockets
+        .iter()
+        .map(|socket| {
+            let copy = socket.as_fd().try_clone_to_owned()?;
+            socket2::SockRef::from(&copy).set_cloexec(false)?;
+            Ok(copy)
+        })
+        .collect::<io::Result<Vec<OwnedFd>>>()?;
+    let (ready, successor_end) = UnixStream::pair()?;
+    socket2::SockRef::from(&successor_end).set_cloexec(false)?;
+
+    let fds: Vec<String> = inherited.iter().map(|fd| fd.as_raw_fd().to_string()).collect();
+    command
+        .env(LISTEN_FDS, fds.join(","))
+        .env(READY_FD, successor_end.as_raw_fd().to_string())
+        .env(LISTEN_PARENT, std::process::id().to_string());
+    let mut child = command.spawn()?;
+    drop(successor_end);
+    drop(inherited);
+
+    ready.set_nonblocking(true)?;
+    let mut ready = tokio::net::UnixStream::from_std(ready)?;
+    let mut byte = [0u8; 1];
+    let outcome = tokio::time::timeout(timeout, tokio::io::AsyncReadExt::read(&mut ready, &mut byte)).await;
+    let failure = match outcome {
+        Ok(Ok(1)) => {
+            HANDED_OVER.store(true, Ordering::SeqCst);
+            tracing::info!("Handed the listening sockets over to process {}", child.id());
+            return Ok(child.id());
+        }
+        Ok(Ok(_)) => "exited before it was ready".to_string(),
+        Ok(Err(err)) => format!("could not report readiness: {}", err),
+        Err(_) => format!("was not ready within {:?}", timeout),
+    };
+    let _ = child.kill();
+    let _ = child.wait();
+    Err(io::Error::other(format!("the successor process {}", failure)))
+}
+
+enum Inherited {
+    Tcp(std::net::TcpListener),
+    Unix(UnixListener),
+}
+
+// The sockets handed over by the parent process, until bound
+fn inherited() -> &'static Mutex<Vec<Inherited>> {
+    static INHERITED: OnceLock<Mutex<Vec<Inherited>>> = OnceLock::new();
+    INHERITED.get_or_init(|| Mutex::new(from_parent(LISTEN_FDS).unwrap_or_default().into_iter().filter_map(wrap).collect()))
+}
+
+// The descriptors named in `variable`, if the parent process set it for us
+fn from_parent(variable: &str) -> Option<Vec<RawFd>> {
+    let parent = std::env::var(LISTEN_PARENT).ok()?.parse::<u32>().ok()?;
+    if parent != std::os::unix::process::parent_id() {
+        return None;
+    }
+    let fds = std::env::var(variable).ok()?;
+    Some(fds.split(',').filter_map(|fd| fd.trim().parse().ok()).collect())
+}
+
+fn wrap(fd: RawFd) -> Option<Inherited> {
+    // SAFETY: the parent process passed this descriptor for us to own, and the
+    // list is read once
+    let tcp = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+    if tcp.local_addr().is_ok() {
+        return Some(Inherited::Tcp(tcp));
+    }
+    // SAFETY: ownership moves back out of the TCP listener above
+    let unix = unsafe { UnixListener::from_raw_fd(tcp.into_raw_fd()) };
+    match unix.local_addr() {
+        Ok(_) => Some(Inherited::Unix(unix)),
+        Err(_) => {
+            tracing::warn!("Ignoring inherited file descriptor {}, which is not a listening socket", fd);
+            None
+        }
+    }
+}
+
+// The inherited TCP socket bound to `addr`, if any
+pub(crate) fn take_tcp(addr: SocketAddr) -> Option<std::net::TcpListener> {
+    let mut inherited = inherited().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let index = inherited
+        .iter()
+        .position(|socket| matches!(socket, Inherited::Tcp(listener) if listener.local_addr().ok() == Some(addr)))?;
+    match inherited.swap_remove(index) {
+        Inherited::Tcp(listener) => Some(listener),
+        Inherited::Unix(_) => None,
+    }
+}
+
+// The inherited Unix socket bound to `path`, if any
+pub(crate) fn take_unix(path: &Path) -> Option<UnixListener> {
+    let mut inherited = inherited().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let index = inherited.iter().position(|socket| match socket {
+        Inherited::Unix(listener) => listener.local_addr().ok().is_some_and(|addr| addr.as_pathname() == Some(path)),
+        Inherited::Tcp(_) => false,
+    })?;
+    match inherited.swap_remove(index) {
+        Inherited::Unix(listener) => Some(listener),
+        Inherited::Tcp(_) => None,
+    }
+}
+
+// Tell the parent process this one serves the sockets it handed over
+pub(crate) fn notify_ready() {
+    static NOTIFIED: AtomicBool = AtomicBool::new(false);
+    let Some(fd) = from_parent(READY_FD).and_then(|fds| fds.first().copied()) else {
+        return;
+    };
+    if NOTIFIED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    // SAFETY: the parent process passed this descriptor for us to own, and
+    // NOTIFIED makes sure it is wrapped only once
+    let mut ready = unsafe { UnixStream::from_raw_fd(fd) };
+    if let Err(err) = ready.write_all(b"1") {
+        tracing::error!("Failed to tell the parent process this one is ready: {}", err);
+    }
+}
@@ -0,0 +1,190 @@
+// Startup validation of the registered routes. Two handlers for the same
+// method and path would otherwise shadow each other, and placeholders with
+// different names at the same position (`/users/:id` and `/users/:name`) make
+// the router panic without saying where either was declared.
+//
+// Also the route listing returned by `routes()` and served by `Server::debug_routes`.
+
+use std::fmt;
+
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Serialize;
+
+use crate::context::AppState;
+use crate::{ParamInfo, RouteKind, RouteRegistration};
+
+/// A route registered by `#[http_method]`, `#[websocket]` or `#[sse]`, as listed by `routes()`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RouteInfo {
+    pub method: &'static str,
+    /// Full path, including any `#[route_group]` prefix.
+    pub path: &'static str,
+    /// `http`, `websocket` or `sse`.
+    pub kind: &'static str,
+    /// Name of the handler function.
+    pub handler: &'static str,
+    /// `file:line` of the route attribute.
+    pub location: &'static str,
+    /// `#[middleware]` and `#[route_group]` middleware, outermost first: function
+    /// paths as written, or the layer type for `layer = ...` entries.
+    pub middleware: Vec<&'static str>,
+    /// `auth` scheme the route requires, if any.
+    pub auth: Option<&'static str>,
+    /// API version the handler serves, if it was registered with one.
+    pub version: Option<&'static str>,
+    /// The handler's doc comment, if it has one.
+    pub doc: Option<&'static str>,
+    /// `tag` the route is documented under, if any.
+    pub tag: Option<&'static str>,
+    /// Named registry the route belongs to, `None` for the default one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry: Option<&'static str>,
+    /// The handler's parameters, with their doc comments.
+    pub params: &'static [ParamInfo],
+}
+
+/// Every registered route, from every registry, sorted by path and then method.
+///
+/// Routes added through plain axum (`into_router` and nesting) are not included.
+pub fn routes() -> Vec<RouteInfo> {
+    let mut routes: Vec<RouteInfo> = inventory::iter::<RouteRegistration>
+        .into_iter()
+        .map(|registration| RouteInfo {
+            method: registration.method,
+            path: registration.path,
+            kind: match registration.handler {
+                RouteKind::Http(_) => "http",
+                RouteKind::WebSocket(_) => "websocket",
+                RouteKind::Sse(_) => "sse",
+            },
+            handler: registration.handler_name,
+            location: registration.location,
+            middleware: registration.middleware_names.to_vec(),
+            auth: registration.options.auth,
+            version: registration.options.version,
+            doc: Some(registration.doc).filter(|doc| !doc.is_empty()),
+            tag: registration.options.tag,
+            registry: registration.options.registry,
+            params: registration.params,
+        })
+        .collect();
+    routes.sort_by_key(|route| (route.path, route.method));
+    routes
+}
+
+// The routes of `registry`, or of the default registry for `None`
+pub(crate) fn registered(registry: Option<&str>) -> impl Iterator<Item = &'static RouteRegistration> + '_ {
+    inventory::iter::<RouteRegistration>
+        .into_iter()
+        .filter(move |registration| registration.options.registry == registry)
+}
+
+// `routes()`, narrowed to `registry`
+pub(crate) fn listed(registry: Option<&str>) -> Vec<RouteInfo> {
+    routes().into_iter().filter(|route| route.registry == registry).collect()
+}
+
+pub(crate) fn mount(router: Router<AppState>, path: &str, registry: Option<&str>) -> Router<AppState> {
+    let routes = listed(registry);
+    router.route(path, get(|| async { Json(routes) }))
+}
+
+/// Route definitions that cannot all be served, found when the router is built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteConflict {
+    conflicts: Vec<String>,
+}
+
+impl RouteConflict {
+    /// One description per conflicting pair, naming both definitions.
+    pub fn conflicts(&self) -> &[String] {
+        &self.conflicts
+    }
+}
+
+impl fmt::Display for RouteConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "conflicting route definitions:")?;
+        for conflict in &self.conflicts {
+            write!(f, "\n  {}", conflict)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RouteConflict {}
+
+pub(crate) fn check<'a>(registrations: impl IntoIterator<Item = &'a RouteRegistration>) -> Result<(), RouteConflict> {
+    let mut registrations: Vec<&RouteRegistration> = registrations.into_iter().collect();
+    // Registration order depends on the linker; report in source order instead
+    registrations.sort_by_key(|registration| (source_order(registration.location), registration.path, registration.method));
+
+    let mut conflicts = Vec::new();
+    for (index, first) in registrations.iter().enumerate() {
+        for second in &registrations[index + 1..] {
+            // Paths of the same shape are told apart by their constraints, when they differ
+            let constrained = first.options.constraints != second.options.constraints
+                && crate::constraints::shape(first.path) == crate::constraints::shape(second.path);
+            let conflict = match overlap(first.path, second.path) {
+                _ if constrained => continue,
+                Overlap::Same if first.method == second.method && first.options.version == second.options.version => {
+                    format!("duplicate route {} {}: {} and {}", first.method, first.path, site(first), site(second))
+                }
+                Overlap::Ambiguous => format!(
+                    "ambiguous paths {} {} ({}) and {} {} ({}); placeholders at the same position must have the same name",
+                    first.method,
+                    first.path,
+                    site(first),
+                    second.method,
+                    second.path,
+                    site(second)
+                ),
+                _ => continue,
+            };
+            conflicts.push(conflict);
+        }
+    }
+    if conflicts.is_empty() {
+        Ok(())
+    } else {
+        Err(RouteConflict { conflicts })
+    }
+}
+
+enum Overlap {
+    // Distinct paths the router can tell apart
+    None,
+    Same,
+    // Different placeholders after the same prefix, e.g. `/users/:id` and `/users/:name`
+    Ambiguous,
+}
+
+fn overlap(first: &str, second: &str) -> Overlap {
+    let mut first_segments = first.split('/');
+    let mut second_segments = second.split('/');
+    loop {
+        match (first_segments.next(), second_segments.next()) {
+            (None, None) => return Overlap::Same,
+            (Some(a), Some(b)) if a == b => continue,
+            (Some(a), Some(b)) if is_placeholder(a) && is_placeholder(b) => return Overlap::Ambiguous,
+            _ => return Overlap::None,
+        }
+    }
+}
+
+fn is_placeholder(segment: &str) -> bool {
+    segment.starts_with(':') || segment.starts_with('*')
+}
+
+// `file:line` as (file, line), so line 9 sorts before line 10
+fn source_order(location: &str) -> (&str, u32) {
+    match location.rsplit_once(':') {
+        Some((file, line)) => (file, line.parse().unwrap_or(0)),
+        None => (location, 0),
+    }
+}
+
+fn site(registration: &RouteRegistration) -> String {
+    format!("`{}` at {}", registration.handler_name, registration.location)
+}
@@ -0,0 +1,93 @@
+//! The tokio runtime the server builds for itself when started from a plain
+//! `main`, without `#[tokio::main]`.
+//!
+//! ```ignore
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     Server::new()
+//!         .runtime(RuntimeConfig::new().worker_threads(8).thread_name("api-worker").max_blocking_threads(64))
+//!         .bind(Listener::tcp("0.0.0.0:8080"))
+//!         .run_blocking()
+//! }
+//! ```
+//!
+//! `Server::run_blocking` builds the runtime, serves on it like `run` and shuts
+//! it down once the server stops; `start` and `run` keep using the runtime they
+//! are awaited on. Unset options keep tokio's defaults: a worker thread per CPU
+//! core and up to 512 blocking threads. The blocking pool runs synchronous
+//! handlers under a timeout and other `spawn_blocking` work; `blocking = true`
+//! handlers use `Server::blocking_threads` instead when set.
+
+use std::io;
+
+use tokio::runtime::{Builder, Runtime};
+
+/// Runtime settings for `Server::runtime`.
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeConfig {
+    current_thread: bool,
+    worker_threads: Option<usize>,
+    thread_name: Option<String>,
+    max_blocking_threads: Option<usize>,
+    thread_stack_size: Option<usize>,
+}
+
+impl RuntimeConfig {
+    /// A multi-threaded runtime with tokio's defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run every task on the thread calling `run_blocking`, without worker threads.
+    pub fn current_thread() -> Self {
+        RuntimeConfig { current_thread: true, ..Self::default() }
+    }
+
+    /// Number of threads running requests (one per CPU core by default).
+    pub fn worker_threads(mut self, threads: usize) -> Self {
+        self.worker_threads = Some(threads.max(1));
+        self
+    }
+
+    /// Name of the runtime's threads, as shown by debuggers and `top -H`.
+    pub fn thread_name(mut self, name: impl Into<String>) -> Self {
+        self.thread_name = Some(name.into());
+        self
+    }
+
+    /// Most threads in the blocking pool at once (512 by default).
+    pub fn max_blocking_threads(mut self, threads: usize) -> Self {
+        self.max_blocking_threads = Some(threads.max(1));
+        self
+    }
+
+    /// Stack size of the runtime's threads, in bytes.
+    pub fn thread_stack_size(mut self, bytes: usize) -> Self {
+        self.thread_stack_size = Some(bytes);
+        self
+    }
+
+    /// Build the runtime with every driver enabled, e.g. to run other work on it
+    /// alongside the server.
+    pub fn build(&self) -> io::Result<Runtime> {
+        let mut builder = match self.current_thread {
+            true => Builder::new_current_thread(),
+            false => Builder::new_multi_thread(),
+        };
+        builder.enable_all();
+        if let Some(threads) = self.worker_threads
+            && !self.current_thread
+        {
+            builder.worker_threads(threads);
+        }
+        if let Some(name) = &self.thread_name {
+            builder.thread_name(name);
+        }
+        if let Some(threads) = self.max_blocking_threads {
+            builder.max_blocking_threads(threads);
+        }
+        if let Some(bytes) = self.thread_stack_size {
+            builder.thread_stack_size(bytes);
+        }
+        builder.build()
+    }
+}
@@ -0,0 +1,274 @@
+//! Tasks run on a cron schedule or at a fixed interval while the server is up.
+//!
+//! ```ignore
+//! #[scheduled("0 */5 * * * *")]
+//! async fn purge_sessions(db: State<Db>) -> Result<(), sqlx::Error> {
+//!     db.purge_expired().await
+//! }
+//!
+//! Server::new().schedule("heartbeat", Schedule::every(Duration::from_secs(30)), || async {
+//!     ping().await
+//! });
+//! ```
+//!
+//! Cron expressions have six fields, `sec min hour day-of-month month
+//! day-of-week`, and an optional seventh for the year, evaluated in UTC.
+//! Interval tasks first run one period after the server starts. A run that
+//! overlaps the next scheduled time skips it rather than running twice at once.
+//! Tasks take `State<S>` parameters and return `()` or `Result<(), E>`; errors
+//! and panics are logged and the task runs again at its next time.
+//!
+//! Tasks start with `Server::start`. On graceful shutdown no new runs begin
+//! and running ones get the shutdown timeout to finish.
+
+use std::fmt::{self, Display};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::context::{AppState, State};
+
+/// Future of one scheduled run.
+pub type TaskFuture = BoxFuture<'static, Result<(), String>>;
+
+/// When a task runs.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    kind: ScheduleKind,
+}
+
+#[derive(Debug, Clone)]
+enum ScheduleKind {
+    Cron(Box<cron::Schedule>),
+    Every(Duration),
+}
+
+impl Schedule {
+    /// Run at the times a six or seven field cron expression (with seconds) matches, in UTC.
+    pub fn cron(expression: &str) -> Result<Self, ScheduleError> {
+        cron::Schedule::from_str(expression)
+            .map(|schedule| Schedule {
+                kind: ScheduleKind::Cron(Box::new(schedule)),
+            })
+            .map_err(|err| ScheduleError {
+                expression: expression.to_string(),
+                message: err.to_string(),
+            })
+    }
+
+    /// Run every `period`, starting one period from now.
+    pub fn every(period: Duration) -> Self {
+        Schedule {
+            kind: ScheduleKind::Every(period.max(Duration::from_millis(1))),
+        }
+    }
+
+    // Time until the next run, or `None` when a cron schedule has no future times
+    fn next_delay(&self) -> Option<Duration> {
+        match &self.kind {
+            ScheduleKind::Every(period) => Some(*period),
+            ScheduleKind::Cron(schedule) => {
+                let next = schedule.upcoming(chrono::Utc).next()?;
+                Some((next - chrono::Utc::now()).to_std().unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// A cron expression that does not parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleError {
+    expression: String,
+    message: String,
+}
+
+impl Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid cron expression {:?}: {}", self.expression, self.message)
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+/// What a task function may return: `()` or `Result<(), E>` for a displayable `E`.
+pub trait TaskOutput {
+    fn into_result(self) -> Result<(), String>;
+}
+
+impl TaskOutput for () {
+    fn into_result(self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl<E: Display> TaskOutput for Result<(), E> {
+    fn into_result(self) -> Result<(), String> {
+        self.map_err(|err| err.to_string())
+    }
+}
+
+/// A task function, called with the application state for each run.
+#[derive(Clone)]
+pub struct Task {
+    run: Arc<dyn Fn(AppState) -> TaskFuture + Send + Sync>,
+}
+
+impl Task {
+    pub fn from_async<F, Fut>(task: F) -> Self
+    where
+        F: Fn(AppState) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: TaskOutput,
+    {
+        Task {
+            run: Arc::new(move |state| {
+                let run = task(state);
+                Box::pin(async move { run.await.into_result() })
+            }),
+        }
+    }
+
+    /// A blocking task, run on the blocking thread pool.
+    pub fn from_sync<F, R>(task: F) -> Self
+    where
+        F: Fn(AppState) -> R + Send + Sync + 'static,
+        R: TaskOutput + Send + 'static,
+    {
+        let task = Arc::new(task);
+        Task {
+            run: Arc::new(move |state| {
+                let task = task.clone();
+                Box::pin(async move {
+                    match tokio::task::spawn_blocking(move || task(state)).await {
+                        Ok(output) => output.into_result(),
+                        Err(_) => Err("task panicked".to_string()),
+                    }
+                })
+            }),
+        }
+    }
+}
+
+/// The schedule given to `#[scheduled]`.
+#[derive(Debug, Clone, Copy)]
+pub enum ScheduleSpec {
+    /// `#[scheduled("<cron expression>")]`
+    Cron(&'static str),
+    /// `#[scheduled(every = "<duration>")]`
+    Every(Duration),
+}
+
+/// A task registered by `#[scheduled]`.
+pub struct TaskRegistration {
+    /// Name of the annotated function.
+    pub name: &'static str,
+    pub schedule: ScheduleSpec,
+    pub task: fn() -> Task,
+    /// `file:line` of the attribute, for error messages.
+    pub location: &'static str,
+}
+
+inventory::collect!(TaskRegistration);
+
+/// The state for a `State<S>` task parameter.
+pub fn state<S: Clone + Send + Sync + 'static>(state: &AppState) -> Result<State<S>, String> {
+    state.get::<S>().map(State).ok_or_else(|| {
+        format!(
+            "no state of type {} was registered with Server::with_state",
+            std::any::type_name::<S>()
+        )
+    })
+}
+
+// A named task with its schedule, registered by `#[scheduled]` or `Server::schedule`
+pub(crate) struct ScheduledTask {
+    pub(crate) name: String,
+    pub(crate) schedule: Schedule,
+    pub(crate) task: Task,
+}
+
+// The `#[scheduled]` tasks, failing on the first invalid cron expression
+pub(crate) fn registered() -> Result<Vec<ScheduledTask>, String> {
+    inventory::iter::<TaskRegistration>
+        .into_iter()
+        .map(|registration| {
+            let schedule = match registration.schedule {
+                ScheduleSpec::Cron(expression) => Schedule::cron(expression)
+                    .map_err(|err| format!("`{}` at {}: {}", registration.name, registration.location, err))?,
+                ScheduleSpec::Every(period) => Schedule::every(period),
+            };
+            Ok(ScheduledTask {
+                name: registration.name.to_string(),
+                schedule,
+                task: (registration.task)(),
+            })
+        })
+        .collect()
+}
+
+// The running tasks of one server
+pub(crate) struct Scheduler {
+    stop: watch::Sender<bool>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Scheduler {
+    pub(crate) fn start(tasks: Vec<ScheduledTask>, state: AppState) -> Option<Scheduler> {
+        if tasks.is_empty() {
+            return None;
+        }
+        let (stop, stopped) = watch::channel(false);
+        let tasks = tasks
+            .into_iter()
+            .map(|task| tokio::spawn(run(task, state.clone(), stopped.clone())))
+            .collect();
+        Some(Scheduler { stop, tasks })
+    }
+
+    // Start no new runs, and wait up to `timeout` for running ones; returns whether they finished
+    pub(crate) async fn stop(mut self, timeout: Duration) -> bool {
+        let _ = self.stop.send(true);
+        let finished = tokio::time::timeout(timeout, async {
+            for task in &mut self.tasks {
+                let _ = task.await;
+            }
+        })
+        .await
+        .is_ok();
+        if !finished {
+            tracing::warn!("Scheduled tasks did not finish within {:?}", timeout);
+            self.abort();
+        }
+        finished
+    }
+
+    pub(crate) fn abort(&self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+async fn run(task: ScheduledTask, state: AppState, mut stopped: watch::Receiver<bool>) {
+    tracing::info!(task = %task.name, "Scheduled task registered");
+    while let Some(delay) = task.schedule.next_delay() {
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            // A dropped `ServerHandle` leaves the server, and its tasks, running
+            Ok(()) = stopped.changed() => return,
+        }
+        let outcome = AssertUnwindSafe((task.task.run)(state.clone())).catch_unwind().await;
+        match outcome {
+            Ok(Ok(())) => tracing::debug!(task = %task.name, "Scheduled task finished"),
+            Ok(Err(message)) => tracing::error!(task = %task.name, "Scheduled task failed: {}", message),
+            Err(_) => tracing::error!(task = %task.name, "Scheduled task panicked"),
+        }
+    }
+}
@@ -0,0 +1,449 @@
+//! Cookie-backed sessions and signed or encrypted cookies.
+//!
+//! ```ignore
+//! Server::new().sessions(SessionConfig::new(&secret).store(MemoryStore::new()));
+//!
+//! #[http_method(POST, "/login")]
+//! async fn login(session: Session, body: Credentials) -> Result<Value, FerroxError> {
+//!     session.regenerate();
+//!     session.set("user_id", check(body).await?)?;
+//!     Ok(json!({}))
+//! }
+//! ```
+//!
+//! `Session` handler parameters hold the data stored under the session cookie's
+//! id. Values set during a request are saved to the `SessionStore` when the
+//! response is sent, and a new session's cookie goes out with it; requests that
+//! leave the session untouched send no cookie. The cookie only holds the id,
+//! signed (or encrypted with `encrypted(true)`) so clients cannot forge one.
+//!
+//! `Cookies` parameters read the request's cookies and add or remove cookies on
+//! the response, with `signed` and `private` variants using the same key.
+//! Store failures answer 500.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use axum::extract::Request;
+use axum::http::header::{COOKIE, SET_COOKIE};
+use axum::http::{HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::Router;
+use cookie::{CookieJar, Key};
+use rand::RngCore;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+pub use cookie::{Cookie, SameSite};
+
+use crate::context::{AppState, RequestContext};
+use crate::error::FerroxError;
+use crate::error_response;
+
+#[cfg(feature = "redis")]
+pub mod redis;
+
+/// The values stored in one session.
+pub type SessionData = Map<String, Value>;
+
+/// Future returned by [`SessionStore`] methods.
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, FerroxError>> + Send + 'a>>;
+
+/// Where session data lives between requests, keyed by session id.
+pub trait SessionStore: Send + Sync + 'static {
+    /// The data saved under `id`, or `None` if there is none or it expired.
+    fn load<'a>(&'a self, id: &'a str) -> StoreFuture<'a, Option<SessionData>>;
+
+    /// Save `data` under `id`, expiring after `ttl`.
+    fn save<'a>(&'a self, id: &'a str, data: &'a SessionData, ttl: Duration) -> StoreFuture<'a, ()>;
+
+    /// Forget the data under `id`.
+    fn delete<'a>(&'a self, id: &'a str) -> StoreFuture<'a, ()>;
+}
+
+/// Sessions kept in process memory; they are lost on restart and not shared
+/// between instances.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    sessions: Arc<Mutex<HashMap<String, (SessionData, Instant)>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load<'a>(&'a self, id: &'a str) -> StoreFuture<'a, Option<SessionData>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let data = match sessions.get(id) {
+            Some((data, expires)) if *expires > Instant::now() => Some(data.clone()),
+            Some(_) => {
+                sessions.remove(id);
+                None
+            }
+            None => None,
+        };
+        Box::pin(async move { Ok(data) })
+    }
+
+    fn save<'a>(&'a self, id: &'a str, data: &'a SessionData, ttl: Duration) -> StoreFuture<'a, ()> {
+        let mut sessions = self.sessions.lock().unwrap();
+        // Drop expired sessions as new ones arrive, so abandoned ones do not pile up
+        let now = Instant::now();
+        sessions.retain(|_, (_, expires)| *expires > now);
+        sessions.insert(id.to_string(), (data.clone(), now + ttl));
+        Box::pin(async { Ok(()) })
+    }
+
+    fn delete<'a>(&'a self, id: &'a str) -> StoreFuture<'a, ()> {
+        self.sessions.lock().unwrap().remove(id);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// How sessions are stored and their cookie is sent; pass it to `Server::sessions`.
+///
+/// By default sessions live in a `MemoryStore` for 24 hours, under a signed,
+/// `HttpOnly`, `Secure`, `SameSite=Lax` cookie named `ferrox_session`.
+#[derive(Clone)]
+pub struct SessionConfig {
+    key: Key,
+    store: Arc<dyn SessionStore>,
+    cookie_name: String,
+    max_age: Duration,
+    encrypted: bool,
+    secure: bool,
+    same_site: SameSite,
+    path: String,
+    domain: Option<String>,
+}
+
+impl SessionConfig {
+    /// Sign cookies with a key derived from `secret`.
+    ///
+    /// Panics if `secret` is shorter than 32 bytes; it should be random and kept
+    /// out of source control.
+    pub fn new(secret: &[u8]) -> Self {
+        assert!(secret.len() >= 32, "session secrets must be at least 32 bytes long");
+        SessionConfig {
+            key: Key::derive_from(secret),
+            store: Arc::new(MemoryStore::new()),
+            cookie_name: "ferrox_session".to_string(),
+            max_age: Duration::from_secs(24 * 60 * 60),
+            encrypted: false,
+            secure: true,
+            same_site: SameSite::Lax,
+            path: "/".to_string(),
+            domain: None,
+        }
+    }
+
+    /// Keep session data in `store` instead of memory.
+    pub fn store(mut self, store: impl SessionStore) -> Self {
+        self.store = Arc::new(store);
+        self
+    }
+
+    pub fn cookie_name(mut self, name: &str) -> Self {
+        self.cookie_name = name.to_string();
+        self
+    }
+
+    /// How long a session lasts after it was last changed (defaults to 24 hours).
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Encrypt the session cookie rather than only signing it.
+    pub fn encrypted(mut self, encrypted: bool) -> Self {
+        self.encrypted = encrypted;
+        self
+    }
+
+    /// Send the cookie over HTTPS only (on by default); turn off for local HTTP development.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = same_site;
+        self
+    }
+
+    /// Cookie `Path` (defaults to `/`).
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
+    /// Cookie `Domain`, to share the session with subdomains.
+    pub fn domain(mut self, domain: &str) -> Self {
+        self.domain = Some(domain.to_string());
+        self
+    }
+
+    // The session cookie carrying `id`, or its removal when `id` is `None`
+    fn cookie(&self, id: Option<String>) -> Cookie<'static> {
+        let mut cookie = Cookie::build((self.cookie_name.clone(), id.clone().unwrap_or_default()))
+            .path(self.path.clone())
+            .http_only(true)
+            .secure(self.secure)
+            .same_site(self.same_site);
+        if let Some(domain) = &self.domain {
+            cookie = cookie.domain(domain.clone());
+        }
+        let mut cookie = cookie.build();
+        match id {
+            Some(_) => cookie.set_max_age(cookie::time::Duration::seconds(self.max_age.as_secs() as i64)),
+            None => cookie.make_removal(),
+        }
+        cookie
+    }
+}
+
+/// The current request's session, taken as a handler parameter.
+///
+/// Clones share the same session.
+#[derive(Clone)]
+pub struct Session {
+    state: Arc<Mutex<SessionState>>,
+}
+
+#[derive(Default)]
+struct SessionState {
+    // `None` until the session is first saved
+    id: Option<String>,
+    data: SessionData,
+    changed: bool,
+    regenerate: bool,
+    destroyed: bool,
+}
+
+impl Session {
+    fn lock(&self) -> MutexGuard<'_, SessionState> {
+        self.state.lock().unwrap()
+    }
+
+    /// The value under `key`, if it is set and deserializes into `T`.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let value = self.lock().data.get(key).cloned()?;
+        serde_json::from_value(value).ok()
+    }
+
+    /// Store `value` under `key`.
+    pub fn set<T: Serialize>(&self, key: &str, value: T) -> Result<(), FerroxError> {
+        let value = serde_json::to_value(value)
+            .map_err(|err| FerroxError::Internal(format!("Cannot store session value `{}`: {}", key, err)))?;
+        let mut state = self.lock();
+        state.data.insert(key.to_string(), value);
+        state.changed = true;
+        state.destroyed = false;
+        Ok(())
+    }
+
+    /// Remove the value under `key`, returning it.
+    pub fn remove(&self, key: &str) -> Option<Value> {
+        let mut state = self.lock();
+        let value = state.data.remove(key);
+        state.changed |= value.is_some();
+        value
+    }
+
+    /// End the session: its data is deleted from the store and the cookie removed.
+    pub fn destroy(&self) {
+        let mut state = self.lock();
+        state.data.clear();
+        state.destroyed = true;
+    }
+
+    /// Move the data to a new session id, e.g. after logging in, so an id
+    /// obtained before cannot be used to take over the session.
+    pub fn regenerate(&self) {
+        let mut state = self.lock();
+        state.regenerate = true;
+        state.changed = true;
+    }
+
+    /// The session id, or `None` for a session not saved yet.
+    pub fn id(&self) -> Option<String> {
+        self.lock().id.clone()
+    }
+}
+
+/// The request's cookies and those to send back, taken as a handler parameter.
+#[derive(Clone)]
+pub struct Cookies {
+    jar: Arc<Mutex<CookieJar>>,
+    key: Key,
+}
+
+impl Cookies {
+    fn lock(&self) -> MutexGuard<'_, CookieJar> {
+        self.jar.lock().unwrap()
+    }
+
+    /// The value of the cookie `name`.
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.lock().get(name).map(|cookie| cookie.value().to_string())
+    }
+
+    /// The value of the signed cookie `name`, if its signature is valid.
+    pub fn signed(&self, name: &str) -> Option<String> {
+        self.lock().signed(&self.key).get(name).map(|cookie| cookie.value().to_string())
+    }
+
+    /// The value of the encrypted cookie `name`, if it decrypts.
+    pub fn private(&self, name: &str) -> Option<String> {
+        self.lock().private(&self.key).get(name).map(|cookie| cookie.value().to_string())
+    }
+
+    /// Send `cookie` with the response.
+    pub fn add(&self, cookie: Cookie<'static>) {
+        self.lock().add(cookie);
+    }
+
+    /// Send `cookie` with its value signed, for reading back with `signed`.
+    pub fn add_signed(&self, cookie: Cookie<'static>) {
+        self.lock().signed_mut(&self.key).add(cookie);
+    }
+
+    /// Send `cookie` with its value encrypted, for reading back with `private`.
+    pub fn add_private(&self, cookie: Cookie<'static>) {
+        self.lock().private_mut(&self.key).add(cookie);
+    }
+
+    /// Tell the client to delete the cookie `name`.
+    pub fn remove(&self, name: &str) {
+        let mut removal = Cookie::from(name.to_string());
+        removal.set_path("/");
+        self.lock().remove(removal);
+    }
+}
+
+/// The session for a `Session` handler parameter.
+///
+/// Answers 500 when sessions are not enabled with `Server::sessions`.
+pub fn session(ctx: &RequestContext) -> Result<Session, FerroxError> {
+    ctx.extensions.get::<Session>().cloned().ok_or_else(not_enabled)
+}
+
+/// The cookies for a `Cookies` handler parameter.
+///
+/// Answers 500 when sessions are not enabled with `Server::sessions`.
+pub fn cookies(ctx: &RequestContext) -> Result<Cookies, FerroxError> {
+    ctx.extensions.get::<Cookies>().cloned().ok_or_else(not_enabled)
+}
+
+fn not_enabled() -> FerroxError {
+    tracing::error!("Session and Cookies parameters need Server::sessions");
+    FerroxError::Internal("Internal server error: sessions are not enabled".to_string())
+}
+
+// Load the session before handlers run and save it, with any cookies, afterwards
+pub(crate) fn layer(router: Router<AppState>, config: SessionConfig) -> Router<AppState> {
+    let config = Arc::new(config);
+    router.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let config = config.clone();
+        async move {
+            match handle(&config, request, next).await {
+                Ok(response) => response,
+                Err(err) => {
+                    tracing::error!("Session store failed: {}", err.message());
+                    error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
+                }
+            }
+        }
+    }))
+}
+
+async fn handle(config: &SessionConfig, mut request: Request, next: Next) -> Result<Response, FerroxError> {
+    let mut jar = CookieJar::new();
+    for header in request.headers().get_all(COOKIE) {
+        let Ok(header) = header.to_str() else { continue };
+        for cookie in Cookie::split_parse_encoded(header.to_string()).flatten() {
+            jar.add_original(cookie);
+        }
+    }
+
+    let id = match config.encrypted {
+        true => jar.private(&config.key).get(&config.cookie_name),
+        false => jar.signed(&config.key).get(&config.cookie_name),
+    }
+    .map(|cookie| cookie.value().to_string());
+    let mut state = SessionState::default();
+    if let Some(id) = id
+        && let Some(data) = config.store.load(&id).await?
+    {
+        state.id = Some(id);
+        state.data = data;
+    }
+
+    let session = Session {
+        state: Arc::new(Mutex::new(state)),
+    };
+    let cookies = Cookies {
+        jar: Arc::new(Mutex::new(jar)),
+        key: config.key.clone(),
+    };
+    request.extensions_mut().insert(session.clone());
+    request.extensions_mut().insert(cookies.clone());
+    let mut response = next.run(request).await;
+
+    let cookie = save(config, &session).await?;
+    let mut jar = cookies.lock();
+    match (cookie, config.encrypted) {
+        (Some(cookie), true) => jar.private_mut(&config.key).add(cookie),
+        (Some(cookie), false) => jar.signed_mut(&config.key).add(cookie),
+        (None, _) => {}
+    }
+    for cookie in jar.delta() {
+        if let Ok(value) = HeaderValue::from_str(&cookie.encoded().to_string()) {
+            response.headers_mut().append(SET_COOKIE, value);
+        }
+    }
+    Ok(response)
+}
+
+// Persist the session's changes, returning the session cookie to send if it changed
+async fn save(config: &SessionConfig, session: &Session) -> Result<Option<Cookie<'static>>, FerroxError> {
+    let (id, data, destroyed, regenerate, changed) = {
+        let state = session.lock();
+        (state.id.clone(), state.data.clone(), state.destroyed, state.regenerate, state.changed)
+    };
+    if destroyed {
+        if let Some(id) = &id {
+            config.store.delete(id).await?;
+        }
+        return Ok(id.map(|_| config.cookie(None)));
+    }
+    if !changed {
+        return Ok(None);
+    }
+    let new_id = match id {
+        Some(id) if !regenerate => id,
+        previous => {
+            if let Some(previous) = previous {
+                config.store.delete(&previous).await?;
+            }
+            new_session_id()
+        }
+    };
+    config.store.save(&new_id, &data, config.max_age).await?;
+    Ok(Some(config.cookie(Some(new_id))))
+}
+
+// 256 random bits, hex encoded
+fn new_session_id() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
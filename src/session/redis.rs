@@ -0,0 +1,83 @@
+//! A `SessionStore` in Redis, shared by every instance of the service.
+//!
+//! ```ignore
+//! let store = RedisStore::connect("redis://127.0.0.1/").await?;
+//! Server::new().sessions(SessionConfig::new(&secret).store(store));
+//! ```
+//!
+//! Each session is one JSON string under `ferrox:session:<id>` (the prefix is
+//! configurable), expiring with the session. Requires the `redis` feature.
+
+use std::time::Duration;
+
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+
+use super::{SessionData, SessionStore, StoreFuture};
+use crate::error::FerroxError;
+
+/// Sessions in Redis, reconnecting automatically when the connection drops.
+#[derive(Clone)]
+pub struct RedisStore {
+    connection: ConnectionManager,
+    prefix: String,
+}
+
+impl RedisStore {
+    /// Connect to the Redis server at `url`, e.g. `redis://127.0.0.1/`.
+    pub async fn connect(url: &str) -> Result<Self, redis::RedisError> {
+        let client = redis::Client::open(url)?;
+        Ok(RedisStore::new(ConnectionManager::new(client).await?))
+    }
+
+    /// Use an existing connection.
+    pub fn new(connection: ConnectionManager) -> Self {
+        RedisStore {
+            connection,
+            prefix: "ferrox:session:".to_string(),
+        }
+    }
+
+    /// Prefix for session keys (defaults to `ferrox:session:`).
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    fn key(&self, id: &str) -> String {
+        format!("{}{}", self.prefix, id)
+    }
+}
+
+fn store_error(err: impl std::fmt::Display) -> FerroxError {
+    FerroxError::Internal(format!("Redis session store: {}", err))
+}
+
+impl SessionStore for RedisStore {
+    fn load<'a>(&'a self, id: &'a str) -> StoreFuture<'a, Option<SessionData>> {
+        Box::pin(async move {
+            let mut connection = self.connection.clone();
+            let value: Option<String> = connection.get(self.key(id)).await.map_err(store_error)?;
+            // Unreadable data counts as no session rather than failing every request
+            Ok(value.and_then(|value| serde_json::from_str(&value).ok()))
+        })
+    }
+
+    fn save<'a>(&'a self, id: &'a str, data: &'a SessionData, ttl: Duration) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let value = serde_json::to_string(data).map_err(store_error)?;
+            let mut connection = self.connection.clone();
+            connection
+                .set_ex::<_, _, ()>(self.key(id), value, ttl.as_secs().max(1))
+                .await
+                .map_err(store_error)
+        })
+    }
+
+    fn delete<'a>(&'a self, id: &'a str) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let mut connection = self.connection.clone();
+            connection.del::<_, ()>(self.key(id)).await.map_err(store_error)
+        })
+    }
+}
@@ -0,0 +1,316 @@
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::connection::ConnectionConfig;
+use crate::dynamic::{DynamicRoutes, RouteError};
+use crate::http2::Http2Config;
+use crate::jobs::JobQueue;
+use crate::lifecycle::{self, Hook};
+use crate::listener::{self, Bound};
+use crate::scheduler::Scheduler;
+
+/// Handle to a server started with `Server::start_in_background` or `Server::run_in_background`.
+///
+/// Dropping the handle leaves the server running.
+pub struct ServerHandle {
+    shutdown_tx: oneshot::Sender<()>,
+    task: JoinHandle<io::Result<()>>,
+    local_addrs: Vec<SocketAddr>,
+    // Stopped with the server, then drained after in-flight requests on graceful shutdown
+    scheduler: Option<Scheduler>,
+    jobs: Option<JobQueue>,
+    routes: DynamicRoutes,
+    // The admin endpoints' own listener, stopped with the server
+    admin: Option<Box<ServerHandle>>,
+    // Reloads the configuration file, stopped with the server
+    config_watch: Option<JoinHandle<()>>,
+    // Run once the server has stopped, however it stops
+    shutdown_hooks: Vec<Hook>,
+    // Copies of the listening sockets, for `hand_over`
+    #[cfg(unix)]
+    sockets: Vec<std::os::fd::OwnedFd>,
+}
+
+impl ServerHandle {
+    pub(crate) fn spawn(
+        listeners: Vec<Bound>,
+        app: axum::Router,
+        http2: &Http2Config,
+        connections: &ConnectionConfig,
+    ) -> Self {
+        let local_addrs = listeners.iter().filter_map(Bound::local_addr).collect();
+        #[cfg(unix)]
+        let sockets = listeners.iter().filter_map(|listener| listener.socket().ok()).collect();
+        let mut protocols = http2.protocols();
+        connections.configure(&mut protocols);
+        let connections = connections.clone();
+        let handle = Self::spawn_with(local_addrs, |shutdown_rx| {
+            listener::serve(listeners, app, protocols, connections, shutdown_rx)
+        });
+        #[cfg(unix)]
+        let handle = handle.with_sockets(sockets);
+        handle
+    }
+
+    // Run `serve` in a task; it should stop accepting connections once the receiver fires
+    pub(crate) fn spawn_with<F, Fut>(local_addrs: Vec<SocketAddr>, serve: F) -> Self
+    where
+        F: FnOnce(oneshot::Receiver<()>) -> Fut,
+        Fut: Future<Output = io::Result<()>> + Send + 'static,
+    {
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let task = tokio::spawn(serve(shutdown_rx));
+        Self {
+            shutdown_tx,
+            task,
+            local_addrs,
+            scheduler: None,
+            jobs: None,
+            routes: DynamicRoutes::default(),
+            admin: None,
+            config_watch: None,
+            shutdown_hooks: Vec::new(),
+            #[cfg(unix)]
+            sockets: Vec::new(),
+        }
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn with_sockets(mut self, sockets: Vec<std::os::fd::OwnedFd>) -> Self {
+        self.sockets = sockets;
+        self
+    }
+
+    /// The address of the first TCP listener, with the actual port when bound to port 0.
+    ///
+    /// `None` when the server only listens on Unix sockets.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addrs.first().copied()
+    }
+
+    /// The addresses of every TCP listener, in the order they were bound.
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        &self.local_addrs
+    }
+
+    pub(crate) fn with_scheduler(mut self, scheduler: Option<Scheduler>) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+
+    pub(crate) fn with_jobs(mut self, jobs: Option<JobQueue>) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// The address of the admin endpoints' own listener, if they have one.
+    pub fn admin_addr(&self) -> Option<SocketAddr> {
+        self.admin.as_ref().and_then(|admin| admin.local_addr())
+    }
+
+    pub(crate) fn with_admin(mut self, admin: Option<ServerHandle>) -> Self {
+        self.admin = admin.map(Box::new);
+        self
+    }
+
+    pub(crate) fn with_dynamic_routes(mut self, routes: DynamicRoutes) -> Self {
+        self.routes = routes;
+        self
+    }
+
+    /// Serve `method` requests to `path` with `handler` from now on; see `Server::dynamic_routes`.
+    pub fn add_route(&self, method: &str, path: &str, handler: crate::RouteHandler) -> Result<(), RouteError> {
+        self.routes.add_route(method, path, handler)
+    }
+
+    /// Stop serving a route added at runtime; `false` if there was none.
+    pub fn remove_route(&self, method: &str, path: &str) -> bool {
+        self.routes.remove_route(method, path)
+    }
+
+    pub(crate) fn with_config_watch(mut self, task: Option<JoinHandle<()>>) -> Self {
+        self.config_watch = task;
+        self
+    }
+
+    pub(crate) fn with_shutdown_hooks(mut self, hooks: Vec<Hook>) -> Self {
+        self.shutdown_hooks = hooks;
+        self
+    }
+
+    /// Start `successor`, e.g. `restart::successor()`, with this server's listening
+    /// sockets, the admin endpoints' included, and wait up to `timeout` until it
+    /// serves them; then `graceful_shutdown` lets this one drain. Returns the
+    /// successor's process id. See `ferrox::restart`.
+    ///
+    /// When the successor exits or is not ready in time, it is killed and this
+    /// server keeps serving.
+    #[cfg(unix)]
+    pub async fn hand_over(&self, successor: &mut std::process::Command, timeout: Duration) -> io::Result<u32> {
+        let admin = self.admin.iter().flat_map(|admin| &admin.sockets);
+        let sockets: Vec<_> = self.sockets.iter().chain(admin).collect();
+        crate::restart::hand_over(&sockets, successor, timeout).await
+    }
+
+    /// Stop immediately, dropping in-flight requests and cancelling scheduled and
+    /// background jobs, then run the shutdown hooks.
+    pub async fn shutdown(mut self) {
+        self.stop_admin().await;
+        if let Some(task) = &self.config_watch {
+            task.abort();
+        }
+        if let Some(scheduler) = &self.scheduler {
+            scheduler.abort();
+        }
+        if let Some(jobs) = &self.jobs {
+            jobs.abort();
+        }
+        self.task.abort();
+        let _ = (&mut self.task).await;
+        lifecycle::shutdown(self.shutdown_hooks).await;
+    }
+
+    /// Stop accepting connections and starting scheduled tasks, and wait up to
+    /// `timeout` for in-flight requests and running tasks, then for queued background
+    /// jobs. The shutdown hooks run afterwards, whether or not everything finished.
+    ///
+    /// Connections still open after the timeout are dropped and a `TimedOut` error is returned.
+    pub async fn graceful_shutdown(mut self, timeout: Duration) -> io::Result<()> {
+        let hooks = std::mem::take(&mut self.shutdown_hooks);
+        let drained = self.drain(timeout).await;
+        lifecycle::shutdown(hooks).await;
+        drained
+    }
+
+    async fn drain(mut self, timeout: Duration) -> io::Result<()> {
+        let deadline = Instant::now() + timeout;
+        self.stop_admin().await;
+        if let Some(task) = &self.config_watch {
+            task.abort();
+        }
+        let _ = self.shutdown_tx.send(());
+        // Close the listening sockets now, not once draining is over
+        #[cfg(unix)]
+        self.sockets.clear();
+        if let Some(scheduler) = self.scheduler.take()
+            && !scheduler.stop(timeout).await
+        {
+            tracing::warn!("Scheduled tasks were cancelled at shutdown");
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let served = match tokio::time::timeout(remaining, &mut self.task).await {
+            Ok(result) => result.map_err(io::Error::other)?,
+            Err(_) => {
+                self.task.abort();
+                Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("in-flight requests did not finish within {:?}", timeout),
+                ))
+            }
+        };
+        if let Some(jobs) = self.jobs.take()
+            && !jobs.shutdown(deadline.saturating_duration_since(Instant::now())).await
+        {
+            jobs.abort();
+            served?;
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("background jobs did not finish within {:?}", timeout),
+            ));
+        }
+        served
+    }
+
+    /// Wait until the server stops on its own (it normally runs forever), then run
+    /// the shutdown hooks.
+    pub async fn wait(mut self) -> io::Result<()> {
+        let served = (&mut self.task).await.map_err(io::Error::other);
+        self.stop_admin().await;
+        lifecycle::shutdown(self.shutdown_hooks).await;
+        served?
+    }
+
+    async fn stop_admin(&mut self) {
+        if let Some(admin) = self.admin.take() {
+            Box::pin(admin.shutdown()).await;
+        }
+    }
+
+    // Wait forever, or drain on SIGINT/SIGTERM when `handle_signals` is set,
+    // or on SIGUSR2 once a successor serves the sockets, under `restart_on_signal`
+    pub(crate) async fn run(self, handle_signals: bool, restart_on_signal: bool, shutdown_timeout: Duration) -> io::Result<()> {
+        if !handle_signals {
+            return self.wait().await;
+        }
+        #[cfg(unix)]
+        if restart_on_signal {
+            self.restart_on_signal(shutdown_timeout).await;
+            return self.graceful_shutdown(shutdown_timeout).await;
+        }
+        #[cfg(not(unix))]
+        let _ = restart_on_signal;
+        signal().await;
+        tracing::info!("Shutdown signal received, draining in-flight requests...");
+        self.graceful_shutdown(shutdown_timeout).await
+    }
+
+    // Resolve on a shutdown signal, or once SIGUSR2 has handed the sockets over
+    #[cfg(unix)]
+    async fn restart_on_signal(&self, timeout: Duration) {
+        use tokio::signal::unix::SignalKind;
+
+        let mut restart = match tokio::signal::unix::signal(SignalKind::user_defined2()) {
+            Ok(restart) => restart,
+            Err(_) => return signal().await,
+        };
+        loop {
+            tokio::select! {
+                _ = signal() => {
+                    tracing::info!("Shutdown signal received, draining in-flight requests...");
+                    return;
+                }
+                _ = restart.recv() => {
+                    let handed_over = match crate::restart::successor() {
+                        Ok(mut successor) => self.hand_over(&mut successor, timeout).await,
+                        Err(err) => Err(err),
+                    };
+                    match handed_over {
+                        Ok(pid) => {
+                            tracing::info!("Restarted as process {}, draining in-flight requests...", pid);
+                            return;
+                        }
+                        Err(err) => tracing::error!("Restart failed, still serving: {}", err),
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Resolves on Ctrl-C, or SIGTERM on Unix
+pub(crate) async fn signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut stream) => {
+                stream.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
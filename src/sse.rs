@@ -0,0 +1,214 @@
+//! Server-Sent Events routes registered with `#[sse]`.
+//!
+//! As with WebSockets, path, query and state parameters are extracted before
+//! the stream starts, so a bad request is answered with the usual JSON error
+//! envelope. The handler then returns an [`SseStream`] of [`SseEvent`]s, which
+//! is sent as `text/event-stream` with periodic keep-alive comments.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Path, Query, State as AxumState};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::IntoResponse;
+use axum::routing::{get, MethodRouter};
+use futures_util::stream::{self, BoxStream, Stream, StreamExt};
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::context::{AppState, RequestContext};
+use crate::dispatch::params_to_json;
+use crate::envelope::Responder;
+use crate::error::FerroxError;
+use crate::format::Format;
+use crate::response::{HandlerResponse, IntoHandlerResponse, NonObjectResponse};
+
+type OpenFuture = Pin<Box<dyn Future<Output = Result<SseStream, HandlerResponse>> + Send>>;
+type OpenFn = Arc<dyn Fn(RequestContext) -> OpenFuture + Send + Sync>;
+
+// Same interval axum uses by default
+const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Stream handler generated by `#[sse]`.
+pub struct SseHandler {
+    open: OpenFn,
+}
+
+impl SseHandler {
+    pub fn new<F, Fut>(open: F) -> Self
+    where
+        F: Fn(RequestContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<SseStream, HandlerResponse>> + Send + 'static,
+    {
+        SseHandler {
+            open: Arc::new(move |ctx| Box::pin(open(ctx))),
+        }
+    }
+}
+
+/// Conversion from an `#[sse]` handler's return type.
+///
+/// Implemented for `SseStream` and for `Result<SseStream, E>` where `E` converts
+/// into `FerroxError`, so a handler can refuse to open the stream.
+pub trait IntoSseStream {
+    fn into_sse_stream(self) -> Result<SseStream, HandlerResponse>;
+}
+
+impl IntoSseStream for SseStream {
+    fn into_sse_stream(self) -> Result<SseStream, HandlerResponse> {
+        Ok(self)
+    }
+}
+
+impl<E: Into<FerroxError>> IntoSseStream for Result<SseStream, E> {
+    fn into_sse_stream(self) -> Result<SseStream, HandlerResponse> {
+        self.map_err(|err| err.into().into_handler_response())
+    }
+}
+
+/// A stream of events returned by an `#[sse]` handler.
+pub struct SseStream {
+    events: BoxStream<'static, SseEvent>,
+    keep_alive: Option<Duration>,
+}
+
+impl SseStream {
+    /// Send every event `events` yields; the response ends with the stream.
+    pub fn new<S>(events: S) -> Self
+    where
+        S: Stream<Item = SseEvent> + Send + 'static,
+    {
+        SseStream {
+            events: events.boxed(),
+            keep_alive: Some(DEFAULT_KEEP_ALIVE),
+        }
+    }
+
+    /// A stream fed through the returned sender; it ends once every sender is dropped.
+    pub fn channel(buffer: usize) -> (mpsc::Sender<SseEvent>, Self) {
+        let (sender, receiver) = mpsc::channel(buffer);
+        let events = stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|event| (event, receiver))
+        });
+        (sender, SseStream::new(events))
+    }
+
+    /// Interval between keep-alive comments (15 seconds by default), or `None` to send none.
+    pub fn keep_alive(mut self, interval: Option<Duration>) -> Self {
+        self.keep_alive = interval;
+        self
+    }
+}
+
+/// One Server-Sent Event.
+///
+/// Line breaks that the format cannot carry are normalized rather than
+/// rejected: carriage returns in data become newlines, and are dropped from
+/// ids and event names along with newlines and NUL characters.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SseEvent {
+    data: Option<String>,
+    event: Option<String>,
+    id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl SseEvent {
+    /// An event carrying `data` as text.
+    pub fn data(data: impl Into<String>) -> Self {
+        SseEvent {
+            data: Some(data.into()),
+            ..SseEvent::default()
+        }
+    }
+
+    /// An event carrying `data` serialized as JSON.
+    pub fn json<T: Serialize>(data: &T) -> Result<Self, FerroxError> {
+        match serde_json::to_string(data) {
+            Ok(text) => Ok(SseEvent::data(text)),
+            Err(err) => {
+                tracing::error!("Failed to serialize event: {}", err);
+                Err(FerroxError::Internal(
+                    "Internal server error: event could not be serialized".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Event name, delivered to `addEventListener(name, ...)` listeners.
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    /// Event id, sent back by reconnecting clients as `Last-Event-ID`.
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// How long a client should wait before reconnecting.
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    fn into_event(self) -> Event {
+        let single_line = |value: String| value.replace(['\r', '\n', '\0'], "");
+        let mut event = Event::default();
+        if let Some(data) = self.data {
+            event = event.data(data.replace("\r\n", "\n").replace('\r', "\n"));
+        }
+        if let Some(name) = self.event {
+            event = event.event(single_line(name));
+        }
+        if let Some(id) = self.id {
+            event = event.id(single_line(id));
+        }
+        if let Some(retry) = self.retry {
+            event = event.retry(retry);
+        }
+        event
+    }
+}
+
+// Build the axum route for an `#[sse]` registration
+pub(crate) fn method_router(handler: SseHandler) -> MethodRouter<AppState> {
+    let open = handler.open;
+    get(
+        move |AxumState(state): AxumState<AppState>,
+              Path(path_params): Path<HashMap<String, String>>,
+              Query(query_params): Query<HashMap<String, String>>,
+              request: axum::extract::Request| async move {
+            let (parts, _) = request.into_parts();
+            let responder = Responder::of(&parts.extensions);
+            let error_context = responder.context(&parts);
+            let ctx = RequestContext::from_parts(
+                parts,
+                params_to_json(path_params),
+                params_to_json(query_params),
+                serde_json::Value::Null,
+                state,
+            );
+            match open(ctx).await {
+                Ok(stream) => {
+                    let events = stream.events.map(|event| Ok::<_, Infallible>(event.into_event()));
+                    let sse = Sse::new(events);
+                    match stream.keep_alive {
+                        Some(interval) => sse.keep_alive(KeepAlive::new().interval(interval)).into_response(),
+                        None => sse.into_response(),
+                    }
+                }
+                Err(response) => responder.handle(response, error_context.as_ref()).render(
+                    NonObjectResponse::PassThrough,
+                    &responder,
+                    Format::Json,
+                ),
+            }
+        },
+    )
+}
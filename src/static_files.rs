@@ -0,0 +1,308 @@
+//! Serving files from a directory next to the API, e.g. a frontend build.
+//!
+//! ```ignore
+//! Server::new()
+//!     .serve_static("/assets", "./public")
+//!     .static_files(StaticFiles::new("/", "./dist").cache_control("public, max-age=3600"));
+//! ```
+//!
+//! Files are served by tower-http's `ServeDir`, with `Range` and conditional
+//! requests. A directory is served by its index file, or listed when listings
+//! are enabled; a request for one without a trailing slash is redirected to
+//! it. Missing and hidden files (names starting with `.`) answer 404 with the
+//! usual error envelope, as do methods other than GET and HEAD under a `/`
+//! prefix, which stands in for the not-found fallback.
+//!
+//! With `Server::spa_fallback`, page navigations (`GET` requests accepting
+//! `text/html`) that match no route or file get a single-page app's index
+//! document instead, so client-side routes survive a reload. API clients
+//! asking for JSON still get the 404.
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use axum::extract::{OriginalUri, Request};
+use axum::body::Body;
+use axum::http::header::{ACCEPT, CACHE_CONTROL, LOCATION};
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::MethodRouter;
+use axum::Router;
+use tower::ServiceExt;
+use tower_http::services::{ServeDir, ServeFile};
+
+use crate::context::AppState;
+use crate::error_response;
+
+/// A directory served under a URL prefix; register it with `Server::static_files`.
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    prefix: String,
+    dir: PathBuf,
+    index_file: Option<String>,
+    listing: bool,
+    cache_control: Option<HeaderValue>,
+    precompressed: bool,
+}
+
+impl StaticFiles {
+    /// Serve the files under `dir` at `prefix`, with `index.html` as the index file.
+    pub fn new(prefix: impl Into<String>, dir: impl Into<PathBuf>) -> Self {
+        StaticFiles {
+            prefix: prefix.into(),
+            dir: dir.into(),
+            index_file: Some("index.html".to_string()),
+            listing: false,
+            cache_control: None,
+            precompressed: false,
+        }
+    }
+
+    /// File served for a directory, or `None` to serve none.
+    pub fn index_file(mut self, name: Option<&str>) -> Self {
+        self.index_file = name.map(str::to_string);
+        self
+    }
+
+    /// List the contents of directories without an index file (off by default).
+    pub fn directory_listing(mut self, enabled: bool) -> Self {
+        self.listing = enabled;
+        self
+    }
+
+    /// `Cache-Control` value sent with files, e.g. `public, max-age=31536000, immutable`.
+    ///
+    /// A value that is not a valid header value is logged and ignored.
+    pub fn cache_control(mut self, value: &str) -> Self {
+        match HeaderValue::from_str(value) {
+            Ok(value) => self.cache_control = Some(value),
+            Err(_) => tracing::warn!("Ignoring invalid Cache-Control value {:?}", value),
+        }
+        self
+    }
+
+    /// Serve `file.br` or `file.gz` in place of `file` to clients accepting those encodings.
+    pub fn precompressed(mut self, enabled: bool) -> Self {
+        self.precompressed = enabled;
+        self
+    }
+}
+
+// The index document of a single-page app, served by `Server::spa_fallback`
+#[derive(Debug, Clone)]
+pub(crate) struct SpaFallback {
+    index: Arc<PathBuf>,
+}
+
+impl SpaFallback {
+    pub(crate) fn new(index: PathBuf) -> Self {
+        SpaFallback { index: Arc::new(index) }
+    }
+
+    // A page navigation: a GET or HEAD whose `Accept` header names `text/html`.
+    // `*/*` alone does not count, so scripts and API clients keep their 404s.
+    fn wants(&self, method: &Method, headers: &HeaderMap) -> bool {
+        matches!(*method, Method::GET | Method::HEAD) && accepts_html(headers)
+    }
+
+    async fn serve(&self, method: Method, headers: HeaderMap) -> Response {
+        let mut request = Request::new(Body::empty());
+        *request.method_mut() = method;
+        *request.headers_mut() = headers;
+        let mut response = match ServeFile::new(self.index.as_ref()).oneshot(request).await {
+            Ok(response) => response.into_response(),
+            Err(never) => match never {},
+        };
+        if response.status() == StatusCode::NOT_FOUND {
+            tracing::error!("SPA index document {} does not exist", self.index.display());
+            return error_response(StatusCode::NOT_FOUND, "Not found".to_string());
+        }
+        // A new deploy must not be hidden behind a cached index
+        response.headers_mut().insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        response
+    }
+}
+
+fn accepts_html(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|range| {
+            let mut params = range.split(';').map(str::trim);
+            let media = params.next().unwrap_or("");
+            let refused = params.any(|param| {
+                param
+                    .strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .is_some_and(|q| q == 0.0)
+            });
+            media.eq_ignore_ascii_case("text/html") && !refused
+        })
+}
+
+// Answer page navigations reaching `route`, the not-found fallback, with the index document
+pub(crate) fn spa_route(route: MethodRouter<AppState>, spa: SpaFallback) -> MethodRouter<AppState> {
+    route.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let spa = spa.clone();
+        async move {
+            if spa.wants(request.method(), request.headers()) {
+                let (parts, _) = request.into_parts();
+                return spa.serve(parts.method, parts.headers).await;
+            }
+            next.run(request).await
+        }
+    }))
+}
+
+// Serve `config` on `router`; a root prefix takes over the not-found fallback,
+// answering page navigations for missing files with `spa` if there is one
+pub(crate) fn mount(router: Router<AppState>, config: StaticFiles, spa: Option<SpaFallback>) -> Router<AppState> {
+    let mut files = ServeDir::new(&config.dir).append_index_html_on_directories(false);
+    if config.precompressed {
+        files = files.precompressed_br().precompressed_gzip();
+    }
+    let prefix = config.prefix.trim_end_matches('/').to_string();
+    let config = Arc::new(config);
+    let service = Router::new()
+        .fallback_service(files)
+        .layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+            let (config, spa) = (config.clone(), spa.clone());
+            async move { serve(&config, spa, request, next).await }
+        }));
+    if prefix.is_empty() {
+        router.fallback_service(service)
+    } else {
+        router.nest_service(&prefix, service)
+    }
+}
+
+async fn serve(config: &StaticFiles, spa: Option<SpaFallback>, mut request: Request, next: Next) -> Response {
+    // Path below the prefix, and as requested
+    let path = request.uri().path().to_string();
+    let original = match request.extensions().get::<OriginalUri>() {
+        Some(OriginalUri(uri)) => uri.path().to_string(),
+        None => path.clone(),
+    };
+    // What a missing file answers with: the SPA index for a page navigation
+    let navigation = spa
+        .filter(|spa| spa.wants(request.method(), request.headers()))
+        .map(|spa| (spa, request.method().clone(), request.headers().clone()));
+    // Under a root prefix this is the not-found fallback, where other methods
+    // match nothing rather than a file that only allows GET
+    let root = config.prefix.trim_end_matches('/').is_empty();
+    if root && !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return error_response(StatusCode::NOT_FOUND, format!("Route {} not found", original));
+    }
+    let Some(relative) = sanitize(&path) else {
+        return missing(navigation, &original).await;
+    };
+    let target = config.dir.join(&relative);
+
+    if tokio::fs::metadata(&target).await.is_ok_and(|meta| meta.is_dir()) {
+        // Relative links in an index page or listing need the trailing slash
+        if !original.ends_with('/') {
+            return match HeaderValue::from_str(&format!("{}/", original)) {
+                Ok(location) => (StatusCode::PERMANENT_REDIRECT, [(LOCATION, location)]).into_response(),
+                Err(_) => not_found(&original),
+            };
+        }
+        let path = if path.ends_with('/') { path } else { format!("{}/", path) };
+        let index = config.index_file.as_ref().map(|name| (name, target.join(name)));
+        match index {
+            Some((name, file)) if tokio::fs::metadata(&file).await.is_ok_and(|meta| meta.is_file()) => {
+                let uri = format!("{}{}", path, name);
+                match uri.parse() {
+                    Ok(uri) => *request.uri_mut() = uri,
+                    Err(_) => return not_found(&original),
+                }
+            }
+            _ if config.listing => return listing(&target, &original).await,
+            _ => return missing(navigation, &original).await,
+        }
+    }
+
+    let mut response = next.run(request).await;
+    let status = response.status();
+    if status == StatusCode::NOT_FOUND {
+        return missing(navigation, &original).await;
+    }
+    if let Some(value) = &config.cache_control
+        && (status.is_success() || status == StatusCode::NOT_MODIFIED)
+    {
+        response.headers_mut().insert(CACHE_CONTROL, value.clone());
+    }
+    response
+}
+
+// The request path as a relative file path, or `None` if it could escape the
+// directory or names a hidden file such as `.env`
+fn sanitize(path: &str) -> Option<PathBuf> {
+    let decoded = percent_encoding::percent_decode_str(path).decode_utf8().ok()?;
+    let mut relative = PathBuf::new();
+    for component in Path::new(decoded.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) if !part.to_string_lossy().starts_with('.') => relative.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(relative)
+}
+
+async fn listing(dir: &Path, path: &str) -> Response {
+    let mut entries = Vec::new();
+    if let Ok(mut reader) = tokio::fs::read_dir(dir).await {
+        while let Ok(Some(entry)) = reader.next_entry().await {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            let is_dir = entry.file_type().await.is_ok_and(|kind| kind.is_dir());
+            entries.push((!is_dir, name));
+        }
+    }
+    // Directories first, then files, each by name
+    entries.sort();
+
+    let title = escape_html(&percent_encoding::percent_decode_str(path).decode_utf8_lossy());
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {title}</title></head>\n<body>\n<h1>Index of {title}</h1>\n<ul>\n"
+    );
+    if path != "/" {
+        page.push_str("<li><a href=\"../\">../</a></li>\n");
+    }
+    for (is_file, name) in entries {
+        let slash = if is_file { "" } else { "/" };
+        let href = percent_encoding::utf8_percent_encode(&name, percent_encoding::NON_ALPHANUMERIC);
+        page.push_str(&format!(
+            "<li><a href=\"{}{}\">{}{}</a></li>\n",
+            href,
+            slash,
+            escape_html(&name),
+            slash
+        ));
+    }
+    page.push_str("</ul>\n</body>\n</html>\n");
+    Html(page).into_response()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+async fn missing(navigation: Option<(SpaFallback, Method, HeaderMap)>, path: &str) -> Response {
+    match navigation {
+        Some((spa, method, headers)) => spa.serve(method, headers).await,
+        None => not_found(path),
+    }
+}
+
+fn not_found(path: &str) -> Response {
+    error_response(StatusCode::NOT_FOUND, format!("File {} not found", path))
+}
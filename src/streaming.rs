@@ -0,0 +1,640 @@
+//! Response bodies streamed chunk by chunk: NDJSON, CSV exports and file downloads.
+//!
+//! ```ignore
+//! #[http_method(GET, "/events.ndjson")]
+//! fn export_events(db: State<Db>) -> StreamingResponse {
+//!     StreamingResponse::ndjson(db.events())
+//! }
+//!
+//! #[http_method(GET, "/orders")]
+//! fn list_orders(db: State<Db>) -> StreamingResponse {
+//!     // A JSON array, NDJSON or CSV, as the `Accept` header asks
+//!     StreamingResponse::list(db.orders())
+//! }
+//!
+//! #[http_method(GET, "/exports/:id")]
+//! async fn export(id: u64, store: State<Store>) -> StreamingResponse {
+//!     let size = store.size(id).await;
+//!     StreamingResponse::sized(size, move |range| store.read(id, range)).etag(&store.version(id))
+//! }
+//!
+//! #[http_method(GET, "/reports/:id/download")]
+//! async fn download(id: u64) -> Result<StreamingResponse, FerroxError> {
+//!     let report = StreamingResponse::file(format!("reports/{}.pdf", id)).await?;
+//!     Ok(report.content_type("application/pdf").attachment(&format!("report-{}.pdf", id)))
+//! }
+//! ```
+//!
+//! The body is pulled from the stream only as fast as the client reads it, so a
+//! slow client slows the producer down instead of filling memory; `channel`
+//! gives a producer task the same backpressure through its sender. Streamed
+//! bodies are sent as they are, without the response envelope, and a stream
+//! that fails part way ends the response early, since its status was sent with
+//! the first chunk.
+//!
+//! `csv` writes a header row of the first item's field names, then a row per
+//! item, quoting fields with commas, quotes or line breaks as RFC 4180 has it;
+//! nested values are written as JSON. `list` sends `ndjson` to clients
+//! accepting `application/x-ndjson`, `csv` to those accepting `text/csv`, and
+//! a JSON array otherwise, serializing each item as it is sent.
+//!
+//! Files, `bytes` and `sized` bodies are sent with `Content-Length` and
+//! `Accept-Ranges: bytes`, and a `Range` header asking for one byte range is
+//! answered with 206 Partial Content and its `Content-Range`, or 416 when the
+//! range starts past the end. Requests for several ranges get the whole body.
+//! Files carry `Last-Modified` and an `ETag` from their size and modification
+//! time; a request whose `If-Range` names another version, by either, gets the
+//! whole body, so resumed downloads never splice two versions together. Other
+//! streams have no known length and ignore `Range`.
+
+use std::convert::Infallible;
+use std::io::SeekFrom;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::body::{Body, Bytes};
+use axum::http::header::{
+    ACCEPT, ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_RANGE,
+    LAST_MODIFIED, RANGE, VARY,
+};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::BoxError;
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::mpsc;
+
+use crate::error::FerroxError;
+use crate::response::{HandlerResponse, IntoHandlerResponse};
+
+// Bytes read from a file per chunk
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A response whose body is streamed; return it from a handler, or a `Result` of it.
+pub struct StreamingResponse {
+    headers: HeaderMap,
+    source: Source,
+}
+
+enum Source {
+    Stream(Body),
+    // A body of known length, opened for the byte range to send
+    Ranged { length: u64, open: Open },
+    // Items encoded in the format the request accepts
+    List(Box<dyn FnOnce(ListFormat) -> Body + Send>),
+}
+
+type Open = Box<dyn FnOnce(Range<u64>) -> Body + Send>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ListFormat {
+    Json,
+    Ndjson,
+    Csv,
+}
+
+impl ListFormat {
+    // The format `Accept` prefers; a JSON array when it names neither of the others
+    fn from_accept(accept: Option<&HeaderValue>) -> ListFormat {
+        let Some(accept) = accept.and_then(|value| value.to_str().ok()) else {
+            return ListFormat::Json;
+        };
+        let mut best = (ListFormat::Json, 0.0);
+        for entry in accept.split(',') {
+            let mut parts = entry.split(';');
+            let format = match parts.next().unwrap_or_default().trim().to_ascii_lowercase().as_str() {
+                "application/x-ndjson" | "application/ndjson" | "application/jsonl" => ListFormat::Ndjson,
+                "text/csv" => ListFormat::Csv,
+                "application/json" | "application/*" | "*/*" => ListFormat::Json,
+                _ => continue,
+            };
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if quality > best.1 {
+                best = (format, quality);
+            }
+        }
+        best.0
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            ListFormat::Json => "application/json",
+            ListFormat::Ndjson => "application/x-ndjson",
+            ListFormat::Csv => "text/csv; charset=utf-8",
+        }
+    }
+}
+
+impl StreamingResponse {
+    /// Send every chunk `chunks` yields, as `application/octet-stream` unless
+    /// `content_type` says otherwise.
+    pub fn new<S, B, E>(chunks: S) -> Self
+    where
+        S: Stream<Item = Result<B, E>> + Send + 'static,
+        B: Into<Bytes> + 'static,
+        E: Into<BoxError> + 'static,
+    {
+        StreamingResponse::from_source(Source::Stream(Body::from_stream(chunks)), "application/octet-stream")
+    }
+
+    /// Send each item of `items` as one line of JSON, as `application/x-ndjson`.
+    ///
+    /// An item that cannot be serialized is logged and ends the response.
+    pub fn ndjson<S, T>(items: S) -> Self
+    where
+        S: Stream<Item = T> + Send + 'static,
+        T: Serialize,
+    {
+        StreamingResponse::from_source(Source::Stream(ndjson_body(items)), ListFormat::Ndjson.content_type())
+    }
+
+    /// Send `items` as CSV, with a header row of the first item's field names,
+    /// as `text/csv; charset=utf-8`.
+    ///
+    /// Items are usually structs or maps; fields the first item lacks are left
+    /// out, and fields missing from later items are left empty. An item that
+    /// cannot be serialized is logged and ends the response.
+    pub fn csv<S, T>(items: S) -> Self
+    where
+        S: Stream<Item = T> + Send + 'static,
+        T: Serialize,
+    {
+        StreamingResponse::from_source(Source::Stream(csv_body(items)), ListFormat::Csv.content_type())
+    }
+
+    /// Send `items` as NDJSON or CSV when the `Accept` header asks for
+    /// `application/x-ndjson` or `text/csv`, and as a JSON array otherwise.
+    pub fn list<S, T>(items: S) -> Self
+    where
+        S: Stream<Item = T> + Send + 'static,
+        T: Serialize,
+    {
+        let encode = move |format| match format {
+            ListFormat::Json => json_array_body(items),
+            ListFormat::Ndjson => ndjson_body(items),
+            ListFormat::Csv => csv_body(items),
+        };
+        StreamingResponse {
+            headers: HeaderMap::new(),
+            source: Source::List(Box::new(encode)),
+        }
+    }
+
+    /// A response fed through the returned sender, which waits while `buffer`
+    /// chunks are unread; the body ends once every sender is dropped.
+    pub fn channel(buffer: usize) -> (mpsc::Sender<Bytes>, Self) {
+        let (sender, receiver) = mpsc::channel(buffer);
+        let chunks = stream::unfold(receiver, |mut receiver| async move {
+            let chunk = receiver.recv().await?;
+            Some((Ok::<_, Infallible>(chunk), receiver))
+        });
+        (sender, StreamingResponse::new(chunks))
+    }
+
+    /// Send `body`, already in memory, as `application/octet-stream` unless
+    /// `content_type` says otherwise, answering `Range` requests.
+    pub fn bytes(body: impl Into<Bytes>) -> Self {
+        let body = body.into();
+        let length = body.len() as u64;
+        StreamingResponse::sized(length, move |range| {
+            let part = body.slice(range.start as usize..range.end as usize);
+            stream::once(async move { Ok::<_, Infallible>(part) })
+        })
+    }
+
+    /// Send a `length` byte body whose parts `open` streams, as
+    /// `application/octet-stream` unless `content_type` says otherwise,
+    /// answering `Range` requests.
+    ///
+    /// `open` is called once, with the byte range to send: the whole body, or
+    /// the part a `Range` header asks for. Its stream must yield exactly that
+    /// many bytes, or the connection is closed. Set `etag` or `last_modified`
+    /// for `If-Range` to tell versions of the body apart.
+    pub fn sized<F, S, B, E>(length: u64, open: F) -> Self
+    where
+        F: FnOnce(Range<u64>) -> S + Send + 'static,
+        S: Stream<Item = Result<B, E>> + Send + 'static,
+        B: Into<Bytes> + 'static,
+        E: Into<BoxError> + 'static,
+    {
+        let open: Open = Box::new(move |range| Body::from_stream(open(range)));
+        StreamingResponse::from_source(Source::Ranged { length, open }, "application/octet-stream")
+    }
+
+    /// Stream the file at `path`, as `application/octet-stream` unless
+    /// `content_type` says otherwise, answering `Range` and `If-Range` requests.
+    ///
+    /// A missing file is answered with 404, and other failures to open it with 500.
+    pub async fn file(path: impl AsRef<Path>) -> Result<Self, FerroxError> {
+        let path = path.as_ref();
+        let open = async {
+            let file = tokio::fs::File::open(path).await?;
+            let metadata = file.metadata().await?;
+            Ok::<_, std::io::Error>((file, metadata))
+        };
+        match open.await {
+            Ok((file, metadata)) if metadata.is_file() => {
+                let length = metadata.len();
+                let source = Source::Ranged {
+                    length,
+                    open: Box::new(move |range| file_body(file, range)),
+                };
+                let mut response = StreamingResponse::from_source(source, "application/octet-stream");
+                if let Ok(modified) = metadata.modified() {
+                    let since_epoch = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+                    response = response.last_modified(modified).etag(&format!(
+                        "\"{:x}-{:x}-{:x}\"",
+                        length,
+                        since_epoch.as_secs(),
+                        since_epoch.subsec_nanos()
+                    ));
+                }
+                Ok(response)
+            }
+            Ok(_) => Err(FerroxError::NotFound("File not found".to_string())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                Err(FerroxError::NotFound("File not found".to_string()))
+            }
+            Err(err) => {
+                tracing::error!("Failed to open {}: {}", path.display(), err);
+                Err(FerroxError::Internal("Internal server error".to_string()))
+            }
+        }
+    }
+
+    /// Replace the `Content-Type`, e.g. `text/csv; charset=utf-8`.
+    pub fn content_type(mut self, content_type: &str) -> Self {
+        self.set(CONTENT_TYPE, content_type);
+        self
+    }
+
+    /// Set the `ETag`, quoted, e.g. `"v42"`.
+    pub fn etag(mut self, etag: &str) -> Self {
+        self.set(ETAG, etag);
+        self
+    }
+
+    /// Set `Last-Modified`.
+    pub fn last_modified(mut self, modified: SystemTime) -> Self {
+        self.set(LAST_MODIFIED, &httpdate::fmt_http_date(modified));
+        self
+    }
+
+    /// Have browsers save the body as `filename` instead of showing it.
+    pub fn attachment(mut self, filename: &str) -> Self {
+        let fallback: String = filename
+            .chars()
+            .map(|c| if c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\' { c } else { '_' })
+            .collect();
+        let mut disposition = format!("attachment; filename=\"{}\"", fallback);
+        // Clients that understand RFC 6266 use the exact name
+        if fallback != filename {
+            let encoded = percent_encoding::utf8_percent_encode(filename, percent_encoding::NON_ALPHANUMERIC);
+            disposition.push_str(&format!("; filename*=UTF-8''{}", encoded));
+        }
+        self.set(CONTENT_DISPOSITION, &disposition);
+        self
+    }
+
+    // A body already in memory, sent with its length
+    #[cfg(feature = "templates")]
+    pub(crate) fn full(body: impl Into<Bytes>, content_type: &str) -> Self {
+        StreamingResponse::from_source(Source::Stream(Body::from(body.into())), content_type)
+    }
+
+    fn from_source(source: Source, content_type: &str) -> Self {
+        let mut response = StreamingResponse {
+            headers: HeaderMap::new(),
+            source,
+        };
+        response.set(CONTENT_TYPE, content_type);
+        response
+    }
+
+    // Replace `name`; an invalid value is logged and skipped
+    fn set(&mut self, name: HeaderName, value: &str) {
+        match HeaderValue::from_str(value) {
+            Ok(value) => {
+                self.headers.insert(name, value);
+            }
+            Err(_) => tracing::warn!("Ignoring invalid {} header value {:?}", name, value),
+        }
+    }
+}
+
+impl IntoHandlerResponse for StreamingResponse {
+    fn into_handler_response(mut self) -> HandlerResponse {
+        let headers = std::mem::take(&mut self.headers);
+        headers.iter().fold(
+            HandlerResponse::streamed(Streamed {
+                source: Arc::new(Mutex::new(Some(self.source))),
+                request: None,
+            }),
+            |response, (name, value)| response.with_header(name, value),
+        )
+    }
+}
+
+// The body of a streamed `HandlerResponse`, taken once when it is sent; clones
+// share it, so only one of them gets the body
+#[derive(Clone)]
+pub(crate) struct Streamed {
+    source: Arc<Mutex<Option<Source>>>,
+    // Boxed to keep `HandlerResponse` small
+    pub(crate) request: Option<Box<StreamRequest>>,
+}
+
+// The request headers a streamed body is sent by
+#[derive(Clone, Default)]
+pub(crate) struct StreamRequest {
+    range: Option<HeaderValue>,
+    if_range: Option<HeaderValue>,
+    accept: Option<HeaderValue>,
+}
+
+impl StreamRequest {
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Option<Box<StreamRequest>> {
+        let request = StreamRequest {
+            range: headers.get(RANGE).cloned(),
+            if_range: headers.get(IF_RANGE).cloned(),
+            accept: headers.get(ACCEPT).cloned(),
+        };
+        (request.range.is_some() || request.accept.is_some()).then(|| Box::new(request))
+    }
+}
+
+impl std::fmt::Debug for Streamed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Streamed")
+    }
+}
+
+impl PartialEq for Streamed {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.source, &other.source)
+    }
+}
+
+impl Streamed {
+    // The response with `status` and `headers`
+    pub(crate) fn into_response(self, status: StatusCode, headers: HeaderMap) -> Response {
+        let source = self.source.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+        let request = self.request.map(|request| *request).unwrap_or_default();
+        // A range of another version of the body would be spliced into the client's copy
+        let range = request
+            .range
+            .filter(|_| request.if_range.as_ref().is_none_or(|validator| is_current(validator, &headers)));
+        let mut response = match source {
+            Some(Source::Stream(body)) => (status, body).into_response(),
+            Some(Source::Ranged { length, open }) => sized_response(status, length, open, range.as_ref()),
+            Some(Source::List(encode)) => {
+                let format = ListFormat::from_accept(request.accept.as_ref());
+                let mut response = (status, encode(format)).into_response();
+                let headers = response.headers_mut();
+                headers.insert(CONTENT_TYPE, HeaderValue::from_static(format.content_type()));
+                headers.insert(VARY, HeaderValue::from_static("accept"));
+                response
+            }
+            None => status.into_response(),
+        };
+        for (name, value) in &headers {
+            // Set by the range handling
+            if name != CONTENT_LENGTH {
+                response.headers_mut().insert(name, value.clone());
+            }
+        }
+        response
+    }
+}
+
+// Whether an `If-Range` validator names the version of the body in `headers`:
+// its strong `ETag`, or its exact `Last-Modified` date
+fn is_current(validator: &HeaderValue, headers: &HeaderMap) -> bool {
+    let Ok(validator) = validator.to_str().map(str::trim) else {
+        return false;
+    };
+    let header = |name| headers.get(name).and_then(|value: &HeaderValue| value.to_str().ok());
+    if validator.starts_with('"') || validator.starts_with("W/") {
+        return !validator.starts_with("W/") && header(ETAG).is_some_and(|etag| etag.trim() == validator);
+    }
+    match (httpdate::parse_http_date(validator), header(LAST_MODIFIED).map(httpdate::parse_http_date)) {
+        (Ok(date), Some(Ok(modified))) => date == modified,
+        _ => false,
+    }
+}
+
+fn sized_response(status: StatusCode, length: u64, open: Open, range: Option<&HeaderValue>) -> Response {
+    let (status, start, end) = match range.map(|range| byte_range(range, length)) {
+        Some(ByteRange::Partial(start, end)) if status == StatusCode::OK => (StatusCode::PARTIAL_CONTENT, start, end),
+        Some(ByteRange::Unsatisfiable) if status == StatusCode::OK => {
+            let mut response = FerroxError::Status(
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                "Requested range is outside the body".to_string(),
+            )
+            .into_response();
+            if let Ok(value) = HeaderValue::from_str(&format!("bytes */{}", length)) {
+                response.headers_mut().insert(CONTENT_RANGE, value);
+            }
+            return response;
+        }
+        _ => (status, 0, length),
+    };
+    let mut response = (status, open(start..end)).into_response();
+    let headers = response.headers_mut();
+    headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    headers.insert(CONTENT_LENGTH, HeaderValue::from(end - start));
+    if status == StatusCode::PARTIAL_CONTENT
+        && let Ok(value) = HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end - 1, length))
+    {
+        headers.insert(CONTENT_RANGE, value);
+    }
+    response
+}
+
+// The bytes of `file` in `range`, read a chunk at a time
+fn file_body(file: tokio::fs::File, range: Range<u64>) -> Body {
+    let (start, remaining) = (range.start, range.end - range.start);
+    let chunks = stream::try_unfold((file, Some(start), remaining), |(mut file, seek, remaining)| async move {
+        if let Some(start) = seek.filter(|start| *start > 0) {
+            file.seek(SeekFrom::Start(start)).await?;
+        }
+        if remaining == 0 {
+            return Ok(None);
+        }
+        let mut chunk = vec![0; CHUNK_SIZE.min(remaining as usize)];
+        let read = file.read(&mut chunk).await?;
+        // The file shrank since it was opened
+        if read == 0 {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+        }
+        chunk.truncate(read);
+        Ok::<_, std::io::Error>(Some((Bytes::from(chunk), (file, None, remaining - read as u64))))
+    });
+    Body::from_stream(chunks)
+}
+
+enum ByteRange {
+    Full,
+    // Start and end, exclusive
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+// The part of a `length` byte body a `Range: bytes=...` header asks for; a header
+// that cannot be parsed, or asks for several ranges, asks for all of it
+fn byte_range(header: &HeaderValue, length: u64) -> ByteRange {
+    let Some(spec) = header.to_str().ok().and_then(|value| value.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Full;
+    };
+    let Some((first, last)) = spec.split_once('-').filter(|_| !spec.contains(',')) else {
+        return ByteRange::Full;
+    };
+    let (first, last) = (first.trim(), last.trim());
+    match (first.parse::<u64>(), last.parse::<u64>()) {
+        // The last `suffix` bytes
+        (Err(_), Ok(suffix)) if first.is_empty() => match suffix.min(length) {
+            0 => ByteRange::Unsatisfiable,
+            suffix => ByteRange::Partial(length - suffix, length),
+        },
+        (Ok(start), _) if start >= length => ByteRange::Unsatisfiable,
+        (Ok(start), Err(_)) if last.is_empty() => ByteRange::Partial(start, length),
+        (Ok(start), Ok(end)) if start <= end => ByteRange::Partial(start, end.saturating_add(1).min(length)),
+        _ => ByteRange::Full,
+    }
+}
+
+// Log a streamed item that cannot be serialized, which ends the response
+fn serialized<T: Serialize>(item: &T) -> Result<Value, serde_json::Error> {
+    serde_json::to_value(item).inspect_err(|err| tracing::error!("Failed to serialize streamed item: {}", err))
+}
+
+fn ndjson_body<S, T>(items: S) -> Body
+where
+    S: Stream<Item = T> + Send + 'static,
+    T: Serialize,
+{
+    let lines = items.map(|item| {
+        let mut line = serde_json::to_vec(&serialized(&item)?)?;
+        line.push(b'\n');
+        Ok::<_, serde_json::Error>(line)
+    });
+    Body::from_stream(lines)
+}
+
+// `[`, the items separated by commas, then `]`
+fn json_array_body<S, T>(items: S) -> Body
+where
+    S: Stream<Item = T> + Send + 'static,
+    T: Serialize,
+{
+    let mut first = true;
+    let elements = items.map(move |item| {
+        let mut element = match std::mem::take(&mut first) {
+            true => Vec::new(),
+            false => b",".to_vec(),
+        };
+        serde_json::to_writer(&mut element, &serialized(&item)?)?;
+        Ok::<_, serde_json::Error>(element)
+    });
+    let open = stream::once(std::future::ready(Ok(b"[".to_vec())));
+    let close = stream::once(std::future::ready(Ok(b"]".to_vec())));
+    Body::from_stream(open.chain(elements).chain(close))
+}
+
+fn csv_body<S, T>(items: S) -> Body
+where
+    S: Stream<Item = T> + Send + 'static,
+    T: Serialize,
+{
+    let mut columns: Option<Vec<String>> = None;
+    let rows = items.map(move |item| {
+        let value = serialized(&item)?;
+        let mut rows = String::new();
+        let columns = columns.get_or_insert_with(|| {
+            let names = match &value {
+                Value::Object(_) => field_names(&item),
+                _ => Vec::new(),
+            };
+            if !names.is_empty() {
+                write_row(&mut rows, names.iter().map(|name| name.as_str().into()));
+            }
+            names
+        });
+        match &value {
+            Value::Object(fields) => write_row(
+                &mut rows,
+                columns.iter().map(|column| fields.get(column).map(csv_field).unwrap_or_default()),
+            ),
+            Value::Array(values) => write_row(&mut rows, values.iter().map(csv_field)),
+            value => write_row(&mut rows, std::iter::once(csv_field(value))),
+        }
+        Ok::<_, serde_json::Error>(rows)
+    });
+    Body::from_stream(rows)
+}
+
+// The field names of a struct or map, in the order it serializes them, which a
+// `Value` object does not keep
+fn field_names<T: Serialize>(item: &T) -> Vec<String> {
+    struct Names(Vec<String>);
+
+    impl<'de> serde::Deserialize<'de> for Names {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_map(NamesVisitor)
+        }
+    }
+
+    struct NamesVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for NamesVisitor {
+        type Value = Names;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("an object")
+        }
+
+        fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Names, A::Error> {
+            let mut names = Vec::new();
+            while let Some((name, serde::de::IgnoredAny)) = map.next_entry::<String, _>()? {
+                names.push(name);
+            }
+            Ok(Names(names))
+        }
+    }
+
+    let names = serde_json::to_string(item).and_then(|text| serde_json::from_str::<Names>(&text));
+    names.map(|names| names.0).unwrap_or_default()
+}
+
+// A value as the text of one CSV field: strings as they are, nested values as JSON
+fn csv_field(value: &Value) -> std::borrow::Cow<'_, str> {
+    match value {
+        Value::Null => "".into(),
+        Value::String(text) => text.as_str().into(),
+        value => value.to_string().into(),
+    }
+}
+
+// Append a CRLF-terminated row, quoting the fields that need it
+fn write_row<'a>(out: &mut String, fields: impl Iterator<Item = std::borrow::Cow<'a, str>>) {
+    for (index, field) in fields.enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        if field.contains([',', '"', '\r', '\n']) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(&field);
+        }
+    }
+    out.push_str("\r\n");
+}
@@ -0,0 +1,118 @@
+//! Server-rendered HTML pages from Tera templates (the `templates` feature).
+//!
+//! ```ignore
+//! #[http_method(GET, "/users/:id")]
+//! async fn user_page(id: u64, db: State<Db>) -> Result<Template, FerroxError> {
+//!     let user = db.user(id).await?;
+//!     Ok(Template("user.html", json!({ "user": user })))
+//! }
+//!
+//! Server::new().templates(Templates::new("templates/**/*.html")?.reload(cfg!(debug_assertions)));
+//! ```
+//!
+//! A `Template` is rendered with any `Serialize` context whose fields become
+//! the template's variables, and sent as `text/html; charset=utf-8`, without
+//! the response envelope. Values are HTML-escaped in `.html`, `.htm` and `.xml`
+//! templates. A template that does not exist or fails to render is logged and
+//! answers 500.
+//!
+//! `Templates::new` parses every template up front, so syntax errors surface
+//! at startup. With `reload(true)` they are read again before each render, to
+//! pick up edits while developing. The templates registered with
+//! `Server::templates` are shared by every server in the process.
+
+use std::sync::{Arc, RwLock};
+
+use serde::Serialize;
+use serde_json::Value;
+use tera::{Context, Tera};
+
+use crate::error::FerroxError;
+use crate::response::{HandlerResponse, IntoHandlerResponse};
+use crate::streaming::StreamingResponse;
+
+static TEMPLATES: RwLock<Option<Arc<Templates>>> = RwLock::new(None);
+
+/// A set of templates; register it with `Server::templates`.
+pub struct Templates {
+    tera: RwLock<Tera>,
+    reload: bool,
+}
+
+impl Templates {
+    /// Load the templates matching `glob`, e.g. `templates/**/*.html`, named by
+    /// their path below the glob's directory.
+    pub fn new(glob: &str) -> Result<Self, tera::Error> {
+        Tera::new(glob).map(Templates::from_tera)
+    }
+
+    /// Use a `Tera` instance set up with its own filters, functions or templates.
+    pub fn from_tera(tera: Tera) -> Self {
+        Templates {
+            tera: RwLock::new(tera),
+            reload: false,
+        }
+    }
+
+    /// Read the templates again before each render (off by default).
+    pub fn reload(mut self, reload: bool) -> Self {
+        self.reload = reload;
+        self
+    }
+
+    /// The template `name` rendered with `context`.
+    pub fn render(&self, name: &str, context: &impl Serialize) -> Result<String, FerroxError> {
+        let context = Context::from_serialize(context).map_err(|err| render_error(name, &err))?;
+        if self.reload {
+            let mut tera = self.tera.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+            tera.full_reload().map_err(|err| render_error(name, &err))?;
+        }
+        let tera = self.tera.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        tera.render(name, &context).map_err(|err| render_error(name, &err))
+    }
+}
+
+// Tera's own message names only the outermost failure; its causes say what went wrong
+fn render_error(name: &str, err: &tera::Error) -> FerroxError {
+    let mut message = err.to_string();
+    let mut source = std::error::Error::source(err);
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    tracing::error!("Failed to render template `{}`: {}", name, message);
+    FerroxError::Internal("Failed to render the page".to_string())
+}
+
+pub(crate) fn install(templates: Templates) {
+    *TEMPLATES.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Arc::new(templates));
+}
+
+/// A page rendered from the template named by the first field, with the second
+/// as its context.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template<C = Value>(pub &'static str, pub C);
+
+impl<C: Serialize> Template<C> {
+    /// The page's HTML.
+    pub fn render(&self) -> Result<String, FerroxError> {
+        let templates = TEMPLATES.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+        match templates {
+            Some(templates) => templates.render(self.0, &self.1),
+            None => {
+                tracing::error!("Cannot render template `{}`: no templates are registered with Server::templates", self.0);
+                Err(FerroxError::Internal("Failed to render the page".to_string()))
+            }
+        }
+    }
+}
+
+impl<C: Serialize> IntoHandlerResponse for Template<C> {
+    fn into_handler_response(self) -> HandlerResponse {
+        match self.render() {
+            Ok(html) => StreamingResponse::full(html, "text/html; charset=utf-8").into_handler_response(),
+            Err(err) => err.into_handler_response(),
+        }
+    }
+}
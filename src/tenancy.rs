@@ -0,0 +1,330 @@
+//! Multi-tenancy: the tenant of each request, from its subdomain, a header or
+//! its path prefix.
+//!
+//! ```ignore
+//! Server::new().tenancy(
+//!     Tenancy::new()
+//!         .subdomain("example.com")
+//!         .header(HeaderName::from_static("x-tenant-id"))
+//!         .rate_limit(RateLimit::per_minute(1000))
+//!         .setting("theme", "light")
+//!         .tenant("acme", TenantConfig::new().rate_limit(RateLimit::per_minute(5000)).setting("theme", "dark"))
+//!         .tenant("globex", TenantConfig::new().with_state(globex_pool)),
+//! );
+//!
+//! #[http_method(GET, "/orders")]
+//! async fn list_orders(tenant: TenantId, db: State<PgPool>) -> Result<Vec<Order>, FerroxError> {
+//!     orders_of(&db, tenant.as_str()).await
+//! }
+//! ```
+//!
+//! The sources are tried in the order they were added, and the first to name
+//! a tenant wins: `subdomain` takes the label in front of the domain in the
+//! `Host` (`acme` in `acme.example.com`), `header` the header's value, and
+//! `path_prefix` the first path segment, which is then removed before routing,
+//! so `/acme/orders` is served by the `/orders` route.
+//!
+//! Tenants have to be registered with `tenant`: a request naming another
+//! answers 404, and a path prefix that is not a tenant is routed as it is.
+//! With `allow_unknown`, every name is a tenant, with the default settings;
+//! every first path segment too, under `path_prefix`.
+//!
+//! `TenantId` handler parameters receive the tenant, answering 400 for requests
+//! without one; `RequestContext::tenant` and `RequestContext::tenant_setting`
+//! read it and its settings, falling back to `Tenancy::setting`. State given to
+//! a tenant with `with_state` is what `State<S>` parameters and
+//! `RequestContext::state` get for its requests, in place of the state
+//! registered with `Server::with_state`. A tenant's rate limit, or else the
+//! `Tenancy::rate_limit`, counts all of its requests together, on top of any
+//! per-client limits, and uses the store of `Server::rate_limit`'s limiter.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use axum::extract::Request;
+use axum::http::header::HOST;
+use axum::http::{Extensions, HeaderName, Uri};
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::Router;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::context::{AppState, RequestContext};
+use crate::error::FerroxError;
+use crate::ratelimit::{RateLimit, RateLimiter};
+
+/// The tenant a request is for, as a handler parameter.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TenantId(Arc<str>);
+
+impl TenantId {
+    pub fn new(id: &str) -> Self {
+        TenantId(id.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for TenantId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for TenantId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// What one tenant overrides: its rate limit, settings and state.
+#[derive(Clone, Default)]
+pub struct TenantConfig {
+    rate_limit: Option<RateLimit>,
+    settings: Map<String, Value>,
+    state: AppState,
+}
+
+impl TenantConfig {
+    /// The defaults of the `Tenancy`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the tenant's requests, all clients together.
+    pub fn rate_limit(mut self, rate: RateLimit) -> Self {
+        self.rate_limit = Some(rate);
+        self
+    }
+
+    /// Set the setting `key` for the tenant.
+    pub fn setting(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.settings.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Give the tenant's requests `state` where others get the state of `Server::with_state`.
+    pub fn with_state<S: Clone + Send + Sync + 'static>(mut self, state: S) -> Self {
+        self.state.insert(state);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+    Subdomain(String),
+    Header(HeaderName),
+    PathPrefix,
+}
+
+/// How tenants are told apart, and their configuration; register it with `Server::tenancy`.
+#[derive(Clone, Default)]
+pub struct Tenancy {
+    sources: Vec<Source>,
+    tenants: HashMap<String, TenantConfig>,
+    allow_unknown: bool,
+    rate_limit: Option<RateLimit>,
+    settings: Map<String, Value>,
+}
+
+impl Tenancy {
+    /// No sources; add at least one.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take the tenant from the subdomain of `domain`, e.g. `acme` in `acme.example.com`.
+    pub fn subdomain(mut self, domain: &str) -> Self {
+        let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+        self.sources.push(Source::Subdomain(domain));
+        self
+    }
+
+    /// Take the tenant from the `name` header.
+    pub fn header(mut self, name: HeaderName) -> Self {
+        self.sources.push(Source::Header(name));
+        self
+    }
+
+    /// Take the tenant from the first path segment, and route the rest of the path.
+    pub fn path_prefix(mut self) -> Self {
+        self.sources.push(Source::PathPrefix);
+        self
+    }
+
+    /// Register the tenant `id`.
+    pub fn tenant(mut self, id: &str, config: TenantConfig) -> Self {
+        self.tenants.insert(id.to_string(), config);
+        self
+    }
+
+    /// Accept tenants that were not registered (off by default).
+    pub fn allow_unknown(mut self, allow: bool) -> Self {
+        self.allow_unknown = allow;
+        self
+    }
+
+    /// Limit the requests of each tenant without a rate limit of its own.
+    pub fn rate_limit(mut self, rate: RateLimit) -> Self {
+        self.rate_limit = Some(rate);
+        self
+    }
+
+    /// Set the setting `key` for tenants that do not set it.
+    pub fn setting(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.settings.insert(key.to_string(), value.into());
+        self
+    }
+}
+
+// A request's tenant, as recorded in its extensions
+pub(crate) struct Tenant {
+    id: TenantId,
+    settings: Map<String, Value>,
+    state: AppState,
+    limiter: Option<RateLimiter>,
+}
+
+impl Tenant {
+    pub(crate) fn id(&self) -> &TenantId {
+        &self.id
+    }
+
+    pub(crate) fn setting<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        serde_json::from_value(self.settings.get(key)?.clone()).ok()
+    }
+
+    pub(crate) fn state<S: Clone + Send + Sync + 'static>(&self) -> Option<S> {
+        self.state.get::<S>()
+    }
+}
+
+// The tenant recorded in a request's extensions
+pub(crate) fn current(extensions: &Extensions) -> Option<&Tenant> {
+    extensions.get::<Arc<Tenant>>().map(Arc::as_ref)
+}
+
+/// The request's tenant, for `TenantId` handler parameters.
+pub fn tenant_id(ctx: &RequestContext) -> Result<TenantId, FerroxError> {
+    ctx.tenant()
+        .cloned()
+        .ok_or_else(|| FerroxError::BadRequest("The request does not name a tenant".to_string()))
+}
+
+// Each tenant's settings merged over the defaults, and its rate limiter
+struct Tenants {
+    tenancy: Tenancy,
+    known: HashMap<String, Arc<Tenant>>,
+    // For tenants not registered, counted apart by their id
+    default_limiter: Option<RateLimiter>,
+}
+
+impl Tenants {
+    fn new(mut tenancy: Tenancy, global: Option<&RateLimiter>) -> Self {
+        let limiter = |rate: RateLimit, scope: String| match global {
+            Some(global) => global.for_route(rate, scope),
+            None => RateLimiter::new(rate),
+        };
+        let known = std::mem::take(&mut tenancy.tenants)
+            .into_iter()
+            .map(|(id, config)| {
+                let mut settings = tenancy.settings.clone();
+                settings.extend(config.settings);
+                let rate = config.rate_limit.or(tenancy.rate_limit);
+                let tenant = Tenant {
+                    id: TenantId::new(&id),
+                    settings,
+                    state: config.state,
+                    limiter: rate.map(|rate| limiter(rate, format!("tenant {}", id))),
+                };
+                (id, Arc::new(tenant))
+            })
+            .collect();
+        let default_limiter = tenancy.rate_limit.map(|rate| limiter(rate, "tenant".to_string()));
+        Tenants { tenancy, known, default_limiter }
+    }
+
+    fn is_tenant(&self, id: &str) -> bool {
+        self.tenancy.allow_unknown || self.known.contains_key(id)
+    }
+
+    // The tenant named by the first source that names one, and whether it was the path prefix
+    fn resolve(&self, request: &Request) -> Option<(String, bool)> {
+        self.tenancy.sources.iter().find_map(|source| match source {
+            Source::Subdomain(domain) => {
+                let host = request.headers().get(HOST).and_then(|value| value.to_str().ok());
+                let host = host.or_else(|| request.uri().host())?;
+                let host = host.rsplit_once(':').map_or(host, |(host, _)| host).to_ascii_lowercase();
+                let label = host.strip_suffix(domain.as_str())?.strip_suffix('.')?;
+                (!label.is_empty() && !label.contains('.')).then(|| (label.to_string(), false))
+            }
+            Source::Header(name) => {
+                let value = request.headers().get(name)?.to_str().ok()?.trim();
+                (!value.is_empty()).then(|| (value.to_string(), false))
+            }
+            Source::PathPrefix => {
+                let segment = request.uri().path().trim_start_matches('/').split('/').next()?;
+                (!segment.is_empty() && self.is_tenant(segment)).then(|| (segment.to_string(), true))
+            }
+        })
+    }
+
+    fn tenant(&self, id: &str) -> Option<Arc<Tenant>> {
+        if let Some(tenant) = self.known.get(id) {
+            return Some(tenant.clone());
+        }
+        self.tenancy.allow_unknown.then(|| {
+            Arc::new(Tenant {
+                id: TenantId::new(id),
+                settings: self.tenancy.settings.clone(),
+                state: AppState::default(),
+                limiter: self.default_limiter.clone(),
+            })
+        })
+    }
+}
+
+// Record each request's tenant, stripping its path prefix, before `router` routes it
+pub(crate) fn layer(router: Router, tenancy: Tenancy, global: Option<&RateLimiter>) -> Router {
+    let tenants = Arc::new(Tenants::new(tenancy, global));
+    let service = axum::middleware::from_fn(move |mut request: Request, next: Next| {
+        let tenants = tenants.clone();
+        async move {
+            let Some((id, prefixed)) = tenants.resolve(&request) else {
+                return next.run(request).await;
+            };
+            let Some(tenant) = tenants.tenant(&id) else {
+                return FerroxError::NotFound(format!("Unknown tenant {}", id)).into_response();
+            };
+            if let Some(uri) = prefixed.then(|| without_prefix(request.uri(), &id)).flatten() {
+                *request.uri_mut() = uri;
+            }
+            request.extensions_mut().insert(tenant.clone());
+            match tenant.limiter.clone() {
+                Some(limiter) => limiter.handle_as(format!("tenant:{}", id), request, next).await,
+                None => next.run(request).await,
+            }
+        }
+    });
+    Router::new().fallback_service(tower::Layer::layer(&service, router))
+}
+
+// `uri` without its first path segment, `prefix`
+fn without_prefix(uri: &Uri, prefix: &str) -> Option<Uri> {
+    let rest = uri.path().trim_start_matches('/').strip_prefix(prefix)?;
+    let path = if rest.is_empty() { "/" } else { rest };
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().ok()?);
+    Uri::from_parts(parts).ok()
+}
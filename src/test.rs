@@ -0,0 +1,222 @@
+//! In-process testing of routes without binding a port.
+//!
+//! ```ignore
+//! #[tokio::test]
+//! async fn creates_users() {
+//!     let client = TestClient::new();
+//!     let response = client.post("/users").json(&json!({ "name": "alice" })).await;
+//!     assert_eq!(response.status(), StatusCode::CREATED);
+//!     assert_eq!(response.json::<Value>()["name"], "alice");
+//! }
+//! ```
+//!
+//! The client serves requests through the same router `Server::start` would,
+//! built from every route registered in the test binary, so middleware,
+//! authentication and error envelopes behave as in production. Requests
+//! appear to come from `127.0.0.1`.
+
+use std::future::{Future, IntoFuture};
+use std::net::SocketAddr;
+use std::pin::Pin;
+
+use axum::body::{Body, Bytes};
+use axum::extract::ConnectInfo;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode};
+use axum::Router;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tower::Service;
+
+use crate::Server;
+
+/// Sends requests straight to a server's router.
+#[derive(Clone)]
+pub struct TestClient {
+    router: Router,
+}
+
+impl TestClient {
+    /// A client for a server with default settings.
+    pub fn new() -> Self {
+        Self::from_server(Server::new())
+    }
+
+    /// A client for `server`, with its state, layers and other settings.
+    pub fn from_server(server: Server) -> Self {
+        TestClient {
+            router: server.into_router(),
+        }
+    }
+
+    pub fn get(&self, path: &str) -> TestRequest {
+        self.request(Method::GET, path)
+    }
+
+    pub fn post(&self, path: &str) -> TestRequest {
+        self.request(Method::POST, path)
+    }
+
+    pub fn put(&self, path: &str) -> TestRequest {
+        self.request(Method::PUT, path)
+    }
+
+    pub fn patch(&self, path: &str) -> TestRequest {
+        self.request(Method::PATCH, path)
+    }
+
+    pub fn delete(&self, path: &str) -> TestRequest {
+        self.request(Method::DELETE, path)
+    }
+
+    pub fn head(&self, path: &str) -> TestRequest {
+        self.request(Method::HEAD, path)
+    }
+
+    pub fn options(&self, path: &str) -> TestRequest {
+        self.request(Method::OPTIONS, path)
+    }
+
+    /// A request with any method; `path` may include a query string.
+    pub fn request(&self, method: Method, path: &str) -> TestRequest {
+        TestRequest {
+            router: self.router.clone(),
+            method,
+            path: path.to_string(),
+            headers: HeaderMap::new(),
+            body: Bytes::new(),
+        }
+    }
+}
+
+impl Default for TestClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A request being built; `.await` it to send it.
+///
+/// Builder methods panic on invalid input, as a failed test should.
+pub struct TestRequest {
+    router: Router,
+    method: Method,
+    path: String,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl TestRequest {
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        let name = HeaderName::try_from(name).unwrap_or_else(|err| panic!("invalid header name {:?}: {}", name, err));
+        let value =
+            HeaderValue::try_from(value).unwrap_or_else(|err| panic!("invalid value for header {}: {}", name, err));
+        self.headers.append(name, value);
+        self
+    }
+
+    /// `Authorization: Bearer <token>`.
+    pub fn bearer(self, token: &str) -> Self {
+        self.header("authorization", &format!("Bearer {}", token))
+    }
+
+    /// Send `body` serialized as JSON.
+    pub fn json<T: Serialize>(mut self, body: &T) -> Self {
+        let body = serde_json::to_vec(body).unwrap_or_else(|err| panic!("failed to serialize request body: {}", err));
+        self.headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        self.body = Bytes::from(body);
+        self
+    }
+
+    /// Send `body` as an urlencoded form.
+    pub fn form<T: Serialize>(mut self, body: &T) -> Self {
+        let body = serde_urlencoded::to_string(body).unwrap_or_else(|err| panic!("failed to encode form body: {}", err));
+        self.headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/x-www-form-urlencoded"));
+        self.body = Bytes::from(body);
+        self
+    }
+
+    /// Send `body` as is, without setting a content type.
+    pub fn body(mut self, body: impl Into<Bytes>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    async fn send(self) -> TestResponse {
+        let mut request = Request::builder()
+            .method(self.method)
+            .uri(&self.path)
+            .body(Body::from(self.body))
+            .unwrap_or_else(|err| panic!("invalid request path {:?}: {}", self.path, err));
+        *request.headers_mut() = self.headers;
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 0))));
+
+        let mut router = self.router;
+        let Ok(()) = std::future::poll_fn(|cx| Service::<Request<Body>>::poll_ready(&mut router, cx)).await;
+        let Ok(response) = router.call(request).await;
+        let (parts, body) = response.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX)
+            .await
+            .unwrap_or_else(|err| panic!("failed to read response body: {}", err));
+        TestResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+        }
+    }
+}
+
+impl IntoFuture for TestRequest {
+    type Output = TestResponse;
+    type IntoFuture = Pin<Box<dyn Future<Output = TestResponse> + Send>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.send())
+    }
+}
+
+/// A response read in full.
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl TestResponse {
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// A header's value, if present and valid text.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    pub fn bytes(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// The body as text; panics if it is not UTF-8.
+    pub fn text(&self) -> &str {
+        std::str::from_utf8(&self.body).unwrap_or_else(|err| panic!("response body is not UTF-8: {}", err))
+    }
+
+    /// The body deserialized from JSON; panics with the body if that fails.
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(&self.body).unwrap_or_else(|err| {
+            panic!(
+                "response body is not the expected JSON ({}): {}",
+                err,
+                String::from_utf8_lossy(&self.body)
+            )
+        })
+    }
+}
@@ -0,0 +1,178 @@
+//! HTTPS serving with rustls, including certificate hot-reload.
+
+use axum::Router;
+use axum_server::accept::Accept;
+use axum_server::tls_rustls::{RustlsAcceptor, RustlsConfig};
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpStream;
+
+use crate::connection::{ConnectionConfig, Tuned, Tuning};
+use crate::http2::Http2Config;
+use crate::shutdown::ServerHandle;
+
+const DEFAULT_RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Certificate chain and private key for `Server::start_tls`.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    source: TlsSource,
+    reload_interval: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+enum TlsSource {
+    Files { cert: PathBuf, key: PathBuf },
+    Pem { cert: Vec<u8>, key: Vec<u8> },
+}
+
+impl TlsConfig {
+    /// Load PEM files from disk. They are checked for changes every 30s and
+    /// reloaded without dropping connections.
+    pub fn from_pem_files(cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        Self {
+            source: TlsSource::Files {
+                cert: cert.into(),
+                key: key.into(),
+            },
+            reload_interval: Some(DEFAULT_RELOAD_INTERVAL),
+        }
+    }
+
+    /// Use an in-memory PEM certificate chain and key.
+    pub fn from_pem(cert: impl Into<Vec<u8>>, key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            source: TlsSource::Pem {
+                cert: cert.into(),
+                key: key.into(),
+            },
+            reload_interval: None,
+        }
+    }
+
+    /// How often certificate files are checked for changes; `None` disables reloading.
+    /// Has no effect for in-memory PEM.
+    pub fn reload_interval(mut self, interval: Option<Duration>) -> Self {
+        self.reload_interval = interval;
+        self
+    }
+}
+
+pub(crate) async fn spawn(
+    listener: std::net::TcpListener,
+    app: Router,
+    tls: TlsConfig,
+    http2: &Http2Config,
+    connections: &ConnectionConfig,
+) -> io::Result<ServerHandle> {
+    // Reloading builds configs through rustls' process-wide default provider
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let rustls_config = match &tls.source {
+        TlsSource::Files { cert, key } => RustlsConfig::from_pem_file(cert, key).await?,
+        TlsSource::Pem { cert, key } => RustlsConfig::from_pem(cert.clone(), key.clone()).await?,
+    };
+    let alpn = http2.offers_alpn();
+    if !alpn {
+        http1_alpn(&rustls_config);
+    }
+
+    let reloader = match (&tls.source, tls.reload_interval) {
+        (TlsSource::Files { cert, key }, Some(interval)) => Some(tokio::spawn(watch_files(
+            rustls_config.clone(),
+            cert.clone(),
+            key.clone(),
+            interval,
+            alpn,
+        ))),
+        _ => None,
+    };
+
+    listener.set_nonblocking(true)?;
+    let local_addr = listener.local_addr()?;
+    #[cfg(unix)]
+    let socket = std::os::fd::AsFd::as_fd(&listener).try_clone_to_owned()?;
+    let handle = axum_server::Handle::new();
+    let acceptor = RustlsAcceptor::new(rustls_config).acceptor(TunedAcceptor(Tuning::new(connections)));
+    let mut server = axum_server::from_tcp(listener).acceptor(acceptor).handle(handle.clone());
+    http2.configure(server.http_builder());
+    connections.configure_auto(server.http_builder());
+
+    let spawned = ServerHandle::spawn_with(vec![local_addr], |shutdown_rx| async move {
+        let graceful = async move {
+            let _ = shutdown_rx.await;
+            handle.graceful_shutdown(None);
+        };
+        tokio::spawn(graceful);
+        let result = server.serve(app.into_make_service_with_connect_info::<std::net::SocketAddr>()).await;
+        if let Some(reloader) = reloader {
+            reloader.abort();
+        }
+        result
+    });
+    #[cfg(unix)]
+    let spawned = spawned.with_sockets(vec![socket]);
+    Ok(spawned)
+}
+
+// Applies the connection settings to accepted TCP streams, before the handshake
+#[derive(Clone)]
+struct TunedAcceptor(Tuning);
+
+impl<S: Send + 'static> Accept<TcpStream, S> for TunedAcceptor {
+    type Stream = Tuned<TcpStream>;
+    type Service = S;
+    type Future = Pin<Box<dyn Future<Output = io::Result<(Self::Stream, Self::Service)>> + Send>>;
+
+    fn accept(&self, stream: TcpStream, service: S) -> Self::Future {
+        let tuning = self.0.clone();
+        Box::pin(async move {
+            // At `max_connections`, hold the connection until another one closes
+            let slot = tuning.slot().await;
+            tuning.tune(&stream);
+            Ok((tuning.wrap(stream, slot), service))
+        })
+    }
+}
+
+// Offer only HTTP/1.1 through ALPN, in place of the default `h2` and `http/1.1`
+fn http1_alpn(config: &RustlsConfig) {
+    let mut server_config = (*config.get_inner()).clone();
+    server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    config.reload_from_config(Arc::new(server_config));
+}
+
+// Reload the certificate whenever either file's modification time changes
+async fn watch_files(config: RustlsConfig, cert: PathBuf, key: PathBuf, interval: Duration, alpn: bool) {
+    let mut last_seen = modified(&cert, &key).await;
+    loop {
+        tokio::time::sleep(interval).await;
+        let current = modified(&cert, &key).await;
+        if current == last_seen {
+            continue;
+        }
+        match config.reload_from_pem_file(&cert, &key).await {
+            Ok(()) => {
+                if !alpn {
+                    http1_alpn(&config);
+                }
+                tracing::info!("Reloaded TLS certificate from {}", cert.display());
+                last_seen = current;
+            }
+            // Keep serving the previous certificate; a half-written file is retried next tick
+            Err(err) => tracing::error!("Failed to reload TLS certificate: {}", err),
+        }
+    }
+}
+
+async fn modified(cert: &Path, key: &Path) -> (Option<SystemTime>, Option<SystemTime>) {
+    (mtime(cert).await, mtime(key).await)
+}
+
+async fn mtime(path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(path).await.and_then(|meta| meta.modified()).ok()
+}
@@ -0,0 +1,255 @@
+//! Constraints on typed `path`, `query` and `body` parameters, checked before the
+//! handler runs.
+//!
+//! ```ignore
+//! use ferrox::validate::Validate;
+//!
+//! #[derive(Deserialize, Validate)]
+//! struct CreateUser {
+//!     #[validate(length(min = 3, max = 32))]
+//!     name: String,
+//!     #[validate(email)]
+//!     email: String,
+//!     #[validate(range(min = 13))]
+//!     age: Option<u32>,
+//! }
+//!
+//! #[http_method(POST, "/users")]
+//! async fn create_user(body: CreateUser) -> ApiResponse<Value> { ... }
+//! ```
+//!
+//! A parameter whose type implements `Validate` is checked as soon as it is
+//! deserialized. Failures are answered with 422 and the messages for each field
+//! in `data`: `{"success": false, "data": {"name": ["length must be at least
+//! 3"]}, "message": "Validation failed"}`.
+//!
+//! The rules are `length(min, max)` for strings (in characters) and collections,
+//! `range(min, max)` for numbers, `email`, `custom = "path::to_fn"` for a
+//! `fn(&T) -> Result<(), String>`, and `nested` for fields that implement
+//! `Validate` themselves, whose errors are reported as `field.inner`. `Option`
+//! fields are only checked when present. Error keys follow `#[serde(rename)]` and
+//! `#[serde(rename_all)]`.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::error::FerroxError;
+
+pub use ferrox_macros::Validate;
+
+/// Checks a value's constraints; usually derived with `#[derive(Validate)]`.
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+/// The failed constraints of a value, as messages by field name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct ValidationErrors {
+    fields: BTreeMap<String, Vec<String>>,
+    // The error message in the request's locale, set by `Server::localization`
+    #[serde(skip)]
+    message: Option<String>,
+}
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a failed constraint on `field`.
+    pub fn add(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.fields.entry(field.into()).or_default().push(message.into());
+    }
+
+    /// Record the errors of a nested value under `field.`.
+    pub fn nest(&mut self, field: &str, errors: ValidationErrors) {
+        for (inner, messages) in errors.fields {
+            self.fields
+                .entry(format!("{}.{}", field, inner))
+                .or_default()
+                .extend(messages);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// The messages for `field`, if any of its constraints failed.
+    pub fn get(&self, field: &str) -> Option<&[String]> {
+        self.fields.get(field).map(Vec::as_slice)
+    }
+
+    /// Failed fields and their messages, in field name order.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.fields.iter().map(|(field, messages)| (field.as_str(), messages.as_slice()))
+    }
+
+    // The message of the 422 error sent for these errors
+    pub(crate) fn message(&self) -> &str {
+        self.message.as_deref().unwrap_or("Validation failed")
+    }
+
+    // These errors with `message` and each field's messages passed through `translate`
+    pub(crate) fn translated(self, message: String, translate: impl Fn(&str) -> String) -> Self {
+        let fields = self
+            .fields
+            .into_iter()
+            .map(|(field, messages)| (field, messages.iter().map(|message| translate(message)).collect()))
+            .collect();
+        ValidationErrors {
+            fields,
+            message: Some(message),
+        }
+    }
+
+    /// `Ok` when nothing failed.
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl From<ValidationErrors> for FerroxError {
+    fn from(errors: ValidationErrors) -> Self {
+        FerroxError::Validation(errors)
+    }
+}
+
+/// Things `length(min, max)` applies to.
+pub trait Length {
+    fn length(&self) -> usize;
+}
+
+impl Length for str {
+    fn length(&self) -> usize {
+        self.chars().count()
+    }
+}
+
+impl Length for String {
+    fn length(&self) -> usize {
+        self.as_str().length()
+    }
+}
+
+impl<T> Length for [T] {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> Length for Vec<T> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<K, V, S> Length for HashMap<K, V, S> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T, S> Length for HashSet<T, S> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<K, V> Length for BTreeMap<K, V> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> Length for BTreeSet<T> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T: Length + ?Sized> Length for &T {
+    fn length(&self) -> usize {
+        (**self).length()
+    }
+}
+
+// Used by `#[derive(Validate)]` and `#[http_method]`; not public API
+#[doc(hidden)]
+pub mod __private {
+    use super::*;
+
+    pub fn length<T: Length + ?Sized>(value: &T, min: Option<usize>, max: Option<usize>) -> Result<(), String> {
+        let length = value.length();
+        match (min, max) {
+            (Some(min), Some(max)) if length < min || length > max => {
+                Err(format!("length must be between {} and {}", min, max))
+            }
+            (Some(min), None) if length < min => Err(format!("length must be at least {}", min)),
+            (None, Some(max)) if length > max => Err(format!("length must be at most {}", max)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn range<T: PartialOrd + Display>(value: &T, min: Option<T>, max: Option<T>) -> Result<(), String> {
+        match (min, max) {
+            (Some(min), Some(max)) if *value < min || *value > max => {
+                Err(format!("must be between {} and {}", min, max))
+            }
+            (Some(min), None) if *value < min => Err(format!("must be at least {}", min)),
+            (None, Some(max)) if *value > max => Err(format!("must be at most {}", max)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn email<T: AsRef<str> + ?Sized>(value: &T) -> Result<(), String> {
+        if is_email(value.as_ref()) {
+            Ok(())
+        } else {
+            Err("must be a valid email address".to_string())
+        }
+    }
+
+    // A pragmatic check: one `@` between a local part and a dotted domain, no spaces
+    fn is_email(value: &str) -> bool {
+        let Some((local, domain)) = value.split_once('@') else {
+            return false;
+        };
+        !local.is_empty()
+            && !domain.contains('@')
+            && !value.chars().any(char::is_whitespace)
+            && domain.split('.').count() >= 2
+            && domain.split('.').all(|label| !label.is_empty())
+    }
+
+    // Lets the handler code check parameters whose type implements `Validate`
+    // and pass the others through: method lookup tries `Check` before `&Check`
+    pub struct Check<'a, T>(pub &'a T);
+
+    pub trait ViaValidate {
+        fn check(&self) -> Result<(), FerroxError>;
+    }
+
+    impl<T: Validate> ViaValidate for Check<'_, T> {
+        fn check(&self) -> Result<(), FerroxError> {
+            self.0.validate().map_err(FerroxError::Validation)
+        }
+    }
+
+    pub trait ViaNothing {
+        fn check(&self) -> Result<(), FerroxError>;
+    }
+
+    impl<T> ViaNothing for &Check<'_, T> {
+        fn check(&self) -> Result<(), FerroxError> {
+            Ok(())
+        }
+    }
+}
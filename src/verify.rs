@@ -0,0 +1,218 @@
+//! Signature checks for incoming webhooks, declared with `verify = "..."`.
+//!
+//! ```ignore
+//! Server::new()
+//!     .verifier("github", GitHub::new(&github_secret))
+//!     .verifier("stripe", Stripe::new(&stripe_secret));
+//!
+//! #[http_method(POST, "/hooks/github", verify = "github")]
+//! fn github_push(body: PushEvent) -> Value { ... }
+//! ```
+//!
+//! Before any authenticator, guard or handler, the route reads the request
+//! body, up to its body size limit, and hands the exact bytes and the headers to
+//! the [`Verifier`] registered under the name. A request whose signature is
+//! missing, stale or wrong answers 401, so the handler only ever sees payloads
+//! the provider signed. [`GitHub`], [`Stripe`] and [`Slack`] check the HMAC-SHA256
+//! schemes of those providers; other schemes implement `Verifier`.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::body::{Body, Bytes};
+use axum::extract::Request;
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::routing::MethodRouter;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::context::AppState;
+use crate::error::FerroxError;
+
+/// Checks that a request was signed by the party it claims to come from.
+pub trait Verifier: Send + Sync + 'static {
+    /// `Err` with the reason when `body` and `headers` do not carry a valid signature.
+    fn verify(&self, headers: &HeaderMap, body: &[u8]) -> Result<(), String>;
+}
+
+/// GitHub's `X-Hub-Signature-256: sha256=<hex>`, the HMAC of the body.
+pub struct GitHub {
+    secret: Vec<u8>,
+}
+
+impl GitHub {
+    pub fn new(secret: &str) -> Self {
+        GitHub {
+            secret: secret.as_bytes().to_vec(),
+        }
+    }
+}
+
+impl Verifier for GitHub {
+    fn verify(&self, headers: &HeaderMap, body: &[u8]) -> Result<(), String> {
+        let signature = header(headers, "x-hub-signature-256")?;
+        let signature = signature.strip_prefix("sha256=").ok_or("unsupported signature scheme")?;
+        check(&self.secret, &[body], signature)
+    }
+}
+
+/// Stripe's `Stripe-Signature: t=<timestamp>,v1=<hex>`, the HMAC of
+/// `<timestamp>.<body>`, refused once older than the tolerance (5 minutes).
+pub struct Stripe {
+    secret: Vec<u8>,
+    tolerance: Duration,
+}
+
+impl Stripe {
+    pub fn new(secret: &str) -> Self {
+        Stripe {
+            secret: secret.as_bytes().to_vec(),
+            tolerance: DEFAULT_TOLERANCE,
+        }
+    }
+
+    /// How far the signature's timestamp may be from now.
+    pub fn tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+}
+
+impl Verifier for Stripe {
+    fn verify(&self, headers: &HeaderMap, body: &[u8]) -> Result<(), String> {
+        let header = header(headers, "stripe-signature")?;
+        let mut timestamp = None;
+        let mut signatures = Vec::new();
+        for part in header.split(',') {
+            match part.trim().split_once('=') {
+                Some(("t", value)) => timestamp = Some(value),
+                Some(("v1", value)) => signatures.push(value),
+                _ => {}
+            }
+        }
+        let timestamp = timestamp.ok_or("signature has no timestamp")?;
+        fresh(timestamp, self.tolerance)?;
+        // Several while a secret is being rolled; one valid signature is enough
+        let signed = [timestamp.as_bytes(), b".", body];
+        signatures
+            .iter()
+            .find(|signature| check(&self.secret, &signed, signature).is_ok())
+            .map(|_| ())
+            .ok_or_else(|| "signature mismatch".to_string())
+    }
+}
+
+/// Slack's `X-Slack-Signature: v0=<hex>`, the HMAC of `v0:<timestamp>:<body>`
+/// with the timestamp from `X-Slack-Request-Timestamp`, refused once older than
+/// the tolerance (5 minutes).
+pub struct Slack {
+    secret: Vec<u8>,
+    tolerance: Duration,
+}
+
+impl Slack {
+    pub fn new(signing_secret: &str) -> Self {
+        Slack {
+            secret: signing_secret.as_bytes().to_vec(),
+            tolerance: DEFAULT_TOLERANCE,
+        }
+    }
+
+    /// How far the request's timestamp may be from now.
+    pub fn tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+}
+
+impl Verifier for Slack {
+    fn verify(&self, headers: &HeaderMap, body: &[u8]) -> Result<(), String> {
+        let timestamp = header(headers, "x-slack-request-timestamp")?;
+        fresh(timestamp, self.tolerance)?;
+        let signature = header(headers, "x-slack-signature")?;
+        let signature = signature.strip_prefix("v0=").ok_or("unsupported signature scheme")?;
+        check(&self.secret, &[b"v0:", timestamp.as_bytes(), b":", body], signature)
+    }
+}
+
+const DEFAULT_TOLERANCE: Duration = Duration::from_secs(300);
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Result<&'a str, String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| format!("missing {} header", name))
+}
+
+// Within `tolerance` of now, so captured requests cannot be replayed later
+fn fresh(timestamp: &str, tolerance: Duration) -> Result<(), String> {
+    let timestamp: u64 = timestamp.trim().parse().map_err(|_| "invalid signature timestamp".to_string())?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    if now.abs_diff(timestamp) > tolerance.as_secs() {
+        return Err("signature timestamp is outside the tolerance".to_string());
+    }
+    Ok(())
+}
+
+// Compare in constant time the HMAC of `parts` with the hex `signature`
+fn check(secret: &[u8], parts: &[&[u8]], signature: &str) -> Result<(), String> {
+    let expected = decode_hex(signature).ok_or("malformed signature")?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.verify_slice(&expected).map_err(|_| "signature mismatch".to_string())
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+// Check the signature of every request to a route, on the body as sent
+pub(crate) fn verify_route(
+    route: MethodRouter<AppState>,
+    name: &'static str,
+    verifier: Option<Arc<dyn Verifier>>,
+    max_body_size: usize,
+) -> MethodRouter<AppState> {
+    if verifier.is_none() {
+        tracing::error!("No verifier registered as `{}`; its routes will answer 500", name);
+    }
+    route.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+        let verifier = verifier.clone();
+        async move {
+            // Fail closed when the verifier is not configured
+            let Some(verifier) = verifier else {
+                return FerroxError::Internal("Internal server error".to_string()).into_response();
+            };
+            if crate::expect::declares_over(request.headers(), max_body_size) {
+                return crate::dispatch::body_too_large(max_body_size);
+            }
+            let (parts, body) = request.into_parts();
+            let bytes: Bytes = match axum::body::to_bytes(body, max_body_size).await {
+                Ok(bytes) => bytes,
+                Err(err) => {
+                    let over_limit = std::error::Error::source(&err)
+                        .is_some_and(|source| source.is::<http_body_util::LengthLimitError>());
+                    if over_limit {
+                        return crate::dispatch::body_too_large(max_body_size);
+                    }
+                    return FerroxError::BadRequest("Failed to read request body".to_string()).into_response();
+                }
+            };
+            if let Err(reason) = verifier.verify(&parts.headers, &bytes) {
+                tracing::warn!(verifier = name, "Rejected webhook: {}", reason);
+                return FerroxError::Unauthorized(format!("Invalid webhook signature: {}", reason)).into_response();
+            }
+            next.run(Request::from_parts(parts, Body::from(bytes))).await
+        }
+    }))
+}
@@ -0,0 +1,285 @@
+//! API versions: routes registered with `version = "..."` and how requests pick one.
+//!
+//! ```ignore
+//! #[http_method(GET, "/users", version = "v1")]
+//! async fn list_users_v1() -> Value { ... }
+//!
+//! #[http_method(GET, "/users", version = "v2")]
+//! async fn list_users_v2() -> Value { ... }
+//!
+//! Server::new().versioning(Versioning::header("Accept-Version").default_version("v1"));
+//! ```
+//!
+//! By default (`Versioning::path`) each version is served under its own prefix,
+//! `/v1/users` and `/v2/users`; inside a route group the version comes before the
+//! group prefix. With `Versioning::header` and `Versioning::media_type` every
+//! version shares the path and the request names the one it wants, in a header
+//! such as `Accept-Version: v2` or in the `Accept` media type
+//! (`application/vnd.acme.v2+json`).
+//!
+//! A request naming no version gets the `default_version`, then an unversioned
+//! route for the path, then the latest version. Naming a version no route at the
+//! path has is answered with 400. Unversioned routes sharing a path with
+//! versioned ones serve every version that has no route of its own for the method.
+//!
+//! Under `Versioning::path`, `suggest_versions(true)` makes the 404 for a path
+//! only other versions serve name them: `/v2/users` answers "Route /v2/users
+//! not found; available as /v1/users".
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use axum::extract::Request;
+use axum::http::header::ACCEPT;
+use axum::http::{HeaderMap, HeaderName, Method};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use futures_util::future::BoxFuture;
+use tower::Service;
+
+use crate::error::FerroxError;
+
+/// How requests choose an API version, for `Server::versioning`.
+#[derive(Debug, Clone)]
+pub struct Versioning {
+    strategy: Strategy,
+    default_version: Option<String>,
+    suggest: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Strategy {
+    Path,
+    Header(HeaderName),
+    MediaType(String),
+}
+
+impl Versioning {
+    /// Serve each version under a path prefix: `/v1/users`.
+    pub fn path() -> Self {
+        Versioning {
+            strategy: Strategy::Path,
+            default_version: None,
+            suggest: false,
+        }
+    }
+
+    /// Serve every version at the route's path, chosen by the `name` request header.
+    ///
+    /// # Panics
+    ///
+    /// If `name` is not a valid header name.
+    pub fn header(name: &str) -> Self {
+        Versioning {
+            strategy: Strategy::Header(HeaderName::try_from(name).expect("invalid version header name")),
+            default_version: None,
+            suggest: false,
+        }
+    }
+
+    /// Serve every version at the route's path, chosen by an `Accept` media type
+    /// such as `application/vnd.acme.v2+json` for the `application/vnd.acme` vendor type.
+    pub fn media_type(vendor: impl Into<String>) -> Self {
+        Versioning {
+            strategy: Strategy::MediaType(vendor.into().to_ascii_lowercase()),
+            default_version: None,
+            suggest: false,
+        }
+    }
+
+    /// Version for requests that name none, under the header and media type strategies.
+    pub fn default_version(mut self, version: impl Into<String>) -> Self {
+        self.default_version = Some(version.into());
+        self
+    }
+
+    /// Point 404s for a path that other versions serve at those versions; off by
+    /// default, and only under `Versioning::path`.
+    pub fn suggest_versions(mut self, suggest: bool) -> Self {
+        self.suggest = suggest;
+        self
+    }
+
+    pub(crate) fn suggests(&self) -> bool {
+        self.suggest && self.by_path()
+    }
+
+    pub(crate) fn by_path(&self) -> bool {
+        matches!(self.strategy, Strategy::Path)
+    }
+
+    // The path a route of `version` is served at
+    pub(crate) fn served_path(&self, version: Option<&str>, path: &str) -> String {
+        match (version, &self.strategy) {
+            (Some(version), Strategy::Path) if path == "/" => format!("/{}", version),
+            (Some(version), Strategy::Path) => format!("/{}{}", version, path),
+            _ => path.to_string(),
+        }
+    }
+
+    // The version a request asks for, or the default
+    fn requested(&self, headers: &HeaderMap) -> Option<String> {
+        let named = match &self.strategy {
+            Strategy::Path => None,
+            Strategy::Header(name) => headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty()),
+            Strategy::MediaType(vendor) => headers
+                .get_all(ACCEPT)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|accept| accept.split(','))
+                .find_map(|entry| {
+                    let media_type = entry.split(';').next()?.trim().to_ascii_lowercase();
+                    let rest = media_type.strip_prefix(vendor.as_str())?.strip_prefix('.')?;
+                    let version = rest.split('+').next()?;
+                    (!version.is_empty()).then(|| version.to_string())
+                }),
+        };
+        named.or_else(|| self.default_version.clone())
+    }
+}
+
+impl Default for Versioning {
+    fn default() -> Self {
+        Self::path()
+    }
+}
+
+// Order versions naturally, so `v10` comes after `v9`
+fn compare_versions(first: &str, second: &str) -> Ordering {
+    let key = |version: &str| -> Vec<u64> {
+        version
+            .split(|c: char| !c.is_ascii_digit())
+            .filter(|part| !part.is_empty())
+            .map(|part| part.parse().unwrap_or(u64::MAX))
+            .collect()
+    };
+    key(first).cmp(&key(second)).then_with(|| first.cmp(second))
+}
+
+// The versions serving each route path, to hint at them in 404 answers
+pub(crate) struct VersionHints(matchit::Router<Vec<&'static str>>);
+
+impl VersionHints {
+    // From the `(path, version)` of each versioned route
+    pub(crate) fn new(routes: impl IntoIterator<Item = (&'static str, &'static str)>) -> Self {
+        // Paths differing only by placeholder names share an entry
+        let mut shapes: BTreeMap<String, (&'static str, Vec<&'static str>)> = BTreeMap::new();
+        for (path, version) in routes {
+            let (_, versions) = shapes.entry(crate::constraints::shape(path)).or_insert((path, Vec::new()));
+            if !versions.contains(&version) {
+                versions.push(version);
+            }
+        }
+        let mut router = matchit::Router::new();
+        for (path, mut versions) in shapes.into_values() {
+            versions.sort_by(|first, second| compare_versions(first, second));
+            // Conflicting paths are reported when the routes themselves are mounted
+            let _ = router.insert(path, versions);
+        }
+        VersionHints(router)
+    }
+
+    // The paths other versions serve `path` at, which no route matched: either
+    // under another version than its first segment names, or under any version
+    pub(crate) fn hint(&self, path: &str) -> Option<String> {
+        let (first, rest) = match path.trim_start_matches('/').split_once('/') {
+            Some((first, rest)) => (first, format!("/{}", rest)),
+            None => (path.trim_start_matches('/'), "/".to_string()),
+        };
+        let served = |path: &str, requested: &str| -> Vec<String> {
+            let Ok(found) = self.0.at(path) else {
+                return Vec::new();
+            };
+            found
+                .value
+                .iter()
+                .filter(|version| **version != requested)
+                .map(|version| match path {
+                    "/" => format!("/{}", version),
+                    _ => format!("/{}{}", version, path),
+                })
+                .collect()
+        };
+        let mut paths = served(&rest, first);
+        if paths.is_empty() {
+            paths = served(path, "");
+        }
+        (!paths.is_empty()).then(|| format!("available as {}", paths.join(", ")))
+    }
+}
+
+// One version's router at a path: `None` for unversioned routes, with the
+// methods the router serves
+pub(crate) type Version = (Option<&'static str>, Vec<&'static str>, Router);
+
+// The versions registered at one path, each routed on its own, for the header
+// and media type strategies
+#[derive(Clone)]
+pub(crate) struct VersionedRoute {
+    versioning: Arc<Versioning>,
+    routes: Arc<Vec<Version>>,
+}
+
+impl VersionedRoute {
+    pub(crate) fn new(versioning: Arc<Versioning>, mut routes: Vec<Version>) -> Self {
+        // Latest version first, unversioned routes last
+        routes.sort_by(|(first, ..), (second, ..)| match (first, second) {
+            (Some(first), Some(second)) => compare_versions(second, first),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+        VersionedRoute {
+            versioning,
+            routes: Arc::new(routes),
+        }
+    }
+
+    fn pick(&self, method: &Method, requested: Option<&str>) -> Result<&Router, FerroxError> {
+        let method = if method == Method::HEAD { "GET" } else { method.as_str() };
+        let serves = |methods: &Vec<&str>| methods.contains(&method);
+        let find = |version: Option<&str>| self.routes.iter().find(|(candidate, ..)| *candidate == version);
+        let candidates: Vec<&Version> = match requested {
+            Some(version) => [find(Some(version)), find(None)].into_iter().flatten().collect(),
+            None => find(None).into_iter().chain(self.routes.iter()).collect(),
+        };
+        if let Some((_, _, router)) = candidates.iter().find(|(_, methods, _)| serves(methods)) {
+            return Ok(router);
+        }
+        match requested {
+            Some(version) if find(Some(version)).is_none() => {
+                Err(FerroxError::BadRequest(format!("Unsupported API version `{}`", version)))
+            }
+            // Answered with 405 and the methods the version has
+            _ => Ok(&candidates[0].2),
+        }
+    }
+}
+
+impl Service<Request> for VersionedRoute {
+    type Response = Response;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Response, Infallible>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let requested = self.versioning.requested(request.headers());
+        match self.pick(request.method(), requested.as_deref()) {
+            Ok(router) => {
+                let mut router = router.clone();
+                Box::pin(async move { router.call(request).await })
+            }
+            Err(err) => Box::pin(async move { Ok(err.into_response()) }),
+        }
+    }
+}
@@ -0,0 +1,454 @@
+//! Outgoing webhooks: signed event deliveries to subscriber endpoints.
+//!
+//! ```ignore
+//! let webhooks = Webhooks::new(WebhooksConfig::new().retry(RetryPolicy::exponential(5, Duration::from_secs(2))));
+//! webhooks.subscribe(Subscription::new("https://partner.example/hooks", "whsec_...").events(["order.*"]));
+//! Server::new().webhooks(webhooks);
+//!
+//! #[http_method(POST, "/orders")]
+//! fn create_order(webhooks: State<Webhooks>, body: NewOrder) -> Result<Value, FerroxError> {
+//!     let order = save(body)?;
+//!     webhooks.emit(Event::new("order.created", json!(order)));
+//!     Ok(json!(order))
+//! }
+//! ```
+//!
+//! `emit` queues one delivery per subscription whose event patterns match
+//! (`order.created`, `order.*` or `*`) and returns at once; deliveries are sent
+//! in the background, at most `concurrency` at a time. Each is a `POST` of
+//!
+//! ```json
+//! {"id": "evt_5f0c...", "type": "order.created", "created_at": "2026-10-14T12:00:00Z", "data": {...}}
+//! ```
+//!
+//! with `X-Webhook-Id`, `X-Webhook-Event`, `X-Webhook-Timestamp` (Unix seconds)
+//! and `X-Webhook-Signature: sha256=<hex>`, the HMAC-SHA256 under the
+//! subscription's secret of `<timestamp>.<body>` (see [`sign`]). A 2xx answer
+//! completes the delivery. Timeouts, connection errors, 408, 429 and 5xx are
+//! retried under the retry policy; other answers, and the last failed attempt,
+//! move the delivery to the dead letters, from where it can be redelivered.
+
+use std::fmt;
+use std::hash::{BuildHasher, RandomState};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::sync::{Notify, Semaphore};
+
+use crate::jobs::RetryPolicy;
+
+/// An event sent to the subscriptions that want it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Event {
+    pub id: String,
+    /// The event name, e.g. `order.created`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// RFC 3339 time the event was created.
+    pub created_at: String,
+    pub data: serde_json::Value,
+}
+
+impl Event {
+    pub fn new(kind: &str, data: serde_json::Value) -> Self {
+        Event {
+            id: generate_id("evt"),
+            kind: kind.to_string(),
+            created_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            data,
+        }
+    }
+}
+
+/// An endpoint receiving events, and the secret its deliveries are signed with.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Subscription {
+    /// Set by `Webhooks::subscribe`.
+    pub id: String,
+    pub url: String,
+    secret: String,
+    /// Event names or `prefix.*` patterns; every event when empty.
+    pub events: Vec<String>,
+}
+
+impl fmt::Debug for Subscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscription")
+            .field("id", &self.id)
+            .field("url", &self.url)
+            .field("events", &self.events)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Subscription {
+    pub fn new(url: &str, secret: &str) -> Self {
+        Subscription {
+            id: String::new(),
+            url: url.to_string(),
+            secret: secret.to_string(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Only receive events matching `patterns`: names such as `order.created`,
+    /// `order.*` for every name under `order.`, or `*`.
+    pub fn events<I, T>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.events = patterns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    fn wants(&self, kind: &str) -> bool {
+        self.events.is_empty()
+            || self.events.iter().any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => kind.starts_with(prefix),
+                None => pattern == kind,
+            })
+    }
+}
+
+/// A delivery that failed for good.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeadLetter {
+    /// The delivery's id, for `Webhooks::redeliver`.
+    pub id: String,
+    pub subscription: String,
+    pub url: String,
+    pub event: Event,
+    pub attempts: u32,
+    /// Why the last attempt failed.
+    pub error: String,
+}
+
+/// Retries, timeouts and concurrency of deliveries; pass it to `Webhooks::new`.
+///
+/// By default each delivery gets `RetryPolicy::default()` attempts of up to
+/// 10 seconds, 16 are sent at once, and the server waits 10 seconds for those
+/// pending when it shuts down.
+#[derive(Debug, Clone)]
+pub struct WebhooksConfig {
+    retry: RetryPolicy,
+    timeout: Duration,
+    concurrency: usize,
+    user_agent: String,
+    flush_timeout: Duration,
+}
+
+impl WebhooksConfig {
+    pub fn new() -> Self {
+        WebhooksConfig {
+            retry: RetryPolicy::default(),
+            timeout: Duration::from_secs(10),
+            concurrency: 16,
+            user_agent: "ferrox-webhooks".to_string(),
+            flush_timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// How often and how soon a failed delivery is tried again.
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// How long one attempt may take, from connecting to the end of the response.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How many deliveries are sent at once.
+    pub fn concurrency(mut self, deliveries: usize) -> Self {
+        self.concurrency = deliveries.max(1);
+        self
+    }
+
+    /// How long `Server` waits for pending deliveries once it has stopped (10 seconds by default).
+    pub fn flush_timeout(mut self, timeout: Duration) -> Self {
+        self.flush_timeout = timeout;
+        self
+    }
+
+    /// `User-Agent` of the delivery requests.
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The subscriptions and deliveries; clones share them.
+///
+/// Register it with `Server::webhooks` to take it as a `State<Webhooks>` handler
+/// parameter and let deliveries finish on shutdown.
+#[derive(Clone)]
+pub struct Webhooks {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    config: WebhooksConfig,
+    client: reqwest::Client,
+    subscriptions: Mutex<Vec<Subscription>>,
+    dead_letters: Mutex<Vec<DeadLetter>>,
+    slots: Arc<Semaphore>,
+    // Deliveries queued or being sent
+    pending: AtomicUsize,
+    idle: Notify,
+}
+
+#[derive(Clone)]
+struct Delivery {
+    id: String,
+    subscription: Subscription,
+    event: Event,
+}
+
+// How an attempt went
+enum Outcome {
+    Delivered,
+    Retry(String),
+    Fail(String),
+}
+
+impl Webhooks {
+    pub fn new(config: WebhooksConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .user_agent(config.user_agent.clone())
+            .build()
+            .expect("the webhook HTTP client can be built");
+        Webhooks {
+            inner: Arc::new(Inner {
+                slots: Arc::new(Semaphore::new(config.concurrency)),
+                config,
+                client,
+                subscriptions: Mutex::new(Vec::new()),
+                dead_letters: Mutex::new(Vec::new()),
+                pending: AtomicUsize::new(0),
+                idle: Notify::new(),
+            }),
+        }
+    }
+
+    /// Send matching events to `subscription` from now on, returning its id.
+    pub fn subscribe(&self, mut subscription: Subscription) -> String {
+        subscription.id = generate_id("sub");
+        let id = subscription.id.clone();
+        self.inner.subscriptions.lock().unwrap().push(subscription);
+        id
+    }
+
+    /// Stop sending events to a subscription; `false` if there was none with `id`.
+    pub fn unsubscribe(&self, id: &str) -> bool {
+        let mut subscriptions = self.inner.subscriptions.lock().unwrap();
+        let before = subscriptions.len();
+        subscriptions.retain(|subscription| subscription.id != id);
+        subscriptions.len() < before
+    }
+
+    /// The current subscriptions, in the order they were made.
+    pub fn subscriptions(&self) -> Vec<Subscription> {
+        self.inner.subscriptions.lock().unwrap().clone()
+    }
+
+    /// Queue `event` for every subscription that wants it, returning how many
+    /// deliveries were queued. Must be called within a Tokio runtime.
+    pub fn emit(&self, event: Event) -> usize {
+        let subscriptions: Vec<Subscription> = self
+            .inner
+            .subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|subscription| subscription.wants(&event.kind))
+            .cloned()
+            .collect();
+        for subscription in &subscriptions {
+            self.send(Delivery {
+                id: generate_id("dlv"),
+                subscription: subscription.clone(),
+                event: event.clone(),
+            });
+        }
+        subscriptions.len()
+    }
+
+    /// Deliveries that failed for good, oldest first.
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.inner.dead_letters.lock().unwrap().clone()
+    }
+
+    /// Send a dead letter again, with a fresh set of attempts; `false` if there
+    /// is none with `id`.
+    pub fn redeliver(&self, id: &str) -> bool {
+        let letter = {
+            let mut letters = self.inner.dead_letters.lock().unwrap();
+            let Some(index) = letters.iter().position(|letter| letter.id == id) else {
+                return false;
+            };
+            letters.remove(index)
+        };
+        // The subscription may have been dropped since; its secret went with it
+        let subscription = self.subscriptions().into_iter().find(|subscription| subscription.id == letter.subscription);
+        let Some(subscription) = subscription else {
+            self.inner.dead_letters.lock().unwrap().push(letter);
+            return false;
+        };
+        self.send(Delivery {
+            id: letter.id,
+            subscription,
+            event: letter.event,
+        });
+        true
+    }
+
+    /// Forget every dead letter.
+    pub fn clear_dead_letters(&self) {
+        self.inner.dead_letters.lock().unwrap().clear();
+    }
+
+    /// Deliveries queued or being sent.
+    pub fn pending(&self) -> usize {
+        self.inner.pending.load(Ordering::SeqCst)
+    }
+
+    /// Wait up to `timeout` for the pending deliveries, returning whether they
+    /// all finished; `Server` calls this on shutdown with the `flush_timeout`.
+    pub async fn flush(&self, timeout: Duration) -> bool {
+        let flushed = tokio::time::timeout(timeout, async {
+            loop {
+                // Registered before checking, so a delivery finishing in between still wakes us
+                let idle = self.inner.idle.notified();
+                tokio::pin!(idle);
+                idle.as_mut().enable();
+                if self.pending() == 0 {
+                    break;
+                }
+                idle.await;
+            }
+        })
+        .await
+        .is_ok();
+        if !flushed {
+            tracing::warn!("{} webhook deliveries did not finish within {:?}", self.pending(), timeout);
+        }
+        flushed
+    }
+
+    pub(crate) fn flush_timeout(&self) -> Duration {
+        self.inner.config.flush_timeout
+    }
+
+    fn send(&self, delivery: Delivery) {
+        self.inner.pending.fetch_add(1, Ordering::SeqCst);
+        let webhooks = self.clone();
+        tokio::spawn(async move {
+            webhooks.deliver(delivery).await;
+            if webhooks.inner.pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+                webhooks.inner.idle.notify_waiters();
+            }
+        });
+    }
+
+    // Attempt `delivery` under the retry policy, dead-lettering it if it never succeeds
+    async fn deliver(&self, delivery: Delivery) {
+        let retry = self.inner.config.retry;
+        let body = serde_json::to_vec(&delivery.event).expect("events serialize to JSON");
+        let mut attempt = 0;
+        let error = loop {
+            attempt += 1;
+            let outcome = {
+                let _slot = self.inner.slots.clone().acquire_owned().await;
+                self.attempt(&delivery, &body).await
+            };
+            match outcome {
+                Outcome::Delivered => {
+                    tracing::debug!(delivery = delivery.id, url = delivery.subscription.url, attempt, "Webhook delivered");
+                    return;
+                }
+                Outcome::Retry(error) if attempt < retry.attempts() => {
+                    let backoff = retry.backoff(attempt);
+                    tracing::warn!(
+                        delivery = delivery.id,
+                        url = delivery.subscription.url,
+                        attempt,
+                        "Webhook delivery failed, retrying in {:?}: {}",
+                        backoff,
+                        error
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+                Outcome::Retry(error) | Outcome::Fail(error) => break error,
+            }
+        };
+        tracing::error!(delivery = delivery.id, url = delivery.subscription.url, attempt, "Webhook delivery failed, giving up: {}", error);
+        self.inner.dead_letters.lock().unwrap().push(DeadLetter {
+            id: delivery.id,
+            subscription: delivery.subscription.id,
+            url: delivery.subscription.url,
+            event: delivery.event,
+            attempts: attempt,
+            error,
+        });
+    }
+
+    async fn attempt(&self, delivery: &Delivery, body: &[u8]) -> Outcome {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let signature = sign(&delivery.subscription.secret, timestamp, body);
+        let request = self
+            .inner
+            .client
+            .post(&delivery.subscription.url)
+            .header("content-type", "application/json")
+            .header("x-webhook-id", &delivery.id)
+            .header("x-webhook-event", &delivery.event.kind)
+            .header("x-webhook-timestamp", timestamp.to_string())
+            .header("x-webhook-signature", format!("sha256={}", signature))
+            .body(body.to_vec());
+        match request.send().await {
+            Ok(response) if response.status().is_success() => Outcome::Delivered,
+            Ok(response) => {
+                let status = response.status();
+                let error = format!("endpoint answered {}", status);
+                if status.is_server_error() || status.as_u16() == 408 || status.as_u16() == 429 {
+                    Outcome::Retry(error)
+                } else {
+                    Outcome::Fail(error)
+                }
+            }
+            Err(err) => Outcome::Retry(err.to_string()),
+        }
+    }
+}
+
+/// The hex HMAC-SHA256 under `secret` of `<timestamp>.<body>`, as sent in
+/// `X-Webhook-Signature` after `sha256=`, for receivers checking deliveries.
+pub fn sign(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Unique within the process and hard to guess, like request ids
+fn generate_id(prefix: &str) -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    format!("{}_{:016x}", prefix, RandomState::new().hash_one(n))
+}
+
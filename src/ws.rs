@@ -0,0 +1,453 @@
+//! WebSocket routes registered with `#[websocket]`.
+//!
+//! Path, query and state parameters are extracted before the upgrade, so a
+//! bad request is still answered with the usual JSON error envelope. The
+//! socket parameter is then handed over once the connection is upgraded,
+//! either as a raw [`WebSocket`] or as a typed [`JsonSocket`].
+//!
+//! A [`ConnectionManager`] groups connections in named rooms, so that any
+//! code holding it, such as a regular HTTP handler, can send to them:
+//!
+//! ```ignore
+//! #[websocket("/ws/orders/:id")]
+//! async fn order_updates(id: u64, socket: JsonSocket, rooms: State<ConnectionManager>) {
+//!     let mut connection = rooms.connect(socket);
+//!     connection.join(&format!("order-{}", id));
+//!     while let Some(_message) = connection.recv().await {}
+//! }
+//!
+//! #[http_method(POST, "/orders/:id/ship")]
+//! fn ship(id: u64, rooms: State<ConnectionManager>) -> Result<Value, FerroxError> {
+//!     let notified = rooms.broadcast(&format!("order-{}", id), &json!({ "status": "shipped" }))?;
+//!     Ok(json!({ "notified": notified }))
+//! }
+//! ```
+//!
+//! Messages are queued per connection and written by a task of their own, so
+//! sending never waits on a slow client; a client more than 256 messages
+//! behind misses the messages sent meanwhile. A [`Connection`] leaves its
+//! rooms when dropped, which is when the handler returns once the client has
+//! disconnected; rooms without members are removed.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{self, Display};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use axum::extract::ws::rejection::WebSocketUpgradeRejection;
+use axum::extract::ws::WebSocketUpgrade;
+use axum::extract::{Path, Query, State as AxumState};
+use axum::response::IntoResponse;
+use axum::routing::{get, MethodRouter};
+use futures_util::stream::{SplitStream, StreamExt};
+use futures_util::SinkExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+pub use axum::extract::ws::{Message, WebSocket};
+
+use crate::context::{AppState, RequestContext};
+use crate::dispatch::params_to_json;
+use crate::envelope::Responder;
+use crate::error::FerroxError;
+use crate::format::Format;
+use crate::response::{HandlerResponse, NonObjectResponse};
+
+type SessionFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+type AcceptFn = Arc<dyn Fn(RequestContext) -> Result<WsSession, HandlerResponse> + Send + Sync>;
+
+/// Upgrade handler generated by `#[websocket]`.
+///
+/// Runs parameter extraction on the upgrade request and either rejects it
+/// with a response or returns the session to run on the upgraded socket.
+pub struct WsHandler {
+    accept: AcceptFn,
+}
+
+impl WsHandler {
+    pub fn new<F>(accept: F) -> Self
+    where
+        F: Fn(RequestContext) -> Result<WsSession, HandlerResponse> + Send + Sync + 'static,
+    {
+        WsHandler { accept: Arc::new(accept) }
+    }
+}
+
+/// The part of a WebSocket handler that runs after the upgrade.
+pub struct WsSession {
+    run: Box<dyn FnOnce(WebSocket) -> SessionFuture + Send>,
+}
+
+impl WsSession {
+    pub fn new<F, Fut>(run: F) -> Self
+    where
+        F: FnOnce(WebSocket) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        WsSession {
+            run: Box::new(move |socket| Box::pin(run(socket))),
+        }
+    }
+}
+
+/// Types a `#[websocket]` handler can receive its socket as.
+pub trait FromSocket {
+    fn from_socket(socket: WebSocket) -> Self;
+}
+
+impl FromSocket for WebSocket {
+    fn from_socket(socket: WebSocket) -> Self {
+        socket
+    }
+}
+
+impl<T> FromSocket for JsonSocket<T> {
+    fn from_socket(socket: WebSocket) -> Self {
+        JsonSocket {
+            socket,
+            _message: PhantomData,
+        }
+    }
+}
+
+/// A WebSocket exchanging JSON messages.
+///
+/// Incoming text and binary frames are deserialized as `T`; anything
+/// serializable can be sent back as a text frame.
+pub struct JsonSocket<T = serde_json::Value> {
+    socket: WebSocket,
+    _message: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> JsonSocket<T> {
+    /// Next message from the client, or `None` once the connection is closed.
+    /// A frame that is not a valid `T` yields an error without closing the socket.
+    pub async fn recv(&mut self) -> Option<Result<T, FerroxError>> {
+        loop {
+            let message = match self.socket.recv().await? {
+                Ok(message) => message,
+                Err(err) => return Some(Err(FerroxError::BadRequest(format!("WebSocket error: {}", err)))),
+            };
+            if let Some(parsed) = decode(message)? {
+                return Some(parsed);
+            }
+        }
+    }
+}
+
+// A frame as a `T`: `None` for a close frame and `Some(None)` for control frames to skip
+fn decode<T: DeserializeOwned>(message: Message) -> Option<Option<Result<T, FerroxError>>> {
+    let parsed = match message {
+        Message::Text(text) => serde_json::from_str(&text),
+        Message::Binary(bytes) => serde_json::from_slice(&bytes),
+        Message::Close(_) => return None,
+        // Pings are answered by the connection itself
+        Message::Ping(_) | Message::Pong(_) => return Some(None),
+    };
+    Some(Some(parsed.map_err(|err| FerroxError::BadRequest(format!("Invalid message: {}", err)))))
+}
+
+fn to_text<M: Serialize + ?Sized>(message: &M) -> Result<Message, FerroxError> {
+    serde_json::to_string(message).map(Message::Text).map_err(|err| {
+        tracing::error!("Failed to serialize WebSocket message: {}", err);
+        FerroxError::Internal("Internal server error: message could not be serialized".to_string())
+    })
+}
+
+impl<T> JsonSocket<T> {
+    /// Send `message` to the client as a JSON text frame.
+    pub async fn send<M: Serialize>(&mut self, message: &M) -> Result<(), FerroxError> {
+        let message = to_text(message)?;
+        self.socket
+            .send(message)
+            .await
+            .map_err(|err| FerroxError::Internal(format!("WebSocket error: {}", err)))
+    }
+
+    /// Close the connection.
+    pub async fn close(mut self) {
+        let _ = self.socket.send(Message::Close(None)).await;
+    }
+
+    /// The underlying socket, for frames that are not JSON.
+    pub fn into_inner(self) -> WebSocket {
+        self.socket
+    }
+}
+
+// Messages a connection may have queued before more are dropped
+const QUEUE_LENGTH: usize = 256;
+
+/// Identifies a connection of a [`ConnectionManager`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConnectionId(u64);
+
+impl Display for ConnectionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// WebSocket connections grouped in named rooms; cheap to clone, clones
+/// share connections. Share it with `Server::with_state`.
+#[derive(Clone, Default)]
+pub struct ConnectionManager {
+    inner: Arc<Mutex<Rooms>>,
+}
+
+#[derive(Default)]
+struct Rooms {
+    next_id: u64,
+    connections: HashMap<ConnectionId, Member>,
+    rooms: HashMap<String, BTreeSet<ConnectionId>>,
+}
+
+struct Member {
+    queue: mpsc::Sender<Message>,
+    rooms: BTreeSet<String>,
+}
+
+impl Rooms {
+    // Queue `message` for `id`, forgetting connections whose writer has stopped
+    fn send(&mut self, id: ConnectionId, message: Message) -> bool {
+        let Some(member) = self.connections.get(&id) else {
+            return false;
+        };
+        match member.queue.try_send(message) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                tracing::debug!(connection = %id, "WebSocket connection is behind; dropping a message");
+                false
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                self.remove(id);
+                false
+            }
+        }
+    }
+
+    fn remove(&mut self, id: ConnectionId) {
+        let Some(member) = self.connections.remove(&id) else {
+            return;
+        };
+        for room in member.rooms {
+            self.leave_room(id, &room);
+        }
+    }
+
+    fn leave_room(&mut self, id: ConnectionId, room: &str) {
+        if let Some(members) = self.rooms.get_mut(room) {
+            members.remove(&id);
+            if members.is_empty() {
+                self.rooms.remove(room);
+            }
+        }
+    }
+
+    fn members(&self, room: &str) -> Vec<ConnectionId> {
+        self.rooms.get(room).map(|members| members.iter().copied().collect()).unwrap_or_default()
+    }
+}
+
+impl ConnectionManager {
+    pub fn new() -> Self {
+        ConnectionManager::default()
+    }
+
+    /// Manage `socket`: messages sent to the returned connection, directly or
+    /// through its rooms, are written to it until it is dropped.
+    pub fn connect<T>(&self, socket: JsonSocket<T>) -> Connection<T> {
+        let (mut sink, stream) = socket.into_inner().split();
+        let (queue, mut queued) = mpsc::channel::<Message>(QUEUE_LENGTH);
+        tokio::spawn(async move {
+            while let Some(message) = queued.recv().await {
+                if sink.send(message).await.is_err() {
+                    return;
+                }
+            }
+            let _ = sink.close().await;
+        });
+        let mut rooms = self.inner.lock().unwrap();
+        rooms.next_id += 1;
+        let id = ConnectionId(rooms.next_id);
+        rooms.connections.insert(
+            id,
+            Member {
+                queue,
+                rooms: BTreeSet::new(),
+            },
+        );
+        Connection {
+            id,
+            manager: self.clone(),
+            stream,
+            _message: PhantomData,
+        }
+    }
+
+    /// Add connection `id` to `room`, creating the room if needed; `false` if it is not connected.
+    pub fn join(&self, id: ConnectionId, room: &str) -> bool {
+        let mut rooms = self.inner.lock().unwrap();
+        let Some(member) = rooms.connections.get_mut(&id) else {
+            return false;
+        };
+        member.rooms.insert(room.to_string());
+        rooms.rooms.entry(room.to_string()).or_default().insert(id);
+        true
+    }
+
+    /// Remove connection `id` from `room`.
+    pub fn leave(&self, id: ConnectionId, room: &str) {
+        let mut rooms = self.inner.lock().unwrap();
+        if let Some(member) = rooms.connections.get_mut(&id) {
+            member.rooms.remove(room);
+        }
+        rooms.leave_room(id, room);
+    }
+
+    /// Send `message` as JSON to every connection in `room`, returning how many it was queued for.
+    pub fn broadcast<M: Serialize + ?Sized>(&self, room: &str, message: &M) -> Result<usize, FerroxError> {
+        Ok(self.broadcast_message(room, to_text(message)?))
+    }
+
+    /// Send a frame to every connection in `room`, returning how many it was queued for.
+    pub fn broadcast_message(&self, room: &str, message: Message) -> usize {
+        let mut rooms = self.inner.lock().unwrap();
+        let members = rooms.members(room);
+        members.into_iter().filter(|id| rooms.send(*id, message.clone())).count()
+    }
+
+    /// Send `message` as JSON to every connection in any of `rooms`, once
+    /// each, returning how many it was queued for.
+    pub fn multicast<M: Serialize + ?Sized>(&self, rooms: &[&str], message: &M) -> Result<usize, FerroxError> {
+        let message = to_text(message)?;
+        let mut state = self.inner.lock().unwrap();
+        let members: BTreeSet<ConnectionId> = rooms.iter().flat_map(|room| state.members(room)).collect();
+        Ok(members.into_iter().filter(|id| state.send(*id, message.clone())).count())
+    }
+
+    /// Send `message` as JSON to connection `id`; `false` if it was not queued.
+    pub fn send_to<M: Serialize + ?Sized>(&self, id: ConnectionId, message: &M) -> Result<bool, FerroxError> {
+        let message = to_text(message)?;
+        Ok(self.inner.lock().unwrap().send(id, message))
+    }
+
+    /// The connections in `room`.
+    pub fn members(&self, room: &str) -> Vec<ConnectionId> {
+        self.inner.lock().unwrap().members(room)
+    }
+
+    /// The rooms with at least one connection.
+    pub fn rooms(&self) -> Vec<String> {
+        let mut rooms: Vec<String> = self.inner.lock().unwrap().rooms.keys().cloned().collect();
+        rooms.sort();
+        rooms
+    }
+
+    /// How many connections are managed.
+    pub fn connection_count(&self) -> usize {
+        self.inner.lock().unwrap().connections.len()
+    }
+}
+
+/// A connection of a [`ConnectionManager`], exchanging JSON messages like a
+/// [`JsonSocket`]; dropping it leaves its rooms and closes the socket.
+pub struct Connection<T = serde_json::Value> {
+    id: ConnectionId,
+    manager: ConnectionManager,
+    stream: SplitStream<WebSocket>,
+    _message: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> Connection<T> {
+    /// Next message from the client, or `None` once the connection is closed.
+    /// A frame that is not a valid `T` yields an error without closing the socket.
+    pub async fn recv(&mut self) -> Option<Result<T, FerroxError>> {
+        loop {
+            let message = match self.stream.next().await? {
+                Ok(message) => message,
+                Err(err) => return Some(Err(FerroxError::BadRequest(format!("WebSocket error: {}", err)))),
+            };
+            if let Some(parsed) = decode(message)? {
+                return Some(parsed);
+            }
+        }
+    }
+}
+
+impl<T> Connection<T> {
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    /// Join `room`, creating it if needed.
+    pub fn join(&self, room: &str) {
+        self.manager.join(self.id, room);
+    }
+
+    /// Leave `room`.
+    pub fn leave(&self, room: &str) {
+        self.manager.leave(self.id, room);
+    }
+
+    /// Send `message` to this client as JSON, after the messages already queued for it.
+    pub fn send<M: Serialize + ?Sized>(&self, message: &M) -> Result<(), FerroxError> {
+        match self.manager.send_to(self.id, message)? {
+            true => Ok(()),
+            false => Err(FerroxError::Internal("WebSocket error: the connection is closed or behind".to_string())),
+        }
+    }
+
+    /// Send `message` as JSON to the other connections in `room`, returning how many it was queued for.
+    pub fn broadcast_others<M: Serialize + ?Sized>(&self, room: &str, message: &M) -> Result<usize, FerroxError> {
+        let message = to_text(message)?;
+        let mut rooms = self.manager.inner.lock().unwrap();
+        let members = rooms.members(room);
+        Ok(members.into_iter().filter(|id| *id != self.id && rooms.send(*id, message.clone())).count())
+    }
+}
+
+impl<T> Drop for Connection<T> {
+    fn drop(&mut self) {
+        // Removing the queue's sender lets the writer finish and close the socket
+        self.manager.inner.lock().unwrap().remove(self.id);
+    }
+}
+
+// Build the axum route for a `#[websocket]` registration
+pub(crate) fn method_router(handler: WsHandler) -> MethodRouter<AppState> {
+    let accept = handler.accept;
+    get(
+        move |AxumState(state): AxumState<AppState>,
+              Path(path_params): Path<HashMap<String, String>>,
+              Query(query_params): Query<HashMap<String, String>>,
+              upgrade: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+              request: axum::extract::Request| async move {
+            let upgrade = match upgrade {
+                Ok(upgrade) => upgrade,
+                Err(rejection) => return FerroxError::new(rejection.status(), rejection.body_text()).into_response(),
+            };
+            let (parts, _) = request.into_parts();
+            let responder = Responder::of(&parts.extensions);
+            let error_context = responder.context(&parts);
+            let ctx = RequestContext::from_parts(
+                parts,
+                params_to_json(path_params),
+                params_to_json(query_params),
+                serde_json::Value::Null,
+                state,
+            );
+            match accept(ctx) {
+                Ok(session) => upgrade.on_upgrade(session.run),
+                Err(response) => responder.handle(response, error_context.as_ref()).render(
+                    NonObjectResponse::PassThrough,
+                    &responder,
+                    Format::Json,
+                ),
+            }
+        },
+    )
+}
@@ -0,0 +1,101 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use ferrox::logging::RequestId;
+use ferrox::test::TestClient;
+use ferrox::{http_method, RequestContext, Server, StatusCode};
+use serde_json::{json, Value};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::util::SubscriberInitExt;
+
+#[http_method(GET, "/logged/users/:id")]
+fn user(id: u64, ctx: &RequestContext) -> Value {
+    let request_id = ctx.extensions.get::<RequestId>().map(|id| id.0.clone());
+    json!({ "id": id, "request_id": request_id })
+}
+
+// JSON events written by the subscriber, one per line
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Captured {
+    fn access_events(&self) -> Vec<Value> {
+        let output = self.0.lock().unwrap();
+        String::from_utf8_lossy(&output)
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .filter(|event| event["target"] == "ferrox::access")
+            .collect()
+    }
+}
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Captured {
+    type Writer = Captured;
+
+    fn make_writer(&'a self) -> Captured {
+        self.clone()
+    }
+}
+
+fn capture() -> (Captured, tracing::subscriber::DefaultGuard) {
+    let captured = Captured::default();
+    let subscriber = tracing_subscriber::fmt().json().flatten_event(true).with_writer(captured.clone()).finish();
+    (captured, subscriber.set_default())
+}
+
+#[tokio::test]
+async fn every_request_is_logged_with_its_route_and_id() {
+    let (captured, _guard) = capture();
+    let client = TestClient::from_server(Server::new().access_log(true));
+
+    let response = client.get("/logged/users/7").await;
+    let request_id = response.header("x-request-id").unwrap().to_string();
+    assert_eq!(request_id.len(), 16);
+    assert_eq!(response.json::<Value>()["request_id"], request_id.as_str());
+    assert_eq!(client.get("/logged/nowhere").await.status(), StatusCode::NOT_FOUND);
+
+    let events = captured.access_events();
+    assert_eq!(events.len(), 2);
+    let event = &events[0];
+    assert_eq!(
+        (&event["message"], &event["method"], &event["path"], &event["route"]),
+        (&json!("request"), &json!("GET"), &json!("/logged/users/7"), &json!("/logged/users/:id"))
+    );
+    assert_eq!((&event["status"], &event["request_id"]), (&json!(200), &json!(request_id)));
+    assert!(event["latency_ms"].is_number());
+    assert_eq!((&events[1]["route"], &events[1]["status"]), (&json!("-"), &json!(404)));
+}
+
+#[tokio::test]
+async fn request_ids_sent_by_the_client_are_kept() {
+    let (captured, _guard) = capture();
+    let client = TestClient::from_server(Server::new().access_log(true));
+
+    let response = client.get("/logged/users/1").header("x-request-id", "req-42").await;
+    assert_eq!(response.header("x-request-id"), Some("req-42"));
+    assert_eq!(captured.access_events()[0]["request_id"], "req-42");
+
+    // Ids too long to be one are replaced
+    let long = "x".repeat(129);
+    let response = client.get("/logged/users/1").header("x-request-id", &long).await;
+    assert_eq!(response.header("x-request-id").unwrap().len(), 16);
+}
+
+#[tokio::test]
+async fn nothing_is_logged_or_added_without_the_access_log() {
+    let (captured, _guard) = capture();
+    let response = TestClient::new().get("/logged/users/1").await;
+    assert_eq!(response.header("x-request-id"), None);
+    assert_eq!(response.json::<Value>()["request_id"], Value::Null);
+    assert!(captured.access_events().is_empty());
+}
@@ -0,0 +1,87 @@
+use std::net::SocketAddr;
+
+use ferrox::admin::Admin;
+use ferrox::auth::api_key::{ApiKeyAuth, InMemoryKeyStore};
+use ferrox::test::TestClient;
+use ferrox::{http_method, logging, RequestContext, RouteHandler, Server, StatusCode};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+#[http_method(GET, "/ops/orders")]
+fn orders() -> Value {
+    json!([])
+}
+
+// One request over a fresh connection, returning the status line and body
+async fn send(addr: SocketAddr, method: &str, path: &str, body: &str) -> (String, String) {
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    (head.lines().next().unwrap().to_string(), body.to_string())
+}
+
+#[tokio::test]
+async fn the_endpoints_report_the_running_server() {
+    let server = Server::new()
+        .max_in_flight(100)
+        .route("POST", "/ops/hooks", RouteHandler::from_sync(|_ctx: RequestContext| json!({})))
+        .admin(Admin::new().version("1.4.2"));
+    let client = TestClient::from_server(server);
+
+    let info = client.get("/admin/info").await.json::<Value>();
+    assert_eq!(info["version"], "1.4.2");
+    assert_eq!(info["in_flight"], 0);
+    assert!(info["uptime_secs"].is_u64());
+    assert!(info["started_at"].is_string());
+
+    let config = client.get("/admin/config").await.json::<Value>();
+    assert_eq!(config["limits"]["max_in_flight"], 100);
+    assert_eq!(config["log"]["format"], "text");
+
+    let routes = client.get("/admin/routes").await.json::<Value>();
+    let registered = routes["routes"].as_array().unwrap();
+    assert!(registered.iter().any(|route| route["path"] == "/ops/orders"));
+    assert_eq!(routes["dynamic"], json!([{ "method": "POST", "path": "/ops/hooks" }]));
+}
+
+#[tokio::test]
+async fn the_endpoints_can_require_authentication() {
+    let keys = ApiKeyAuth::new(InMemoryKeyStore::new().with_key("ops", "ops-key"));
+    let client = TestClient::from_server(Server::new().api_keys(keys).admin(Admin::new().path("/_ops").auth("api_key")));
+    assert_eq!(client.get("/_ops/info").await.status(), StatusCode::UNAUTHORIZED);
+    let response = client.get("/_ops/info").header("x-api-key", "ops-key").await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn the_endpoints_can_have_their_own_listener_and_change_the_log_level() {
+    let server = Server::new().admin(Admin::new().bind("127.0.0.1:0"));
+    let handle = server.start_in_background("127.0.0.1:0").await.unwrap();
+    let (main, admin) = (handle.local_addr().unwrap(), handle.admin_addr().unwrap());
+
+    let (status, _) = send(main, "GET", "/admin/info", "").await;
+    assert_eq!(status, "HTTP/1.1 404 Not Found");
+    let (status, body) = send(admin, "GET", "/admin/info", "").await;
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert!(serde_json::from_str::<Value>(&body).unwrap()["connections"].as_u64().unwrap() >= 1);
+    let (status, _) = send(admin, "GET", "/ops/orders", "").await;
+    assert_eq!(status, "HTTP/1.1 404 Not Found");
+
+    let (status, body) = send(admin, "PUT", "/admin/log-level", r#"{"level":"warn,ferrox=debug"}"#).await;
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap(), json!({ "level": "warn,ferrox=debug" }));
+    assert_eq!(logging::level().as_deref(), Some("warn,ferrox=debug"));
+    let (status, _) = send(admin, "PUT", "/admin/log-level", r#"{"level":"[["}"#).await;
+    assert_eq!(status, "HTTP/1.1 400 Bad Request");
+
+    handle.shutdown().await;
+}
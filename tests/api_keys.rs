@@ -0,0 +1,123 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use ferrox::auth::api_key::{ApiKey, ApiKeyAuth, FileKeyStore, InMemoryKeyStore, KeyFuture, KeyStore};
+use ferrox::axum::http::HeaderName;
+use ferrox::test::{TestClient, TestResponse};
+use ferrox::{http_method, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/keyed/reports", auth = "api_key")]
+fn reports(key: ApiKey) -> Value {
+    json!({ "client": key.name })
+}
+
+#[http_method(GET, "/keyed/public")]
+fn public() -> Value {
+    json!({ "open": true })
+}
+
+fn client(auth: ApiKeyAuth) -> TestClient {
+    TestClient::from_server(Server::new().api_keys(auth))
+}
+
+fn keys() -> InMemoryKeyStore {
+    InMemoryKeyStore::new().with_key("reporting", "k-report").with_key("billing", "k-bill")
+}
+
+fn holder(response: TestResponse) -> Result<String, String> {
+    let body = response.json::<Value>();
+    match response.status() {
+        StatusCode::OK => Ok(body["client"].as_str().unwrap().to_string()),
+        StatusCode::UNAUTHORIZED => Err(body["message"].as_str().unwrap().to_string()),
+        status => panic!("unexpected {}", status),
+    }
+}
+
+#[tokio::test]
+async fn handlers_see_the_holder_of_a_valid_key() {
+    let client = client(ApiKeyAuth::new(keys()));
+    let response = client.get("/keyed/reports").header("x-api-key", "k-bill").await;
+    assert_eq!(holder(response), Ok("billing".to_string()));
+
+    let response = client.get("/keyed/reports").await;
+    assert_eq!(holder(response), Err("Missing API key".to_string()));
+    let response = client.get("/keyed/reports").header("x-api-key", "k-bil").await;
+    assert_eq!(holder(response), Err("Invalid API key".to_string()));
+
+    // Routes without `auth` do not ask for a key
+    assert_eq!(client.get("/keyed/public").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn keys_can_come_from_another_header_or_the_query_string() {
+    let auth = ApiKeyAuth::new(keys()).header(HeaderName::from_static("x-token")).query_param("api_key");
+    let client = client(auth);
+    let response = client.get("/keyed/reports").header("x-token", "k-report").await;
+    assert_eq!(holder(response), Ok("reporting".to_string()));
+    let response = client.get("/keyed/reports?api_key=k-bill").await;
+    assert_eq!(holder(response), Ok("billing".to_string()));
+    // The header wins over the query parameter
+    let response = client.get("/keyed/reports?api_key=k-bill").header("x-token", "k-report").await;
+    assert_eq!(holder(response), Ok("reporting".to_string()));
+    assert!(holder(client.get("/keyed/reports").header("x-api-key", "k-bill").await).is_err());
+
+    let client = self::client(ApiKeyAuth::new(keys()).query_param_only("api_key"));
+    assert!(holder(client.get("/keyed/reports").header("x-api-key", "k-bill").await).is_err());
+    assert!(holder(client.get("/keyed/reports?api_key=k-bill").await).is_ok());
+}
+
+fn key_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("ferrox-api-keys-{}-{}.txt", name, std::process::id()));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[tokio::test]
+async fn file_stores_reload_when_the_file_changes() {
+    let path = key_file("reload", "# partners\nreporting: k-report\n\n");
+    let store = FileKeyStore::open(&path).unwrap().reload_interval(Duration::ZERO);
+    let client = client(ApiKeyAuth::new(store));
+    let with_key = |key: &str| client.get("/keyed/reports").header("x-api-key", key);
+    assert_eq!(holder(with_key("k-report").await), Ok("reporting".to_string()));
+
+    // Modification times can be coarse; make sure the new file looks newer
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    std::fs::write(&path, "billing:k-bill\n").unwrap();
+    assert_eq!(holder(with_key("k-bill").await), Ok("billing".to_string()));
+    assert!(holder(with_key("k-report").await).is_err());
+
+    // A broken file keeps the previous keys in use
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    std::fs::write(&path, "billing k-bill\n").unwrap();
+    assert_eq!(holder(with_key("k-bill").await), Ok("billing".to_string()));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn malformed_key_files_are_refused() {
+    let path = key_file("malformed", "reporting:k-report\nbilling\n");
+    let err = FileKeyStore::open(&path).err().unwrap();
+    assert_eq!(err.to_string(), "line 2: expected `name:key`");
+    std::fs::remove_file(path).unwrap();
+}
+
+// Keys named after their holder, as a stand-in for a database lookup
+struct PrefixStore;
+
+impl KeyStore for PrefixStore {
+    fn lookup<'a>(&'a self, key: &'a str) -> KeyFuture<'a> {
+        Box::pin(async move {
+            let name = key.strip_prefix("key-of-")?;
+            Some(ApiKey { name: name.to_string() })
+        })
+    }
+}
+
+#[tokio::test]
+async fn custom_stores_decide_who_holds_a_key() {
+    let client = client(ApiKeyAuth::new(PrefixStore));
+    let response = client.get("/keyed/reports").header("x-api-key", "key-of-ada").await;
+    assert_eq!(holder(response), Ok("ada".to_string()));
+    assert!(holder(client.get("/keyed/reports").header("x-api-key", "ada").await).is_err());
+}
@@ -0,0 +1,74 @@
+use std::time::{Duration, Instant};
+
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/async/reports/:id")]
+async fn report(path: Value, query: Value, _body: Value) -> Value {
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    json!({ "id": path["id"], "format": query["format"] })
+}
+
+#[http_method(POST, "/async/echo")]
+async fn echo(body: Value) -> Value {
+    tokio::task::yield_now().await;
+    json!({ "received": body })
+}
+
+#[http_method(GET, "/async/slow")]
+async fn slow() -> Value {
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    json!({ "done": true })
+}
+
+#[http_method(GET, "/async/missing")]
+async fn missing() -> Result<Value, FerroxError> {
+    tokio::task::yield_now().await;
+    Err(FerroxError::NotFound("Report not found".to_string()))
+}
+
+#[http_method(GET, "/async/panics")]
+async fn panics() -> Value {
+    tokio::task::yield_now().await;
+    panic!("report generator crashed");
+}
+
+#[tokio::test]
+async fn async_handlers_get_the_request_and_are_awaited() {
+    let client = TestClient::new();
+    let response = client.get("/async/reports/7?format=csv").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "id": "7", "format": "csv" }));
+
+    let response = client.post("/async/echo").json(&json!({ "name": "widget" })).await;
+    assert_eq!(response.json::<Value>(), json!({ "received": { "name": "widget" } }));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn waiting_handlers_do_not_hold_up_other_requests() {
+    let client = TestClient::new();
+    let started = Instant::now();
+    let mut requests = tokio::task::JoinSet::new();
+    for _ in 0..8 {
+        let client = client.clone();
+        requests.spawn(async move { client.get("/async/slow").await.status() });
+    }
+    while let Some(status) = requests.join_next().await {
+        assert_eq!(status.unwrap(), StatusCode::OK);
+    }
+    // Eight 200ms waits on two workers overlap rather than queue
+    assert!(started.elapsed() < Duration::from_millis(800), "{:?}", started.elapsed());
+}
+
+#[tokio::test]
+async fn async_errors_and_panics_become_error_responses() {
+    let client = TestClient::new();
+    let response = client.get("/async/missing").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.json::<Value>()["message"], "Report not found");
+
+    let response = client.get("/async/panics").await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(response.json::<Value>()["success"], false);
+}
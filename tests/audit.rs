@@ -0,0 +1,115 @@
+use ferrox::audit::{AuditLog, FileSink, MemorySink};
+use ferrox::auth::{AuthFuture, Authenticator};
+use ferrox::rbac::{Principal, Rbac};
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, Server, StatusCode};
+use serde_json::{json, Value};
+
+// Takes the principal from headers, as a real authenticator would from a token
+struct HeaderAuth;
+
+impl Authenticator for HeaderAuth {
+    fn authenticate<'a>(&'a self, request: &'a mut axum::http::request::Parts) -> AuthFuture<'a> {
+        Box::pin(async move {
+            let header = |name: &str| request.headers.get(name).and_then(|value| value.to_str().ok());
+            let user = header("x-user").ok_or_else(|| FerroxError::Unauthorized("No user".to_string()))?;
+            let roles: Vec<String> = header("x-roles").unwrap_or("").split(',').map(str::to_string).collect();
+            request.extensions.insert(Principal::new(user, roles));
+            Ok(())
+        })
+    }
+}
+
+#[http_method(PUT, "/audit/users/:id", auth = "header")]
+fn update_user(id: u64, body: Value) -> Value {
+    json!({ "id": id, "updated": body })
+}
+
+#[http_method(DELETE, "/audit/users/:id", auth = "header", permission = "users:delete")]
+fn delete_user(id: u64) -> Value {
+    json!({ "deleted": id })
+}
+
+#[http_method(GET, "/audit/users/:id")]
+fn get_user(id: u64) -> Value {
+    json!({ "id": id })
+}
+
+#[http_method(POST, "/audit/ping", audit = false)]
+fn ping() -> Value {
+    json!("pong")
+}
+
+fn client(log: AuditLog) -> TestClient {
+    TestClient::from_server(
+        Server::new()
+            .authenticator("header", HeaderAuth)
+            .rbac(Rbac::new().role("admin", ["*"]))
+            .audit(log),
+    )
+}
+
+#[tokio::test]
+async fn mutating_requests_are_recorded_with_their_principal() {
+    let sink = MemorySink::new();
+    let client = client(AuditLog::new(sink.clone()).redact_field("ssn"));
+    let response = client
+        .put("/audit/users/7")
+        .header("x-user", "ada")
+        .header("x-roles", "editor")
+        .json(&json!({ "email": "ada@example.com", "password": "hunter2", "profile": { "ssn": "123" } }))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let records = sink.records();
+    assert_eq!(records.len(), 1);
+    let record = &records[0];
+    assert_eq!(record.principal.as_deref(), Some("ada"));
+    assert_eq!(record.roles, ["editor"]);
+    assert_eq!(record.method, "PUT");
+    assert_eq!(record.route, "/audit/users/:id");
+    assert_eq!(record.path, "/audit/users/7");
+    assert_eq!(record.status, 200);
+    assert_eq!(record.body_sha256.as_ref().map(String::len), Some(64));
+    assert_eq!(
+        record.body,
+        Some(json!({ "email": "ada@example.com", "password": "[redacted]", "profile": { "ssn": "[redacted]" } }))
+    );
+}
+
+#[tokio::test]
+async fn refusals_are_recorded_and_reads_and_opted_out_routes_are_not() {
+    let sink = MemorySink::new();
+    let client = client(AuditLog::new(sink.clone()).bodies(false));
+    let response = client.delete("/audit/users/7").header("x-user", "mallory").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(client.get("/audit/users/7").await.status(), StatusCode::OK);
+    assert_eq!(client.post("/audit/ping").await.status(), StatusCode::OK);
+    // Unauthenticated, so there is nobody to record
+    assert_eq!(client.delete("/audit/users/7").await.status(), StatusCode::UNAUTHORIZED);
+
+    let records = sink.records();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].principal.as_deref(), Some("mallory"));
+    assert_eq!(records[0].status, 403);
+    assert_eq!(records[0].body_sha256, None);
+    assert_eq!(records[0].body, None);
+}
+
+#[tokio::test]
+async fn file_sinks_append_json_lines() {
+    let path = std::env::temp_dir().join(format!("ferrox-audit-{}.ndjson", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let client = client(AuditLog::new(FileSink::new(&path)));
+    for id in [1, 2] {
+        let response = client.put(&format!("/audit/users/{}", id)).header("x-user", "ada").json(&json!({})).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    let paths: Vec<String> = contents
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap()["path"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(paths, ["/audit/users/1", "/audit/users/2"]);
+}
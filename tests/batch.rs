@@ -0,0 +1,94 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use ferrox::batch::Batch;
+use ferrox::test::TestClient;
+use ferrox::{http_method, RequestContext, Server, StatusCode};
+use serde_json::{json, Value};
+
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+static MOST_RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+#[http_method(GET, "/batch-test/users/:id")]
+fn user(id: u64) -> Value {
+    json!({ "id": id })
+}
+
+#[http_method(POST, "/batch-test/echo")]
+fn echo(ctx: &RequestContext, body: Value) -> Value {
+    json!({ "body": body, "token": ctx.header("x-token"), "locale": ctx.header("accept-language") })
+}
+
+#[http_method(GET, "/batch-test/slow")]
+async fn slow() -> Value {
+    let running = RUNNING.fetch_add(1, Ordering::SeqCst) + 1;
+    MOST_RUNNING.fetch_max(running, Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    RUNNING.fetch_sub(1, Ordering::SeqCst);
+    json!({ "done": true })
+}
+
+#[tokio::test]
+async fn batched_requests_answer_in_order() {
+    let client = TestClient::from_server(Server::new().batch(Batch::new()));
+    let batch = json!([
+        { "method": "GET", "path": "/batch-test/users/7" },
+        { "method": "post", "path": "/batch-test/echo", "body": { "sku": "A-1" } },
+        { "method": "GET", "path": "/batch-test/missing" },
+        { "method": "GET", "path": "/batch-test/users/seven" },
+    ]);
+    let response = client.post("/batch").json(&batch).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let responses = response.json::<Value>();
+    let statuses: Vec<u64> = responses.as_array().unwrap().iter().map(|item| item["status"].as_u64().unwrap()).collect();
+    assert_eq!(statuses, [200, 200, 404, 400]);
+    assert_eq!(responses[0]["body"], json!({ "id": 7 }));
+    assert_eq!(responses[0]["headers"]["content-type"], "application/json");
+    assert_eq!(responses[1]["body"]["body"], json!({ "sku": "A-1" }));
+}
+
+#[tokio::test]
+async fn batched_requests_carry_the_batch_headers_and_their_own() {
+    let client = TestClient::from_server(Server::new().batch(Batch::new().path("/_batch")));
+    let batch = json!([
+        { "method": "POST", "path": "/batch-test/echo", "body": 1 },
+        { "method": "POST", "path": "/batch-test/echo", "body": 2, "headers": { "x-token": "own", "accept-language": "de" } },
+    ]);
+    let response = client.post("/_batch").header("x-token", "shared").json(&batch).await;
+    let responses = response.json::<Value>();
+    assert_eq!(responses[0]["body"], json!({ "body": 1, "token": "shared", "locale": null }));
+    assert_eq!(responses[1]["body"], json!({ "body": 2, "token": "own", "locale": "de" }));
+}
+
+#[tokio::test]
+async fn batches_are_checked_before_they_run() {
+    let client = TestClient::from_server(Server::new().batch(Batch::new().max_requests(2)));
+    let too_many = json!([
+        { "method": "GET", "path": "/batch-test/users/1" },
+        { "method": "GET", "path": "/batch-test/users/2" },
+        { "method": "GET", "path": "/batch-test/users/3" },
+    ]);
+    assert_eq!(client.post("/batch").json(&too_many).await.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(client.post("/batch").body("not json").await.status(), StatusCode::BAD_REQUEST);
+
+    let invalid = json!([
+        { "method": "GET", "path": "/batch" },
+        { "method": "GET", "path": "users/1" },
+    ]);
+    let responses = client.post("/batch").json(&invalid).await.json::<Value>();
+    assert_eq!(responses[0]["status"], 400);
+    assert_eq!(responses[1]["status"], 400);
+
+    // Not served unless configured
+    let client = TestClient::from_server(Server::new());
+    assert_eq!(client.post("/batch").json(&json!([])).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn batches_run_a_bounded_number_of_requests_at_once() {
+    let client = TestClient::from_server(Server::new().batch(Batch::new().concurrency(2)));
+    let batch: Vec<Value> = (0..6).map(|_| json!({ "method": "GET", "path": "/batch-test/slow" })).collect();
+    let responses = client.post("/batch").json(&batch).await.json::<Value>();
+    assert_eq!(responses.as_array().unwrap().len(), 6);
+    assert_eq!(MOST_RUNNING.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use ferrox::bench::LoadTest;
+use ferrox::{http_method, FerroxError, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/bench/items/:id")]
+fn bench_item(id: u64) -> Value {
+    json!({ "id": id })
+}
+
+#[http_method(POST, "/bench/items")]
+async fn bench_create(body: Value) -> Result<Value, FerroxError> {
+    match body["name"].as_str() {
+        Some(name) => Ok(json!({ "name": name })),
+        None => Err(FerroxError::new(StatusCode::UNPROCESSABLE_ENTITY, "name is required")),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn reports_the_mix_by_request() {
+    let report = LoadTest::new(Server::new().quiet())
+        .request("show", 3, |client| client.get("/bench/items/42"))
+        .request("create", 1, |client| client.post("/bench/items").json(&json!({ "name": "widget" })))
+        .request("invalid", 0, |client| client.post("/bench/items").json(&json!({})))
+        .concurrency(16)
+        .requests(400)
+        .run()
+        .await;
+
+    assert_eq!(report.count(), 400);
+    assert_eq!(report.failures(), 0);
+    assert_eq!(report.statuses().get(&200), Some(&400));
+    assert_eq!(report.request("show").unwrap().count(), 300);
+    assert_eq!(report.request("create").unwrap().count(), 100);
+    assert_eq!(report.request("invalid").unwrap().count(), 0);
+    assert!(report.percentile(50.0) <= report.percentile(99.0));
+    assert!(report.percentile(99.0) <= report.max());
+    assert!(report.throughput() > 0.0);
+    assert!(report.to_string().contains("show: 300 requests, 0 failed"), "{}", report);
+}
+
+#[tokio::test]
+async fn runs_for_a_duration_and_counts_failures() {
+    let report = LoadTest::new(Server::new().quiet())
+        .request("invalid", 1, |client| client.post("/bench/items").json(&json!({})))
+        .duration(Duration::from_millis(200))
+        .run()
+        .await;
+
+    assert!(report.count() > 0);
+    assert_eq!(report.failures(), report.count());
+    assert_eq!(report.statuses().get(&422), Some(&report.count()));
+    assert!(report.elapsed() >= Duration::from_millis(200));
+}
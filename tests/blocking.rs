@@ -0,0 +1,68 @@
+use std::time::{Duration, Instant};
+
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/thread", blocking = true)]
+fn thread() -> Value {
+    json!({ "thread": std::thread::current().name() })
+}
+
+#[http_method(GET, "/crunch", blocking = true)]
+fn crunch() -> Value {
+    std::thread::sleep(Duration::from_millis(150));
+    json!({ "done": true })
+}
+
+#[http_method(GET, "/quick")]
+async fn quick() -> Value {
+    json!({ "done": true })
+}
+
+#[http_method(GET, "/broken", blocking = true)]
+fn broken() -> Value {
+    panic!("corrupt image")
+}
+
+#[tokio::test]
+async fn blocking_handlers_leave_the_async_worker_free() {
+    // #[tokio::test] has a single worker, which an inline handler would stall
+    let client = TestClient::new();
+    let started = Instant::now();
+    let (crunch, quick) = tokio::join!(
+        async { client.get("/crunch").await },
+        async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            let response = client.get("/quick").await;
+            (response, started.elapsed())
+        }
+    );
+    assert_eq!(crunch.status(), StatusCode::OK);
+    assert_eq!(quick.0.status(), StatusCode::OK);
+    assert!(quick.1 < Duration::from_millis(120), "quick request took {:?}", quick.1);
+}
+
+#[tokio::test]
+async fn a_dedicated_pool_runs_handlers_on_its_own_threads() {
+    let client = TestClient::from_server(Server::new().blocking_threads(1));
+    let response = client.get("/thread").await;
+    assert_eq!(response.json::<Value>(), json!({ "thread": "ferrox-blocking-0" }));
+
+    // One thread, so the second request waits for the first
+    let started = Instant::now();
+    let (first, second) = tokio::join!(
+        async { client.get("/crunch").await },
+        async { client.get("/crunch").await }
+    );
+    assert_eq!((first.status(), second.status()), (StatusCode::OK, StatusCode::OK));
+    assert!(started.elapsed() >= Duration::from_millis(300));
+}
+
+#[tokio::test]
+async fn panics_on_the_pool_answer_500() {
+    let client = TestClient::from_server(Server::new().blocking_threads(1));
+    assert_eq!(client.get("/broken").await.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    // The thread survives the panic
+    assert_eq!(client.get("/thread").await.status(), StatusCode::OK);
+}
@@ -0,0 +1,58 @@
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(POST, "/limits/default")]
+fn default_limit(body: Value) -> Value {
+    json!({ "len": body.as_str().map(str::len) })
+}
+
+#[http_method(POST, "/limits/small", max_body_size = "1KB")]
+fn small_limit(body: Value) -> Value {
+    json!({ "len": body.as_str().map(str::len) })
+}
+
+fn string_body(len: usize) -> String {
+    format!("\"{}\"", "x".repeat(len))
+}
+
+#[tokio::test]
+async fn bodies_within_the_limit_are_read() {
+    let response = TestClient::new().post("/limits/small").body(string_body(1000)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "len": 1000 }));
+}
+
+#[tokio::test]
+async fn route_limits_reject_larger_bodies() {
+    let response = TestClient::new().post("/limits/small").body(string_body(2000)).await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(
+        response.json::<Value>()["message"],
+        "Request body exceeds the limit of 1024 bytes"
+    );
+}
+
+#[tokio::test]
+async fn declared_lengths_over_the_limit_are_rejected_upfront() {
+    let response = TestClient::new()
+        .post("/limits/small")
+        .header("content-length", "5000000")
+        .await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn the_server_limit_applies_to_other_routes() {
+    let client = TestClient::new();
+    assert_eq!(client.post("/limits/default").body(string_body(1 << 20)).await.status(), StatusCode::OK);
+    assert_eq!(
+        client.post("/limits/default").body(string_body(3 << 20)).await.status(),
+        StatusCode::PAYLOAD_TOO_LARGE
+    );
+
+    let client = TestClient::from_server(Server::new().max_body_size(100));
+    assert_eq!(client.post("/limits/default").body(string_body(200)).await.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    // The route's own option still wins
+    assert_eq!(client.post("/limits/small").body(string_body(200)).await.status(), StatusCode::OK);
+}
@@ -0,0 +1,142 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ferrox::admin::Admin;
+use ferrox::body_log::BodyLog;
+use ferrox::test::TestClient;
+use ferrox::{http_method, RequestContext, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(POST, "/debugged/logins")]
+fn login(ctx: &RequestContext) -> Value {
+    json!({ "user": ctx.body["user"], "token": "issued-token", "profile": { "card": { "number": "4111" } } })
+}
+
+#[http_method(GET, "/debugged/orders")]
+fn orders() -> Value {
+    json!([{ "id": 1 }])
+}
+
+// Log output, collected for the test's thread
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Captured {
+    fn install(&self) -> tracing::subscriber::DefaultGuard {
+        let writer = self.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .with_max_level(tracing::Level::INFO)
+            .finish();
+        tracing::subscriber::set_default(subscriber)
+    }
+
+    fn lines(&self, target: &str) -> Vec<String> {
+        String::from_utf8_lossy(&self.0.lock().unwrap())
+            .lines()
+            .filter(|line| line.contains(target))
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn matching_bodies_are_logged_with_secrets_redacted() {
+    let captured = Captured::default();
+    let _guard = captured.install();
+    let log = BodyLog::new().redact("$..card.number").enabled(true).routes(["POST /debugged/*"]);
+    let client = TestClient::from_server(Server::new().body_logging(log));
+
+    let response = client.post("/debugged/logins").json(&json!({ "user": "ada", "password": "hunter2" })).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>()["token"], "issued-token");
+    assert_eq!(client.get("/debugged/orders").await.status(), StatusCode::OK);
+
+    let lines = captured.lines("ferrox::body_log");
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].contains("/debugged/logins"));
+    assert!(lines[0].contains("ada"));
+    assert!(lines[0].contains("[redacted]"));
+    for secret in ["hunter2", "issued-token", "4111"] {
+        assert!(!lines[0].contains(secret), "{} was logged", secret);
+    }
+}
+
+#[tokio::test]
+async fn logged_bodies_are_cut_at_the_size_cap() {
+    let captured = Captured::default();
+    let _guard = captured.install();
+    let log = BodyLog::new().max_body_size(8).enabled(true);
+    let client = TestClient::from_server(Server::new().body_logging(log));
+
+    let long = "x".repeat(100);
+    client.post("/debugged/logins").json(&json!({ "user": long })).await;
+
+    let lines = captured.lines("ferrox::body_log");
+    assert_eq!(lines.len(), 1);
+    assert!(!lines[0].contains(&long));
+    assert!(lines[0].contains("bytes]"));
+}
+
+#[tokio::test]
+async fn nothing_is_logged_until_enabled_from_the_admin_endpoint() {
+    let captured = Captured::default();
+    let _guard = captured.install();
+    let log = BodyLog::new();
+    let client = TestClient::from_server(Server::new().body_logging(log.clone()).admin(Admin::new()));
+
+    client.get("/debugged/orders").await;
+    assert!(captured.lines("ferrox::body_log").is_empty());
+    let status = client.get("/admin/body-log").await.json::<Value>();
+    assert_eq!(status, json!({ "enabled": false, "routes": [], "expires_in_secs": null }));
+
+    let response = client
+        .put("/admin/body-log")
+        .json(&json!({ "enabled": true, "routes": ["GET /debugged/orders"], "for_secs": 600 }))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let status = response.json::<Value>();
+    assert_eq!(status["enabled"], true);
+    assert_eq!(status["routes"], json!(["GET /debugged/orders"]));
+    assert!(status["expires_in_secs"].as_u64().unwrap() > 590);
+    assert!(log.status().enabled);
+
+    client.get("/debugged/orders").await;
+    assert_eq!(captured.lines("ferrox::body_log").len(), 1);
+
+    client.put("/admin/body-log").json(&json!({ "enabled": false })).await;
+    client.get("/debugged/orders").await;
+    assert_eq!(captured.lines("ferrox::body_log").len(), 1);
+
+    let response = client.put("/admin/body-log").json(&json!({ "routes": [] })).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn logging_turns_itself_off_when_its_time_is_up() {
+    let log = BodyLog::new();
+    log.enable(["/debugged/*"], Some(Duration::from_millis(20)));
+    assert!(log.status().enabled);
+    tokio::time::sleep(Duration::from_millis(40)).await;
+    assert!(!log.status().enabled);
+    assert!(log.status().routes.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "invalid JSON path")]
+fn invalid_redaction_paths_are_refused() {
+    BodyLog::new().redact("card.number");
+}
@@ -0,0 +1,76 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, Server, StatusCode};
+use serde_json::{json, Value};
+
+static USER_CALLS: AtomicUsize = AtomicUsize::new(0);
+static CLOCK_CALLS: AtomicUsize = AtomicUsize::new(0);
+static ITEM_CALLS: AtomicUsize = AtomicUsize::new(0);
+static FLAKY_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+#[http_method(GET, "/users", cache = "30s")]
+fn list_users(query: Value) -> Value {
+    let calls = USER_CALLS.fetch_add(1, Ordering::SeqCst) + 1;
+    json!({ "calls": calls, "query": query })
+}
+
+#[http_method(GET, "/clock", cache = "50ms")]
+fn clock() -> Value {
+    json!({ "calls": CLOCK_CALLS.fetch_add(1, Ordering::SeqCst) + 1 })
+}
+
+#[http_method(GET, "/items/:id", cache = "30s")]
+fn get_item(id: u64) -> Value {
+    json!({ "id": id, "calls": ITEM_CALLS.fetch_add(1, Ordering::SeqCst) + 1 })
+}
+
+#[http_method(GET, "/flaky", cache = "30s")]
+fn flaky() -> Result<Value, FerroxError> {
+    match FLAKY_CALLS.fetch_add(1, Ordering::SeqCst) {
+        0 => Err(FerroxError::NotFound("Not there yet".to_string())),
+        calls => Ok(json!({ "calls": calls + 1 })),
+    }
+}
+
+#[tokio::test]
+async fn responses_are_served_from_the_cache_until_invalidated() {
+    let client = TestClient::new();
+    let first = client.get("/users?page=1").await.json::<Value>();
+    assert_eq!(client.get("/users?page=1").await.json::<Value>(), first);
+    // Another query string is another entry
+    let second = client.get("/users?page=2").await.json::<Value>();
+    assert_ne!(second["calls"], first["calls"]);
+
+    ferrox::cache::invalidate("/users");
+    let fresh = client.get("/users?page=1").await.json::<Value>();
+    assert_ne!(fresh["calls"], first["calls"]);
+    assert_eq!(fresh["query"], json!({ "page": "1" }));
+}
+
+#[tokio::test]
+async fn entries_expire_after_the_ttl() {
+    let client = TestClient::new();
+    let first = client.get("/clock").await.json::<Value>();
+    assert_eq!(client.get("/clock").await.json::<Value>(), first);
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    assert_ne!(client.get("/clock").await.json::<Value>(), first);
+}
+
+#[tokio::test]
+async fn the_least_recently_used_entry_is_dropped_at_capacity() {
+    let client = TestClient::from_server(Server::new().cache_capacity(1));
+    let first = client.get("/items/1").await.json::<Value>();
+    assert_eq!(client.get("/items/1").await.json::<Value>(), first);
+    client.get("/items/2").await;
+    assert_ne!(client.get("/items/1").await.json::<Value>(), first);
+}
+
+#[tokio::test]
+async fn errors_are_not_cached() {
+    let client = TestClient::new();
+    assert_eq!(client.get("/flaky").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(client.get("/flaky").await.json::<Value>(), json!({ "calls": 2 }));
+    assert_eq!(client.get("/flaky").await.json::<Value>(), json!({ "calls": 2 }));
+}
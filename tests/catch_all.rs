@@ -0,0 +1,56 @@
+use ferrox::test::TestClient;
+use ferrox::{http_method, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/users/{id}")]
+fn get_user(id: u64) -> Value {
+    json!({ "id": id })
+}
+
+#[http_method(GET, "/files/{*path}")]
+fn get_file(path: String) -> Value {
+    json!({ "path": path })
+}
+
+#[http_method(GET, "/repos/{owner}/tree/{*path}")]
+fn repo_tree(owner: String, path: String) -> Value {
+    json!({ "owner": owner, "path": path })
+}
+
+// An SPA fallback: every GET no other route matches
+#[http_method(GET, "/{*rest}")]
+fn spa(rest: String) -> Value {
+    json!({ "page": rest })
+}
+
+#[tokio::test]
+async fn brace_placeholders_match_one_segment() {
+    let client = TestClient::new();
+    assert_eq!(client.get("/users/7").await.json::<Value>(), json!({ "id": 7 }));
+}
+
+#[tokio::test]
+async fn a_catch_all_receives_the_rest_of_the_path() {
+    let client = TestClient::new();
+    let response = client.get("/files/reports/2026/q3%20summary.pdf").await;
+    assert_eq!(response.json::<Value>(), json!({ "path": "reports/2026/q3 summary.pdf" }));
+
+    let response = client.get("/repos/ferrox/tree/src/lib.rs").await;
+    assert_eq!(response.json::<Value>(), json!({ "owner": "ferrox", "path": "src/lib.rs" }));
+}
+
+#[tokio::test]
+async fn a_root_catch_all_serves_unmatched_paths() {
+    let client = TestClient::new();
+    assert_eq!(client.get("/settings/profile").await.json::<Value>(), json!({ "page": "settings/profile" }));
+    // Other routes still win
+    assert_eq!(client.get("/users/1").await.json::<Value>(), json!({ "id": 1 }));
+    assert_eq!(client.post("/settings").await.status(), StatusCode::METHOD_NOT_ALLOWED);
+}
+
+#[tokio::test]
+async fn the_listing_uses_router_syntax() {
+    let paths: Vec<_> = ferrox::routes().into_iter().map(|route| route.path).collect();
+    assert!(paths.contains(&"/files/*path"));
+    assert!(paths.contains(&"/users/:id"));
+}
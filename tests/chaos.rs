@@ -0,0 +1,88 @@
+use std::time::{Duration, Instant};
+
+use ferrox::axum::http::Method;
+use ferrox::chaos::{Chaos, Fault};
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, StatusCode};
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper::Request;
+use hyper_util::rt::TokioIo;
+use serde_json::{json, Value};
+
+#[http_method(GET, "/chaos/orders/:id")]
+fn get_order(id: u64) -> Value {
+    json!({ "id": id })
+}
+
+#[http_method(POST, "/chaos/orders/:id")]
+fn update_order(id: u64) -> Value {
+    json!({ "id": id, "updated": true })
+}
+
+#[http_method(GET, "/chaos/stable")]
+fn stable() -> Value {
+    json!({ "ok": true })
+}
+
+fn unavailable() -> Fault {
+    Fault::error(StatusCode::SERVICE_UNAVAILABLE).on("/chaos/orders/*")
+}
+
+#[tokio::test]
+async fn faults_are_only_injected_when_enabled() {
+    let client = TestClient::from_server(Server::new().chaos(Chaos::new().inject(unavailable())));
+    assert_eq!(client.get("/chaos/orders/1").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn errors_are_injected_into_matching_routes() {
+    let chaos = Chaos::new()
+        .enabled(true)
+        .inject(unavailable().method(Method::GET))
+        .inject(Fault::error(StatusCode::INTERNAL_SERVER_ERROR).on("/chaos/stable").probability(0.0));
+    let client = TestClient::from_server(Server::new().chaos(chaos));
+
+    let response = client.get("/chaos/orders/1").await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.header("x-chaos-fault"), Some("error"));
+    assert_eq!(client.post("/chaos/orders/1").await.status(), StatusCode::OK);
+    assert_eq!(client.get("/chaos/stable").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn health_probes_are_never_affected() {
+    let chaos = Chaos::new().enabled(true).inject(Fault::error(StatusCode::SERVICE_UNAVAILABLE));
+    let client = TestClient::from_server(Server::new().enable_health_checks().chaos(chaos));
+    assert_eq!(client.get("/chaos/stable").await.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(client.get("/livez").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn latency_delays_matching_requests() {
+    let delay = Duration::from_millis(150);
+    let chaos = Chaos::new().enabled(true).inject(Fault::latency(delay..delay * 2).on("/chaos/orders/*"));
+    let client = TestClient::from_server(Server::new().chaos(chaos));
+
+    let started = Instant::now();
+    let response = client.get("/chaos/orders/2").await;
+    assert!(started.elapsed() >= delay);
+    assert_eq!(response.json::<Value>(), json!({ "id": 2 }));
+}
+
+#[tokio::test]
+async fn dropped_connections_end_without_a_complete_response() {
+    let chaos = Chaos::new().enabled(true).inject(Fault::drop().on("/chaos/orders/*"));
+    let handle = Server::new().chaos(chaos).start_in_background("127.0.0.1:0").await.unwrap();
+    let stream = tokio::net::TcpStream::connect(handle.local_addr().unwrap()).await.unwrap();
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+    tokio::spawn(connection);
+
+    let request = Request::get("/chaos/orders/3").header("host", "localhost").body(Empty::<Bytes>::new()).unwrap();
+    let completed = match sender.send_request(request).await {
+        Ok(response) => response.into_body().collect().await.is_ok(),
+        Err(_) => false,
+    };
+    assert!(!completed);
+    handle.shutdown().await;
+}
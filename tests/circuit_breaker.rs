@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use ferrox::resilience::{CircuitBreaker, CircuitError, CircuitMetrics, CircuitState};
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, Server, State, StatusCode};
+use serde_json::{json, Value};
+
+async fn fail() -> Result<(), &'static str> {
+    Err("connection refused")
+}
+
+async fn succeed() -> Result<(), &'static str> {
+    Ok(())
+}
+
+#[http_method(GET, "/circuits/quote", circuits = ["pricing"])]
+async fn quote(pricing: State<CircuitBreaker>) -> Result<Value, FerroxError> {
+    pricing
+        .call(|| async { Err::<(), _>(FerroxError::Internal("pricing is down".to_string())) })
+        .await?;
+    Ok(json!({ "price": 10 }))
+}
+
+#[tokio::test]
+async fn circuits_open_after_failures_and_close_after_a_probe() {
+    let breaker = CircuitBreaker::new("inventory").failure_threshold(2).open_for(Duration::from_millis(50));
+    assert_eq!(breaker.call(fail).await, Err(CircuitError::Failed("connection refused")));
+    assert_eq!(breaker.state(), CircuitState::Closed);
+    breaker.call(fail).await.unwrap_err();
+    assert_eq!(breaker.state(), CircuitState::Open);
+    assert!(matches!(breaker.call(succeed).await, Err(CircuitError::Open { .. })));
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(breaker.state(), CircuitState::HalfOpen);
+    // A failed probe opens the circuit again
+    breaker.call(fail).await.unwrap_err();
+    assert_eq!(breaker.state(), CircuitState::Open);
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    breaker.call(succeed).await.unwrap();
+    assert_eq!(breaker.state(), CircuitState::Closed);
+    let metrics = CircuitMetrics {
+        successes: 1,
+        failures: 3,
+        rejected: 1,
+        opened: 2,
+    };
+    assert_eq!(breaker.metrics(), metrics);
+}
+
+#[tokio::test]
+async fn routes_answer_503_while_their_circuit_is_open() {
+    let pricing = CircuitBreaker::new("pricing").failure_threshold(1).open_for(Duration::from_secs(30));
+    let server = Server::new().circuit_breaker(pricing.clone()).with_state(pricing).enable_metrics();
+    let client = TestClient::from_server(server);
+
+    // The failing call opens the circuit and answers as the call failed
+    assert_eq!(client.get("/circuits/quote").await.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let response = client.get("/circuits/quote").await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.header("retry-after"), Some("30"));
+    assert_eq!(response.json::<Value>()["message"], "Service unavailable: dependency `pricing` is failing");
+
+    let metrics = client.get("/metrics").await.text().to_string();
+    assert!(metrics.contains("ferrox_circuit_state{circuit=\"pricing\"} 2"));
+    assert!(metrics.contains("ferrox_circuit_calls_total{circuit=\"pricing\",outcome=\"failure\"} 1"));
+}
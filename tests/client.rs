@@ -0,0 +1,95 @@
+#![cfg(feature = "client")]
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::any;
+use axum::Router;
+use ferrox::client::Client;
+use ferrox::jobs::RetryPolicy;
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, Server, State};
+use serde_json::{json, Value};
+
+// A service answering with the given statuses in turn, then 200 with the headers it received
+async fn upstream(statuses: &[StatusCode]) -> SocketAddr {
+    let statuses = Arc::new(Mutex::new(statuses.iter().rev().copied().collect::<Vec<_>>()));
+    let app = Router::new().route(
+        "/echo",
+        any(move |headers: HeaderMap| async move {
+            let status = statuses.lock().unwrap().pop().unwrap_or(StatusCode::OK);
+            let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+            let body = json!({ "request_id": header("x-request-id"), "traceparent": header("traceparent") });
+            (status, axum::Json(body))
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    addr
+}
+
+#[derive(Clone)]
+struct Upstream(String);
+
+#[http_method(GET, "/client/echo")]
+async fn echo(client: State<Client>, upstream: State<Upstream>) -> Result<Value, FerroxError> {
+    let response = client.get(format!("{}/echo", upstream.0 .0)).send().await?;
+    Ok(response.json().await?)
+}
+
+#[tokio::test]
+async fn requests_carry_the_request_id_and_trace_context() {
+    let addr = upstream(&[]).await;
+    let server =
+        Server::new().access_log(true).with_state(Client::new()).with_state(Upstream(format!("http://{}", addr)));
+    let client = TestClient::from_server(server);
+    let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    let response = client.get("/client/echo").header("x-request-id", "req-42").header("traceparent", traceparent).await;
+    assert_eq!(response.json::<Value>(), json!({ "request_id": "req-42", "traceparent": traceparent }));
+
+    // Outside a request there is nothing to propagate
+    let response = Client::new().get(format!("http://{}/echo", addr)).send().await.unwrap();
+    assert_eq!(response.json::<Value>().await.unwrap(), json!({ "request_id": null, "traceparent": null }));
+}
+
+#[tokio::test]
+async fn idempotent_requests_are_retried() {
+    let unavailable = [StatusCode::SERVICE_UNAVAILABLE, StatusCode::BAD_GATEWAY];
+    let client = Client::new().retry(RetryPolicy::fixed(3, Duration::from_millis(10)));
+    let addr = upstream(&unavailable).await;
+    let response = client.get(format!("http://{}/echo", addr)).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // A POST is sent once, unless marked idempotent
+    let addr = upstream(&unavailable).await;
+    let response = client.post(format!("http://{}/echo", addr)).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let response = client.post(format!("http://{}/echo", addr)).idempotent(true).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Attempts run out with the last answer
+    let addr = upstream(&[StatusCode::SERVICE_UNAVAILABLE; 3]).await;
+    let response = client.get(format!("http://{}/echo", addr)).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn failed_calls_answer_502_and_are_counted() {
+    // Nothing listens on the port once the listener is dropped
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+    let server = Server::new()
+        .with_state(Client::new())
+        .with_state(Upstream(format!("http://{}", closed)))
+        .enable_metrics();
+    let client = TestClient::from_server(server);
+    let response = client.get("/client/echo").await;
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(response.json::<Value>()["message"], "Upstream service request failed");
+
+    let metrics = client.get("/metrics").await.text().to_string();
+    assert!(metrics.contains("ferrox_http_client_requests_total{host=\"127.0.0.1\",method=\"GET\",status=\"error\"}"));
+    assert!(metrics.contains("ferrox_http_client_request_duration_seconds_count{host=\"127.0.0.1\",method=\"GET\"}"));
+}
@@ -0,0 +1,60 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use ferrox::test::TestClient;
+use ferrox::{http_method, StatusCode};
+use futures_util::future::join_all;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+static UNCOALESCED_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Deserialize)]
+struct Report {
+    region: String,
+}
+
+#[http_method(GET, "/coalescing/report", coalesce = true)]
+async fn report(query: Report) -> Value {
+    let call = CALLS.fetch_add(1, Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    json!({ "region": query.region, "call": call })
+}
+
+#[http_method(GET, "/coalescing/plain")]
+async fn plain() -> Value {
+    UNCOALESCED_CALLS.fetch_add(1, Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    json!({})
+}
+
+#[tokio::test]
+async fn identical_requests_share_one_handler_call() {
+    let client = TestClient::new();
+    let responses = join_all((0..5).map(|_| client.get("/coalescing/report?region=eu").into_future())).await;
+    assert!(responses.iter().all(|response| response.status() == StatusCode::OK));
+    let bodies: Vec<Value> = responses.iter().map(|response| response.json()).collect();
+    assert!(bodies.iter().all(|body| *body == bodies[0]));
+
+    // Once answered, the next request runs the handler again
+    let response = client.get("/coalescing/report?region=eu").await;
+    assert_ne!(response.json::<Value>()["call"], bodies[0]["call"]);
+
+    join_all((0..3).map(|_| client.get("/coalescing/plain").into_future())).await;
+    assert_eq!(UNCOALESCED_CALLS.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn requests_differing_by_query_or_credentials_run_separately() {
+    let client = TestClient::new();
+    let requests = [
+        client.get("/coalescing/report?region=us"),
+        client.get("/coalescing/report?region=ap"),
+        client.get("/coalescing/report?region=us").header("authorization", "Bearer other"),
+    ];
+    let responses = join_all(requests.map(|request| request.into_future())).await;
+    let calls: Vec<u64> = responses.iter().map(|response| response.json::<Value>()["call"].as_u64().unwrap()).collect();
+    assert_ne!(calls[0], calls[2]);
+    assert_eq!(responses[1].json::<Value>()["region"], "ap");
+}
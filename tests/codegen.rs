@@ -0,0 +1,54 @@
+use ferrox::codegen::{self, ClientConfig};
+use ferrox::http_method;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+struct Filters {
+    page: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct NewUser {
+    name: String,
+}
+
+#[http_method(GET, "/users/:id")]
+fn get_user(id: u64, query: Filters) -> Value {
+    json!({ "id": id, "page": query.page })
+}
+
+#[http_method(POST, "/users")]
+async fn create_user(body: NewUser) -> Value {
+    json!({ "name": body.name })
+}
+
+#[http_method(GET, "/files/*path")]
+fn download(path: Value, query: Value, body: Value) -> Value {
+    json!([path, query, body])
+}
+
+#[test]
+fn the_rust_client_has_a_function_per_route() {
+    let client = codegen::rust_client(&ClientConfig::new("UsersApi"));
+    assert!(client.contains("pub struct UsersApi {"), "{}", client);
+    assert!(client.contains(
+        "pub async fn get_user<R: serde::de::DeserializeOwned>(&self, id: u64, query: &impl serde::Serialize) -> Result<R, ClientError>"
+    ));
+    assert!(client.contains("let url = format!(\"{}/users/{}\", self.base_url, encode(&id.to_string(), false));"));
+    assert!(client.contains("self.http.request(reqwest::Method::POST, url).json(body);"));
+    // Wildcards keep their slashes, and untyped GET bodies are left out
+    assert!(client.contains("pub async fn download<R: serde::de::DeserializeOwned>(&self, path: &str) -> Result<R, ClientError>"));
+    assert!(client.contains("encode(&path.to_string(), true)"));
+}
+
+#[test]
+fn the_typescript_client_has_a_method_per_route() {
+    let client = codegen::typescript_client(&ClientConfig::default());
+    assert!(client.contains("export class ApiClient {"), "{}", client);
+    assert!(client.contains("export type Filters = Record<string, unknown>;"));
+    assert!(client.contains("async getUser<T = unknown>(id: number, query: Filters): Promise<T> {"));
+    assert!(client.contains("return this.request<T>(\"GET\", `/users/${encodeURIComponent(String(id))}`, query, undefined);"));
+    assert!(client.contains("async createUser<T = unknown>(body: NewUser): Promise<T> {"));
+    assert!(client.contains("async download<T = unknown>(path: string): Promise<T> {"));
+}
@@ -0,0 +1,5 @@
+// Malformed routes are reported at compile time, pointing at the bad path or method
+#[test]
+fn malformed_routes_do_not_compile() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
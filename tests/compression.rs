@@ -0,0 +1,106 @@
+use std::io::{Read, Write};
+
+use ferrox::compression::{CompressionConfig, Encoding};
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/compression/large")]
+fn large() -> Value {
+    json!({ "items": vec!["a fairly repetitive item"; 200] })
+}
+
+#[http_method(GET, "/compression/small")]
+fn small() -> Value {
+    json!({ "ok": true })
+}
+
+#[http_method(POST, "/compression/echo")]
+fn echo(body: Value) -> Value {
+    body
+}
+
+fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn gunzip(bytes: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::new();
+    flate2::read::GzDecoder::new(bytes).read_to_end(&mut decoded).unwrap();
+    decoded
+}
+
+#[tokio::test]
+async fn large_responses_are_compressed_when_accepted() {
+    let client = TestClient::from_server(Server::new().compression(CompressionConfig::new()));
+    let response = client.get("/compression/large").header("accept-encoding", "gzip").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("content-encoding"), Some("gzip"));
+    let body: Value = serde_json::from_slice(&gunzip(response.bytes())).unwrap();
+    assert_eq!(body["items"].as_array().unwrap().len(), 200);
+
+    let response = client.get("/compression/large").await;
+    assert_eq!(response.header("content-encoding"), None);
+}
+
+#[tokio::test]
+async fn small_responses_are_sent_as_is() {
+    let client = TestClient::from_server(Server::new().compression(CompressionConfig::new()));
+    let response = client.get("/compression/small").header("accept-encoding", "gzip").await;
+    assert_eq!(response.header("content-encoding"), None);
+    assert_eq!(response.json::<Value>(), json!({ "ok": true }));
+
+    let config = CompressionConfig::new().min_size(0);
+    let client = TestClient::from_server(Server::new().compression(config));
+    let response = client.get("/compression/small").header("accept-encoding", "gzip").await;
+    assert_eq!(response.header("content-encoding"), Some("gzip"));
+}
+
+#[tokio::test]
+async fn the_configured_order_breaks_ties() {
+    let client = TestClient::from_server(Server::new().compression(CompressionConfig::new()));
+    let encoding = |accept: &'static str| {
+        let client = client.clone();
+        async move {
+            let response = client.get("/compression/large").header("accept-encoding", accept).await;
+            response.header("content-encoding").map(str::to_string)
+        }
+    };
+    assert_eq!(encoding("gzip, zstd, br").await.as_deref(), Some("br"));
+    assert_eq!(encoding("gzip, br;q=0.5").await.as_deref(), Some("gzip"));
+    assert_eq!(encoding("*").await.as_deref(), Some("br"));
+
+    let config = CompressionConfig::new().algorithms([Encoding::Zstd, Encoding::Gzip]);
+    let client = TestClient::from_server(Server::new().compression(config));
+    let response = client.get("/compression/large").header("accept-encoding", "br, gzip, zstd").await;
+    assert_eq!(response.header("content-encoding"), Some("zstd"));
+    let response = client.get("/compression/large").header("accept-encoding", "br").await;
+    assert_eq!(response.header("content-encoding"), None);
+}
+
+#[tokio::test]
+async fn gzipped_request_bodies_are_decompressed() {
+    let client = TestClient::from_server(Server::new().compression(CompressionConfig::new()));
+    let response = client
+        .post("/compression/echo")
+        .header("content-type", "application/json")
+        .header("content-encoding", "gzip")
+        .body(gzip(br#"{"name":"alice"}"#))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "name": "alice" }));
+}
+
+#[tokio::test]
+async fn unsupported_request_encodings_are_rejected() {
+    let client = TestClient::from_server(Server::new().compression(CompressionConfig::new()));
+    let response = client
+        .post("/compression/echo")
+        .header("content-encoding", "compress")
+        .body("...")
+        .await;
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(response.json::<Value>()["success"], false);
+}
@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/slow", concurrency_limit = 1)]
+async fn slow() -> Value {
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    json!({ "done": true })
+}
+
+#[http_method(GET, "/sleep")]
+async fn sleep() -> Value {
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    json!({ "done": true })
+}
+
+#[http_method(GET, "/fast")]
+async fn fast() -> Value {
+    json!({ "done": true })
+}
+
+#[tokio::test]
+async fn a_route_over_its_limit_sheds_with_503() {
+    let client = TestClient::new();
+    let (first, second) = tokio::join!(async { client.get("/slow").await }, async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        client.get("/slow").await
+    });
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(second.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(second.header("retry-after"), Some("1"));
+    assert_eq!(second.json::<Value>()["success"], false);
+
+    // Other routes are unaffected, and the slot is free again afterwards
+    assert_eq!(client.get("/fast").await.status(), StatusCode::OK);
+    assert_eq!(client.get("/slow").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn the_server_sheds_requests_beyond_max_in_flight() {
+    let client = TestClient::from_server(Server::new().max_in_flight(1).enable_health_checks());
+    let (first, second, probe) = tokio::join!(
+        async { client.get("/sleep").await },
+        async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            client.get("/fast").await
+        },
+        async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            client.get("/healthz").await
+        }
+    );
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(second.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(second.header("retry-after"), Some("1"));
+    assert_eq!(probe.status(), StatusCode::OK);
+}
@@ -0,0 +1,262 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use ferrox::config::{Config, ConfigError, ConfigWatcher};
+use ferrox::logging::LogFormat;
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(POST, "/config/echo")]
+fn echo(body: Value) -> Value {
+    json!({ "len": body.as_str().map(str::len) })
+}
+
+#[http_method(GET, "/config/beta", flag = "config-beta")]
+fn beta() -> Value {
+    json!({ "beta": true })
+}
+
+// A config file with `contents` in a fresh directory
+fn config_file(name: &str, contents: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ferrox-config-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn reads_json_files() {
+    let path = config_file(
+        "server.json",
+        r#"{
+            "addr": "0.0.0.0:8080",
+            "timeouts": { "handler": "5s", "shutdown": "2m" },
+            "log": { "level": "debug", "format": "json", "access_log": true, "quiet": true },
+            "limits": { "max_body_size": "10MB", "rate_limit": "100/min" },
+            "cors": { "allow_origins": ["*"] }
+        }"#,
+    );
+    let config = Config::from_file(&path).unwrap();
+    assert_eq!(config.addr.as_deref(), Some("0.0.0.0:8080"));
+    assert_eq!(config.timeouts.handler, Some(Duration::from_secs(5)));
+    assert_eq!(config.timeouts.shutdown, Some(Duration::from_secs(120)));
+    assert_eq!(config.timeouts.body_read, None);
+    assert_eq!(config.log.level.as_deref(), Some("debug"));
+    assert_eq!(config.log.format, LogFormat::Json);
+    assert!(config.log.quiet);
+    assert_eq!(config.limits.max_body_size, Some(10 << 20));
+    assert_eq!(config.limits.rate_limit, Some("100/min".parse().unwrap()));
+    assert_eq!(config.cors.unwrap().allow_origins, ["*"]);
+    assert_eq!(config.compression, None);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn reads_toml_files() {
+    let path = config_file(
+        "server.toml",
+        r#"
+        addr = "127.0.0.1:9000"
+
+        [limits]
+        max_body_size = 4096
+
+        [compression]
+        algorithms = ["br", "gzip"]
+        "#,
+    );
+    let config = Config::from_file(&path).unwrap();
+    assert_eq!(config.addr.as_deref(), Some("127.0.0.1:9000"));
+    assert_eq!(config.limits.max_body_size, Some(4096));
+    assert!(config.compression.unwrap().algorithms.is_some());
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+fn reads_yaml_files() {
+    let path = config_file("server.yaml", "timeouts:\n  body_read: 500ms\ntls:\n  cert: cert.pem\n  key: key.pem\n");
+    let config = Config::from_file(&path).unwrap();
+    assert_eq!(config.timeouts.body_read, Some(Duration::from_millis(500)));
+    assert_eq!(config.tls.unwrap().key, PathBuf::from("key.pem"));
+}
+
+#[test]
+fn rejects_invalid_files() {
+    let unknown = config_file("unknown.json", r#"{ "adress": "0.0.0.0:80" }"#);
+    assert!(matches!(Config::from_file(unknown), Err(ConfigError::Parse(..))));
+
+    let duration = config_file("duration.json", r#"{ "timeouts": { "handler": "soon" } }"#);
+    let err = Config::from_file(duration).unwrap_err();
+    assert!(err.to_string().contains("expected a duration"), "{}", err);
+
+    let format = config_file("server.ini", "addr = 0.0.0.0:80");
+    assert!(matches!(Config::from_file(format), Err(ConfigError::UnsupportedFormat(_))));
+
+    assert!(matches!(Config::from_file("/nonexistent/ferrox.json"), Err(ConfigError::Read(..))));
+}
+
+#[test]
+fn environment_variables_override_the_file() {
+    let path = config_file("base.json", r#"{ "addr": "127.0.0.1:3000", "log": { "level": "info" } }"#);
+    let config = Config::from_file(&path)
+        .unwrap()
+        .with_vars([
+            ("FERROX_ADDR", "0.0.0.0:8080"),
+            ("FERROX_HANDLER_TIMEOUT", "250ms"),
+            ("FERROX_MAX_BODY_SIZE", "1KB"),
+            ("FERROX_MAX_IN_FLIGHT", "64"),
+            ("FERROX_CORS_ALLOW_ORIGINS", "https://a.example, https://b.example"),
+            ("FERROX_COMPRESSION", "true"),
+            ("FERROX_UNRELATED", "ignored"),
+            ("HOME", "/root"),
+        ])
+        .unwrap();
+    assert_eq!(config.addr.as_deref(), Some("0.0.0.0:8080"));
+    assert_eq!(config.log.level.as_deref(), Some("info"));
+    assert_eq!(config.timeouts.handler, Some(Duration::from_millis(250)));
+    assert_eq!(config.limits.max_body_size, Some(1024));
+    assert_eq!(config.limits.max_in_flight, Some(64));
+    assert_eq!(config.cors.unwrap().allow_origins, ["https://a.example", "https://b.example"]);
+    assert!(config.compression.is_some());
+}
+
+#[test]
+fn rejects_invalid_environment_variables() {
+    let err = Config::default().with_vars([("FERROX_RATE_LIMIT", "lots")]).unwrap_err();
+    assert!(matches!(&err, ConfigError::Env { var, .. } if var == "FERROX_RATE_LIMIT"));
+
+    let err = Config::default().with_vars([("FERROX_TLS_CERT", "cert.pem")]).unwrap_err();
+    assert!(err.to_string().contains("must be set together"), "{}", err);
+}
+
+#[tokio::test]
+async fn servers_use_the_loaded_settings() {
+    let config = Config::default()
+        .with_vars([
+            ("FERROX_MAX_BODY_SIZE", "100"),
+            ("FERROX_CORS_ALLOW_ORIGINS", "https://app.example.com"),
+        ])
+        .unwrap();
+    let client = TestClient::from_server(Server::from_config(config));
+
+    let response = client.post("/config/echo").body(format!("\"{}\"", "x".repeat(200))).await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let response = client
+        .post("/config/echo")
+        .header("origin", "https://app.example.com")
+        .body("\"hi\"")
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("access-control-allow-origin"), Some("https://app.example.com"));
+}
+
+#[tokio::test]
+async fn reloads_apply_the_reloadable_settings_and_report_the_rest() {
+    let path = config_file(
+        "reload.json",
+        r#"{
+            "limits": { "rate_limit": "50/min", "max_body_size": "1KB" },
+            "cors": { "allow_origins": ["https://a.example"] },
+            "flags": { "config-beta": false }
+        }"#,
+    );
+    let watcher = ConfigWatcher::load(&path).unwrap();
+    let client = TestClient::from_server(Server::from_config(watcher.config()).watch_config(watcher.clone()));
+
+    let response = client.get("/config/beta").header("origin", "https://b.example").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.header("x-ratelimit-limit"), Some("50"));
+    assert_eq!(response.header("access-control-allow-origin"), None);
+
+    std::fs::write(
+        &path,
+        r#"{
+            "addr": "0.0.0.0:8080",
+            "limits": { "rate_limit": "100/min", "max_body_size": "2KB" },
+            "cors": { "allow_origins": ["https://b.example"] },
+            "flags": { "config-beta": true }
+        }"#,
+    )
+    .unwrap();
+    let report = watcher.reload().unwrap();
+    assert_eq!(report.applied, ["limits.rate_limit", "cors.allow_origins", "flags"]);
+    assert_eq!(report.needs_restart, ["addr", "limits.max_body_size"]);
+
+    let response = client.get("/config/beta").header("origin", "https://b.example").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("x-ratelimit-limit"), Some("100"));
+    assert_eq!(response.header("access-control-allow-origin"), Some("https://b.example"));
+
+    // The settings left for a restart keep their old values
+    let config = watcher.config();
+    assert_eq!(config.addr, None);
+    assert_eq!(config.limits.max_body_size, Some(1024));
+    assert_eq!(config.limits.rate_limit, Some("100/min".parse().unwrap()));
+}
+
+#[tokio::test]
+async fn invalid_reloads_change_nothing() {
+    let path = config_file(
+        "invalid-reload.json",
+        r#"{ "cors": { "allow_origins": ["https://a.example"] }, "flags": { "config-beta": true } }"#,
+    );
+    let watcher = ConfigWatcher::load(&path).unwrap();
+    let client = TestClient::from_server(Server::from_config(watcher.config()).watch_config(watcher.clone()));
+
+    std::fs::write(
+        &path,
+        r#"{ "cors": { "allow_origins": ["https://bad\nexample"] }, "flags": { "config-beta": false } }"#,
+    )
+    .unwrap();
+    let err = watcher.reload().unwrap_err();
+    assert!(err.to_string().contains("cors.allow_origins"), "{}", err);
+
+    std::fs::write(&path, r#"{ "log": { "level": "info,ferrox=loud" }, "flags": { "config-beta": false } }"#).unwrap();
+    assert!(matches!(watcher.reload(), Err(ConfigError::Parse(..))));
+    std::fs::write(&path, "{").unwrap();
+    assert!(matches!(watcher.reload(), Err(ConfigError::Parse(..))));
+
+    assert_eq!(client.get("/config/beta").await.status(), StatusCode::OK);
+    assert!(watcher.config().flags.unwrap()["config-beta"]);
+}
+
+#[tokio::test]
+async fn running_servers_reload_changed_files() {
+    let path = config_file("watched.json", r#"{ "log": { "quiet": true }, "flags": { "config-beta": false } }"#);
+    let watcher = ConfigWatcher::load(&path).unwrap().interval(Duration::from_millis(20));
+    let server = Server::from_config(watcher.config()).watch_config(watcher.clone());
+    let handle = server.start_in_background("127.0.0.1:0").await.unwrap();
+
+    // Past the modification time the watcher last saw
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    std::fs::write(&path, r#"{ "log": { "quiet": true }, "flags": { "config-beta": true } }"#).unwrap();
+    let mut reloaded = false;
+    for _ in 0..100 {
+        if watcher.config().flags.unwrap()["config-beta"] {
+            reloaded = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(reloaded);
+    handle.shutdown().await;
+}
+
+#[test]
+fn credentialed_wildcards_are_rejected() {
+    let path = config_file(
+        "credentialed.json",
+        r#"{ "cors": { "allow_origins": ["*"], "allow_headers": ["*"], "allow_credentials": true } }"#,
+    );
+    let err = Config::from_file(&path).unwrap_err();
+    assert!(matches!(err, ConfigError::Parse(..)));
+    assert!(err.to_string().contains("cors.allow_origins, cors.allow_headers"), "{}", err);
+
+    let path = config_file("listed.json", r#"{ "cors": { "allow_origins": ["https://a.example"], "allow_credentials": true } }"#);
+    let config = Config::from_file(&path).unwrap();
+    let err = config.with_vars([("FERROX_CORS_ALLOW_ORIGINS", "*")]).unwrap_err();
+    assert!(matches!(err, ConfigError::Env { ref var, .. } if var == "FERROX_CORS_ALLOW_ORIGINS"));
+}
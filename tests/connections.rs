@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use ferrox::connection::ConnectionConfig;
+use ferrox::{http_method, Server};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+#[http_method(GET, "/connections/ping")]
+fn ping() -> Value {
+    json!({ "pong": true })
+}
+
+async fn start(config: ConnectionConfig) -> (ferrox::ServerHandle, std::net::SocketAddr) {
+    let handle = Server::new().quiet().connections(config).start_in_background("127.0.0.1:0").await.unwrap();
+    let addr = handle.local_addr().unwrap();
+    (handle, addr)
+}
+
+// Send a keep-alive GET and read the response head
+async fn ping_over(stream: &mut TcpStream) -> String {
+    stream
+        .write_all(b"GET /connections/ping HTTP/1.1\r\nhost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut buf = vec![0; 1024];
+    let read = stream.read(&mut buf).await.unwrap();
+    String::from_utf8_lossy(&buf[..read]).to_string()
+}
+
+#[tokio::test]
+async fn connections_beyond_the_limit_wait_for_a_slot() {
+    let (handle, addr) = start(ConnectionConfig::new().max_connections(1).tcp_nodelay(true)).await;
+
+    let mut first = TcpStream::connect(addr).await.unwrap();
+    assert!(ping_over(&mut first).await.starts_with("HTTP/1.1 200"));
+
+    let mut second = TcpStream::connect(addr).await.unwrap();
+    let waiting = tokio::time::timeout(Duration::from_millis(200), ping_over(&mut second)).await;
+    assert!(waiting.is_err());
+
+    drop(first);
+    let mut buf = vec![0; 1024];
+    let read = tokio::time::timeout(Duration::from_secs(2), second.read(&mut buf)).await.unwrap().unwrap();
+    assert!(String::from_utf8_lossy(&buf[..read]).starts_with("HTTP/1.1 200"));
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn slow_headers_are_cut_off() {
+    let config = ConnectionConfig::new()
+        .header_read_timeout(Duration::from_millis(100))
+        .tcp_keepalive(Duration::from_secs(30));
+    let (handle, addr) = start(config).await;
+
+    let mut slow = TcpStream::connect(addr).await.unwrap();
+    slow.write_all(b"GET /connections/ping HTTP/1.1\r\nhost: localhost\r\n").await.unwrap();
+    let mut rest = Vec::new();
+    let closed = tokio::time::timeout(Duration::from_secs(2), slow.read_to_end(&mut rest)).await;
+    assert!(closed.is_ok());
+
+    // Requests sent in time are served
+    let mut prompt = TcpStream::connect(addr).await.unwrap();
+    assert!(ping_over(&mut prompt).await.starts_with("HTTP/1.1 200"));
+    handle.shutdown().await;
+}
@@ -0,0 +1,103 @@
+use ferrox::test::TestClient;
+use ferrox::{http_method, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+struct Order {
+    item: String,
+    quantity: u32,
+}
+
+#[http_method(GET, "/orders/1")]
+fn get_order() -> Value {
+    json!({ "item": "tea", "quantity": 2, "tags": ["hot", "green"] })
+}
+
+#[http_method(POST, "/orders")]
+fn create_order(body: Order) -> Value {
+    json!({ "item": body.item, "quantity": body.quantity })
+}
+
+#[tokio::test]
+async fn json_is_the_default() {
+    let response = TestClient::new().get("/orders/1").header("accept", "text/html").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("content-type"), Some("application/json"));
+    assert_eq!(response.json::<Value>()["item"], "tea");
+}
+
+#[cfg(feature = "msgpack")]
+#[tokio::test]
+async fn msgpack_round_trip() {
+    let client = TestClient::new();
+    let response = client
+        .get("/orders/1")
+        .header("accept", "application/json;q=0.5, application/msgpack")
+        .await;
+    assert_eq!(response.header("content-type"), Some("application/msgpack"));
+    assert_eq!(response.header("vary"), Some("accept"));
+    let body: Value = rmp_serde::from_slice(response.bytes()).unwrap();
+    assert_eq!(body["tags"], json!(["hot", "green"]));
+
+    let request = rmp_serde::to_vec_named(&json!({ "item": "tea", "quantity": 3 })).unwrap();
+    let response = client
+        .post("/orders")
+        .header("content-type", "application/msgpack")
+        .body(request)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "item": "tea", "quantity": 3 }));
+}
+
+#[cfg(feature = "cbor")]
+#[tokio::test]
+async fn cbor_round_trip() {
+    let client = TestClient::new();
+    let response = client.get("/orders/1").header("accept", "application/cbor").await;
+    assert_eq!(response.header("content-type"), Some("application/cbor"));
+    let body: Value = ciborium::from_reader(&response.bytes()[..]).unwrap();
+    assert_eq!(body["quantity"], 2);
+
+    let mut request = Vec::new();
+    ciborium::into_writer(&json!({ "item": "tea", "quantity": 4 }), &mut request).unwrap();
+    let response = client
+        .post("/orders")
+        .header("content-type", "application/cbor")
+        .header("accept", "application/cbor")
+        .body(request)
+        .await;
+    let body: Value = ciborium::from_reader(&response.bytes()[..]).unwrap();
+    assert_eq!(body, json!({ "item": "tea", "quantity": 4 }));
+}
+
+#[cfg(feature = "xml")]
+#[tokio::test]
+async fn xml_round_trip() {
+    let client = TestClient::new();
+    let response = client.get("/orders/1").header("accept", "application/xml").await;
+    assert_eq!(response.header("content-type"), Some("application/xml"));
+    assert_eq!(
+        response.text(),
+        r#"<?xml version="1.0" encoding="UTF-8"?><response><item>tea</item><quantity>2</quantity><tags>hot</tags><tags>green</tags></response>"#
+    );
+
+    let response = client
+        .post("/orders")
+        .header("content-type", "application/xml")
+        .body("<order><item>tea &amp; cake</item><quantity>5</quantity></order>")
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "item": "tea & cake", "quantity": 5 }));
+}
+
+#[cfg(feature = "xml")]
+#[tokio::test]
+async fn malformed_bodies_are_rejected() {
+    let response = TestClient::new()
+        .post("/orders")
+        .header("content-type", "application/xml")
+        .body("<order><item>tea</order>")
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
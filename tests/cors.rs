@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use ferrox::auth::api_key::{ApiKeyAuth, InMemoryKeyStore};
+use ferrox::axum::http::{header, Method};
+use ferrox::cors::CorsConfig;
+use ferrox::ratelimit::{RateLimit, RateLimiter};
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET | POST, "/cors/items")]
+fn items() -> Value {
+    json!([])
+}
+
+#[http_method(GET, "/cors/private", auth = "api_key")]
+fn private() -> Value {
+    json!({ "secret": true })
+}
+
+#[http_method(GET, "/cors/public", auth = "api_key", cors = "any")]
+fn public() -> Value {
+    json!({ "public": true })
+}
+
+fn config() -> CorsConfig {
+    CorsConfig::new()
+        .allow_origin("https://app.example")
+        .allow_methods([Method::GET, Method::POST])
+        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION])
+        .allow_credentials(true)
+        .max_age(Duration::from_secs(600))
+}
+
+fn server() -> Server {
+    let keys = InMemoryKeyStore::new().with_key("app", "app-key");
+    Server::new().api_keys(ApiKeyAuth::new(keys)).cors(config())
+}
+
+#[tokio::test]
+async fn preflights_are_answered_from_the_config() {
+    let client = TestClient::from_server(server());
+    let response = client
+        .options("/cors/items")
+        .header("origin", "https://app.example")
+        .header("access-control-request-method", "POST")
+        .header("access-control-request-headers", "content-type")
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("access-control-allow-origin"), Some("https://app.example"));
+    assert_eq!(response.header("access-control-allow-methods"), Some("GET,POST"));
+    assert_eq!(response.header("access-control-allow-headers"), Some("content-type,authorization"));
+    assert_eq!(response.header("access-control-allow-credentials"), Some("true"));
+    assert_eq!(response.header("access-control-max-age"), Some("600"));
+
+    // Preflights never reach authentication
+    let response = client
+        .options("/cors/private")
+        .header("origin", "https://app.example")
+        .header("access-control-request-method", "GET")
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = client
+        .options("/cors/items")
+        .header("origin", "https://evil.example")
+        .header("access-control-request-method", "POST")
+        .await;
+    assert_eq!(response.header("access-control-allow-origin"), None);
+}
+
+#[tokio::test]
+async fn error_responses_carry_the_cors_headers() {
+    let client = TestClient::from_server(server().rate_limit(RateLimiter::new(RateLimit::per_minute(1))));
+
+    let response = client.get("/cors/private").header("origin", "https://app.example").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.header("access-control-allow-origin"), Some("https://app.example"));
+    assert_eq!(response.header("access-control-allow-credentials"), Some("true"));
+
+    let response = client.get("/cors/items").header("origin", "https://app.example").await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.header("access-control-allow-origin"), Some("https://app.example"));
+}
+
+#[tokio::test]
+async fn route_policies_apply_to_error_responses() {
+    let client = TestClient::from_server(server());
+    let response = client.get("/cors/public").header("origin", "https://elsewhere.example").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.header("access-control-allow-origin"), Some("*"));
+    assert_eq!(response.header("access-control-allow-credentials"), None);
+}
+
+#[test]
+#[should_panic(expected = "allow_any_origin() cannot be combined with allow_credentials(true)")]
+fn any_origin_with_credentials_is_refused() {
+    let _ = Server::new().cors(CorsConfig::new().allow_any_origin().allow_credentials(true));
+}
+
+#[test]
+#[should_panic(expected = "allow_any_method(), allow_any_header() cannot be combined")]
+fn any_method_or_header_with_credentials_is_refused() {
+    let config = CorsConfig::new()
+        .allow_origin("https://app.example")
+        .allow_any_method()
+        .allow_any_header()
+        .allow_credentials(true);
+    let _ = Server::new().cors(config);
+}
+
+#[tokio::test]
+async fn credentialed_wildcards_must_be_asked_for() {
+    let config = CorsConfig::new().mirror_any_origin_with_credentials().allow_any_method().allow_any_header();
+    let client = TestClient::from_server(Server::new().cors(config));
+
+    let response = client
+        .options("/cors/items")
+        .header("origin", "https://anywhere.example")
+        .header("access-control-request-method", "POST")
+        .header("access-control-request-headers", "x-custom")
+        .await;
+    assert_eq!(response.header("access-control-allow-origin"), Some("https://anywhere.example"));
+    assert_eq!(response.header("access-control-allow-methods"), Some("POST"));
+    assert_eq!(response.header("access-control-allow-headers"), Some("x-custom"));
+    assert_eq!(response.header("access-control-allow-credentials"), Some("true"));
+}
@@ -0,0 +1,111 @@
+use std::sync::{Arc, Mutex};
+
+use ferrox::openapi::{spec, OpenApiConfig};
+use ferrox::sse::{SseEvent, SseStream};
+use ferrox::test::TestClient;
+use ferrox::{http_method, sse, FerroxError, FromFerroxRequest, ParamSource, RequestContext, Server, StatusCode};
+use serde_json::{json, Value};
+
+// The user named by a header, looked up in the application's directory
+struct CurrentUser {
+    name: String,
+    admin: bool,
+}
+
+#[derive(Clone, Default)]
+struct Directory(Arc<Mutex<Vec<(String, bool)>>>);
+
+impl FromFerroxRequest for CurrentUser {
+    async fn from_request(ctx: &RequestContext) -> Result<Self, FerroxError> {
+        let name = ctx
+            .headers
+            .get("x-user")
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| FerroxError::Unauthorized("Sign in first".to_string()))?;
+        let directory = ctx.state::<Directory>().unwrap_or_default();
+        tokio::task::yield_now().await;
+        let users = directory.0.lock().unwrap();
+        let (name, admin) = users
+            .iter()
+            .find(|(known, _)| known == name)
+            .cloned()
+            .ok_or_else(|| FerroxError::Forbidden(format!("Unknown user {}", name)))?;
+        Ok(CurrentUser { name, admin })
+    }
+}
+
+// Whether the request asked for a dry run
+struct DryRun(bool);
+
+impl FromFerroxRequest for DryRun {
+    async fn from_request(ctx: &RequestContext) -> Result<Self, FerroxError> {
+        Ok(DryRun(ctx.query.get("dry_run").is_some_and(|value| value == "true")))
+    }
+}
+
+#[http_method(POST, "/extracted/projects/:id")]
+async fn rename_project(id: u64, user: CurrentUser, dry_run: DryRun, body: Value) -> Value {
+    json!({ "id": id, "by": user.name, "admin": user.admin, "dry_run": dry_run.0, "name": body["name"] })
+}
+
+#[sse("/extracted/feed")]
+async fn feed(user: CurrentUser) -> SseStream {
+    SseStream::new(futures_util::stream::iter([SseEvent::data(format!("hello {}", user.name))]))
+}
+
+fn client() -> TestClient {
+    let directory = Directory::default();
+    directory.0.lock().unwrap().push(("ada".to_string(), true));
+    TestClient::from_server(Server::new().with_state(directory))
+}
+
+#[tokio::test]
+async fn handlers_take_the_applications_own_extractors() {
+    let client = client();
+    let response = client
+        .post("/extracted/projects/3?dry_run=true")
+        .header("x-user", "ada")
+        .json(&json!({ "name": "ferrox" }))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.json::<Value>(),
+        json!({ "id": 3, "by": "ada", "admin": true, "dry_run": true, "name": "ferrox" })
+    );
+
+    let response = client.get("/extracted/feed").header("x-user", "ada").await;
+    assert!(response.text().contains("data: hello ada"));
+}
+
+#[tokio::test]
+async fn extractor_errors_are_the_response() {
+    let client = client();
+    let response = client.post("/extracted/projects/3").json(&json!({})).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.json::<Value>()["message"], "Sign in first");
+
+    let response = client.post("/extracted/projects/3").header("x-user", "eve").json(&json!({})).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(client.get("/extracted/feed").await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn extractors_are_listed_but_not_documented_as_inputs() {
+    let route = ferrox::routes()
+        .into_iter()
+        .find(|route| route.path == "/extracted/projects/:id")
+        .unwrap();
+    let sources: Vec<_> = route.params.iter().map(|param| (param.name, param.source)).collect();
+    assert_eq!(
+        sources,
+        [
+            ("id", ParamSource::PathParam),
+            ("user", ParamSource::Extractor),
+            ("dry_run", ParamSource::Extractor),
+            ("body", ParamSource::Body),
+        ]
+    );
+    let document = spec(&OpenApiConfig::new("Extractors", "1.0"));
+    let parameters = document["paths"]["/extracted/projects/{id}"]["post"]["parameters"].as_array().unwrap();
+    assert_eq!(parameters.len(), 1);
+}
@@ -0,0 +1,96 @@
+#![cfg(feature = "sqlx")]
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use ferrox::database::PoolConfig;
+use ferrox::listener::Listener;
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, Server, State, StatusCode};
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+static SERVED_POOL: OnceLock<SqlitePool> = OnceLock::new();
+
+#[http_method(GET, "/database/answer")]
+async fn answer(pool: State<SqlitePool>) -> Result<Value, FerroxError> {
+    let answer: i64 = sqlx::query_scalar("SELECT 40 + 2").fetch_one(&*pool).await?;
+    Ok(json!({ "answer": answer }))
+}
+
+#[http_method(GET, "/database/missing")]
+async fn missing(pool: State<SqlitePool>) -> Result<Value, FerroxError> {
+    let name: String = sqlx::query_scalar("SELECT 'x' WHERE 1 = 0").fetch_one(&*pool).await?;
+    Ok(json!({ "name": name }))
+}
+
+#[http_method(GET, "/database/remember")]
+fn remember(pool: State<SqlitePool>) -> Value {
+    let _ = SERVED_POOL.set(pool.0.clone());
+    json!({ "closed": pool.is_closed() })
+}
+
+fn memory() -> PoolConfig {
+    PoolConfig::new().max_connections(1).acquire_timeout(Duration::from_secs(1))
+}
+
+#[tokio::test]
+async fn handlers_share_the_pool() {
+    let client = TestClient::from_server(Server::new().with_database::<SqlitePool>("sqlite::memory:", memory()));
+    let response = client.get("/database/answer").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "answer": 42 }));
+    assert_eq!(client.get("/database/missing").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn the_database_is_a_readiness_check() {
+    let server = Server::new()
+        .enable_health_checks()
+        .with_database::<SqlitePool>("sqlite::memory:", memory());
+    let client = TestClient::from_server(server);
+    let response = client.get("/readyz").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>()["checks"]["database"]["status"], "ok");
+
+    let unreachable = "sqlite:///ferrox-missing-directory/app.db";
+    let server = Server::new()
+        .with_database::<SqlitePool>(unreachable, memory())
+        .enable_health_checks();
+    let client = TestClient::from_server(server);
+    let response = client.get("/readyz").await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.json::<Value>()["checks"]["database"]["status"], "fail");
+    assert_eq!(client.get("/livez").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn the_pool_is_closed_on_shutdown() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = Server::new()
+        .with_database::<SqlitePool>("sqlite::memory:", memory())
+        .bind(Listener::from_tcp(listener))
+        .run_in_background()
+        .await
+        .unwrap();
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let request = "GET /database/remember HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"));
+
+    let pool = SERVED_POOL.get().unwrap();
+    assert!(!pool.is_closed());
+    handle.graceful_shutdown(Duration::from_secs(1)).await.unwrap();
+    assert!(pool.is_closed());
+}
+
+#[tokio::test]
+#[should_panic(expected = "invalid database URL")]
+async fn invalid_urls_are_refused() {
+    let _ = Server::new().with_database::<SqlitePool>("sqlite::memory:?mode=sideways", PoolConfig::new());
+}
@@ -0,0 +1,53 @@
+use ferrox::openapi::{spec, OpenApiConfig};
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/deprecation/v1/users", deprecated = true, sunset = "2026-06-01", successor = "/v2/users")]
+fn list_users_v1() -> Value {
+    json!([])
+}
+
+#[http_method(GET, "/deprecation/v1/users/:id", deprecated = true)]
+fn get_user_v1(id: u64) -> Result<Value, FerroxError> {
+    Err(FerroxError::NotFound(format!("User {} not found", id)))
+}
+
+#[http_method(GET, "/deprecation/v2/users")]
+fn list_users_v2() -> Value {
+    json!([])
+}
+
+#[tokio::test]
+async fn deprecated_routes_send_deprecation_headers() {
+    let client = TestClient::new();
+    let response = client.get("/deprecation/v1/users").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("deprecation"), Some("true"));
+    assert_eq!(response.header("sunset"), Some("Mon, 01 Jun 2026 00:00:00 GMT"));
+    assert_eq!(response.header("link"), Some("</v2/users>; rel=\"successor-version\""));
+
+    // Errors too, with only the headers the route asks for
+    let response = client.get("/deprecation/v1/users/7").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.header("deprecation"), Some("true"));
+    assert_eq!(response.header("sunset"), None);
+    assert_eq!(response.header("link"), None);
+
+    let response = client.get("/deprecation/v2/users").await;
+    assert_eq!(response.header("deprecation"), None);
+}
+
+#[test]
+fn deprecated_operations_are_marked_in_openapi() {
+    let document = spec(&OpenApiConfig::new("Deprecation", "1.0"));
+    let operation = &document["paths"]["/deprecation/v1/users"]["get"];
+    assert_eq!(operation["deprecated"], json!(true));
+    assert_eq!(operation["x-sunset"], "2026-06-01");
+    assert_eq!(operation["x-successor"], "/v2/users");
+
+    assert_eq!(document["paths"]["/deprecation/v1/users/{id}"]["get"]["deprecated"], json!(true));
+    let current = &document["paths"]["/deprecation/v2/users"]["get"];
+    assert_eq!(current.get("deprecated"), None);
+    assert_eq!(current.get("x-sunset"), None);
+}
@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use ferrox::dynamic::RouteError;
+use ferrox::test::TestClient;
+use ferrox::{http_method, RequestContext, RouteHandler, Server, StatusCode};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+#[http_method(GET, "/dynamic/static/:id")]
+fn registered(id: u64) -> Value {
+    json!({ "registered": id })
+}
+
+fn echo(name: &'static str) -> RouteHandler {
+    RouteHandler::from_sync(move |ctx: RequestContext| json!({ "hook": name, "path": ctx.path, "body": ctx.body }))
+}
+
+#[tokio::test]
+async fn routes_can_be_added_and_removed_while_serving() {
+    let server = Server::new();
+    let routes = server.dynamic_routes();
+    routes.add_route("GET", "/dynamic/early", echo("early")).unwrap();
+    let client = TestClient::from_server(server);
+    assert_eq!(client.get("/dynamic/early").await.json::<Value>()["hook"], "early");
+
+    assert_eq!(client.post("/dynamic/hooks/github").await.status(), StatusCode::NOT_FOUND);
+    routes.add_route("post", "/dynamic/hooks/:name", echo("late")).unwrap();
+    let response = client.post("/dynamic/hooks/github").json(&json!({ "ok": true })).await;
+    assert_eq!(
+        response.json::<Value>(),
+        json!({ "hook": "late", "path": { "name": "github" }, "body": { "ok": true } })
+    );
+    let response = client.get("/dynamic/hooks/github").await;
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.header("allow"), Some("POST, OPTIONS"));
+
+    assert!(routes.remove_route("POST", "/dynamic/hooks/:name"));
+    assert!(!routes.remove_route("POST", "/dynamic/hooks/:name"));
+    assert_eq!(client.post("/dynamic/hooks/github").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(routes.routes(), vec![("GET".to_string(), "/dynamic/early".to_string())]);
+}
+
+#[tokio::test]
+async fn registered_paths_are_refused_and_keep_precedence() {
+    let server = Server::new();
+    let routes = server.dynamic_routes();
+    let client = TestClient::from_server(server);
+
+    let conflict = routes.add_route("GET", "/dynamic/static/:key", echo("shadow"));
+    assert!(matches!(conflict, Err(RouteError::Conflict(_))));
+    assert!(matches!(routes.add_route("TRACE", "/dynamic/x", echo("x")), Err(RouteError::UnsupportedMethod(_))));
+    assert!(matches!(routes.add_route("GET", "dynamic/x", echo("x")), Err(RouteError::InvalidPath(_))));
+
+    // A more specific path still routes to the dynamic handler
+    routes.add_route("GET", "/dynamic/static/latest", echo("latest")).unwrap();
+    assert_eq!(client.get("/dynamic/static/latest").await.json::<Value>()["hook"], "latest");
+    assert_eq!(client.get("/dynamic/static/7").await.json::<Value>(), json!({ "registered": 7 }));
+}
+
+#[tokio::test]
+async fn in_flight_requests_finish_after_their_route_is_removed() {
+    let server = Server::new();
+    let routes = server.dynamic_routes();
+    let slow = RouteHandler::from_async(|_ctx: RequestContext| async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        json!({ "done": true })
+    });
+    routes.add_route("GET", "/dynamic/slow", slow).unwrap();
+    let client = TestClient::from_server(server);
+
+    let (response, ()) = tokio::join!(async { client.get("/dynamic/slow").await }, async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(routes.remove_route("GET", "/dynamic/slow"));
+    });
+    assert_eq!(response.json::<Value>(), json!({ "done": true }));
+    assert_eq!(client.get("/dynamic/slow").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn the_server_handle_adds_routes_after_startup() {
+    let handle = Server::new().start_in_background("127.0.0.1:0").await.unwrap();
+    handle.add_route("GET", "/dynamic/live", echo("live")).unwrap();
+    let mut stream = tokio::net::TcpStream::connect(handle.local_addr().unwrap()).await.unwrap();
+    let request = "GET /dynamic/live HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains(r#""hook":"live""#));
+    handle.shutdown().await;
+}
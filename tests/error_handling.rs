@@ -0,0 +1,117 @@
+use std::sync::{Arc, Mutex};
+
+use ferrox::axum::response::IntoResponse;
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/errors/panic")]
+fn panics() -> Value {
+    panic!("boom")
+}
+
+#[http_method(GET, "/errors/panic_async")]
+async fn panics_async() -> Value {
+    panic!("async boom")
+}
+
+#[http_method(GET, "/errors/users/:id")]
+fn user(id: u32) -> Result<Value, FerroxError> {
+    Err(FerroxError::NotFound(format!("User {} not found", id)))
+}
+
+#[tokio::test]
+async fn panics_become_500_envelopes() {
+    let client = TestClient::new();
+    for path in ["/errors/panic", "/errors/panic_async"] {
+        let response = client.get(path).await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR, "{}", path);
+        assert_eq!(
+            response.json::<Value>(),
+            json!({ "success": false, "data": null, "message": "Internal server error" })
+        );
+    }
+    // The client is still usable afterwards
+    assert_eq!(client.get("/errors/users/1").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn on_error_sees_handler_and_framework_errors() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = seen.clone();
+    let server = Server::new().on_error(move |error, request| {
+        log.lock().unwrap().push((
+            request.method.to_string(),
+            request.path.clone(),
+            request.route.clone(),
+            error.status().as_u16(),
+        ));
+        error
+    });
+    let client = TestClient::from_server(server);
+    client.get("/errors/users/7").await;
+    client.get("/errors/nowhere").await;
+    client.get("/errors/panic").await;
+
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            ("GET".to_string(), "/errors/users/7".to_string(), Some("/errors/users/:id".to_string()), 404),
+            ("GET".to_string(), "/errors/nowhere".to_string(), None, 404),
+            ("GET".to_string(), "/errors/panic".to_string(), Some("/errors/panic".to_string()), 500),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn on_error_can_replace_errors() {
+    let server = Server::new().on_error(|error, _| match error.status() {
+        StatusCode::NOT_FOUND => FerroxError::Status(StatusCode::GONE, "Gone for good".to_string()),
+        _ => error,
+    });
+    let client = TestClient::from_server(server);
+    for path in ["/errors/users/7", "/errors/nowhere"] {
+        let response = client.get(path).await;
+        assert_eq!(response.status(), StatusCode::GONE, "{}", path);
+        assert_eq!(response.json::<Value>()["message"], "Gone for good");
+    }
+}
+
+#[tokio::test]
+async fn not_found_handlers_answer_unmatched_routes() {
+    let server = Server::new().not_found(|uri: ferrox::axum::http::Uri| async move {
+        (StatusCode::NOT_FOUND, ferrox::axum::Json(json!({ "error": "no_route", "path": uri.path() })))
+    });
+    let client = TestClient::from_server(server);
+    let response = client.get("/errors/missing").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.json::<Value>(), json!({ "error": "no_route", "path": "/errors/missing" }));
+    // Matched routes keep their own errors
+    let response = client.get("/errors/users/3").await;
+    assert_eq!(response.json::<Value>()["message"], "User 3 not found");
+}
+
+#[tokio::test]
+async fn error_mappers_replace_the_envelope() {
+    let server = Server::new().error_mapper(|error, request| {
+        request.path.starts_with("/errors/users").then(|| {
+            let page = format!("<h1>{}</h1>", error.message());
+            (error.status(), ferrox::axum::response::Html(page)).into_response()
+        })
+    });
+    let client = TestClient::from_server(server);
+
+    let response = client.get("/errors/users/5").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.header("content-type"), Some("text/html; charset=utf-8"));
+    assert_eq!(response.text(), "<h1>User 5 not found</h1>");
+    let response = client.get("/errors/users").await;
+    assert_eq!(response.text(), "<h1>Route /errors/users not found</h1>");
+
+    // Errors the mapper declines keep the envelope, framework errors included
+    let response = client.get("/errors/missing").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.json::<Value>()["success"], false);
+    let response = client.get("/errors/panic").await;
+    assert_eq!(response.json::<Value>()["message"], "Internal server error");
+}
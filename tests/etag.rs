@@ -0,0 +1,71 @@
+use std::time::{Duration, SystemTime};
+
+use ferrox::test::TestClient;
+use ferrox::{etag, http_method, HandlerResponse, RequestContext, Server, StatusCode};
+use serde_json::{json, Value};
+
+// Mon, 05 Oct 2026 10:00:00 GMT
+fn published() -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(1_791_194_400)
+}
+
+#[http_method(GET, "/articles")]
+fn articles() -> Value {
+    json!({ "articles": ["etags", "caching"] })
+}
+
+#[http_method(POST, "/articles")]
+fn create_article() -> Value {
+    json!({ "created": true })
+}
+
+#[http_method(GET, "/feed")]
+fn feed(ctx: &RequestContext) -> HandlerResponse {
+    if etag::not_modified_since(ctx, published()) {
+        return etag::not_modified(published());
+    }
+    HandlerResponse::new(StatusCode::OK, json!({ "items": 3 })).with_last_modified(published())
+}
+
+#[tokio::test]
+async fn json_responses_get_a_strong_etag() {
+    let client = TestClient::from_server(Server::new().etags(true));
+    let response = client.get("/articles").await;
+    let etag = response.header("etag").unwrap().to_string();
+    assert!(etag.starts_with('"') && etag.len() == 34, "{}", etag);
+    // Same body, same tag
+    assert_eq!(client.get("/articles").await.header("etag"), Some(etag.as_str()));
+
+    let response = client.get("/articles").header("if-none-match", &format!("\"other\", {}", etag)).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.header("etag"), Some(etag.as_str()));
+    assert!(response.text().is_empty());
+
+    let response = client.get("/articles").header("if-none-match", "\"stale\"").await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = client.post("/articles").header("if-none-match", "*").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("etag"), None);
+}
+
+#[tokio::test]
+async fn etags_are_off_by_default() {
+    let response = TestClient::new().get("/articles").await;
+    assert_eq!(response.header("etag"), None);
+}
+
+#[tokio::test]
+async fn last_modified_answers_if_modified_since() {
+    let client = TestClient::from_server(Server::new().etags(true));
+    let response = client.get("/feed").await;
+    assert_eq!(response.header("last-modified"), Some("Mon, 05 Oct 2026 10:00:00 GMT"));
+
+    let response = client.get("/feed").header("if-modified-since", "Mon, 05 Oct 2026 10:00:00 GMT").await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert!(response.text().is_empty());
+
+    let response = client.get("/feed").header("if-modified-since", "Sun, 04 Oct 2026 10:00:00 GMT").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "items": 3 }));
+}
@@ -0,0 +1,131 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ferrox::events::{Bridge, EventBus};
+use ferrox::test::TestClient;
+use ferrox::{http_method, subscribe, Server, State, StatusCode};
+use futures_util::future::BoxFuture;
+use futures_util::stream::{self, BoxStream};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+
+#[derive(Clone, Debug, PartialEq)]
+struct UserCreated {
+    email: String,
+}
+
+// What the subscribers saw
+#[derive(Clone, Default)]
+struct Seen(Arc<Mutex<Vec<String>>>);
+
+impl Seen {
+    fn sorted(&self) -> Vec<String> {
+        let mut seen = self.0.lock().unwrap().clone();
+        seen.sort();
+        seen
+    }
+}
+
+#[subscribe]
+async fn welcome(user: UserCreated, seen: State<Seen>) {
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    seen.0 .0.lock().unwrap().push(format!("welcome {}", user.email));
+}
+
+#[subscribe]
+fn audit(seen: State<Seen>, user: UserCreated) -> Result<(), String> {
+    seen.0 .0.lock().unwrap().push(format!("audit {}", user.email));
+    Err("the audit log is full".to_string())
+}
+
+#[http_method(POST, "/events/users")]
+fn create_user(bus: State<EventBus>) -> Value {
+    let subscribers = bus.publish(UserCreated { email: "ada@example.com".to_string() });
+    json!({ "subscribers": subscribers })
+}
+
+#[tokio::test]
+async fn published_events_reach_every_subscriber_after_the_response() {
+    let (bus, seen) = (EventBus::new(), Seen::default());
+    let client = TestClient::from_server(Server::new().event_bus(bus.clone()).with_state(seen.clone()));
+    let response = client.post("/events/users").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    // One subscriber failing does not keep the event from the others
+    assert_eq!(response.json::<Value>(), json!({ "subscribers": 2 }));
+
+    assert!(bus.flush(Duration::from_secs(1)).await);
+    assert_eq!(seen.sorted(), ["audit ada@example.com", "welcome ada@example.com"]);
+}
+
+#[tokio::test]
+async fn closures_subscribe_to_one_event_type() {
+    let (bus, seen) = (EventBus::new(), Seen::default());
+    let orders = seen.clone();
+    bus.subscribe("orders", move |order: OrderPlaced| {
+        let orders = orders.clone();
+        async move { orders.0.lock().unwrap().push(format!("order {}", order.id)) }
+    });
+    assert_eq!(bus.publish(OrderPlaced { id: 7 }), 1);
+    // Nothing subscribes to `UserCreated` on a bus no server has bound
+    assert_eq!(bus.publish(UserCreated { email: "ada@example.com".to_string() }), 0);
+    assert!(bus.flush(Duration::from_secs(1)).await);
+    assert_eq!(seen.sorted(), ["order 7"]);
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct OrderPlaced {
+    id: u64,
+}
+
+// A broker in memory: every topic's payloads go to every subscriber, the sender included
+#[derive(Clone)]
+struct MemoryBroker(broadcast::Sender<(String, Vec<u8>)>);
+
+impl Bridge for MemoryBroker {
+    fn publish(&self, topic: &str, payload: Vec<u8>) -> BoxFuture<'static, Result<(), String>> {
+        let sent = self.0.send((topic.to_string(), payload)).map(drop).map_err(|err| err.to_string());
+        Box::pin(async move { sent })
+    }
+
+    fn subscribe(&self, topic: &str) -> BoxFuture<'static, Result<BoxStream<'static, Vec<u8>>, String>> {
+        let (receiver, topic) = (self.0.subscribe(), topic.to_string());
+        let payloads = stream::unfold(receiver, move |mut receiver| {
+            let topic = topic.clone();
+            async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok((sent_to, payload)) if sent_to == topic => return Some((payload, receiver)),
+                        Ok(_) => {}
+                        Err(_) => return None,
+                    }
+                }
+            }
+        })
+        .boxed();
+        Box::pin(async move { Ok(payloads) })
+    }
+}
+
+#[tokio::test]
+async fn bridged_events_reach_the_other_instances_once() {
+    let broker = MemoryBroker(broadcast::channel(16).0);
+    let seen = Seen::default();
+    let instances = ["a", "b"].map(|instance| {
+        let bus = EventBus::new();
+        bus.bridge::<OrderPlaced>("orders", broker.clone());
+        let seen = seen.clone();
+        bus.subscribe("orders", move |order: OrderPlaced| {
+            let seen = seen.clone();
+            async move { seen.0.lock().unwrap().push(format!("{} got order {}", instance, order.id)) }
+        });
+        bus
+    });
+    // Let both consumers subscribe before publishing
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    instances[0].publish(OrderPlaced { id: 1 });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(seen.sorted(), ["a got order 1", "b got order 1"]);
+}
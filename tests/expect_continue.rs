@@ -0,0 +1,86 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use ferrox::auth::api_key::{ApiKeyAuth, InMemoryKeyStore};
+use ferrox::body_log::BodyLog;
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, ServerHandle, StatusCode};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+#[http_method(POST, "/expect/uploads", max_body_size = "1KB", auth = "api_key")]
+fn upload(body: Value) -> Value {
+    json!({ "size": body["data"].as_str().map(str::len) })
+}
+
+// Bodies are logged, which must not read them early either
+fn server() -> Server {
+    Server::new()
+        .quiet()
+        .api_keys(ApiKeyAuth::new(InMemoryKeyStore::new().with_key("uploader", "upload-key")))
+        .body_logging(BodyLog::new().enabled(true))
+}
+
+async fn start() -> (ServerHandle, SocketAddr) {
+    let handle = server().start_in_background("127.0.0.1:0").await.unwrap();
+    let addr = handle.local_addr().unwrap();
+    (handle, addr)
+}
+
+// Send the head of an upload of `length` bytes that waits for 100 Continue,
+// and read what the server answers before any of the body is sent
+async fn announce(addr: SocketAddr, length: usize, key: Option<&str>) -> (TcpStream, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let key = key.map(|key| format!("X-Api-Key: {}\r\n", key)).unwrap_or_default();
+    let head = format!(
+        "POST /expect/uploads HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nExpect: 100-continue\r\n\r\n",
+        key, length
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    let mut buf = vec![0; 4096];
+    let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf)).await.unwrap().unwrap();
+    let answer = String::from_utf8_lossy(&buf[..read]).to_string();
+    (stream, answer)
+}
+
+#[tokio::test]
+async fn oversized_uploads_are_refused_before_the_body_is_sent() {
+    let (handle, addr) = start().await;
+    let (_, answer) = announce(addr, 10 * 1024 * 1024, Some("upload-key")).await;
+    assert!(answer.starts_with("HTTP/1.1 413"), "{}", answer);
+    assert!(!answer.contains("100 Continue"));
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn unauthenticated_uploads_are_refused_before_the_body_is_sent() {
+    let (handle, addr) = start().await;
+    let (_, answer) = announce(addr, 512, None).await;
+    assert!(answer.starts_with("HTTP/1.1 401"), "{}", answer);
+    assert!(!answer.contains("100 Continue"));
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn accepted_uploads_get_100_continue_then_the_response() {
+    let (handle, addr) = start().await;
+    let body = json!({ "data": "x".repeat(100) }).to_string();
+    let (mut stream, answer) = announce(addr, body.len(), Some("upload-key")).await;
+    assert!(answer.starts_with("HTTP/1.1 100 Continue"), "{}", answer);
+
+    stream.write_all(body.as_bytes()).await.unwrap();
+    let mut buf = vec![0; 4096];
+    let read = stream.read(&mut buf).await.unwrap();
+    let response = String::from_utf8_lossy(&buf[..read]).to_string();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.ends_with(r#"{"size":100}"#), "{}", response);
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn other_expectations_are_refused() {
+    let client = TestClient::from_server(server());
+    let response = client.post("/expect/uploads").header("expect", "200-ok").json(&json!({})).await;
+    assert_eq!(response.status(), StatusCode::EXPECTATION_FAILED);
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use ferrox::flags::{EnvFlags, Flags, RemoteFlags, StaticFlags};
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, Server, State, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/flags/cart")]
+fn cart(flags: State<Flags>) -> Value {
+    json!({ "checkout": if flags.is_enabled("new-checkout") { "v2" } else { "v1" } })
+}
+
+#[http_method(POST, "/flags/checkout", flag = "new-checkout")]
+fn checkout() -> Value {
+    json!({ "paid": true })
+}
+
+#[http_method(GET, "/flags/beta", flag = "beta")]
+fn beta() -> Value {
+    json!({ "beta": true })
+}
+
+#[tokio::test]
+async fn handlers_look_flags_up() {
+    let client = TestClient::from_server(Server::new().flags(Flags::new().provider(StaticFlags::new().set("new-checkout", true))));
+    assert_eq!(client.get("/flags/cart").await.json::<Value>(), json!({ "checkout": "v2" }));
+
+    let client = TestClient::from_server(Server::new().flags(Flags::new()));
+    assert_eq!(client.get("/flags/cart").await.json::<Value>(), json!({ "checkout": "v1" }));
+}
+
+#[tokio::test]
+async fn routes_behind_an_off_flag_answer_404() {
+    let flags: StaticFlags = [("new-checkout", false), ("beta", true)].into_iter().collect();
+    let client = TestClient::from_server(Server::new().flags(Flags::new().provider(flags)));
+    assert_eq!(client.post("/flags/checkout").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(client.get("/flags/beta").await.json::<Value>(), json!({ "beta": true }));
+
+    // Without any flags, gated routes are off
+    let client = TestClient::new();
+    assert_eq!(client.get("/flags/beta").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn the_first_provider_knowing_a_flag_decides() {
+    // SAFETY: no other test reads or writes these variables
+    unsafe {
+        std::env::set_var("FLAGS_TEST_NEW_CHECKOUT", "off");
+        std::env::set_var("FLAGS_TEST_BETA", "maybe");
+    }
+    let flags = Flags::new()
+        .provider(EnvFlags::prefix("FLAGS_TEST_"))
+        .provider(StaticFlags::new().set("new-checkout", true).set("beta", true));
+    assert!(!flags.is_enabled("new-checkout"));
+    // Invalid values are skipped
+    assert!(flags.is_enabled("beta"));
+    assert!(!flags.is_enabled("unknown"));
+}
+
+#[tokio::test]
+async fn remote_flags_keep_their_last_values_while_fetches_fail() {
+    let failing = Arc::new(AtomicBool::new(false));
+    let fetch = {
+        let failing = failing.clone();
+        move || {
+            let failing = failing.load(Ordering::SeqCst);
+            async move {
+                match failing {
+                    true => Err(FerroxError::Internal("flag service unavailable".to_string())),
+                    false => Ok(HashMap::from([("new-checkout".to_string(), true)])),
+                }
+            }
+        }
+    };
+    let remote = RemoteFlags::new(Duration::from_millis(10), fetch);
+    let flags = Flags::new().provider(remote.clone());
+    assert!(!flags.is_enabled("new-checkout"));
+    remote.refresh().await.unwrap();
+    assert!(flags.is_enabled("new-checkout"));
+
+    failing.store(true, Ordering::SeqCst);
+    assert!(remote.refresh().await.is_err());
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    assert!(flags.is_enabled("new-checkout"));
+}
@@ -0,0 +1,82 @@
+#![cfg(feature = "graphql")]
+
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Schema};
+use ferrox::auth::api_key::{ApiKey, ApiKeyAuth, InMemoryKeyStore};
+use ferrox::graphql::GraphQL;
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[derive(Clone)]
+struct Greeting(&'static str);
+
+struct Query;
+
+#[Object]
+impl Query {
+    async fn hello(&self, ctx: &Context<'_>, name: String) -> async_graphql::Result<String> {
+        let greeting: Greeting = ferrox::graphql::state(ctx)?;
+        Ok(format!("{}, {}!", greeting.0, name))
+    }
+
+    async fn me(&self, ctx: &Context<'_>) -> async_graphql::Result<String> {
+        Ok(ferrox::graphql::identity::<ApiKey>(ctx)?.name)
+    }
+}
+
+#[http_method(GET, "/rest")]
+fn rest() -> Value {
+    json!({ "rest": true })
+}
+
+fn server(endpoint: GraphQL) -> Server {
+    Server::new()
+        .with_state(Greeting("Hello"))
+        .api_keys(ApiKeyAuth::new(InMemoryKeyStore::new().with_key("ops", "secret")))
+        .graphql(endpoint)
+}
+
+fn schema() -> Schema<Query, EmptyMutation, EmptySubscription> {
+    Schema::new(Query, EmptyMutation, EmptySubscription)
+}
+
+#[tokio::test]
+async fn queries_are_served_next_to_rest_routes() {
+    let client = TestClient::from_server(server(GraphQL::new(schema())));
+    let response = client.post("/graphql").json(&json!({ "query": "{ hello(name: \"Ada\") }" })).await;
+    assert_eq!(response.json::<Value>(), json!({ "data": { "hello": "Hello, Ada!" } }));
+
+    let response = client.get("/graphql?query=%7B%20hello(name%3A%20%22Bob%22)%20%7D").await;
+    assert_eq!(response.json::<Value>(), json!({ "data": { "hello": "Hello, Bob!" } }));
+
+    let response = client
+        .post("/graphql")
+        .json(&json!([{ "query": "{ a: hello(name: \"A\") }" }, { "query": "{ b: hello(name: \"B\") }" }]))
+        .await;
+    assert_eq!(response.json::<Value>(), json!([{ "data": { "a": "Hello, A!" } }, { "data": { "b": "Hello, B!" } }]));
+
+    assert_eq!(client.get("/rest").await.json::<Value>(), json!({ "rest": true }));
+    assert_eq!(client.post("/graphql").body("not json").await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn browsers_get_graphiql() {
+    let client = TestClient::from_server(server(GraphQL::new(schema()).path("/api/graphql")));
+    let response = client.get("/api/graphql").header("accept", "text/html").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.text().contains("graphiql"), "{}", response.text());
+
+    let client = TestClient::from_server(server(GraphQL::new(schema()).graphiql(false)));
+    let response = client.get("/graphql").header("accept", "text/html").await;
+    assert!(response.json::<Value>()["errors"].is_array());
+}
+
+#[tokio::test]
+async fn the_endpoint_can_require_authentication() {
+    let client = TestClient::from_server(server(GraphQL::new(schema()).auth("api_key")));
+    let query = json!({ "query": "{ me }" });
+    assert_eq!(client.post("/graphql").json(&query).await.status(), StatusCode::UNAUTHORIZED);
+
+    let response = client.post("/graphql").header("x-api-key", "secret").json(&query).await;
+    assert_eq!(response.json::<Value>(), json!({ "data": { "me": "ops" } }));
+}
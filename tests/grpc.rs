@@ -0,0 +1,132 @@
+#![cfg(feature = "grpc")]
+
+use std::convert::Infallible;
+use std::task::{Context, Poll};
+
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, StatusCode};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::{Request, Version};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use prost::Message;
+use serde_json::{json, Value};
+use tonic::body::BoxBody;
+use tonic::codec::ProstCodec;
+use tonic::codegen::{empty_body, http, BoxFuture};
+use tonic::server::NamedService;
+
+#[http_method(GET, "/grpc/ping")]
+fn ping() -> Value {
+    json!({ "pong": true })
+}
+
+// What `tonic-build` generates for `service Greeter { rpc SayHello (StringValue) returns (StringValue); }`
+#[derive(Clone)]
+struct Greeter;
+
+impl NamedService for Greeter {
+    const NAME: &'static str = "test.Greeter";
+}
+
+impl tower::Service<http::Request<BoxBody>> for Greeter {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Infallible>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+        Box::pin(async move {
+            if request.uri().path() != "/test.Greeter/SayHello" {
+                let unimplemented = http::Response::builder()
+                    .header("grpc-status", "12")
+                    .header("content-type", "application/grpc")
+                    .body(empty_body())
+                    .unwrap();
+                return Ok(unimplemented);
+            }
+            let say_hello = tower::service_fn(|request: tonic::Request<String>| async move {
+                Ok::<_, tonic::Status>(tonic::Response::new(format!("hello {}", request.into_inner())))
+            });
+            let mut grpc = tonic::server::Grpc::new(ProstCodec::<String, String>::default());
+            Ok(grpc.unary(say_hello, request).await)
+        })
+    }
+}
+
+// A length-prefixed, uncompressed gRPC message
+fn frame(message: &str) -> Vec<u8> {
+    let message = message.to_string().encode_to_vec();
+    let mut frame = vec![0];
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend(message);
+    frame
+}
+
+fn unframe(frame: &[u8]) -> String {
+    String::decode(&frame[5..]).unwrap()
+}
+
+#[tokio::test]
+async fn grpc_calls_reach_the_service_over_h2c() {
+    let handle = Server::new().grpc(Greeter).start_in_background("127.0.0.1:0").await.unwrap();
+    let stream = tokio::net::TcpStream::connect(handle.local_addr().unwrap()).await.unwrap();
+    let (mut sender, connection) = hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+        .await
+        .unwrap();
+    tokio::spawn(connection);
+
+    let request = Request::post("http://localhost/test.Greeter/SayHello")
+        .header("content-type", "application/grpc")
+        .header("te", "trailers")
+        .body(Full::new(Bytes::from(frame("alice"))))
+        .unwrap();
+    let response = sender.send_request(request).await.unwrap();
+    assert_eq!(response.version(), Version::HTTP_2);
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap();
+    assert_eq!(body.trailers().unwrap()["grpc-status"], "0");
+    assert_eq!(unframe(&body.to_bytes()), "hello alice");
+
+    // REST routes on the same connection
+    let request = Request::get("http://localhost/grpc/ping").body(Full::new(Bytes::new())).unwrap();
+    let response = sender.send_request(request).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), json!({ "pong": true }));
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn unknown_services_answer_unimplemented() {
+    let client = TestClient::from_server(Server::new().grpc(Greeter));
+    let response = client
+        .post("/other.Service/Call")
+        .header("content-type", "application/grpc+proto")
+        .body(frame("bob"))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("grpc-status"), Some("12"));
+    let response = client
+        .post("/test.Greeter/Missing")
+        .header("content-type", "application/grpc")
+        .body(frame("bob"))
+        .await;
+    assert_eq!(response.header("grpc-status"), Some("12"));
+}
+
+#[tokio::test]
+async fn other_requests_are_served_as_rest() {
+    let client = TestClient::from_server(Server::new().grpc(Greeter));
+    assert_eq!(client.get("/grpc/ping").await.json::<Value>(), json!({ "pong": true }));
+    // Not gRPC, so routed like any request
+    let response = client
+        .post("/test.Greeter/SayHello")
+        .header("content-type", "application/grpc-web")
+        .body(frame("carol"))
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert!(response.header("grpc-status").is_none());
+}
@@ -0,0 +1,136 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ferrox::auth::api_key::{ApiKey, ApiKeyAuth, InMemoryKeyStore};
+use ferrox::guard::{Guard, GuardResult};
+use ferrox::test::TestClient;
+use ferrox::{http_method, route_group, RequestContext, Server, StatusCode};
+use serde_json::{json, Value};
+
+static HANDLER_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+struct Verified;
+
+impl Guard for Verified {
+    async fn check(&self, ctx: &RequestContext) -> GuardResult {
+        match ctx.header("x-verified") {
+            Some("yes") => GuardResult::Allow,
+            Some(_) => GuardResult::Forbidden("Account is not verified".to_string()),
+            None => GuardResult::Unauthorized("Who are you?".to_string()),
+        }
+    }
+}
+
+struct HolderIs(&'static str);
+
+impl Guard for HolderIs {
+    async fn check(&self, ctx: &RequestContext) -> GuardResult {
+        match ctx.extensions.get::<ApiKey>() {
+            Some(key) if key.name == self.0 => GuardResult::Allow,
+            _ => GuardResult::Forbidden(format!("Only {} may do this", self.0)),
+        }
+    }
+}
+
+struct OwnAccount;
+
+impl Guard for OwnAccount {
+    async fn check(&self, ctx: &RequestContext) -> GuardResult {
+        if ctx.path["id"] == json!(ctx.header("x-account")) {
+            GuardResult::Allow
+        } else {
+            GuardResult::Forbidden("Not your account".to_string())
+        }
+    }
+}
+
+#[http_method(POST, "/guarded/accounts/:id", guards = [Verified, OwnAccount])]
+fn update_account(id: u64, body: Value) -> Value {
+    HANDLER_CALLS.fetch_add(1, Ordering::SeqCst);
+    json!({ "id": id, "changes": body })
+}
+
+#[http_method(DELETE, "/guarded/keys", auth = "api_key", guards = [HolderIs("ops")])]
+fn revoke_keys() -> Value {
+    json!({ "revoked": true })
+}
+
+#[route_group(prefix = "/guarded/admin", guards = [crate::Verified])]
+mod admin {
+    use ferrox::http_method;
+    use serde_json::{json, Value};
+
+    #[http_method(GET, "/stats", guards = [super::HolderIs("ops")], auth = "api_key")]
+    fn stats() -> Value {
+        json!({ "requests": 42 })
+    }
+}
+
+fn server() -> Server {
+    Server::new().api_keys(ApiKeyAuth::new(
+        InMemoryKeyStore::new().with_key("ops", "ops-key").with_key("intern", "intern-key"),
+    ))
+}
+
+#[tokio::test]
+async fn guards_run_in_order_before_the_handler() {
+    let client = TestClient::from_server(server());
+    let before = HANDLER_CALLS.load(Ordering::SeqCst);
+
+    let response = client.post("/guarded/accounts/7").json(&json!({ "name": "alice" })).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.json::<Value>()["message"], "Who are you?");
+
+    let response = client.post("/guarded/accounts/7").header("x-verified", "no").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = client
+        .post("/guarded/accounts/7")
+        .header("x-verified", "yes")
+        .header("x-account", "8")
+        .await;
+    assert_eq!(response.json::<Value>()["message"], "Not your account");
+    assert_eq!(HANDLER_CALLS.load(Ordering::SeqCst), before);
+
+    let response = client
+        .post("/guarded/accounts/7")
+        .header("x-verified", "yes")
+        .header("x-account", "7")
+        .json(&json!({ "name": "alice" }))
+        .await;
+    assert_eq!(response.json::<Value>(), json!({ "id": 7, "changes": { "name": "alice" } }));
+}
+
+#[tokio::test]
+async fn guards_see_the_authenticated_identity() {
+    let client = TestClient::from_server(server());
+    let response = client.delete("/guarded/keys").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = client.delete("/guarded/keys").header("x-api-key", "intern-key").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.json::<Value>()["message"], "Only ops may do this");
+
+    let response = client.delete("/guarded/keys").header("x-api-key", "ops-key").await;
+    assert_eq!(response.json::<Value>(), json!({ "revoked": true }));
+}
+
+#[tokio::test]
+async fn group_guards_run_before_the_routes_own() {
+    let client = TestClient::from_server(server());
+    let response = client.get("/guarded/admin/stats").header("x-api-key", "intern-key").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = client
+        .get("/guarded/admin/stats")
+        .header("x-api-key", "intern-key")
+        .header("x-verified", "yes")
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = client
+        .get("/guarded/admin/stats")
+        .header("x-api-key", "ops-key")
+        .header("x-verified", "yes")
+        .await;
+    assert_eq!(response.json::<Value>(), json!({ "requests": 42 }));
+}
@@ -0,0 +1,45 @@
+use ferrox::test::TestClient;
+use ferrox::{http_method, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/reports/:id")]
+fn get_report(id: u64) -> Value {
+    json!({ "id": id, "title": "Quarterly numbers" })
+}
+
+#[http_method(GET, "/exports", head = false)]
+fn export() -> Value {
+    json!({ "rows": [] })
+}
+
+#[http_method(POST, "/exports")]
+fn start_export() -> Value {
+    json!({ "started": true })
+}
+
+#[tokio::test]
+async fn get_routes_answer_head_without_the_body() {
+    let client = TestClient::new();
+    let get = client.get("/reports/3").await;
+    let head = client.head("/reports/3").await;
+    assert_eq!(head.status(), StatusCode::OK);
+    assert_eq!(head.text(), "");
+    assert_eq!(head.header("content-length"), Some(get.text().len().to_string().as_str()));
+    assert_eq!(head.header("content-type"), get.header("content-type"));
+}
+
+#[tokio::test]
+async fn routes_can_opt_out_of_head() {
+    let client = TestClient::new();
+    let response = client.head("/exports").await;
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.header("allow"), Some("GET, POST, OPTIONS"));
+    assert_eq!(client.get("/exports").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn allow_lists_head_for_get_routes() {
+    let client = TestClient::new();
+    let response = client.delete("/reports/3").await;
+    assert_eq!(response.header("allow"), Some("GET, HEAD, OPTIONS"));
+}
@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use ferrox::health::HealthChecks;
+use ferrox::test::TestClient;
+use ferrox::{Server, StatusCode};
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn probes_without_checks_report_healthy() {
+    let client = TestClient::from_server(Server::new().enable_health_checks());
+    for path in ["/healthz", "/readyz", "/livez"] {
+        let response = client.get(path).await;
+        assert_eq!(response.status(), StatusCode::OK, "{}", path);
+        assert_eq!(response.json::<Value>(), json!({ "status": "ok", "checks": {} }));
+    }
+    assert_eq!(TestClient::new().get("/healthz").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn failing_readiness_checks_answer_503() {
+    let ready = Arc::new(AtomicBool::new(false));
+    let flag = ready.clone();
+    let checks = HealthChecks::new()
+        .check("database", move || {
+            let ready = flag.load(Ordering::SeqCst);
+            async move { if ready { Ok(()) } else { Err("connection refused") } }
+        })
+        .check("cache", || async { Ok::<(), String>(()) });
+    let client = TestClient::from_server(Server::new().health_checks(checks));
+
+    let response = client.get("/readyz").await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let report = response.json::<Value>();
+    assert_eq!(report["status"], "fail");
+    assert_eq!(report["checks"]["database"]["status"], "fail");
+    assert_eq!(report["checks"]["database"]["error"], "connection refused");
+    assert_eq!(report["checks"]["cache"]["status"], "ok");
+    assert!(report["checks"]["cache"]["duration_ms"].is_u64());
+
+    // Readiness checks do not affect liveness
+    assert_eq!(client.get("/livez").await.status(), StatusCode::OK);
+    assert_eq!(client.get("/healthz").await.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    ready.store(true, Ordering::SeqCst);
+    assert_eq!(client.get("/readyz").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn slow_and_panicking_checks_fail() {
+    let checks = HealthChecks::new()
+        .timeout(Duration::from_millis(50))
+        .liveness_check("slow", || async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok::<(), String>(())
+        })
+        .liveness_check("broken", || async {
+            panic!("boom");
+            #[allow(unreachable_code)]
+            Ok::<(), String>(())
+        });
+    let client = TestClient::from_server(Server::new().health_checks(checks));
+
+    let response = client.get("/livez").await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let report = response.json::<Value>();
+    assert_eq!(report["checks"]["slow"]["error"], "timed out after 50ms");
+    assert_eq!(report["checks"]["broken"]["error"], "check panicked");
+    assert_eq!(client.get("/readyz").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn paths_can_be_changed() {
+    let checks = HealthChecks::new().paths("/health", "/health/ready", "/health/live");
+    let client = TestClient::from_server(Server::new().health_checks(checks));
+    assert_eq!(client.get("/health/ready").await.status(), StatusCode::OK);
+    assert_eq!(client.get("/readyz").await.status(), StatusCode::NOT_FOUND);
+}
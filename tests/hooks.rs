@@ -0,0 +1,96 @@
+use std::sync::{Arc, Mutex};
+
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, HandlerResponse, RequestContext, Server, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+struct Listing {
+    limit: u32,
+}
+
+#[http_method(GET, "/hooks/users")]
+fn list_users(query: Listing) -> Value {
+    json!({ "limit": query.limit, "_shard": 3 })
+}
+
+#[http_method(POST, "/hooks/users")]
+fn create_user(body: Value) -> Value {
+    json!({ "created": body, "_internal": true })
+}
+
+#[http_method(GET, "/hooks/users/:id")]
+fn get_user(id: u64) -> Result<Value, FerroxError> {
+    Err(FerroxError::NotFound(format!("User {} not found", id)))
+}
+
+fn strip_internal(response: &mut HandlerResponse) {
+    if let Some(object) = response.body_mut().as_object_mut() {
+        object.retain(|key, _| !key.starts_with('_'));
+    }
+}
+
+#[tokio::test]
+async fn before_hooks_change_the_parameters() {
+    let server = Server::new()
+        .before(|ctx: &mut RequestContext| {
+            if ctx.body.is_object() {
+                ctx.body["source"] = json!("api");
+            }
+            Ok(())
+        })
+        .before_route("GET", "/hooks/users", |ctx: &mut RequestContext| {
+            if ctx.query.get("limit").is_none() {
+                ctx.query["limit"] = json!("20");
+            }
+            Ok(())
+        });
+    let client = TestClient::from_server(server);
+
+    let response = client.get("/hooks/users").await;
+    assert_eq!(response.json::<Value>(), json!({ "limit": 20, "_shard": 3 }));
+    assert_eq!(client.get("/hooks/users?limit=5").await.json::<Value>()["limit"], 5);
+    let response = client.post("/hooks/users").json(&json!({ "name": "ada" })).await;
+    assert_eq!(response.json::<Value>()["created"], json!({ "name": "ada", "source": "api" }));
+}
+
+#[tokio::test]
+async fn before_hooks_can_reject_requests() {
+    let server = Server::new().before(|ctx: &mut RequestContext| match ctx.header("x-client") {
+        Some(_) => Ok(()),
+        None => Err(FerroxError::BadRequest("Missing X-Client header".to_string())),
+    });
+    let client = TestClient::from_server(server);
+    let response = client.get("/hooks/users?limit=1").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.json::<Value>()["message"], "Missing X-Client header");
+    let response = client.get("/hooks/users?limit=1").header("x-client", "cli").await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn after_hooks_change_successful_results() {
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let (route_order, global_order) = (order.clone(), order.clone());
+    let server = Server::new()
+        .after(strip_internal)
+        .after(move |_: &mut HandlerResponse| global_order.lock().unwrap().push("server"))
+        .after_route("POST", "/hooks/users", move |response: &mut HandlerResponse| {
+            route_order.lock().unwrap().push("route");
+            response.body_mut()["_audited"] = json!(true);
+        });
+    let client = TestClient::from_server(server);
+
+    let response = client.post("/hooks/users").json(&json!({ "name": "ada" })).await;
+    assert_eq!(response.json::<Value>(), json!({ "created": { "name": "ada" } }));
+    // The route's hooks run first
+    assert_eq!(*order.lock().unwrap(), ["route", "server"]);
+    assert_eq!(client.get("/hooks/users?limit=2").await.json::<Value>(), json!({ "limit": 2 }));
+
+    // Errors are sent as they are
+    order.lock().unwrap().clear();
+    let response = client.get("/hooks/users/7").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert!(order.lock().unwrap().is_empty());
+}
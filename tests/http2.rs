@@ -0,0 +1,53 @@
+use ferrox::http2::Http2Config;
+use ferrox::{http_method, Server, ServerHandle};
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper::{Request, Version};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/ping")]
+fn ping() -> Value {
+    json!({ "pong": true })
+}
+
+async fn start(http2: Http2Config) -> ServerHandle {
+    Server::new().http2(http2).start_in_background("127.0.0.1:0").await.unwrap()
+}
+
+// Send GET /ping over an HTTP/2 connection opened with prior knowledge
+async fn h2c_ping(handle: &ServerHandle) -> Result<(Version, Value), hyper::Error> {
+    let stream = tokio::net::TcpStream::connect(handle.local_addr().unwrap()).await.unwrap();
+    let (mut sender, connection) =
+        hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream)).await?;
+    tokio::spawn(connection);
+    let request = Request::get("http://localhost/ping").body(Empty::<Bytes>::new()).unwrap();
+    let response = sender.send_request(request).await?;
+    let version = response.version();
+    let body = response.into_body().collect().await?.to_bytes();
+    Ok((version, serde_json::from_slice(&body).unwrap()))
+}
+
+#[tokio::test]
+async fn plain_listeners_accept_h2c() {
+    let handle = start(Http2Config::new().max_concurrent_streams(10).max_frame_size(32 * 1024)).await;
+    let (version, body) = h2c_ping(&handle).await.unwrap();
+    assert_eq!(version, Version::HTTP_2);
+    assert_eq!(body, json!({ "pong": true }));
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn h2c_can_be_turned_off() {
+    let handle = start(Http2Config::new().h2c(false)).await;
+    assert!(h2c_ping(&handle).await.is_err());
+
+    // HTTP/1.1 is still served
+    let stream = tokio::net::TcpStream::connect(handle.local_addr().unwrap()).await.unwrap();
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+    tokio::spawn(connection);
+    let request = Request::get("/ping").header("host", "localhost").body(Empty::<Bytes>::new()).unwrap();
+    let response = sender.send_request(request).await.unwrap();
+    assert_eq!(response.version(), Version::HTTP_11);
+    handle.shutdown().await;
+}
@@ -0,0 +1,92 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use ferrox::idempotency::{IdempotencyLayer, MemoryStore};
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, Server, StatusCode};
+use serde_json::{json, Value};
+
+static CHARGES: AtomicUsize = AtomicUsize::new(0);
+static SLOW_CALLS: AtomicUsize = AtomicUsize::new(0);
+static DECLINES: AtomicUsize = AtomicUsize::new(0);
+
+#[http_method(POST, "/idempotency/charges")]
+fn charge(body: Value) -> Value {
+    let number = CHARGES.fetch_add(1, Ordering::SeqCst) + 1;
+    json!({ "charge": number, "amount": body["amount"] })
+}
+
+#[http_method(POST, "/idempotency/slow")]
+async fn slow() -> Value {
+    SLOW_CALLS.fetch_add(1, Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    json!({ "done": true })
+}
+
+#[http_method(POST, "/idempotency/flaky")]
+fn flaky() -> Result<Value, FerroxError> {
+    match DECLINES.fetch_add(1, Ordering::SeqCst) {
+        0 => Err(FerroxError::Internal("Processor unavailable".to_string())),
+        _ => Ok(json!({ "ok": true })),
+    }
+}
+
+fn client() -> TestClient {
+    TestClient::from_server(Server::new().layer(IdempotencyLayer::new(MemoryStore::new())))
+}
+
+#[tokio::test]
+async fn retries_with_the_same_key_replay_the_first_response() {
+    let client = client();
+    let first = client.post("/idempotency/charges").header("idempotency-key", "k-1").json(&json!({ "amount": 5 })).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(first.header("idempotent-replayed"), None);
+
+    let retry = client.post("/idempotency/charges").header("idempotency-key", "k-1").json(&json!({ "amount": 5 })).await;
+    assert_eq!(retry.status(), StatusCode::OK);
+    assert_eq!(retry.header("idempotent-replayed"), Some("true"));
+    assert_eq!(retry.json::<Value>(), first.json::<Value>());
+    assert_eq!(retry.header("content-type"), first.header("content-type"));
+
+    // A key reused for another payload is refused
+    let reused = client.post("/idempotency/charges").header("idempotency-key", "k-1").json(&json!({ "amount": 9 })).await;
+    assert_eq!(reused.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Requests without a key, and other keys, run as usual
+    let before = CHARGES.load(Ordering::SeqCst);
+    client.post("/idempotency/charges").json(&json!({ "amount": 5 })).await;
+    client.post("/idempotency/charges").header("idempotency-key", "k-2").json(&json!({ "amount": 5 })).await;
+    assert_eq!(CHARGES.load(Ordering::SeqCst), before + 2);
+}
+
+#[tokio::test]
+async fn a_retry_during_the_first_request_conflicts() {
+    let client = client();
+    let first = client.post("/idempotency/slow").header("idempotency-key", "slow-1");
+    let second = async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        client.post("/idempotency/slow").header("idempotency-key", "slow-1").await
+    };
+    let (first, second) = tokio::join!(first.into_future(), second);
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(second.status(), StatusCode::CONFLICT);
+    assert_eq!(SLOW_CALLS.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn server_errors_are_not_stored() {
+    let client = client();
+    let failed = client.post("/idempotency/flaky").header("idempotency-key", "f-1").await;
+    assert_eq!(failed.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let retry = client.post("/idempotency/flaky").header("idempotency-key", "f-1").await;
+    assert_eq!(retry.status(), StatusCode::OK);
+    assert_eq!(retry.header("idempotent-replayed"), None);
+}
+
+#[tokio::test]
+async fn keys_can_be_required() {
+    let layer = IdempotencyLayer::new(MemoryStore::new()).required(true);
+    let client = TestClient::from_server(Server::new().layer(layer));
+    let response = client.post("/idempotency/charges").json(&json!({ "amount": 1 })).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,140 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use ferrox::jobs::{JobQueue, JobsConfig, RetryPolicy};
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, Server, State, StatusCode};
+use serde_json::{json, Value};
+
+#[derive(Clone, Default)]
+struct Sent(Arc<AtomicUsize>);
+
+#[http_method(POST, "/jobs/welcome")]
+fn welcome(jobs: State<JobQueue>, sent: State<Sent>) -> Result<Value, FerroxError> {
+    let sent = sent.0.clone();
+    jobs.enqueue("welcome_email", move || {
+        let sent = sent.clone();
+        async move {
+            sent.0.fetch_add(1, Ordering::SeqCst);
+            Ok::<(), String>(())
+        }
+    })?;
+    Ok(json!({ "queued": true }))
+}
+
+// Wait until `counter` reaches `expected`, failing after a second
+async fn wait_for(counter: &AtomicUsize, expected: usize) {
+    for _ in 0..100 {
+        if counter.load(Ordering::SeqCst) >= expected {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("expected {} runs, saw {}", expected, counter.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn handlers_queue_jobs_that_run_after_the_response() {
+    let sent = Sent::default();
+    let server = Server::new()
+        .with_state(sent.clone())
+        .jobs(JobQueue::new(JobsConfig::new().workers(2)));
+    let client = TestClient::from_server(server);
+
+    for _ in 0..3 {
+        assert_eq!(client.post("/jobs/welcome").await.status(), StatusCode::OK);
+    }
+    wait_for(&sent.0, 3).await;
+}
+
+#[tokio::test]
+async fn failed_jobs_are_retried_until_they_succeed_or_give_up() {
+    let queue = JobQueue::new(JobsConfig::new().retry(RetryPolicy::fixed(3, Duration::from_millis(5))));
+
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let counter = attempts.clone();
+    queue
+        .enqueue("flaky", move || {
+            let attempt = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            async move { if attempt < 3 { Err(format!("attempt {} failed", attempt)) } else { Ok(()) } }
+        })
+        .unwrap();
+    wait_for(&attempts, 3).await;
+
+    let panics = Arc::new(AtomicUsize::new(0));
+    let counter = panics.clone();
+    queue
+        .enqueue_with("broken", RetryPolicy::fixed(2, Duration::ZERO), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                panic!("boom");
+                #[allow(unreachable_code)]
+                Ok::<(), String>(())
+            }
+        })
+        .unwrap();
+    assert!(queue.shutdown(Duration::from_secs(1)).await);
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    assert_eq!(panics.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn full_queues_refuse_jobs_with_503() {
+    let queue = JobQueue::new(JobsConfig::new().workers(1).capacity(1));
+    let slow = || async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        Ok::<(), String>(())
+    };
+    queue.enqueue("running", slow).unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    queue.enqueue("waiting", slow).unwrap();
+
+    let err = queue.enqueue("refused", slow).unwrap_err();
+    assert_eq!(err.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(err.message(), "Job queue is full");
+}
+
+#[tokio::test]
+async fn graceful_shutdown_drains_queued_jobs() {
+    let queue = JobQueue::new(JobsConfig::new().workers(1));
+    let handle = Server::new()
+        .jobs(queue.clone())
+        .start_in_background("127.0.0.1:0")
+        .await
+        .unwrap();
+
+    let done = Arc::new(AtomicUsize::new(0));
+    for _ in 0..5 {
+        let done = done.clone();
+        queue
+            .enqueue("report", move || {
+                let done = done.clone();
+                async move {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    done.fetch_add(1, Ordering::SeqCst);
+                    Ok::<(), String>(())
+                }
+            })
+            .unwrap();
+    }
+
+    handle.graceful_shutdown(Duration::from_secs(5)).await.unwrap();
+    assert_eq!(done.load(Ordering::SeqCst), 5);
+    assert_eq!(queue.pending(), 0);
+    let err = queue.enqueue("late", || async { Ok::<(), String>(()) }).unwrap_err();
+    assert_eq!(err.message(), "Job queue is shutting down");
+}
+
+#[tokio::test]
+async fn shutdown_reports_jobs_that_outlive_the_timeout() {
+    let queue = JobQueue::new(JobsConfig::new());
+    queue
+        .enqueue("endless", || async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok::<(), String>(())
+        })
+        .unwrap();
+    assert!(!queue.shutdown(Duration::from_millis(50)).await);
+    assert_eq!(queue.pending(), 1);
+}
@@ -0,0 +1,258 @@
+#![cfg(feature = "jwt")]
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ferrox::auth::jwt::{Claims, JwtVerifier};
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, StatusCode};
+use jsonwebtoken::{EncodingKey, Header};
+use serde_json::{json, Value};
+
+const SECRET: &[u8] = b"jwt-test-secret";
+
+#[http_method(GET, "/jwt/me", auth = "jwt")]
+fn me(claims: Claims) -> Value {
+    json!({ "sub": claims.subject() })
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+fn claims() -> Value {
+    json!({ "sub": "ada", "iss": "https://auth.example.com", "aud": "api", "exp": now() + 3600 })
+}
+
+fn sign(claims: &Value, secret: &[u8]) -> String {
+    jsonwebtoken::encode(&Header::default(), claims, &EncodingKey::from_secret(secret)).unwrap()
+}
+
+fn client() -> TestClient {
+    let verifier = JwtVerifier::hs256(SECRET).issuer("https://auth.example.com").audience("api");
+    TestClient::from_server(Server::new().jwt(verifier))
+}
+
+#[tokio::test]
+async fn valid_tokens_reach_the_handler_with_their_claims() {
+    let response = client().get("/jwt/me").bearer(&sign(&claims(), SECRET)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>()["sub"], "ada");
+
+    let response = client().get("/jwt/me").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.header("www-authenticate"), Some("Bearer"));
+}
+
+#[tokio::test]
+async fn tokens_signed_with_another_key_are_rejected() {
+    let response = client().get("/jwt/me").bearer(&sign(&claims(), b"someone-else")).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Tampering with the payload breaks the signature too
+    let token = sign(&claims(), SECRET);
+    let forged = sign(&json!({ "sub": "root", "iss": "https://auth.example.com", "aud": "api", "exp": now() + 3600 }), SECRET);
+    let mut parts: Vec<&str> = token.split('.').collect();
+    parts[1] = forged.split('.').nth(1).unwrap();
+    let response = client().get("/jwt/me").bearer(&parts.join(".")).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn expiry_and_not_before_are_checked_with_leeway() {
+    let mut expired = claims();
+    expired["exp"] = json!(now() - 3600);
+    let response = client().get("/jwt/me").bearer(&sign(&expired, SECRET)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let mut early = claims();
+    early["nbf"] = json!(now() + 3600);
+    let response = client().get("/jwt/me").bearer(&sign(&early, SECRET)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Within the default minute of clock skew
+    let mut skewed = claims();
+    skewed["exp"] = json!(now() - 10);
+    skewed["nbf"] = json!(now() + 10);
+    let response = client().get("/jwt/me").bearer(&sign(&skewed, SECRET)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn issuer_and_audience_must_match() {
+    let mut other_issuer = claims();
+    other_issuer["iss"] = json!("https://evil.example.com");
+    let response = client().get("/jwt/me").bearer(&sign(&other_issuer, SECRET)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let mut other_audience = claims();
+    other_audience["aud"] = json!("billing");
+    let response = client().get("/jwt/me").bearer(&sign(&other_audience, SECRET)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // `aud` may list several audiences
+    let mut audiences = claims();
+    audiences["aud"] = json!(["billing", "api"]);
+    let response = client().get("/jwt/me").bearer(&sign(&audiences, SECRET)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[cfg(feature = "jwks")]
+mod jwks {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use ferrox::auth::jwt::JwtVerifier;
+    use ferrox::axum::{routing::get, Json, Router};
+    use ferrox::FerroxError;
+    use jsonwebtoken::{Algorithm, EncodingKey, Header};
+    use serde_json::{json, Value};
+    use tokio::net::TcpListener;
+
+    use super::claims;
+
+    const KEY_1: &str = include_str!("fixtures/jwt/rsa-1.pem");
+    const KEY_2: &str = include_str!("fixtures/jwt/rsa-2.pem");
+
+    fn jwk(kid: &str) -> Value {
+        match kid {
+            "key-1" => serde_json::from_str(include_str!("fixtures/jwt/key-1.json")).unwrap(),
+            _ => serde_json::from_str(include_str!("fixtures/jwt/key-2.json")).unwrap(),
+        }
+    }
+
+    fn sign(kid: &str, pem: &str) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(kid.to_string());
+        jsonwebtoken::encode(&header, &claims(), &EncodingKey::from_rsa_pem(pem.as_bytes()).unwrap()).unwrap()
+    }
+
+    // A JWKS endpoint publishing `published` and counting fetches
+    #[derive(Clone, Default)]
+    struct Issuer {
+        published: Arc<Mutex<Vec<Value>>>,
+        fetches: Arc<AtomicUsize>,
+        delay: Arc<Mutex<Duration>>,
+    }
+
+    impl Issuer {
+        async fn serve(&self) -> String {
+            let issuer = self.clone();
+            let app = Router::new().route(
+                "/jwks.json",
+                get(move || async move {
+                    issuer.fetches.fetch_add(1, Ordering::SeqCst);
+                    let delay = *issuer.delay.lock().unwrap();
+                    tokio::time::sleep(delay).await;
+                    Json(json!({ "keys": *issuer.published.lock().unwrap() }))
+                }),
+            );
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/jwks.json", listener.local_addr().unwrap());
+            tokio::spawn(async move { ferrox::axum::serve(listener, app).await });
+            url
+        }
+
+        fn publish(&self, kids: &[&str]) {
+            *self.published.lock().unwrap() = kids.iter().map(|kid| jwk(kid)).collect();
+        }
+
+        fn fetches(&self) -> usize {
+            self.fetches.load(Ordering::SeqCst)
+        }
+    }
+
+    #[tokio::test]
+    async fn rotated_keys_are_fetched_when_a_token_names_them() {
+        let issuer = Issuer::default();
+        issuer.publish(&["key-1"]);
+        let verifier = JwtVerifier::jwks(issuer.serve().await)
+            .audience("api")
+            .refresh_interval(Duration::ZERO);
+
+        let claims = verifier.verify(&sign("key-1", KEY_1)).await.unwrap();
+        assert_eq!(claims.subject(), Some("ada"));
+        assert_eq!(issuer.fetches(), 1);
+
+        // The issuer rotates to a new key and retires the old one
+        issuer.publish(&["key-2"]);
+        assert!(verifier.verify(&sign("key-2", KEY_2)).await.is_ok());
+        assert_eq!(issuer.fetches(), 2);
+        assert!(matches!(
+            verifier.verify(&sign("key-1", KEY_1)).await,
+            Err(FerroxError::Unauthorized(_))
+        ));
+
+        // A token naming one key but signed with another is still refused
+        assert!(verifier.verify(&sign("key-2", KEY_1)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn unknown_key_ids_do_not_hammer_the_endpoint() {
+        let issuer = Issuer::default();
+        issuer.publish(&["key-1"]);
+        let verifier = JwtVerifier::jwks(issuer.serve().await).audience("api");
+
+        assert!(verifier.verify(&sign("key-1", KEY_1)).await.is_ok());
+        for _ in 0..3 {
+            assert!(verifier.verify(&sign("key-2", KEY_2)).await.is_err());
+        }
+        // Refreshed at most once per interval, 5 minutes by default
+        assert_eq!(issuer.fetches(), 1);
+    }
+
+    #[tokio::test]
+    async fn cached_keys_do_not_wait_for_a_slow_fetch() {
+        let issuer = Issuer::default();
+        issuer.publish(&["key-1"]);
+        let verifier = JwtVerifier::jwks(issuer.serve().await)
+            .audience("api")
+            .refresh_interval(Duration::ZERO);
+        assert!(verifier.verify(&sign("key-1", KEY_1)).await.is_ok());
+
+        *issuer.delay.lock().unwrap() = Duration::from_secs(2);
+        let refresh = {
+            let verifier = verifier.clone();
+            tokio::spawn(async move { verifier.verify(&sign("key-2", KEY_2)).await })
+        };
+        while issuer.fetches() < 2 {
+            tokio::task::yield_now().await;
+        }
+        let cached = tokio::time::timeout(Duration::from_millis(500), verifier.verify(&sign("key-1", KEY_1))).await;
+        assert!(cached.expect("blocked behind the refresh").is_ok());
+        refresh.abort();
+    }
+
+    #[tokio::test]
+    async fn concurrent_misses_share_one_fetch() {
+        let issuer = Issuer::default();
+        issuer.publish(&["key-1"]);
+        let verifier = JwtVerifier::jwks(issuer.serve().await)
+            .audience("api")
+            .refresh_interval(Duration::ZERO);
+
+        let token = sign("key-1", KEY_1);
+        let mut tasks = tokio::task::JoinSet::new();
+        for _ in 0..8 {
+            let (verifier, token) = (verifier.clone(), token.clone());
+            tasks.spawn(async move { verifier.verify(&token).await.is_ok() });
+        }
+        while let Some(verified) = tasks.join_next().await {
+            assert!(verified.unwrap());
+        }
+        assert_eq!(issuer.fetches(), 1);
+    }
+
+    #[tokio::test]
+    async fn unreachable_endpoints_reject_tokens() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/jwks.json", listener.local_addr().unwrap());
+        drop(listener);
+
+        let verifier = JwtVerifier::jwks(url).audience("api");
+        assert!(matches!(
+            verifier.verify(&sign("key-1", KEY_1)).await,
+            Err(FerroxError::Unauthorized(_))
+        ));
+    }
+}
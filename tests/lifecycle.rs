@@ -0,0 +1,78 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ferrox::{Server, StartupError};
+
+type Log = Arc<Mutex<Vec<&'static str>>>;
+
+fn server_logging_to(log: &Log) -> Server {
+    let (first, second, stopped) = (log.clone(), log.clone(), log.clone());
+    Server::new()
+        .on_startup(move || async move {
+            first.lock().unwrap().push("migrate");
+            Ok::<(), String>(())
+        })
+        .on_startup(move || async move {
+            second.lock().unwrap().push("warm cache");
+            Ok::<(), String>(())
+        })
+        .on_shutdown(move || async move {
+            stopped.lock().unwrap().push("flush");
+            Ok::<(), String>(())
+        })
+}
+
+#[tokio::test]
+async fn hooks_run_in_order_around_the_server() {
+    let log = Log::default();
+    let handle = server_logging_to(&log).start_in_background("127.0.0.1:0").await.unwrap();
+    assert_eq!(*log.lock().unwrap(), ["migrate", "warm cache"]);
+
+    handle.graceful_shutdown(Duration::from_secs(1)).await.unwrap();
+    assert_eq!(*log.lock().unwrap(), ["migrate", "warm cache", "flush"]);
+}
+
+#[tokio::test]
+async fn a_failing_startup_hook_aborts_the_boot() {
+    let log = Log::default();
+    let (late, stopped) = (log.clone(), log.clone());
+    let err = Server::new()
+        .on_startup(|| async { Ok::<(), String>(()) })
+        .on_startup(|| async { Err("database unreachable") })
+        .on_startup(move || async move {
+            late.lock().unwrap().push("late");
+            Ok::<(), String>(())
+        })
+        .on_shutdown(move || async move {
+            stopped.lock().unwrap().push("flush");
+            Ok::<(), String>(())
+        })
+        .start_in_background("127.0.0.1:0")
+        .await
+        .err()
+        .unwrap();
+
+    assert_eq!(err.to_string(), "startup hook 2 of 3 failed: database unreachable");
+    let err = err.downcast::<StartupError>().unwrap();
+    assert_eq!(err.hook(), 2);
+    assert_eq!(err.message(), "database unreachable");
+    assert!(log.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn shutdown_hooks_all_run_when_one_fails() {
+    let log = Log::default();
+    let stopped = log.clone();
+    let handle = Server::new()
+        .on_shutdown(|| async { Err("buffer lost") })
+        .on_shutdown(move || async move {
+            stopped.lock().unwrap().push("close");
+            Ok::<(), String>(())
+        })
+        .start_in_background("127.0.0.1:0")
+        .await
+        .unwrap();
+
+    handle.shutdown().await;
+    assert_eq!(*log.lock().unwrap(), ["close"]);
+}
@@ -0,0 +1,69 @@
+use ferrox::test::TestClient;
+use ferrox::{http_method, StreamingResponse};
+use futures_util::stream;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+#[derive(Serialize)]
+struct Order {
+    id: u64,
+    customer: String,
+    note: Option<String>,
+    tags: Vec<&'static str>,
+}
+
+fn orders() -> impl futures_util::Stream<Item = Order> {
+    stream::iter([
+        Order { id: 1, customer: "Ada".to_string(), note: None, tags: vec!["vip"] },
+        Order { id: 2, customer: "Lovelace, Ada".to_string(), note: Some("said \"hi\"\nthen left".to_string()), tags: vec![] },
+    ])
+}
+
+#[http_method(GET, "/list/orders")]
+fn list_orders() -> StreamingResponse {
+    StreamingResponse::list(orders())
+}
+
+#[http_method(GET, "/list/orders.csv")]
+fn export_orders() -> StreamingResponse {
+    StreamingResponse::csv(orders()).attachment("orders.csv")
+}
+
+#[http_method(GET, "/list/empty")]
+fn empty() -> StreamingResponse {
+    StreamingResponse::list(stream::iter(Vec::<Value>::new()))
+}
+
+#[tokio::test]
+async fn csv_has_a_header_row_and_escapes_fields() {
+    let response = TestClient::new().get("/list/orders.csv").await;
+    assert_eq!(response.header("content-type"), Some("text/csv; charset=utf-8"));
+    assert_eq!(
+        response.text(),
+        "id,customer,note,tags\r\n1,Ada,,\"[\"\"vip\"\"]\"\r\n2,\"Lovelace, Ada\",\"said \"\"hi\"\"\nthen left\",[]\r\n"
+    );
+}
+
+#[tokio::test]
+async fn lists_are_encoded_as_the_client_accepts() {
+    let client = TestClient::new();
+    let response = client.get("/list/orders").await;
+    assert_eq!(response.header("content-type"), Some("application/json"));
+    assert_eq!(response.header("vary"), Some("accept"));
+    let orders: Value = response.json();
+    assert_eq!(orders[1]["customer"], "Lovelace, Ada");
+    assert_eq!(orders.as_array().unwrap().len(), 2);
+
+    let response = client.get("/list/orders").header("accept", "application/x-ndjson").await;
+    assert_eq!(response.header("content-type"), Some("application/x-ndjson"));
+    let lines: Vec<Value> = response.text().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(lines[0], json!({ "id": 1, "customer": "Ada", "note": null, "tags": ["vip"] }));
+
+    let response = client.get("/list/orders").header("accept", "application/json;q=0.5, text/csv").await;
+    assert!(response.text().starts_with("id,customer,note,tags\r\n"));
+}
+
+#[tokio::test]
+async fn empty_lists_are_empty_arrays() {
+    assert_eq!(TestClient::new().get("/list/empty").await.json::<Value>(), json!([]));
+}
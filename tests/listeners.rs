@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use ferrox::listener::Listener;
+use ferrox::{http_method, RequestContext, Server};
+use serde_json::{json, Value};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+#[http_method(GET, "/whoami")]
+fn whoami(ctx: &RequestContext) -> Value {
+    json!({ "remote": ctx.remote_addr.map(|addr| addr.ip().to_string()) })
+}
+
+#[http_method(GET, "/slow")]
+async fn slow() -> Value {
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    json!({ "done": true })
+}
+
+// Send a GET over `stream` and return the response's status line and JSON body
+async fn get(mut stream: impl AsyncRead + AsyncWrite + Unpin, path: &str) -> (String, Value) {
+    let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    (head.lines().next().unwrap().to_string(), serde_json::from_str(body).unwrap())
+}
+
+fn prebound() -> (std::net::TcpListener, std::net::SocketAddr) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    (listener, addr)
+}
+
+#[tokio::test]
+async fn every_listener_serves_the_same_routes() {
+    let (first, first_addr) = prebound();
+    let (second, second_addr) = prebound();
+    let handle = Server::new()
+        .bind(Listener::from_tcp(first))
+        .bind(Listener::from_tcp(second))
+        .run_in_background()
+        .await
+        .unwrap();
+
+    for addr in [first_addr, second_addr] {
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let (status, body) = get(stream, "/whoami").await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body, json!({ "remote": "127.0.0.1" }));
+    }
+    handle.graceful_shutdown(Duration::from_secs(1)).await.unwrap();
+}
+
+#[tokio::test]
+async fn the_handle_reports_ports_picked_by_the_system() {
+    let handle = Server::new()
+        .bind(Listener::tcp("127.0.0.1:0"))
+        .start_in_background("127.0.0.1:0")
+        .await
+        .unwrap();
+    let addrs = handle.local_addrs().to_vec();
+    assert_eq!(addrs.len(), 2);
+    assert!(addrs.iter().all(|addr| addr.port() != 0));
+    assert_eq!(handle.local_addr(), Some(addrs[0]));
+
+    let stream = tokio::net::TcpStream::connect(addrs[1]).await.unwrap();
+    let (status, _) = get(stream, "/whoami").await;
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    handle.shutdown().await;
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn unix_sockets_are_served_and_removed_on_shutdown() {
+    let path = std::env::temp_dir().join(format!("ferrox-listeners-{}.sock", std::process::id()));
+    // A socket file left behind by an earlier run is replaced
+    let stale = std::os::unix::net::UnixListener::bind(&path).unwrap();
+    drop(stale);
+
+    let handle = Server::new().bind(Listener::unix(&path)).run_in_background().await.unwrap();
+    let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+    let (status, body) = get(stream, "/whoami").await;
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert_eq!(body, json!({ "remote": null }));
+
+    assert_eq!(handle.local_addr(), None);
+    handle.graceful_shutdown(Duration::from_secs(1)).await.unwrap();
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn graceful_shutdown_finishes_requests_in_progress() {
+    let (listener, addr) = prebound();
+    let handle = Server::new().bind(Listener::from_tcp(listener)).run_in_background().await.unwrap();
+
+    let request = tokio::spawn(async move {
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        get(stream, "/slow").await
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    handle.graceful_shutdown(Duration::from_secs(2)).await.unwrap();
+
+    let (status, body) = request.await.unwrap();
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert_eq!(body, json!({ "done": true }));
+    assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+}
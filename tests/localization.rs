@@ -0,0 +1,115 @@
+use ferrox::envelope::ProblemDetails;
+use ferrox::i18n::{Catalog, Locale, Localization};
+use ferrox::test::TestClient;
+use ferrox::validate::Validate;
+use ferrox::{http_method, FerroxError, Server, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize, Validate)]
+struct Signup {
+    #[validate(length(min = 3))]
+    name: String,
+}
+
+#[http_method(POST, "/i18n/signup")]
+fn signup(body: Signup) -> Value {
+    json!({ "name": body.name })
+}
+
+#[http_method(GET, "/i18n/greeting")]
+fn greeting(locale: Locale) -> Value {
+    json!({ "locale": locale.as_str(), "language": locale.language() })
+}
+
+#[http_method(GET, "/i18n/orders/:id")]
+fn get_order(id: u64) -> Result<Value, FerroxError> {
+    Err(FerroxError::NotFound(format!("Order {} not found", id)))
+}
+
+#[http_method(GET, "/i18n/limited", rate_limit = "1/min")]
+fn limited() -> Value {
+    json!({})
+}
+
+fn localization() -> Localization {
+    let catalog = Catalog::new()
+        .message("es", "Route {path} not found", "Ruta {path} no encontrada")
+        .message("es", "Method not allowed", "Método no permitido")
+        .message("es", "Rate limit exceeded", "Demasiadas solicitudes")
+        .message("es", "Validation failed", "La validación falló")
+        .message("es", "length must be at least {min}", "la longitud debe ser al menos {min}")
+        .messages("es", [("Order {id} not found", "Pedido {id} no encontrado")])
+        .message("pt-br", "Method not allowed", "Método não permitido");
+    Localization::new("en").supported(&["es", "pt-BR"]).source(catalog)
+}
+
+fn client() -> TestClient {
+    TestClient::from_server(Server::new().localization(localization()))
+}
+
+#[tokio::test]
+async fn locales_are_negotiated_from_accept_language() {
+    let client = client();
+    let locale = |header: &'static str| {
+        let request = client.get("/i18n/greeting").header("accept-language", header);
+        async move { request.await.json::<Value>()["locale"].clone() }
+    };
+    assert_eq!(locale("es-MX,es;q=0.9,en;q=0.5").await, "es");
+    assert_eq!(locale("fr;q=0.9, pt-br").await, "pt-BR");
+    assert_eq!(locale("de, *;q=0.5").await, "en");
+    assert_eq!(locale("es;q=0, fr").await, "en");
+    assert_eq!(client.get("/i18n/greeting").await.json::<Value>(), json!({ "locale": "en", "language": "en" }));
+
+    // Without localization, the client's first preference
+    let plain = TestClient::new();
+    let response = plain.get("/i18n/greeting").header("accept-language", "pt-BR;q=0.8, de-CH").await;
+    assert_eq!(response.json::<Value>(), json!({ "locale": "de-CH", "language": "de" }));
+}
+
+#[tokio::test]
+async fn framework_errors_are_translated() {
+    let client = client();
+    let response = client.get("/i18n/missing").header("accept-language", "es").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.header("content-language"), Some("es"));
+    assert_eq!(response.json::<Value>()["message"], "Ruta /i18n/missing no encontrada");
+
+    let response = client.delete("/i18n/greeting").header("accept-language", "pt-BR").await;
+    assert_eq!(response.json::<Value>()["message"], "Método não permitido");
+
+    let response = client
+        .post("/i18n/signup")
+        .header("accept-language", "es-ES")
+        .json(&json!({ "name": "al" }))
+        .await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        response.json::<Value>(),
+        json!({
+            "success": false,
+            "data": { "name": ["la longitud debe ser al menos 3"] },
+            "message": "La validación falló",
+        })
+    );
+
+    client.get("/i18n/limited").await;
+    let response = client.get("/i18n/limited").header("accept-language", "es").await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.json::<Value>()["message"], "Demasiadas solicitudes");
+}
+
+#[tokio::test]
+async fn handler_errors_are_translated_in_any_envelope() {
+    let client = TestClient::from_server(Server::new().localization(localization()).response_envelope(ProblemDetails));
+    let response = client.get("/i18n/orders/7").header("accept-language", "es").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.header("content-language"), Some("es"));
+    assert_eq!(response.json::<Value>()["detail"], "Pedido 7 no encontrado");
+
+    // Untranslated messages are sent as they are
+    let response = client.get("/i18n/orders/7").header("accept-language", "pt-BR").await;
+    assert_eq!(response.json::<Value>()["detail"], "Order 7 not found");
+    let response = client.get("/i18n/orders/7").await;
+    assert_eq!(response.header("content-language"), Some("en"));
+}
@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use ferrox::ratelimit::{RateLimit, RateLimiter};
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/measured/items/:id")]
+fn item(id: u64) -> Result<Value, FerroxError> {
+    match id {
+        0 => Err(FerroxError::NotFound("No item 0".to_string())),
+        _ => Ok(json!({ "id": id })),
+    }
+}
+
+#[http_method(POST, "/measured/slow")]
+async fn slow() -> Value {
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    json!({ "done": true })
+}
+
+// The sample lines of `name` in `metrics`, without comments
+fn samples<'a>(metrics: &'a str, name: &str) -> Vec<&'a str> {
+    metrics.lines().filter(|line| line.starts_with(name)).collect()
+}
+
+#[tokio::test]
+async fn requests_are_counted_by_route_pattern_and_status() {
+    let client = TestClient::from_server(Server::new().enable_metrics());
+    for id in [1, 2, 0] {
+        client.get(&format!("/measured/items/{}", id)).await;
+    }
+    client.get("/measured/random-1").await;
+    client.get("/measured/random-2").await;
+
+    let response = client.get("/metrics").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("content-type"), Some("text/plain; version=0.0.4; charset=utf-8"));
+    let metrics = response.text();
+    assert!(metrics.contains("# TYPE ferrox_http_requests_total counter\n"));
+    assert_eq!(
+        samples(metrics, "ferrox_http_requests_total{"),
+        [
+            r#"ferrox_http_requests_total{method="GET",route="/measured/items/:id",status="200"} 2"#,
+            r#"ferrox_http_requests_total{method="GET",route="/measured/items/:id",status="404"} 1"#,
+            r#"ferrox_http_requests_total{method="GET",route="unmatched",status="404"} 2"#,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn latencies_fill_a_cumulative_histogram() {
+    let client = TestClient::from_server(Server::new().enable_metrics());
+    client.post("/measured/slow").await;
+    client.post("/measured/slow").await;
+
+    let response = client.get("/metrics").await;
+    let histogram = samples(response.text(), r#"ferrox_http_request_duration_seconds_bucket{method="POST""#);
+    let bucket = |bound: &str| {
+        let label = format!(r#"le="{}"}}"#, bound);
+        let line = histogram.iter().find(|line| line.contains(&label)).unwrap();
+        line.rsplit(' ').next().unwrap().parse::<u64>().unwrap()
+    };
+    assert_eq!(bucket("0.025"), 0);
+    assert_eq!(bucket("0.05"), 2);
+    assert_eq!(bucket("10"), 2);
+    assert_eq!(bucket("+Inf"), 2);
+    assert!(response
+        .text()
+        .contains(r#"ferrox_http_request_duration_seconds_count{method="POST",route="/measured/slow"} 2"#));
+}
+
+#[tokio::test]
+async fn scrapes_are_neither_counted_nor_limited() {
+    let limiter = RateLimiter::new(RateLimit::per_minute(1));
+    let client = TestClient::from_server(Server::new().rate_limit(limiter).metrics_path("/internal/metrics"));
+    assert_eq!(client.get("/measured/items/1").await.status(), StatusCode::OK);
+    assert_eq!(client.get("/measured/items/1").await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    for _ in 0..3 {
+        let response = client.get("/internal/metrics").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.text().contains("metrics\""));
+    }
+
+    let client = TestClient::from_server(Server::new().metrics_path("/internal/metrics"));
+    assert_eq!(client.get("/metrics").await.status(), StatusCode::NOT_FOUND);
+}
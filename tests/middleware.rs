@@ -0,0 +1,110 @@
+use ferrox::axum::extract::Request;
+use ferrox::axum::http::HeaderValue;
+use ferrox::axum::middleware::{from_fn, Next};
+use ferrox::axum::response::{IntoResponse, Response};
+use ferrox::test::TestClient;
+use ferrox::{http_method, middleware, RequestContext, Server, StatusCode};
+use serde_json::{json, Value};
+
+// Append `step` to the `x-trail` request header, so handlers see the order layers ran in
+fn mark(request: &mut Request, step: &str) {
+    let trail = match request.headers().get("x-trail").and_then(|value| value.to_str().ok()) {
+        Some(trail) => format!("{},{}", trail, step),
+        None => step.to_string(),
+    };
+    request.headers_mut().insert("x-trail", HeaderValue::from_str(&trail).unwrap());
+}
+
+async fn outer(mut request: Request, next: Next) -> Response {
+    mark(&mut request, "outer");
+    next.run(request).await
+}
+
+async fn inner(mut request: Request, next: Next) -> Response {
+    mark(&mut request, "inner");
+    next.run(request).await
+}
+
+async fn server_wide(mut request: Request, next: Next) -> Response {
+    mark(&mut request, "server");
+    let mut response = next.run(request).await;
+    response.headers_mut().insert("x-server-layer", HeaderValue::from_static("1"));
+    response
+}
+
+async fn route_only(mut request: Request, next: Next) -> Response {
+    mark(&mut request, "route");
+    next.run(request).await
+}
+
+async fn require_key(request: Request, next: Next) -> Response {
+    if request.headers().get("x-api-key").is_none() {
+        return (StatusCode::FORBIDDEN, "no key").into_response();
+    }
+    next.run(request).await
+}
+
+fn trail(ctx: &RequestContext) -> Value {
+    json!({ "trail": ctx.header("x-trail") })
+}
+
+#[http_method(GET, "/middleware/ordered")]
+#[middleware(outer)]
+#[middleware(layer = from_fn(inner))]
+fn ordered(ctx: &RequestContext) -> Value {
+    trail(ctx)
+}
+
+#[http_method(GET, "/middleware/plain")]
+fn plain(ctx: &RequestContext) -> Value {
+    trail(ctx)
+}
+
+#[http_method(DELETE, "/middleware/guarded")]
+#[middleware(require_key)]
+fn guarded() -> Value {
+    json!({ "deleted": true })
+}
+
+#[tokio::test]
+async fn handler_middleware_runs_in_declaration_order() {
+    let client = TestClient::new();
+    let response = client.get("/middleware/ordered").await;
+    assert_eq!(response.json::<Value>()["trail"], "outer,inner");
+    assert_eq!(client.get("/middleware/plain").await.json::<Value>()["trail"], Value::Null);
+}
+
+#[tokio::test]
+async fn middleware_can_answer_instead_of_the_handler() {
+    let client = TestClient::new();
+    let response = client.delete("/middleware/guarded").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.text(), "no key");
+
+    let response = client.delete("/middleware/guarded").header("x-api-key", "k").await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn server_layers_wrap_route_layers_and_handler_middleware() {
+    let client = TestClient::from_server(
+        Server::new()
+            .layer(from_fn(server_wide))
+            .route_layer("get", "/middleware/ordered", from_fn(route_only)),
+    );
+    let response = client.get("/middleware/ordered").await;
+    assert_eq!(response.json::<Value>()["trail"], "server,outer,inner,route");
+    // Route layers stay on their route
+    assert_eq!(client.get("/middleware/plain").await.json::<Value>()["trail"], "server");
+
+    // The not-found fallback goes through server layers too
+    let response = client.get("/middleware/nowhere").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.header("x-server-layer"), Some("1"));
+}
+
+#[tokio::test]
+async fn later_server_layers_wrap_earlier_ones() {
+    let client = TestClient::from_server(Server::new().layer(from_fn(inner)).layer(from_fn(outer)));
+    assert_eq!(client.get("/middleware/plain").await.json::<Value>()["trail"], "outer,inner");
+}
@@ -0,0 +1,101 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ferrox::mock::MockServer;
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, Json, Server, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct User {
+    id: u64,
+    name: String,
+}
+
+#[http_method(GET, "/mock/users/:id")]
+fn get_user(id: u64) -> Result<Json<User>, FerroxError> {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    Ok(Json(User { id, name: "real".to_string() }))
+}
+
+#[http_method(GET, "/mock/users")]
+fn list_users() -> Vec<User> {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    Vec::new()
+}
+
+#[http_method(POST, "/mock/orders")]
+fn create_order(body: Value) -> Value {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    body
+}
+
+#[http_method(GET, "/mock/count")]
+fn count() -> u64 {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    42
+}
+
+#[tokio::test]
+async fn routes_answer_with_canned_responses_without_running_handlers() {
+    let calls = CALLS.load(Ordering::SeqCst);
+    let mock = MockServer::new().respond("get", "/mock/users/:id", StatusCode::OK, json!({ "id": 7, "name": "alice" }));
+    let client = TestClient::from_server(Server::new().mock(mock));
+
+    let response = client.get("/mock/users/123").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "id": 7, "name": "alice" }));
+    // Values shaped like the return type
+    assert_eq!(client.get("/mock/count").await.json::<Value>()["data"], json!(0));
+    assert_eq!(client.get("/mock/users").await.json::<Value>()["data"], json!([{}]));
+    assert_eq!(CALLS.load(Ordering::SeqCst), calls);
+
+    // The routes themselves are unchanged
+    assert_eq!(client.get("/mock/missing").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(client.delete("/mock/users/1").await.status(), StatusCode::METHOD_NOT_ALLOWED);
+}
+
+#[tokio::test]
+async fn type_examples_fill_in_responses() {
+    let alice = json!({ "id": 1, "name": "alice" });
+    let client = TestClient::from_server(Server::new().mock(MockServer::new().example("User", alice.clone())));
+    assert_eq!(client.get("/mock/users/1").await.json::<Value>(), alice);
+    assert_eq!(client.get("/mock/users").await.json::<Value>()["data"], json!([alice]));
+}
+
+#[tokio::test]
+async fn openapi_examples_are_served_with_their_status() {
+    let document = json!({
+        "openapi": "3.1.0",
+        "paths": {
+            "/mock/orders": {
+                "post": {
+                    "responses": {
+                        "400": { "content": { "application/json": { "example": { "error": "bad" } } } },
+                        "201": {
+                            "content": {
+                                "application/json": {
+                                    "examples": { "paid": { "value": { "id": 9, "status": "paid" } } },
+                                },
+                            },
+                        },
+                    },
+                },
+            },
+            "/mock/users/{id}": {
+                "get": {
+                    "responses": {
+                        "200": { "content": { "application/json": { "example": { "id": 3, "name": "carol" } } } },
+                    },
+                },
+            },
+        },
+    });
+    let client = TestClient::from_server(Server::new().mock(MockServer::new().openapi(document)));
+    let response = client.post("/mock/orders").json(&json!({})).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.json::<Value>(), json!({ "id": 9, "status": "paid" }));
+    assert_eq!(client.get("/mock/users/3").await.json::<Value>(), json!({ "id": 3, "name": "carol" }));
+}
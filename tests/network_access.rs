@@ -0,0 +1,77 @@
+use ferrox::network::{IpFilter, IpNet};
+use ferrox::test::TestClient;
+use ferrox::{http_method, route_group, RequestContext, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/whoami")]
+fn whoami(ctx: &RequestContext) -> Value {
+    json!({ "ip": ctx.client_ip().map(|ip| ip.to_string()) })
+}
+
+#[route_group(prefix = "/admin", middleware = [
+    layer = ferrox::network::IpFilter::new().allow("10.0.0.0/8").deny("10.0.13.0/24"),
+])]
+mod admin {
+    use ferrox::http_method;
+    use serde_json::{json, Value};
+
+    #[http_method(GET, "/stats")]
+    fn stats() -> Value {
+        json!({ "requests": 42 })
+    }
+}
+
+fn behind_proxy() -> TestClient {
+    TestClient::from_server(Server::new().trusted_proxies(["127.0.0.1", "172.16.0.0/12"]))
+}
+
+#[tokio::test]
+async fn the_client_ip_is_read_through_trusted_proxies() {
+    let client = behind_proxy();
+    let response = client.get("/whoami").header("x-forwarded-for", "203.0.113.7, 172.16.4.1").await;
+    assert_eq!(response.json::<Value>(), json!({ "ip": "203.0.113.7" }));
+
+    // Anything left of an untrusted hop may be forged
+    let response = client.get("/whoami").header("x-forwarded-for", "1.1.1.1, 198.51.100.2").await;
+    assert_eq!(response.json::<Value>(), json!({ "ip": "198.51.100.2" }));
+
+    let response = client
+        .get("/whoami")
+        .header("forwarded", "for=\"[2001:db8::1]:4711\";proto=https, for=172.16.0.9")
+        .await;
+    assert_eq!(response.json::<Value>(), json!({ "ip": "2001:db8::1" }));
+}
+
+#[tokio::test]
+async fn forwarding_headers_are_ignored_without_trusted_proxies() {
+    let response = TestClient::new().get("/whoami").header("x-forwarded-for", "203.0.113.7").await;
+    assert_eq!(response.json::<Value>(), json!({ "ip": "127.0.0.1" }));
+}
+
+#[tokio::test]
+async fn a_route_group_can_be_restricted_to_internal_ranges() {
+    let client = behind_proxy();
+    let response = client.get("/admin/stats").header("x-forwarded-for", "10.1.2.3").await;
+    assert_eq!(response.json::<Value>(), json!({ "requests": 42 }));
+
+    let response = client.get("/admin/stats").header("x-forwarded-for", "10.0.13.5").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.json::<Value>()["message"], "Access denied");
+    let response = client.get("/admin/stats").header("x-forwarded-for", "203.0.113.7").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(client.get("/whoami").header("x-forwarded-for", "203.0.113.7").await.status(), StatusCode::OK);
+}
+
+#[test]
+fn ranges_parse_and_match() {
+    let net: IpNet = "192.168.0.0/16".parse().unwrap();
+    assert!(net.contains("192.168.40.1".parse().unwrap()));
+    assert!(net.contains("::ffff:192.168.0.1".parse().unwrap()));
+    assert!(!net.contains("192.169.0.1".parse().unwrap()));
+    assert!("10.0.0.0/33".parse::<IpNet>().is_err());
+    assert_eq!("fd00::/8".parse::<IpNet>().unwrap().to_string(), "fd00::/8");
+
+    let filter = IpFilter::new().deny("0.0.0.0/0");
+    assert!(!filter.permits(Some("8.8.8.8".parse().unwrap())));
+    assert!(IpFilter::new().permits(None));
+}
@@ -0,0 +1,104 @@
+use ferrox::openapi::{spec, OpenApiConfig};
+use ferrox::sse::SseStream;
+use ferrox::test::TestClient;
+use ferrox::{http_method, sse, ApiResponse, Server, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+struct Filters {
+    page: u32,
+}
+
+#[derive(Deserialize)]
+struct NewComment {
+    text: String,
+}
+
+#[http_method(GET, "/spec/posts/:post_id/comments/:id")]
+fn comment(post_id: u64, id: String, query: Option<Filters>) -> ApiResponse<String> {
+    let page = query.map_or(1, |filters| filters.page);
+    ApiResponse::ok(format!("{}/{} (page {})", post_id, id, page))
+}
+
+#[http_method(POST, "/spec/posts/:post_id/comments")]
+fn add_comment(post_id: u64, body: NewComment) -> Value {
+    json!({ "post": post_id, "text": body.text })
+}
+
+#[http_method(PUT, "/spec/files/*rest")]
+fn upload(path: Value, body: Option<Value>) -> Value {
+    json!({ "path": path, "body": body })
+}
+
+#[sse("/spec/events")]
+async fn events() -> SseStream {
+    SseStream::new(futures_util::stream::empty())
+}
+
+fn document() -> Value {
+    spec(&OpenApiConfig::new("Spec", "2.1.0"))
+}
+
+#[test]
+fn paths_and_parameters_follow_the_handler_signatures() {
+    let document = document();
+    assert_eq!(document["openapi"], "3.1.0");
+    assert_eq!(document["info"], json!({ "title": "Spec", "version": "2.1.0" }));
+
+    let operation = &document["paths"]["/spec/posts/{post_id}/comments/{id}"]["get"];
+    assert_eq!(operation["operationId"], "comment");
+    assert_eq!(
+        operation["parameters"],
+        json!([
+            { "name": "post_id", "in": "path", "required": true, "schema": { "type": "integer" } },
+            { "name": "id", "in": "path", "required": true, "schema": { "type": "string" } },
+            {
+                "name": "query",
+                "in": "query",
+                "required": false,
+                "style": "form",
+                "explode": true,
+                "schema": { "type": "object", "title": "Filters" },
+            },
+        ])
+    );
+    assert!(operation["responses"]["400"].is_object());
+    let data = &operation["responses"]["200"]["content"]["application/json"]["schema"]["properties"]["data"];
+    assert_eq!(data, &json!({ "type": "string" }));
+}
+
+#[test]
+fn typed_bodies_are_required_request_bodies() {
+    let document = document();
+    let body = &document["paths"]["/spec/posts/{post_id}/comments"]["post"]["requestBody"];
+    assert_eq!(body["required"], true);
+    let schema = json!({ "type": "object", "title": "NewComment" });
+    assert_eq!(body["content"]["application/json"]["schema"], schema);
+    assert_eq!(body["content"]["application/x-www-form-urlencoded"]["schema"], schema);
+
+    // Wildcards become placeholders too, documented even when taken as a whole `path`
+    let operation = &document["paths"]["/spec/files/{rest}"]["put"];
+    assert_eq!(operation["requestBody"]["required"], false);
+    assert_eq!(operation["parameters"][0]["name"], "rest");
+}
+
+#[test]
+fn streaming_routes_are_left_out() {
+    assert!(document()["paths"].get("/spec/events").is_none());
+}
+
+#[tokio::test]
+async fn the_document_and_swagger_ui_are_served() {
+    let config = OpenApiConfig::new("Spec", "2.1.0").spec_path("/api/spec.json").swagger_ui("/docs");
+    let client = TestClient::from_server(Server::new().openapi(config));
+
+    let served = client.get("/api/spec.json").await;
+    assert_eq!(served.status(), StatusCode::OK);
+    assert_eq!(served.json::<Value>(), document());
+    assert_eq!(client.get("/openapi.json").await.status(), StatusCode::NOT_FOUND);
+
+    let page = client.get("/docs").await;
+    assert!(page.header("content-type").unwrap().starts_with("text/html"));
+    assert!(page.text().contains("/api/spec.json"));
+}
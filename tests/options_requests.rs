@@ -0,0 +1,52 @@
+use ferrox::cors::CorsConfig;
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/articles/:id")]
+fn get_article(id: u64) -> Value {
+    json!({ "id": id })
+}
+
+#[http_method(DELETE, "/articles/:id")]
+fn delete_article(id: u64) -> Value {
+    json!({ "deleted": id })
+}
+
+#[http_method(OPTIONS, "/uploads")]
+fn upload_options() -> Value {
+    json!({ "max_size": "10MB" })
+}
+
+#[http_method(POST, "/uploads")]
+fn upload() -> Value {
+    json!({ "uploaded": true })
+}
+
+#[tokio::test]
+async fn options_lists_the_methods_a_path_serves() {
+    let client = TestClient::new();
+    let response = client.options("/articles/1").await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(response.header("allow"), Some("GET, HEAD, DELETE, OPTIONS"));
+    assert_eq!(response.text(), "");
+}
+
+#[tokio::test]
+async fn an_options_route_replaces_the_default() {
+    let client = TestClient::new();
+    let response = client.options("/uploads").await;
+    assert_eq!(response.json::<Value>(), json!({ "max_size": "10MB" }));
+}
+
+#[tokio::test]
+async fn preflights_are_still_answered_by_cors() {
+    let client = TestClient::from_server(Server::new().cors(CorsConfig::new().allow_origin("https://app.example")));
+    let response = client
+        .options("/articles/1")
+        .header("origin", "https://app.example")
+        .header("access-control-request-method", "DELETE")
+        .await;
+    assert_eq!(response.header("access-control-allow-origin"), Some("https://app.example"));
+    assert_eq!(response.header("allow"), None);
+}
@@ -0,0 +1,99 @@
+#![cfg(feature = "otel")]
+
+use std::sync::{Arc, Mutex};
+
+use ferrox::otel::OtelConfig;
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, RequestContext, Server, StatusCode};
+use futures_util::future::BoxFuture;
+use opentelemetry::trace::{SpanKind, Status, TraceContextExt};
+use opentelemetry::Value as AttributeValue;
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use opentelemetry_sdk::trace::TracerProvider;
+use serde_json::{json, Value};
+
+#[http_method(GET, "/traced/:id")]
+fn traced(id: u32) -> Value {
+    json!({ "id": id })
+}
+
+#[http_method(GET, "/traced-context")]
+fn traced_context(ctx: RequestContext) -> Value {
+    let cx = ferrox::otel::context(&ctx.extensions);
+    json!({ "trace_id": cx.span().span_context().trace_id().to_string() })
+}
+
+#[http_method(GET, "/traced-failure")]
+fn traced_failure() -> Result<Value, FerroxError> {
+    Err(FerroxError::new(StatusCode::INTERNAL_SERVER_ERROR, "database unavailable"))
+}
+
+// Collects finished spans in memory
+#[derive(Debug, Clone, Default)]
+struct Collector(Arc<Mutex<Vec<SpanData>>>);
+
+impl SpanExporter for Collector {
+    fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+        self.0.lock().unwrap().extend(batch);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+fn traced_client() -> (TestClient, Collector) {
+    let collector = Collector::default();
+    let provider = TracerProvider::builder().with_simple_exporter(collector.clone()).build();
+    let server = Server::new().opentelemetry(OtelConfig::new("test").tracer_provider(provider));
+    (TestClient::from_server(server), collector)
+}
+
+fn attribute(span: &SpanData, key: &str) -> Option<AttributeValue> {
+    span.attributes.iter().find(|kv| kv.key.as_str() == key).map(|kv| kv.value.clone())
+}
+
+#[tokio::test]
+async fn requests_get_server_spans_named_after_the_route() {
+    let (client, collector) = traced_client();
+    let response = client.get("/traced/7").header("user-agent", "probe/1.0").await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let spans = collector.0.lock().unwrap();
+    assert_eq!(spans.len(), 1);
+    let span = &spans[0];
+    assert_eq!(span.name, "GET /traced/:id");
+    assert_eq!(span.span_kind, SpanKind::Server);
+    assert_eq!(attribute(span, "http.route"), Some("/traced/:id".into()));
+    assert_eq!(attribute(span, "url.path"), Some("/traced/7".into()));
+    assert_eq!(attribute(span, "http.request.method"), Some("GET".into()));
+    assert_eq!(attribute(span, "user_agent.original"), Some("probe/1.0".into()));
+    assert_eq!(attribute(span, "http.response.status_code"), Some(200i64.into()));
+    assert!(span.end_time >= span.start_time);
+    assert_eq!(span.status, Status::Unset);
+}
+
+#[tokio::test]
+async fn incoming_traceparent_headers_are_continued() {
+    let (client, collector) = traced_client();
+    let response = client
+        .get("/traced-context")
+        .header("traceparent", "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+        .await;
+    assert_eq!(response.json::<Value>()["trace_id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+
+    let spans = collector.0.lock().unwrap();
+    assert_eq!(spans[0].span_context.trace_id().to_string(), "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(spans[0].parent_span_id.to_string(), "00f067aa0ba902b7");
+}
+
+#[tokio::test]
+async fn server_errors_mark_spans_as_failed() {
+    let (client, collector) = traced_client();
+    assert_eq!(client.get("/traced-failure").await.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(client.get("/untraced").await.status(), StatusCode::NOT_FOUND);
+
+    let spans = collector.0.lock().unwrap();
+    assert!(matches!(spans[0].status, Status::Error { .. }));
+    // Unmatched requests are named after the method alone
+    assert_eq!(spans[1].name, "GET");
+    assert_eq!(spans[1].status, Status::Unset);
+    assert_eq!(attribute(&spans[1], "http.route"), None);
+}
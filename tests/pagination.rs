@@ -0,0 +1,97 @@
+use ferrox::openapi::{spec, OpenApiConfig};
+use ferrox::pagination::{Paginated, Pagination, PaginationConfig};
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, StatusCode};
+use serde_json::{json, Value};
+
+const NUMBERS: u64 = 45;
+
+#[http_method(GET, "/pagination/numbers")]
+fn numbers(page: Pagination) -> Paginated<u64> {
+    let items = (1..=NUMBERS).skip(page.offset() as usize).take(page.limit() as usize).collect();
+    Paginated::new(items, &page).total(NUMBERS)
+}
+
+#[http_method(GET, "/pagination/events")]
+fn events(page: Pagination) -> Paginated<u64> {
+    let start: u64 = page.cursor.as_deref().and_then(|cursor| cursor.parse().ok()).unwrap_or(0);
+    let items: Vec<u64> = (start..start + page.per_page).filter(|event| *event < 5).collect();
+    let next = items.last().filter(|last| **last < 4).map(|last| (last + 1).to_string());
+    Paginated::new(items, &page).next_cursor(next)
+}
+
+#[tokio::test]
+async fn pages_carry_their_position_and_links() {
+    let client = TestClient::from_server(Server::new());
+    let response = client.get("/pagination/numbers?page=2&per_page=10&sort=asc").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.json::<Value>(),
+        json!({
+            "items": [11, 12, 13, 14, 15, 16, 17, 18, 19, 20],
+            "page": 2,
+            "per_page": 10,
+            "total": 45,
+            "next_cursor": null,
+        })
+    );
+    assert_eq!(
+        response.header("link"),
+        Some(concat!(
+            r#"</pagination/numbers?sort=asc&page=3&per_page=10>; rel="next", "#,
+            r#"</pagination/numbers?sort=asc&page=1&per_page=10>; rel="prev", "#,
+            r#"</pagination/numbers?sort=asc&page=1&per_page=10>; rel="first", "#,
+            r#"</pagination/numbers?sort=asc&page=5&per_page=10>; rel="last""#,
+        ))
+    );
+
+    let last = client.get("/pagination/numbers?page=5&per_page=10").await;
+    assert_eq!(last.json::<Value>()["items"], json!([41, 42, 43, 44, 45]));
+    assert!(!last.header("link").unwrap().contains("rel=\"next\""));
+}
+
+#[tokio::test]
+async fn page_sizes_follow_the_configured_limits() {
+    let client = TestClient::from_server(Server::new());
+    assert_eq!(client.get("/pagination/numbers").await.json::<Value>()["per_page"], 20);
+
+    let config = PaginationConfig::new().default_per_page(5).max_per_page(8);
+    let client = TestClient::from_server(Server::new().pagination(config));
+    assert_eq!(client.get("/pagination/numbers").await.json::<Value>()["per_page"], 5);
+    assert_eq!(client.get("/pagination/numbers?per_page=500").await.json::<Value>()["per_page"], 8);
+
+    for query in ["page=0", "per_page=-1", "page=two"] {
+        let response = client.get(&format!("/pagination/numbers?{}", query)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+    }
+}
+
+#[tokio::test]
+async fn cursors_link_to_the_next_page() {
+    let client = TestClient::from_server(Server::new());
+    let first = client.get("/pagination/events?per_page=3").await;
+    assert_eq!(first.json::<Value>()["next_cursor"], "3");
+    assert_eq!(first.header("link"), Some(r#"</pagination/events?cursor=3&per_page=3>; rel="next""#));
+
+    let second = client.get("/pagination/events?cursor=3&per_page=3").await;
+    let body = second.json::<Value>();
+    assert_eq!(body["items"], json!([3, 4]));
+    assert_eq!(body["page"], Value::Null);
+    assert_eq!(body["next_cursor"], Value::Null);
+    assert_eq!(second.header("link"), None);
+}
+
+#[test]
+fn pagination_parameters_are_documented() {
+    let document = spec(&OpenApiConfig::new("Pages", "1.0"));
+    let operation = &document["paths"]["/pagination/numbers"]["get"];
+    let names: Vec<&str> = operation["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|param| param["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["page", "per_page", "cursor"]);
+    let schema = &operation["responses"]["200"]["content"]["application/json"]["schema"];
+    assert_eq!(schema["properties"]["items"], json!({ "type": "array", "items": { "type": "integer" } }));
+}
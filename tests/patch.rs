@@ -0,0 +1,109 @@
+use ferrox::patch::Patch;
+use ferrox::test::TestClient;
+use ferrox::validate::Validate;
+use ferrox::{http_method, FerroxError, Json, Server, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate)]
+struct Profile {
+    #[validate(length(min = 3))]
+    name: String,
+    email: Option<String>,
+    tags: Vec<String>,
+    settings: Settings,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate)]
+struct Settings {
+    theme: String,
+    beta: bool,
+}
+
+fn stored() -> Profile {
+    Profile {
+        name: "alice".to_string(),
+        email: Some("alice@example.com".to_string()),
+        tags: vec!["admin".to_string()],
+        settings: Settings {
+            theme: "dark".to_string(),
+            beta: false,
+        },
+    }
+}
+
+#[http_method(PATCH, "/patch/profile")]
+fn update_profile(body: Patch) -> Result<Json<Profile>, FerroxError> {
+    Ok(Json(body.apply_validated(&stored())?))
+}
+
+#[tokio::test]
+async fn merge_patches_merge_objects_and_remove_nulls() {
+    let client = TestClient::from_server(Server::new());
+    let response = client
+        .patch("/patch/profile")
+        .header("content-type", "application/merge-patch+json")
+        .body(r#"{"email": null, "settings": {"beta": true}}"#)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let profile = response.json::<Value>();
+    assert_eq!(profile["email"], Value::Null);
+    assert_eq!(profile["settings"], json!({ "theme": "dark", "beta": true }));
+    assert_eq!(profile["name"], "alice");
+}
+
+#[tokio::test]
+async fn json_patches_apply_their_operations_in_order() {
+    let client = TestClient::from_server(Server::new());
+    let operations = json!([
+        { "op": "test", "path": "/name", "value": "alice" },
+        { "op": "add", "path": "/tags/-", "value": "ops" },
+        { "op": "copy", "from": "/settings/theme", "path": "/tags/0" },
+        { "op": "replace", "path": "/settings/theme", "value": "light" },
+    ]);
+    let response = client.patch("/patch/profile").json(&operations).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let profile = response.json::<Value>();
+    assert_eq!(profile["tags"], json!(["dark", "admin", "ops"]));
+    assert_eq!(profile["settings"]["theme"], "light");
+}
+
+#[tokio::test]
+async fn patches_that_do_not_apply_are_refused() {
+    let client = TestClient::from_server(Server::new());
+    let failed_test = json!([{ "op": "test", "path": "/name", "value": "bob" }]);
+    assert_eq!(client.patch("/patch/profile").json(&failed_test).await.status(), StatusCode::CONFLICT);
+    let missing = json!([{ "op": "remove", "path": "/nickname" }]);
+    assert_eq!(client.patch("/patch/profile").json(&missing).await.status(), StatusCode::CONFLICT);
+    let unknown = json!([{ "op": "frobnicate", "path": "/name" }]);
+    assert_eq!(client.patch("/patch/profile").json(&unknown).await.status(), StatusCode::BAD_REQUEST);
+
+    // Results that break the type or its rules
+    let wrong_type = json!({ "tags": "admin" });
+    assert_eq!(client.patch("/patch/profile").json(&wrong_type).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let too_short = json!({ "name": "al" });
+    let response = client.patch("/patch/profile").json(&too_short).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(response.json::<Value>()["data"]["name"].is_array());
+}
+
+#[test]
+fn failed_patches_leave_the_document_unchanged() {
+    let mut document = json!({ "a": [1, 2], "b": { "c~d/e": 1 } });
+    let patch: Patch = serde_json::from_value(json!([
+        { "op": "remove", "path": "/b/c~0d~1e" },
+        { "op": "move", "from": "/a/0", "path": "/b/first" },
+        { "op": "remove", "path": "/missing" },
+    ]))
+    .unwrap();
+    assert!(patch.apply_to_value(&mut document).is_err());
+    assert_eq!(document, json!({ "a": [1, 2], "b": { "c~d/e": 1 } }));
+
+    let patch: Patch = serde_json::from_value(json!([
+        { "op": "remove", "path": "/b/c~0d~1e" },
+        { "op": "move", "from": "/a/0", "path": "/b/first" },
+    ]))
+    .unwrap();
+    patch.apply_to_value(&mut document).unwrap();
+    assert_eq!(document, json!({ "a": [2], "b": { "first": 1 } }));
+}
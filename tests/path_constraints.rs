@@ -0,0 +1,56 @@
+use ferrox::test::TestClient;
+use ferrox::{http_method, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/orders/{id:u64}")]
+fn get_order(id: u64) -> Value {
+    json!({ "order": id })
+}
+
+#[http_method(GET, "/items/{id:u64}")]
+fn item_by_id(id: u64) -> Value {
+    json!({ "id": id })
+}
+
+#[http_method(GET, "/items/{slug:[a-z-]+}")]
+fn item_by_slug(slug: String) -> Value {
+    json!({ "slug": slug })
+}
+
+#[http_method(DELETE, "/items/{name}")]
+fn delete_item(name: String) -> Value {
+    json!({ "deleted": name })
+}
+
+#[tokio::test]
+async fn values_breaking_a_type_constraint_are_rejected() {
+    let client = TestClient::new();
+    assert_eq!(client.get("/orders/42").await.json::<Value>(), json!({ "order": 42 }));
+
+    let response = client.get("/orders/abc").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.json::<Value>()["message"], "Invalid path parameter `id`: expected u64");
+}
+
+#[tokio::test]
+async fn routes_of_the_same_shape_are_picked_by_constraint() {
+    let client = TestClient::new();
+    assert_eq!(client.get("/items/7").await.json::<Value>(), json!({ "id": 7 }));
+    assert_eq!(client.get("/items/blue-chair").await.json::<Value>(), json!({ "slug": "blue-chair" }));
+}
+
+#[tokio::test]
+async fn values_no_route_accepts_are_rejected() {
+    let client = TestClient::new();
+    let response = client.get("/items/Blue_Chair").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(response.json::<Value>()["message"].as_str().unwrap().starts_with("Invalid path parameter"));
+}
+
+#[tokio::test]
+async fn other_methods_reach_their_own_route() {
+    let client = TestClient::new();
+    let response = client.delete("/items/Blue_Chair").await;
+    assert_eq!(response.json::<Value>(), json!({ "deleted": "Blue_Chair" }));
+    assert_eq!(client.post("/items/7").await.status(), StatusCode::METHOD_NOT_ALLOWED);
+}
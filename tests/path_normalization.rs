@@ -0,0 +1,68 @@
+use ferrox::normalize::{PathNormalization, TrailingSlash};
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/users")]
+fn list_users() -> Value {
+    json!({ "users": ["alice", "bob"] })
+}
+
+#[http_method(GET, "/users/{id}/Posts")]
+fn user_posts(id: String) -> Value {
+    json!({ "user": id })
+}
+
+#[http_method(GET, "/docs/")]
+fn docs() -> Value {
+    json!({ "docs": true })
+}
+
+fn client(config: PathNormalization) -> TestClient {
+    TestClient::from_server(Server::new().path_normalization(config))
+}
+
+#[tokio::test]
+async fn paths_are_matched_exactly_by_default() {
+    let client = TestClient::new();
+    assert_eq!(client.get("/users").await.status(), StatusCode::OK);
+    assert_eq!(client.get("/users/").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(client.get("//users").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn trailing_slashes_are_redirected_to_the_route() {
+    let client = client(PathNormalization::new());
+    let response = client.get("/users/?page=2").await;
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(response.header("location"), Some("/users?page=2"));
+
+    let response = client.get("/docs").await;
+    assert_eq!(response.header("location"), Some("/docs/"));
+    assert_eq!(client.get("/users").await.json::<Value>(), json!({ "users": ["alice", "bob"] }));
+}
+
+#[tokio::test]
+async fn rewritten_paths_are_served_in_place() {
+    let client = client(PathNormalization::new().trailing_slash(TrailingSlash::Rewrite));
+    assert_eq!(client.get("/users/").await.json::<Value>(), json!({ "users": ["alice", "bob"] }));
+    assert_eq!(client.get("//users///7/Posts").await.json::<Value>(), json!({ "user": "7" }));
+}
+
+#[tokio::test]
+async fn strict_trailing_slashes_still_merge_slashes() {
+    let client = client(PathNormalization::new().trailing_slash(TrailingSlash::Strict));
+    assert_eq!(client.get("/users/").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(client.get("/users//7/Posts").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn case_insensitive_matching_keeps_parameter_values() {
+    let client = client(
+        PathNormalization::new()
+            .trailing_slash(TrailingSlash::Rewrite)
+            .case_insensitive(true),
+    );
+    assert_eq!(client.get("/USERS/Alice/posts/").await.json::<Value>(), json!({ "user": "Alice" }));
+    assert_eq!(client.get("/nothing").await.status(), StatusCode::NOT_FOUND);
+}
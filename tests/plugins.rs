@@ -0,0 +1,89 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use axum::extract::Request;
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+use ferrox::plugin::Plugin;
+use ferrox::test::TestClient;
+use ferrox::{http_method, RequestContext, RouteHandler, Server, State, StatusCode};
+use serde_json::{json, Value};
+
+#[derive(Clone)]
+struct Greeting(&'static str);
+
+struct Greeter {
+    greeting: &'static str,
+    started: Arc<AtomicUsize>,
+}
+
+impl Plugin for Greeter {
+    fn name(&self) -> &str {
+        "greeter"
+    }
+
+    fn install(self, server: Server) -> Server {
+        let started = self.started;
+        server
+            .with_state(Greeting(self.greeting))
+            .route(
+                "GET",
+                "/plugin/greet/:name",
+                RouteHandler::from_sync(|ctx: RequestContext| {
+                    let greeting = ctx.state::<Greeting>().map(|greeting| greeting.0).unwrap_or_default();
+                    json!({ "message": format!("{}, {}", greeting, ctx.path["name"].as_str().unwrap_or_default()) })
+                }),
+            )
+            .layer(axum::middleware::from_fn(tag))
+            .on_startup(move || async move {
+                started.fetch_add(1, Ordering::SeqCst);
+                Ok::<(), String>(())
+            })
+    }
+}
+
+async fn tag(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    response.headers_mut().insert("x-plugin", HeaderValue::from_static("greeter"));
+    response
+}
+
+#[http_method(GET, "/plugin/greeting")]
+fn greeting(greeting: State<Greeting>) -> Value {
+    json!({ "greeting": greeting.0.0 })
+}
+
+fn greeter(started: &Arc<AtomicUsize>) -> Greeter {
+    Greeter {
+        greeting: "Hello",
+        started: started.clone(),
+    }
+}
+
+#[tokio::test]
+async fn plugins_add_routes_state_and_middleware() {
+    let started = Arc::new(AtomicUsize::new(0));
+    let server = Server::new().plugin(greeter(&started));
+    assert_eq!(server.plugins(), ["greeter".to_string()]);
+    let client = TestClient::from_server(server);
+
+    let response = client.get("/plugin/greet/ada").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("x-plugin"), Some("greeter"));
+    assert_eq!(response.json::<Value>(), json!({ "message": "Hello, ada" }));
+
+    let response = client.get("/plugin/greeting").await;
+    assert_eq!(response.json::<Value>(), json!({ "greeting": "Hello" }));
+}
+
+#[tokio::test]
+async fn plugins_are_installed_once_and_run_their_startup_tasks() {
+    let started = Arc::new(AtomicUsize::new(0));
+    let server = Server::new().plugin(greeter(&started)).plugin(greeter(&started));
+    assert_eq!(server.plugins().len(), 1);
+
+    let handle = server.start_in_background("127.0.0.1:0").await.unwrap();
+    assert_eq!(started.load(Ordering::SeqCst), 1);
+    handle.shutdown().await;
+}
@@ -0,0 +1,90 @@
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use ferrox::etag::{self, ResourceVersion};
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, HandlerResponse, RequestContext, Server, StatusCode};
+use serde_json::{json, Value};
+
+// Revision and title of the one document
+static DOCUMENT: Mutex<(u64, String)> = Mutex::new((1, String::new()));
+
+#[http_method(GET, "/versioned/document")]
+fn show() -> HandlerResponse {
+    let (revision, title) = DOCUMENT.lock().unwrap().clone();
+    HandlerResponse::new(StatusCode::OK, json!({ "title": title })).with_version(&ResourceVersion::tag(revision))
+}
+
+#[http_method(PUT, "/versioned/document", if_match = true)]
+fn update(ctx: &RequestContext, body: Value) -> Result<HandlerResponse, FerroxError> {
+    let mut document = DOCUMENT.lock().unwrap();
+    etag::check_precondition(ctx, &ResourceVersion::tag(document.0))?;
+    *document = (document.0 + 1, body["title"].as_str().unwrap_or_default().to_string());
+    Ok(HandlerResponse::new(StatusCode::OK, json!({ "title": document.1 }))
+        .with_version(&ResourceVersion::tag(document.0)))
+}
+
+// Mon, 05 Oct 2026 10:00:00 GMT
+fn edited() -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(1_791_194_400)
+}
+
+#[http_method(PATCH, "/versioned/note")]
+fn edit_note(ctx: &RequestContext) -> Result<Value, FerroxError> {
+    etag::check_precondition(ctx, &ResourceVersion::modified_at(edited()))?;
+    Ok(json!({ "saved": true }))
+}
+
+#[tokio::test]
+async fn writes_must_name_the_current_version() {
+    let client = TestClient::from_server(Server::new());
+    let response = client.get("/versioned/document").await;
+    let etag = response.header("etag").unwrap().to_string();
+
+    // Without a precondition the write is refused before the handler runs
+    let response = client.put("/versioned/document").json(&json!({ "title": "draft" })).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+
+    let response = client
+        .put("/versioned/document")
+        .header("if-match", &etag)
+        .json(&json!({ "title": "first" }))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let current = response.header("etag").unwrap().to_string();
+    assert_ne!(current, etag);
+
+    // A second writer holding the old version loses instead of overwriting
+    let response = client
+        .put("/versioned/document")
+        .header("if-match", &etag)
+        .json(&json!({ "title": "second" }))
+        .await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    assert_eq!(client.get("/versioned/document").await.json::<Value>()["title"], "first");
+
+    // Weak tags never match, `*` matches any version
+    let weak = format!("W/{}", current);
+    let response = client.put("/versioned/document").header("if-match", &weak).json(&json!({})).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let response = client
+        .put("/versioned/document")
+        .header("if-match", "*")
+        .json(&json!({ "title": "third" }))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn if_unmodified_since_is_checked_against_the_modification_date() {
+    let client = TestClient::from_server(Server::new());
+    let before = httpdate::fmt_http_date(edited() - Duration::from_secs(60));
+    let after = httpdate::fmt_http_date(edited());
+
+    let response = client.patch("/versioned/note").header("if-unmodified-since", &before).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let response = client.patch("/versioned/note").header("if-unmodified-since", &after).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    // Unconditional requests go through on routes without `if_match`
+    assert_eq!(client.patch("/versioned/note").await.status(), StatusCode::OK);
+}
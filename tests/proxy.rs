@@ -0,0 +1,106 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use ferrox::axum::body::Bytes;
+use ferrox::axum::http::{HeaderMap, StatusCode as UpstreamStatus, Uri};
+use ferrox::axum::routing::{any, get};
+use ferrox::proxy::Proxy;
+use ferrox::test::TestClient;
+use ferrox::{Server, StatusCode};
+use serde_json::{json, Value};
+
+// An upstream echoing what it received, with `/flaky` failing until its third call
+async fn upstream() -> SocketAddr {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let app = ferrox::axum::Router::new()
+        .route(
+            "/flaky",
+            get(move || {
+                let calls = calls.clone();
+                async move {
+                    match calls.fetch_add(1, Ordering::SeqCst) {
+                        0 | 1 => (UpstreamStatus::SERVICE_UNAVAILABLE, "busy".to_string()),
+                        _ => (UpstreamStatus::OK, "ready".to_string()),
+                    }
+                }
+            }),
+        )
+        .fallback(any(|uri: Uri, headers: HeaderMap, body: Bytes| async move {
+            let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+            ferrox::axum::Json(json!({
+                "uri": uri.to_string(),
+                "host": header("host"),
+                "forwarded_for": header("x-forwarded-for"),
+                "gateway": header("x-gateway"),
+                "cookie": header("cookie"),
+                "body": String::from_utf8_lossy(&body),
+            }))
+        }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { ferrox::axum::serve(listener, app).await.unwrap() });
+    addr
+}
+
+#[tokio::test]
+async fn requests_are_forwarded_below_the_prefix() {
+    let addr = upstream().await;
+    let client = TestClient::from_server(Server::new().proxy("/legacy/*path", &format!("http://{}/api", addr)));
+    let response = client
+        .post("/legacy/users/7?page=2")
+        .header("host", "gateway.example.com")
+        .body("hello upstream")
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let echoed = response.json::<Value>();
+    assert_eq!(echoed["uri"], "/api/users/7?page=2");
+    assert_eq!(echoed["host"], addr.to_string());
+    assert_eq!(echoed["forwarded_for"], "127.0.0.1");
+    assert_eq!(echoed["body"], "hello upstream");
+}
+
+#[tokio::test]
+async fn headers_can_be_rewritten() {
+    let addr = upstream().await;
+    let proxy = Proxy::new("/legacy/*path", &format!("http://{}", addr))
+        .set_header("x-gateway", "ferrox")
+        .remove_header("cookie")
+        .preserve_host(true);
+    let client = TestClient::from_server(Server::new().reverse_proxy(proxy));
+    let response = client
+        .get("/legacy/profile")
+        .header("host", "gateway.example.com")
+        .header("cookie", "session=secret")
+        .await;
+    let echoed = response.json::<Value>();
+    assert_eq!(echoed["uri"], "/profile");
+    assert_eq!(echoed["host"], "gateway.example.com");
+    assert_eq!(echoed["gateway"], "ferrox");
+    assert_eq!(echoed["cookie"], Value::Null);
+}
+
+#[tokio::test]
+async fn failed_requests_without_a_body_are_retried() {
+    let addr = upstream().await;
+    let proxy = |retries| Proxy::new("/svc/*path", &format!("http://{}", addr)).retries(retries);
+
+    let client = TestClient::from_server(Server::new().reverse_proxy(proxy(0)));
+    assert_eq!(client.get("/svc/flaky").await.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let client = TestClient::from_server(Server::new().reverse_proxy(proxy(2)));
+    let response = client.get("/svc/flaky").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), "ready");
+}
+
+#[tokio::test]
+async fn an_unreachable_upstream_answers_502() {
+    let addr = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap()
+    };
+    let client = TestClient::from_server(Server::new().proxy("/gone/*path", &format!("http://{}", addr)));
+    let response = client.get("/gone/anything").await;
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(response.json::<Value>()["message"], "Upstream unavailable");
+}
@@ -0,0 +1,144 @@
+use ferrox::openapi::{spec, OpenApiConfig};
+use ferrox::query::{Direction, FilterOp, QuerySpec};
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/query/users", filter = ["status", "age"], sort = ["created_at", "name"], fields = ["id", "name"])]
+fn list_users(spec: QuerySpec) -> Value {
+    json!(spec)
+}
+
+#[http_method(GET, "/query/projects", fields = ["id", "name"])]
+fn list_projects(spec: QuerySpec) -> Value {
+    let projects = json!([
+        { "id": 1, "name": "ferrox", "owner": "ops" },
+        { "id": 2, "name": "docs", "owner": "web" },
+    ]);
+    spec.select(projects)
+}
+
+#[http_method(GET, "/query/open")]
+fn open(spec: QuerySpec) -> Value {
+    json!({ "filters": spec.filters.len() })
+}
+
+#[tokio::test]
+async fn the_query_string_becomes_a_query_spec() {
+    let client = TestClient::from_server(Server::new());
+    let response = client
+        .get("/query/users?filter[status]=active&filter[age][gte]=18&sort=-created_at,name&fields=id,name&page=2")
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.json::<Value>(),
+        json!({
+            "filters": [
+                { "field": "status", "op": "eq", "value": "active" },
+                { "field": "age", "op": "gte", "value": "18" },
+            ],
+            "sort": [
+                { "field": "created_at", "direction": "desc" },
+                { "field": "name", "direction": "asc" },
+            ],
+            "fields": ["id", "name"],
+        })
+    );
+}
+
+#[tokio::test]
+async fn fields_outside_the_allowlist_are_refused() {
+    let client = TestClient::from_server(Server::new());
+    for query in ["filter[password]=x", "sort=-email", "fields=id,password", "filter[age][like]=1"] {
+        let response = client.get(&format!("/query/users?{}", query)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+    }
+    let response = client.get("/query/users?sort=email").await;
+    assert!(response.text().contains("expected one of `created_at`, `name`"));
+
+    // A route without allowlists takes no filters at all
+    assert_eq!(client.get("/query/open").await.json::<Value>(), json!({ "filters": 0 }));
+    assert_eq!(client.get("/query/open?filter[id]=1").await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn sparse_fieldsets_narrow_the_response() {
+    let client = TestClient::from_server(Server::new());
+    let response = client.get("/query/projects?fields=name").await;
+    assert_eq!(response.json::<Value>()["data"], json!([{ "name": "ferrox" }, { "name": "docs" }]));
+    let response = client.get("/query/projects").await;
+    assert_eq!(response.json::<Value>()["data"][0]["owner"], "ops");
+}
+
+#[test]
+fn filters_expose_their_values() {
+    let spec = QuerySpec {
+        filters: vec![ferrox::query::Filter {
+            field: "status".to_string(),
+            op: FilterOp::In,
+            value: "active, invited".to_string(),
+        }],
+        ..QuerySpec::default()
+    };
+    assert_eq!(spec.filters[0].values(), ["active", "invited"]);
+    assert_eq!(spec.filter("status"), None);
+    assert_eq!(Direction::Desc.to_string(), "DESC");
+}
+
+#[test]
+fn allowed_fields_are_documented() {
+    let document = spec(&OpenApiConfig::new("Query", "1.0"));
+    let names: Vec<String> = document["paths"]["/query/users"]["get"]["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|param| param["name"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(names, ["filter[status]", "filter[age]", "sort", "fields"]);
+}
+
+#[http_method(GET, "/query/customers/:id", fields = ["id", "name", "address.city"])]
+fn customer(id: u64) -> Value {
+    json!({ "id": id, "name": "Ada", "email": "ada@example.com", "address": { "city": "London", "street": "Baker St" } })
+}
+
+#[http_method(GET, "/query/customers", fields = ["id", "address"])]
+fn customers() -> Value {
+    json!([
+        { "id": 1, "name": "Ada", "address": { "city": "London" } },
+        { "id": 2, "name": "Grace", "address": { "city": "New York" } },
+    ])
+}
+
+#[tokio::test]
+async fn sparse_fieldsets_narrow_results_without_a_query_spec() {
+    let client = TestClient::from_server(Server::new().sparse_fieldsets(true));
+    let response = client.get("/query/customers/7?fields=name,address.city").await;
+    assert_eq!(response.json::<Value>(), json!({ "name": "Ada", "address": { "city": "London" } }));
+
+    let response = client.get("/query/customers?fields=address.city").await;
+    assert_eq!(
+        response.json::<Value>()["data"],
+        json!([{ "address": { "city": "London" } }, { "address": { "city": "New York" } }])
+    );
+
+    // Without a fieldset the result is sent whole
+    let response = client.get("/query/customers/7").await;
+    assert_eq!(response.json::<Value>()["email"], "ada@example.com");
+}
+
+#[tokio::test]
+async fn sparse_fieldsets_refuse_fields_outside_the_allowlist() {
+    let client = TestClient::from_server(Server::new().sparse_fieldsets(true));
+    for query in ["fields=email", "fields=address", "fields=address.street"] {
+        let response = client.get(&format!("/query/customers/7?{}", query)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+    }
+    let response = client.get("/query/customers/7?fields=email").await;
+    assert!(response.text().contains("expected one of `id`, `name`, `address.city`"));
+
+    // Without the server option, the handler decides what to send
+    let client = TestClient::from_server(Server::new());
+    let response = client.get("/query/customers/7?fields=email").await;
+    assert_eq!(response.json::<Value>()["name"], "Ada");
+}
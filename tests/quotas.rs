@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use ferrox::auth::{AuthFuture, Authenticator};
+use ferrox::quota::{Quotas, Usage};
+use ferrox::ratelimit::RateLimit;
+use ferrox::rbac::Principal;
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, Json, RequestContext, Server, State, StatusCode};
+use serde_json::{json, Value};
+
+// Takes the principal from headers, as a real authenticator would from a token
+struct HeaderAuth;
+
+impl Authenticator for HeaderAuth {
+    fn authenticate<'a>(&'a self, request: &'a mut axum::http::request::Parts) -> AuthFuture<'a> {
+        Box::pin(async move {
+            let header = |name: &str| request.headers.get(name).and_then(|value| value.to_str().ok());
+            let user = header("x-user").ok_or_else(|| FerroxError::Unauthorized("No user".to_string()))?;
+            let roles: Vec<String> = header("x-roles").unwrap_or("").split(',').map(str::to_string).collect();
+            request.extensions.insert(Principal::new(user, roles));
+            Ok(())
+        })
+    }
+}
+
+#[http_method(POST, "/quota/reports", auth = "header", cost = 4)]
+fn report() -> Value {
+    json!({ "report": true })
+}
+
+#[http_method(GET, "/quota/search", cost = 1)]
+fn search() -> Value {
+    json!([])
+}
+
+#[http_method(GET, "/quota/broken", auth = "header", cost = 4)]
+fn broken() -> Result<Value, FerroxError> {
+    Err(FerroxError::Internal("database is down".to_string()))
+}
+
+#[http_method(GET, "/quota/usage", auth = "header")]
+fn usage(ctx: &RequestContext, quotas: State<Quotas>) -> Json<Usage> {
+    Json(quotas.usage_of(ctx))
+}
+
+fn quotas() -> Quotas {
+    Quotas::new(RateLimit::new(10, Duration::from_secs(3600)))
+        .role("pro", RateLimit::new(100, Duration::from_secs(3600)))
+        .principal("vip", RateLimit::new(1000, Duration::from_secs(3600)))
+}
+
+fn client(quotas: Quotas) -> TestClient {
+    TestClient::from_server(Server::new().authenticator("header", HeaderAuth).quotas(quotas))
+}
+
+#[tokio::test]
+async fn requests_are_charged_their_cost() {
+    let quotas = quotas();
+    let client = client(quotas.clone());
+    for remaining in ["6", "2"] {
+        let response = client.post("/quota/reports").header("x-user", "ada").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.header("x-quota-limit"), Some("10"));
+        assert_eq!(response.header("x-quota-remaining"), Some(remaining));
+        assert!(response.header("x-quota-reset").is_some());
+    }
+
+    let response = client.post("/quota/reports").header("x-user", "ada").await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.header("retry-after").is_some());
+    assert_eq!(response.header("x-quota-remaining"), Some("2"));
+    assert_eq!(quotas.usage("principal:ada").unwrap().used, 8);
+
+    // Others have budgets of their own
+    let response = client.post("/quota/reports").header("x-user", "grace").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        quotas.usage_all().into_iter().map(|(key, usage)| (key, usage.used)).collect::<Vec<_>>(),
+        [("principal:ada".to_string(), 8), ("principal:grace".to_string(), 4)]
+    );
+
+    assert!(quotas.reset("principal:ada"));
+    let response = client.post("/quota/reports").header("x-user", "ada").await;
+    assert_eq!(response.header("x-quota-remaining"), Some("6"));
+}
+
+#[tokio::test]
+async fn budgets_follow_roles_and_principals() {
+    let client = client(quotas());
+    let pro = client.post("/quota/reports").header("x-user", "lin").header("x-roles", "basic,pro").await;
+    assert_eq!(pro.header("x-quota-limit"), Some("100"));
+    let vip = client.post("/quota/reports").header("x-user", "vip").header("x-roles", "pro").await;
+    assert_eq!(vip.header("x-quota-limit"), Some("1000"));
+
+    let usage = client.get("/quota/usage").header("x-user", "lin").header("x-roles", "pro").await;
+    let usage = &usage.json::<Value>();
+    assert_eq!((&usage["used"], &usage["limit"], &usage["remaining"]), (&json!(4), &json!(100), &json!(96)));
+}
+
+#[tokio::test]
+async fn anonymous_clients_are_charged_by_address() {
+    let quotas = quotas();
+    let client = client(quotas.clone());
+    assert_eq!(client.get("/quota/search").await.header("x-quota-remaining"), Some("9"));
+    let (key, usage) = quotas.usage_all().remove(0);
+    assert!(key.starts_with("ip:"));
+    assert_eq!(usage.used, 1);
+}
+
+#[tokio::test]
+async fn rejected_and_failed_requests_are_free() {
+    let quotas = quotas();
+    let client = client(quotas.clone());
+    assert_eq!(client.post("/quota/reports").await.status(), StatusCode::UNAUTHORIZED);
+    assert!(quotas.usage_all().is_empty());
+
+    let response = client.get("/quota/broken").header("x-user", "ada").await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(response.header("x-quota-remaining"), Some("10"));
+    assert_eq!(quotas.usage("principal:ada").unwrap().used, 0);
+}
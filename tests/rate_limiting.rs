@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+use ferrox::auth::api_key::{ApiKeyAuth, InMemoryKeyStore};
+use ferrox::axum::http::HeaderName;
+use ferrox::ratelimit::{Algorithm, Decision, KeyBy, RateLimit, RateLimiter};
+use ferrox::test::{TestClient, TestResponse};
+use ferrox::{http_method, Server, StatusCode};
+
+#[http_method(GET, "/limited/ping")]
+fn ping() -> &'static str {
+    "pong"
+}
+
+// Decisions are computed in floating point
+fn about(duration: Duration, secs: f64) -> bool {
+    (duration.as_secs_f64() - secs).abs() < 1e-6
+}
+
+fn limits(response: &TestResponse) -> (Option<&str>, Option<&str>, Option<&str>) {
+    (
+        response.header("x-ratelimit-limit"),
+        response.header("x-ratelimit-remaining"),
+        response.header("x-ratelimit-reset"),
+    )
+}
+
+#[test]
+fn token_bucket_decisions() {
+    let rate = RateLimit::per_minute(3);
+    // One token left after a request: two more now, the bucket is full in 40s
+    let decision = Decision::token_bucket(true, 1.0, rate);
+    assert_eq!(decision.remaining, 1);
+    assert!(about(decision.reset, 40.0));
+    assert_eq!(decision.retry_after, Duration::ZERO);
+
+    // Half a token left: the next one is 10s away
+    let decision = Decision::token_bucket(false, 0.5, rate);
+    assert!(!decision.allowed);
+    assert_eq!(decision.remaining, 0);
+    assert!(about(decision.retry_after, 10.0));
+    assert!(about(decision.reset, 50.0));
+}
+
+#[test]
+fn sliding_window_decisions() {
+    let rate = RateLimit::per_minute(10);
+    // 15s into the window, 3/4 of the previous window's 8 requests still count
+    let decision = Decision::sliding_window(true, 3, 8, Duration::from_secs(15), rate);
+    assert_eq!(decision.remaining, 1);
+    assert!(about(decision.reset, 105.0));
+
+    // Over the limit: allowed again once enough of the previous window decayed
+    let decision = Decision::sliding_window(false, 4, 8, Duration::from_secs(15), rate);
+    assert!(!decision.allowed);
+    assert_eq!(decision.remaining, 0);
+    assert!(about(decision.retry_after, 7.5));
+
+    // Nothing in the previous window: only the end of the current one helps
+    let decision = Decision::sliding_window(false, 10, 0, Duration::from_secs(15), rate);
+    assert!(about(decision.retry_after, 45.0));
+}
+
+#[tokio::test]
+async fn token_bucket_reports_the_limit_on_every_response() {
+    let client = TestClient::from_server(Server::new().rate_limit(RateLimiter::new(RateLimit::per_minute(3))));
+
+    for remaining in ["2", "1", "0"] {
+        let response = client.get("/limited/ping").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let (limit, left, _) = limits(&response);
+        assert_eq!((limit, left), (Some("3"), Some(remaining)));
+    }
+
+    let refused = client.get("/limited/ping").await;
+    assert_eq!(refused.status(), StatusCode::TOO_MANY_REQUESTS);
+    // A token comes back every 20s, the full bucket in a minute
+    assert_eq!(refused.header("retry-after"), Some("20"));
+    assert_eq!(limits(&refused), (Some("3"), Some("0"), Some("60")));
+}
+
+#[tokio::test]
+async fn sliding_window_refuses_until_the_window_ends() {
+    let limiter = RateLimiter::new(RateLimit::per_minute(2)).algorithm(Algorithm::SlidingWindow);
+    let client = TestClient::from_server(Server::new().rate_limit(limiter));
+
+    assert_eq!(client.get("/limited/ping").await.header("x-ratelimit-remaining"), Some("1"));
+    assert_eq!(client.get("/limited/ping").await.header("x-ratelimit-remaining"), Some("0"));
+    let refused = client.get("/limited/ping").await;
+    assert_eq!(refused.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(refused.header("retry-after"), Some("60"));
+    assert_eq!(limits(&refused), (Some("2"), Some("0"), Some("120")));
+}
+
+#[tokio::test]
+async fn headers_can_be_turned_off() {
+    let limiter = RateLimiter::new(RateLimit::per_minute(1)).headers(None);
+    let client = TestClient::from_server(Server::new().rate_limit(limiter));
+
+    assert_eq!(limits(&client.get("/limited/ping").await), (None, None, None));
+    let refused = client.get("/limited/ping").await;
+    assert_eq!(refused.status(), StatusCode::TOO_MANY_REQUESTS);
+    // Clients still need to know when to come back
+    assert!(refused.header("retry-after").is_some());
+}
+
+#[tokio::test]
+async fn made_up_api_keys_count_for_the_client_address() {
+    let keys = InMemoryKeyStore::new().with_key("partner", "partner-key");
+    let limiter = RateLimiter::new(RateLimit::per_minute(2)).key_by(KeyBy::ApiKey);
+    let client = TestClient::from_server(Server::new().api_keys(ApiKeyAuth::new(keys)).rate_limit(limiter));
+
+    let with_key = |key: &str| client.get("/limited/ping").header("x-api-key", key);
+    assert_eq!(with_key("random-1").await.status(), StatusCode::OK);
+    assert_eq!(client.get("/limited/ping").await.status(), StatusCode::OK);
+    // A fresh random key does not buy a fresh limit
+    assert_eq!(with_key("random-2").await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // The holder of a valid key has a limit of their own
+    for _ in 0..2 {
+        assert_eq!(with_key("partner-key").await.status(), StatusCode::OK);
+    }
+    assert_eq!(with_key("partner-key").await.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn header_keys_are_only_trusted_from_proxies() {
+    let consumer = HeaderName::from_static("x-consumer-id");
+    let limiter = || RateLimiter::new(RateLimit::per_minute(1)).key_by(KeyBy::Header(consumer.clone()));
+    let as_consumer = |client: &TestClient, id: &str| client.get("/limited/ping").header("x-consumer-id", id);
+
+    // Sent straight by the client, the header is ignored
+    let direct = TestClient::from_server(Server::new().rate_limit(limiter()));
+    assert_eq!(as_consumer(&direct, "a").await.status(), StatusCode::OK);
+    assert_eq!(as_consumer(&direct, "b").await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // Set by a trusted gateway, it tells its consumers apart
+    let proxied = TestClient::from_server(Server::new().trusted_proxies(["127.0.0.1/32"]).rate_limit(limiter()));
+    assert_eq!(as_consumer(&proxied, "a").await.status(), StatusCode::OK);
+    assert_eq!(as_consumer(&proxied, "b").await.status(), StatusCode::OK);
+    assert_eq!(as_consumer(&proxied, "a").await.status(), StatusCode::TOO_MANY_REQUESTS);
+}
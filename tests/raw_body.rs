@@ -0,0 +1,54 @@
+use ferrox::axum::body::Bytes;
+use ferrox::openapi::{spec, OpenApiConfig};
+use ferrox::test::TestClient;
+use ferrox::{http_method, RequestContext, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(POST, "/raw/uploads/:name", max_body_size = "16")]
+fn upload(name: String, payload: Bytes) -> Value {
+    json!({ "name": name, "size": payload.len(), "first": payload.first() })
+}
+
+#[http_method(POST, "/raw/echo")]
+fn echo(ctx: &RequestContext, body: Bytes) -> Value {
+    json!({ "content_type": ctx.header("content-type"), "body": String::from_utf8_lossy(&body) })
+}
+
+#[http_method(POST, "/raw/parsed")]
+fn parsed(ctx: &RequestContext, body: Value) -> Value {
+    json!({ "parsed": body, "raw": String::from_utf8_lossy(ctx.raw_body()) })
+}
+
+#[tokio::test]
+async fn bytes_parameters_receive_the_body_as_sent() {
+    let client = TestClient::from_server(Server::new());
+    let response = client.post("/raw/uploads/logo.png").body(vec![0x89u8, b'P', b'N', b'G', 0, 0xff]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "name": "logo.png", "size": 6, "first": 0x89 }));
+
+    // The body is not parsed, so a malformed one still reaches the handler
+    let response = client.post("/raw/echo").header("content-type", "application/json").body("{not json").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "content_type": "application/json", "body": "{not json" }));
+}
+
+#[tokio::test]
+async fn raw_bodies_keep_the_size_limit() {
+    let client = TestClient::from_server(Server::new());
+    let response = client.post("/raw/uploads/big.bin").body(vec![0u8; 17]).await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn parsed_bodies_are_also_available_raw() {
+    let client = TestClient::from_server(Server::new());
+    let response = client.post("/raw/parsed").json(&json!({ "a": 1 })).await;
+    assert_eq!(response.json::<Value>(), json!({ "parsed": { "a": 1 }, "raw": r#"{"a":1}"# }));
+}
+
+#[test]
+fn raw_bodies_are_documented_as_binary() {
+    let document = spec(&OpenApiConfig::new("Raw", "1.0"));
+    let body = &document["paths"]["/raw/echo"]["post"]["requestBody"];
+    assert_eq!(body["content"]["application/octet-stream"]["schema"], json!({ "type": "string", "format": "binary" }));
+}
@@ -0,0 +1,114 @@
+use ferrox::auth::{AuthFuture, Authenticator};
+use ferrox::rbac::{Principal, Rbac};
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, RequestContext, Server, State, StatusCode};
+use serde_json::{json, Value};
+
+// Takes the principal from headers, as a real authenticator would from a token
+struct HeaderAuth;
+
+impl Authenticator for HeaderAuth {
+    fn authenticate<'a>(&'a self, request: &'a mut axum::http::request::Parts) -> AuthFuture<'a> {
+        Box::pin(async move {
+            let header = |name: &str| request.headers.get(name).and_then(|value| value.to_str().ok());
+            let user = header("x-user").ok_or_else(|| FerroxError::Unauthorized("No user".to_string()))?;
+            let roles: Vec<String> = header("x-roles").unwrap_or("").split(',').map(str::to_string).collect();
+            let principal = Principal::new(user, roles);
+            request.extensions.insert(principal);
+            Ok(())
+        })
+    }
+}
+
+#[http_method(GET, "/rbac/users", auth = "header", permission = "users:read")]
+fn list_users() -> Value {
+    json!([{ "id": 1 }])
+}
+
+#[http_method(PUT, "/rbac/users/:id", auth = "header", permission = "users:write")]
+fn update_user(id: u64) -> Value {
+    json!({ "id": id })
+}
+
+#[http_method(DELETE, "/rbac/reports", auth = "header")]
+fn delete_reports(ctx: &RequestContext, policy: State<Rbac>) -> Result<Value, FerroxError> {
+    policy.require(ctx, "reports:delete")?;
+    Ok(json!({ "deleted": true }))
+}
+
+#[http_method(GET, "/rbac/open", permission = "open:read")]
+fn open() -> Value {
+    json!({})
+}
+
+fn policy() -> Rbac {
+    Rbac::new()
+        .role("viewer", ["users:read"])
+        .role("editor", ["users:write"])
+        .inherits("editor", "viewer")
+        .role("reporter", ["reports:*"])
+        .role("admin", ["*"])
+}
+
+fn client() -> TestClient {
+    TestClient::from_server(Server::new().authenticator("header", HeaderAuth).rbac(policy()))
+}
+
+#[tokio::test]
+async fn routes_require_their_permission() {
+    let client = client();
+    let viewer = |request: ferrox::test::TestRequest| request.header("x-user", "vic").header("x-roles", "viewer");
+    assert_eq!(viewer(client.get("/rbac/users")).await.status(), StatusCode::OK);
+
+    let response = viewer(client.put("/rbac/users/3")).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.json::<Value>(),
+        json!({
+            "success": false,
+            "data": { "permission": "users:write", "roles": ["viewer"] },
+            "message": "Permission denied",
+        })
+    );
+
+    let response = client.put("/rbac/users/3").header("x-user", "ed").header("x-roles", "editor").await;
+    assert_eq!(response.json::<Value>(), json!({ "id": 3 }));
+    let response = client.get("/rbac/users").header("x-user", "ed").header("x-roles", "editor").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = client.put("/rbac/users/3").header("x-user", "root").header("x-roles", "admin").await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn requests_without_a_principal_are_unauthorized() {
+    let response = client().get("/rbac/open").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn handlers_can_check_permissions_themselves() {
+    let client = client();
+    let response = client.delete("/rbac/reports").header("x-user", "vic").header("x-roles", "viewer").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.json::<Value>()["data"]["permission"], "reports:delete");
+
+    let response = client.delete("/rbac/reports").header("x-user", "rita").header("x-roles", "reporter").await;
+    assert_eq!(response.json::<Value>(), json!({ "deleted": true }));
+}
+
+#[tokio::test]
+async fn routes_fail_closed_without_a_policy() {
+    let client = TestClient::from_server(Server::new().authenticator("header", HeaderAuth));
+    let response = client.get("/rbac/users").header("x-user", "root").header("x-roles", "admin").await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[test]
+fn inherited_and_wildcard_grants() {
+    let policy = policy();
+    assert!(policy.allows(&["editor"], "users:read"));
+    assert!(policy.allows(&["reporter"], "reports:export"));
+    assert!(!policy.allows(&["reporter"], "users:read"));
+    assert!(!policy.allows(&["unknown"], "users:read"));
+    assert!(policy.allows(&["admin"], "anything"));
+}
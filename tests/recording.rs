@@ -0,0 +1,87 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ferrox::recorder::{Exchange, Recorder, Replay};
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, StatusCode};
+use serde_json::{json, Value};
+
+static RENAMED: AtomicBool = AtomicBool::new(false);
+
+#[http_method(POST, "/recording/login")]
+fn login(body: Value) -> Value {
+    json!({ "user": body["user"], "token": "secret-token", "issued_at": now_nanos() })
+}
+
+#[http_method(GET, "/recording/items/:id")]
+fn get_item(id: u64) -> Value {
+    let name = if RENAMED.load(Ordering::SeqCst) { "renamed" } else { "widget" };
+    json!({ "id": id, "name": name })
+}
+
+#[http_method(GET, "/recording/text")]
+fn text() -> ferrox::StreamingResponse {
+    ferrox::StreamingResponse::new(futures_util::stream::iter([Ok::<_, std::io::Error>("streamed")]))
+}
+
+fn now_nanos() -> u128 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos()
+}
+
+fn recording(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("ferrox-recording-{}-{}.ndjson", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn read(path: &PathBuf) -> Vec<Exchange> {
+    Replay::load(path).unwrap().exchanges().to_vec()
+}
+
+#[tokio::test]
+async fn exchanges_are_recorded_with_credentials_redacted() {
+    let path = recording("redacted");
+    let client = TestClient::from_server(Server::new().record(Recorder::new(&path).redact_field("token")));
+    let response = client
+        .post("/recording/login?remember=1")
+        .header("authorization", "Bearer abc")
+        .header("x-trace", "t-1")
+        .json(&json!({ "user": "alice", "password": "hunter2" }))
+        .await;
+    assert_eq!(response.json::<Value>()["token"], "secret-token");
+    client.get("/recording/text").await;
+
+    let exchanges = read(&path);
+    assert_eq!(exchanges.len(), 2);
+    let login = &exchanges[0];
+    assert_eq!(login.request.method, "POST");
+    assert_eq!(login.request.uri, "/recording/login?remember=1");
+    assert_eq!(login.request.headers["authorization"], "[redacted]");
+    assert_eq!(login.request.headers["x-trace"], "t-1");
+    assert_eq!(login.request.body, Some(json!({ "user": "alice", "password": "[redacted]" })));
+    assert_eq!(login.response.status, 200);
+    assert_eq!(login.response.body.as_ref().unwrap()["token"], "[redacted]");
+    // Streamed bodies are left out
+    assert_eq!(exchanges[1].response.body, None);
+}
+
+#[tokio::test]
+async fn replays_report_responses_that_changed() {
+    let path = recording("replay");
+    let client = TestClient::from_server(Server::new().record(Recorder::new(&path)));
+    client.get("/recording/items/1").await;
+    client.post("/recording/login").json(&json!({ "user": "bob" })).await;
+    assert_eq!(client.get("/recording/missing").await.status(), StatusCode::NOT_FOUND);
+
+    let replay = Replay::load(&path).unwrap().ignore_field("issued_at");
+    let fresh = TestClient::new();
+    assert_eq!(replay.run(&fresh).await, vec![]);
+
+    RENAMED.store(true, Ordering::SeqCst);
+    let mismatches = replay.run(&fresh).await;
+    RENAMED.store(false, Ordering::SeqCst);
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].index, 0);
+    assert_eq!(mismatches[0].expected.body, Some(json!({ "id": 1, "name": "widget" })));
+    assert_eq!(mismatches[0].actual.body, Some(json!({ "id": 1, "name": "renamed" })));
+}
@@ -0,0 +1,66 @@
+use ferrox::codegen::{self, ClientConfig};
+use ferrox::openapi::{spec, OpenApiConfig};
+use ferrox::test::TestClient;
+use ferrox::{http_method, routes, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/registry/catalog")]
+fn catalog() -> Value {
+    json!({ "items": 3 })
+}
+
+#[http_method(GET, "/registry/stats", registry = "admin")]
+fn stats() -> Value {
+    json!({ "requests": 42 })
+}
+
+// The same method and path in two registries is no conflict
+#[http_method(GET, "/registry/status")]
+fn public_status() -> Value {
+    json!({ "status": "ok" })
+}
+
+#[http_method(GET, "/registry/status", registry = "admin")]
+fn admin_status() -> Value {
+    json!({ "status": "ok", "workers": 4 })
+}
+
+#[tokio::test]
+async fn servers_only_serve_their_registry() {
+    let public = TestClient::new();
+    let admin = TestClient::from_server(Server::from_registry("admin"));
+
+    assert_eq!(public.get("/registry/catalog").await.json::<Value>()["items"], 3);
+    assert_eq!(public.get("/registry/stats").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(public.get("/registry/status").await.json::<Value>(), json!({ "status": "ok" }));
+
+    assert_eq!(admin.get("/registry/stats").await.json::<Value>()["requests"], 42);
+    assert_eq!(admin.get("/registry/catalog").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(admin.get("/registry/status").await.json::<Value>()["workers"], 4);
+
+    let unknown = TestClient::from_server(Server::from_registry("internal"));
+    assert_eq!(unknown.get("/registry/catalog").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn docs_and_listings_follow_the_registry() {
+    let document = spec(&OpenApiConfig::new("Admin", "1.0").registry("admin"));
+    let mut paths: Vec<&String> = document["paths"].as_object().unwrap().keys().collect();
+    paths.sort();
+    assert_eq!(paths, ["/registry/stats", "/registry/status"]);
+    assert!(spec(&OpenApiConfig::new("Public", "1.0"))["paths"].get("/registry/stats").is_none());
+
+    let served = TestClient::from_server(Server::from_registry("admin").openapi(OpenApiConfig::new("Admin", "1.0")));
+    let served = served.get("/openapi.json").await.json::<Value>();
+    assert!(served["paths"].get("/registry/catalog").is_none());
+    assert_eq!(served["paths"]["/registry/status"]["get"]["operationId"], "admin_status");
+
+    let listed = routes();
+    let stats = listed.iter().find(|route| route.handler == "stats").unwrap();
+    assert_eq!(stats.registry, Some("admin"));
+    assert_eq!(listed.iter().find(|route| route.handler == "catalog").unwrap().registry, None);
+
+    let client = codegen::typescript_client(&ClientConfig::new("AdminApi").registry("admin"));
+    assert!(client.contains("`GET /registry/stats`"));
+    assert!(!client.contains("/registry/catalog"));
+}
@@ -0,0 +1,50 @@
+use ferrox::test::TestClient;
+use ferrox::{http_method, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+struct NewItem {
+    name: String,
+}
+
+#[http_method(POST, "/bodies/echo")]
+fn echo(body: Value) -> Value {
+    json!({ "received": body })
+}
+
+#[http_method(POST, "/bodies/items")]
+fn create(body: NewItem) -> Value {
+    json!({ "name": body.name })
+}
+
+#[tokio::test]
+async fn malformed_json_answers_400_with_the_parse_error() {
+    let client = TestClient::new();
+    for path in ["/bodies/echo", "/bodies/items"] {
+        let response = client
+            .post(path)
+            .header("content-type", "application/json")
+            .body(r#"{"name": "widget""#)
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let message = response.json::<Value>()["message"].as_str().unwrap().to_string();
+        assert!(message.starts_with("Invalid JSON body: EOF while parsing an object"), "{}", message);
+    }
+
+    // Without a content type the body is still read as JSON
+    let response = client.post("/bodies/echo").body("not json").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn well_formed_bodies_reach_the_handler() {
+    let client = TestClient::new();
+    let response = client.post("/bodies/items").json(&json!({ "name": "widget" })).await;
+    assert_eq!(response.json::<Value>(), json!({ "name": "widget" }));
+
+    // An empty body is no body rather than a malformed one
+    let response = client.post("/bodies/echo").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "received": null }));
+}
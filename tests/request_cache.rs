@@ -0,0 +1,83 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use ferrox::guard::{Guard, GuardResult};
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, RequestCache, RequestContext, Server, StatusCode};
+use serde_json::{json, Value};
+
+static PLAN_LOOKUPS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone, Debug, PartialEq)]
+struct Principal(String);
+
+#[derive(Clone, Debug, PartialEq)]
+struct Plan(&'static str);
+
+// What a database lookup would compute, counted to show it happens once per request
+fn plan_of(ctx: &RequestContext) -> Plan {
+    ctx.cached(|| {
+        PLAN_LOOKUPS.fetch_add(1, Ordering::SeqCst);
+        match ctx.header("x-plan") {
+            Some("pro") => Plan("pro"),
+            _ => Plan("free"),
+        }
+    })
+}
+
+async fn authenticate(request: Request, next: Next) -> Response {
+    if let Some(user) = request.headers().get("x-user").and_then(|value| value.to_str().ok()) {
+        RequestCache::of(request.extensions()).insert(Principal(user.to_string()));
+    }
+    next.run(request).await
+}
+
+struct ProPlan;
+
+impl Guard for ProPlan {
+    async fn check(&self, ctx: &RequestContext) -> GuardResult {
+        match plan_of(ctx) {
+            Plan("pro") => GuardResult::Allow,
+            _ => GuardResult::Forbidden("Upgrade to pro".to_string()),
+        }
+    }
+}
+
+#[http_method(GET, "/cached/reports", guards = [ProPlan])]
+fn reports(ctx: &RequestContext) -> Result<Value, FerroxError> {
+    let Principal(user) = ctx
+        .cache()
+        .get::<Principal>()
+        .ok_or_else(|| FerroxError::Unauthorized("Sign in first".to_string()))?;
+    Ok(json!({ "user": user, "plan": plan_of(ctx).0 }))
+}
+
+#[tokio::test]
+async fn middleware_guards_and_handlers_share_the_request_cache() {
+    let client = TestClient::from_server(Server::new().layer(axum::middleware::from_fn(authenticate)));
+    let before = PLAN_LOOKUPS.load(Ordering::SeqCst);
+
+    let response = client.get("/cached/reports").header("x-user", "alice").header("x-plan", "pro").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "user": "alice", "plan": "pro" }));
+    // Computed by the guard and read back by the handler
+    assert_eq!(PLAN_LOOKUPS.load(Ordering::SeqCst) - before, 1);
+
+    // Nothing carries over to the next request
+    let response = client.get("/cached/reports").header("x-plan", "pro").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = client.get("/cached/reports").header("x-user", "alice").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn failed_computations_are_not_cached() {
+    let cache = RequestCache::default();
+    assert_eq!(cache.get_or_try_insert_with(|| Err::<Plan, _>("unavailable")), Err("unavailable"));
+    assert_eq!(cache.get::<Plan>(), None);
+    assert_eq!(cache.get_or_try_insert_with(|| Ok::<_, ()>(Plan("pro"))), Ok(Plan("pro")));
+    assert_eq!(cache.get_or_insert_with(|| Plan("free")), Plan("pro"));
+    assert_eq!(cache.remove::<Plan>(), Some(Plan("pro")));
+}
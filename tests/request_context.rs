@@ -0,0 +1,87 @@
+use ferrox::axum::body::Body;
+use ferrox::axum::http::Request;
+use ferrox::test::TestClient;
+use ferrox::{http_method, ApiResponse, HandlerResponse, IntoHandlerResponse, RequestContext, Server, StatusCode};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+#[http_method(GET, "/context/echo")]
+fn echo(ctx: &RequestContext) -> Value {
+    let accepts: Vec<&str> = ctx.headers.get_all("accept").iter().map(|value| value.to_str().unwrap()).collect();
+    json!({
+        "method": ctx.method.as_str(),
+        "uri": ctx.uri.to_string(),
+        "agent": ctx.header("user-agent"),
+        "missing": ctx.header("x-missing"),
+        "accepts": accepts,
+        "remote": ctx.remote_addr.map(|addr| addr.ip().to_string()),
+    })
+}
+
+#[http_method(DELETE, "/context/echo")]
+fn echo_delete(ctx: &RequestContext) -> Value {
+    json!({ "method": ctx.method.as_str() })
+}
+
+#[http_method(POST, "/context/notes")]
+fn create_note(body: Value) -> HandlerResponse {
+    ApiResponse::ok(body)
+        .into_handler_response()
+        .with_status(StatusCode::CREATED)
+        .with_header("location", "/context/notes/1")
+        .with_header("x-note", "first")
+        .with_header("x-note", "second")
+        .with_header("bad header", "skipped")
+        .with_header("x-bad-value", "line\nbreak")
+}
+
+#[http_method(GET, "/context/plain")]
+fn plain() -> HandlerResponse {
+    HandlerResponse::new(StatusCode::OK, json!({ "text": "hi" })).with_header("content-type", "application/vnd.notes+json")
+}
+
+#[tokio::test]
+async fn handlers_see_the_request_line_and_headers() {
+    let client = TestClient::new();
+    let response = client
+        .get("/context/echo?page=2")
+        .header("user-agent", "ferrox-tests")
+        .header("accept", "application/json")
+        .await;
+    assert_eq!(
+        response.json::<Value>(),
+        json!({
+            "method": "GET",
+            "uri": "/context/echo?page=2",
+            "agent": "ferrox-tests",
+            "missing": null,
+            "accepts": ["application/json"],
+            "remote": "127.0.0.1",
+        })
+    );
+    assert_eq!(client.delete("/context/echo").await.json::<Value>()["method"], "DELETE");
+}
+
+#[tokio::test]
+async fn the_peer_address_is_unknown_outside_a_server() {
+    let router = Server::new().into_router();
+    let request = Request::get("/context/echo").body(Body::empty()).unwrap();
+    let response = router.oneshot(request).await.unwrap();
+    let body = ferrox::axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(serde_json::from_slice::<Value>(&body).unwrap()["remote"], Value::Null);
+}
+
+#[tokio::test]
+async fn handlers_set_the_status_and_headers_of_their_response() {
+    let response = TestClient::new().post("/context/notes").json(&json!({ "text": "hi" })).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.header("location"), Some("/context/notes/1"));
+    // Later values replace earlier ones, and invalid headers are skipped
+    assert_eq!(response.header("x-note"), Some("second"));
+    assert!(response.headers().get("x-bad-value").is_none());
+    assert_eq!(response.json::<Value>()["data"], json!({ "text": "hi" }));
+
+    // Even the content type can be replaced
+    let response = TestClient::new().get("/context/plain").await;
+    assert_eq!(response.header("content-type"), Some("application/vnd.notes+json"));
+}
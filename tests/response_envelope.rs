@@ -0,0 +1,85 @@
+use ferrox::envelope::{NoEnvelope, ProblemDetails, ResponseEnvelope};
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/envelope/names")]
+fn names() -> Value {
+    json!(["alice", "bob"])
+}
+
+#[http_method(GET, "/envelope/user")]
+fn user() -> Value {
+    json!({ "id": 1 })
+}
+
+#[http_method(GET, "/envelope/missing")]
+fn missing() -> Result<Value, FerroxError> {
+    Err(FerroxError::NotFound("User 9 not found".to_string()))
+}
+
+struct Custom;
+
+impl ResponseEnvelope for Custom {
+    fn success(&self, data: Value) -> Value {
+        json!({ "result": data })
+    }
+
+    fn error(&self, error: &FerroxError) -> Value {
+        json!({ "error": { "code": error.status().as_u16(), "reason": error.message() } })
+    }
+}
+
+#[tokio::test]
+async fn the_default_envelope_is_unchanged() {
+    let client = TestClient::new();
+    let response = client.get("/envelope/names").await;
+    assert_eq!(response.json::<Value>(), json!({ "success": true, "data": ["alice", "bob"], "message": "" }));
+    let response = client.get("/envelope/nowhere").await;
+    assert_eq!(
+        response.json::<Value>(),
+        json!({ "success": false, "data": null, "message": "Route /envelope/nowhere not found" })
+    );
+}
+
+#[tokio::test]
+async fn a_custom_envelope_wraps_results_and_every_error() {
+    let client = TestClient::from_server(Server::new().response_envelope(Custom));
+    assert_eq!(client.get("/envelope/names").await.json::<Value>(), json!({ "result": ["alice", "bob"] }));
+    assert_eq!(client.get("/envelope/user").await.json::<Value>(), json!({ "id": 1 }));
+
+    let response = client.get("/envelope/missing").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.json::<Value>(), json!({ "error": { "code": 404, "reason": "User 9 not found" } }));
+
+    let response = client.post("/envelope/user").await;
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.header("allow"), Some("GET, HEAD, OPTIONS"));
+    assert_eq!(response.json::<Value>(), json!({ "error": { "code": 405, "reason": "Method not allowed" } }));
+}
+
+#[tokio::test]
+async fn no_envelope_sends_values_and_messages_bare() {
+    let client = TestClient::from_server(Server::new().response_envelope(NoEnvelope));
+    assert_eq!(client.get("/envelope/names").await.json::<Value>(), json!(["alice", "bob"]));
+    assert_eq!(client.get("/envelope/missing").await.json::<Value>(), json!("User 9 not found"));
+}
+
+#[tokio::test]
+async fn problem_details_are_sent_as_problem_json() {
+    let client = TestClient::from_server(Server::new().response_envelope(ProblemDetails));
+    for path in ["/envelope/missing", "/envelope/nowhere"] {
+        let response = client.get(path).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.header("content-type"), Some("application/problem+json"));
+        let problem = response.json::<Value>();
+        assert_eq!(problem["type"], "about:blank");
+        assert_eq!(problem["title"], "Not Found");
+        assert_eq!(problem["status"], 404);
+    }
+    assert_eq!(
+        client.get("/envelope/missing").await.json::<Value>()["detail"],
+        "User 9 not found"
+    );
+    assert_eq!(client.get("/envelope/names").await.json::<Value>(), json!(["alice", "bob"]));
+}
@@ -0,0 +1,89 @@
+use ferrox::openapi::{spec, OpenApiConfig};
+use ferrox::test::TestClient;
+use ferrox::{http_method, Accepted, Created, FerroxError, NoContent, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+#[derive(Serialize)]
+struct Widget {
+    id: u64,
+    name: String,
+}
+
+#[http_method(POST, "/status/widgets")]
+async fn create_widget(body: Value) -> Result<Created<Widget>, FerroxError> {
+    let name = body["name"].as_str().ok_or_else(|| FerroxError::BadRequest("name is required".to_string()))?;
+    Ok(Created("/status/widgets/7".to_string(), Widget { id: 7, name: name.to_string() }))
+}
+
+#[http_method(DELETE, "/status/widgets/:id")]
+fn delete_widget(id: u64) -> Result<NoContent, FerroxError> {
+    match id {
+        7 => Ok(NoContent),
+        _ => Err(FerroxError::NotFound("No such widget".to_string())),
+    }
+}
+
+#[http_method(POST, "/status/exports")]
+fn export() -> Accepted<Value> {
+    Accepted(json!({ "job": "export-1" }))
+}
+
+#[http_method(POST, "/status/imports", status = 201)]
+fn import(body: Value) -> Result<Value, FerroxError> {
+    match body["rows"].as_u64() {
+        Some(rows) => Ok(json!({ "imported": rows })),
+        None => Err(FerroxError::BadRequest("rows is required".to_string())),
+    }
+}
+
+#[tokio::test]
+async fn created_sends_201_with_the_location() {
+    let client = TestClient::new();
+    let response = client.post("/status/widgets").json(&json!({ "name": "gear" })).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.header("location"), Some("/status/widgets/7"));
+    assert_eq!(response.json::<Value>(), json!({ "id": 7, "name": "gear" }));
+
+    let response = client.post("/status/widgets").json(&json!({})).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.header("location"), None);
+}
+
+#[tokio::test]
+async fn no_content_and_accepted_send_their_status() {
+    let client = TestClient::new();
+    let response = client.delete("/status/widgets/7").await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(response.bytes().is_empty());
+    assert_eq!(response.header("content-type"), None);
+    assert_eq!(client.delete("/status/widgets/8").await.status(), StatusCode::NOT_FOUND);
+
+    let response = client.post("/status/exports").await;
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    assert_eq!(response.json::<Value>(), json!({ "job": "export-1" }));
+}
+
+#[tokio::test]
+async fn the_status_option_replaces_200_for_successes_only() {
+    let client = TestClient::new();
+    let response = client.post("/status/imports").json(&json!({ "rows": 3 })).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.json::<Value>(), json!({ "imported": 3 }));
+    assert_eq!(client.post("/status/imports").json(&json!({})).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn the_document_lists_the_success_status() {
+    let document = spec(&OpenApiConfig::new("Status", "1.0"));
+    let responses = |path: &str, method: &str| document["paths"][path][method]["responses"].clone();
+
+    let created = responses("/status/widgets", "post");
+    assert!(created.get("200").is_none());
+    assert_eq!(created["201"]["content"]["application/json"]["schema"]["title"], "Widget");
+    assert!(created["201"]["headers"].get("Location").is_some());
+    let deleted = responses("/status/widgets/{id}", "delete");
+    assert_eq!(deleted["204"], json!({ "description": "Successful response" }));
+    assert!(responses("/status/exports", "post").get("202").is_some());
+    assert!(responses("/status/imports", "post").get("201").is_some());
+}
@@ -0,0 +1,56 @@
+use ferrox::NonObjectResponse;
+use serde_json::{json, Value};
+
+fn enveloped(data: Value) -> Value {
+    json!({ "success": true, "data": data, "message": "" })
+}
+
+#[test]
+fn null_is_wrapped_in_data() {
+    assert_eq!(NonObjectResponse::Envelope.apply(Value::Null), enveloped(Value::Null));
+}
+
+#[test]
+fn bool_is_wrapped_in_data() {
+    assert_eq!(NonObjectResponse::Envelope.apply(json!(true)), enveloped(json!(true)));
+}
+
+#[test]
+fn number_is_wrapped_in_data() {
+    assert_eq!(NonObjectResponse::Envelope.apply(json!(42)), enveloped(json!(42)));
+    assert_eq!(NonObjectResponse::Envelope.apply(json!(-1.5)), enveloped(json!(-1.5)));
+}
+
+#[test]
+fn string_is_wrapped_in_data() {
+    assert_eq!(NonObjectResponse::Envelope.apply(json!("alice")), enveloped(json!("alice")));
+}
+
+#[test]
+fn array_is_wrapped_in_data_unchanged() {
+    let users = json!(["alice", "bob", { "name": "charlie" }]);
+    assert_eq!(NonObjectResponse::Envelope.apply(users.clone()), enveloped(users));
+}
+
+#[test]
+fn object_is_sent_unchanged() {
+    let response = json!({ "success": false, "data": null, "message": "nope" });
+    assert_eq!(NonObjectResponse::Envelope.apply(response.clone()), response);
+
+    let bare = json!({ "id": 7 });
+    assert_eq!(NonObjectResponse::Envelope.apply(bare.clone()), bare);
+}
+
+#[test]
+fn pass_through_leaves_every_type_alone() {
+    for value in [
+        Value::Null,
+        json!(false),
+        json!(3),
+        json!("text"),
+        json!([1, 2, 3]),
+        json!({ "key": "value" }),
+    ] {
+        assert_eq!(NonObjectResponse::PassThrough.apply(value.clone()), value);
+    }
+}
@@ -0,0 +1,103 @@
+#![cfg(unix)]
+
+use std::net::SocketAddr;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use ferrox::listener::Listener;
+use ferrox::{http_method, Server};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+// Set in the successor process this test binary starts
+const SUCCESSOR_ADDR: &str = "RESTART_TEST_SUCCESSOR_ADDR";
+
+static STOP: AtomicBool = AtomicBool::new(false);
+
+#[http_method(GET, "/restart/pid")]
+fn pid() -> Value {
+    json!({ "pid": std::process::id() })
+}
+
+#[http_method(POST, "/restart/stop")]
+fn stop() -> Value {
+    STOP.store(true, Ordering::SeqCst);
+    json!({})
+}
+
+// The body of a request over a new connection
+async fn request(addr: SocketAddr, method: &str, path: &str) -> Value {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!("{} {} HTTP/1.1\r\nhost: localhost\r\ncontent-length: 0\r\nconnection: close\r\n\r\n", method, path);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    serde_json::from_str(body).unwrap()
+}
+
+// Serves as the successor when started by `sockets_are_handed_over_to_a_successor`
+#[tokio::test]
+#[ignore]
+async fn successor() {
+    let Ok(addr) = std::env::var(SUCCESSOR_ADDR) else {
+        return;
+    };
+    let handle = Server::new().quiet().start_in_background(&addr).await.unwrap();
+    for _ in 0..500 {
+        if STOP.load(Ordering::SeqCst) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    handle.graceful_shutdown(Duration::from_secs(1)).await.unwrap();
+}
+
+#[tokio::test]
+async fn sockets_are_handed_over_to_a_successor() {
+    let handle = Server::new().quiet().start_in_background("127.0.0.1:0").await.unwrap();
+    let addr = handle.local_addr().unwrap();
+    assert_eq!(request(addr, "GET", "/restart/pid").await["pid"], std::process::id());
+
+    let mut successor = Command::new(std::env::current_exe().unwrap());
+    successor
+        .args(["successor", "--exact", "--ignored", "--quiet"])
+        .env(SUCCESSOR_ADDR, addr.to_string())
+        .stdout(Stdio::null());
+    let pid = handle.hand_over(&mut successor, Duration::from_secs(10)).await.unwrap();
+    handle.graceful_shutdown(Duration::from_secs(1)).await.unwrap();
+
+    // The same address is served by the successor
+    assert_eq!(request(addr, "GET", "/restart/pid").await["pid"], pid);
+    request(addr, "POST", "/restart/stop").await;
+}
+
+#[tokio::test]
+async fn a_failed_hand_over_keeps_the_server_running() {
+    let handle = Server::new().quiet().start_in_background("127.0.0.1:0").await.unwrap();
+    let addr = handle.local_addr().unwrap();
+
+    let err = handle.hand_over(&mut Command::new("true"), Duration::from_secs(5)).await.unwrap_err();
+    assert!(err.to_string().contains("exited before it was ready"));
+    assert_eq!(request(addr, "GET", "/restart/pid").await["pid"], std::process::id());
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn reuse_port_listeners_share_an_address() {
+    let first = Server::new().quiet().bind(Listener::reuse_port("127.0.0.1:0")).run_in_background().await.unwrap();
+    let addr = first.local_addr().unwrap();
+    let second = Server::new()
+        .quiet()
+        .bind(Listener::reuse_port(addr.to_string()))
+        .run_in_background()
+        .await
+        .unwrap();
+    assert_eq!(second.local_addr(), Some(addr));
+
+    first.shutdown().await;
+    assert_eq!(request(addr, "GET", "/restart/pid").await["pid"], std::process::id());
+    second.shutdown().await;
+}
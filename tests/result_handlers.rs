@@ -0,0 +1,75 @@
+use ferrox::test::TestClient;
+use ferrox::{http_method, ApiResponse, FerroxError, HandlerResponse, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/results/users/:id")]
+fn user(path: Value, _query: Value, _body: Value) -> Result<ApiResponse<Value>, FerroxError> {
+    match path["id"].as_str() {
+        Some("1") => Ok(ApiResponse::ok(json!({ "id": 1, "name": "alice" }))),
+        _ => Err(FerroxError::NotFound("User not found".to_string())),
+    }
+}
+
+#[http_method(GET, "/results/errors/:kind")]
+fn error(kind: String) -> Result<Value, FerroxError> {
+    let message = format!("{} failed", kind);
+    Err(match kind.as_str() {
+        "bad-request" => FerroxError::BadRequest(message),
+        "unauthorized" => FerroxError::Unauthorized(message),
+        "forbidden" => FerroxError::Forbidden(message),
+        "not-found" => FerroxError::NotFound(message),
+        "conflict" => FerroxError::Conflict(message),
+        "unprocessable" => FerroxError::UnprocessableEntity(message),
+        "internal" => FerroxError::Internal(message),
+        _ => FerroxError::Status(StatusCode::IM_A_TEAPOT, message),
+    })
+}
+
+#[http_method(GET, "/results/queued")]
+fn queued() -> Result<HandlerResponse, FerroxError> {
+    Ok(HandlerResponse::new(StatusCode::ACCEPTED, json!({ "queued": true })))
+}
+
+#[tokio::test]
+async fn ok_results_are_sent_with_200() {
+    let response = TestClient::new().get("/results/users/1").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.json::<Value>(),
+        json!({ "success": true, "data": { "id": 1, "name": "alice" }, "message": "" })
+    );
+}
+
+#[tokio::test]
+async fn each_error_is_sent_with_its_status() {
+    let client = TestClient::new();
+    let expected = [
+        ("bad-request", StatusCode::BAD_REQUEST),
+        ("unauthorized", StatusCode::UNAUTHORIZED),
+        ("forbidden", StatusCode::FORBIDDEN),
+        ("not-found", StatusCode::NOT_FOUND),
+        ("conflict", StatusCode::CONFLICT),
+        ("unprocessable", StatusCode::UNPROCESSABLE_ENTITY),
+        ("internal", StatusCode::INTERNAL_SERVER_ERROR),
+        ("teapot", StatusCode::IM_A_TEAPOT),
+    ];
+    for (kind, status) in expected {
+        let response = client.get(&format!("/results/errors/{}", kind)).await;
+        assert_eq!(response.status(), status, "{}", kind);
+        assert_eq!(
+            response.json::<Value>(),
+            json!({ "success": false, "data": null, "message": format!("{} failed", kind) })
+        );
+    }
+
+    let response = client.get("/results/users/2").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.json::<Value>()["message"], "User not found");
+}
+
+#[tokio::test]
+async fn ok_responses_keep_the_status_they_were_built_with() {
+    let response = TestClient::new().get("/results/queued").await;
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    assert_eq!(response.json::<Value>(), json!({ "queued": true }));
+}
@@ -0,0 +1,73 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ferrox::openapi::{spec, OpenApiConfig};
+use ferrox::test::TestClient;
+use ferrox::{http_method, route_group, StatusCode};
+use serde_json::{json, Value};
+
+static THING_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+#[http_method(GET | HEAD, "/things/:id", "/v1/things/:id")]
+fn get_thing(id: u64) -> Value {
+    THING_CALLS.fetch_add(1, Ordering::SeqCst);
+    json!({ "id": id })
+}
+
+#[route_group(prefix = "/shop")]
+mod shop {
+    use ferrox::http_method;
+    use serde_json::Value;
+
+    #[http_method(PUT | PATCH, "/orders/:id", "/carts/:id")]
+    fn update_order(id: u64, body: Value) -> Value {
+        serde_json::json!({ "id": id, "changes": body })
+    }
+}
+
+#[tokio::test]
+async fn a_handler_serves_every_method_and_path_listed() {
+    let client = TestClient::new();
+    assert_eq!(client.get("/things/1").await.json::<Value>(), json!({ "id": 1 }));
+    assert_eq!(client.get("/v1/things/2").await.json::<Value>(), json!({ "id": 2 }));
+
+    // HEAD runs the handler through its own registration and sends no body
+    let before = THING_CALLS.load(Ordering::SeqCst);
+    let response = client.head("/v1/things/3").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.bytes().is_empty());
+    assert_eq!(THING_CALLS.load(Ordering::SeqCst) - before, 1);
+
+    for path in ["/shop/orders/4", "/shop/carts/4"] {
+        let response = client.patch(path).json(&json!({ "paid": true })).await;
+        assert_eq!(response.json::<Value>(), json!({ "id": 4, "changes": { "paid": true } }));
+        assert_eq!(client.put(path).json(&json!({})).await.status(), StatusCode::OK);
+    }
+    let response = client.delete("/shop/carts/4").await;
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.header("allow"), Some("PUT, PATCH, OPTIONS"));
+}
+
+#[test]
+fn every_registration_is_listed_and_documented() {
+    let listed: Vec<_> = ferrox::routes().iter().map(|route| (route.method, route.path)).collect();
+    assert_eq!(
+        listed,
+        [
+            ("PATCH", "/shop/carts/:id"),
+            ("PUT", "/shop/carts/:id"),
+            ("PATCH", "/shop/orders/:id"),
+            ("PUT", "/shop/orders/:id"),
+            ("GET", "/things/:id"),
+            ("HEAD", "/things/:id"),
+            ("GET", "/v1/things/:id"),
+            ("HEAD", "/v1/things/:id"),
+        ]
+    );
+
+    let document = spec(&OpenApiConfig::new("Aliases", "1.0"));
+    let ids: Vec<&Value> = ["/things/{id}", "/v1/things/{id}"]
+        .iter()
+        .flat_map(|path| ["get", "head"].map(|method| &document["paths"][path][method]["operationId"]))
+        .collect();
+    assert_eq!(ids, [&json!("get_thing"), &json!("get_thing_2"), &json!("get_thing_3"), &json!("get_thing_4")]);
+}
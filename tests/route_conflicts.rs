@@ -0,0 +1,48 @@
+// Every route in this binary conflicts with another, so it is kept apart from
+// the other tests
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/users/:id")]
+fn get_user(id: u32) -> Value {
+    json!({ "id": id })
+}
+
+#[http_method(GET, "/users/:id")]
+fn find_user(id: u32) -> Value {
+    json!({ "id": id })
+}
+
+#[http_method(DELETE, "/users/:name")]
+fn delete_user(name: String) -> Value {
+    json!({ "name": name })
+}
+
+#[tokio::test]
+async fn start_reports_every_conflict_with_both_locations() {
+    let err = Server::new().start_in_background("127.0.0.1:0").await.err().expect("conflicting routes must fail");
+    let conflict = err.downcast_ref::<ferrox::RouteConflict>().expect("a RouteConflict");
+    let file = file!();
+    assert_eq!(
+        conflict.conflicts(),
+        [
+            format!("duplicate route GET /users/:id: `get_user` at {file}:7 and `find_user` at {file}:12"),
+            format!(
+                "ambiguous paths GET /users/:id (`get_user` at {file}:7) and DELETE /users/:name (`delete_user` at {file}:17); \
+                 placeholders at the same position must have the same name"
+            ),
+            format!(
+                "ambiguous paths GET /users/:id (`find_user` at {file}:12) and DELETE /users/:name (`delete_user` at {file}:17); \
+                 placeholders at the same position must have the same name"
+            ),
+        ]
+    );
+    assert!(err.to_string().starts_with("conflicting route definitions:\n  duplicate route GET /users/:id"));
+}
+
+#[test]
+#[should_panic(expected = "duplicate route GET /users/:id")]
+fn into_router_panics() {
+    TestClient::new();
+}
@@ -0,0 +1,98 @@
+use ferrox::axum::http::Method;
+use ferrox::cors::CorsConfig;
+use ferrox::test::TestClient;
+use ferrox::{http_method, route_group, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/feed", cors = "any")]
+fn feed() -> Value {
+    json!({ "items": [] })
+}
+
+#[http_method(GET | DELETE, "/account")]
+fn account() -> Value {
+    json!({ "id": 1 })
+}
+
+#[route_group(prefix = "/partners", cors = ["https://partner.example"])]
+mod partners {
+    use super::*;
+
+    #[http_method(GET, "/orders")]
+    fn orders() -> Value {
+        json!([])
+    }
+
+    #[http_method(DELETE, "/orders/:id", cors = false)]
+    fn cancel(id: u64) -> Value {
+        json!({ "cancelled": id })
+    }
+}
+
+fn server() -> Server {
+    Server::new().cors(
+        CorsConfig::new()
+            .allow_origin("https://app.example")
+            .allow_methods([Method::GET, Method::DELETE])
+            .allow_credentials(true),
+    )
+}
+
+#[tokio::test]
+async fn any_origin_reads_public_routes() {
+    let client = TestClient::from_server(server());
+    let response = client.get("/feed").header("origin", "https://elsewhere.example").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("access-control-allow-origin"), Some("*"));
+    assert_eq!(response.header("access-control-allow-credentials"), None);
+
+    // Other routes keep the server's policy
+    let response = client.get("/account").header("origin", "https://elsewhere.example").await;
+    assert_eq!(response.header("access-control-allow-origin"), None);
+    let response = client.get("/account").header("origin", "https://app.example").await;
+    assert_eq!(response.header("access-control-allow-origin"), Some("https://app.example"));
+    assert_eq!(response.header("access-control-allow-credentials"), Some("true"));
+}
+
+#[tokio::test]
+async fn groups_list_their_own_origins() {
+    let client = TestClient::from_server(server());
+    let response = client
+        .options("/partners/orders")
+        .header("origin", "https://partner.example")
+        .header("access-control-request-method", "GET")
+        .await;
+    assert_eq!(response.header("access-control-allow-origin"), Some("https://partner.example"));
+    assert_eq!(response.header("access-control-allow-credentials"), Some("true"));
+
+    let response = client.get("/partners/orders").header("origin", "https://app.example").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("access-control-allow-origin"), None);
+}
+
+#[tokio::test]
+async fn routes_can_opt_out_of_cors() {
+    let client = TestClient::from_server(server());
+    let response = client
+        .options("/partners/orders/7")
+        .header("origin", "https://partner.example")
+        .header("access-control-request-method", "DELETE")
+        .await;
+    assert_eq!(response.header("access-control-allow-origin"), None);
+    let response = client.delete("/partners/orders/7").header("origin", "https://app.example").await;
+    assert_eq!(response.json::<Value>(), json!({ "cancelled": 7 }));
+    assert_eq!(response.header("access-control-allow-origin"), None);
+}
+
+#[tokio::test]
+async fn route_policies_apply_without_a_server_policy() {
+    let client = TestClient::new();
+    let response = client
+        .options("/feed")
+        .header("origin", "https://elsewhere.example")
+        .header("access-control-request-method", "GET")
+        .await;
+    assert_eq!(response.header("access-control-allow-origin"), Some("*"));
+    let response = client.get("/account").header("origin", "https://app.example").await;
+    assert_eq!(response.header("access-control-allow-origin"), None);
+}
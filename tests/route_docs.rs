@@ -0,0 +1,67 @@
+use ferrox::openapi::{spec, OpenApiConfig};
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+struct NewUser {
+    name: String,
+}
+
+/// Fetch one user.
+///
+/// Deleted users are answered with 404.
+#[http_method(GET, "/docs/users/:id", tag = "Users")]
+fn get_user(
+    /// The user's numeric id
+    id: u64,
+) -> Value {
+    json!({ "id": id })
+}
+
+/// Create a user
+/// from a name.
+#[http_method(POST, "/docs/users", tag = "Users")]
+fn create_user(
+    /// The user to create
+    body: NewUser,
+) -> Value {
+    json!({ "name": body.name })
+}
+
+#[http_method(GET, "/docs/health")]
+fn health() -> Value {
+    json!({ "ok": true })
+}
+
+#[test]
+fn doc_comments_and_tags_document_the_operations() {
+    let document = spec(&OpenApiConfig::new("Docs", "1.0"));
+    let get_user = &document["paths"]["/docs/users/{id}"]["get"];
+    assert_eq!(get_user["summary"], "Fetch one user.");
+    assert_eq!(get_user["description"], "Fetch one user.\n\nDeleted users are answered with 404.");
+    assert_eq!(get_user["tags"], json!(["Users"]));
+    assert_eq!(get_user["parameters"][0]["description"], "The user's numeric id");
+
+    let create_user = &document["paths"]["/docs/users"]["post"];
+    assert_eq!(create_user["summary"], "Create a user from a name.");
+    assert!(create_user.get("description").is_none());
+    assert_eq!(create_user["requestBody"]["description"], "The user to create");
+
+    let health = &document["paths"]["/docs/health"]["get"];
+    assert!(health.get("summary").is_none() && health.get("tags").is_none());
+}
+
+#[tokio::test]
+async fn the_route_listing_carries_the_docs() {
+    let client = TestClient::from_server(Server::new().debug_routes("/_routes"));
+    let routes = client.get("/_routes").await.json::<Value>();
+    let get_user = routes.as_array().unwrap().iter().find(|route| route["handler"] == "get_user").unwrap();
+    assert_eq!(get_user["doc"], "Fetch one user.\n\nDeleted users are answered with 404.");
+    assert_eq!(get_user["tag"], "Users");
+    assert_eq!(
+        get_user["params"],
+        json!([{ "name": "id", "source": "path_param", "type": "u64", "description": "The user's numeric id" }])
+    );
+}
@@ -0,0 +1,84 @@
+use ferrox::axum::extract::Request;
+use ferrox::axum::http::HeaderValue;
+use ferrox::axum::middleware::Next;
+use ferrox::axum::response::{IntoResponse, Response};
+use ferrox::test::TestClient;
+use ferrox::{route_group, StatusCode};
+use serde_json::{json, Value};
+
+// Append `step` to the `x-trail` request header, so handlers see the order layers ran in
+fn mark(mut request: Request, step: &str) -> Request {
+    let trail = match request.headers().get("x-trail").and_then(|value| value.to_str().ok()) {
+        Some(trail) => format!("{},{}", trail, step),
+        None => step.to_string(),
+    };
+    request.headers_mut().insert("x-trail", HeaderValue::from_str(&trail).unwrap());
+    request
+}
+
+async fn api_layer(request: Request, next: Next) -> Response {
+    next.run(mark(request, "api")).await
+}
+
+async fn admin_layer(request: Request, next: Next) -> Response {
+    if request.headers().get("x-admin").is_none() {
+        return (StatusCode::FORBIDDEN, "admins only").into_response();
+    }
+    next.run(mark(request, "admin")).await
+}
+
+async fn handler_layer(request: Request, next: Next) -> Response {
+    next.run(mark(request, "handler")).await
+}
+
+#[route_group(prefix = "/grouped/api/v1", middleware = [crate::api_layer])]
+mod api {
+    use ferrox::sse::{SseEvent, SseStream};
+    use ferrox::{http_method, route_group, sse, RequestContext};
+    use serde_json::{json, Value};
+
+    #[http_method(GET, "/users/:id")]
+    fn get_user(id: u64, ctx: &RequestContext) -> Value {
+        json!({ "id": id, "trail": ctx.header("x-trail") })
+    }
+
+    #[sse("/feed")]
+    fn feed() -> SseStream {
+        SseStream::new(futures_util::stream::iter([SseEvent::data("grouped")]))
+    }
+
+    #[route_group(prefix = "/admin", middleware = [crate::admin_layer])]
+    mod admin {
+        use ferrox::{http_method, middleware, RequestContext};
+        use serde_json::{json, Value};
+
+        #[http_method(DELETE, "/users/:id")]
+        #[middleware(crate::handler_layer)]
+        fn delete_user(id: u64, ctx: &RequestContext) -> Value {
+            json!({ "deleted": id, "trail": ctx.header("x-trail") })
+        }
+    }
+}
+
+#[tokio::test]
+async fn groups_prefix_their_routes() {
+    let client = TestClient::new();
+    let response = client.get("/grouped/api/v1/users/7").await;
+    assert_eq!(response.json::<Value>(), json!({ "id": 7, "trail": "api" }));
+    assert!(client.get("/grouped/api/v1/feed").await.text().contains("data: grouped"));
+
+    // Only the prefixed paths exist
+    assert_eq!(client.get("/users/7").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(client.delete("/grouped/api/v1/users/7").await.status(), StatusCode::METHOD_NOT_ALLOWED);
+}
+
+#[tokio::test]
+async fn nested_groups_extend_the_prefix_and_run_inside_the_outer_middleware() {
+    let client = TestClient::new();
+    let response = client.delete("/grouped/api/v1/admin/users/7").header("x-admin", "yes").await;
+    assert_eq!(response.json::<Value>(), json!({ "deleted": 7, "trail": "api,admin,handler" }));
+
+    let refused = client.delete("/grouped/api/v1/admin/users/7").await;
+    assert_eq!(refused.status(), StatusCode::FORBIDDEN);
+    assert_eq!(refused.text(), "admins only");
+}
@@ -0,0 +1,87 @@
+use ferrox::axum::{extract::Request, middleware::Next, response::Response};
+use ferrox::test::TestClient;
+use ferrox::{http_method, middleware, route_group, Server, StatusCode};
+use serde_json::{json, Value};
+
+async fn audit(request: Request, next: Next) -> Response {
+    next.run(request).await
+}
+
+async fn tag(request: Request, next: Next) -> Response {
+    next.run(request).await
+}
+
+#[http_method(GET, "/users/:id")]
+#[middleware(audit, layer = tower_http::cors::CorsLayer::permissive())]
+fn get_user(id: u32) -> Value {
+    json!({ "id": id })
+}
+
+#[http_method(POST, "/users", auth = "jwt")]
+fn create_user(body: Value) -> Value {
+    body
+}
+
+#[route_group(prefix = "/admin", middleware = [crate::tag])]
+mod admin {
+    use ferrox::http_method;
+    use serde_json::{json, Value};
+
+    #[http_method(DELETE, "/users/:id")]
+    fn delete_user(id: u32) -> Value {
+        json!({ "deleted": id })
+    }
+}
+
+#[test]
+fn routes_lists_every_registration_by_path() {
+    let routes = ferrox::routes();
+    let listed: Vec<_> = routes.iter().map(|route| (route.method, route.path, route.handler)).collect();
+    assert_eq!(
+        listed,
+        [
+            ("DELETE", "/admin/users/:id", "delete_user"),
+            ("POST", "/users", "create_user"),
+            ("GET", "/users/:id", "get_user"),
+        ]
+    );
+
+    let get_user = &routes[2];
+    assert_eq!(get_user.kind, "http");
+    assert_eq!(get_user.middleware, ["audit", "tower_http::cors::CorsLayer"]);
+    assert_eq!(get_user.location, format!("{}:14", file!()));
+    assert_eq!(routes[0].middleware, ["crate::tag"]);
+    assert_eq!(routes[1].auth, Some("jwt"));
+}
+
+#[tokio::test]
+async fn debug_endpoint_serves_the_listing() {
+    let client = TestClient::from_server(Server::new().debug_routes("/_routes"));
+    let response = client.get("/_routes").await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = response.json::<Value>();
+    assert_eq!(body.as_array().unwrap().len(), 3);
+    assert_eq!(
+        body[1],
+        json!({
+            "method": "POST",
+            "path": "/users",
+            "kind": "http",
+            "handler": "create_user",
+            "location": format!("{}:20", file!()),
+            "middleware": [],
+            "auth": "jwt",
+            "version": null,
+            "doc": null,
+            "tag": null,
+            "params": [{ "name": "body", "source": "body", "type": "Value", "description": null }],
+        })
+    );
+}
+
+#[tokio::test]
+async fn the_listing_is_off_by_default() {
+    let client = TestClient::new();
+    assert_eq!(client.get("/_routes").await.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use ferrox::axum::body::Body;
+use ferrox::axum::http::Request;
+use ferrox::axum::Router;
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, StatusCode};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+macro_rules! shelves {
+    ($($name:ident => $index:literal at $path:literal),* $(,)?) => {
+        $(
+            #[http_method(GET, $path)]
+            fn $name(item: u64) -> Value {
+                json!({ "shelf": $index, "item": item })
+            }
+        )*
+    };
+}
+
+shelves!(
+    shelf_0 => 0 at "/router/shelves/0/:item",
+    shelf_1 => 1 at "/router/shelves/1/:item",
+    shelf_2 => 2 at "/router/shelves/2/:item",
+    shelf_3 => 3 at "/router/shelves/3/:item",
+    shelf_4 => 4 at "/router/shelves/4/:item",
+    shelf_5 => 5 at "/router/shelves/5/:item",
+    shelf_6 => 6 at "/router/shelves/6/:item",
+    shelf_7 => 7 at "/router/shelves/7/:item",
+    shelf_8 => 8 at "/router/shelves/8/:item",
+    shelf_9 => 9 at "/router/shelves/9/:item",
+    shelf_10 => 10 at "/router/shelves/10/:item",
+    shelf_11 => 11 at "/router/shelves/11/:item",
+    shelf_12 => 12 at "/router/shelves/12/:item",
+    shelf_13 => 13 at "/router/shelves/13/:item",
+    shelf_14 => 14 at "/router/shelves/14/:item",
+    shelf_15 => 15 at "/router/shelves/15/:item",
+);
+
+async fn oneshot(router: &Router, uri: &str) -> (StatusCode, Value) {
+    let request = Request::get(uri).body(Body::empty()).unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = ferrox::axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn the_router_can_be_driven_and_nested_without_a_server() {
+    let router = Server::new().into_router();
+    assert_eq!(oneshot(&router, "/router/shelves/3/9").await, (StatusCode::OK, json!({ "shelf": 3, "item": 9 })));
+
+    let outer = Router::new().nest("/inner", router);
+    let (status, body) = oneshot(&outer, "/inner/router/shelves/15/1").await;
+    assert_eq!((status, body["shelf"].clone()), (StatusCode::OK, json!(15)));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_requests_reach_their_own_routes() {
+    let client = Arc::new(TestClient::new());
+    let mut tasks = tokio::task::JoinSet::new();
+    for task in 0..32u64 {
+        let client = client.clone();
+        tasks.spawn(async move {
+            for request in 0..25u64 {
+                let shelf = (task + request) % 16;
+                let response = client.get(&format!("/router/shelves/{}/{}", shelf, task)).await;
+                assert_eq!(response.json::<Value>(), json!({ "shelf": shelf, "item": task }));
+            }
+        });
+    }
+    while let Some(result) = tasks.join_next().await {
+        result.unwrap();
+    }
+}
@@ -0,0 +1,52 @@
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+use ferrox::listener::Listener;
+use ferrox::runtime::RuntimeConfig;
+use ferrox::{http_method, Server};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/runtime/thread")]
+async fn thread() -> Value {
+    json!({ "name": std::thread::current().name() })
+}
+
+#[test]
+fn run_blocking_serves_on_its_own_runtime() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = Server::new()
+        .quiet()
+        .runtime(RuntimeConfig::new().worker_threads(2).thread_name("runtime-test").max_blocking_threads(4))
+        .bind(Listener::from_tcp(listener));
+    // Serves until the test process exits
+    std::thread::spawn(move || server.run_blocking().unwrap());
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .write_all(b"GET /runtime/thread HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    assert_eq!(serde_json::from_str::<Value>(body).unwrap()["name"], "runtime-test");
+}
+
+#[test]
+fn built_runtimes_use_the_settings() {
+    let runtime = RuntimeConfig::new().worker_threads(1).thread_name("runtime-build").build().unwrap();
+    let name = runtime.block_on(async {
+        tokio::spawn(async { std::thread::current().name().map(str::to_string) }).await.unwrap()
+    });
+    assert_eq!(name.as_deref(), Some("runtime-build"));
+
+    let runtime = RuntimeConfig::current_thread().build().unwrap();
+    let caller = std::thread::current().id();
+    assert_eq!(runtime.block_on(async { std::thread::current().id() }), caller);
+}
+
+#[tokio::test]
+async fn run_blocking_refuses_to_nest_runtimes() {
+    let err = Server::new().quiet().run_blocking().unwrap_err();
+    assert!(err.to_string().contains("await `run` instead"));
+}
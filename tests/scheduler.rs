@@ -0,0 +1,112 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use ferrox::scheduler::Schedule;
+use ferrox::{scheduled, Server, State};
+
+#[derive(Clone, Default)]
+struct Ticks(Arc<AtomicUsize>);
+
+#[derive(Clone, Default)]
+struct BlockingTicks(Arc<AtomicUsize>);
+
+#[scheduled(every = "20ms")]
+async fn tick(ticks: State<Ticks>) {
+    ticks.0.0.fetch_add(1, Ordering::SeqCst);
+}
+
+#[scheduled(every = "20ms")]
+fn blocking_tick(ticks: State<BlockingTicks>) -> Result<(), String> {
+    ticks.0.0.fetch_add(1, Ordering::SeqCst);
+    Ok(())
+}
+
+// Wait until `counter` reaches `expected`, failing after `limit`
+async fn wait_for(counter: &AtomicUsize, expected: usize, limit: Duration) {
+    let deadline = tokio::time::Instant::now() + limit;
+    while counter.load(Ordering::SeqCst) < expected {
+        assert!(tokio::time::Instant::now() < deadline, "saw {} runs", counter.load(Ordering::SeqCst));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+async fn attribute_tasks_run_until_shutdown() {
+    let (ticks, blocking) = (Ticks::default(), BlockingTicks::default());
+    let handle = Server::new()
+        .with_state(ticks.clone())
+        .with_state(blocking.clone())
+        .start_in_background("127.0.0.1:0")
+        .await
+        .unwrap();
+    wait_for(&ticks.0, 3, Duration::from_secs(2)).await;
+    wait_for(&blocking.0, 3, Duration::from_secs(2)).await;
+
+    handle.graceful_shutdown(Duration::from_secs(1)).await.unwrap();
+    let stopped_at = ticks.0.load(Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(ticks.0.load(Ordering::SeqCst), stopped_at);
+}
+
+#[tokio::test]
+async fn failing_and_panicking_tasks_keep_their_schedule() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let counter = runs.clone();
+    let handle = Server::new()
+        .schedule("flaky", Schedule::every(Duration::from_millis(20)), move || {
+            let run = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                match run {
+                    0 => panic!("first run"),
+                    1 => Err("second run".to_string()),
+                    _ => Ok(()),
+                }
+            }
+        })
+        .start_in_background("127.0.0.1:0")
+        .await
+        .unwrap();
+    wait_for(&runs, 3, Duration::from_secs(2)).await;
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn graceful_shutdown_lets_running_tasks_finish() {
+    let finished = Arc::new(AtomicUsize::new(0));
+    let counter = finished.clone();
+    let handle = Server::new()
+        .schedule("slow", Schedule::every(Duration::from_millis(10)), move || {
+            let counter = counter.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(150)).await;
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .start_in_background("127.0.0.1:0")
+        .await
+        .unwrap();
+    // Shut down while the first run is in progress
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    handle.graceful_shutdown(Duration::from_secs(1)).await.unwrap();
+    assert_eq!(finished.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn cron_schedules() {
+    let err = Schedule::cron("every tuesday").unwrap_err();
+    assert!(err.to_string().starts_with("invalid cron expression \"every tuesday\""), "{}", err);
+
+    let runs = Arc::new(AtomicUsize::new(0));
+    let counter = runs.clone();
+    let handle = Server::new()
+        .schedule("every_second", Schedule::cron("* * * * * *").unwrap(), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async {}
+        })
+        .start_in_background("127.0.0.1:0")
+        .await
+        .unwrap();
+    wait_for(&runs, 1, Duration::from_secs(3)).await;
+    handle.shutdown().await;
+}
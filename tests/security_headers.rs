@@ -0,0 +1,63 @@
+use ferrox::security::SecurityHeaders;
+use ferrox::test::TestClient;
+use ferrox::{http_method, route_group, HandlerResponse, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/status")]
+fn status() -> Value {
+    json!({ "ok": true })
+}
+
+#[http_method(GET, "/embed")]
+fn embed() -> HandlerResponse {
+    HandlerResponse::new(StatusCode::OK, json!({ "embeddable": true })).with_header("x-frame-options", "SAMEORIGIN")
+}
+
+#[route_group(prefix = "/app", middleware = [
+    layer = ferrox::security::SecurityHeaders::new().content_security_policy("default-src 'self'; img-src *"),
+])]
+mod app {
+    use ferrox::http_method;
+    use serde_json::{json, Value};
+
+    #[http_method(GET, "/home")]
+    fn home() -> Value {
+        json!({ "page": "home" })
+    }
+}
+
+#[tokio::test]
+async fn every_route_gets_the_defaults() {
+    let client = TestClient::from_server(Server::new().layer(SecurityHeaders::new()));
+    let response = client.get("/status").await;
+    assert_eq!(response.header("strict-transport-security"), Some("max-age=31536000; includeSubDomains"));
+    assert_eq!(response.header("x-content-type-options"), Some("nosniff"));
+    assert_eq!(response.header("x-frame-options"), Some("DENY"));
+    assert_eq!(response.header("referrer-policy"), Some("strict-origin-when-cross-origin"));
+    assert_eq!(response.header("content-security-policy"), Some("default-src 'self'; frame-ancestors 'none'"));
+
+    // Not-found responses too, and handlers keep the headers they set
+    assert_eq!(client.get("/nowhere").await.header("x-frame-options"), Some("DENY"));
+    assert_eq!(client.get("/embed").await.header("x-frame-options"), Some("SAMEORIGIN"));
+}
+
+#[tokio::test]
+async fn a_route_group_can_turn_them_on() {
+    let client = TestClient::new();
+    let response = client.get("/app/home").await;
+    assert_eq!(response.header("content-security-policy"), Some("default-src 'self'; img-src *"));
+    assert_eq!(response.header("x-content-type-options"), Some("nosniff"));
+    assert_eq!(client.get("/status").await.header("x-content-type-options"), None);
+}
+
+#[tokio::test]
+async fn headers_can_be_configured_or_dropped() {
+    let headers = SecurityHeaders::new()
+        .hsts_max_age(std::time::Duration::from_secs(600))
+        .referrer_policy("no-referrer")
+        .without(ferrox::axum::http::header::CONTENT_SECURITY_POLICY);
+    let response = TestClient::from_server(Server::new().layer(headers)).get("/status").await;
+    assert_eq!(response.header("strict-transport-security"), Some("max-age=600; includeSubDomains"));
+    assert_eq!(response.header("referrer-policy"), Some("no-referrer"));
+    assert_eq!(response.header("content-security-policy"), None);
+}
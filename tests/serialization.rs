@@ -0,0 +1,72 @@
+use axum::http::StatusCode;
+use ferrox::{json_response, ApiResponse};
+use serde::{Serialize, Serializer};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+async fn body_json(response: axum::response::Response) -> Value {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+#[derive(Serialize)]
+struct Reading {
+    value: f64,
+}
+
+struct Unserializable;
+
+impl Serialize for Unserializable {
+    fn serialize<S: Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+        Err(serde::ser::Error::custom("secret internal detail"))
+    }
+}
+
+#[tokio::test]
+async fn nan_and_infinity_are_written_as_null() {
+    for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+        let response = json_response(StatusCode::OK, &ApiResponse::ok(Reading { value }));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await["data"], json!({ "value": null }));
+    }
+}
+
+#[tokio::test]
+async fn nan_inside_json_value_is_null() {
+    let response = json_response(StatusCode::OK, &json!({ "value": f64::NAN }));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body_json(response).await, json!({ "value": null }));
+}
+
+#[tokio::test]
+async fn non_string_map_keys_return_500() {
+    let mut grid = HashMap::new();
+    grid.insert((1u8, 2u8), "cell");
+
+    let response = json_response(StatusCode::OK, &grid);
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let body = body_json(response).await;
+    assert_eq!(body["success"], json!(false));
+    assert_eq!(body["message"], json!("Internal server error: response could not be serialized"));
+}
+
+#[tokio::test]
+async fn failing_serialize_impl_does_not_leak_detail() {
+    let response = json_response(StatusCode::CREATED, &ApiResponse::ok(Unserializable));
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let body = body_json(response).await;
+    assert!(!body["message"].as_str().unwrap().contains("secret internal detail"));
+}
+
+#[tokio::test]
+async fn serializable_body_keeps_requested_status() {
+    let response = json_response(StatusCode::ACCEPTED, &json!({ "queued": true }));
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    assert_eq!(
+        response.headers()[axum::http::header::CONTENT_TYPE],
+        "application/json"
+    );
+    assert_eq!(body_json(response).await, json!({ "queued": true }));
+}
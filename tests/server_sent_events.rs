@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use ferrox::sse::{SseEvent, SseStream};
+use ferrox::test::TestClient;
+use ferrox::{sse, FerroxError, StatusCode};
+use futures_util::stream;
+use serde_json::{json, Value};
+
+#[sse("/events/ticks/:count")]
+fn ticks(count: u32) -> SseStream {
+    let events = (1..=count).map(|tick| SseEvent::data(format!("tick {}", tick)).id(tick.to_string()));
+    SseStream::new(stream::iter(events))
+}
+
+#[sse("/events/detailed")]
+async fn detailed() -> SseStream {
+    let events = [
+        SseEvent::data("first line\nsecond line")
+            .event("update")
+            .id("7")
+            .retry(Duration::from_secs(3)),
+        SseEvent::json(&json!({ "status": "shipped" })).unwrap(),
+        SseEvent::data("carriage\rreturn").id("8\n9").event("odd\r\nname"),
+    ];
+    SseStream::new(stream::iter(events))
+}
+
+#[sse("/events/channel")]
+async fn channel() -> SseStream {
+    let (sender, stream) = SseStream::channel(4);
+    tokio::spawn(async move {
+        for step in ["one", "two"] {
+            sender.send(SseEvent::data(step)).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(60)).await;
+        }
+    });
+    stream.keep_alive(Some(Duration::from_millis(20)))
+}
+
+#[sse("/events/private")]
+fn private() -> Result<SseStream, FerroxError> {
+    Err(FerroxError::Unauthorized("Sign in to follow events".to_string()))
+}
+
+#[tokio::test]
+async fn events_are_streamed_in_the_sse_format() {
+    let response = TestClient::new().get("/events/ticks/3").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("content-type"), Some("text/event-stream"));
+    assert_eq!(
+        response.text(),
+        "data: tick 1\nid: 1\n\ndata: tick 2\nid: 2\n\ndata: tick 3\nid: 3\n\n"
+    );
+}
+
+#[tokio::test]
+async fn events_carry_names_ids_retry_hints_and_json() {
+    let response = TestClient::new().get("/events/detailed").await;
+    let text = response.text();
+    let events: Vec<&str> = text.split("\n\n").filter(|event| !event.is_empty()).collect();
+    let mut first: Vec<&str> = events[0].lines().collect();
+    first.sort_unstable();
+    assert_eq!(first, ["data: first line", "data: second line", "event: update", "id: 7", "retry:3000"]);
+    assert_eq!(events[1], r#"data: {"status":"shipped"}"#);
+
+    // Line breaks the format cannot carry are normalized
+    let mut third: Vec<&str> = events[2].lines().collect();
+    third.sort_unstable();
+    assert_eq!(third, ["data: carriage", "data: return", "event: oddname", "id: 89"]);
+}
+
+#[tokio::test]
+async fn channels_stream_until_the_sender_is_dropped_with_keep_alives_between() {
+    let response = TestClient::new().get("/events/channel").await;
+    let text = response.text();
+    let data: Vec<&str> = text.lines().filter(|line| line.starts_with("data:")).collect();
+    assert_eq!(data, ["data: one", "data: two"]);
+    assert!(text.lines().any(|line| line.starts_with(':')), "{:?}", text);
+}
+
+#[tokio::test]
+async fn errors_and_bad_parameters_are_answered_before_the_stream() {
+    let client = TestClient::new();
+    let response = client.get("/events/private").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        response.json::<Value>(),
+        json!({ "success": false, "data": null, "message": "Sign in to follow events" })
+    );
+    assert_eq!(client.get("/events/ticks/many").await.status(), StatusCode::BAD_REQUEST);
+}
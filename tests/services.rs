@@ -0,0 +1,96 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use ferrox::di::{self, Container, Dep};
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, ParamSource, RequestContext, Server, StatusCode};
+use serde_json::{json, Value};
+
+struct Greeter {
+    greeting: String,
+}
+
+// Counts how often per-request services are built
+#[derive(Default)]
+struct Builds {
+    count: AtomicUsize,
+}
+
+struct Visitor {
+    name: String,
+    greeter: Dep<Greeter>,
+}
+
+impl Visitor {
+    fn for_request(ctx: &RequestContext) -> Result<Self, FerroxError> {
+        di::resolve::<Builds>(ctx)?.count.fetch_add(1, Ordering::SeqCst);
+        let name = ctx
+            .headers
+            .get("x-name")
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| FerroxError::BadRequest("Who are you?".to_string()))?;
+        Ok(Visitor {
+            name: name.to_string(),
+            greeter: di::resolve(ctx)?,
+        })
+    }
+
+    fn greet(&self) -> String {
+        format!("{}, {}", self.greeter.greeting, self.name)
+    }
+}
+
+struct Unregistered;
+
+#[http_method(GET, "/services/greeting")]
+fn greeting(visitor: Dep<Visitor>, again: Dep<Visitor>, greeter: Dep<Greeter>) -> Value {
+    json!({ "message": visitor.greet(), "shared": Arc::ptr_eq(&visitor.0, &again.0), "greeting": greeter.greeting })
+}
+
+#[http_method(GET, "/services/missing")]
+async fn missing(_service: Dep<Unregistered>) -> Value {
+    json!({})
+}
+
+fn container() -> Container {
+    Container::new()
+        .singleton(Greeter { greeting: "Hello".to_string() })
+        .singleton(Builds::default())
+        .per_request(Visitor::for_request)
+}
+
+#[tokio::test]
+async fn handlers_receive_singletons_and_per_request_services() {
+    let container = container();
+    let client = TestClient::from_server(Server::new().services(container.clone()));
+
+    let response = client.get("/services/greeting").header("x-name", "Ada").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "message": "Hello, Ada", "shared": true, "greeting": "Hello" }));
+    let response = client.get("/services/greeting").header("x-name", "Grace").await;
+    assert_eq!(response.json::<Value>()["message"], "Hello, Grace");
+
+    // Built once for each request, not once per parameter
+    assert_eq!(container.get::<Builds>().unwrap().count.load(Ordering::SeqCst), 2);
+    assert!(container.contains::<Visitor>());
+    assert!(container.get::<Visitor>().is_none());
+}
+
+#[tokio::test]
+async fn factory_errors_and_missing_services_answer_the_request() {
+    let client = TestClient::from_server(Server::new().services(container()));
+    let response = client.get("/services/greeting").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.json::<Value>()["message"], "Who are you?");
+
+    assert_eq!(client.get("/services/missing").await.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let client = TestClient::new();
+    let response = client.get("/services/greeting").header("x-name", "Ada").await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[test]
+fn services_are_listed_as_such() {
+    let route = ferrox::routes().into_iter().find(|route| route.path == "/services/greeting").unwrap();
+    assert!(route.params.iter().all(|param| param.source == ParamSource::Service));
+}
@@ -0,0 +1,151 @@
+use ferrox::session::{Cookie, Cookies, MemoryStore, Session, SessionConfig, SessionStore};
+use ferrox::test::{TestClient, TestResponse};
+use ferrox::{http_method, FerroxError, Server, StatusCode};
+use serde_json::{json, Value};
+
+const SECRET: &[u8] = b"a test secret that is at least thirty-two bytes long";
+
+#[http_method(POST, "/session/login")]
+fn login(session: Session, body: Value) -> Result<Value, FerroxError> {
+    session.regenerate();
+    session.set("user", body["user"].clone())?;
+    Ok(json!({}))
+}
+
+#[http_method(GET, "/session/me")]
+fn me(session: Session) -> Value {
+    json!({ "user": session.get::<String>("user") })
+}
+
+#[http_method(POST, "/session/logout")]
+fn logout(session: Session) -> Value {
+    session.destroy();
+    json!({})
+}
+
+#[http_method(POST, "/cookies/set")]
+fn set_cookies(cookies: Cookies) -> Value {
+    cookies.add(Cookie::new("theme", "dark"));
+    cookies.add_signed(Cookie::new("plan", "pro"));
+    cookies.add_private(Cookie::new("token", "s3cret"));
+    json!({})
+}
+
+#[http_method(GET, "/cookies/get")]
+fn get_cookies(cookies: Cookies) -> Value {
+    json!({
+        "theme": cookies.get("theme"),
+        "plan": cookies.signed("plan"),
+        "token": cookies.private("token"),
+    })
+}
+
+fn client(store: MemoryStore) -> TestClient {
+    TestClient::from_server(Server::new().sessions(SessionConfig::new(SECRET).store(store)))
+}
+
+// `name=value` pairs from the response's Set-Cookie headers, as a Cookie header
+fn cookie_header(response: &TestResponse) -> String {
+    response
+        .headers()
+        .get_all("set-cookie")
+        .iter()
+        .map(|value| value.to_str().unwrap().split(';').next().unwrap().to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[tokio::test]
+async fn sessions_persist_between_requests() {
+    let store = MemoryStore::new();
+    let client = client(store.clone());
+
+    let response = client.get("/session/me").await;
+    assert_eq!(response.json::<Value>(), json!({ "user": null }));
+    assert!(response.header("set-cookie").is_none(), "untouched sessions send no cookie");
+
+    let response = client.post("/session/login").json(&json!({ "user": "ada" })).await;
+    let set_cookie = response.header("set-cookie").unwrap().to_string();
+    assert!(set_cookie.starts_with("ferrox_session="));
+    assert!(set_cookie.contains("HttpOnly") && set_cookie.contains("Secure") && set_cookie.contains("SameSite=Lax"));
+    let cookie = cookie_header(&response);
+
+    let response = client.get("/session/me").header("cookie", &cookie).await;
+    assert_eq!(response.json::<Value>(), json!({ "user": "ada" }));
+
+    // The signed id is stored without its signature
+    let id = cookie.trim_start_matches("ferrox_session=");
+    let id = &id[id.len() - 64..];
+    assert!(store.load(id).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn tampered_cookies_start_a_new_session() {
+    let client = client(MemoryStore::new());
+    let response = client.post("/session/login").json(&json!({ "user": "ada" })).await;
+    let mut cookie = cookie_header(&response);
+    let last = cookie.pop().unwrap();
+    cookie.push(if last == '0' { '1' } else { '0' });
+
+    let response = client.get("/session/me").header("cookie", &cookie).await;
+    assert_eq!(response.json::<Value>(), json!({ "user": null }));
+}
+
+#[tokio::test]
+async fn regenerating_and_destroying_sessions() {
+    let store = MemoryStore::new();
+    let client = client(store.clone());
+    let first = cookie_header(&client.post("/session/login").json(&json!({ "user": "ada" })).await);
+    let second = cookie_header(
+        &client
+            .post("/session/login")
+            .header("cookie", &first)
+            .json(&json!({ "user": "grace" }))
+            .await,
+    );
+    assert_ne!(first, second);
+    // The old id no longer works after logging in again
+    let response = client.get("/session/me").header("cookie", &first).await;
+    assert_eq!(response.json::<Value>(), json!({ "user": null }));
+
+    let response = client.post("/session/logout").header("cookie", &second).await;
+    assert!(response.header("set-cookie").unwrap().contains("Max-Age=0"));
+    let response = client.get("/session/me").header("cookie", &second).await;
+    assert_eq!(response.json::<Value>(), json!({ "user": null }));
+}
+
+#[tokio::test]
+async fn encrypted_session_cookies_hide_the_id() {
+    let config = SessionConfig::new(SECRET).encrypted(true).cookie_name("sid").secure(false);
+    let client = TestClient::from_server(Server::new().sessions(config));
+    let response = client.post("/session/login").json(&json!({ "user": "ada" })).await;
+    assert!(!response.header("set-cookie").unwrap().contains("Secure"));
+    let cookie = cookie_header(&response);
+    assert!(cookie.starts_with("sid="));
+
+    let response = client.get("/session/me").header("cookie", &cookie).await;
+    assert_eq!(response.json::<Value>(), json!({ "user": "ada" }));
+}
+
+#[tokio::test]
+async fn cookies_can_be_plain_signed_or_encrypted() {
+    let client = client(MemoryStore::new());
+    let response = client.post("/cookies/set").await;
+    assert_eq!(response.headers().get_all("set-cookie").iter().count(), 3);
+    let cookie = cookie_header(&response);
+    assert!(cookie.contains("theme=dark"));
+    assert!(!cookie.contains("s3cret"));
+
+    let response = client.get("/cookies/get").header("cookie", &cookie).await;
+    assert_eq!(response.json::<Value>(), json!({ "theme": "dark", "plan": "pro", "token": "s3cret" }));
+
+    // Values set by the client itself are not trusted
+    let response = client.get("/cookies/get").header("cookie", "theme=light; plan=pro; token=s3cret").await;
+    assert_eq!(response.json::<Value>(), json!({ "theme": "light", "plan": null, "token": null }));
+}
+
+#[tokio::test]
+async fn session_parameters_need_sessions_enabled() {
+    let response = TestClient::new().get("/session/me").await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
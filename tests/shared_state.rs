@@ -0,0 +1,77 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use ferrox::test::TestClient;
+use ferrox::{http_method, RequestContext, Server, State, StatusCode};
+use serde_json::{json, Value};
+
+#[derive(Clone)]
+struct AppConfig {
+    greeting: String,
+}
+
+#[derive(Clone, Default)]
+struct Visits(Arc<AtomicU64>);
+
+#[derive(Clone)]
+struct Unregistered;
+
+#[http_method(GET, "/state/hello")]
+fn hello(config: State<AppConfig>, visits: State<Visits>) -> Value {
+    let State(Visits(visits)) = visits;
+    let count = visits.fetch_add(1, Ordering::SeqCst) + 1;
+    json!({ "greeting": config.greeting, "visits": count })
+}
+
+#[http_method(GET, "/state/async")]
+async fn greet_later(config: State<AppConfig>) -> Value {
+    tokio::task::yield_now().await;
+    json!({ "greeting": config.greeting })
+}
+
+#[http_method(GET, "/state/context")]
+fn from_context(ctx: &RequestContext) -> Value {
+    json!({
+        "config": ctx.state::<AppConfig>().map(|config| config.greeting),
+        "unregistered": ctx.state::<Unregistered>().is_some(),
+    })
+}
+
+#[http_method(GET, "/state/missing")]
+fn missing(_state: State<Unregistered>) -> Value {
+    json!("unreachable")
+}
+
+fn server(greeting: &str) -> Server {
+    Server::new()
+        .with_state(AppConfig { greeting: greeting.to_string() })
+        .with_state(Visits::default())
+}
+
+#[tokio::test]
+async fn handlers_receive_each_registered_state_by_type() {
+    let client = TestClient::from_server(server("hi"));
+    assert_eq!(client.get("/state/hello").await.json::<Value>(), json!({ "greeting": "hi", "visits": 1 }));
+    // Clones share what the state points to
+    assert_eq!(client.get("/state/hello").await.json::<Value>()["visits"], 2);
+    assert_eq!(client.get("/state/async").await.json::<Value>()["greeting"], "hi");
+    assert_eq!(
+        client.get("/state/context").await.json::<Value>(),
+        json!({ "config": "hi", "unregistered": false })
+    );
+}
+
+#[tokio::test]
+async fn registering_a_type_again_replaces_it() {
+    let client = TestClient::from_server(server("hi").with_state(AppConfig { greeting: "hello".to_string() }));
+    assert_eq!(client.get("/state/hello").await.json::<Value>()["greeting"], "hello");
+}
+
+#[tokio::test]
+async fn missing_state_answers_500_without_details() {
+    let client = TestClient::from_server(server("hi"));
+    let response = client.get("/state/missing").await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let message = response.json::<Value>()["message"].as_str().unwrap().to_string();
+    assert!(!message.contains("Unregistered"), "{}", message);
+}
@@ -0,0 +1,71 @@
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use ferrox::{http_method, Server};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+
+#[http_method(GET, "/shutdown/slow")]
+async fn slow() -> Value {
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    json!({ "done": true })
+}
+
+// Send a GET and read until the server closes the connection
+fn request(addr: SocketAddr, path: &'static str) -> JoinHandle<String> {
+    tokio::spawn(async move {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response).await;
+        String::from_utf8_lossy(&response).into_owned()
+    })
+}
+
+#[tokio::test]
+async fn shutdown_drops_requests_in_progress() {
+    let handle = Server::new().quiet().start_in_background("127.0.0.1:0").await.unwrap();
+    let addr = handle.local_addr().unwrap();
+
+    let pending = request(addr, "/shutdown/slow");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let started = Instant::now();
+    handle.shutdown().await;
+    assert!(started.elapsed() < Duration::from_millis(200));
+
+    assert_eq!(pending.await.unwrap(), "");
+    assert!(TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn draining_refuses_new_connections() {
+    let handle = Server::new().quiet().start_in_background("127.0.0.1:0").await.unwrap();
+    let addr = handle.local_addr().unwrap();
+
+    let pending = request(addr, "/shutdown/slow");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let draining = tokio::spawn(handle.graceful_shutdown(Duration::from_secs(2)));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(TcpStream::connect(addr).await.is_err());
+
+    draining.await.unwrap().unwrap();
+    assert!(pending.await.unwrap().starts_with("HTTP/1.1 200 OK"));
+}
+
+#[tokio::test]
+async fn graceful_shutdown_gives_up_after_its_timeout() {
+    let handle = Server::new().quiet().start_in_background("127.0.0.1:0").await.unwrap();
+    let addr = handle.local_addr().unwrap();
+
+    let pending = request(addr, "/shutdown/slow");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let err = handle.graceful_shutdown(Duration::from_millis(50)).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+
+    // The request was cut off instead of answered
+    assert_eq!(pending.await.unwrap(), "");
+}
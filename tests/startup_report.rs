@@ -0,0 +1,39 @@
+use std::sync::{Arc, Mutex};
+
+use ferrox::logging::StartupReport;
+use ferrox::{http_method, RequestContext, RouteHandler, Server};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/report/status")]
+fn status() -> Value {
+    json!({ "up": true })
+}
+
+fn recording(reports: &Arc<Mutex<Vec<StartupReport>>>) -> Server {
+    let reports = reports.clone();
+    Server::new()
+        .route("POST", "/report/hook", RouteHandler::from_sync(|_ctx: RequestContext| json!({})))
+        .startup_report(move |report| reports.lock().unwrap().push(report.clone()))
+}
+
+#[tokio::test]
+async fn the_callback_gets_the_report_once_listening() {
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let handle = recording(&reports).start_in_background("127.0.0.1:0").await.unwrap();
+
+    let reports = reports.lock().unwrap().clone();
+    assert_eq!(reports.len(), 1);
+    let report = &reports[0];
+    assert_eq!(report.addresses, [format!("http://{}", handle.local_addr().unwrap())]);
+    assert!(report.routes.iter().any(|route| route.path == "/report/status" && route.method == "GET"));
+    assert_eq!(report.dynamic_routes, [("POST".to_string(), "/report/hook".to_string())]);
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn quiet_servers_report_nothing() {
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let handle = recording(&reports).quiet().start_in_background("127.0.0.1:0").await.unwrap();
+    assert!(reports.lock().unwrap().is_empty());
+    handle.shutdown().await;
+}
@@ -0,0 +1,133 @@
+use std::path::PathBuf;
+
+use ferrox::static_files::StaticFiles;
+use ferrox::test::TestClient;
+use ferrox::{Server, StatusCode};
+use serde_json::Value;
+
+// A fresh directory of files for one test
+fn site(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ferrox-static-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("docs")).unwrap();
+    std::fs::create_dir_all(dir.join("empty")).unwrap();
+    std::fs::write(dir.join("index.html"), "<h1>home</h1>").unwrap();
+    std::fs::write(dir.join("app.js"), "console.log(1)").unwrap();
+    std::fs::write(dir.join("docs/index.html"), "<h1>docs</h1>").unwrap();
+    std::fs::write(dir.join("docs/guide.txt"), "guide").unwrap();
+    std::fs::write(dir.join(".env"), "SECRET=1").unwrap();
+    dir
+}
+
+#[tokio::test]
+async fn serves_files_under_the_prefix() {
+    let client = TestClient::from_server(Server::new().serve_static("/assets", site("files")));
+    let response = client.get("/assets/app.js").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), "console.log(1)");
+    assert!(response.header("content-type").unwrap().contains("javascript"));
+}
+
+#[tokio::test]
+async fn directories_serve_their_index_file() {
+    let client = TestClient::from_server(Server::new().serve_static("/assets", site("index")));
+    assert_eq!(client.get("/assets/").await.text(), "<h1>home</h1>");
+    assert_eq!(client.get("/assets/docs/").await.text(), "<h1>docs</h1>");
+
+    let response = client.get("/assets/docs").await;
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(response.header("location"), Some("/assets/docs/"));
+}
+
+#[tokio::test]
+async fn listings_are_opt_in_and_skip_hidden_files() {
+    let dir = site("listing");
+    let client = TestClient::from_server(Server::new().serve_static("/assets", &dir));
+    assert_eq!(client.get("/assets/empty/").await.status(), StatusCode::NOT_FOUND);
+
+    let files = StaticFiles::new("/assets", &dir).index_file(None).directory_listing(true);
+    let client = TestClient::from_server(Server::new().static_files(files));
+    let response = client.get("/assets/").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let page = response.text();
+    assert!(page.contains("<a href=\"docs/\">docs/</a>"));
+    assert!(page.contains("app%2Ejs"));
+    assert!(!page.contains(".env"));
+}
+
+#[tokio::test]
+async fn missing_hidden_and_escaping_paths_are_not_found() {
+    let client = TestClient::from_server(Server::new().serve_static("/assets", site("missing")));
+    for path in ["/assets/nope.css", "/assets/.env", "/assets/%2E%2E/secret", "/assets/docs/%2e%2e/%2e%2e/etc/passwd"] {
+        let response = client.get(path).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", path);
+        assert_eq!(response.json::<Value>()["success"], false);
+    }
+}
+
+#[tokio::test]
+async fn cache_control_is_sent_with_files() {
+    let files = StaticFiles::new("/assets", site("cache")).cache_control("public, max-age=60");
+    let client = TestClient::from_server(Server::new().static_files(files));
+    assert_eq!(client.get("/assets/app.js").await.header("cache-control"), Some("public, max-age=60"));
+    assert_eq!(client.get("/assets/nope.js").await.header("cache-control"), None);
+}
+
+#[tokio::test]
+async fn precompressed_variants_are_served_when_accepted() {
+    let dir = site("precompressed");
+    std::fs::write(dir.join("app.js.gz"), "gzipped").unwrap();
+    let files = StaticFiles::new("/assets", &dir).precompressed(true);
+    let client = TestClient::from_server(Server::new().static_files(files));
+
+    let response = client.get("/assets/app.js").header("accept-encoding", "gzip").await;
+    assert_eq!(response.header("content-encoding"), Some("gzip"));
+    assert_eq!(response.text(), "gzipped");
+    assert_eq!(client.get("/assets/app.js").await.text(), "console.log(1)");
+}
+
+#[tokio::test]
+async fn a_root_prefix_replaces_the_not_found_fallback() {
+    let client = TestClient::from_server(Server::new().serve_static("/", site("root")));
+    assert_eq!(client.get("/").await.text(), "<h1>home</h1>");
+    assert_eq!(client.get("/app.js").await.status(), StatusCode::OK);
+    assert_eq!(client.get("/nope").await.status(), StatusCode::NOT_FOUND);
+    // Other methods match no route, as without static files
+    let response = client.post("/nope").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.json::<Value>()["message"], "Route /nope not found");
+}
+
+#[tokio::test]
+async fn the_spa_fallback_answers_page_navigations() {
+    let dir = site("spa");
+    let client = TestClient::from_server(
+        Server::new()
+            .serve_static("/", &dir)
+            .spa_fallback(dir.join("index.html")),
+    );
+    let page = client.get("/settings/profile").header("accept", "text/html,*/*;q=0.8").await;
+    assert_eq!(page.status(), StatusCode::OK);
+    assert_eq!(page.text(), "<h1>home</h1>");
+    assert_eq!(page.header("cache-control"), Some("no-cache"));
+    assert_eq!(client.get("/app.js").header("accept", "text/html").await.text(), "console.log(1)");
+
+    let api = client.get("/api/users").header("accept", "application/json").await;
+    assert_eq!(api.status(), StatusCode::NOT_FOUND);
+    assert_eq!(api.json::<Value>()["message"], "File /api/users not found");
+    assert_eq!(client.get("/missing.js").header("accept", "*/*").await.status(), StatusCode::NOT_FOUND);
+    // Only GET and HEAD navigate to a page
+    let post = client.post("/settings/profile").header("accept", "text/html").await;
+    assert_eq!(post.status(), StatusCode::NOT_FOUND);
+    assert_eq!(client.post("/app.js").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn the_spa_fallback_works_without_static_files() {
+    let dir = site("spa-only");
+    let client = TestClient::from_server(Server::new().spa_fallback(dir.join("index.html")));
+    assert_eq!(client.get("/dashboard").header("accept", "text/html").await.text(), "<h1>home</h1>");
+    let api = client.get("/dashboard").await;
+    assert_eq!(api.status(), StatusCode::NOT_FOUND);
+    assert_eq!(api.json::<Value>()["message"], "Route /dashboard not found");
+}
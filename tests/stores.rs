@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ferrox::auth::api_key::{ApiKeyAuth, InMemoryKeyStore};
+use ferrox::cache::{CacheKey, CacheStore, CachedResponse};
+use ferrox::ratelimit::{Algorithm, Decision, KeyBy, RateLimit, RateLimitStore, RateLimiter};
+use ferrox::session::StoreFuture;
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, Server, StatusCode};
+use serde_json::{json, Value};
+
+static REPORT_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+#[http_method(GET, "/stores/report", cache = "30s")]
+fn report() -> Value {
+    json!({ "calls": REPORT_CALLS.fetch_add(1, Ordering::SeqCst) + 1 })
+}
+
+#[http_method(GET, "/stores/ping")]
+fn ping() -> &'static str {
+    "pong"
+}
+
+// Stand-ins for shared stores such as Redis, kept in memory
+#[derive(Clone, Default)]
+struct SharedCache(Arc<Mutex<HashMap<String, CachedResponse>>>);
+
+impl CacheStore for SharedCache {
+    fn get<'a>(&'a self, key: &'a CacheKey) -> StoreFuture<'a, Option<CachedResponse>> {
+        Box::pin(async move { Ok(self.0.lock().unwrap().get(&key.path).cloned()) })
+    }
+
+    fn set<'a>(&'a self, key: &'a CacheKey, response: &'a CachedResponse, _ttl: Duration) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            self.0.lock().unwrap().insert(key.path.clone(), response.clone());
+            Ok(())
+        })
+    }
+
+    fn invalidate<'a>(&'a self, path: &'a str) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            self.0.lock().unwrap().remove(path);
+            Ok(())
+        })
+    }
+
+    fn clear(&self) -> StoreFuture<'_, ()> {
+        Box::pin(async move {
+            self.0.lock().unwrap().clear();
+            Ok(())
+        })
+    }
+}
+
+#[derive(Clone, Default)]
+struct SharedCounts(Arc<Mutex<HashMap<String, u32>>>);
+
+impl RateLimitStore for SharedCounts {
+    fn check<'a>(&'a self, key: &'a str, rate: RateLimit, _algorithm: Algorithm) -> StoreFuture<'a, Decision> {
+        Box::pin(async move {
+            let mut counts = self.0.lock().unwrap();
+            let count = counts.entry(key.to_string()).or_default();
+            let allowed = *count < rate.limit();
+            if allowed {
+                *count += 1;
+            }
+            Ok(Decision::sliding_window(allowed, *count, 0, Duration::ZERO, rate))
+        })
+    }
+}
+
+struct Unavailable;
+
+impl RateLimitStore for Unavailable {
+    fn check<'a>(&'a self, _key: &'a str, _rate: RateLimit, _algorithm: Algorithm) -> StoreFuture<'a, Decision> {
+        Box::pin(async { Err(FerroxError::Internal("connection refused".to_string())) })
+    }
+}
+
+#[tokio::test]
+async fn cached_responses_are_kept_in_the_store() {
+    let store = SharedCache::default();
+    let client = TestClient::from_server(Server::new().cache_store(store.clone()));
+    let first = client.get("/stores/report").await;
+    assert_eq!(first.status(), StatusCode::OK);
+    let first = first.json::<Value>();
+    assert_eq!(client.get("/stores/report").await.json::<Value>(), first);
+    assert!(store.0.lock().unwrap().contains_key("/stores/report"));
+
+    // Another instance sharing the store serves the same response
+    let other = TestClient::from_server(Server::new().cache_store(store.clone()));
+    assert_eq!(other.get("/stores/report").await.json::<Value>(), first);
+
+    store.invalidate("/stores/report").await.unwrap();
+    assert_ne!(client.get("/stores/report").await.json::<Value>(), first);
+}
+
+#[tokio::test]
+async fn rate_limits_are_counted_in_the_store() {
+    let store = SharedCounts::default();
+    let limiter = || {
+        RateLimiter::new(RateLimit::per_minute(2))
+            .key_by(KeyBy::ApiKey)
+            .store(store.clone())
+    };
+    let server = || {
+        let keys = InMemoryKeyStore::new().with_key("partner", "shared");
+        Server::new().api_keys(ApiKeyAuth::new(keys)).rate_limit(limiter())
+    };
+    let first = TestClient::from_server(server());
+    let second = TestClient::from_server(server());
+
+    let ping = |client: &TestClient| client.get("/stores/ping").header("x-api-key", "shared");
+    assert_eq!(ping(&first).await.status(), StatusCode::OK);
+    assert_eq!(ping(&second).await.status(), StatusCode::OK);
+    // Both instances share the client's limit
+    let refused = ping(&first).await;
+    assert_eq!(refused.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(refused.headers().contains_key("retry-after"));
+    assert_eq!(store.0.lock().unwrap().get("key:partner"), Some(&2));
+}
+
+#[tokio::test]
+async fn requests_go_through_when_the_store_fails() {
+    let limiter = RateLimiter::new(RateLimit::per_minute(1)).store(Unavailable);
+    let client = TestClient::from_server(Server::new().rate_limit(limiter));
+    assert_eq!(client.get("/stores/ping").await.status(), StatusCode::OK);
+    assert_eq!(client.get("/stores/ping").await.status(), StatusCode::OK);
+}
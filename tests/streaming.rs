@@ -0,0 +1,144 @@
+use std::path::PathBuf;
+
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, StatusCode, StreamingResponse};
+use futures_util::stream;
+use serde_json::json;
+
+fn downloads() -> PathBuf {
+    std::env::temp_dir().join(format!("ferrox-streaming-{}", std::process::id()))
+}
+
+// A file of `contents` in the downloads directory, for one test
+fn download(name: &str, contents: &str) {
+    std::fs::create_dir_all(downloads()).unwrap();
+    std::fs::write(downloads().join(name), contents).unwrap();
+}
+
+#[http_method(GET, "/events.ndjson")]
+fn events() -> StreamingResponse {
+    StreamingResponse::ndjson(stream::iter([json!({ "id": 1 }), json!({ "id": 2 })]))
+}
+
+#[http_method(GET, "/export.csv")]
+fn export() -> StreamingResponse {
+    let rows = ["id,name\n", "1,alice\n", "2,bob\n"].map(Ok::<_, std::io::Error>);
+    StreamingResponse::new(stream::iter(rows))
+        .content_type("text/csv")
+        .attachment("users 2026.csv")
+}
+
+#[http_method(GET, "/ticker")]
+fn ticker() -> StreamingResponse {
+    let (sender, response) = StreamingResponse::channel(1);
+    tokio::spawn(async move {
+        for tick in 0..3 {
+            sender.send(format!("tick {}\n", tick).into()).await.unwrap();
+        }
+    });
+    response
+}
+
+#[http_method(GET, "/archive")]
+fn archive() -> StreamingResponse {
+    let body = "abcdefghijklmnopqrstuvwxyz";
+    StreamingResponse::sized(body.len() as u64, move |range| {
+        let part = body[range.start as usize..range.end as usize].to_string();
+        stream::iter([Ok::<_, std::io::Error>(part)])
+    })
+    .etag("\"v2\"")
+}
+
+#[http_method(GET, "/downloads/:name")]
+async fn get_download(name: String) -> Result<StreamingResponse, FerroxError> {
+    let file = StreamingResponse::file(downloads().join(&name)).await?;
+    Ok(file.attachment(&name))
+}
+
+#[tokio::test]
+async fn ndjson_streams_one_line_per_item() {
+    let response = TestClient::new().get("/events.ndjson").await;
+    assert_eq!(response.header("content-type"), Some("application/x-ndjson"));
+    assert_eq!(response.text(), "{\"id\":1}\n{\"id\":2}\n");
+}
+
+#[tokio::test]
+async fn chunks_are_sent_as_they_are() {
+    let response = TestClient::new().get("/export.csv").await;
+    assert_eq!(response.header("content-type"), Some("text/csv"));
+    assert_eq!(
+        response.header("content-disposition"),
+        Some("attachment; filename=\"users 2026.csv\"")
+    );
+    assert_eq!(response.text(), "id,name\n1,alice\n2,bob\n");
+    assert_eq!(TestClient::new().get("/ticker").await.text(), "tick 0\ntick 1\ntick 2\n");
+}
+
+#[tokio::test]
+async fn files_are_streamed_with_their_length() {
+    download("report.txt", "0123456789");
+    let response = TestClient::new().get("/downloads/report.txt").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("content-length"), Some("10"));
+    assert_eq!(response.header("accept-ranges"), Some("bytes"));
+    assert_eq!(response.text(), "0123456789");
+
+    let response = TestClient::new().get("/downloads/missing.txt").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn ranges_of_files_are_partial_content() {
+    download("range.txt", "0123456789");
+    let client = TestClient::new();
+    let response = client.get("/downloads/range.txt").header("range", "bytes=2-5").await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.header("content-range"), Some("bytes 2-5/10"));
+    assert_eq!(response.text(), "2345");
+
+    let response = client.get("/downloads/range.txt").header("range", "bytes=-3").await;
+    assert_eq!(response.text(), "789");
+
+    let response = client.get("/downloads/range.txt").header("range", "bytes=20-").await;
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(response.header("content-range"), Some("bytes */10"));
+}
+
+#[tokio::test]
+async fn sized_bodies_answer_ranges() {
+    let client = TestClient::new();
+    let response = client.get("/archive").await;
+    assert_eq!(response.header("accept-ranges"), Some("bytes"));
+    assert_eq!(response.header("content-length"), Some("26"));
+
+    let response = client.get("/archive").header("range", "bytes=23-").await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.header("content-range"), Some("bytes 23-25/26"));
+    assert_eq!(response.text(), "xyz");
+}
+
+#[tokio::test]
+async fn if_range_resumes_only_the_same_version() {
+    let client = TestClient::new();
+    let response = client.get("/archive").header("range", "bytes=0-2").header("if-range", "\"v2\"").await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.text(), "abc");
+
+    // Another version, or a weak validator, gets the whole body
+    for validator in ["\"v1\"", "W/\"v2\""] {
+        let response = client.get("/archive").header("range", "bytes=0-2").header("if-range", validator).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.text().len(), 26);
+    }
+
+    download("resume.txt", "0123456789");
+    let response = client.get("/downloads/resume.txt").await;
+    let modified = response.header("last-modified").unwrap().to_string();
+    assert!(response.header("etag").is_some());
+    let response = client.get("/downloads/resume.txt").header("range", "bytes=4-").header("if-range", &modified).await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.text(), "456789");
+    let stale = "Mon, 01 Jan 2024 00:00:00 GMT";
+    let response = client.get("/downloads/resume.txt").header("range", "bytes=4-").header("if-range", stale).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
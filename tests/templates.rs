@@ -0,0 +1,94 @@
+#![cfg(feature = "templates")]
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use ferrox::templates::{Template, Templates};
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, Server, StatusCode};
+use serde::Serialize;
+use serde_json::json;
+
+#[derive(Serialize)]
+struct Profile {
+    name: String,
+    roles: Vec<&'static str>,
+}
+
+#[http_method(GET, "/templates/users/:name")]
+fn user_page(name: String) -> Template<Profile> {
+    Template("user.html", Profile { name, roles: vec!["admin", "ops"] })
+}
+
+#[http_method(GET, "/templates/notice")]
+fn notice() -> Result<Template, FerroxError> {
+    Ok(Template("notice.html", json!({ "text": "maintenance" })))
+}
+
+#[http_method(GET, "/templates/missing")]
+fn missing() -> Template {
+    Template("missing.html", json!({}))
+}
+
+// The templates every test renders with, also registered by every test
+fn templates() -> &'static PathBuf {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let dir = std::env::temp_dir().join(format!("ferrox-templates-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("base.html"), "<title>{% block title %}{% endblock %}</title>{% block body %}{% endblock %}").unwrap();
+        std::fs::write(
+            dir.join("user.html"),
+            "{% extends \"base.html\" %}{% block title %}{{ name }}{% endblock %}{% block body %}{% for role in roles %}<li>{{ role }}</li>{% endfor %}{% endblock %}",
+        )
+        .unwrap();
+        std::fs::write(dir.join("notice.html"), "<p>{{ text }}</p>").unwrap();
+        dir
+    })
+}
+
+fn client() -> TestClient {
+    let glob = format!("{}/**/*.html", templates().display());
+    TestClient::from_server(Server::new().templates(Templates::new(&glob).unwrap().reload(true)))
+}
+
+#[tokio::test]
+async fn templates_render_as_html_pages() {
+    let response = client().get("/templates/users/alice").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("content-type"), Some("text/html; charset=utf-8"));
+    assert_eq!(response.text(), "<title>alice</title><li>admin</li><li>ops</li>");
+}
+
+#[tokio::test]
+async fn values_are_escaped() {
+    let response = client().get("/templates/users/%3Cb%3Ebob%3C%2Fb%3E").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.text().starts_with("<title>&lt;b&gt;bob&lt;&#x2F;b&gt;</title>"));
+}
+
+#[tokio::test]
+async fn missing_templates_answer_500() {
+    let response = client().get("/templates/missing").await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(!response.text().contains("missing.html"));
+}
+
+#[tokio::test]
+async fn reloading_picks_up_new_templates() {
+    let client = client();
+    assert_eq!(client.get("/templates/notice").await.text(), "<p>maintenance</p>");
+    // A template added since loading is found on the next render
+    std::fs::write(templates().join("notice-edited.html"), "<p>new</p>").unwrap();
+    let rendered = Template("notice-edited.html", json!({})).render().unwrap();
+    assert_eq!(rendered, "<p>new</p>");
+}
+
+#[test]
+fn syntax_errors_surface_when_loading() {
+    let dir = std::env::temp_dir().join(format!("ferrox-templates-broken-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("broken.html"), "{% if %}").unwrap();
+    assert!(Templates::new(&format!("{}/*.html", dir.display())).is_err());
+}
@@ -0,0 +1,104 @@
+use ferrox::axum::http::HeaderName;
+use ferrox::ratelimit::RateLimit;
+use ferrox::tenancy::{Tenancy, TenantConfig, TenantId};
+use ferrox::test::TestClient;
+use ferrox::{http_method, RequestContext, Server, State, StatusCode};
+use serde_json::{json, Value};
+
+#[derive(Clone)]
+struct Database(&'static str);
+
+#[http_method(GET, "/tenancy/whoami")]
+fn whoami(tenant: TenantId, db: State<Database>) -> Value {
+    json!({ "tenant": tenant.as_str(), "database": db.0.0 })
+}
+
+#[http_method(GET, "/tenancy/settings")]
+fn settings(ctx: &RequestContext) -> Value {
+    json!({
+        "tenant": ctx.tenant().map(TenantId::as_str),
+        "theme": ctx.tenant_setting::<String>("theme"),
+        "seats": ctx.tenant_setting::<u32>("seats"),
+    })
+}
+
+fn tenancy() -> Tenancy {
+    Tenancy::new()
+        .setting("theme", "light")
+        .tenant("acme", TenantConfig::new().setting("theme", "dark").setting("seats", 50))
+        .tenant("globex", TenantConfig::new().with_state(Database("globex-db")))
+}
+
+fn client(tenancy: Tenancy) -> TestClient {
+    TestClient::from_server(Server::new().with_state(Database("shared-db")).tenancy(tenancy))
+}
+
+#[tokio::test]
+async fn tenants_come_from_the_subdomain_or_a_header() {
+    let client = client(tenancy().subdomain("example.com").header(HeaderName::from_static("x-tenant-id")));
+
+    let response = client.get("/tenancy/whoami").header("host", "acme.example.com:8080").await;
+    assert_eq!(response.json::<Value>(), json!({ "tenant": "acme", "database": "shared-db" }));
+    let response = client.get("/tenancy/whoami").header("x-tenant-id", "globex").await;
+    assert_eq!(response.json::<Value>(), json!({ "tenant": "globex", "database": "globex-db" }));
+    // The subdomain is tried first
+    let response = client
+        .get("/tenancy/whoami")
+        .header("host", "acme.example.com")
+        .header("x-tenant-id", "globex")
+        .await;
+    assert_eq!(response.json::<Value>()["tenant"], "acme");
+
+    let response = client.get("/tenancy/whoami").header("host", "initech.example.com").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    // Routes taking a `TenantId` need one
+    let response = client.get("/tenancy/whoami").header("host", "example.com").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = client.get("/tenancy/settings").await;
+    assert_eq!(response.json::<Value>(), json!({ "tenant": null, "theme": null, "seats": null }));
+}
+
+#[tokio::test]
+async fn path_prefixes_are_removed_before_routing() {
+    let client = client(tenancy().path_prefix());
+
+    let response = client.get("/acme/tenancy/settings").await;
+    assert_eq!(response.json::<Value>(), json!({ "tenant": "acme", "theme": "dark", "seats": 50 }));
+    let response = client.get("/globex/tenancy/settings?x=1").await;
+    assert_eq!(response.json::<Value>(), json!({ "tenant": "globex", "theme": "light", "seats": null }));
+    // Other prefixes are routed as they are
+    assert_eq!(client.get("/initech/tenancy/settings").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(client.get("/tenancy/settings").await.json::<Value>()["tenant"], Value::Null);
+}
+
+#[tokio::test]
+async fn unknown_tenants_can_be_allowed() {
+    let client = client(tenancy().header(HeaderName::from_static("x-tenant-id")).allow_unknown(true));
+    let response = client.get("/tenancy/settings").header("x-tenant-id", "initech").await;
+    assert_eq!(response.json::<Value>(), json!({ "tenant": "initech", "theme": "light", "seats": null }));
+}
+
+#[tokio::test]
+async fn each_tenant_has_its_own_rate_limit() {
+    let tenancy = Tenancy::new()
+        .header(HeaderName::from_static("x-tenant-id"))
+        .allow_unknown(true)
+        .rate_limit(RateLimit::per_minute(1))
+        .tenant("acme", TenantConfig::new().rate_limit(RateLimit::per_minute(2)));
+    let client = client(tenancy);
+    let status = |tenant: &'static str| {
+        let request = client.get("/tenancy/settings").header("x-tenant-id", tenant);
+        async move { request.await.status() }
+    };
+
+    assert_eq!(status("acme").await, StatusCode::OK);
+    assert_eq!(status("acme").await, StatusCode::OK);
+    assert_eq!(status("acme").await, StatusCode::TOO_MANY_REQUESTS);
+    // Tenants without their own limit are counted apart
+    assert_eq!(status("initech").await, StatusCode::OK);
+    assert_eq!(status("initech").await, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(status("hooli").await, StatusCode::OK);
+    // Requests without a tenant are not limited
+    assert_eq!(client.get("/tenancy/settings").await.status(), StatusCode::OK);
+    assert_eq!(client.get("/tenancy/settings").await.status(), StatusCode::OK);
+}
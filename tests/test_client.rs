@@ -0,0 +1,130 @@
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, HandlerResponse, RequestContext, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+struct NewUser {
+    name: String,
+}
+
+#[http_method(GET, "/users/:id")]
+fn get_user(id: u32) -> Result<Value, FerroxError> {
+    match id {
+        1 => Ok(json!({ "id": 1, "name": "alice" })),
+        _ => Err(FerroxError::NotFound(format!("User {} not found", id))),
+    }
+}
+
+#[http_method(POST, "/users")]
+fn create_user(body: NewUser) -> HandlerResponse {
+    HandlerResponse::new(StatusCode::CREATED, json!({ "name": body.name })).with_header("location", "/users/2")
+}
+
+#[http_method(GET, "/whoami")]
+fn whoami(ctx: &RequestContext) -> Value {
+    json!({ "agent": ctx.header("user-agent"), "ip": ctx.remote_addr.map(|addr| addr.ip().to_string()) })
+}
+
+#[tokio::test]
+async fn get_returns_handler_json() {
+    let response = TestClient::new().get("/users/1").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "id": 1, "name": "alice" }));
+}
+
+#[tokio::test]
+async fn errors_use_the_envelope() {
+    let response = TestClient::new().get("/users/9").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        response.json::<Value>(),
+        json!({ "success": false, "data": null, "message": "User 9 not found" })
+    );
+}
+
+#[tokio::test]
+async fn post_sends_json_and_reads_headers() {
+    let response = TestClient::new().post("/users").json(&json!({ "name": "bob" })).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.header("location"), Some("/users/2"));
+    assert_eq!(response.json::<Value>()["name"], "bob");
+}
+
+#[tokio::test]
+async fn invalid_body_is_rejected() {
+    let response = TestClient::new().post("/users").json(&json!({ "nickname": "bob" })).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn request_headers_and_peer_address_reach_the_handler() {
+    let response = TestClient::new().get("/whoami").header("user-agent", "tests").await;
+    assert_eq!(response.json::<Value>(), json!({ "agent": "tests", "ip": "127.0.0.1" }));
+}
+
+#[tokio::test]
+async fn unknown_routes_are_not_found() {
+    let response = TestClient::new().delete("/nowhere").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn wrong_method_on_known_path_is_not_allowed() {
+    let client = TestClient::new();
+
+    let response = client.delete("/users/1").await;
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.header("allow"), Some("GET, HEAD, OPTIONS"));
+    assert_eq!(response.json::<Value>()["message"], "Method not allowed");
+
+    let response = client.get("/users").await;
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.header("allow"), Some("POST, OPTIONS"));
+}
+
+#[derive(Deserialize)]
+struct Signup {
+    email: String,
+    age: u32,
+    subscribe: Option<bool>,
+}
+
+#[http_method(POST, "/signup")]
+fn signup(body: Signup) -> Value {
+    json!({ "email": body.email, "age": body.age, "subscribe": body.subscribe })
+}
+
+#[http_method(POST, "/raw-form")]
+fn raw_form(body: Value) -> Value {
+    body
+}
+
+#[tokio::test]
+async fn form_bodies_fill_typed_parameters() {
+    let response = TestClient::new()
+        .post("/signup")
+        .form(&[("email", "a@example.com"), ("age", "30"), ("subscribe", "true")])
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.json::<Value>(),
+        json!({ "email": "a@example.com", "age": 30, "subscribe": true })
+    );
+}
+
+#[tokio::test]
+async fn form_fields_arrive_as_strings_with_repeats_as_arrays() {
+    let response = TestClient::new()
+        .post("/raw-form")
+        .header("content-type", "application/x-www-form-urlencoded; charset=utf-8")
+        .body("name=alice+smith&tag=a&tag=b")
+        .await;
+    assert_eq!(response.json::<Value>(), json!({ "name": "alice smith", "tag": ["a", "b"] }));
+}
+
+#[tokio::test]
+async fn invalid_form_fields_are_rejected() {
+    let response = TestClient::new().post("/signup").form(&[("email", "a@example.com"), ("age", "old")]).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,104 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use ferrox::test::TestClient;
+use ferrox::{http_method, Server, StatusCode};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+#[http_method(GET, "/timeouts/slow")]
+async fn slow() -> Value {
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    json!({ "done": true })
+}
+
+#[http_method(GET, "/timeouts/slow-sync")]
+fn slow_sync() -> Value {
+    std::thread::sleep(Duration::from_millis(500));
+    json!({ "done": true })
+}
+
+#[http_method(GET, "/timeouts/fast")]
+async fn fast() -> Value {
+    json!({ "done": true })
+}
+
+#[http_method(GET, "/timeouts/patient", timeout = "1s")]
+async fn patient() -> Value {
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    json!({ "done": true })
+}
+
+#[http_method(GET, "/timeouts/strict", timeout = "50ms")]
+async fn strict() -> Value {
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    json!({ "done": true })
+}
+
+#[http_method(POST, "/timeouts/upload")]
+fn upload(body: Value) -> Value {
+    json!({ "received": body })
+}
+
+#[tokio::test]
+async fn slow_handlers_answer_504() {
+    let client = TestClient::from_server(Server::new().default_timeout(Duration::from_millis(50)));
+    let response = client.get("/timeouts/slow").await;
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(client.get("/timeouts/slow-sync").await.status(), StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(client.get("/timeouts/fast").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn handlers_run_unbounded_without_a_timeout() {
+    let client = TestClient::new();
+    assert_eq!(client.get("/timeouts/slow-sync").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn route_timeouts_take_precedence_over_the_default() {
+    let client = TestClient::from_server(Server::new().default_timeout(Duration::from_millis(50)));
+    assert_eq!(client.get("/timeouts/patient").await.status(), StatusCode::OK);
+
+    // And apply without a default
+    let response = TestClient::new().get("/timeouts/strict").await;
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+}
+
+// Send `head`, then `body`, and read the whole answer
+async fn exchange(addr: SocketAddr, head: &str, body: &[u8]) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(body).await.unwrap();
+    let mut answer = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut answer))
+        .await
+        .unwrap()
+        .unwrap();
+    String::from_utf8_lossy(&answer).to_string()
+}
+
+#[tokio::test]
+async fn slow_uploads_answer_408() {
+    let handle = Server::new()
+        .quiet()
+        .body_read_timeout(Duration::from_millis(100))
+        .start_in_background("127.0.0.1:0")
+        .await
+        .unwrap();
+    let addr = handle.local_addr().unwrap();
+    // Only part of the announced body ever arrives
+    let head = "POST /timeouts/upload HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: 64\r\nConnection: close\r\n\r\n";
+    let answer = exchange(addr, head, b"{\"name\":").await;
+    assert!(answer.starts_with("HTTP/1.1 408"), "{}", answer);
+
+    let body = br#"{"name":"widget"}"#;
+    let head = format!(
+        "POST /timeouts/upload HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    let answer = exchange(addr, &head, body).await;
+    assert!(answer.starts_with("HTTP/1.1 200"), "{}", answer);
+    handle.shutdown().await;
+}
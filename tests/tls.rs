@@ -0,0 +1,92 @@
+#![cfg(feature = "tls")]
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use ferrox::tls::TlsConfig;
+use ferrox::{http_method, Server};
+use serde_json::{json, Value};
+
+const CA_1: &str = include_str!("fixtures/tls/ca-1.pem");
+const CA_2: &str = include_str!("fixtures/tls/ca-2.pem");
+const CERT_1: &str = include_str!("fixtures/tls/server-1.pem");
+const KEY_1: &str = include_str!("fixtures/tls/server-1.key");
+const CERT_2: &str = include_str!("fixtures/tls/server-2.pem");
+const KEY_2: &str = include_str!("fixtures/tls/server-2.key");
+
+#[http_method(GET, "/tls/hello")]
+fn hello() -> Value {
+    json!({ "secure": true })
+}
+
+// A client trusting only `ca`, with `localhost` pointing at the server
+fn client(ca: &str, addr: SocketAddr) -> reqwest::Client {
+    reqwest::Client::builder()
+        .tls_built_in_root_certs(false)
+        .add_root_certificate(reqwest::Certificate::from_pem(ca.as_bytes()).unwrap())
+        .resolve("localhost", addr)
+        .build()
+        .unwrap()
+}
+
+async fn hello_over(ca: &str, addr: SocketAddr) -> reqwest::Result<Value> {
+    let url = format!("https://localhost:{}/tls/hello", addr.port());
+    client(ca, addr).get(url).send().await?.json().await
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ferrox-tls-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[tokio::test]
+async fn routes_are_served_over_https() {
+    let tls = TlsConfig::from_pem(CERT_1, KEY_1);
+    let handle = Server::new().quiet().start_tls_in_background("127.0.0.1:0", tls).await.unwrap();
+    let addr = handle.local_addr().unwrap();
+
+    assert_eq!(hello_over(CA_1, addr).await.unwrap(), json!({ "secure": true }));
+    // Clients that do not trust the certificate's issuer refuse it
+    assert!(hello_over(CA_2, addr).await.is_err());
+    // Plain HTTP gets no answer
+    let plain = reqwest::get(format!("http://{}/tls/hello", addr)).await;
+    assert!(plain.is_err());
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn replaced_certificate_files_are_reloaded() {
+    let dir = temp_dir("reload");
+    let (cert, key) = (dir.join("server.pem"), dir.join("server.key"));
+    std::fs::write(&cert, CERT_1).unwrap();
+    std::fs::write(&key, KEY_1).unwrap();
+
+    let tls = TlsConfig::from_pem_files(&cert, &key).reload_interval(Some(Duration::from_millis(50)));
+    let handle = Server::new().quiet().start_tls_in_background("127.0.0.1:0", tls).await.unwrap();
+    let addr = handle.local_addr().unwrap();
+    assert!(hello_over(CA_1, addr).await.is_ok());
+
+    // Modification times can be coarse; make sure the new files look newer
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    std::fs::write(&cert, CERT_2).unwrap();
+    std::fs::write(&key, KEY_2).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while hello_over(CA_2, addr).await.is_err() {
+        assert!(Instant::now() < deadline, "the new certificate was not picked up");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(hello_over(CA_1, addr).await.is_err());
+
+    handle.shutdown().await;
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn missing_certificate_files_fail_the_start() {
+    let dir = temp_dir("missing");
+    let tls = TlsConfig::from_pem_files(dir.join("server.pem"), dir.join("server.key"));
+    assert!(Server::new().quiet().start_tls_in_background("127.0.0.1:0", tls).await.is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+}
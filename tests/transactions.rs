@@ -0,0 +1,82 @@
+#![cfg(feature = "sqlx")]
+
+use ferrox::database::{TransactionLayer, Tx};
+use ferrox::test::TestClient;
+use ferrox::{http_method, FerroxError, Server, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::{Sqlite, SqlitePool};
+
+#[derive(Deserialize)]
+struct NewItem {
+    name: String,
+}
+
+#[http_method(POST, "/tx/items")]
+async fn create_item(body: NewItem, tx: Tx<Sqlite>) -> Result<Value, FerroxError> {
+    let mut conn = tx.conn().await?;
+    sqlx::query("INSERT INTO items (name) VALUES (?)").bind(&body.name).execute(&mut *conn).await?;
+    if body.name.is_empty() {
+        return Err(FerroxError::BadRequest("Items need a name".to_string()));
+    }
+    let count: i64 = sqlx::query_scalar("SELECT count(*) FROM items").fetch_one(&mut *conn).await?;
+    Ok(json!({ "count": count }))
+}
+
+#[http_method(POST, "/tx/untracked")]
+async fn untracked(_tx: Tx<Sqlite>) -> Value {
+    json!({ "reached": true })
+}
+
+async fn pool() -> SqlitePool {
+    // One connection, so the in-memory database lives as long as the pool
+    let pool = SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+    sqlx::query("CREATE TABLE items (name TEXT NOT NULL)").execute(&pool).await.unwrap();
+    pool
+}
+
+async fn names(pool: &SqlitePool) -> Vec<String> {
+    sqlx::query_scalar("SELECT name FROM items ORDER BY name").fetch_all(pool).await.unwrap()
+}
+
+#[tokio::test]
+async fn successful_requests_commit() {
+    let pool = pool().await;
+    let client = TestClient::from_server(Server::new().route_layer("POST", "/tx/items", TransactionLayer::new(pool.clone())));
+    let response = client.post("/tx/items").json(&json!({ "name": "lamp" })).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "count": 1 }));
+    client.post("/tx/items").json(&json!({ "name": "desk" })).await;
+    assert_eq!(names(&pool).await, ["desk", "lamp"]);
+}
+
+#[tokio::test]
+async fn failed_requests_roll_back() {
+    let pool = pool().await;
+    let client = TestClient::from_server(Server::new().layer(TransactionLayer::new(pool.clone())));
+    let response = client.post("/tx/items").json(&json!({ "name": "" })).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(names(&pool).await.is_empty());
+
+    // Rejected before the handler runs, so nothing was written either
+    let response = client.post("/tx/items").json(&json!({ "title": "lamp" })).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(names(&pool).await.is_empty());
+}
+
+#[tokio::test]
+async fn a_busy_pool_answers_503() {
+    let options = SqlitePoolOptions::new().max_connections(1).acquire_timeout(std::time::Duration::from_millis(50));
+    let busy = options.connect("sqlite::memory:").await.unwrap();
+    let _hold = busy.acquire().await.unwrap();
+    let client = TestClient::from_server(Server::new().layer(TransactionLayer::new(busy.clone())));
+    assert_eq!(client.post("/tx/untracked").await.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn routes_without_the_layer_have_no_transaction() {
+    let client = TestClient::new();
+    let response = client.post("/tx/untracked").await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
@@ -0,0 +1,70 @@
+use ferrox::test::TestClient;
+use ferrox::{http_method, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+struct Filters {
+    page: u32,
+    #[serde(default)]
+    tag: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NewUser {
+    name: String,
+    age: u8,
+}
+
+#[http_method(GET, "/typed/users/:id")]
+fn get_user(id: u64, query: Filters) -> Value {
+    json!({ "id": id, "page": query.page, "tag": query.tag })
+}
+
+#[http_method(POST, "/typed/teams/:team/users")]
+fn create_user(team: String, body: NewUser) -> Value {
+    json!({ "team": team, "name": body.name, "age": body.age })
+}
+
+async fn rejection(response: ferrox::test::TestResponse) -> String {
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    response.json::<Value>()["message"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn parameters_are_deserialized_into_their_types() {
+    let client = TestClient::new();
+    let response = client.get("/typed/users/42?page=3&tag=admin").await;
+    assert_eq!(response.json::<Value>(), json!({ "id": 42, "page": 3, "tag": "admin" }));
+
+    let response = client.post("/typed/teams/core/users").json(&json!({ "name": "ada", "age": 36 })).await;
+    assert_eq!(response.json::<Value>(), json!({ "team": "core", "name": "ada", "age": 36 }));
+
+    // Form fields are parsed into numbers where the type asks for one
+    let response = client
+        .post("/typed/teams/core/users")
+        .form(&[("name", "ada"), ("age", "36")])
+        .await;
+    assert_eq!(response.json::<Value>(), json!({ "team": "core", "name": "ada", "age": 36 }));
+}
+
+#[tokio::test]
+async fn values_of_the_wrong_type_answer_400_naming_the_parameter() {
+    let client = TestClient::new();
+    let message = rejection(client.get("/typed/users/abc?page=1").await).await;
+    assert!(message.starts_with("Invalid path parameter `id`"), "{}", message);
+
+    let message = rejection(client.get("/typed/users/42?page=first").await).await;
+    assert!(message.starts_with("Invalid query string"), "{}", message);
+    let message = rejection(client.get("/typed/users/42").await).await;
+    assert!(message.contains("missing field `page`"), "{}", message);
+
+    let response = client.post("/typed/teams/core/users").json(&json!({ "name": "ada", "age": 300 })).await;
+    let message = rejection(response).await;
+    assert!(message.starts_with("Invalid request body"), "{}", message);
+    let response = client.post("/typed/teams/core/users").json(&json!({ "age": 36 })).await;
+    let message = rejection(response).await;
+    assert!(message.contains("missing field `name`"), "{}", message);
+    let response = client.post("/typed/teams/core/users").form(&[("name", "ada"), ("age", "old")]).await;
+    assert!(rejection(response).await.starts_with("Invalid form body"));
+}
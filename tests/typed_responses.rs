@@ -0,0 +1,94 @@
+use ferrox::openapi::{spec, OpenApiConfig};
+use ferrox::test::TestClient;
+use ferrox::{http_method, ApiResponse, FerroxError, Json, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+#[derive(Serialize)]
+struct User {
+    id: u64,
+    name: String,
+}
+
+#[http_method(GET, "/typed/users/:id")]
+async fn get_user(id: u64) -> Result<Json<User>, FerroxError> {
+    if id == 0 {
+        return Err(FerroxError::NotFound("No such user".to_string()));
+    }
+    Ok(Json(User {
+        id,
+        name: "alice".to_string(),
+    }))
+}
+
+#[http_method(GET, "/typed/me")]
+fn me() -> User {
+    User {
+        id: 1,
+        name: "alice".to_string(),
+    }
+}
+
+#[http_method(GET, "/typed/accounts/:id")]
+fn get_account(id: u64) -> Result<User, FerroxError> {
+    match id {
+        0 => Err(FerroxError::Forbidden("Not yours".to_string())),
+        id => Ok(User {
+            id,
+            name: "bob".to_string(),
+        }),
+    }
+}
+
+#[http_method(GET, "/typed/count")]
+fn count() -> u64 {
+    3
+}
+
+#[http_method(GET, "/typed/wrapped")]
+fn wrapped() -> ApiResponse<User> {
+    ApiResponse::ok(User {
+        id: 2,
+        name: "carol".to_string(),
+    })
+}
+
+#[tokio::test]
+async fn serialize_types_are_sent_as_json() {
+    let client = TestClient::new();
+    assert_eq!(
+        client.get("/typed/users/7").await.json::<Value>(),
+        json!({ "id": 7, "name": "alice" })
+    );
+    assert_eq!(client.get("/typed/me").await.json::<Value>(), json!({ "id": 1, "name": "alice" }));
+    assert_eq!(client.get("/typed/accounts/4").await.json::<Value>()["name"], "bob");
+}
+
+#[tokio::test]
+async fn errors_keep_their_status() {
+    let client = TestClient::new();
+    assert_eq!(client.get("/typed/users/0").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(client.get("/typed/accounts/0").await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn non_objects_are_enveloped() {
+    let response = TestClient::new().get("/typed/count").await;
+    assert_eq!(response.json::<Value>()["data"], json!(3));
+}
+
+#[test]
+fn response_schemas_follow_the_return_type() {
+    let document = spec(&OpenApiConfig::new("Typed", "1.0"));
+    let success = |path: &str| {
+        document["paths"][path]["get"]["responses"]["200"]["content"]["application/json"]["schema"].clone()
+    };
+    assert_eq!(success("/typed/users/{id}"), json!({ "type": "object", "title": "User" }));
+    assert_eq!(success("/typed/me"), json!({ "type": "object", "title": "User" }));
+    assert_eq!(success("/typed/accounts/{id}")["title"], "User");
+    assert_eq!(success("/typed/count")["properties"]["data"], json!({ "type": "integer" }));
+    assert_eq!(
+        success("/typed/wrapped")["properties"]["data"],
+        json!({ "type": "object", "title": "User" })
+    );
+}
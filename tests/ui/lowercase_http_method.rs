@@ -0,0 +1,8 @@
+use ferrox::http_method;
+
+#[http_method(post, "/users")]
+fn create_user() -> &'static str {
+    "ok"
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use ferrox::http_method;
+
+#[http_method(GET, "/files/*rest/meta")]
+fn file_meta() -> &'static str {
+    "ok"
+}
+
+fn main() {}
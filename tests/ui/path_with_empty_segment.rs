@@ -0,0 +1,8 @@
+use ferrox::http_method;
+
+#[http_method(GET, "/users//posts")]
+fn list_posts() -> &'static str {
+    "ok"
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use ferrox::http_method;
+
+#[http_method(GET, "/users?active=true")]
+fn active_users() -> &'static str {
+    "ok"
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use ferrox::http_method;
+
+#[http_method(GET, "/users/:id/posts/:id")]
+fn get_post() -> &'static str {
+    "ok"
+}
+
+fn main() {}
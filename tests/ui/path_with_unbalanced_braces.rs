@@ -0,0 +1,8 @@
+use ferrox::http_method;
+
+#[http_method(GET, "/users/{id")]
+fn get_user() -> &'static str {
+    "ok"
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use ferrox::http_method;
+
+#[http_method(GET, "users")]
+fn list_users() -> &'static str {
+    "ok"
+}
+
+fn main() {}
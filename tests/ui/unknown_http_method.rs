@@ -0,0 +1,8 @@
+use ferrox::http_method;
+
+#[http_method(FETCH, "/users")]
+fn fetch_users() -> &'static str {
+    "ok"
+}
+
+fn main() {}
@@ -0,0 +1,126 @@
+use ferrox::envelope::ProblemDetails;
+use ferrox::test::TestClient;
+use ferrox::validate::{Validate, ValidationErrors};
+use ferrox::{http_method, ApiResponse, Server, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+struct Address {
+    #[validate(length(min = 2))]
+    postal_code: String,
+}
+
+#[derive(Deserialize, Validate)]
+struct NewUser {
+    #[validate(length(min = 3, max = 12))]
+    name: String,
+    #[validate(email)]
+    email: String,
+    #[validate(range(min = 13, max = 130))]
+    age: Option<u32>,
+    #[validate(custom = "no_admins")]
+    #[serde(rename = "role")]
+    kind: String,
+    #[validate(nested)]
+    address: Address,
+}
+
+fn no_admins(kind: &str) -> Result<(), String> {
+    match kind {
+        "admin" => Err("cannot be admin".to_string()),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Deserialize, Validate)]
+struct Page {
+    #[validate(range(max = 100))]
+    per_page: u32,
+}
+
+#[http_method(POST, "/validated/users")]
+async fn create(body: NewUser) -> ApiResponse<Value> {
+    ApiResponse::ok(json!({ "name": body.name }))
+}
+
+#[http_method(GET, "/validated/users")]
+fn list(query: Page) -> Value {
+    json!({ "per_page": query.per_page })
+}
+
+fn valid_user() -> Value {
+    json!({
+        "name": "alice",
+        "email": "alice@example.com",
+        "role": "member",
+        "address": { "postalCode": "75001" },
+    })
+}
+
+#[tokio::test]
+async fn valid_bodies_reach_the_handler() {
+    let client = TestClient::new();
+    let response = client.post("/validated/users").json(&valid_user()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>()["data"], json!({ "name": "alice" }));
+}
+
+#[tokio::test]
+async fn failures_are_answered_with_422_and_the_errors_by_field() {
+    let client = TestClient::new();
+    let response = client
+        .post("/validated/users")
+        .json(&json!({
+            "name": "al",
+            "email": "alice@",
+            "age": 7,
+            "role": "admin",
+            "address": { "postalCode": "7" },
+        }))
+        .await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        response.json::<Value>(),
+        json!({
+            "success": false,
+            "data": {
+                "address.postalCode": ["length must be at least 2"],
+                "age": ["must be between 13 and 130"],
+                "email": ["must be a valid email address"],
+                "name": ["length must be between 3 and 12"],
+                "role": ["cannot be admin"],
+            },
+            "message": "Validation failed",
+        })
+    );
+
+    let response = client.get("/validated/users?per_page=500").await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(response.json::<Value>()["data"], json!({ "per_page": ["must be at most 100"] }));
+    // Deserialization failures are still a 400
+    let response = client.get("/validated/users?per_page=many").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn other_envelopes_carry_the_field_errors() {
+    let client = TestClient::from_server(Server::new().response_envelope(ProblemDetails));
+    let response = client.get("/validated/users?per_page=101").await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let problem = response.json::<Value>();
+    assert_eq!(problem["detail"], "Validation failed");
+    assert_eq!(problem["errors"], json!({ "per_page": ["must be at most 100"] }));
+}
+
+#[test]
+fn types_can_be_validated_directly() {
+    let user: NewUser = serde_json::from_value(valid_user()).unwrap();
+    assert_eq!(user.validate(), Ok(()));
+
+    let mut errors = ValidationErrors::new();
+    errors.add("name", "is taken");
+    assert_eq!(errors.get("name"), Some(&["is taken".to_string()][..]));
+    assert!(errors.into_result().is_err());
+}
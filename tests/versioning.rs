@@ -0,0 +1,106 @@
+use ferrox::test::TestClient;
+use ferrox::versioning::Versioning;
+use ferrox::{http_method, Server, StatusCode};
+use serde_json::{json, Value};
+
+#[http_method(GET, "/users/:id", version = "v1")]
+fn get_user_v1(id: u64) -> Value {
+    json!({ "id": id, "version": 1 })
+}
+
+#[http_method(GET, "/users/:id", version = "v2")]
+fn get_user_v2(id: u64) -> Value {
+    json!({ "id": id, "version": 2 })
+}
+
+#[http_method(GET, "/users/:id", version = "v10")]
+fn get_user_v10(id: u64) -> Value {
+    json!({ "id": id, "version": 10 })
+}
+
+#[http_method(GET, "/reports", version = "v1")]
+fn reports_v1() -> Value {
+    json!([])
+}
+
+#[http_method(DELETE, "/users/:id")]
+fn delete_user(id: u64) -> Value {
+    json!({ "deleted": id })
+}
+
+#[tokio::test]
+async fn versions_are_served_under_a_path_prefix_by_default() {
+    let client = TestClient::new();
+    assert_eq!(client.get("/v1/users/7").await.json::<Value>(), json!({ "id": 7, "version": 1 }));
+    assert_eq!(client.get("/v2/users/7").await.json::<Value>(), json!({ "id": 7, "version": 2 }));
+    assert_eq!(client.get("/users/7").await.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(client.delete("/users/7").await.json::<Value>(), json!({ "deleted": 7 }));
+}
+
+#[tokio::test]
+async fn a_header_picks_the_version() {
+    let client = TestClient::from_server(Server::new().versioning(Versioning::header("Accept-Version")));
+    let response = client.get("/users/7").header("accept-version", "v2").await;
+    assert_eq!(response.json::<Value>(), json!({ "id": 7, "version": 2 }));
+    // The latest version when none is named, ordered by number
+    let response = client.get("/users/7").await;
+    assert_eq!(response.json::<Value>(), json!({ "id": 7, "version": 10 }));
+
+    let response = client.get("/users/7").header("accept-version", "v3").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.json::<Value>()["message"], "Unsupported API version `v3`");
+
+    // Unversioned routes serve every version
+    let response = client.delete("/users/7").header("accept-version", "v1").await;
+    assert_eq!(response.json::<Value>(), json!({ "deleted": 7 }));
+    let response = client.put("/users/7").header("accept-version", "v1").await;
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+}
+
+#[tokio::test]
+async fn a_media_type_picks_the_version() {
+    let versioning = Versioning::media_type("application/vnd.acme").default_version("v1");
+    let client = TestClient::from_server(Server::new().versioning(versioning));
+    let response = client
+        .get("/users/7")
+        .header("accept", "text/html, application/vnd.acme.v2+json; q=0.9")
+        .await;
+    assert_eq!(response.json::<Value>(), json!({ "id": 7, "version": 2 }));
+    let response = client.get("/users/7").header("accept", "application/json").await;
+    assert_eq!(response.json::<Value>(), json!({ "id": 7, "version": 1 }));
+}
+
+#[tokio::test]
+async fn the_listing_shows_each_version() {
+    let versions: Vec<_> = ferrox::routes()
+        .into_iter()
+        .filter(|route| route.path == "/users/:id" && route.method == "GET")
+        .map(|route| route.version)
+        .collect();
+    assert_eq!(versions.len(), 3);
+    assert!(versions.contains(&Some("v10")));
+}
+
+#[tokio::test]
+async fn not_found_answers_can_point_at_other_versions() {
+    let client = TestClient::from_server(Server::new().versioning(Versioning::path().suggest_versions(true)));
+    let message = |path: &'static str| {
+        let client = &client;
+        async move {
+            let response = client.get(path).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            response.json::<Value>()["message"].as_str().unwrap().to_string()
+        }
+    };
+    assert_eq!(message("/v2/reports").await, "Route /v2/reports not found; available as /v1/reports");
+    assert_eq!(message("/reports").await, "Route /reports not found; available as /v1/reports");
+    assert_eq!(
+        message("/v3/users/7").await,
+        "Route /v3/users/7 not found; available as /v1/users/7, /v2/users/7, /v10/users/7"
+    );
+    assert_eq!(message("/v2/invoices").await, "Route /v2/invoices not found");
+
+    // Off by default
+    let response = TestClient::new().get("/v2/reports").await;
+    assert_eq!(response.json::<Value>()["message"], "Route /v2/reports not found");
+}
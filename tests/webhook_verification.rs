@@ -0,0 +1,121 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ferrox::test::TestClient;
+use ferrox::verify::{GitHub, Slack, Stripe};
+use ferrox::{http_method, Server, StatusCode};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+
+#[http_method(POST, "/verify/github", verify = "github")]
+fn github_push(body: Value) -> Value {
+    json!({ "ref": body["ref"] })
+}
+
+#[http_method(POST, "/verify/stripe", verify = "stripe")]
+fn stripe_event(body: Value) -> Value {
+    json!({ "type": body["type"] })
+}
+
+#[http_method(POST, "/verify/slack", verify = "slack")]
+fn slack_command() -> Value {
+    json!({ "ok": true })
+}
+
+#[http_method(POST, "/verify/unconfigured", verify = "missing")]
+fn unconfigured() -> Value {
+    json!({})
+}
+
+fn sign(secret: &str, message: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(message.as_bytes());
+    mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+fn client() -> TestClient {
+    TestClient::from_server(
+        Server::new()
+            .verifier("github", GitHub::new("gh-secret"))
+            .verifier("stripe", Stripe::new("whsec_test"))
+            .verifier("slack", Slack::new("slack-secret")),
+    )
+}
+
+#[tokio::test]
+async fn github_payloads_must_carry_their_signature() {
+    let client = client();
+    let payload = r#"{"ref":"refs/heads/main"}"#;
+    let signature = format!("sha256={}", sign("gh-secret", payload));
+
+    let response = client.post("/verify/github").header("x-hub-signature-256", &signature).body(payload).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "ref": "refs/heads/main" }));
+
+    // The same signature on a tampered payload
+    let tampered = r#"{"ref":"refs/heads/evil"}"#;
+    let response = client.post("/verify/github").header("x-hub-signature-256", &signature).body(tampered).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = client.post("/verify/github").body(payload).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn stripe_signatures_cover_the_timestamp() {
+    let client = client();
+    let payload = r#"{"type":"invoice.paid"}"#;
+    let timestamp = now();
+    let header = format!(
+        "t={},v1={},v1={}",
+        timestamp,
+        sign("whsec_old", &format!("{}.{}", timestamp, payload)),
+        sign("whsec_test", &format!("{}.{}", timestamp, payload))
+    );
+    let response = client.post("/verify/stripe").header("stripe-signature", &header).body(payload).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "type": "invoice.paid" }));
+
+    // A validly signed request replayed after the tolerance
+    let stale = timestamp - 600;
+    let header = format!("t={},v1={}", stale, sign("whsec_test", &format!("{}.{}", stale, payload)));
+    let response = client.post("/verify/stripe").header("stripe-signature", &header).body(payload).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn slack_requests_are_checked_before_the_handler() {
+    let client = client();
+    let payload = "command=/deploy&text=prod";
+    let timestamp = now().to_string();
+    let signature = format!("v0={}", sign("slack-secret", &format!("v0:{}:{}", timestamp, payload)));
+
+    let response = client
+        .post("/verify/slack")
+        .header("x-slack-request-timestamp", &timestamp)
+        .header("x-slack-signature", &signature)
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(payload)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>(), json!({ "ok": true }));
+
+    let response = client
+        .post("/verify/slack")
+        .header("x-slack-request-timestamp", &timestamp)
+        .header("x-slack-signature", "v0=00")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(payload)
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn routes_naming_an_unregistered_verifier_fail_closed() {
+    let response = client().post("/verify/unconfigured").body("{}").await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
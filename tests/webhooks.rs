@@ -0,0 +1,135 @@
+#![cfg(feature = "webhooks")]
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use ferrox::jobs::RetryPolicy;
+use ferrox::test::TestClient;
+use ferrox::webhooks::{self, Event, Subscription, Webhooks, WebhooksConfig};
+use ferrox::{http_method, Server, State};
+use serde_json::{json, Value};
+
+// Requests received by a test endpoint, which answers with the given statuses in turn, then 200
+#[derive(Clone, Default)]
+struct Receiver {
+    received: Arc<Mutex<Vec<(HeaderMap, String)>>>,
+    statuses: Arc<Mutex<Vec<StatusCode>>>,
+}
+
+impl Receiver {
+    async fn start(statuses: &[StatusCode]) -> (Self, SocketAddr) {
+        let receiver = Receiver::default();
+        receiver.statuses.lock().unwrap().extend(statuses.iter().rev());
+        let app = {
+            let receiver = receiver.clone();
+            Router::new().route(
+                "/hook",
+                post(move |headers: HeaderMap, body: String| async move {
+                    receiver.received.lock().unwrap().push((headers, body));
+                    receiver.statuses.lock().unwrap().pop().unwrap_or(StatusCode::OK)
+                }),
+            )
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (receiver, addr)
+    }
+
+    fn received(&self) -> Vec<(HeaderMap, String)> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+fn fast_retries(attempts: u32) -> Webhooks {
+    Webhooks::new(WebhooksConfig::new().retry(RetryPolicy::fixed(attempts, Duration::from_millis(10))))
+}
+
+#[http_method(POST, "/webhooks/orders")]
+fn create_order(webhooks: State<Webhooks>) -> Value {
+    let queued = webhooks.0.emit(Event::new("order.created", json!({ "id": 7 })));
+    json!({ "queued": queued })
+}
+
+#[tokio::test]
+async fn handlers_emit_signed_events_to_matching_subscriptions() {
+    let (receiver, addr) = Receiver::start(&[]).await;
+    let webhooks = fast_retries(3);
+    webhooks.subscribe(Subscription::new(&format!("http://{}/hook", addr), "s3cret").events(["order.*"]));
+    webhooks.subscribe(Subscription::new(&format!("http://{}/hook", addr), "other").events(["invoice.paid"]));
+    let client = TestClient::from_server(Server::new().webhooks(webhooks.clone()));
+
+    let response = client.post("/webhooks/orders").await;
+    assert_eq!(response.json::<Value>(), json!({ "queued": 1 }));
+    assert!(webhooks.flush(Duration::from_secs(5)).await);
+
+    let received = receiver.received();
+    assert_eq!(received.len(), 1);
+    let (headers, body) = &received[0];
+    let event: Value = serde_json::from_str(body).unwrap();
+    assert_eq!(event["type"], "order.created");
+    assert_eq!(event["data"], json!({ "id": 7 }));
+    assert_eq!(headers["x-webhook-event"], "order.created");
+    let timestamp: u64 = headers["x-webhook-timestamp"].to_str().unwrap().parse().unwrap();
+    let expected = format!("sha256={}", webhooks::sign("s3cret", timestamp, body.as_bytes()));
+    assert_eq!(headers["x-webhook-signature"], expected.as_str());
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried() {
+    let failures = [StatusCode::SERVICE_UNAVAILABLE, StatusCode::TOO_MANY_REQUESTS];
+    let (receiver, addr) = Receiver::start(&failures).await;
+    let webhooks = fast_retries(3);
+    webhooks.subscribe(Subscription::new(&format!("http://{}/hook", addr), "s3cret"));
+
+    assert_eq!(webhooks.emit(Event::new("ping", json!({}))), 1);
+    assert!(webhooks.flush(Duration::from_secs(5)).await);
+    let received = receiver.received();
+    assert_eq!(received.len(), 3);
+    // Every attempt carries the same delivery id
+    assert_eq!(received[0].0["x-webhook-id"], received[2].0["x-webhook-id"]);
+    assert!(webhooks.dead_letters().is_empty());
+}
+
+#[tokio::test]
+async fn deliveries_that_keep_failing_become_dead_letters() {
+    let failures = [StatusCode::INTERNAL_SERVER_ERROR, StatusCode::INTERNAL_SERVER_ERROR, StatusCode::GONE];
+    let (receiver, addr) = Receiver::start(&failures).await;
+    let webhooks = fast_retries(2);
+    let subscription = webhooks.subscribe(Subscription::new(&format!("http://{}/hook", addr), "s3cret"));
+
+    webhooks.emit(Event::new("ping", json!({})));
+    assert!(webhooks.flush(Duration::from_secs(5)).await);
+    let letters = webhooks.dead_letters();
+    assert_eq!(letters.len(), 1);
+    assert_eq!(letters[0].subscription, subscription);
+    assert_eq!(letters[0].attempts, 2);
+    assert_eq!(letters[0].error, "endpoint answered 500 Internal Server Error");
+
+    // A client error is not retried
+    assert!(webhooks.redeliver(&letters[0].id));
+    assert!(webhooks.flush(Duration::from_secs(5)).await);
+    let letters = webhooks.dead_letters();
+    assert_eq!(letters[0].attempts, 1);
+    assert_eq!(receiver.received().len(), 3);
+
+    // Until the endpoint recovers
+    assert!(webhooks.redeliver(&letters[0].id));
+    assert!(webhooks.flush(Duration::from_secs(5)).await);
+    assert!(webhooks.dead_letters().is_empty());
+    assert_eq!(receiver.received().len(), 4);
+}
+
+#[tokio::test]
+async fn unsubscribed_endpoints_receive_nothing() {
+    let webhooks = fast_retries(1);
+    let id = webhooks.subscribe(Subscription::new("http://127.0.0.1:9/hook", "s3cret"));
+    assert_eq!(webhooks.subscriptions().len(), 1);
+    assert!(webhooks.unsubscribe(&id));
+    assert!(!webhooks.unsubscribe(&id));
+    assert_eq!(webhooks.emit(Event::new("ping", json!({}))), 0);
+}
@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use ferrox::ws::{ConnectionManager, JsonSocket};
+use ferrox::{http_method, websocket, FerroxError, Server, State};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+#[websocket("/rooms/ws/:room")]
+async fn join_room(room: String, socket: JsonSocket, rooms: State<ConnectionManager>) {
+    let mut connection = rooms.connect(socket);
+    connection.join(&room);
+    connection.send(&json!({ "joined": room })).unwrap();
+    while let Some(Ok(message)) = connection.recv().await {
+        connection.broadcast_others(&room, &message).unwrap();
+    }
+}
+
+#[http_method(POST, "/rooms/:room/announce")]
+fn announce(room: String, rooms: State<ConnectionManager>) -> Result<Value, FerroxError> {
+    let notified = rooms.broadcast(&room, &json!({ "announcement": room }))?;
+    Ok(json!({ "notified": notified }))
+}
+
+async fn connect(addr: &str, room: &str) -> Client {
+    let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}/rooms/ws/{}", addr, room)).await.unwrap();
+    assert_eq!(next(&mut client).await, json!({ "joined": room }));
+    client
+}
+
+async fn next(client: &mut Client) -> Value {
+    let message = tokio::time::timeout(Duration::from_secs(1), client.next()).await.unwrap().unwrap().unwrap();
+    serde_json::from_str(message.to_text().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn http_handlers_broadcast_to_the_connections_in_a_room() {
+    let rooms = ConnectionManager::new();
+    let handle = Server::new().quiet().with_state(rooms.clone()).start_in_background("127.0.0.1:0").await.unwrap();
+    let addr = handle.local_addr().unwrap().to_string();
+    let (mut first, mut second, mut other) =
+        (connect(&addr, "order-42").await, connect(&addr, "order-42").await, connect(&addr, "order-7").await);
+    assert_eq!(rooms.rooms(), ["order-42", "order-7"]);
+
+    let response = hyper_post(&addr, "/rooms/order-42/announce").await;
+    assert_eq!(response, json!({ "notified": 2 }));
+    assert_eq!(next(&mut first).await, json!({ "announcement": "order-42" }));
+    assert_eq!(next(&mut second).await, json!({ "announcement": "order-42" }));
+
+    // Messages from a client reach the rest of its room only
+    first.send(Message::text(r#"{"text":"hi"}"#)).await.unwrap();
+    assert_eq!(next(&mut second).await, json!({ "text": "hi" }));
+    other.send(Message::text(r#"{"text":"elsewhere"}"#)).await.unwrap();
+    assert!(tokio::time::timeout(Duration::from_millis(50), first.next()).await.is_err());
+}
+
+#[tokio::test]
+async fn disconnected_clients_leave_their_rooms() {
+    let rooms = ConnectionManager::new();
+    let handle = Server::new().quiet().with_state(rooms.clone()).start_in_background("127.0.0.1:0").await.unwrap();
+    let addr = handle.local_addr().unwrap().to_string();
+    let mut client = connect(&addr, "lobby").await;
+    let _stays = connect(&addr, "lobby").await;
+    assert_eq!(rooms.members("lobby").len(), 2);
+
+    client.close(None).await.unwrap();
+    for _ in 0..50 {
+        if rooms.connection_count() == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(rooms.members("lobby").len(), 1);
+    assert_eq!(rooms.broadcast("lobby", &json!({})).unwrap(), 1);
+    assert_eq!(rooms.broadcast("empty", &json!({})).unwrap(), 0);
+}
+
+// POST to the server over a plain HTTP/1 connection, returning the JSON body
+async fn hyper_post(addr: &str, path: &str) -> Value {
+    use http_body_util::BodyExt;
+
+    let stream = hyper_util::rt::TokioIo::new(TcpStream::connect(addr).await.unwrap());
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(stream).await.unwrap();
+    tokio::spawn(connection);
+    let request = hyper::Request::post(path)
+        .header("host", addr)
+        .body(http_body_util::Empty::<hyper::body::Bytes>::new())
+        .unwrap();
+    let body = sender.send_request(request).await.unwrap().into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice(&body).unwrap()
+}
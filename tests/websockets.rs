@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use ferrox::axum::extract::ws::{Message as Frame, WebSocket};
+use ferrox::test::TestClient;
+use ferrox::ws::JsonSocket;
+use ferrox::{websocket, Server, StatusCode};
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+#[derive(Deserialize)]
+struct Options {
+    prefix: String,
+}
+
+#[derive(Deserialize)]
+struct Chat {
+    text: String,
+}
+
+#[websocket("/sockets/chat/:room")]
+async fn chat(room: u64, query: Options, mut socket: JsonSocket<Chat>) {
+    while let Some(message) = socket.recv().await {
+        let reply = match message {
+            Ok(message) => json!({ "room": room, "text": format!("{}{}", query.prefix, message.text) }),
+            Err(err) => json!({ "error": err.to_string() }),
+        };
+        if socket.send(&reply).await.is_err() {
+            break;
+        }
+    }
+}
+
+#[websocket("/sockets/raw")]
+async fn raw(mut socket: WebSocket) {
+    while let Some(Ok(frame)) = socket.recv().await {
+        if let Frame::Binary(mut bytes) = frame {
+            bytes.reverse();
+            let _ = socket.send(Frame::Binary(bytes)).await;
+        }
+    }
+}
+
+async fn serve() -> (ferrox::ServerHandle, String) {
+    let handle = Server::new().quiet().start_in_background("127.0.0.1:0").await.unwrap();
+    let addr = handle.local_addr().unwrap().to_string();
+    (handle, addr)
+}
+
+async fn next(client: &mut Client) -> Message {
+    tokio::time::timeout(Duration::from_secs(1), client.next()).await.unwrap().unwrap().unwrap()
+}
+
+async fn next_json(client: &mut Client) -> Value {
+    serde_json::from_str(next(client).await.to_text().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn json_sockets_exchange_typed_messages() {
+    let (handle, addr) = serve().await;
+    let url = format!("ws://{}/sockets/chat/7?prefix=%3E%20", addr);
+    let (mut client, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+    client.send(Message::text(r#"{"text":"hello"}"#)).await.unwrap();
+    assert_eq!(next_json(&mut client).await, json!({ "room": 7, "text": "> hello" }));
+
+    // A message that is not a `Chat` is reported without closing the socket
+    client.send(Message::text(r#"{"body":"hello"}"#)).await.unwrap();
+    let error = next_json(&mut client).await["error"].as_str().unwrap().to_string();
+    assert!(error.contains("missing field `text`"), "{}", error);
+    client.send(Message::binary(br#"{"text":"again"}"#.to_vec())).await.unwrap();
+    assert_eq!(next_json(&mut client).await["text"], "> again");
+
+    client.close(None).await.unwrap();
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn raw_sockets_see_every_frame() {
+    let (handle, addr) = serve().await;
+    let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}/sockets/raw", addr)).await.unwrap();
+    client.send(Message::binary(vec![1, 2, 3])).await.unwrap();
+    assert_eq!(next(&mut client).await, Message::binary(vec![3, 2, 1]));
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn bad_parameters_are_refused_before_the_upgrade() {
+    let (handle, addr) = serve().await;
+    for path in ["/sockets/chat/general?prefix=x", "/sockets/chat/7"] {
+        match tokio_tungstenite::connect_async(format!("ws://{}{}", addr, path)).await {
+            Err(tungstenite::Error::Http(response)) => assert_eq!(response.status(), StatusCode::BAD_REQUEST),
+            other => panic!("{} was upgraded: {:?}", path, other.map(|(_, response)| response)),
+        }
+    }
+    handle.shutdown().await;
+
+    // Plain requests without an upgrade are refused too
+    let response = TestClient::new().get("/sockets/raw").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}